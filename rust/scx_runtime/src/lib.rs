@@ -0,0 +1,486 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+//! # Common runtime for sched_ext schedulers
+//!
+//! Schedulers which implement their scheduling policy in BPF and use
+//! userspace only to load the BPF program, report statistics and poll for
+//! exit share the same boilerplate: version and stats help options, logger
+//! setup, Ctrl-C handling, the stats server and monitor, and a loop which
+//! reinitializes the scheduler when the BPF scheduler requests a restart.
+//!
+//! This crate implements all of it in [`run()`]. A scheduler provides its
+//! command line options, which include [`RuntimeOpts`], implements
+//! [`SchedulerOpts`] on them to create the scheduler, and implements
+//! [`Scheduler`] on the scheduler itself:
+//!
+//! ```ignore
+//! #[derive(Debug, Parser)]
+//! struct Opts {
+//!     /// Maximum scheduling slice duration in microseconds.
+//!     #[clap(short = 's', long, default_value = "20000")]
+//!     slice_us: u64,
+//!
+//!     #[clap(flatten)]
+//!     runtime: RuntimeOpts,
+//! }
+//!
+//! impl SchedulerOpts for Opts {
+//!     const NAME: &'static str = "scx_foo";
+//!     const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+//!     type Metrics = Metrics;
+//!     type Sched<'a> = Scheduler<'a>;
+//!
+//!     fn runtime(&self) -> &RuntimeOpts {
+//!         &self.runtime
+//!     }
+//!
+//!     fn init<'a>(
+//!         &'a self,
+//!         open_object: &'a mut MaybeUninit<OpenObject>,
+//!     ) -> Result<Scheduler<'a>> {
+//!         Scheduler::init(self, open_object)
+//!     }
+//!
+//!     fn stats_server_data() -> StatsServerData<(), Metrics> {
+//!         stats::server_data()
+//!     }
+//!
+//!     fn monitor(intv: Duration, shutdown: Arc<AtomicBool>) -> Result<()> {
+//!         stats::monitor(intv, shutdown)
+//!     }
+//! }
+//!
+//! fn main() -> Result<()> {
+//!     scx_runtime::run(&Opts::parse())
+//! }
+//! ```
+
+use std::mem::MaybeUninit;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use anyhow::Result;
+use clap::Args;
+use crossbeam::channel::Receiver;
+use crossbeam::channel::RecvTimeoutError;
+use crossbeam::channel::Sender;
+use libbpf_rs::OpenObject;
+use log::debug;
+use log::info;
+use log::warn;
+use scx_stats::prelude::*;
+use scx_utils::build_id;
+use scx_utils::libbpf_clap_opts::LibbpfOpts;
+use scx_utils::UserExitInfo;
+
+/// Command line options handled by the runtime, to be flattened into the
+/// options of the scheduler.
+#[derive(Clone, Debug, Args)]
+#[command(about = None, long_about = None)]
+pub struct RuntimeOpts {
+    /// Exit debug dump buffer length. 0 indicates default.
+    #[clap(long, default_value = "0")]
+    pub exit_dump_len: u32,
+
+    /// Specifies the set of CPUs to prioritize.
+    ///
+    /// Accepts a CPU domain expression (e.g., 0xff, 0-3,8, "node:0 & smt:first" or
+    /// "capacity>=800 - cpus:0"), see scx_utils::CpuDomain, or one of the special values
+    /// supported by the scheduler, e.g. "auto", "turbo", "performance", "powersave", "all" or
+    /// "none".
+    ///
+    /// The default depends on the scheduler.
+    #[clap(short = 'm', long)]
+    pub primary_domain: Option<String>,
+
+    /// Enable stats monitoring with the specified interval.
+    #[clap(long)]
+    pub stats: Option<f64>,
+
+    /// Run in stats monitoring mode with the specified interval. Scheduler
+    /// is not launched.
+    #[clap(long)]
+    pub monitor: Option<f64>,
+
+    /// Enable verbose output, including libbpf details.
+    #[clap(short = 'v', long, action = clap::ArgAction::SetTrue)]
+    pub verbose: bool,
+
+    /// Print scheduler version and exit.
+    #[clap(short = 'V', long, action = clap::ArgAction::SetTrue)]
+    pub version: bool,
+
+    /// Show descriptions for statistics.
+    #[clap(long)]
+    pub help_stats: bool,
+
+    #[clap(flatten, next_help_heading = "Libbpf Options")]
+    pub libbpf: LibbpfOpts,
+}
+
+impl RuntimeOpts {
+    /// The --primary-domain expression, or @default if it wasn't specified.
+    pub fn primary_domain_or<'a>(&'a self, default: &'a str) -> &'a str {
+        self.primary_domain.as_deref().unwrap_or(default)
+    }
+}
+
+/// A loaded scheduler. The scheduler is detached when it's dropped.
+pub trait Scheduler {
+    type Metrics;
+
+    /// Attach the scheduler.
+    fn attach(&mut self) -> Result<()>;
+
+    /// Whether the BPF scheduler has exited.
+    fn exited(&mut self) -> bool;
+
+    /// Called periodically while the scheduler is running, at least every
+    /// interval(). Returns true to restart the scheduler, e.g. after a
+    /// configuration change which requires reloading the BPF program.
+    fn on_interval(&mut self) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Maximum time between on_interval() calls.
+    fn interval(&self) -> Duration {
+        Duration::from_secs(1)
+    }
+
+    /// Current metrics, sent to the stats server clients.
+    fn metrics(&mut self) -> Self::Metrics;
+
+    /// Register the scheduler's runtime tunables on @data before the stats
+    /// server is launched. The stats requests carry no payload, so setters
+    /// usually share the tunable with the scheduler through an atomic which
+    /// on_interval() applies.
+    fn add_tunables(
+        &self,
+        data: StatsServerData<(), Self::Metrics>,
+    ) -> StatsServerData<(), Self::Metrics>
+    where
+        Self::Metrics: Send + 'static,
+    {
+        data
+    }
+
+    /// Detach the scheduler and report why it exited, usually
+    /// `uei_report!()`.
+    fn exit_report(&mut self) -> Result<UserExitInfo>;
+}
+
+/// Command line options of a scheduler, used by [`run()`] to create the
+/// scheduler and its stats server.
+pub trait SchedulerOpts: Sized + 'static {
+    /// Scheduler name, e.g. "scx_bpfland".
+    const NAME: &'static str;
+
+    /// Scheduler version, usually `env!("CARGO_PKG_VERSION")`.
+    const VERSION: &'static str;
+
+    type Metrics: Send + Sync + 'static;
+
+    type Sched<'a>: Scheduler<Metrics = Self::Metrics>
+    where
+        Self: 'a;
+
+    fn runtime(&self) -> &RuntimeOpts;
+
+    /// Open and load the BPF scheduler without attaching it.
+    fn init<'a>(&'a self, open_object: &'a mut MaybeUninit<OpenObject>) -> Result<Self::Sched<'a>>;
+
+    fn stats_server_data() -> StatsServerData<(), Self::Metrics>;
+
+    /// Print the stats of the running scheduler every @intv until
+    /// @shutdown is set.
+    fn monitor(intv: Duration, shutdown: Arc<AtomicBool>) -> Result<()>;
+}
+
+fn init_logger() -> Result<()> {
+    let mut lcfg = simplelog::ConfigBuilder::new();
+    lcfg.set_time_offset_to_local()
+        .expect("Failed to set local time offset")
+        .set_time_level(simplelog::LevelFilter::Error)
+        .set_location_level(simplelog::LevelFilter::Off)
+        .set_target_level(simplelog::LevelFilter::Off)
+        .set_thread_level(simplelog::LevelFilter::Off);
+    simplelog::TermLogger::init(
+        simplelog::LevelFilter::Info,
+        lcfg.build(),
+        simplelog::TerminalMode::Stderr,
+        simplelog::ColorChoice::Auto,
+    )?;
+    Ok(())
+}
+
+/// Serves stats requests received on @req_ch until the scheduler exits,
+/// @shutdown is set or the scheduler asks to be restarted. Returns whether a
+/// restart was requested.
+fn serve<S: Scheduler>(
+    sched: &mut S,
+    res_ch: &Sender<S::Metrics>,
+    req_ch: &Receiver<()>,
+    shutdown: &AtomicBool,
+) -> Result<bool>
+where
+    S::Metrics: Send + Sync + 'static,
+{
+    while !shutdown.load(Ordering::Relaxed) && !sched.exited() {
+        if sched.on_interval()? {
+            return Ok(true);
+        }
+        match req_ch.recv_timeout(sched.interval()) {
+            Ok(()) => res_ch.send(sched.metrics())?,
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(e) => Err(e)?,
+        }
+    }
+    Ok(false)
+}
+
+/// Run the scheduler configured by @opts until it exits or the process is
+/// interrupted, restarting it whenever the BPF scheduler or
+/// Scheduler::on_interval() requests it.
+pub fn run<O: SchedulerOpts>(opts: &O) -> Result<()> {
+    let rt = opts.runtime();
+
+    if rt.version {
+        println!("{} {}", O::NAME, build_id::full_version(O::VERSION));
+        return Ok(());
+    }
+
+    if rt.help_stats {
+        O::stats_server_data().describe_meta(&mut std::io::stdout(), None)?;
+        return Ok(());
+    }
+
+    init_logger()?;
+
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let shutdown_clone = shutdown.clone();
+    ctrlc::set_handler(move || {
+        shutdown_clone.store(true, Ordering::Relaxed);
+    })
+    .context("Error setting Ctrl-C handler")?;
+
+    if let Some(intv) = rt.monitor.or(rt.stats) {
+        let shutdown_copy = shutdown.clone();
+        let jh = std::thread::spawn(move || {
+            match O::monitor(Duration::from_secs_f64(intv), shutdown_copy) {
+                Ok(_) => {
+                    debug!("stats monitor thread finished successfully")
+                }
+                Err(error_object) => {
+                    warn!(
+                        "stats monitor thread finished because of an error {}",
+                        error_object
+                    )
+                }
+            }
+        });
+        if rt.monitor.is_some() {
+            let _ = jh.join();
+            return Ok(());
+        }
+    }
+
+    let mut open_object = MaybeUninit::uninit();
+    loop {
+        let mut sched = opts.init(&mut open_object)?;
+        sched.attach()?;
+        let stats_server = StatsServer::new(sched.add_tunables(O::stats_server_data())).launch()?;
+
+        let (res_ch, req_ch) = stats_server.channels();
+        let restart = serve(&mut sched, &res_ch, &req_ch, &shutdown)?;
+        let uei = sched.exit_report()?;
+        info!("Unregister {} scheduler", O::NAME);
+
+        if !restart && !uei.should_restart() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use crossbeam::channel::unbounded;
+
+    #[derive(Debug, Parser)]
+    struct Opts {
+        #[clap(short = 's', long, default_value = "20000")]
+        slice_us: u64,
+
+        #[clap(flatten)]
+        runtime: RuntimeOpts,
+    }
+
+    #[test]
+    fn test_runtime_opts_default() {
+        let opts = Opts::try_parse_from(["scx_test"]).unwrap();
+        let rt = &opts.runtime;
+
+        assert_eq!(opts.slice_us, 20000);
+        assert_eq!(rt.exit_dump_len, 0);
+        assert_eq!(rt.primary_domain, None);
+        assert_eq!(rt.primary_domain_or("auto"), "auto");
+        assert_eq!(rt.stats, None);
+        assert_eq!(rt.monitor, None);
+        assert!(!rt.verbose);
+        assert!(!rt.version);
+        assert!(!rt.help_stats);
+    }
+
+    #[test]
+    fn test_runtime_opts() {
+        let opts = Opts::try_parse_from([
+            "scx_test",
+            "-s",
+            "5000",
+            "-m",
+            "node:0 & smt:first",
+            "--exit-dump-len",
+            "4096",
+            "--stats",
+            "0.5",
+            "-v",
+        ])
+        .unwrap();
+        let rt = &opts.runtime;
+
+        assert_eq!(opts.slice_us, 5000);
+        assert_eq!(rt.exit_dump_len, 4096);
+        assert_eq!(rt.primary_domain_or("auto"), "node:0 & smt:first");
+        assert_eq!(rt.stats, Some(0.5));
+        assert_eq!(rt.monitor, None);
+        assert!(rt.verbose);
+
+        let opts = Opts::try_parse_from(["scx_test", "--primary-domain", "0-3", "--monitor", "2"])
+            .unwrap();
+        assert_eq!(opts.runtime.primary_domain.as_deref(), Some("0-3"));
+        assert_eq!(opts.runtime.monitor, Some(2.0));
+        assert_eq!(opts.runtime.stats, None);
+
+        assert!(Opts::try_parse_from(["scx_test", "--exit-dump-len", "-1"]).is_err());
+        assert!(Opts::try_parse_from(["scx_test", "--primary-domain"]).is_err());
+    }
+
+    /// Exits, or asks to be restarted, after a number of intervals.
+    #[derive(Default)]
+    struct TestSched {
+        exit_after: Option<u32>,
+        restart_after: Option<u32>,
+        fail_after: Option<u32>,
+        nr_intervals: u32,
+        nr_metrics: u64,
+    }
+
+    impl Scheduler for TestSched {
+        type Metrics = u64;
+
+        fn attach(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn exited(&mut self) -> bool {
+            self.exit_after == Some(self.nr_intervals)
+        }
+
+        fn on_interval(&mut self) -> Result<bool> {
+            self.nr_intervals += 1;
+            if self.fail_after == Some(self.nr_intervals) {
+                anyhow::bail!("interval failed");
+            }
+            Ok(self.restart_after == Some(self.nr_intervals))
+        }
+
+        fn interval(&self) -> Duration {
+            Duration::from_millis(1)
+        }
+
+        fn metrics(&mut self) -> u64 {
+            self.nr_metrics += 1;
+            self.nr_metrics
+        }
+
+        fn exit_report(&mut self) -> Result<UserExitInfo> {
+            unreachable!()
+        }
+    }
+
+    fn serve_test(sched: &mut TestSched, shutdown: bool) -> Result<bool> {
+        let (res_ch, _res_rx) = unbounded();
+        let (_req_tx, req_ch) = unbounded();
+        serve(sched, &res_ch, &req_ch, &AtomicBool::new(shutdown))
+    }
+
+    #[test]
+    fn test_serve_exit() {
+        let mut sched = TestSched {
+            exit_after: Some(3),
+            ..Default::default()
+        };
+        assert!(!serve_test(&mut sched, false).unwrap());
+        assert_eq!(sched.nr_intervals, 3);
+    }
+
+    #[test]
+    fn test_serve_restart() {
+        let mut sched = TestSched {
+            exit_after: Some(5),
+            restart_after: Some(2),
+            ..Default::default()
+        };
+        assert!(serve_test(&mut sched, false).unwrap());
+        assert_eq!(sched.nr_intervals, 2);
+    }
+
+    #[test]
+    fn test_serve_shutdown() {
+        let mut sched = TestSched {
+            restart_after: Some(1),
+            ..Default::default()
+        };
+        assert!(!serve_test(&mut sched, true).unwrap());
+        assert_eq!(sched.nr_intervals, 0);
+    }
+
+    #[test]
+    fn test_serve_error() {
+        let mut sched = TestSched {
+            fail_after: Some(2),
+            ..Default::default()
+        };
+        assert!(serve_test(&mut sched, false).is_err());
+        assert_eq!(sched.nr_intervals, 2);
+    }
+
+    #[test]
+    fn test_serve_metrics() {
+        let mut sched = TestSched {
+            exit_after: Some(3),
+            ..Default::default()
+        };
+        let (res_ch, res_rx) = unbounded();
+        let (req_tx, req_ch) = unbounded();
+        req_tx.send(()).unwrap();
+        req_tx.send(()).unwrap();
+
+        assert!(!serve(&mut sched, &res_ch, &req_ch, &AtomicBool::new(false)).unwrap());
+        assert_eq!(res_rx.try_iter().collect::<Vec<_>>(), vec![1, 2]);
+
+        // The stats server going away is an error.
+        let mut sched = TestSched::default();
+        drop(req_tx);
+        assert!(serve(&mut sched, &res_ch, &req_ch, &AtomicBool::new(false)).is_err());
+    }
+}
@@ -3,7 +3,12 @@
 // This software may be used and distributed according to the terms of the
 // GNU General Public License version 2.
 
+use std::cell::Cell;
 use std::mem::MaybeUninit;
+use std::os::fd::AsFd;
+use std::os::fd::AsRawFd;
+use std::os::fd::OwnedFd;
+use std::rc::Rc;
 
 use crate::bpf_intf;
 use crate::bpf_intf::*;
@@ -11,7 +16,6 @@ use crate::bpf_skel::*;
 
 use std::ffi::c_int;
 use std::ffi::c_ulong;
-use std::ffi::CStr;
 
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
@@ -25,11 +29,15 @@ use anyhow::Result;
 use plain::Plain;
 use procfs::process::all_processes;
 
+use libbpf_rs::libbpf_sys;
 use libbpf_rs::libbpf_sys::bpf_object_open_opts;
+use libbpf_rs::MapCore;
+use libbpf_rs::MapFlags;
+use libbpf_rs::MapHandle;
+use libbpf_rs::MapType;
 use libbpf_rs::OpenObject;
-use libbpf_rs::ProgramInput;
 
-use libc::{c_char, pthread_self, pthread_setschedparam, sched_param};
+use libc::{pthread_self, pthread_setschedparam, sched_param};
 
 #[cfg(target_env = "musl")]
 use libc::timespec;
@@ -43,17 +51,22 @@ use scx_utils::uei_report;
 use scx_utils::Topology;
 use scx_utils::UserExitInfo;
 
+use scx_rustland_core::cpu_shard_map;
+use scx_rustland_core::shard_cpus;
+use scx_rustland_core::Runner;
+use scx_rustland_core::SchedBackend;
+use scx_rustland_core::SchedPolicy;
 use scx_rustland_core::ALLOCATOR;
 
 // Defined in UAPI
 const SCHED_EXT: i32 = 7;
-const TASK_COMM_LEN: usize = 16;
 
 // Allow to dispatch the task on any CPU.
 //
 // The task will be dispatched to the global shared DSQ and it will run on the first CPU available.
 #[allow(dead_code)]
 pub const RL_CPU_ANY: i32 = bpf_intf::RL_CPU_ANY as i32;
+const _: () = assert!(RL_CPU_ANY == scx_rustland_core::RL_CPU_ANY);
 
 /// High-level Rust abstraction to interact with a generic sched-ext BPF component.
 ///
@@ -73,62 +86,25 @@ pub const RL_CPU_ANY: i32 = bpf_intf::RL_CPU_ANY as i32;
 ///
 /// Finally the methods exited() and shutdown_and_report() can be used respectively to test
 /// whether the BPF component exited, and to shutdown and report the exit message.
-
-// Task queued for scheduling from the BPF component (see bpf_intf::queued_task_ctx).
-#[derive(Debug, PartialEq, Eq, PartialOrd, Clone)]
-pub struct QueuedTask {
-    pub pid: i32,             // pid that uniquely identifies a task
-    pub cpu: i32,             // CPU previously used by the task
-    pub nr_cpus_allowed: u64, // Number of CPUs that the task can use
-    pub flags: u64,           // task's enqueue flags
-    pub start_ts: u64,        // Timestamp since last time the task ran on a CPU (in ns)
-    pub stop_ts: u64,         // Timestamp since last time the task released a CPU (in ns)
-    pub exec_runtime: u64,    // Total cpu time since last sleep (in ns)
-    pub weight: u64,          // Task priority in the range [1..10000] (default is 100)
-    pub vtime: u64,           // Current task vruntime / deadline (set by the scheduler)
-    pub enq_cnt: u64,
-    pub comm: [c_char; TASK_COMM_LEN], // Task's executable name
-}
-
-impl QueuedTask {
-    /// Convert the task's comm field (C char array) into a Rust String.
-    #[allow(dead_code)]
-    pub fn comm_str(&self) -> String {
-        // Convert the C char array into a Rust String
-        let c_str = unsafe { CStr::from_ptr(self.comm.as_ptr()) };
-
-        // Handle potential invalid UTF-8
-        c_str.to_string_lossy().into_owned()
-    }
-}
-
-// Task queued for dispatching to the BPF component (see bpf_intf::dispatched_task_ctx).
-#[derive(Debug, PartialEq, Eq, PartialOrd, Clone)]
-pub struct DispatchedTask {
-    pub pid: i32,      // pid that uniquely identifies a task
-    pub cpu: i32, // target CPU selected by the scheduler (RL_CPU_ANY = dispatch on the first CPU available)
-    pub flags: u64, // task's enqueue flags
-    pub slice_ns: u64, // time slice in nanoseconds assigned to the task (0 = use default time slice)
-    pub vtime: u64, // this value can be used to send the task's vruntime or deadline directly to the underlying BPF dispatcher
-    pub enq_cnt: u64,
-}
-
-impl DispatchedTask {
-    // Create a DispatchedTask from a QueuedTask.
-    //
-    // A dispatched task should be always originated from a QueuedTask (there is no reason to
-    // dispatch a task if it wasn't queued to the scheduler earlier).
-    pub fn new(task: &QueuedTask) -> Self {
-        DispatchedTask {
-            pid: task.pid,
-            cpu: task.cpu,
-            flags: task.flags,
-            slice_ns: 0, // use default time slice
-            vtime: 0,
-            enq_cnt: task.enq_cnt,
-        }
-    }
-}
+///
+/// Multi-threaded dispatch
+/// =======================
+///
+/// When initialized with BpfScheduler::init_with_shards() the CPUs are partitioned into multiple
+/// shards (grouping CPUs by LLC), each one with its own pair of queued / dispatched ring buffers.
+///
+/// Shard 0 is served by the BpfScheduler instance itself (that must be used by the thread that
+/// initialized the scheduler), the remaining shards can be obtained with take_shards() and moved
+/// to dedicated worker threads. Each worker must call BpfShard::register_thread() before
+/// dequeuing or dispatching tasks, then it can use the same dequeue_task(), dispatch_task(),
+/// select_cpu() and notify_complete() methods provided by BpfScheduler.
+
+// Tasks exchanged with the BPF component (see bpf_intf::queued_task_ctx and
+// bpf_intf::dispatched_task_ctx).
+#[allow(unused_imports)]
+pub use scx_rustland_core::DispatchedTask;
+#[allow(unused_imports)]
+pub use scx_rustland_core::QueuedTask;
 
 // Helpers used to submit tasks to the BPF user ring buffer.
 unsafe impl Plain for bpf_intf::dispatched_task_ctx {}
@@ -148,7 +124,8 @@ struct EnqueuedMessage {
 
 impl EnqueuedMessage {
     fn from_bytes(bytes: &[u8]) -> Self {
-        let queued_task_struct = unsafe { *(bytes.as_ptr() as *const bpf_intf::queued_task_ctx) };
+        let queued_task_struct =
+            unsafe { std::ptr::read_unaligned(bytes.as_ptr() as *const bpf_intf::queued_task_ctx) };
         EnqueuedMessage {
             inner: queued_task_struct,
         }
@@ -171,24 +148,303 @@ impl EnqueuedMessage {
     }
 }
 
-pub struct BpfScheduler<'cb> {
-    pub skel: BpfSkel<'cb>,                // Low-level BPF connector
-    shutdown: Arc<AtomicBool>,             // Determine scheduler shutdown
-    queued: libbpf_rs::RingBuffer<'cb>,    // Ring buffer of queued tasks
-    dispatched: libbpf_rs::UserRingBuffer, // User Ring buffer of dispatched tasks
-    struct_ops: Option<libbpf_rs::Link>,   // Low-level BPF methods
+/// Connector to a single shard of the BPF dispatcher.
+///
+/// A shard owns a subset of the CPUs (see cpus()): tasks that were running on these CPUs are
+/// queued to the shard and tasks dispatched through the shard are consumed by these CPUs. Each
+/// shard must be served by exactly one thread.
+pub struct BpfShard<'cb> {
+    id: usize,                               // Shard index
+    cpus: Vec<usize>,                        // CPUs owned by the shard
+    ctx: *mut types::shard_ctx,              // Shard state shared with the BPF component
+    queued: libbpf_rs::RingBuffer<'cb>,      // Ring buffer of queued tasks
+    dispatched: libbpf_rs::UserRingBuffer,   // User Ring buffer of dispatched tasks
+    slot: Rc<Cell<Option<queued_task_ctx>>>, // Last task read from the ring buffer
+    exited: Vec<i32>,                        // Exit notifications received from the ring buffer
+    shutdown: Arc<AtomicBool>,               // Determine scheduler shutdown
+    select_cpu_prog: OwnedFd,                // rs_select_cpu() program
+    usersched_tids: MapHandle,               // TID -> shard map
+    _maps: [MapHandle; 2],                   // Ring buffer maps used by the shard
 }
 
-// Buffer to store a task read from the ring buffer.
-//
-// NOTE: make the buffer aligned to 64-bits to prevent misaligned dereferences when accessing the
-// buffer using a pointer.
-const BUFSIZE: usize = size_of::<queued_task_ctx>();
+// SAFETY: a shard is used by exactly one thread at a time. The ring buffers are only accessed
+// through &mut self and the slot is shared only with the ring buffer callback, that
+// is executed synchronously by consume_raw_n() on the same thread. The shard context points to
+// the BPF .bss map, that stays mapped as long as the BpfScheduler that created the shard is alive
+// (see BpfScheduler::take_shards()).
+unsafe impl Send for BpfShard<'_> {}
+
+impl<'cb> BpfShard<'cb> {
+    fn new(
+        id: usize,
+        cpus: Vec<usize>,
+        skel: &mut BpfSkel<'cb>,
+        shutdown: Arc<AtomicBool>,
+        queued_map: MapHandle,
+        dispatched_map: MapHandle,
+    ) -> Result<Self> {
+        // Copy one item from the ring buffer.
+        //
+        // Each invocation of the callback copies exactly one QueuedTask item to the shard's slot,
+        // this is guaranteed by the fact that dequeue_task() always consumes one item at a time.
+        let slot = Rc::new(Cell::new(None));
+        let callback_slot = slot.clone();
+        let callback = move |data: &[u8]| -> i32 {
+            callback_slot.set(Some(EnqueuedMessage::from_bytes(data).inner));
+
+            // Return 0 to indicate successful completion of the copy.
+            0
+        };
+
+        // Build the ring buffer of queued tasks.
+        let mut rbb = libbpf_rs::RingBufferBuilder::new();
+        rbb.add(&queued_map, callback)
+            .context("Failed to add ringbuf callback")?;
+        let queued = rbb.build().context("Failed to build ringbuf")?;
+
+        // Build the user ring buffer of dispatched tasks.
+        let dispatched = libbpf_rs::UserRingBuffer::new(&dispatched_map)
+            .context("Failed to create user ringbuf")?;
+
+        let select_cpu_prog = skel
+            .progs
+            .rs_select_cpu
+            .as_fd()
+            .try_clone_to_owned()
+            .context("Failed to duplicate rs_select_cpu fd")?;
+        let usersched_tids = MapHandle::try_from(&skel.maps.usersched_tids)
+            .context("Failed to duplicate usersched_tids map")?;
+        let ctx = &mut skel.maps.bss_data.as_mut().unwrap().shards[id] as *mut types::shard_ctx;
+
+        Ok(Self {
+            id,
+            cpus,
+            ctx,
+            queued,
+            dispatched,
+            slot,
+            exited: Vec::new(),
+            shutdown,
+            select_cpu_prog,
+            usersched_tids,
+            _maps: [queued_map, dispatched_map],
+        })
+    }
+
+    // Index of the shard.
+    #[allow(dead_code)]
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    // CPUs owned by the shard.
+    #[allow(dead_code)]
+    pub fn cpus(&self) -> &[usize] {
+        &self.cpus
+    }
 
-#[repr(align(8))]
-struct AlignedBuffer([u8; BUFSIZE]);
+    // Register the calling thread as the user-space scheduler thread serving this shard and
+    // restrict its affinity to the CPUs owned by the shard.
+    //
+    // This must be called by each worker thread before using the shard. Shard 0 is always served
+    // by the thread that initialized the BpfScheduler, so calling this method on shard 0 only
+    // updates the thread affinity.
+    #[allow(dead_code)]
+    pub fn register_thread(&mut self) -> Result<()> {
+        let tid = unsafe { libc::syscall(libc::SYS_gettid) } as u32;
+
+        if self.id > 0 {
+            self.usersched_tids
+                .update(
+                    &tid.to_ne_bytes(),
+                    &(self.id as u32).to_ne_bytes(),
+                    MapFlags::ANY,
+                )
+                .context("Failed to register shard thread")?;
+            unsafe { std::ptr::write_volatile(&mut (*self.ctx).tid, tid) };
+        }
 
-static mut BUF: AlignedBuffer = AlignedBuffer([0; BUFSIZE]);
+        let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+        for &cpu in &self.cpus {
+            unsafe { libc::CPU_SET(cpu, &mut set) };
+        }
+        let ret = unsafe { libc::sched_setaffinity(0, std::mem::size_of_val(&set), &set) };
+        if ret < 0 {
+            bail!(
+                "Failed to set affinity of shard {}: {}",
+                self.id,
+                std::io::Error::last_os_error()
+            );
+        }
+
+        Ok(())
+    }
+
+    // Counter of tasks queued to this shard.
+    #[allow(dead_code)]
+    pub fn nr_queued(&self) -> u64 {
+        unsafe { std::ptr::read_volatile(&(*self.ctx).nr_queued) }
+    }
+
+    // Counter of tasks scheduled by this shard.
+    #[allow(dead_code)]
+    pub fn nr_scheduled(&self) -> u64 {
+        unsafe { std::ptr::read_volatile(&(*self.ctx).nr_scheduled) }
+    }
+
+    fn set_nr_queued(&mut self, nr_queued: u64) {
+        unsafe { std::ptr::write_volatile(&mut (*self.ctx).nr_queued, nr_queued) };
+    }
+
+    // Notify the BPF component that the thread serving this shard has completed its scheduling
+    // cycle, updating the amount tasks that are still pending.
+    pub fn notify_complete(&mut self, nr_pending: u64) {
+        unsafe { std::ptr::write_volatile(&mut (*self.ctx).nr_scheduled, nr_pending) };
+        std::thread::yield_now();
+    }
+
+    // Pick an idle CPU for the target PID.
+    //
+    // Return a negative value if no idle CPU can be selected, or a negative errno if running the
+    // rs_select_cpu() program failed.
+    pub fn select_cpu(&mut self, pid: i32, cpu: i32, flags: u64) -> i32 {
+        let args = task_cpu_arg {
+            pid: pid as c_int,
+            cpu: cpu as c_int,
+            flags: flags as c_ulong,
+        };
+        let mut opts = unsafe { std::mem::zeroed::<libbpf_sys::bpf_test_run_opts>() };
+        opts.sz = std::mem::size_of_val(&opts) as _;
+        opts.ctx_in = (&args as *const task_cpu_arg).cast();
+        opts.ctx_size_in = std::mem::size_of_val(&args) as _;
+
+        let ret = unsafe {
+            libbpf_sys::bpf_prog_test_run_opts(self.select_cpu_prog.as_raw_fd(), &mut opts)
+        };
+        if ret < 0 {
+            return ret;
+        }
+
+        opts.retval as i32
+    }
+
+    // Receive a task to be scheduled from the BPF dispatcher.
+    //
+    // Task exit notifications are collected and can be retrieved with take_exited_tasks().
+    pub fn dequeue_task(&mut self) -> Result<Option<QueuedTask>, i32> {
+        loop {
+            // Try to consume the first task from the ring buffer.
+            match self.queued.consume_raw_n(1) {
+                0 => {
+                    // Ring buffer is empty.
+                    self.set_nr_queued(0);
+                    return Ok(None);
+                }
+                1 => {
+                    let inner = self.slot.take().expect("ringbuf callback not invoked");
+                    self.set_nr_queued(self.nr_queued().saturating_sub(1));
+                    if inner.exited != 0 {
+                        self.exited.push(inner.pid);
+                        continue;
+                    }
+
+                    // A valid task is received, convert data to a proper task struct.
+                    let task = EnqueuedMessage { inner }.to_queued_task();
+
+                    return Ok(Some(task));
+                }
+                res if res < 0 => return Err(res),
+                res => panic!("Unexpected return value from libbpf-rs::consume_raw(): {res}"),
+            }
+        }
+    }
+
+    // Return the PIDs of the tasks that exited since the last call (see
+    // BpfScheduler::set_notify_exit()).
+    pub fn take_exited_tasks(&mut self) -> Vec<i32> {
+        std::mem::take(&mut self.exited)
+    }
+
+    // Return true if the scheduler is shutting down.
+    #[allow(dead_code)]
+    pub fn exited(&self) -> bool {
+        self.shutdown.load(Ordering::Relaxed)
+    }
+
+    // Send a task to the dispatcher.
+    pub fn dispatch_task(&mut self, task: &DispatchedTask) -> Result<(), libbpf_rs::Error> {
+        // Reserve a slot in the user ring buffer.
+        let mut urb_sample = self
+            .dispatched
+            .reserve(std::mem::size_of::<bpf_intf::dispatched_task_ctx>())?;
+        let bytes = urb_sample.as_mut();
+        let dispatched_task = plain::from_mut_bytes::<bpf_intf::dispatched_task_ctx>(bytes)
+            .expect("failed to convert bytes");
+
+        // Convert the dispatched task into the low-level dispatched task context.
+        let bpf_intf::dispatched_task_ctx {
+            pid,
+            cpu,
+            flags,
+            slice_ns,
+            vtime,
+            enq_cnt,
+            ..
+        } = dispatched_task;
+
+        *pid = task.pid;
+        *cpu = task.cpu;
+        *flags = task.flags;
+        *slice_ns = task.slice_ns;
+        *vtime = task.vtime;
+        *enq_cnt = task.enq_cnt;
+
+        // Store the task in the user ring buffer.
+        //
+        // NOTE: submit() only updates the reserved slot in the user ring buffer, so it is not
+        // expected to fail.
+        self.dispatched
+            .submit(urb_sample)
+            .expect("failed to submit task");
+
+        Ok(())
+    }
+}
+
+impl SchedBackend for BpfShard<'_> {
+    fn dequeue_task(&mut self) -> Result<Option<QueuedTask>, i32> {
+        BpfShard::dequeue_task(self)
+    }
+
+    fn dispatch_task(&mut self, task: &DispatchedTask) -> Result<()> {
+        Ok(BpfShard::dispatch_task(self, task)?)
+    }
+
+    fn select_cpu(&mut self, pid: i32, cpu: i32, flags: u64) -> i32 {
+        BpfShard::select_cpu(self, pid, cpu, flags)
+    }
+
+    fn notify_complete(&mut self, nr_pending: u64) {
+        BpfShard::notify_complete(self, nr_pending)
+    }
+
+    fn take_exited_tasks(&mut self) -> Vec<i32> {
+        BpfShard::take_exited_tasks(self)
+    }
+
+    fn exited(&mut self) -> bool {
+        BpfShard::exited(self)
+    }
+}
+
+pub struct BpfScheduler<'cb> {
+    pub skel: BpfSkel<'cb>,              // Low-level BPF connector
+    shutdown: Arc<AtomicBool>,           // Determine scheduler shutdown
+    shard: BpfShard<'cb>,                // Shard served by the scheduler thread (shard 0)
+    shards: Vec<BpfShard<'cb>>,          // Shards to be handed over to worker threads
+    nr_shards: usize,                    // Total amount of shards
+    struct_ops: Option<libbpf_rs::Link>, // Low-level BPF methods
+}
 
 static SET_HANDLER: Once = Once::new();
 
@@ -203,6 +459,24 @@ fn set_ctrlc_handler(shutdown: Arc<AtomicBool>) -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+// Partition the CPUs into @nr_shards groups, keeping the CPUs of the same LLC (or of the same
+// core, if there are less LLCs than shards) in the same group.
+fn topo_shard_cpus(topo: &Topology, nr_shards: usize) -> Vec<Vec<usize>> {
+    let units: Vec<Vec<usize>> = if topo.all_llcs.len() >= nr_shards {
+        topo.all_llcs
+            .values()
+            .map(|llc| llc.span.iter().collect())
+            .collect()
+    } else {
+        topo.all_cores
+            .values()
+            .map(|core| core.span.iter().collect())
+            .collect()
+    };
+
+    shard_cpus(units, nr_shards)
+}
+
 impl<'cb> BpfScheduler<'cb> {
     #[allow(clippy::too_many_arguments)]
     pub fn init(
@@ -216,6 +490,39 @@ impl<'cb> BpfScheduler<'cb> {
         slice_ns: u64,
         name: &str,
     ) -> Result<Self> {
+        Self::init_with_shards(
+            open_object,
+            open_opts,
+            exit_dump_len,
+            partial,
+            debug,
+            builtin_idle,
+            numa_local,
+            slice_ns,
+            name,
+            1,
+        )
+    }
+
+    // Initialize the scheduler partitioning the CPUs into @nr_shards shards, each one served by a
+    // separate user-space thread (see take_shards()).
+    #[allow(clippy::too_many_arguments)]
+    pub fn init_with_shards(
+        open_object: &'cb mut MaybeUninit<OpenObject>,
+        open_opts: Option<bpf_object_open_opts>,
+        exit_dump_len: u32,
+        partial: bool,
+        debug: bool,
+        builtin_idle: bool,
+        numa_local: bool,
+        slice_ns: u64,
+        name: &str,
+        nr_shards: usize,
+    ) -> Result<Self> {
+        if nr_shards == 0 || nr_shards > MAX_SHARDS as usize {
+            bail!("nr_shards must be in the range [1..{}]", MAX_SHARDS);
+        }
+
         let shutdown = Arc::new(AtomicBool::new(false));
         set_ctrlc_handler(shutdown.clone()).context("Error setting Ctrl-C handler")?;
 
@@ -224,36 +531,20 @@ impl<'cb> BpfScheduler<'cb> {
         skel_builder.obj_builder.debug(debug);
         let mut skel = scx_ops_open!(skel_builder, open_object, rustland, open_opts)?;
 
-        // Copy one item from the ring buffer.
-        //
-        // # Safety
-        //
-        // Each invocation of the callback will trigger the copy of exactly one QueuedTask item to
-        // BUF. The caller must be synchronize to ensure that multiple invocations of the callback
-        // are not happening at the same time, but this is implicitly guaranteed by the fact that
-        // the caller is a single-thread process (for now).
-        //
-        // Use of a `str` whose contents are not valid UTF-8 is undefined behavior.
-        fn callback(data: &[u8]) -> i32 {
-            #[allow(static_mut_refs)]
-            unsafe {
-                // SAFETY: copying from the BPF ring buffer to BUF is safe, since the size of BUF
-                // is exactly the size of QueuedTask and the callback operates in chunks of
-                // QueuedTask items. It also copies exactly one QueuedTask at a time, this is
-                // guaranteed by the error code returned by this callback (see below). From a
-                // thread-safety perspective this is also correct, assuming the caller is a
-                // single-thread process (as it is for now).
-                BUF.0.copy_from_slice(data);
-            }
-
-            // Return 0 to indicate successful completion of the copy.
-            0
-        }
-
         // Check host topology to determine if we need to enable SMT capabilities.
         let topo = Topology::new().unwrap();
         skel.maps.rodata_data.as_mut().unwrap().smt_enabled = topo.smt_enabled;
 
+        // Assign the CPUs to the shards.
+        let shard_cpus = topo_shard_cpus(&topo, nr_shards);
+        if shard_cpus.iter().any(|cpus| cpus.is_empty()) {
+            bail!("Not enough CPUs to create {} shards", nr_shards);
+        }
+        let rodata = skel.maps.rodata_data.as_mut().unwrap();
+        rodata.nr_shards = nr_shards as u32;
+        let cpu_shard = cpu_shard_map(&shard_cpus, MAX_CPUS as usize);
+        rodata.cpu_shard.copy_from_slice(&cpu_shard);
+
         // Enable scheduler flags.
         skel.struct_ops.rustland_mut().flags =
             *compat::SCX_OPS_ENQ_LAST | *compat::SCX_OPS_ALLOW_QUEUED_WAKEUP;
@@ -272,22 +563,47 @@ impl<'cb> BpfScheduler<'cb> {
         skel.maps.rodata_data.as_mut().unwrap().debug = debug;
         let _ = Self::set_scx_ops_name(&mut skel.struct_ops.rustland_mut().name, name);
 
-        // Attach BPF scheduler.
         let mut skel = scx_ops_load!(skel, rustland, uei)?;
 
-        let struct_ops = Some(scx_ops_attach!(skel, rustland)?);
-
-        // Build the ring buffer of queued tasks.
-        let maps = &skel.maps;
-        let queued_ring_buffer = &maps.queued;
-        let mut rbb = libbpf_rs::RingBufferBuilder::new();
-        rbb.add(queued_ring_buffer, callback)
-            .expect("failed to add ringbuf callback");
-        let queued = rbb.build().expect("failed to build ringbuf");
+        // Create the ring buffers of the additional shards (shard 0 uses the statically defined
+        // ring buffers) and connect to all of them.
+        let mut shard_cpus = shard_cpus.into_iter();
+        let queued_map = MapHandle::try_from(&skel.maps.queued)?;
+        let dispatched_map = MapHandle::try_from(&skel.maps.dispatched)?;
+        let shard = BpfShard::new(
+            0,
+            shard_cpus.next().unwrap_or_default(),
+            &mut skel,
+            shutdown.clone(),
+            queued_map,
+            dispatched_map,
+        )?;
+        let mut shards = Vec::with_capacity(nr_shards - 1);
+        for (id, cpus) in (1..nr_shards).zip(shard_cpus) {
+            let queued_map = Self::create_shard_map(
+                &skel.maps.queued_shards,
+                id,
+                MapType::RingBuf,
+                skel.maps.queued.max_entries(),
+            )?;
+            let dispatched_map = Self::create_shard_map(
+                &skel.maps.dispatched_shards,
+                id,
+                MapType::UserRingBuf,
+                skel.maps.dispatched.max_entries(),
+            )?;
+            shards.push(BpfShard::new(
+                id,
+                cpus,
+                &mut skel,
+                shutdown.clone(),
+                queued_map,
+                dispatched_map,
+            )?);
+        }
 
-        // Build the user ring buffer of dispatched tasks.
-        let dispatched = libbpf_rs::UserRingBuffer::new(&maps.dispatched)
-            .expect("failed to create user ringbuf");
+        // Attach BPF scheduler.
+        let struct_ops = Some(scx_ops_attach!(skel, rustland)?);
 
         // Lock all the memory to prevent page faults that could trigger potential deadlocks during
         // scheduling.
@@ -307,12 +623,66 @@ impl<'cb> BpfScheduler<'cb> {
         Ok(Self {
             skel,
             shutdown,
-            queued,
-            dispatched,
+            shard,
+            shards,
+            nr_shards,
             struct_ops,
         })
     }
 
+    // Create a ring buffer for @shard and insert it into the @outer array of maps.
+    fn create_shard_map(
+        outer: &dyn MapCore,
+        shard: usize,
+        map_type: MapType,
+        max_entries: u32,
+    ) -> Result<MapHandle> {
+        let opts = libbpf_sys::bpf_map_create_opts {
+            sz: std::mem::size_of::<libbpf_sys::bpf_map_create_opts>() as _,
+            ..Default::default()
+        };
+        let map = MapHandle::create(
+            map_type,
+            Some(format!("shard_{shard}")),
+            0,
+            0,
+            max_entries,
+            &opts,
+        )
+        .with_context(|| format!("Failed to create {map_type:?} for shard {shard}"))?;
+
+        let fd = map.as_fd().as_raw_fd() as u32;
+        outer
+            .update(
+                &(shard as u32).to_ne_bytes(),
+                &fd.to_ne_bytes(),
+                MapFlags::ANY,
+            )
+            .with_context(|| format!("Failed to insert {map_type:?} of shard {shard}"))?;
+
+        Ok(map)
+    }
+
+    // Total amount of shards.
+    #[allow(dead_code)]
+    pub fn nr_shards(&self) -> usize {
+        self.nr_shards
+    }
+
+    /// Hand over shards 1..nr_shards to the caller, so that they can be moved to dedicated worker
+    /// threads (shard 0 is served by the BpfScheduler instance itself).
+    ///
+    /// # Safety
+    ///
+    /// The shards access the BPF .bss map, that is unmapped when the BpfScheduler is dropped. The
+    /// caller must guarantee that the returned shards are not used after that, e.g., by serving
+    /// them from scoped threads that are joined before dropping the BpfScheduler. Dropping the
+    /// shards afterwards is fine.
+    #[allow(dead_code)]
+    pub unsafe fn take_shards(&mut self) -> Vec<BpfShard<'cb>> {
+        std::mem::take(&mut self.shards)
+    }
+
     // Set the name of the scx ops.
     fn set_scx_ops_name(name_field: &mut [i8], src: &str) -> Result<()> {
         if !src.is_ascii() {
@@ -383,8 +753,7 @@ impl<'cb> BpfScheduler<'cb> {
     // some point, otherwise the BPF component will keep waking-up the user-space scheduler in a
     // busy loop, causing unnecessary high CPU consumption.
     pub fn notify_complete(&mut self, nr_pending: u64) {
-        self.skel.maps.bss_data.as_mut().unwrap().nr_scheduled = nr_pending;
-        std::thread::yield_now();
+        self.shard.notify_complete(nr_pending);
     }
 
     // Counter of the online CPUs.
@@ -399,16 +768,36 @@ impl<'cb> BpfScheduler<'cb> {
         &mut self.skel.maps.bss_data.as_mut().unwrap().nr_running
     }
 
-    // Counter of queued tasks.
+    // Counter of queued tasks (shard 0).
     #[allow(dead_code)]
     pub fn nr_queued_mut(&mut self) -> &mut u64 {
-        &mut self.skel.maps.bss_data.as_mut().unwrap().nr_queued
+        &mut self.skel.maps.bss_data.as_mut().unwrap().shards[0].nr_queued
     }
 
-    // Counter of scheduled tasks.
+    // Counter of scheduled tasks (shard 0).
     #[allow(dead_code)]
     pub fn nr_scheduled_mut(&mut self) -> &mut u64 {
-        &mut self.skel.maps.bss_data.as_mut().unwrap().nr_scheduled
+        &mut self.skel.maps.bss_data.as_mut().unwrap().shards[0].nr_scheduled
+    }
+
+    // Counter of queued tasks across all the shards.
+    #[allow(dead_code)]
+    pub fn nr_queued_total(&self) -> u64 {
+        let bss_data = self.skel.maps.bss_data.as_ref().unwrap();
+        bss_data.shards[..self.nr_shards]
+            .iter()
+            .map(|shard| unsafe { std::ptr::read_volatile(&shard.nr_queued) })
+            .sum()
+    }
+
+    // Counter of scheduled tasks across all the shards.
+    #[allow(dead_code)]
+    pub fn nr_scheduled_total(&self) -> u64 {
+        let bss_data = self.skel.maps.bss_data.as_ref().unwrap();
+        bss_data.shards[..self.nr_shards]
+            .iter()
+            .map(|shard| unsafe { std::ptr::read_volatile(&shard.nr_scheduled) })
+            .sum()
     }
 
     // Counter of user dispatch events.
@@ -496,92 +885,39 @@ impl<'cb> BpfScheduler<'cb> {
     // Pick an idle CPU for the target PID.
     #[allow(dead_code)]
     pub fn select_cpu(&mut self, pid: i32, cpu: i32, flags: u64) -> i32 {
-        let prog = &mut self.skel.progs.rs_select_cpu;
-        let mut args = task_cpu_arg {
-            pid: pid as c_int,
-            cpu: cpu as c_int,
-            flags: flags as c_ulong,
-        };
-        let input = ProgramInput {
-            context_in: Some(unsafe {
-                std::slice::from_raw_parts_mut(
-                    &mut args as *mut _ as *mut u8,
-                    std::mem::size_of_val(&args),
-                )
-            }),
-            ..Default::default()
-        };
-        let out = prog.test_run(input).unwrap();
-
-        out.return_value as i32
+        self.shard.select_cpu(pid, cpu, flags)
     }
 
     // Receive a task to be scheduled from the BPF dispatcher.
-    #[allow(static_mut_refs)]
     pub fn dequeue_task(&mut self) -> Result<Option<QueuedTask>, i32> {
-        let bss_data = self.skel.maps.bss_data.as_mut().unwrap();
-        
-        // Try to consume the first task from the ring buffer.
-        match self.queued.consume_raw_n(1) {
-            0 => {
-                // Ring buffer is empty.
-                bss_data.nr_queued = 0;
-                Ok(None)
-            }
-            1 => {
-                // A valid task is received, convert data to a proper task struct.
-                let task = unsafe { EnqueuedMessage::from_bytes(&BUF.0).to_queued_task() };
-                bss_data.nr_queued = bss_data.nr_queued.saturating_sub(1);
-
-                Ok(Some(task))
-            }
-            res if res < 0 => Err(res),
-            res => panic!("Unexpected return value from libbpf-rs::consume_raw(): {res}"),
-        }
+        self.shard.dequeue_task()
     }
 
     // Send a task to the dispatcher.
     pub fn dispatch_task(&mut self, task: &DispatchedTask) -> Result<(), libbpf_rs::Error> {
-        // Reserve a slot in the user ring buffer.
-        let mut urb_sample = self
-            .dispatched
-            .reserve(std::mem::size_of::<bpf_intf::dispatched_task_ctx>())?;
-        let bytes = urb_sample.as_mut();
-        let dispatched_task = plain::from_mut_bytes::<bpf_intf::dispatched_task_ctx>(bytes)
-            .expect("failed to convert bytes");
-
-        // Convert the dispatched task into the low-level dispatched task context.
-        let bpf_intf::dispatched_task_ctx {
-            pid,
-            cpu,
-            flags,
-            slice_ns,
-            vtime,
-            enq_cnt,
-            ..
-        } = dispatched_task;
-
-        *pid = task.pid;
-        *cpu = task.cpu;
-        *flags = task.flags;
-        *slice_ns = task.slice_ns;
-        *vtime = task.vtime;
-        *enq_cnt = task.enq_cnt;
+        self.shard.dispatch_task(task)
+    }
 
-        // Store the task in the user ring buffer.
-        //
-        // NOTE: submit() only updates the reserved slot in the user ring buffer, so it is not
-        // expected to fail.
-        self.dispatched
-            .submit(urb_sample)
-            .expect("failed to submit task");
+    // Enable or disable task exit notifications (see take_exited_tasks()).
+    #[allow(dead_code)]
+    pub fn set_notify_exit(&mut self, enabled: bool) {
+        self.skel.maps.bss_data.as_mut().unwrap().notify_exit = enabled;
+    }
 
-        Ok(())
+    // Return the PIDs of the tasks that exited since the last call (shard 0).
+    #[allow(dead_code)]
+    pub fn take_exited_tasks(&mut self) -> Vec<i32> {
+        self.shard.take_exited_tasks()
     }
 
     // Read exit code from the BPF part.
+    //
+    // When the BPF part exits the shutdown is propagated to all the shards.
     pub fn exited(&mut self) -> bool {
-        self.shutdown.load(Ordering::Relaxed) || uei_exited!(&self.skel, uei)
+        if uei_exited!(&self.skel, uei) {
+            self.shutdown.store(true, Ordering::Relaxed);
+        }
+        self.shutdown.load(Ordering::Relaxed)
     }
 
     // Called on exit to shutdown and report exit message from the BPF part.
@@ -591,6 +927,57 @@ impl<'cb> BpfScheduler<'cb> {
     }
 }
 
+impl SchedBackend for BpfScheduler<'_> {
+    fn dequeue_task(&mut self) -> Result<Option<QueuedTask>, i32> {
+        BpfScheduler::dequeue_task(self)
+    }
+
+    fn dispatch_task(&mut self, task: &DispatchedTask) -> Result<()> {
+        Ok(BpfScheduler::dispatch_task(self, task)?)
+    }
+
+    fn select_cpu(&mut self, pid: i32, cpu: i32, flags: u64) -> i32 {
+        BpfScheduler::select_cpu(self, pid, cpu, flags)
+    }
+
+    fn notify_complete(&mut self, nr_pending: u64) {
+        BpfScheduler::notify_complete(self, nr_pending)
+    }
+
+    fn take_exited_tasks(&mut self) -> Vec<i32> {
+        BpfScheduler::take_exited_tasks(self)
+    }
+
+    fn exited(&mut self) -> bool {
+        BpfScheduler::exited(self)
+    }
+}
+
+/// Run a scheduling policy on top of a BpfScheduler created by @init, until the scheduler exits.
+///
+/// Task exit notifications are enabled automatically. If the BPF component requests a restart
+/// the pending tasks are dropped and a new BpfScheduler instance is created.
+#[allow(dead_code)]
+pub fn run_policy<P, F>(runner: &mut Runner<P>, mut init: F) -> Result<UserExitInfo>
+where
+    P: SchedPolicy,
+    F: for<'a> FnMut(&'a mut MaybeUninit<OpenObject>) -> Result<BpfScheduler<'a>>,
+{
+    loop {
+        let mut open_object = MaybeUninit::uninit();
+        let mut bpf = init(&mut open_object)?;
+        bpf.set_notify_exit(true);
+
+        runner.run(&mut bpf);
+
+        let uei = bpf.shutdown_and_report()?;
+        if !uei.should_restart() {
+            return Ok(uei);
+        }
+        runner.reset();
+    }
+}
+
 // Disconnect the low-level BPF scheduler.
 impl Drop for BpfScheduler<'_> {
     fn drop(&mut self) {
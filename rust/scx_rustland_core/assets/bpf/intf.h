@@ -45,6 +45,13 @@ typedef int pid_t;
  */
 #define MAX_CPUS 1024
 
+/*
+ * Maximum amount of user-space dispatcher shards: each shard owns a subset of
+ * the CPUs and has its own queued / dispatched ring buffers, so that it can be
+ * served by a dedicated user-space thread.
+ */
+#define MAX_SHARDS 64
+
 #ifndef TASK_COMM_LEN
 #define TASK_COMM_LEN	16
 #endif
@@ -95,6 +102,7 @@ struct queued_task_ctx {
 	u64 vtime; /* Current task's vruntime */
 	u64 enq_cnt;
 	char comm[TASK_COMM_LEN]; /* Task's executable name */
+	u8 exited; /* Task exit notification (see notify_exit) */
 };
 
 /*
@@ -18,6 +18,12 @@
  * @dispatched for the messages sent by the user-space scheduler to the BPF
  * dispatcher.
  *
+ * The CPUs can also be partitioned into multiple shards: each shard has its
+ * own pair of @queued / @dispatched ring buffers (see @queued_shards and
+ * @dispatched_shards) and it is served by a dedicated user-space thread, so
+ * that the scheduling work can be spread across multiple threads. A single
+ * shard (the default) matches the classic single-threaded model.
+ *
  * The BPF dispatcher is completely agnostic of the particular scheduling
  * policy implemented in user-space. For this reason developers that are
  * willing to use this scheduler to experiment scheduling policies should be
@@ -57,6 +63,9 @@ UEI_DEFINE(uei);
  * This ensures to work in bursts: tasks are queued, then the user-space
  * scheduler runs and dispatches them. Once all these tasks exhaust their
  * time slices, the scheduler is invoked again, repeating the cycle.
+ *
+ * Each shard has its own scheduler DSQ (SCHED_DSQ + shard), holding the
+ * user-space thread that serves the shard.
  */
 #define SCHED_DSQ (MAX_CPUS + 1)
 
@@ -65,30 +74,58 @@ UEI_DEFINE(uei);
  */
 const volatile u32 usersched_pid; /* User-space scheduler PID */
 const volatile u32 khugepaged_pid; /* khugepaged PID */
-u64 usersched_last_run_at; /* Timestamp of the last user-space scheduler execution */
 static u64 nr_cpu_ids; /* Maximum possible CPU number */
 
 /*
- * Default task time slice.
+ * Amount of user-space dispatcher shards and CPU -> shard mapping.
  */
-const volatile u64 slice_ns;
+const volatile u32 nr_shards = 1;
+const volatile u32 cpu_shard[MAX_CPUS];
 
 /*
- * Number of tasks that are queued for scheduling.
- *
- * This number is incremented by the BPF component when a task is queued to the
- * user-space scheduler and it must be decremented by the user-space scheduler
- * when a task is consumed.
+ * Default task time slice.
  */
-volatile u64 nr_queued;
+const volatile u64 slice_ns;
 
 /*
- * Number of tasks that are waiting for scheduling.
- *
- * This number must be updated by the user-space scheduler to keep track if
- * there is still some scheduling work to do.
+ * Per-shard state shared with the user-space scheduler.
  */
-volatile u64 nr_scheduled;
+struct shard_ctx {
+	/*
+	 * Number of tasks that are queued for scheduling.
+	 *
+	 * This number is incremented by the BPF component when a task is
+	 * queued to the shard and it must be decremented by the user-space
+	 * thread serving the shard when a task is consumed.
+	 */
+	volatile u64 nr_queued;
+
+	/*
+	 * Number of tasks that are waiting for scheduling.
+	 *
+	 * This number must be updated by the user-space thread serving the
+	 * shard to keep track if there is still some scheduling work to do.
+	 */
+	volatile u64 nr_scheduled;
+
+	/*
+	 * Timestamp of the last execution of the shard's user-space thread.
+	 */
+	u64 last_run_at;
+
+	/*
+	 * TID of the user-space thread serving the shard (0 = not registered
+	 * yet, shard 0 is always served by @usersched_pid).
+	 */
+	volatile u32 tid;
+
+	/*
+	 * Flag used to wake-up the shard's user-space thread.
+	 */
+	volatile u32 needed;
+};
+
+struct shard_ctx shards[MAX_SHARDS];
 
 /*
  * Amount of currently running tasks.
@@ -102,6 +139,12 @@ volatile u64 nr_user_dispatches, nr_kernel_dispatches,
 /* Failure statistics */
 volatile u64 nr_failed_dispatches, nr_sched_congested;
 
+/*
+ * Notify task exits to user-space, sending a queued_task_ctx with @exited set
+ * (enabled by user-space).
+ */
+volatile bool notify_exit;
+
 /* Report additional debugging information */
 const volatile bool debug;
 
@@ -141,7 +184,7 @@ const volatile bool smt_enabled = true;
  *
  * This map is drained by the user-space scheduler.
  */
-struct {
+struct queued_map {
 	__uint(type, BPF_MAP_TYPE_RINGBUF);
 	__uint(max_entries, MAX_ENQUEUED_TASKS *
 				sizeof(struct queued_task_ctx));
@@ -153,12 +196,48 @@ struct {
  *
  * Drained by the kernel in .dispatch().
  */
-struct {
+struct dispatched_map {
         __uint(type, BPF_MAP_TYPE_USER_RINGBUF);
 	__uint(max_entries, MAX_ENQUEUED_TASKS *
 				sizeof(struct dispatched_task_ctx));
 } dispatched SEC(".maps");
 
+/*
+ * Per-shard @queued and @dispatched ring buffers.
+ *
+ * Shard 0 always uses the statically defined ring buffers, the ring buffers
+ * of the other shards are created and inserted by user-space before the
+ * scheduler is attached.
+ */
+struct {
+	__uint(type, BPF_MAP_TYPE_ARRAY_OF_MAPS);
+	__uint(max_entries, MAX_SHARDS);
+	__type(key, u32);
+	__array(values, struct queued_map);
+} queued_shards SEC(".maps") = {
+	.values = { [0] = &queued },
+};
+
+struct {
+	__uint(type, BPF_MAP_TYPE_ARRAY_OF_MAPS);
+	__uint(max_entries, MAX_SHARDS);
+	__type(key, u32);
+	__array(values, struct dispatched_map);
+} dispatched_shards SEC(".maps") = {
+	.values = { [0] = &dispatched },
+};
+
+/*
+ * Map the TIDs of the user-space threads serving shards 1..nr_shards-1 to
+ * their shard (populated by user-space).
+ */
+struct {
+	__uint(type, BPF_MAP_TYPE_HASH);
+	__uint(max_entries, MAX_SHARDS);
+	__type(key, u32);
+	__type(value, u32);
+} usersched_tids SEC(".maps");
+
 /*
  * Per-task local storage.
  *
@@ -232,12 +311,67 @@ struct {
  */
 #define USERSCHED_TIMER_NS	NSEC_PER_SEC
 
+/*
+ * Return the shard served by the target task @p if it is a user-space
+ * scheduler thread, or -ENOENT otherwise.
+ */
+static s32 usersched_shard(const struct task_struct *p)
+{
+	u32 pid = p->pid, *shard;
+
+	if (pid == usersched_pid)
+		return 0;
+	if (nr_shards <= 1)
+		return -ENOENT;
+
+	shard = bpf_map_lookup_elem(&usersched_tids, &pid);
+	if (!shard || *shard >= nr_shards)
+		return -ENOENT;
+
+	return *shard;
+}
+
 /*
  * Return true if the target task @p is the user-space scheduler.
  */
 static inline bool is_usersched_task(const struct task_struct *p)
 {
-	return p->pid == usersched_pid;
+	return usersched_shard(p) >= 0;
+}
+
+/*
+ * Return the shard that owns @cpu.
+ */
+static u32 cpu_to_shard(s32 cpu)
+{
+	u32 shard;
+
+	if (nr_shards <= 1 || cpu < 0 || cpu >= MAX_CPUS)
+		return 0;
+
+	shard = cpu_shard[cpu];
+
+	return shard < nr_shards ? shard : 0;
+}
+
+/*
+ * Return the state of @shard.
+ */
+static struct shard_ctx *lookup_shard_ctx(u32 shard)
+{
+	if (shard >= MAX_SHARDS) {
+		scx_bpf_error("Invalid shard: %u", shard);
+		return NULL;
+	}
+	return &shards[shard];
+}
+
+/*
+ * Return the DSQ used to dispatch the user-space thread serving @shard.
+ */
+static inline u64 shard_to_sched_dsq(u32 shard)
+{
+	return SCHED_DSQ + shard;
 }
 
 /*
@@ -272,27 +406,22 @@ static bool is_queued(const struct task_struct *p)
 	return p->scx.flags & SCX_TASK_QUEUED;
 }
 
-/*
- * Flag used to wake-up the user-space scheduler.
- */
-static volatile u32 usersched_needed;
-
 /*
  * Set user-space scheduler wake-up flag (equivalent to an atomic release
  * operation).
  */
-static void set_usersched_needed(void)
+static void set_usersched_needed(struct shard_ctx *sctx)
 {
-	__sync_fetch_and_or(&usersched_needed, 1);
+	__sync_fetch_and_or(&sctx->needed, 1);
 }
 
 /*
  * Check and clear user-space scheduler wake-up flag (equivalent to an atomic
  * acquire operation).
  */
-static bool test_and_clear_usersched_needed(void)
+static bool test_and_clear_usersched_needed(struct shard_ctx *sctx)
 {
-	return __sync_fetch_and_and(&usersched_needed, 0) == 1;
+	return __sync_fetch_and_and(&sctx->needed, 0) == 1;
 }
 
 /*
@@ -315,18 +444,30 @@ static bool test_and_clear_usersched_needed(void)
  * (even if a CPU becomes idle), because there is nothing to do.
  *
  * Also keep in mind that we don't need any protection here since this code
- * doesn't run concurrently with the user-space scheduler (that is single
- * threaded), therefore this check is also safe from a concurrency perspective.
+ * doesn't run concurrently with the user-space thread serving @shard (each
+ * shard is served by a single thread), therefore this check is also safe from
+ * a concurrency perspective.
  */
-static bool usersched_has_pending_tasks(void)
+static bool usersched_has_pending_tasks(u32 shard)
 {
-	if (test_and_clear_usersched_needed())
+	struct shard_ctx *sctx;
+	void *ringbuf;
+
+	sctx = lookup_shard_ctx(shard);
+	if (!sctx)
+		return false;
+
+	if (test_and_clear_usersched_needed(sctx))
 		return true;
 
-	if (nr_scheduled)
+	if (sctx->nr_scheduled)
 		return true;
 
-	return bpf_ringbuf_query(&queued, BPF_RB_AVAIL_DATA) > 0;
+	ringbuf = bpf_map_lookup_elem(&queued_shards, &shard);
+	if (!ringbuf)
+		return false;
+
+	return bpf_ringbuf_query(ringbuf, BPF_RB_AVAIL_DATA) > 0;
 }
 
 /*
@@ -687,6 +828,7 @@ static void get_task_info(struct queued_task_ctx *task,
 	task->weight = p->scx.weight;
 	task->vtime = p->scx.dsq_vtime;
 	task->enq_cnt = ++tctx->enq_cnt;
+	task->exited = 0;
 
 	bpf_core_read_str(&task->comm, sizeof(task->comm), &p->comm);
 }
@@ -714,21 +856,31 @@ static bool is_queued_wakeup(const struct task_struct *p, u64 enq_flags)
  */
 static void queue_task_to_userspace(struct task_struct *p, s32 prev_cpu, u64 enq_flags)
 {
-	struct queued_task_ctx *task;
+	u32 shard = cpu_to_shard(prev_cpu);
+	struct queued_task_ctx *task = NULL;
+	struct shard_ctx *sctx;
 	struct task_ctx *tctx;
+	void *ringbuf;
 
 	tctx = try_lookup_task_ctx(p);
 	if (!tctx)
 		return;
 
+	sctx = lookup_shard_ctx(shard);
+	if (!sctx)
+		return;
+
 	/*
-	 * Allocate a new entry in the ring buffer.
+	 * Allocate a new entry in the ring buffer of the shard that owns
+	 * @prev_cpu.
 	 *
 	 * If ring buffer is full, the user-space scheduler is congested,
 	 * so dispatch the task directly using the shared DSQ (the task
 	 * will be consumed by the first CPU available).
 	 */
-	task = bpf_ringbuf_reserve(&queued, sizeof(*task), 0);
+	ringbuf = bpf_map_lookup_elem(&queued_shards, &shard);
+	if (ringbuf)
+		task = bpf_ringbuf_reserve(ringbuf, sizeof(*task), 0);
 	if (!task) {
 		sched_congested(p);
 		scx_bpf_dsq_insert_vtime(p, SHARED_DSQ,
@@ -744,7 +896,7 @@ static void queue_task_to_userspace(struct task_struct *p, s32 prev_cpu, u64 enq
 	dbg_msg("enqueue: pid=%d (%s)", p->pid, p->comm);
 	get_task_info(task, p, tctx, enq_flags, prev_cpu);
 	bpf_ringbuf_submit(task, 0);
-	__sync_fetch_and_add(&nr_queued, 1);
+	__sync_fetch_and_add(&sctx->nr_queued, 1);
 }
 
 /*
@@ -754,7 +906,7 @@ static void queue_task_to_userspace(struct task_struct *p, s32 prev_cpu, u64 enq
  */
 void BPF_STRUCT_OPS(rustland_enqueue, struct task_struct *p, u64 enq_flags)
 {
-	s32 prev_cpu = scx_bpf_task_cpu(p), cpu;
+	s32 prev_cpu = scx_bpf_task_cpu(p), cpu, shard;
 	bool is_wakeup = is_queued_wakeup(p, enq_flags);
 
 	/*
@@ -762,8 +914,9 @@ void BPF_STRUCT_OPS(rustland_enqueue, struct task_struct *p, u64 enq_flags)
 	 * consumed from ops.dispatch() only when there's any pending
 	 * scheduling action to do.
 	 */
-	if (is_usersched_task(p)) {
-		scx_bpf_dsq_insert(p, SCHED_DSQ, slice_ns, enq_flags);
+	shard = usersched_shard(p);
+	if (shard >= 0) {
+		scx_bpf_dsq_insert(p, shard_to_sched_dsq(shard), slice_ns, enq_flags);
 		goto out_kick;
 	}
 
@@ -873,22 +1026,29 @@ static long handle_dispatched_task(struct bpf_dynptr *dynptr, void *context)
  */
 void BPF_STRUCT_OPS(rustland_dispatch, s32 cpu, struct task_struct *prev)
 {
+	u32 shard = cpu_to_shard(cpu);
+	void *ringbuf;
+	s32 ret;
+
 	/*
-	 * Consume all tasks from the @dispatched list and immediately
-	 * dispatch them on the target CPU decided by the user-space
-	 * scheduler.
+	 * Consume all tasks from the @dispatched list of the shard that
+	 * owns @cpu and immediately dispatch them on the target CPU
+	 * decided by the user-space scheduler.
 	 */
-	s32 ret = bpf_user_ringbuf_drain(&dispatched,
-					 handle_dispatched_task, NULL, BPF_RB_NO_WAKEUP);
-	if (ret)
-		dbg_msg("User ringbuf drain error: %d", ret);
+	ringbuf = bpf_map_lookup_elem(&dispatched_shards, &shard);
+	if (ringbuf) {
+		ret = bpf_user_ringbuf_drain(ringbuf, handle_dispatched_task,
+					     NULL, BPF_RB_NO_WAKEUP);
+		if (ret)
+			dbg_msg("User ringbuf drain error: %d", ret);
+	}
 
 	/*
-	 * Dispatch the user-space scheduler if there's any pending action
-	 * to do.
+	 * Dispatch the user-space scheduler thread serving this shard if
+	 * there's any pending action to do.
 	 */
-	if (usersched_has_pending_tasks() &&
-	    scx_bpf_dsq_move_to_local(SCHED_DSQ, 0))
+	if (usersched_has_pending_tasks(shard) &&
+	    scx_bpf_dsq_move_to_local(shard_to_sched_dsq(shard), 0))
 		return;
 
 	/*
@@ -911,9 +1071,12 @@ void BPF_STRUCT_OPS(rustland_dispatch, s32 cpu, struct task_struct *prev)
 	 * In case of the user-space scheduler task, replenish its time
 	 * slice only if there're still pending scheduling actions to do.
 	 */
-	if (prev && is_queued(prev) &&
-	    (!is_usersched_task(prev) || usersched_has_pending_tasks()))
-		prev->scx.slice = slice_ns;
+	if (prev && is_queued(prev)) {
+		s32 prev_shard = usersched_shard(prev);
+
+		if (prev_shard < 0 || usersched_has_pending_tasks(prev_shard))
+			prev->scx.slice = slice_ns;
+	}
 }
 
 void BPF_STRUCT_OPS(rustland_runnable, struct task_struct *p, u64 enq_flags)
@@ -935,11 +1098,15 @@ void BPF_STRUCT_OPS(rustland_runnable, struct task_struct *p, u64 enq_flags)
  */
 void BPF_STRUCT_OPS(rustland_running, struct task_struct *p)
 {
-	s32 cpu = scx_bpf_task_cpu(p);
+	s32 cpu = scx_bpf_task_cpu(p), shard;
+	struct shard_ctx *sctx;
 	struct task_ctx *tctx;
 
-	if (is_usersched_task(p)) {
-		usersched_last_run_at = scx_bpf_now();
+	shard = usersched_shard(p);
+	if (shard >= 0) {
+		sctx = lookup_shard_ctx(shard);
+		if (sctx)
+			sctx->last_run_at = scx_bpf_now();
 		return;
 	}
 
@@ -993,6 +1160,51 @@ void BPF_STRUCT_OPS(rustland_enable, struct task_struct *p)
 	p->scx.slice = slice_ns;
 }
 
+/*
+ * A task is leaving the sched_ext scheduler: notify user-space if requested,
+ * so that any state associated to the task can be released.
+ */
+void BPF_STRUCT_OPS(rustland_exit_task, struct task_struct *p,
+		    struct scx_exit_task_args *args)
+{
+	struct queued_task_ctx *task;
+	struct shard_ctx *sctx;
+	u32 shard;
+	void *ringbuf;
+
+	if (!notify_exit || is_usersched_task(p))
+		return;
+
+	shard = cpu_to_shard(scx_bpf_task_cpu(p));
+	sctx = lookup_shard_ctx(shard);
+	if (!sctx)
+		return;
+
+	ringbuf = bpf_map_lookup_elem(&queued_shards, &shard);
+	if (!ringbuf)
+		return;
+
+	/*
+	 * Exit notifications are best-effort: if the ring buffer is full
+	 * the user-space scheduler will simply never dispatch the task.
+	 */
+	task = bpf_ringbuf_reserve(ringbuf, sizeof(*task), 0);
+	if (!task)
+		return;
+
+	__builtin_memset(task, 0, sizeof(*task));
+	task->pid = p->pid;
+	task->cpu = scx_bpf_task_cpu(p);
+	task->exited = 1;
+	bpf_ringbuf_submit(task, 0);
+
+	/*
+	 * Exit notifications are consumed from the same ring buffer as the
+	 * queued tasks, account them in the same counter.
+	 */
+	__sync_fetch_and_add(&sctx->nr_queued, 1);
+}
+
 /*
  * Heartbeat scheduler timer callback.
  *
@@ -1004,17 +1216,30 @@ void BPF_STRUCT_OPS(rustland_enable, struct task_struct *p)
  */
 static int usersched_timer_fn(void *map, int *key, struct bpf_timer *timer)
 {
+	u64 now = scx_bpf_now();
+	struct shard_ctx *sctx;
 	struct task_struct *p;
+	u32 shard, pid;
 	int err = 0;
 
 	/*
-	 * Trigger the user-space scheduler if it has been inactive for
-	 * more than USERSCHED_TIMER_NS.
+	 * Trigger the user-space thread of each shard if it has been
+	 * inactive for more than USERSCHED_TIMER_NS.
 	 */
-	if (time_delta(scx_bpf_now(), usersched_last_run_at) >= USERSCHED_TIMER_NS) {
-		p = bpf_task_from_pid(usersched_pid);
+	bpf_for(shard, 0, nr_shards) {
+		sctx = lookup_shard_ctx(shard);
+		if (!sctx)
+			break;
+		if (time_delta(now, sctx->last_run_at) < USERSCHED_TIMER_NS)
+			continue;
+
+		pid = shard ? sctx->tid : usersched_pid;
+		if (!pid)
+			continue;
+
+		p = bpf_task_from_pid(pid);
 		if (p) {
-			set_usersched_needed();
+			set_usersched_needed(sctx);
 			scx_bpf_kick_cpu(scx_bpf_task_cpu(p), SCX_KICK_IDLE);
 			bpf_task_release(p);
 		}
@@ -1078,6 +1303,7 @@ static s32 get_nr_online_cpus(void)
 static int dsq_init(void)
 {
 	int err;
+	u32 shard;
 	s32 cpu;
 
 	/* Initialize amount of online CPUs */
@@ -1100,11 +1326,14 @@ static int dsq_init(void)
 		return err;
 	}
 
-	/* Create the scheduler's DSQ */
-	err = scx_bpf_create_dsq(SCHED_DSQ, -1);
-	if (err) {
-		scx_bpf_error("failed to create scheduler DSQ: %d", err);
-		return err;
+	/* Create the scheduler's DSQs (one per shard) */
+	bpf_for(shard, 0, nr_shards) {
+		err = scx_bpf_create_dsq(shard_to_sched_dsq(shard), -1);
+		if (err) {
+			scx_bpf_error("failed to create scheduler DSQ %u: %d",
+				      shard, err);
+			return err;
+		}
 	}
 
 	return 0;
@@ -1139,6 +1368,11 @@ s32 BPF_STRUCT_OPS_SLEEPABLE(rustland_init)
 	/* Compile-time checks */
 	BUILD_BUG_ON((MAX_CPUS % 2));
 
+	if (!nr_shards || nr_shards > MAX_SHARDS) {
+		scx_bpf_error("invalid number of shards: %u", nr_shards);
+		return -EINVAL;
+	}
+
 	/* Initialize maximum possible CPU number */
 	nr_cpu_ids = scx_bpf_nr_cpu_ids();
 
@@ -1173,6 +1407,7 @@ SCX_OPS_DEFINE(rustland,
 	       .stopping		= (void *)rustland_stopping,
 	       .enable			= (void *)rustland_enable,
 	       .init_task		= (void *)rustland_init_task,
+	       .exit_task		= (void *)rustland_exit_task,
 	       .init			= (void *)rustland_init,
 	       .exit			= (void *)rustland_exit,
 	       .timeout_ms		= 5000,
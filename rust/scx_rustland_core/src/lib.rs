@@ -3,5 +3,19 @@ pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 mod alloc;
 pub use alloc::ALLOCATOR;
 
+mod policy;
+pub use policy::DispatchedTask;
+pub use policy::FifoPolicy;
+pub use policy::QueuedTask;
+pub use policy::Runner;
+pub use policy::RunnerStats;
+pub use policy::SchedBackend;
+pub use policy::SchedPolicy;
+pub use policy::RL_CPU_ANY;
+
+mod shard;
+pub use shard::cpu_shard_map;
+pub use shard::shard_cpus;
+
 mod rustland_builder;
 pub use rustland_builder::RustLandBuilder;
@@ -0,0 +1,526 @@
+// Copyright (c) Andrea Righi <andrea.righi@linux.dev>
+
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+use std::collections::VecDeque;
+use std::ffi::c_char;
+use std::ffi::CStr;
+
+use anyhow::Result;
+
+/// Allow to dispatch the task on any CPU.
+///
+/// The task will be dispatched to the global shared DSQ and it will run on the first CPU
+/// available (must match RL_CPU_ANY in the BPF interface).
+pub const RL_CPU_ANY: i32 = 1 << 20;
+
+const TASK_COMM_LEN: usize = 16;
+
+// Task queued for scheduling from the BPF component (see bpf_intf::queued_task_ctx).
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Clone)]
+pub struct QueuedTask {
+    pub pid: i32,             // pid that uniquely identifies a task
+    pub cpu: i32,             // CPU previously used by the task
+    pub nr_cpus_allowed: u64, // Number of CPUs that the task can use
+    pub flags: u64,           // task's enqueue flags
+    pub start_ts: u64,        // Timestamp since last time the task ran on a CPU (in ns)
+    pub stop_ts: u64,         // Timestamp since last time the task released a CPU (in ns)
+    pub exec_runtime: u64,    // Total cpu time since last sleep (in ns)
+    pub weight: u64,          // Task priority in the range [1..10000] (default is 100)
+    pub vtime: u64,           // Current task vruntime / deadline (set by the scheduler)
+    pub enq_cnt: u64,
+    pub comm: [c_char; TASK_COMM_LEN], // Task's executable name
+}
+
+impl QueuedTask {
+    /// Convert the task's comm field (C char array) into a Rust String.
+    pub fn comm_str(&self) -> String {
+        // Convert the C char array into a Rust String
+        let c_str = unsafe { CStr::from_ptr(self.comm.as_ptr()) };
+
+        // Handle potential invalid UTF-8
+        c_str.to_string_lossy().into_owned()
+    }
+}
+
+// Task queued for dispatching to the BPF component (see bpf_intf::dispatched_task_ctx).
+#[derive(Debug, PartialEq, Eq, PartialOrd, Clone)]
+pub struct DispatchedTask {
+    pub pid: i32,      // pid that uniquely identifies a task
+    pub cpu: i32, // target CPU selected by the scheduler (RL_CPU_ANY = dispatch on the first CPU available)
+    pub flags: u64, // task's enqueue flags
+    pub slice_ns: u64, // time slice in nanoseconds assigned to the task (0 = use default time slice)
+    pub vtime: u64, // this value can be used to send the task's vruntime or deadline directly to the underlying BPF dispatcher
+    pub enq_cnt: u64,
+}
+
+impl DispatchedTask {
+    // Create a DispatchedTask from a QueuedTask.
+    //
+    // A dispatched task should be always originated from a QueuedTask (there is no reason to
+    // dispatch a task if it wasn't queued to the scheduler earlier).
+    pub fn new(task: &QueuedTask) -> Self {
+        DispatchedTask {
+            pid: task.pid,
+            cpu: task.cpu,
+            flags: task.flags,
+            slice_ns: 0, // use default time slice
+            vtime: 0,
+            enq_cnt: task.enq_cnt,
+        }
+    }
+}
+
+/// Scheduling policy driven by a [`Runner`].
+///
+/// A policy only decides the order and the placement of the tasks, the runner takes care of
+/// exchanging tasks with the BPF component, so policies can be unit-tested without BPF, using a
+/// fake [`SchedBackend`].
+pub trait SchedPolicy {
+    /// Task @task wants to run.
+    fn on_enqueue(&mut self, task: QueuedTask);
+
+    /// Return the next task to dispatch, or None if there is nothing to dispatch.
+    fn pick_next(&mut self) -> Option<DispatchedTask>;
+
+    /// An idle @cpu has been found for @task: return the CPU where the task should be dispatched
+    /// (RL_CPU_ANY to run on the first CPU available).
+    ///
+    /// If no idle CPU is found the task is dispatched using RL_CPU_ANY.
+    fn on_cpu_idle(&mut self, _task: &DispatchedTask, cpu: i32) -> i32 {
+        cpu
+    }
+
+    /// Task @pid exited: drop any state associated with it.
+    fn on_task_exit(&mut self, _pid: i32) {}
+
+    /// Amount of tasks still held by the policy.
+    fn nr_pending(&self) -> u64;
+}
+
+/// Connector used by a [`Runner`] to exchange tasks with the BPF component.
+///
+/// Implemented by BpfScheduler and BpfShard.
+pub trait SchedBackend {
+    /// Receive a task to be scheduled, or None if there are no more queued tasks.
+    fn dequeue_task(&mut self) -> Result<Option<QueuedTask>, i32>;
+
+    /// Send a task to the dispatcher.
+    fn dispatch_task(&mut self, task: &DispatchedTask) -> Result<()>;
+
+    /// Pick an idle CPU for the target PID, returns a negative value if no idle CPU is found.
+    fn select_cpu(&mut self, pid: i32, cpu: i32, flags: u64) -> i32;
+
+    /// Notify the BPF component that the scheduling cycle has been completed.
+    fn notify_complete(&mut self, nr_pending: u64);
+
+    /// Return the PIDs of the tasks that exited since the last call.
+    ///
+    /// Exit notifications are received together with the queued tasks: the PIDs returned after a
+    /// dequeue_task() call refer to tasks that exited before the task returned by that call was
+    /// queued (a PID can be reused by a new task right after the exit).
+    fn take_exited_tasks(&mut self) -> Vec<i32> {
+        vec![]
+    }
+
+    /// Return true if the scheduler needs to stop.
+    fn exited(&mut self) -> bool;
+}
+
+/// Statistics collected by a [`Runner`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RunnerStats {
+    pub nr_cycles: u64,          // Completed scheduling cycles
+    pub nr_enqueued: u64,        // Tasks received from the BPF component
+    pub nr_dispatched: u64,      // Tasks sent to the BPF component
+    pub nr_idle_dispatches: u64, // Tasks dispatched to an idle CPU
+    pub nr_dispatch_errors: u64, // Failed dispatches (retried in the next cycle)
+    pub nr_dequeue_errors: u64,  // Errors receiving tasks from the BPF component
+    pub nr_exited: u64,          // Task exit notifications
+    pub nr_restarts: u64,        // Scheduler restarts
+}
+
+/// Main scheduling loop around a [`SchedPolicy`].
+pub struct Runner<P: SchedPolicy> {
+    policy: P,
+    stats: RunnerStats,
+    retry: Option<DispatchedTask>, // Task that failed to dispatch in the previous cycle
+}
+
+impl<P: SchedPolicy> Runner<P> {
+    pub fn new(policy: P) -> Self {
+        Self {
+            policy,
+            stats: RunnerStats::default(),
+            retry: None,
+        }
+    }
+
+    pub fn policy(&self) -> &P {
+        &self.policy
+    }
+
+    pub fn policy_mut(&mut self) -> &mut P {
+        &mut self.policy
+    }
+
+    pub fn stats(&self) -> &RunnerStats {
+        &self.stats
+    }
+
+    pub fn into_policy(self) -> P {
+        self.policy
+    }
+
+    // Amount of tasks still pending (held by the policy or waiting for a retry).
+    fn nr_pending(&self) -> u64 {
+        self.policy.nr_pending() + self.retry.is_some() as u64
+    }
+
+    // Send @task to the dispatcher, return false if dispatching failed.
+    fn dispatch<B: SchedBackend>(&mut self, backend: &mut B, task: DispatchedTask) -> bool {
+        if backend.dispatch_task(&task).is_err() {
+            // Keep the task and retry in the next scheduling cycle.
+            self.stats.nr_dispatch_errors += 1;
+            self.retry = Some(task);
+            return false;
+        }
+        self.stats.nr_dispatched += 1;
+
+        true
+    }
+
+    // Drop the tasks that exited since the last call.
+    fn drop_exited_tasks<B: SchedBackend>(&mut self, backend: &mut B) {
+        for pid in backend.take_exited_tasks() {
+            self.stats.nr_exited += 1;
+            if self.retry.as_ref().is_some_and(|task| task.pid == pid) {
+                self.retry = None;
+            }
+            self.policy.on_task_exit(pid);
+        }
+    }
+
+    /// Run a single scheduling cycle: receive all the queued tasks, dispatch the tasks selected
+    /// by the policy and notify the BPF component.
+    pub fn run_once<B: SchedBackend>(&mut self, backend: &mut B) {
+        // Drain all the tasks from the queued list.
+        loop {
+            let ret = backend.dequeue_task();
+
+            // Drop the tasks that exited before the dequeued task was queued, so that a task
+            // reusing the PID of an exited task is not dropped.
+            self.drop_exited_tasks(backend);
+
+            match ret {
+                Ok(Some(task)) => {
+                    self.stats.nr_enqueued += 1;
+                    self.policy.on_enqueue(task);
+                }
+                Ok(None) => break,
+                Err(_) => {
+                    self.stats.nr_dequeue_errors += 1;
+                    break;
+                }
+            }
+        }
+
+        // Retry the task that failed to dispatch in the previous cycle first.
+        let mut dispatching = match self.retry.take() {
+            Some(task) => self.dispatch(backend, task),
+            None => true,
+        };
+
+        // Dispatch the tasks selected by the policy, until there are no more tasks or the
+        // dispatcher is congested.
+        while dispatching {
+            let Some(mut task) = self.policy.pick_next() else {
+                break;
+            };
+            let cpu = backend.select_cpu(task.pid, task.cpu, task.flags);
+            task.cpu = if cpu >= 0 {
+                self.stats.nr_idle_dispatches += 1;
+                self.policy.on_cpu_idle(&task, cpu)
+            } else {
+                RL_CPU_ANY
+            };
+            dispatching = self.dispatch(backend, task);
+        }
+
+        // Notify the dispatcher if there are still pending tasks to be processed.
+        self.stats.nr_cycles += 1;
+        backend.notify_complete(self.nr_pending());
+    }
+
+    /// Run scheduling cycles until the backend exits.
+    pub fn run<B: SchedBackend>(&mut self, backend: &mut B) {
+        while !backend.exited() {
+            self.run_once(backend);
+        }
+    }
+
+    /// Prepare the runner for a scheduler restart, dropping all the pending tasks (they will be
+    /// queued again by the new scheduler instance).
+    pub fn reset(&mut self) {
+        self.retry = None;
+        while self.policy.pick_next().is_some() {}
+        self.stats.nr_restarts += 1;
+    }
+}
+
+/// First-in first-out policy: tasks are dispatched in the same order they are received.
+#[derive(Debug, Default)]
+pub struct FifoPolicy {
+    slice_ns: u64,
+    queue: VecDeque<QueuedTask>,
+}
+
+impl FifoPolicy {
+    /// Create a FIFO policy that assigns @slice_ns to each task (0 = default time slice).
+    pub fn new(slice_ns: u64) -> Self {
+        Self {
+            slice_ns,
+            queue: VecDeque::new(),
+        }
+    }
+}
+
+impl SchedPolicy for FifoPolicy {
+    fn on_enqueue(&mut self, task: QueuedTask) {
+        self.queue.push_back(task);
+    }
+
+    fn pick_next(&mut self) -> Option<DispatchedTask> {
+        let task = self.queue.pop_front()?;
+        let mut dispatched_task = DispatchedTask::new(&task);
+        dispatched_task.slice_ns = self.slice_ns;
+
+        Some(dispatched_task)
+    }
+
+    fn on_task_exit(&mut self, pid: i32) {
+        self.queue.retain(|task| task.pid != pid);
+    }
+
+    fn nr_pending(&self) -> u64 {
+        self.queue.len() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Message in the queued ring buffer.
+    enum Msg {
+        Task(QueuedTask),
+        Exit(i32),
+    }
+
+    #[derive(Default)]
+    struct FakeBackend {
+        queued: VecDeque<Msg>,
+        dispatched: Vec<DispatchedTask>,
+        exited: Vec<i32>,
+        idle_cpus: Vec<i32>,
+        ring_size: usize,
+        nr_pending: u64,
+    }
+
+    impl SchedBackend for FakeBackend {
+        fn dequeue_task(&mut self) -> Result<Option<QueuedTask>, i32> {
+            while let Some(msg) = self.queued.pop_front() {
+                match msg {
+                    Msg::Task(task) => return Ok(Some(task)),
+                    Msg::Exit(pid) => self.exited.push(pid),
+                }
+            }
+            Ok(None)
+        }
+
+        fn dispatch_task(&mut self, task: &DispatchedTask) -> Result<()> {
+            if self.dispatched.len() >= self.ring_size {
+                anyhow::bail!("ring buffer full");
+            }
+            self.dispatched.push(task.clone());
+            Ok(())
+        }
+
+        fn select_cpu(&mut self, _pid: i32, _cpu: i32, _flags: u64) -> i32 {
+            self.idle_cpus.pop().unwrap_or(-1)
+        }
+
+        fn notify_complete(&mut self, nr_pending: u64) {
+            self.nr_pending = nr_pending;
+        }
+
+        fn take_exited_tasks(&mut self) -> Vec<i32> {
+            std::mem::take(&mut self.exited)
+        }
+
+        fn exited(&mut self) -> bool {
+            self.queued.is_empty()
+        }
+    }
+
+    fn task(pid: i32, cpu: i32) -> QueuedTask {
+        QueuedTask {
+            pid,
+            cpu,
+            ..Default::default()
+        }
+    }
+
+    fn backend(pids: &[i32]) -> FakeBackend {
+        FakeBackend {
+            queued: pids.iter().map(|&pid| Msg::Task(task(pid, 0))).collect(),
+            ring_size: usize::MAX,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_fifo_order() {
+        let mut backend = backend(&[1, 2, 3]);
+        let mut runner = Runner::new(FifoPolicy::new(1000));
+
+        runner.run_once(&mut backend);
+
+        let pids: Vec<i32> = backend.dispatched.iter().map(|t| t.pid).collect();
+        assert_eq!(pids, vec![1, 2, 3]);
+        assert!(backend.dispatched.iter().all(|t| t.slice_ns == 1000));
+        assert!(backend.dispatched.iter().all(|t| t.cpu == RL_CPU_ANY));
+        assert_eq!(backend.nr_pending, 0);
+        assert_eq!(runner.stats().nr_enqueued, 3);
+        assert_eq!(runner.stats().nr_dispatched, 3);
+        assert_eq!(runner.stats().nr_cycles, 1);
+    }
+
+    #[test]
+    fn test_idle_cpu() {
+        let mut backend = backend(&[1, 2]);
+        backend.idle_cpus = vec![3];
+        let mut runner = Runner::new(FifoPolicy::new(0));
+        runner.run_once(&mut backend);
+
+        // The first task gets the idle CPU, the second one any CPU.
+        assert_eq!(backend.dispatched[0].cpu, 3);
+        assert_eq!(backend.dispatched[1].cpu, RL_CPU_ANY);
+        assert_eq!(runner.stats().nr_idle_dispatches, 1);
+    }
+
+    #[test]
+    fn test_idle_cpu_override() {
+        // Policy that always keeps the tasks on their previously used CPU.
+        struct Sticky(FifoPolicy);
+
+        impl SchedPolicy for Sticky {
+            fn on_enqueue(&mut self, task: QueuedTask) {
+                self.0.on_enqueue(task);
+            }
+
+            fn pick_next(&mut self) -> Option<DispatchedTask> {
+                self.0.pick_next()
+            }
+
+            fn on_cpu_idle(&mut self, task: &DispatchedTask, _cpu: i32) -> i32 {
+                task.cpu
+            }
+
+            fn nr_pending(&self) -> u64 {
+                self.0.nr_pending()
+            }
+        }
+
+        let mut backend = backend(&[1]);
+        backend.idle_cpus = vec![3];
+        let mut runner = Runner::new(Sticky(FifoPolicy::new(0)));
+        runner.run_once(&mut backend);
+
+        assert_eq!(backend.dispatched[0].cpu, 0);
+    }
+
+    #[test]
+    fn test_dispatch_retry() {
+        let mut backend = backend(&[1, 2, 3]);
+        backend.ring_size = 1;
+        let mut runner = Runner::new(FifoPolicy::new(0));
+
+        // Only one task fits in the ring buffer, the second one is kept for a retry.
+        runner.run_once(&mut backend);
+        assert_eq!(backend.dispatched.len(), 1);
+        assert_eq!(backend.nr_pending, 2);
+        assert_eq!(runner.stats().nr_dispatch_errors, 1);
+
+        // Drain the ring buffer: the failed task is dispatched first.
+        backend.dispatched.clear();
+        backend.ring_size = usize::MAX;
+        runner.run_once(&mut backend);
+        let pids: Vec<i32> = backend.dispatched.iter().map(|t| t.pid).collect();
+        assert_eq!(pids, vec![2, 3]);
+        assert_eq!(backend.nr_pending, 0);
+    }
+
+    #[test]
+    fn test_task_exit() {
+        let mut backend = backend(&[1, 2, 3]);
+        backend.ring_size = 0;
+        let mut runner = Runner::new(FifoPolicy::new(0));
+
+        runner.run_once(&mut backend);
+        assert_eq!(backend.nr_pending, 3);
+
+        // Task 1 is waiting for a retry, task 2 is still held by the policy.
+        backend.exited = vec![1, 2];
+        backend.ring_size = usize::MAX;
+        runner.run_once(&mut backend);
+        let pids: Vec<i32> = backend.dispatched.iter().map(|t| t.pid).collect();
+        assert_eq!(pids, vec![3]);
+        assert_eq!(runner.stats().nr_exited, 2);
+    }
+
+    #[test]
+    fn test_task_exit_order() {
+        let mut backend = backend(&[]);
+        let mut runner = Runner::new(FifoPolicy::new(0));
+
+        // Task 1 exits and its PID is reused by a new task in the same cycle, task 2 exits after
+        // being queued.
+        backend.queued = VecDeque::from([
+            Msg::Task(task(1, 0)),
+            Msg::Task(task(2, 0)),
+            Msg::Exit(1),
+            Msg::Task(QueuedTask {
+                enq_cnt: 1,
+                ..task(1, 0)
+            }),
+            Msg::Exit(2),
+        ]);
+        runner.run_once(&mut backend);
+        let tasks: Vec<(i32, u64)> = backend
+            .dispatched
+            .iter()
+            .map(|t| (t.pid, t.enq_cnt))
+            .collect();
+        assert_eq!(tasks, vec![(1, 1)]);
+        assert_eq!(runner.stats().nr_enqueued, 3);
+        assert_eq!(runner.stats().nr_exited, 2);
+        assert_eq!(backend.nr_pending, 0);
+    }
+
+    #[test]
+    fn test_run_and_reset() {
+        let mut backend = backend(&[1, 2]);
+        backend.ring_size = 1;
+        let mut runner = Runner::new(FifoPolicy::new(0));
+
+        runner.run(&mut backend);
+        assert_eq!(runner.stats().nr_cycles, 1);
+        assert_eq!(backend.nr_pending, 1);
+
+        runner.reset();
+        assert_eq!(runner.policy().nr_pending(), 0);
+        assert_eq!(runner.stats().nr_restarts, 1);
+    }
+}
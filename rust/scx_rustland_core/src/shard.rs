@@ -0,0 +1,84 @@
+// Copyright (c) Andrea Righi <andrea.righi@linux.dev>
+
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+/// Partition the CPUs into @nr_shards shards.
+///
+/// @units are groups of CPUs that must be kept in the same shard (e.g., the CPUs of the same LLC
+/// or of the same core). Consecutive units are assigned to the same shard, spreading them as
+/// evenly as possible. Shards are left empty if there are less units than shards.
+pub fn shard_cpus(units: Vec<Vec<usize>>, nr_shards: usize) -> Vec<Vec<usize>> {
+    let mut shards = vec![vec![]; nr_shards];
+    let nr_units = units.len();
+    for (i, cpus) in units.into_iter().enumerate() {
+        shards[i * nr_shards / nr_units].extend(cpus);
+    }
+    shards
+}
+
+/// Map each CPU to the shard that owns it, i.e., the shard whose ring buffers are used for the
+/// tasks running on the CPU (see cpu_shard[] in the BPF component).
+///
+/// CPUs that are not owned by any shard, or that are not below @nr_cpus, are assigned to shard 0.
+pub fn cpu_shard_map(shards: &[Vec<usize>], nr_cpus: usize) -> Vec<u32> {
+    let mut map = vec![0; nr_cpus];
+    for (shard, cpus) in shards.iter().enumerate() {
+        for &cpu in cpus {
+            if cpu < nr_cpus {
+                map[cpu] = shard as u32;
+            }
+        }
+    }
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shard_cpus() {
+        // 4 LLCs with 4 CPUs each.
+        let llcs: Vec<Vec<usize>> = (0..4).map(|i| (i * 4..(i + 1) * 4).collect()).collect();
+
+        assert_eq!(
+            shard_cpus(llcs.clone(), 1),
+            vec![(0..16).collect::<Vec<_>>()]
+        );
+        assert_eq!(
+            shard_cpus(llcs.clone(), 2),
+            vec![(0..8).collect::<Vec<_>>(), (8..16).collect()]
+        );
+        assert_eq!(shard_cpus(llcs.clone(), 4), llcs);
+
+        // Uneven split: LLCs are never broken up.
+        let shards = shard_cpus(llcs.clone(), 3);
+        assert_eq!(
+            shards,
+            vec![
+                (0..8).collect::<Vec<_>>(),
+                (8..12).collect(),
+                (12..16).collect()
+            ]
+        );
+
+        // Not enough units, the units are spread out and shards 2 and 5 are empty.
+        let shards = shard_cpus(llcs, 6);
+        assert_eq!(shards.len(), 6);
+        let empty: Vec<usize> = (0..6).filter(|&i| shards[i].is_empty()).collect();
+        assert_eq!(empty, [2, 5]);
+        assert_eq!(shards.concat(), (0..16).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_cpu_shard_map() {
+        // Non-contiguous CPU IDs, e.g., SMT siblings enumerated last.
+        let shards = vec![vec![0, 1, 4, 5], vec![2, 3, 6, 7]];
+        assert_eq!(cpu_shard_map(&shards, 8), vec![0, 0, 1, 1, 0, 0, 1, 1]);
+
+        // CPUs beyond @nr_cpus are ignored, missing CPUs default to shard 0.
+        let shards = vec![vec![0], vec![1, 9]];
+        assert_eq!(cpu_shard_map(&shards, 4), vec![0, 1, 0, 0]);
+    }
+}
@@ -3,6 +3,7 @@ use scx_stats_derive::Stats;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::env::args;
+use std::time::Duration;
 
 // Hacky definition sharing. See stats_def.rs.h.
 include!("stats_defs.rs.h");
@@ -17,7 +18,7 @@ fn main() {
     std::assert_eq!(args().len(), 2, "Usage: client UNIX_SOCKET_PATH");
     let path = args().nth(1).unwrap();
 
-    let mut client = StatsClient::new().set_path(path).connect(None).unwrap();
+    let mut client = StatsClient::new().set_path(&path).connect(None).unwrap();
 
     println!("===== Requesting \"stats_meta\":");
     let resp = client.request::<BTreeMap<String, StatsMeta>>("stats_meta", vec![]);
@@ -45,4 +46,42 @@ fn main() {
         .request::<serde_json::Value>("stats_meta", vec![])
         .unwrap();
     println!("{}", serde_json::to_string_pretty(&resp).unwrap());
+
+    println!("\n===== Listing tunables:");
+    let resp = client.tunables();
+    println!("{:#?}", resp);
+
+    println!("\n===== Setting \"slice_us\" to 5000 and then to out-of-range 1:");
+    let resp = client.set_tunable("slice_us", "5000");
+    println!("{:#?}", resp);
+    let resp = client.set_tunable("slice_us", "1");
+    println!("{:#?}", resp);
+
+    println!("\n===== Requesting the last 3 samples of \"top\" history:");
+    let resp = client.history::<ClusterStats>("top", Some(3));
+    println!("{:#?}", resp);
+
+    if let Ok(samples) = resp {
+        if samples.len() >= 2 {
+            let (prev, cur) = (&samples[samples.len() - 2], &samples[samples.len() - 1]);
+            let dur = Duration::from_millis(cur.at_ms - prev.at_ms);
+            println!("\n===== Domain 0 delta and rate between the last two samples:");
+            let (dp, dc) = (&prev.data.doms_dict[&0], &cur.data.doms_dict[&0]);
+            println!("{:#?}", dc.delta(dp));
+            println!("{:#?}", dc.rate(dp, dur));
+        }
+    }
+
+    println!("\n===== Subscribing to \"top\" every 500ms and receiving 3 samples:");
+    let mut client = StatsClient::new().set_path(&path).connect(None).unwrap();
+    client
+        .subscribe(
+            Duration::from_millis(500),
+            vec![("agg".into(), "max".into())],
+        )
+        .unwrap();
+    for _ in 0..3 {
+        let resp = client.recv::<ClusterStats>();
+        println!("{:#?}", resp);
+    }
 }
@@ -0,0 +1,39 @@
+use scx_stats::prelude::*;
+use std::env::args;
+use std::thread::sleep;
+use std::time::Duration;
+
+fn main() {
+    simple_logger::SimpleLogger::new()
+        .with_level(log::LevelFilter::Info)
+        .env()
+        .init()
+        .unwrap();
+
+    let args: Vec<String> = args().collect();
+    std::assert!(
+        args.len() == 2 || args.len() == 4,
+        "Usage: openmetrics UNIX_SOCKET_PATH [http ADDR | textfile DIR]"
+    );
+
+    let mut exporter = OpenMetricsExporter::new(&args[1]);
+
+    if args.len() == 2 {
+        print!("{}", exporter.collect().unwrap());
+        return;
+    }
+
+    match args[2].as_str() {
+        "http" => {
+            println!("Serving http://{}/metrics", &args[3]);
+            exporter.serve_http(&args[3]).unwrap();
+        }
+        "textfile" => loop {
+            if let Err(e) = exporter.write_textfile(&args[3], "scx_stats.prom") {
+                log::warn!("{e:?}, retrying...");
+            }
+            sleep(Duration::from_secs(2));
+        },
+        mode => panic!("unknown mode {mode:?}"),
+    }
+}
@@ -6,6 +6,7 @@ use std::collections::BTreeMap;
 use std::env::args;
 use std::io::Read;
 use std::thread::{current, spawn, ThreadId};
+use std::time::Duration;
 
 // Hacky definition sharing. See stats_def.rs.h.
 include!("stats_defs.rs.h");
@@ -60,6 +61,17 @@ fn main() {
                 debug!("Received {:?}", res);
                 stats.to_json()
             }),
+        )
+        .add_history("top", Duration::from_secs(1), 60)
+        .add_tunable(
+            "slice_us",
+            StatsTunableMeta::new(StatsKind::U64, 20000.into())
+                .desc("an example tunable")
+                .range(1000.0, 100000.0),
+            Box::new(|val, _chan| {
+                info!("slice_us set to {}", val);
+                Ok(())
+            }),
         );
 
     info!("stats_meta:");
@@ -6,9 +6,9 @@
 #[stat(desc = "domain statistics", _om_prefix="d_", _om_label="domain_name")]
 struct DomainStats {
     pub name: String,
-    #[stat(desc = "an event counter")]
+    #[stat(desc = "an event counter", _counter)]
     pub events: u64,
-    #[stat(desc = "a gauge number")]
+    #[stat(desc = "a gauge number", _gauge)]
     pub pressure: f64,
 }
 
@@ -45,6 +45,60 @@ pub fn stat(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     };
     output.extend(trait_body);
 
+    // Fields marked with _counter or _gauge opt the struct into StatsDelta.
+    let is_counter = |f: &scx_stats::StatsField| f.attrs.user.contains_key("_counter");
+    let is_gauge = |f: &scx_stats::StatsField| f.attrs.user.contains_key("_gauge");
+
+    if meta.fields.values().any(|f| is_counter(f) || is_gauge(f)) {
+        let mut delta_fields = vec![];
+        let mut rate_fields = vec![];
+
+        for (fname, field) in meta.fields.iter() {
+            let fident = format_ident!("{}", fname);
+            if is_counter(field) && is_gauge(field) {
+                return syn::Error::new(
+                    ident.span(),
+                    format!("scx_stats: {fname} can't be both _counter and _gauge"),
+                )
+                .to_compile_error()
+                .into();
+            }
+
+            if is_counter(field) {
+                delta_fields.push(quote! {
+                    #fident: scx_stats::StatsDelta::delta(&self.#fident, &prev.#fident)
+                });
+                rate_fields.push(quote! {
+                    (#fname.to_string(), scx_stats::StatsDelta::rate(&self.#fident, &prev.#fident, dur))
+                });
+            } else {
+                delta_fields.push(quote! {
+                    #fident: self.#fident.clone()
+                });
+                rate_fields.push(quote! {
+                    (#fname.to_string(), scx_stats::serde_json::to_value(&self.#fident)
+                        .unwrap_or(scx_stats::serde_json::Value::Null))
+                });
+            }
+        }
+
+        let delta_body = quote! {
+        #[rustfmt::skip]
+        impl scx_stats::StatsDelta for #ident {
+            fn delta(&self, prev: &Self) -> Self {
+                Self {
+                    #(#delta_fields),*
+                }
+            }
+
+            fn rate(&self, prev: &Self, dur: std::time::Duration) -> scx_stats::serde_json::Value {
+                scx_stats::serde_json::Value::Object([#(#rate_fields),*].into_iter().collect())
+            }
+        }
+        };
+        output.extend(delta_body);
+    }
+
     output.into()
 }
 
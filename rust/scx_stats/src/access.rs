@@ -0,0 +1,187 @@
+use crate::StatsErrno;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixStream;
+
+/// Who may access a stats target, op or tunable. Root is always allowed.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StatsAccess {
+    #[serde(rename = "any")]
+    Any,
+    #[serde(rename = "group")]
+    Group(u32),
+    #[serde(rename = "root")]
+    Root,
+}
+
+impl std::fmt::Display for StatsAccess {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Any => write!(f, "any"),
+            Self::Group(gid) => write!(f, "group {gid}"),
+            Self::Root => write!(f, "root"),
+        }
+    }
+}
+
+/// Credentials of the process on the other side of a stats connection as
+/// reported by SO_PEERCRED and SO_PEERGROUPS. Both are captured by the
+/// kernel when the connection is established, so they can't be swapped by
+/// the peer exiting and its PID being reused.
+#[derive(Clone, Debug)]
+pub struct StatsPeer {
+    pub pid: i32,
+    pub uid: u32,
+    pub gid: u32,
+    pub groups: Vec<u32>,
+}
+
+impl StatsPeer {
+    pub fn from_stream(stream: &UnixStream) -> Result<Self> {
+        let mut cred = libc::ucred {
+            pid: 0,
+            uid: 0,
+            gid: 0,
+        };
+        let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+
+        let ret = unsafe {
+            libc::getsockopt(
+                stream.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_PEERCRED,
+                &mut cred as *mut libc::ucred as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        if ret < 0 {
+            return Err(anyhow!(
+                "getsockopt(SO_PEERCRED) failed ({})",
+                std::io::Error::last_os_error()
+            ));
+        }
+
+        Ok(Self {
+            pid: cred.pid,
+            uid: cred.uid,
+            gid: cred.gid,
+            groups: Self::peer_groups(stream)?,
+        })
+    }
+
+    /// Read the supplementary groups of the peer with SO_PEERGROUPS. Kernels
+    /// older than v4.13 don't support it, in which case only the primary
+    /// group from SO_PEERCRED is considered.
+    fn peer_groups(stream: &UnixStream) -> Result<Vec<u32>> {
+        let mut groups: Vec<libc::gid_t> = vec![0; 64];
+        loop {
+            let mut len = std::mem::size_of_val(groups.as_slice()) as libc::socklen_t;
+            let ret = unsafe {
+                libc::getsockopt(
+                    stream.as_raw_fd(),
+                    libc::SOL_SOCKET,
+                    libc::SO_PEERGROUPS,
+                    groups.as_mut_ptr() as *mut libc::c_void,
+                    &mut len,
+                )
+            };
+            if ret == 0 {
+                groups.truncate(len as usize / std::mem::size_of::<libc::gid_t>());
+                return Ok(groups);
+            }
+
+            let err = std::io::Error::last_os_error();
+            match err.raw_os_error() {
+                // @len has been updated to the required size.
+                Some(libc::ERANGE) => {
+                    groups.resize(len as usize / std::mem::size_of::<libc::gid_t>(), 0)
+                }
+                Some(libc::ENOPROTOOPT) => return Ok(vec![]),
+                _ => return Err(anyhow!("getsockopt(SO_PEERGROUPS) failed ({})", err)),
+            }
+        }
+    }
+
+    pub fn allowed(&self, access: &StatsAccess) -> bool {
+        if self.uid == 0 {
+            return true;
+        }
+        match access {
+            StatsAccess::Any => true,
+            StatsAccess::Group(gid) => self.gid == *gid || self.groups.contains(gid),
+            StatsAccess::Root => false,
+        }
+    }
+
+    /// Fail with `StatsErrno(EACCES)` if the peer isn't allowed @access to
+    /// @what.
+    pub fn check(&self, access: &StatsAccess, what: &str) -> Result<()> {
+        if self.allowed(access) {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "{} requires {} access, denied for uid {} (pid {})",
+                what,
+                access,
+                self.uid,
+                self.pid
+            )
+            .context(StatsErrno(libc::EACCES)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer(uid: u32, gid: u32, groups: &[u32]) -> StatsPeer {
+        StatsPeer {
+            pid: 1234,
+            uid,
+            gid,
+            groups: groups.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_allowed() {
+        let root = peer(0, 0, &[]);
+        let user = peer(1000, 1000, &[10, 20]);
+
+        for access in [StatsAccess::Any, StatsAccess::Group(30), StatsAccess::Root] {
+            assert!(root.allowed(&access), "{access}");
+        }
+
+        assert!(user.allowed(&StatsAccess::Any));
+        assert!(user.allowed(&StatsAccess::Group(1000)));
+        assert!(user.allowed(&StatsAccess::Group(20)));
+        assert!(!user.allowed(&StatsAccess::Group(30)));
+        assert!(!user.allowed(&StatsAccess::Root));
+
+        let e = user.check(&StatsAccess::Root, "tunable").unwrap_err();
+        assert_eq!(
+            e.downcast_ref::<StatsErrno>().map(|errno| errno.0),
+            Some(libc::EACCES)
+        );
+        assert!(user.check(&StatsAccess::Group(10), "tunable").is_ok());
+    }
+
+    #[test]
+    fn test_from_stream() {
+        let (a, _b) = UnixStream::pair().unwrap();
+        let peer = StatsPeer::from_stream(&a).unwrap();
+        assert_eq!(peer.pid, std::process::id() as i32);
+        assert_eq!(peer.uid, unsafe { libc::geteuid() });
+        assert_eq!(peer.gid, unsafe { libc::getegid() });
+
+        let nr = unsafe { libc::getgroups(0, std::ptr::null_mut()) };
+        let mut groups = vec![0; nr as usize];
+        unsafe { libc::getgroups(nr, groups.as_mut_ptr()) };
+        let mut peer_groups = peer.groups.clone();
+        groups.sort();
+        peer_groups.sort();
+        assert_eq!(peer_groups, groups);
+    }
+}
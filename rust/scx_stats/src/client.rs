@@ -1,11 +1,14 @@
 use crate::StatsErrno;
 use crate::StatsRequest;
 use crate::StatsResponse;
+use crate::StatsSample;
+use crate::StatsTunableMeta;
 use anyhow::anyhow;
 use anyhow::bail;
 use anyhow::Result;
 use log::trace;
 use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::io::BufRead;
 use std::io::BufReader;
 use std::io::Write;
@@ -96,6 +99,19 @@ impl StatsClient {
             }
         }
 
+        self.recv()
+    }
+
+    /// Receive the next response from the server. Used directly to read the
+    /// samples pushed after a successful `subscribe()`.
+    pub fn recv<T>(&mut self) -> Result<T>
+    where
+        T: for<'a> Deserialize<'a>,
+    {
+        if self.reader.is_none() {
+            bail!("not connected");
+        }
+
         let mut line = String::new();
         match self.reader.as_mut().unwrap().read_line(&mut line) {
             Ok(0) => return Err(anyhow!("connection closed")),
@@ -130,4 +146,62 @@ impl StatsClient {
     {
         self.send_request(&StatsRequest::new(req, args))
     }
+
+    /// Retrieve up to @nr most recent samples, oldest first, of stats
+    /// target @target. The server must have been set up to keep history
+    /// for the target with `StatsServerData::add_history()`.
+    pub fn history<T>(&mut self, target: &str, nr: Option<usize>) -> Result<Vec<StatsSample<T>>>
+    where
+        T: for<'a> Deserialize<'a>,
+    {
+        let mut args = vec![("target".into(), target.to_string())];
+        if let Some(nr) = nr {
+            args.push(("nr".into(), nr.to_string()));
+        }
+        self.request("history", args)
+    }
+
+    /// List the runtime tunables along with their current values.
+    pub fn tunables(&mut self) -> Result<BTreeMap<String, StatsTunableMeta>> {
+        self.request("tunables", vec![])
+    }
+
+    /// Set runtime tunable @name to @value. The server validates @value
+    /// against the tunable's type and bounds. On success, the updated
+    /// tunable is returned.
+    pub fn set_tunable(&mut self, name: &str, value: &str) -> Result<StatsTunableMeta> {
+        self.request(
+            "set",
+            vec![
+                ("name".into(), name.to_string()),
+                ("value".into(), value.to_string()),
+            ],
+        )
+    }
+
+    /// Ask the server to push samples of the stats target every @intv. The
+    /// target and any other arguments are specified in @args as with
+    /// `request("stats", ...)`. `sample_ms` and `agg` can be added to
+    /// @args to make the server sample more frequently and aggregate the
+    /// samples within each interval. See `StatsAgg`.
+    ///
+    /// On success, the connection is dedicated to the subscription and the
+    /// samples should be read with `recv()`. If the server doesn't support
+    /// subscriptions, the returned error carries `StatsErrno(EOPNOTSUPP)`.
+    pub fn subscribe(&mut self, intv: Duration, mut args: Vec<(String, String)>) -> Result<()> {
+        args.push(("intv_ms".into(), intv.as_millis().to_string()));
+        match self.request::<serde_json::Value>("subscribe", args) {
+            Ok(_) => Ok(()),
+            // Servers predating EOPNOTSUPP reject unknown commands with EINVAL.
+            Err(e)
+                if matches!(
+                    e.downcast_ref::<StatsErrno>(),
+                    Some(StatsErrno(libc::EINVAL))
+                ) && e.root_cause().to_string().contains("unknown command") =>
+            {
+                Err(e.context(StatsErrno(libc::EOPNOTSUPP)))
+            }
+            Err(e) => Err(e),
+        }
+    }
 }
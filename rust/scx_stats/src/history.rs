@@ -0,0 +1,242 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, VecDeque};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Delta and rate computation between two snapshots of statistics.
+///
+/// `scx_stats_derive::Stats` implements this trait for structs which have
+/// at least one field marked with `_counter` or `_gauge`. Fields marked
+/// with `_counter` are subtracted while all others keep the value from
+/// `self`.
+///
+/// A counter which went backwards, e.g. because the scheduler restarted,
+/// is treated as reset and its delta is the current value.
+pub trait StatsDelta: Sized {
+    /// Return the change from @prev to @self.
+    fn delta(&self, prev: &Self) -> Self;
+
+    /// Return the rate of change per second from @prev to @self over @dur.
+    /// Values which aren't counters are reported as-is.
+    fn rate(&self, prev: &Self, dur: Duration) -> Value;
+}
+
+fn per_sec(delta: f64, dur: Duration) -> Value {
+    match dur.as_secs_f64() {
+        secs if secs > 0.0 => serde_json::json!(delta / secs),
+        _ => serde_json::json!(0.0),
+    }
+}
+
+macro_rules! impl_stats_delta_int {
+    ($($ty:ty),*) => {
+        $(
+            impl StatsDelta for $ty {
+                fn delta(&self, prev: &Self) -> Self {
+                    match self.checked_sub(*prev) {
+                        Some(v) if *self >= *prev => v,
+                        _ => *self,
+                    }
+                }
+
+                fn rate(&self, prev: &Self, dur: Duration) -> Value {
+                    per_sec(self.delta(prev) as f64, dur)
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_stats_delta_float {
+    ($($ty:ty),*) => {
+        $(
+            impl StatsDelta for $ty {
+                fn delta(&self, prev: &Self) -> Self {
+                    match self >= prev {
+                        true => self - prev,
+                        false => *self,
+                    }
+                }
+
+                fn rate(&self, prev: &Self, dur: Duration) -> Value {
+                    per_sec(self.delta(prev) as f64, dur)
+                }
+            }
+        )*
+    };
+}
+
+impl_stats_delta_int!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+impl_stats_delta_float!(f32, f64);
+
+impl<K, V> StatsDelta for BTreeMap<K, V>
+where
+    K: Ord + Clone + std::fmt::Display,
+    V: StatsDelta + Clone,
+{
+    fn delta(&self, prev: &Self) -> Self {
+        self.iter()
+            .map(|(k, v)| match prev.get(k) {
+                Some(p) => (k.clone(), v.delta(p)),
+                None => (k.clone(), v.clone()),
+            })
+            .collect()
+    }
+
+    fn rate(&self, prev: &Self, dur: Duration) -> Value {
+        Value::Object(
+            self.iter()
+                .filter_map(|(k, v)| prev.get(k).map(|p| (k.to_string(), v.rate(p, dur))))
+                .collect(),
+        )
+    }
+}
+
+impl<V> StatsDelta for Vec<V>
+where
+    V: StatsDelta + Clone,
+{
+    fn delta(&self, prev: &Self) -> Self {
+        self.iter()
+            .enumerate()
+            .map(|(i, v)| match prev.get(i) {
+                Some(p) => v.delta(p),
+                None => v.clone(),
+            })
+            .collect()
+    }
+
+    fn rate(&self, prev: &Self, dur: Duration) -> Value {
+        Value::Array(
+            self.iter()
+                .zip(prev.iter())
+                .map(|(v, p)| v.rate(p, dur))
+                .collect(),
+        )
+    }
+}
+
+/// A timestamped sample of a stats target.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StatsSample<T = Value> {
+    /// Milliseconds since the UNIX epoch when the sample was taken.
+    pub at_ms: u64,
+    pub data: T,
+}
+
+impl<T> StatsSample<T> {
+    pub fn new(data: T) -> Self {
+        let at_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        Self { at_ms, data }
+    }
+}
+
+/// Bounded ring buffer of the most recent samples of a stats target.
+#[derive(Clone, Debug)]
+pub struct StatsHistory {
+    depth: usize,
+    samples: VecDeque<StatsSample>,
+}
+
+impl StatsHistory {
+    pub fn new(depth: usize) -> Self {
+        Self {
+            depth: depth.max(1),
+            samples: VecDeque::with_capacity(depth.max(1)),
+        }
+    }
+
+    pub fn push(&mut self, sample: StatsSample) {
+        if self.samples.len() >= self.depth {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    /// Return up to @nr most recent samples, oldest first. All samples are
+    /// returned if @nr is `None`.
+    pub fn last(&self, nr: Option<usize>) -> Vec<StatsSample> {
+        let nr = nr.unwrap_or(self.samples.len()).min(self.samples.len());
+        self.samples
+            .iter()
+            .skip(self.samples.len() - nr)
+            .cloned()
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_delta() {
+        assert_eq!(150u64.delta(&100), 50);
+        assert_eq!(100u64.delta(&100), 0);
+        assert_eq!((-5i64).delta(&-20), 15);
+        assert_eq!(2.5f64.delta(&1.0), 1.5);
+
+        // Counters going backwards were reset.
+        assert_eq!(30u64.delta(&100), 30);
+        assert_eq!(0u32.delta(&u32::MAX), 0);
+        assert_eq!((-20i64).delta(&-5), -20);
+        assert_eq!(i64::MIN.delta(&i64::MAX), i64::MIN);
+        assert_eq!(0.5f64.delta(&1.0), 0.5);
+
+        assert_eq!(300u64.rate(&100, Duration::from_secs(2)), json!(100.0));
+        assert_eq!(30u64.rate(&100, Duration::from_secs(2)), json!(15.0));
+        assert_eq!(300u64.rate(&100, Duration::ZERO), json!(0.0));
+    }
+
+    #[test]
+    fn test_delta_nested() {
+        let prev = BTreeMap::from([("a", 10u64), ("b", 20)]);
+        let cur = BTreeMap::from([("a", 15u64), ("b", 5), ("c", 7)]);
+        assert_eq!(
+            cur.delta(&prev),
+            BTreeMap::from([("a", 5u64), ("b", 5), ("c", 7)])
+        );
+        assert_eq!(
+            cur.rate(&prev, Duration::from_secs(1)),
+            json!({ "a": 5.0, "b": 5.0 })
+        );
+
+        let prev = vec![1u64, 2];
+        let cur = vec![4u64, 1, 9];
+        assert_eq!(cur.delta(&prev), vec![3, 1, 9]);
+        assert_eq!(
+            cur.rate(&prev, Duration::from_millis(500)),
+            json!([6.0, 2.0])
+        );
+    }
+
+    #[test]
+    fn test_history() {
+        let mut history = StatsHistory::new(3);
+        assert!(history.is_empty());
+        for i in 0..5 {
+            history.push(StatsSample::new(json!(i)));
+        }
+        assert_eq!(history.len(), 3);
+
+        let data = |samples: Vec<StatsSample>| -> Vec<Value> {
+            samples.into_iter().map(|s| s.data).collect()
+        };
+        assert_eq!(data(history.last(None)), vec![json!(2), json!(3), json!(4)]);
+        assert_eq!(data(history.last(Some(2))), vec![json!(3), json!(4)]);
+        assert_eq!(data(history.last(Some(10))).len(), 3);
+        assert!(history.last(Some(0)).is_empty());
+    }
+}
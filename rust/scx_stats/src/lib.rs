@@ -6,15 +6,29 @@ pub use stats::{
     StatsStructAttrs,
 };
 
+mod history;
+pub use history::{StatsDelta, StatsHistory, StatsSample};
+
+mod access;
+pub use access::{StatsAccess, StatsPeer};
+
+mod tunable;
+pub use tunable::{StatsSetter, StatsTunable, StatsTunableMeta};
+
 mod server;
 pub use server::{
-    StatsCloser, StatsErrno, StatsOpener, StatsOps, StatsReader, StatsReaderSend, StatsReaderSync,
-    StatsRequest, StatsResponse, StatsServer, StatsServerData, ToJson,
+    StatsAgg, StatsCloser, StatsErrno, StatsOpener, StatsOps, StatsReader, StatsReaderSend,
+    StatsReaderSync, StatsRequest, StatsResponse, StatsServer, StatsServerData, ToJson,
 };
 
 mod client;
 pub use client::StatsClient;
 
+mod openmetrics;
+pub use openmetrics::{
+    OpenMetricsExporter, OpenMetricsRenderer, OpenMetricsType, OPENMETRICS_CONTENT_TYPE,
+};
+
 pub mod prelude {
     pub use crate::*;
 }
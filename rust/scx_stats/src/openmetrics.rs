@@ -0,0 +1,473 @@
+use crate::{StatsClient, StatsData, StatsField, StatsKind, StatsMeta};
+use anyhow::{anyhow, bail, Context, Result};
+use log::{debug, warn};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt::Write as FmtWrite;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const OPENMETRICS_CONTENT_TYPE: &str =
+    "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Requests are handled one at a time. Bound how long a slow or idle client
+/// can hold up the following scrapes.
+const HTTP_IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OpenMetricsType {
+    Gauge,
+    Counter,
+}
+
+impl OpenMetricsType {
+    /// Determine the metric type from the field's user attributes. `_counter`
+    /// marks monotonically increasing values. Everything else, including
+    /// fields explicitly marked with `_gauge`, is reported as a gauge.
+    fn from_field(field: &StatsField) -> Self {
+        if field.attrs.user.contains_key("_counter") {
+            Self::Counter
+        } else {
+            Self::Gauge
+        }
+    }
+}
+
+impl std::fmt::Display for OpenMetricsType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Gauge => write!(f, "gauge"),
+            Self::Counter => write!(f, "counter"),
+        }
+    }
+}
+
+struct OpenMetricsFamily {
+    ty: OpenMetricsType,
+    help: String,
+    samples: Vec<(Vec<(String, String)>, f64)>,
+}
+
+/// Converts statistics described by `StatsMeta` into the OpenMetrics text
+/// exposition format.
+///
+/// The following user attributes are recognized:
+///
+/// - `_om_prefix` (struct): Prefixed to the field names of the struct to
+///   form the metric names.
+/// - `_om_label` (struct): Name of the label which distinguishes the
+///   members of a dict containing the struct.
+/// - `_om_skip` (field): The field is not exported.
+/// - `_counter` / `_gauge` (field): The metric type. Defaults to gauge.
+pub struct OpenMetricsRenderer {
+    meta: BTreeMap<String, StatsMeta>,
+    top: String,
+}
+
+impl OpenMetricsRenderer {
+    pub fn new(meta: BTreeMap<String, StatsMeta>) -> Result<Self> {
+        let top = meta
+            .values()
+            .find(|m| m.attrs.top.is_some())
+            .map(|m| m.name.clone())
+            .ok_or_else(|| anyhow!("top-level stats metadata missing"))?;
+        Ok(Self { meta, top })
+    }
+
+    pub fn set_top(mut self, name: &str) -> Result<Self> {
+        if !self.meta.contains_key(name) {
+            bail!("unknown stats meta name {}", name);
+        }
+        self.top = name.to_string();
+        Ok(self)
+    }
+
+    pub fn top(&self) -> &str {
+        &self.top
+    }
+
+    fn sanitize_name(name: &str) -> String {
+        let mut out = String::with_capacity(name.len());
+        for (i, c) in name.chars().enumerate() {
+            match c {
+                'a'..='z' | 'A'..='Z' | '_' | ':' => out.push(c),
+                '0'..='9' if i > 0 => out.push(c),
+                _ => out.push('_'),
+            }
+        }
+        out
+    }
+
+    fn escape(val: &str) -> String {
+        val.replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    }
+
+    fn add_sample(
+        families: &mut BTreeMap<String, OpenMetricsFamily>,
+        name: String,
+        field: &StatsField,
+        labels: &[(String, String)],
+        val: f64,
+    ) -> Result<()> {
+        let ty = OpenMetricsType::from_field(field);
+        let family = families
+            .entry(name.clone())
+            .or_insert_with(|| OpenMetricsFamily {
+                ty,
+                help: field.attrs.desc.clone().unwrap_or_default(),
+                samples: vec![],
+            });
+        if family.ty != ty {
+            bail!("conflicting types for metric {}", name);
+        }
+        family.samples.push((labels.to_vec(), val));
+        Ok(())
+    }
+
+    fn collect_struct(
+        &self,
+        sname: &str,
+        stats: &Value,
+        labels: &mut Vec<(String, String)>,
+        families: &mut BTreeMap<String, OpenMetricsFamily>,
+    ) -> Result<()> {
+        let meta = self
+            .meta
+            .get(sname)
+            .ok_or_else(|| anyhow!("unknown stats meta name {}", sname))?;
+        let prefix = meta
+            .attrs
+            .user
+            .get("_om_prefix")
+            .cloned()
+            .unwrap_or_default();
+
+        for (fname, field) in meta.fields.iter() {
+            if field.attrs.user.contains_key("_om_skip") {
+                continue;
+            }
+            let val = match stats.get(fname) {
+                Some(v) => v,
+                None => continue,
+            };
+
+            match &field.data {
+                StatsData::Datum(StatsKind::I64 | StatsKind::U64 | StatsKind::Float) => {
+                    if let Some(v) = val.as_f64() {
+                        let name = Self::sanitize_name(&format!("{prefix}{fname}"));
+                        Self::add_sample(families, name, field, labels, v)?;
+                    }
+                }
+                StatsData::Datum(StatsKind::Struct(inner)) => {
+                    self.collect_struct(inner, val, labels, families)?
+                }
+                StatsData::Dict {
+                    key: _,
+                    datum: StatsKind::Struct(inner),
+                } => {
+                    let label = match self
+                        .meta
+                        .get(inner)
+                        .and_then(|m| m.attrs.user.get("_om_label"))
+                    {
+                        Some(v) => Self::sanitize_name(v),
+                        None => bail!(
+                            "{}.{} is nested inside but does not have _om_label",
+                            sname,
+                            fname
+                        ),
+                    };
+                    let dict = match val.as_object() {
+                        Some(v) => v,
+                        None => continue,
+                    };
+                    for (key, inner_val) in dict.iter() {
+                        labels.push((label.clone(), key.clone()));
+                        let res = self.collect_struct(inner, inner_val, labels, families);
+                        labels.pop();
+                        res?;
+                    }
+                }
+                _ => debug!("{}.{} has unsupported type, skipping", sname, fname),
+            }
+        }
+        Ok(())
+    }
+
+    /// Render @stats, which should be an instance of the top-level struct,
+    /// into OpenMetrics text. The output is terminated with `# EOF`.
+    pub fn render(&self, stats: &Value) -> Result<String> {
+        let mut families = BTreeMap::<String, OpenMetricsFamily>::new();
+        self.collect_struct(&self.top, stats, &mut vec![], &mut families)?;
+
+        let mut out = String::new();
+        for (name, family) in families.iter() {
+            writeln!(out, "# TYPE {} {}", name, family.ty)?;
+            if !family.help.is_empty() {
+                writeln!(out, "# HELP {} {}", name, Self::escape(&family.help))?;
+            }
+            let suffix = match family.ty {
+                OpenMetricsType::Counter => "_total",
+                OpenMetricsType::Gauge => "",
+            };
+            for (labels, val) in family.samples.iter() {
+                write!(out, "{name}{suffix}")?;
+                if !labels.is_empty() {
+                    let labels: Vec<String> = labels
+                        .iter()
+                        .map(|(k, v)| format!("{}=\"{}\"", k, Self::escape(v)))
+                        .collect();
+                    write!(out, "{{{}}}", labels.join(","))?;
+                }
+                writeln!(out, " {val}")?;
+            }
+        }
+        out.push_str("# EOF\n");
+        Ok(out)
+    }
+}
+
+/// Scrapes a running `StatsServer` and exports the statistics in the
+/// OpenMetrics format either through HTTP or into a textfile collector
+/// directory.
+pub struct OpenMetricsExporter {
+    path: PathBuf,
+    target: String,
+    meta_name: Option<String>,
+    client: Option<StatsClient>,
+    renderer: Option<OpenMetricsRenderer>,
+}
+
+impl OpenMetricsExporter {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: PathBuf::from(path.as_ref()),
+            target: "top".into(),
+            meta_name: None,
+            client: None,
+            renderer: None,
+        }
+    }
+
+    /// Export stats target @target which is described by stats meta
+    /// @meta_name instead of the default top-level stats.
+    pub fn set_target(mut self, target: &str, meta_name: &str) -> Self {
+        self.target = target.to_string();
+        self.meta_name = Some(meta_name.to_string());
+        self
+    }
+
+    fn connect(&mut self) -> Result<()> {
+        let mut client = StatsClient::new().set_path(&self.path).connect(None)?;
+        let meta = client.request::<BTreeMap<String, StatsMeta>>("stats_meta", vec![])?;
+        let mut renderer = OpenMetricsRenderer::new(meta)?;
+        if let Some(name) = self.meta_name.as_ref() {
+            renderer = renderer.set_top(name)?;
+        }
+        self.client = Some(client);
+        self.renderer = Some(renderer);
+        Ok(())
+    }
+
+    /// Fetch the current statistics and render them. The connection to the
+    /// stats server is (re-)established as necessary so that the exporter
+    /// survives scheduler restarts.
+    pub fn collect(&mut self) -> Result<String> {
+        if self.client.is_none() {
+            self.connect()?;
+        }
+
+        let res = self
+            .client
+            .as_mut()
+            .unwrap()
+            .request::<Value>("stats", vec![("target".into(), self.target.clone())])
+            .and_then(|stats| self.renderer.as_ref().unwrap().render(&stats));
+
+        if res.is_err() {
+            self.client = None;
+            self.renderer = None;
+        }
+        res
+    }
+
+    /// Write the current statistics to @dir/@name for the node_exporter
+    /// textfile collector. The file is replaced atomically.
+    pub fn write_textfile<P: AsRef<Path>>(&mut self, dir: P, name: &str) -> Result<()> {
+        let out = self.collect()?;
+        let dst = dir.as_ref().join(name);
+        let tmp = dir.as_ref().join(format!(".{name}.tmp"));
+        std::fs::write(&tmp, out.as_bytes()).with_context(|| format!("writing {tmp:?}"))?;
+        std::fs::rename(&tmp, &dst).with_context(|| format!("renaming {tmp:?} to {dst:?}"))?;
+        Ok(())
+    }
+
+    fn respond(&mut self, mut stream: TcpStream) -> Result<()> {
+        stream.set_read_timeout(Some(HTTP_IO_TIMEOUT))?;
+        stream.set_write_timeout(Some(HTTP_IO_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+                break;
+            }
+        }
+
+        let mut parts = request_line.split_whitespace();
+        let (status, ctype, body) = match (parts.next(), parts.next()) {
+            (Some("GET"), Some("/metrics")) => match self.collect() {
+                Ok(body) => ("200 OK", OPENMETRICS_CONTENT_TYPE, body),
+                Err(e) => ("503 Service Unavailable", "text/plain", format!("{e:?}\n")),
+            },
+            (Some("GET"), Some(_)) => ("404 Not Found", "text/plain", "not found\n".into()),
+            _ => (
+                "405 Method Not Allowed",
+                "text/plain",
+                "method not allowed\n".into(),
+            ),
+        };
+
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            ctype,
+            body.len(),
+            body
+        )?;
+        Ok(())
+    }
+
+    /// Serve `/metrics` on @addr. Blocks indefinitely and handles one
+    /// request at a time which is plenty for metrics scraping. Clients which
+    /// don't complete their request within `HTTP_IO_TIMEOUT` are dropped.
+    pub fn serve_http<A: ToSocketAddrs>(&mut self, addr: A) -> Result<()> {
+        let listener = TcpListener::bind(addr).context("binding OpenMetrics HTTP listener")?;
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(e) = self.respond(stream) {
+                        warn!("OpenMetrics request errored ({e})");
+                    }
+                }
+                Err(e) => warn!("failed to accept OpenMetrics connection ({e})"),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn test_meta() -> BTreeMap<String, StatsMeta> {
+        serde_json::from_value(json!({
+            "ClusterStats": {
+                "name": "ClusterStats",
+                "top": "true",
+                "desc": "cluster statistics",
+                "fields": {
+                    "at": { "datum": "u64", "desc": "timestamp", "user": { "_om_skip": "true" } },
+                    "name": { "datum": "string" },
+                    "nr_dispatched": {
+                        "datum": "u64",
+                        "desc": "dispatched tasks",
+                        "user": { "_counter": "true" }
+                    },
+                    "load": { "datum": "float", "desc": "load \"avg\"" },
+                    "doms": {
+                        "dict": { "key": "string", "datum": { "struct": "DomainStats" } }
+                    }
+                }
+            },
+            "DomainStats": {
+                "name": "DomainStats",
+                "user": { "_om_prefix": "d_", "_om_label": "domain_name" },
+                "fields": {
+                    "events": { "datum": "u64", "user": { "_counter": "true" } },
+                    "pressure": { "datum": "float", "user": { "_gauge": "true" } }
+                }
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_render() {
+        let renderer = OpenMetricsRenderer::new(test_meta()).unwrap();
+        assert_eq!(renderer.top(), "ClusterStats");
+
+        let stats = json!({
+            "at": 12345,
+            "name": "test",
+            "nr_dispatched": 42,
+            "load": 1.5,
+            "doms": {
+                "a": { "events": 3, "pressure": 0.25 },
+                "b\"": { "events": 7, "pressure": 0.5 }
+            }
+        });
+
+        assert_eq!(
+            renderer.render(&stats).unwrap(),
+            concat!(
+                "# TYPE d_events counter\n",
+                "d_events_total{domain_name=\"a\"} 3\n",
+                "d_events_total{domain_name=\"b\\\"\"} 7\n",
+                "# TYPE d_pressure gauge\n",
+                "d_pressure{domain_name=\"a\"} 0.25\n",
+                "d_pressure{domain_name=\"b\\\"\"} 0.5\n",
+                "# TYPE load gauge\n",
+                "# HELP load load \\\"avg\\\"\n",
+                "load 1.5\n",
+                "# TYPE nr_dispatched counter\n",
+                "# HELP nr_dispatched dispatched tasks\n",
+                "nr_dispatched_total 42\n",
+                "# EOF\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_render_missing_label() {
+        let mut meta = test_meta();
+        meta.get_mut("DomainStats")
+            .unwrap()
+            .attrs
+            .user
+            .remove("_om_label");
+        let renderer = OpenMetricsRenderer::new(meta).unwrap();
+        assert!(renderer.render(&json!({ "doms": {} })).is_err());
+    }
+
+    #[test]
+    fn test_set_top() {
+        let renderer = OpenMetricsRenderer::new(test_meta()).unwrap();
+        assert!(renderer.set_top("NoSuchStats").is_err());
+
+        let renderer = OpenMetricsRenderer::new(test_meta())
+            .unwrap()
+            .set_top("DomainStats")
+            .unwrap();
+        assert_eq!(
+            renderer
+                .render(&json!({ "events": 1, "pressure": 2.0 }))
+                .unwrap(),
+            concat!(
+                "# TYPE d_events counter\n",
+                "d_events_total 1\n",
+                "# TYPE d_pressure gauge\n",
+                "d_pressure 2\n",
+                "# EOF\n",
+            )
+        );
+    }
+}
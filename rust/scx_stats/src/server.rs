@@ -1,17 +1,22 @@
 use crate::StatsClient;
-use crate::{Meta, StatsData, StatsKind, StatsMeta};
+use crate::{
+    Meta, StatsAccess, StatsData, StatsHistory, StatsKind, StatsMeta, StatsPeer, StatsSample,
+    StatsSetter, StatsTunable, StatsTunableMeta,
+};
 use anyhow::{anyhow, bail, Context, Result};
 use crossbeam::channel::{unbounded, Receiver, RecvError, Select, Sender};
-use log::{debug, error, warn};
+use log::{debug, error, info, warn};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::{BTreeMap, BTreeSet};
 use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
 use std::os::unix::net::{UnixListener, UnixStream};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use std::thread::spawn;
+use std::thread::{sleep, spawn};
+use std::time::{Duration, Instant};
 
 pub trait StatsReader<Req, Res>:
     FnMut(&BTreeMap<String, String>, (&Sender<Req>, &Receiver<Res>)) -> Result<Value>
@@ -124,6 +129,91 @@ pub struct StatsResponse {
 
 pub struct StatsErrno(pub i32);
 
+/// How the samples taken within a subscription interval are combined.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatsAgg {
+    Last,
+    Min,
+    Max,
+    Avg,
+}
+
+impl std::str::FromStr for StatsAgg {
+    type Err = anyhow::Error;
+
+    fn from_str(agg: &str) -> Result<Self> {
+        match agg {
+            "last" => Ok(Self::Last),
+            "min" => Ok(Self::Min),
+            "max" => Ok(Self::Max),
+            "avg" => Ok(Self::Avg),
+            v => Err(anyhow!("unknown aggregation {:?}", v).context(StatsErrno(libc::EINVAL))),
+        }
+    }
+}
+
+impl StatsAgg {
+    /// Combine @samples recursively. Numbers are aggregated, objects and
+    /// arrays are descended into following the shape of the last sample
+    /// and everything else takes the value from the last sample.
+    pub fn reduce(&self, samples: &[&Value]) -> Value {
+        let last = match samples.last() {
+            Some(v) => *v,
+            None => return Value::Null,
+        };
+
+        match last {
+            Value::Number(_) if *self != Self::Last => {
+                let nums: Vec<(&Value, f64)> = samples
+                    .iter()
+                    .filter_map(|v| v.as_f64().map(|f| (*v, f)))
+                    .collect();
+                match self {
+                    Self::Min => nums
+                        .iter()
+                        .min_by(|a, b| a.1.total_cmp(&b.1))
+                        .map(|v| v.0.clone())
+                        .unwrap_or(Value::Null),
+                    Self::Max => nums
+                        .iter()
+                        .max_by(|a, b| a.1.total_cmp(&b.1))
+                        .map(|v| v.0.clone())
+                        .unwrap_or(Value::Null),
+                    _ => {
+                        // Keep integers integers so that the result can
+                        // still be deserialized into the stats struct.
+                        let avg = nums.iter().map(|v| v.1).sum::<f64>() / nums.len().max(1) as f64;
+                        if last.is_u64() {
+                            serde_json::json!(avg.round() as u64)
+                        } else if last.is_i64() {
+                            serde_json::json!(avg.round() as i64)
+                        } else {
+                            serde_json::json!(avg)
+                        }
+                    }
+                }
+            }
+            Value::Object(obj) => Value::Object(
+                obj.keys()
+                    .map(|k| {
+                        let vals: Vec<&Value> = samples.iter().filter_map(|v| v.get(k)).collect();
+                        (k.clone(), self.reduce(&vals))
+                    })
+                    .collect(),
+            ),
+            Value::Array(arr) => Value::Array(
+                (0..arr.len())
+                    .map(|i| {
+                        let vals: Vec<&Value> = samples.iter().filter_map(|v| v.get(i)).collect();
+                        self.reduce(&vals)
+                    })
+                    .collect(),
+            ),
+            v => v.clone(),
+        }
+    }
+}
+
 impl std::fmt::Display for StatsErrno {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "{}", std::io::Error::from_raw_os_error(self.0))
@@ -174,6 +264,10 @@ where
     top: Option<String>,
     meta: BTreeMap<String, StatsMeta>,
     ops: BTreeMap<String, Arc<Mutex<StatsOps<Req, Res>>>>,
+    history: BTreeMap<String, (Duration, Arc<Mutex<StatsHistory>>)>,
+    tunables: BTreeMap<String, Arc<Mutex<StatsTunable<Req, Res>>>>,
+    access: BTreeMap<String, StatsAccess>,
+    default_access: StatsAccess,
 }
 
 impl<Req, Res> StatsServerData<Req, Res>
@@ -186,6 +280,10 @@ where
             top: None,
             meta: BTreeMap::new(),
             ops: BTreeMap::new(),
+            history: BTreeMap::new(),
+            tunables: BTreeMap::new(),
+            access: BTreeMap::new(),
+            default_access: StatsAccess::Any,
         }
     }
 
@@ -218,6 +316,68 @@ where
         self.add_ops(name, ops)
     }
 
+    /// Sample stats target @name every @intv and keep the last @depth
+    /// samples so that they can be retrieved with the "history" request.
+    /// The target is read through its own open instance, so stateful
+    /// readers see the samples as a separate client.
+    pub fn add_history(mut self, name: &str, intv: Duration, depth: usize) -> Self {
+        self.history.insert(
+            name.to_string(),
+            (intv, Arc::new(Mutex::new(StatsHistory::new(depth)))),
+        );
+        self
+    }
+
+    /// Register runtime tunable @name which can be listed with the
+    /// "tunables" request and modified with the "set" request.
+    pub fn add_tunable(
+        mut self,
+        name: &str,
+        meta: StatsTunableMeta,
+        set: Box<dyn StatsSetter<Req, Res>>,
+    ) -> Self {
+        self.tunables.insert(
+            name.to_string(),
+            Arc::new(Mutex::new(StatsTunable { meta, set })),
+        );
+        self
+    }
+
+    /// Restrict who can read stats target or op @name. Targets without
+    /// explicit access use the default access.
+    pub fn set_access(mut self, name: &str, access: StatsAccess) -> Self {
+        self.access.insert(name.to_string(), access);
+        self
+    }
+
+    /// Set the access required for targets without explicit access and for
+    /// requests which aren't tied to a target such as "stats_meta". The
+    /// default is `StatsAccess::Any`.
+    pub fn set_default_access(mut self, access: StatsAccess) -> Self {
+        self.default_access = access;
+        self
+    }
+
+    fn check_access(&self, peer: &StatsPeer, req: &StatsRequest) -> Result<()> {
+        match req.req.as_str() {
+            "stats" | "subscribe" | "history" => {
+                let target = req.args.get("target").map(|v| v.as_str()).unwrap_or("top");
+                let access = self.access.get(target).unwrap_or(&self.default_access);
+                peer.check(access, &format!("stat target {target:?}"))
+            }
+            "set" => {
+                let name = req.args.get("name").map(|v| v.as_str()).unwrap_or("");
+                match self.tunables.get(name) {
+                    Some(t) => {
+                        peer.check(&t.lock().unwrap().meta.access, &format!("tunable {name:?}"))
+                    }
+                    None => Ok(()),
+                }
+            }
+            req => peer.check(&self.default_access, &format!("{req:?}")),
+        }
+    }
+
     fn visit_meta_inner(
         &self,
         name: &str,
@@ -384,44 +544,208 @@ where
         })
     }
 
+    fn read_stats(
+        req: &StatsRequest,
+        data: &Arc<Mutex<StatsServerData<Req, Res>>>,
+        ch: &ChannelPair<Req, Res>,
+        open_ops: &mut StatsOpenOps<Req, Res>,
+    ) -> Result<Value> {
+        let target = match req.args.get("target") {
+            Some(v) => v,
+            None => "top",
+        };
+
+        let ops = match data.lock().unwrap().ops.get(target) {
+            Some(v) => v.clone(),
+            None => {
+                Err(anyhow!("unknown stat target {:?}", req).context(StatsErrno(libc::EINVAL)))?
+            }
+        };
+
+        if !open_ops.map.contains_key(target) {
+            let read = (ops.lock().unwrap().open)((&ch.req, &ch.res))?;
+            open_ops
+                .map
+                .insert(target.into(), (ops.clone(), read, ch.clone()));
+        }
+
+        let read = &mut open_ops.map.get_mut(target).unwrap().1;
+
+        read(&req.args, (&ch.req, &ch.res))
+    }
+
     fn handle_request(
-        line: String,
+        req: &StatsRequest,
         data: &Arc<Mutex<StatsServerData<Req, Res>>>,
         ch: &ChannelPair<Req, Res>,
         open_ops: &mut StatsOpenOps<Req, Res>,
     ) -> Result<StatsResponse> {
-        let req: StatsRequest = serde_json::from_str(&line)?;
-
         match req.req.as_str() {
-            "stats" => {
-                let target = match req.args.get("target") {
-                    Some(v) => v,
-                    None => "top",
+            "stats" => Self::build_resp(0, &Self::read_stats(req, data, ch, open_ops)?),
+            "stats_meta" => Ok(Self::build_resp(0, &data.lock().unwrap().meta)?),
+            "tunables" => {
+                let tunables: Vec<_> = data.lock().unwrap().tunables.clone().into_iter().collect();
+                let metas: BTreeMap<String, StatsTunableMeta> = tunables
+                    .into_iter()
+                    .map(|(name, t)| (name, t.lock().unwrap().meta.clone()))
+                    .collect();
+                Self::build_resp(0, &metas)
+            }
+            "set" => {
+                let (name, input) = match (req.args.get("name"), req.args.get("value")) {
+                    (Some(n), Some(v)) => (n, v),
+                    _ => Err(anyhow!("name and value required").context(StatsErrno(libc::EINVAL)))?,
+                };
+                let tunable = match data.lock().unwrap().tunables.get(name) {
+                    Some(v) => v.clone(),
+                    None => {
+                        Err(anyhow!("unknown tunable {:?}", name).context(StatsErrno(libc::ENOENT)))?
+                    }
                 };
 
-                let ops =
-                    match data.lock().unwrap().ops.get(target) {
-                        Some(v) => v.clone(),
-                        None => Err(anyhow!("unknown stat target {:?}", req)
-                            .context(StatsErrno(libc::EINVAL)))?,
-                    };
-
-                if !open_ops.map.contains_key(target) {
-                    let read = (ops.lock().unwrap().open)((&ch.req, &ch.res))?;
-                    open_ops
-                        .map
-                        .insert(target.into(), (ops.clone(), read, ch.clone()));
-                }
+                let mut tunable = tunable.lock().unwrap();
+                let val = tunable.meta.parse(input)?;
+                (tunable.set)(&val, (&ch.req, &ch.res))
+                    .with_context(|| format!("setting tunable {name:?} to {val}"))?;
+
+                info!(
+                    "tunable {:?} changed from {} to {}",
+                    name, &tunable.meta.value, &val
+                );
+                tunable.meta.value = val;
+                Self::build_resp(0, &tunable.meta)
+            }
+            "history" => {
+                let target = req.args.get("target").map(|v| v.as_str()).unwrap_or("top");
+                let nr = match req.args.get("nr") {
+                    Some(v) => Some(v.parse::<usize>().map_err(|e| {
+                        anyhow!("invalid nr {:?} ({})", v, e).context(StatsErrno(libc::EINVAL))
+                    })?),
+                    None => None,
+                };
+                let history = match data.lock().unwrap().history.get(target) {
+                    Some((_, v)) => v.clone(),
+                    None => Err(anyhow!("no history for stat target {:?}", target)
+                        .context(StatsErrno(libc::ENOENT)))?,
+                };
+                let samples = history.lock().unwrap().last(nr);
+                Self::build_resp(0, &samples)
+            }
+            req => Err(anyhow!("unknown command {:?}", req).context(StatsErrno(libc::EOPNOTSUPP)))?,
+        }
+    }
 
-                let read = &mut open_ops.map.get_mut(target).unwrap().1;
+    fn build_err_resp(e: &anyhow::Error) -> Result<StatsResponse> {
+        let errno = match e.downcast_ref::<StatsErrno>() {
+            Some(e) if e.0 != 0 => e.0,
+            _ => libc::EINVAL,
+        };
+        Self::build_resp(errno, &format!("{:?}", e))
+    }
+
+    fn write_resp(stream: &mut UnixStream, resp: &StatsResponse) -> Result<()> {
+        let output = serde_json::to_string(resp)? + "\n";
+        stream.write_all(output.as_bytes())?;
+        Ok(())
+    }
 
-                let resp = read(&req.args, (&ch.req, &ch.res))?;
+    fn subscribe_params(
+        req: &StatsRequest,
+        data: &Arc<Mutex<StatsServerData<Req, Res>>>,
+    ) -> Result<(Duration, Duration, StatsAgg)> {
+        let parse_ms = |key: &str| -> Result<Option<u64>> {
+            match req.args.get(key) {
+                Some(v) => Ok(Some(v.parse::<u64>().map_err(|e| {
+                    anyhow!("invalid {} {:?} ({})", key, v, e).context(StatsErrno(libc::EINVAL))
+                })?)),
+                None => Ok(None),
+            }
+        };
 
-                Self::build_resp(0, &resp)
+        let intv_ms = match parse_ms("intv_ms")? {
+            Some(v) => v,
+            None => Err(anyhow!("intv_ms missing").context(StatsErrno(libc::EINVAL)))?,
+        };
+        let sample_ms = match parse_ms("sample_ms")? {
+            Some(v) if v == 0 || v > intv_ms => {
+                Err(
+                    anyhow!("sample_ms {} must be in (0, intv_ms {}]", v, intv_ms)
+                        .context(StatsErrno(libc::EINVAL)),
+                )?
             }
-            "stats_meta" => Ok(Self::build_resp(0, &data.lock().unwrap().meta)?),
-            req => Err(anyhow!("unknown command {:?}", req).context(StatsErrno(libc::EINVAL)))?,
+            Some(v) => v,
+            None => intv_ms,
+        };
+        let agg = match req.args.get("agg") {
+            Some(v) => v.parse::<StatsAgg>()?,
+            None => StatsAgg::Last,
+        };
+
+        let target = req.args.get("target").map(|v| v.as_str()).unwrap_or("top");
+        if !data.lock().unwrap().ops.contains_key(target) {
+            Err(anyhow!("unknown stat target {:?}", req).context(StatsErrno(libc::EINVAL)))?;
+        }
+
+        Ok((
+            Duration::from_millis(intv_ms),
+            Duration::from_millis(sample_ms),
+            agg,
+        ))
+    }
+
+    /// Handle a "subscribe" request. After acknowledging the request, the
+    /// connection is dedicated to pushing a sample of the target every
+    /// `intv_ms` until the client disconnects or the server exits.
+    ///
+    /// If `sample_ms` is shorter than `intv_ms`, the target is read every
+    /// `sample_ms` and the samples in each interval are combined according
+    /// to `agg` before being pushed.
+    fn subscribe(
+        stream: &mut UnixStream,
+        req: &StatsRequest,
+        (intv, sample_intv, agg): (Duration, Duration, StatsAgg),
+        data: &Arc<Mutex<StatsServerData<Req, Res>>>,
+        ch: &ChannelPair<Req, Res>,
+        open_ops: &mut StatsOpenOps<Req, Res>,
+        exit: &Arc<AtomicBool>,
+    ) -> Result<()> {
+        let ack: BTreeMap<&str, u128> = [
+            ("intv_ms", intv.as_millis()),
+            ("sample_ms", sample_intv.as_millis()),
+        ]
+        .into_iter()
+        .collect();
+        Self::write_resp(stream, &Self::build_resp(0, &ack)?)?;
+
+        let mut next_push = Instant::now() + intv;
+        let mut samples = vec![];
+
+        while !exit.load(Ordering::Relaxed) {
+            match Self::read_stats(req, data, ch, open_ops) {
+                Ok(v) => samples.push(v),
+                Err(e) => return Self::write_resp(stream, &Self::build_err_resp(&e)?),
+            }
+
+            let now = Instant::now();
+            if now >= next_push || sample_intv >= intv {
+                let resp = agg.reduce(&samples.iter().collect::<Vec<_>>());
+                samples.clear();
+                if let Err(e) = Self::write_resp(stream, &Self::build_resp(0, &resp)?) {
+                    debug!("subscriber went away ({e})");
+                    return Ok(());
+                }
+                next_push += intv;
+                if next_push < now {
+                    next_push = now + intv;
+                }
+            }
+
+            let now = Instant::now();
+            sleep(sample_intv.min(next_push.saturating_duration_since(now)));
         }
+
+        debug!("subscription exiting due to exit");
+        Ok(())
     }
 
     fn serve(
@@ -432,6 +756,8 @@ where
     ) -> Result<()> {
         let mut stream_reader = BufReader::new(stream.try_clone()?);
         let mut open_ops = StatsOpenOps::new();
+        let peer = StatsPeer::from_stream(&stream)?;
+        debug!("serving {:?}", &peer);
 
         loop {
             let mut line = String::new();
@@ -444,19 +770,38 @@ where
                 return Ok(());
             }
 
-            let resp = match Self::handle_request(line, &data, &inner_ch, &mut open_ops) {
+            let req = serde_json::from_str::<StatsRequest>(&line)
+                .map_err(anyhow::Error::from)
+                .and_then(|req| {
+                    data.lock().unwrap().check_access(&peer, &req)?;
+                    Ok(req)
+                });
+
+            let resp = match req {
+                Ok(req) if req.req == "subscribe" => match Self::subscribe_params(&req, &data) {
+                    Ok(params) => {
+                        return Self::subscribe(
+                            &mut stream,
+                            &req,
+                            params,
+                            &data,
+                            &inner_ch,
+                            &mut open_ops,
+                            &exit,
+                        );
+                    }
+                    Err(e) => Err(e),
+                },
+                Ok(req) => Self::handle_request(&req, &data, &inner_ch, &mut open_ops),
+                Err(e) => Err(e),
+            };
+
+            let resp = match resp {
                 Ok(v) => v,
-                Err(e) => {
-                    let errno = match e.downcast_ref::<StatsErrno>() {
-                        Some(e) if e.0 != 0 => e.0,
-                        _ => libc::EINVAL,
-                    };
-                    Self::build_resp(errno, &format!("{:?}", &e))?
-                }
+                Err(e) => Self::build_err_resp(&e)?,
             };
 
-            let output = serde_json::to_string(&resp)? + "\n";
-            stream.write_all(output.as_bytes())?;
+            Self::write_resp(&mut stream, &resp)?;
         }
     }
 
@@ -541,12 +886,61 @@ where
         }
     }
 
+    fn sample_history(
+        target: String,
+        intv: Duration,
+        history: Arc<Mutex<StatsHistory>>,
+        data: Arc<Mutex<StatsServerData<Req, Res>>>,
+        ch: ChannelPair<Req, Res>,
+        exit: Arc<AtomicBool>,
+    ) {
+        let req = StatsRequest::new("stats", vec![("target".into(), target.clone())]);
+        let mut open_ops = StatsOpenOps::new();
+        let mut failing = false;
+
+        while !exit.load(Ordering::Relaxed) {
+            match Self::read_stats(&req, &data, &ch, &mut open_ops) {
+                Ok(v) => {
+                    history.lock().unwrap().push(StatsSample::new(v));
+                    failing = false;
+                }
+                Err(e) => {
+                    if !failing {
+                        warn!("failed to sample stats history for {:?} ({})", &target, &e);
+                    }
+                    failing = true;
+                }
+            }
+            sleep(intv);
+        }
+    }
+
     fn listen(self) {
         let inner_ch_copy = self.inner_ch.clone();
         let (add_req, add_res) = unbounded::<ChannelPair<Res, Req>>();
 
         spawn(move || Self::proxy(inner_ch_copy, add_res));
 
+        let history: Vec<_> = self
+            .data
+            .lock()
+            .unwrap()
+            .history
+            .iter()
+            .map(|(target, (intv, history))| (target.clone(), *intv, history.clone()))
+            .collect();
+        for (target, intv, history) in history.into_iter() {
+            let data = self.data.clone();
+            let exit = self.exit.clone();
+
+            let (req_pair, res_pair) = ChannelPair::<Req, Res>::bidi();
+            if let Err(e) = add_req.send(res_pair) {
+                warn!("StatsServer::proxy() failed ({e})");
+            }
+
+            spawn(move || Self::sample_history(target, intv, history, data, req_pair, exit));
+        }
+
         for stream in self.listener.incoming() {
             if self.exit.load(Ordering::Relaxed) {
                 debug!("listener exiting");
@@ -584,6 +978,8 @@ where
     sched_path: PathBuf,
     stats_path: PathBuf,
     path: Option<PathBuf>,
+    mode: Option<u32>,
+    owner: (Option<u32>, Option<u32>),
 
     data: Arc<Mutex<StatsServerData<Req, Res>>>,
 
@@ -605,6 +1001,8 @@ where
             sched_path: PathBuf::from("root"),
             stats_path: PathBuf::from("stats"),
             path: None,
+            mode: None,
+            owner: (None, None),
             data: Arc::new(Mutex::new(data)),
             outer_ch: och,
             inner_ch: Some(ich),
@@ -632,6 +1030,66 @@ where
         self
     }
 
+    /// Set the permission bits of the UNIX socket. Connecting requires write
+    /// permission. If not set, the process umask applies.
+    pub fn set_socket_mode(mut self, mode: u32) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Set the owner and group of the UNIX socket. `None` leaves the
+    /// respective id unchanged.
+    pub fn set_socket_owner(mut self, uid: Option<u32>, gid: Option<u32>) -> Self {
+        self.owner = (uid, gid);
+        self
+    }
+
+    /// Bind a UNIX socket at @path with @mode and @owner applied. The
+    /// socket is created and set up in a private directory and then moved to
+    /// @path, so that it can't be connected to before the permissions are
+    /// restricted.
+    fn bind(
+        path: &Path,
+        mode: Option<u32>,
+        owner: (Option<u32>, Option<u32>),
+    ) -> Result<UnixListener> {
+        if mode.is_none() && owner.0.is_none() && owner.1.is_none() {
+            return UnixListener::bind(path)
+                .with_context(|| format!("creating UNIX socket {path:?}"));
+        }
+
+        let name = path
+            .file_name()
+            .ok_or_else(|| anyhow!("invalid socket path {path:?}"))?
+            .to_string_lossy();
+        let tmp_dir = path.with_file_name(format!(".{}.{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&tmp_dir);
+        std::fs::DirBuilder::new()
+            .mode(0o700)
+            .create(&tmp_dir)
+            .with_context(|| format!("creating {tmp_dir:?}"))?;
+
+        let tmp_path = tmp_dir.join(&*name);
+        let res = (|| {
+            let listener = UnixListener::bind(&tmp_path)
+                .with_context(|| format!("creating UNIX socket {tmp_path:?}"))?;
+            if owner.0.is_some() || owner.1.is_some() {
+                std::os::unix::fs::chown(&tmp_path, owner.0, owner.1)
+                    .with_context(|| format!("changing owner of {tmp_path:?}"))?;
+            }
+            if let Some(mode) = mode {
+                std::fs::set_permissions(&tmp_path, std::fs::Permissions::from_mode(mode))
+                    .with_context(|| format!("changing mode of {tmp_path:?}"))?;
+            }
+            std::fs::rename(&tmp_path, path)
+                .with_context(|| format!("moving {tmp_path:?} to {path:?}"))?;
+            Ok(listener)
+        })();
+
+        let _ = std::fs::remove_dir_all(&tmp_dir);
+        res
+    }
+
     pub fn launch(mut self) -> Result<Self> {
         self.data.lock().unwrap().verify_meta()?;
 
@@ -651,8 +1109,7 @@ where
             }
         }
 
-        let listener =
-            UnixListener::bind(path).with_context(|| format!("creating UNIX socket {path:?}"))?;
+        let listener = Self::bind(path, self.mode, self.owner)?;
 
         let inner = StatsServerInner::new(
             listener,
@@ -695,3 +1152,191 @@ where
         Ok(serde_json::to_value(self)?)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::atomic::AtomicU64;
+
+    fn test_sock_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("scx_stats_test_{}_{}", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn test_server_data() -> StatsServerData<(), ()> {
+        let seq = AtomicU64::new(0);
+        StatsServerData::new().add_stats(
+            "top",
+            Box::new(move |_args, _chan| Ok(json!({ "seq": seq.fetch_add(1, Ordering::Relaxed) }))),
+        )
+    }
+
+    fn errno_of(e: &anyhow::Error) -> Option<i32> {
+        e.downcast_ref::<StatsErrno>().map(|errno| errno.0)
+    }
+
+    #[test]
+    fn test_subscribe() {
+        let path = test_sock_path("subscribe");
+        let _server = StatsServer::new(test_server_data())
+            .set_path(&path)
+            .launch()
+            .unwrap();
+
+        let mut client = StatsClient::new()
+            .set_path(&path)
+            .connect(Some(5000))
+            .unwrap();
+        client.subscribe(Duration::from_millis(10), vec![]).unwrap();
+        let a: Value = client.recv().unwrap();
+        let b: Value = client.recv().unwrap();
+        assert!(b["seq"].as_u64().unwrap() > a["seq"].as_u64().unwrap());
+    }
+
+    #[test]
+    fn test_subscribe_invalid_args() {
+        let path = test_sock_path("subscribe_invalid");
+        let _server = StatsServer::new(test_server_data())
+            .set_path(&path)
+            .launch()
+            .unwrap();
+
+        let mut client = StatsClient::new()
+            .set_path(&path)
+            .connect(Some(5000))
+            .unwrap();
+        for args in [
+            vec![("sample_ms".to_string(), "20".to_string())],
+            vec![("agg".to_string(), "median".to_string())],
+            vec![("target".to_string(), "nope".to_string())],
+        ] {
+            let e = client
+                .subscribe(Duration::from_millis(10), args)
+                .unwrap_err();
+            assert_eq!(errno_of(&e), Some(libc::EINVAL), "{e:?}");
+        }
+
+        // The connection stays usable after a rejected subscription.
+        let stats: Value = client.request("stats", vec![]).unwrap();
+        assert!(stats["seq"].is_u64());
+
+        let e = client.request::<Value>("nope", vec![]).unwrap_err();
+        assert_eq!(errno_of(&e), Some(libc::EOPNOTSUPP));
+    }
+
+    #[test]
+    fn test_tunables() {
+        let path = test_sock_path("tunables");
+        let slice_us = Arc::new(AtomicU64::new(20000));
+        let slice_us_copy = slice_us.clone();
+        let sdata = test_server_data().add_tunable(
+            "slice_us",
+            StatsTunableMeta::new(StatsKind::U64, 20000.into())
+                .range(1000.0, 100000.0)
+                .access(StatsAccess::Any),
+            Box::new(move |val, _chan| {
+                slice_us_copy.store(val.as_u64().unwrap(), Ordering::Relaxed);
+                Ok(())
+            }),
+        );
+        let _server = StatsServer::new(sdata).set_path(&path).launch().unwrap();
+
+        let mut client = StatsClient::new()
+            .set_path(&path)
+            .connect(Some(5000))
+            .unwrap();
+        let tunables = client.tunables().unwrap();
+        assert_eq!(tunables["slice_us"].value, json!(20000));
+
+        let meta = client.set_tunable("slice_us", "5000").unwrap();
+        assert_eq!(meta.value, json!(5000));
+        assert_eq!(slice_us.load(Ordering::Relaxed), 5000);
+
+        for (name, value, errno) in [
+            ("slice_us", "500", libc::ERANGE),
+            ("slice_us", "fast", libc::EINVAL),
+            ("slice_us", "-1", libc::EINVAL),
+            ("slice_lag_us", "1000", libc::ENOENT),
+        ] {
+            let e = client.set_tunable(name, value).unwrap_err();
+            assert_eq!(errno_of(&e), Some(errno), "{name}={value}: {e:?}");
+        }
+
+        // Rejected values are neither applied nor recorded.
+        assert_eq!(slice_us.load(Ordering::Relaxed), 5000);
+        let tunables = client.tunables().unwrap();
+        assert_eq!(tunables["slice_us"].value, json!(5000));
+    }
+
+    #[test]
+    fn test_socket_mode() {
+        let path = test_sock_path("mode");
+        let _server = StatsServer::new(test_server_data())
+            .set_path(&path)
+            .set_socket_mode(0o600)
+            .launch()
+            .unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let stats: Value = StatsClient::new()
+            .set_path(&path)
+            .connect(Some(5000))
+            .unwrap()
+            .request("stats", vec![])
+            .unwrap();
+        assert!(stats["seq"].is_u64());
+    }
+
+    #[test]
+    fn test_access() {
+        let path = test_sock_path("access");
+        let sdata = test_server_data()
+            .add_stats("tasks", Box::new(|_args, _chan| Ok(json!({}))))
+            .set_access("tasks", StatsAccess::Group(u32::MAX - 1));
+        let _server = StatsServer::new(sdata).set_path(&path).launch().unwrap();
+
+        let mut client = StatsClient::new()
+            .set_path(&path)
+            .connect(Some(5000))
+            .unwrap();
+        let res =
+            client.request::<Value>("stats", vec![("target".to_string(), "tasks".to_string())]);
+        if unsafe { libc::geteuid() } == 0 {
+            assert!(res.is_ok());
+        } else {
+            assert_eq!(errno_of(&res.unwrap_err()), Some(libc::EACCES));
+        }
+        assert!(client.request::<Value>("stats", vec![]).is_ok());
+    }
+
+    #[test]
+    fn test_subscribe_unsupported() {
+        // Emulate a server predating subscriptions which rejects unknown
+        // commands with EINVAL.
+        let path = test_sock_path("subscribe_old");
+        let listener = UnixListener::bind(&path).unwrap();
+        spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut line = String::new();
+            BufReader::new(stream.try_clone().unwrap())
+                .read_line(&mut line)
+                .unwrap();
+            let e = anyhow!("unknown command {:?}", "subscribe").context(StatsErrno(libc::EINVAL));
+            let resp = StatsServerInner::<(), ()>::build_err_resp(&e).unwrap();
+            StatsServerInner::<(), ()>::write_resp(&mut stream, &resp).unwrap();
+        });
+
+        let mut client = StatsClient::new()
+            .set_path(&path)
+            .connect(Some(5000))
+            .unwrap();
+        let e = client
+            .subscribe(Duration::from_millis(10), vec![])
+            .unwrap_err();
+        assert_eq!(errno_of(&e), Some(libc::EOPNOTSUPP));
+    }
+}
@@ -0,0 +1,175 @@
+use crate::{StatsAccess, StatsErrno, StatsKind};
+use anyhow::{anyhow, Result};
+use crossbeam::channel::{Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub trait StatsSetter<Req, Res>:
+    FnMut(&Value, (&Sender<Req>, &Receiver<Res>)) -> Result<()> + Send
+{
+}
+impl<Req, Res, T: FnMut(&Value, (&Sender<Req>, &Receiver<Res>)) -> Result<()> + Send>
+    StatsSetter<Req, Res> for T
+{
+}
+
+/// Description and current value of a runtime tunable.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StatsTunableMeta {
+    pub kind: StatsKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub desc: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+    pub value: Value,
+    #[serde(default = "StatsTunableMeta::default_access")]
+    pub access: StatsAccess,
+}
+
+impl StatsTunableMeta {
+    /// Create a tunable of type @kind whose initial value is @value. Only
+    /// the scalar kinds are allowed.
+    pub fn new(kind: StatsKind, value: Value) -> Self {
+        Self {
+            kind,
+            desc: None,
+            min: None,
+            max: None,
+            value,
+            access: Self::default_access(),
+        }
+    }
+
+    fn default_access() -> StatsAccess {
+        StatsAccess::Root
+    }
+
+    /// Allow @access to modify the tunable. Only root can by default.
+    pub fn access(mut self, access: StatsAccess) -> Self {
+        self.access = access;
+        self
+    }
+
+    pub fn desc(mut self, desc: &str) -> Self {
+        self.desc = Some(desc.to_string());
+        self
+    }
+
+    /// Restrict numeric values to [@min, @max].
+    pub fn range(mut self, min: f64, max: f64) -> Self {
+        self.min = Some(min);
+        self.max = Some(max);
+        self
+    }
+
+    /// Parse and validate @input against the tunable's type and bounds.
+    pub fn parse(&self, input: &str) -> Result<Value> {
+        let einval = |e: String| anyhow!(e).context(StatsErrno(libc::EINVAL));
+
+        let (val, num) = match &self.kind {
+            StatsKind::I64 => {
+                let v = input
+                    .parse::<i64>()
+                    .map_err(|e| einval(format!("invalid i64 {input:?} ({e})")))?;
+                (Value::from(v), v as f64)
+            }
+            StatsKind::U64 => {
+                let v = input
+                    .parse::<u64>()
+                    .map_err(|e| einval(format!("invalid u64 {input:?} ({e})")))?;
+                (Value::from(v), v as f64)
+            }
+            StatsKind::Float => {
+                let v = input
+                    .parse::<f64>()
+                    .map_err(|e| einval(format!("invalid float {input:?} ({e})")))?;
+                if !v.is_finite() {
+                    return Err(einval(format!("invalid float {input:?}")));
+                }
+                (Value::from(v), v)
+            }
+            StatsKind::String => return Ok(Value::from(input)),
+            StatsKind::Struct(name) => {
+                return Err(einval(format!("struct {name} can't be a tunable")))
+            }
+        };
+
+        if self.min.is_some_and(|min| num < min) || self.max.is_some_and(|max| num > max) {
+            Err(anyhow!(
+                "{} out of range [{}, {}]",
+                input,
+                self.min.map(|v| v.to_string()).unwrap_or("-inf".into()),
+                self.max.map(|v| v.to_string()).unwrap_or("inf".into())
+            )
+            .context(StatsErrno(libc::ERANGE)))?;
+        }
+
+        Ok(val)
+    }
+}
+
+/// A runtime tunable which can be modified through the "set" request.
+/// @set is called with the validated new value along with the stats
+/// channels, which can be used to forward the change to the scheduler.
+pub struct StatsTunable<Req, Res> {
+    pub meta: StatsTunableMeta,
+    pub set: Box<dyn StatsSetter<Req, Res>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn errno_of(e: &anyhow::Error) -> Option<i32> {
+        e.downcast_ref::<StatsErrno>().map(|errno| errno.0)
+    }
+
+    #[test]
+    fn test_parse() {
+        let meta = StatsTunableMeta::new(StatsKind::U64, json!(20000)).range(1000.0, 100000.0);
+        assert_eq!(meta.parse("1000").unwrap(), json!(1000));
+        assert_eq!(meta.parse("100000").unwrap(), json!(100000));
+        assert_eq!(
+            errno_of(&meta.parse("999").unwrap_err()),
+            Some(libc::ERANGE)
+        );
+        assert_eq!(
+            errno_of(&meta.parse("100001").unwrap_err()),
+            Some(libc::ERANGE)
+        );
+        for input in ["-1", "1.5", "", "abc"] {
+            assert_eq!(
+                errno_of(&meta.parse(input).unwrap_err()),
+                Some(libc::EINVAL),
+                "{input:?}"
+            );
+        }
+
+        let meta = StatsTunableMeta::new(StatsKind::I64, json!(0));
+        assert_eq!(meta.parse("-5").unwrap(), json!(-5));
+        assert_eq!(errno_of(&meta.parse("5x").unwrap_err()), Some(libc::EINVAL));
+
+        let meta = StatsTunableMeta::new(StatsKind::Float, json!(0.5)).range(0.0, 1.0);
+        assert_eq!(meta.parse("0.25").unwrap(), json!(0.25));
+        assert_eq!(
+            errno_of(&meta.parse("1.5").unwrap_err()),
+            Some(libc::ERANGE)
+        );
+        for input in ["nan", "inf", "-inf"] {
+            assert_eq!(
+                errno_of(&meta.parse(input).unwrap_err()),
+                Some(libc::EINVAL),
+                "{input:?}"
+            );
+        }
+
+        let meta = StatsTunableMeta::new(StatsKind::String, json!("a"));
+        assert_eq!(meta.parse("b c").unwrap(), json!("b c"));
+
+        let meta = StatsTunableMeta::new(StatsKind::Struct("Foo".into()), json!({}));
+        assert_eq!(errno_of(&meta.parse("{}").unwrap_err()), Some(libc::EINVAL));
+    }
+}
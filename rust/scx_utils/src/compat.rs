@@ -48,6 +48,33 @@ lazy_static::lazy_static! {
         env::var("SCX_SYSFS_PREFIX").unwrap_or("".to_string());
 }
 
+#[cfg(any(test, feature = "testutils"))]
+thread_local! {
+    /// Per-thread override for ROOT_PREFIX. Thread-local so that parallel
+    /// test threads can each use their own fake sysfs tree.
+    static ROOT_PREFIX_OVERRIDE: std::cell::RefCell<Option<String>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+/// Return the effective sysfs root prefix: the test override if set, else
+/// ROOT_PREFIX.
+pub fn root_prefix() -> String {
+    #[cfg(any(test, feature = "testutils"))]
+    {
+        if let Some(prefix) = ROOT_PREFIX_OVERRIDE.with(|p| p.borrow().clone()) {
+            return prefix;
+        }
+    }
+    ROOT_PREFIX.clone()
+}
+
+/// Override the sysfs root prefix for the current thread. Set to None to
+/// restore the default.
+#[cfg(any(test, feature = "testutils"))]
+pub fn set_root_prefix_test_override(prefix: Option<&str>) {
+    ROOT_PREFIX_OVERRIDE.with(|p| *p.borrow_mut() = prefix.map(|v| v.to_string()));
+}
+
 fn load_vmlinux_btf() -> &'static mut btf {
     let btf = unsafe { btf__load_vmlinux_btf() };
     if btf.is_null() {
@@ -0,0 +1,503 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+//! # SCX CPU Domain
+//!
+//! Expressions selecting a set of CPUs from a [`Topology`], e.g. to specify
+//! the primary scheduling domain of a scheduler.
+//!
+//! An expression combines the following terms:
+//!
+//! | Term               | CPUs                                          |
+//! |--------------------|-----------------------------------------------|
+//! | `all`, `none`      | all CPUs, no CPU                              |
+//! | `node:0,2`         | CPUs of the listed NUMA nodes                 |
+//! | `llc:0-3`          | CPUs of the listed LLCs                       |
+//! | `cpus:0-7`, `0-7`  | the listed CPUs                               |
+//! | `0xff`, `ff`       | the CPUs in a hexadecimal cpumask             |
+//! | `core_type:big`    | big cores, either turbo or not                |
+//! | `core_type:little` | little cores                                  |
+//! | `turbo`            | big cores with the highest max frequency, or  |
+//! |                    | all CPUs if there are none                    |
+//! | `performance`      | alias of `core_type:big`                      |
+//! | `powersave`        | alias of `core_type:little`                   |
+//! | `smt:first`        | the first hardware thread of each core        |
+//! | `capacity>=800`    | CPUs with at least (`<=`: at most) a capacity |
+//!
+//! Terms are combined with `&` (intersection), `|` (union) and `-`
+//! (difference). A single number is rejected, as it could be either a CPU
+//! or a cpumask: use `cpus:N` or `0xN` instead.
+//!
+//! `&` binds tighter than `|` and `-`, which are evaluated left
+//! to right, and parentheses can be used for grouping:
+//!
+//!```
+//!     use scx_utils::CpuDomain;
+//!     let domain: CpuDomain = "node:0 & smt:first - cpus:0".parse().unwrap();
+//!     let domain: CpuDomain = "(turbo | capacity>=800) & llc:0-1".parse().unwrap();
+//!```
+//!
+//! A parsed expression is evaluated against a topology:
+//!
+//!```no_run
+//!     use scx_utils::CpuDomain;
+//!     use scx_utils::Topology;
+//!     let topo = Topology::new().unwrap();
+//!     let domain: CpuDomain = "powersave".parse().unwrap();
+//!     let cpumask = domain.eval(&topo).unwrap();
+//!```
+
+use crate::read_cpulist;
+use crate::CoreType;
+use crate::Cpu;
+use crate::Cpumask;
+use crate::Topology;
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use std::str::FromStr;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CpuDomain {
+    All,
+    None,
+    Nodes(Vec<usize>),
+    Llcs(Vec<usize>),
+    Cpus(Vec<usize>),
+    BigCores,
+    LittleCores,
+    TurboCores,
+    SmtFirst,
+    CapacityAtLeast(usize),
+    CapacityAtMost(usize),
+    Union(Box<CpuDomain>, Box<CpuDomain>),
+    Intersection(Box<CpuDomain>, Box<CpuDomain>),
+    Difference(Box<CpuDomain>, Box<CpuDomain>),
+}
+
+impl CpuDomain {
+    /// Returns the CPUs of @topo selected by the expression.
+    pub fn eval(&self, topo: &Topology) -> Result<Cpumask> {
+        let cpus_where = |pred: &dyn Fn(&Cpu) -> bool| -> Result<Cpumask> {
+            let mut mask = Cpumask::new();
+            for cpu in topo.all_cpus.values().filter(|cpu| pred(cpu)) {
+                mask.set_cpu(cpu.id)?;
+            }
+            Ok(mask)
+        };
+
+        match self {
+            Self::All => Ok(topo.span.clone()),
+            Self::None => Ok(Cpumask::new()),
+            Self::Nodes(ids) => {
+                let mut mask = Cpumask::new();
+                for id in ids {
+                    let Some(node) = topo.nodes.get(id) else {
+                        bail!("node {} does not exist", id);
+                    };
+                    mask |= &node.span;
+                }
+                Ok(mask)
+            }
+            Self::Llcs(ids) => {
+                let mut mask = Cpumask::new();
+                for id in ids {
+                    let Some(llc) = topo.all_llcs.get(id) else {
+                        bail!("LLC {} does not exist", id);
+                    };
+                    mask |= &llc.span;
+                }
+                Ok(mask)
+            }
+            Self::Cpus(ids) => {
+                let mut mask = Cpumask::new();
+                for &id in ids {
+                    mask.set_cpu(id)?;
+                }
+                Ok(mask)
+            }
+            Self::BigCores => cpus_where(&|cpu| matches!(cpu.core_type, CoreType::Big { .. })),
+            Self::LittleCores => cpus_where(&|cpu| cpu.core_type == CoreType::Little),
+            Self::TurboCores => {
+                // Without turbo cores, all the CPUs share the highest max frequency.
+                let mask = cpus_where(&|cpu| cpu.core_type == CoreType::Big { turbo: true })?;
+                match mask.is_empty() {
+                    true => Ok(topo.span.clone()),
+                    false => Ok(mask),
+                }
+            }
+            Self::SmtFirst => {
+                let mut mask = Cpumask::new();
+                for core in topo.all_cores.values() {
+                    if let Some(&id) = core.cpus.keys().next() {
+                        mask.set_cpu(id)?;
+                    }
+                }
+                Ok(mask)
+            }
+            Self::CapacityAtLeast(cap) => cpus_where(&|cpu| cpu.cpu_capacity >= *cap),
+            Self::CapacityAtMost(cap) => cpus_where(&|cpu| cpu.cpu_capacity <= *cap),
+            Self::Union(a, b) => Ok(a.eval(topo)?.or(&b.eval(topo)?)),
+            Self::Intersection(a, b) => Ok(a.eval(topo)?.and(&b.eval(topo)?)),
+            Self::Difference(a, b) => Ok(a.eval(topo)?.and(&b.eval(topo)?.not())),
+        }
+    }
+}
+
+impl FromStr for CpuDomain {
+    type Err = anyhow::Error;
+
+    fn from_str(expr: &str) -> Result<Self> {
+        let mut parser = Parser { expr, pos: 0 };
+        parser
+            .parse()
+            .map_err(|err| anyhow!("invalid CPU domain {:?}: {:#}", expr, err))
+    }
+}
+
+/// Recursive descent parser of CPU domain expressions:
+///
+/// ```text
+/// expr    := and ( ( '|' | '-' ) and )*
+/// and     := primary ( '&' primary )*
+/// primary := '(' expr ')' | term
+/// ```
+struct Parser<'a> {
+    expr: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn parse(&mut self) -> Result<CpuDomain> {
+        let domain = self.parse_expr()?;
+        if let Some(c) = self.peek() {
+            bail!("unexpected {:?} at offset {}", c, self.pos);
+        }
+        Ok(domain)
+    }
+
+    fn rest(&self) -> &str {
+        &self.expr[self.pos..]
+    }
+
+    /// Skips whitespace and returns the next character.
+    fn peek(&mut self) -> Option<char> {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+        self.rest().chars().next()
+    }
+
+    /// Consumes the characters at the current position matching @pred.
+    fn take_while(&mut self, pred: impl Fn(&str, char) -> bool) -> &'a str {
+        let start = self.pos;
+        while let Some(c) = self.rest().chars().next() {
+            if !pred(&self.expr[self.pos + c.len_utf8()..], c) {
+                break;
+            }
+            self.pos += c.len_utf8();
+        }
+        &self.expr[start..self.pos]
+    }
+
+    fn parse_expr(&mut self) -> Result<CpuDomain> {
+        let mut domain = self.parse_and()?;
+        loop {
+            match self.peek() {
+                Some('|') => {
+                    self.pos += 1;
+                    domain = CpuDomain::Union(Box::new(domain), Box::new(self.parse_and()?));
+                }
+                Some('-') => {
+                    self.pos += 1;
+                    domain = CpuDomain::Difference(Box::new(domain), Box::new(self.parse_and()?));
+                }
+                _ => return Ok(domain),
+            }
+        }
+    }
+
+    fn parse_and(&mut self) -> Result<CpuDomain> {
+        let mut domain = self.parse_primary()?;
+        while self.peek() == Some('&') {
+            self.pos += 1;
+            domain = CpuDomain::Intersection(Box::new(domain), Box::new(self.parse_primary()?));
+        }
+        Ok(domain)
+    }
+
+    fn parse_primary(&mut self) -> Result<CpuDomain> {
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let domain = self.parse_expr()?;
+                if self.peek() != Some(')') {
+                    bail!("missing ')' at offset {}", self.pos);
+                }
+                self.pos += 1;
+                Ok(domain)
+            }
+            Some(c) if c.is_ascii_digit() => self.parse_cpus(),
+            Some(c) if c.is_ascii_alphabetic() => self.parse_term(),
+            Some(c) => bail!("unexpected {:?} at offset {}", c, self.pos),
+            None => bail!("unexpected end of expression"),
+        }
+    }
+
+    /// Parses a CPU list like "0-3,8" or a hexadecimal cpumask like "0xf0". For
+    /// compatibility, a cpumask without the "0x" prefix is accepted as long as
+    /// it can't be read as a CPU list, e.g. "f0" but not "10".
+    fn parse_cpus(&mut self) -> Result<CpuDomain> {
+        let start = self.pos;
+        if self.rest().starts_with("0x") {
+            self.pos += 2;
+            let hex = self.take_while(|_, c| c.is_ascii_hexdigit() || c == '_');
+            return Ok(CpuDomain::Cpus(parse_hex_mask(hex)?));
+        }
+
+        let hex = self.take_while(|_, c| c.is_ascii_hexdigit() || c == '_');
+        if !hex.chars().all(|c| c.is_ascii_digit()) {
+            return Ok(CpuDomain::Cpus(parse_hex_mask(hex)?));
+        }
+
+        self.pos = start;
+        let list = self.parse_list()?;
+        let num = &self.expr[start..self.pos];
+        if !num.contains([',', '-']) {
+            bail!(
+                "ambiguous {:?} at offset {}, use cpus:{} for a CPU or 0x{} for a cpumask",
+                num,
+                start,
+                num,
+                num
+            );
+        }
+        Ok(CpuDomain::Cpus(list))
+    }
+
+    /// Parses a CPU list like "0-3,8". A '-' only belongs to the list if it's
+    /// followed by a digit, otherwise it's the difference operator.
+    fn parse_list(&mut self) -> Result<Vec<usize>> {
+        let list = self.take_while(|next, c| {
+            c.is_ascii_digit()
+                || c == ','
+                || (c == '-' && next.starts_with(|c: char| c.is_ascii_digit()))
+        });
+        if list.is_empty() {
+            bail!("missing list at offset {}", self.pos);
+        }
+        read_cpulist(list)
+    }
+
+    fn parse_term(&mut self) -> Result<CpuDomain> {
+        let start = self.pos;
+        let name = self.take_while(|_, c| c.is_ascii_alphanumeric() || c == '_');
+        self.peek();
+        let rest = self.rest();
+
+        if rest.starts_with(">=") || rest.starts_with("<=") {
+            if name != "capacity" {
+                bail!("{:?} can't be compared at offset {}", name, start);
+            }
+            let at_least = rest.starts_with(">=");
+            self.pos += 2;
+            self.peek();
+            let val = self.take_while(|_, c| c.is_ascii_digit());
+            let cap = val
+                .parse::<usize>()
+                .with_context(|| format!("invalid capacity at offset {}", self.pos))?;
+            return Ok(match at_least {
+                true => CpuDomain::CapacityAtLeast(cap),
+                false => CpuDomain::CapacityAtMost(cap),
+            });
+        }
+
+        if rest.starts_with(':') {
+            self.pos += 1;
+            return match name {
+                "node" => Ok(CpuDomain::Nodes(self.parse_list()?)),
+                "llc" => Ok(CpuDomain::Llcs(self.parse_list()?)),
+                "cpus" => Ok(CpuDomain::Cpus(self.parse_list()?)),
+                "core_type" => match self.take_while(|_, c| c.is_ascii_alphanumeric()) {
+                    "big" => Ok(CpuDomain::BigCores),
+                    "little" => Ok(CpuDomain::LittleCores),
+                    val => bail!("invalid core type {:?}, expected big or little", val),
+                },
+                "smt" => match self.take_while(|_, c| c.is_ascii_alphanumeric()) {
+                    "first" => Ok(CpuDomain::SmtFirst),
+                    val => bail!("invalid SMT selector {:?}, expected first", val),
+                },
+                _ => bail!("unknown term {:?} at offset {}", name, start),
+            };
+        }
+
+        match name {
+            "all" => Ok(CpuDomain::All),
+            "none" => Ok(CpuDomain::None),
+            "turbo" => Ok(CpuDomain::TurboCores),
+            "performance" => Ok(CpuDomain::BigCores),
+            "powersave" => Ok(CpuDomain::LittleCores),
+            hex if hex.chars().all(|c| c.is_ascii_hexdigit() || c == '_') => {
+                Ok(CpuDomain::Cpus(parse_hex_mask(hex)?))
+            }
+            _ => bail!("unknown term {:?} at offset {}", name, start),
+        }
+    }
+}
+
+/// Returns the CPUs set in a hexadecimal cpumask without the "0x" prefix.
+fn parse_hex_mask(hex: &str) -> Result<Vec<usize>> {
+    let digits: Vec<u32> = hex.chars().filter_map(|c| c.to_digit(16)).collect();
+    if digits.is_empty() {
+        bail!("empty cpumask");
+    }
+    let mut cpus = vec![];
+    for (i, digit) in digits.iter().rev().enumerate() {
+        for bit in 0..4 {
+            if digit & (1 << bit) != 0 {
+                cpus.push(i * 4 + bit);
+            }
+        }
+    }
+    Ok(cpus)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::*;
+    use std::sync::Arc;
+
+    fn eval(topo: &Topology, expr: &str) -> Vec<usize> {
+        let domain: CpuDomain = expr.parse().unwrap();
+        domain.eval(topo).unwrap().iter().collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        use CpuDomain::*;
+
+        assert_eq!("all".parse::<CpuDomain>().unwrap(), All);
+        assert_eq!(
+            "cpus:0-2,5".parse::<CpuDomain>().unwrap(),
+            Cpus(vec![0, 1, 2, 5])
+        );
+        assert_eq!("0x31".parse::<CpuDomain>().unwrap(), Cpus(vec![0, 4, 5]));
+        assert_eq!(
+            "3f0".parse::<CpuDomain>().unwrap(),
+            "0x3f0".parse().unwrap()
+        );
+        assert_eq!(
+            "f_00".parse::<CpuDomain>().unwrap(),
+            "0xf00".parse().unwrap()
+        );
+        assert_eq!("cpus:10".parse::<CpuDomain>().unwrap(), Cpus(vec![10]));
+        assert_eq!("10-10".parse::<CpuDomain>().unwrap(), Cpus(vec![10]));
+        assert_eq!(
+            "capacity >= 800".parse::<CpuDomain>().unwrap(),
+            CapacityAtLeast(800)
+        );
+
+        // '&' binds tighter than '|' and '-', which are left associative.
+        assert_eq!(
+            "node:0 | llc:1 & turbo - cpus:3"
+                .parse::<CpuDomain>()
+                .unwrap(),
+            Difference(
+                Box::new(Union(
+                    Box::new(Nodes(vec![0])),
+                    Box::new(Intersection(Box::new(Llcs(vec![1])), Box::new(TurboCores))),
+                )),
+                Box::new(Cpus(vec![3])),
+            )
+        );
+        assert_eq!(
+            "llc:0-1-cpus:1".parse::<CpuDomain>().unwrap(),
+            Difference(Box::new(Llcs(vec![0, 1])), Box::new(Cpus(vec![1])))
+        );
+
+        for expr in [
+            "",
+            "node",
+            "node:",
+            "node:x",
+            "core_type:medium",
+            "smt:last",
+            "capacity>=",
+            "llc>=2",
+            "foo",
+            "(all",
+            "all)",
+            "all &",
+            "all | | none",
+        ] {
+            assert!(expr.parse::<CpuDomain>().is_err(), "{:?}", expr);
+        }
+
+        // A single number could be either a CPU or a cpumask.
+        for expr in ["0", "10", "all - 3"] {
+            let err = expr.parse::<CpuDomain>().unwrap_err().to_string();
+            assert!(err.contains("ambiguous"), "{:?}: {}", expr, err);
+            assert!(err.contains("cpus:"), "{:?}: {}", expr, err);
+        }
+    }
+
+    #[test]
+    fn test_eval() {
+        // 2 nodes, 2 LLCs/node, 2 cores/LLC, 2 HTs/core = 16 CPUs
+        let (topo, total) = make_test_topo(2, 2, 2, 2);
+        assert_eq!(total, 16);
+
+        assert_eq!(eval(&topo, "all").len(), 16);
+        assert!(eval(&topo, "none").is_empty());
+        assert_eq!(eval(&topo, "node:1"), (8..16).collect::<Vec<_>>());
+        assert_eq!(eval(&topo, "llc:1-2"), (4..12).collect::<Vec<_>>());
+        assert_eq!(eval(&topo, "smt:first & node:0"), [0, 2, 4, 6]);
+        assert_eq!(eval(&topo, "llc:0 | cpus:15"), [0, 1, 2, 3, 15]);
+        assert_eq!(eval(&topo, "node:0 - llc:0 - cpus:7"), [4, 5, 6]);
+        assert_eq!(eval(&topo, "node:0 - (llc:0 | cpus:7)"), [4, 5, 6]);
+        assert_eq!(eval(&topo, "(0-3 | 0xf00) & smt:first"), [0, 2, 8, 10]);
+
+        assert!("node:2".parse::<CpuDomain>().unwrap().eval(&topo).is_err());
+        assert!("llc:4".parse::<CpuDomain>().unwrap().eval(&topo).is_err());
+        assert!("cpus:16".parse::<CpuDomain>().unwrap().eval(&topo).is_err());
+    }
+
+    #[test]
+    fn test_eval_core_types() {
+        let (mut topo, _) = make_test_topo(1, 1, 4, 2);
+
+        // Core 0 is a turbo big core, core 1 a big core and cores 2-3 are
+        // little cores.
+        for cpu in topo.all_cpus.values_mut() {
+            let mut new = (**cpu).clone();
+            (new.core_type, new.cpu_capacity) = match new.core_id {
+                0 => (CoreType::Big { turbo: true }, 1024),
+                1 => (CoreType::Big { turbo: false }, 900),
+                _ => (CoreType::Little, 400),
+            };
+            *cpu = Arc::new(new);
+        }
+
+        assert_eq!(eval(&topo, "turbo"), [0, 1]);
+        assert_eq!(eval(&topo, "core_type:big"), [0, 1, 2, 3]);
+        assert_eq!(eval(&topo, "performance"), [0, 1, 2, 3]);
+        assert_eq!(eval(&topo, "core_type:little"), [4, 5, 6, 7]);
+        assert_eq!(eval(&topo, "powersave & smt:first"), [4, 6]);
+        assert_eq!(eval(&topo, "capacity>=900"), [0, 1, 2, 3]);
+        assert_eq!(eval(&topo, "capacity<=900 - powersave"), [2, 3]);
+
+        // Without turbo cores, "turbo" selects all the CPUs.
+        for cpu in topo.all_cpus.values_mut() {
+            let mut new = (**cpu).clone();
+            if new.core_type == (CoreType::Big { turbo: true }) {
+                new.core_type = CoreType::Big { turbo: false };
+            }
+            *cpu = Arc::new(new);
+        }
+        assert_eq!(eval(&topo, "turbo"), (0..8).collect::<Vec<_>>());
+        assert_eq!(eval(&topo, "turbo & powersave"), [4, 5, 6, 7]);
+    }
+}
@@ -60,31 +60,52 @@ use anyhow::bail;
 use anyhow::Context;
 use anyhow::Result;
 use bitvec::prelude::*;
+use serde::de::Error as _;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
 use sscanf::sscanf;
 use std::fmt;
 use std::ops::BitAndAssign;
 use std::ops::BitOrAssign;
 use std::ops::BitXorAssign;
 
-#[cfg(any(test, feature = "testutils"))]
 thread_local! {
     /// Per-thread override for Cpumask width. 0 means use *NR_CPU_IDS.
     /// Thread-local so parallel test threads don't interfere.
     static MASK_WIDTH_OVERRIDE: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
 }
 
-/// Return the effective Cpumask width: the test override if set, else *NR_CPU_IDS.
+/// Return the effective Cpumask width: the override if set, else *NR_CPU_IDS.
 fn mask_width() -> usize {
-    #[cfg(any(test, feature = "testutils"))]
-    {
-        let ovr = MASK_WIDTH_OVERRIDE.with(|c| c.get());
-        if ovr > 0 {
-            return ovr;
-        }
+    let ovr = MASK_WIDTH_OVERRIDE.with(|c| c.get());
+    if ovr > 0 {
+        return ovr;
     }
     *NR_CPU_IDS
 }
 
+/// Restores the previous Cpumask width override when dropped.
+struct MaskWidthGuard {
+    prev: usize,
+}
+
+impl Drop for MaskWidthGuard {
+    fn drop(&mut self) {
+        MASK_WIDTH_OVERRIDE.with(|c| c.set(self.prev));
+    }
+}
+
+/// Run `f` with Cpumasks created on this thread `width` CPUs wide. The
+/// previous width is restored afterwards, also if `f` panics.
+pub(crate) fn with_cpumask_width<R>(width: usize, f: impl FnOnce() -> R) -> R {
+    let _guard = MaskWidthGuard {
+        prev: MASK_WIDTH_OVERRIDE.with(|c| c.replace(width)),
+    };
+    f()
+}
+
 /// Override the Cpumask width for the current thread. All subsequent
 /// Cpumask::new(), from_str(), and related calls on this thread will use
 /// this width instead of NR_CPU_IDS. Set to 0 to restore the default.
@@ -100,8 +121,8 @@ pub struct Cpumask {
 
 impl Cpumask {
     fn check_cpu(&self, cpu: usize) -> Result<()> {
-        if cpu >= mask_width() {
-            bail!("Invalid CPU {} passed, max {}", cpu, mask_width());
+        if cpu >= self.len() {
+            bail!("Invalid CPU {} passed, max {}", cpu, self.len());
         }
 
         Ok(())
@@ -209,13 +230,16 @@ impl Cpumask {
         ranges.join(",")
     }
 
+    /// Build a Cpumask from raw u64 words. Bits beyond the Cpumask width
+    /// are dropped and missing ones are cleared.
     pub fn from_vec(vec: Vec<u64>) -> Self {
-        Self {
-            mask: BitVec::from_vec(vec),
-        }
+        Self::from_bitvec(BitVec::from_vec(vec))
     }
 
-    pub fn from_bitvec(bitvec: BitVec<u64, Lsb0>) -> Self {
+    /// Build a Cpumask from a BitVec, resized to the Cpumask width.
+    pub fn from_bitvec(mut bitvec: BitVec<u64, Lsb0>) -> Self {
+        bitvec.resize(mask_width(), false);
+        bitvec.set_uninitialized(false);
         Self { mask: bitvec }
     }
 
@@ -281,12 +305,12 @@ impl Cpumask {
 
     /// Return true if the Cpumask has all bits set, false otherwise.
     pub fn is_full(&self) -> bool {
-        self.mask.count_ones() == mask_width()
+        self.mask.count_ones() == self.len()
     }
 
     /// The total size of the cpumask.
     pub fn len(&self) -> usize {
-        mask_width()
+        self.mask.len()
     }
 
     /// Create a Cpumask that is the negation of the current Cpumask.
@@ -371,10 +395,10 @@ impl Cpumask {
             .collect();
 
         // Throw out possible stray from u64 -> u32.
-        masks.truncate((mask_width()).div_ceil(32));
+        masks.truncate(self.len().div_ceil(32));
 
-        // Print the highest 32bit. Trim digits beyond mask_width().
-        let width = match (mask_width()).div_ceil(4) % 8 {
+        // Print the highest 32bit. Trim digits beyond len().
+        let width = match self.len().div_ceil(4) % 8 {
             0 => 8,
             v => v,
         };
@@ -427,7 +451,7 @@ impl Iterator for CpumaskIterator<'_> {
     type Item = usize;
 
     fn next(&mut self) -> Option<Self::Item> {
-        while self.index < mask_width() {
+        while self.index < self.mask.mask.len() {
             let index = self.index;
             self.index += 1;
             let bit_val = self.mask.test_cpu(index);
@@ -458,6 +482,29 @@ impl fmt::UpperHex for Cpumask {
     }
 }
 
+/// Cpumasks are serialized as CPU list strings, e.g. "0-7,16-23", so that
+/// the serialized form doesn't depend on the width of the mask.
+impl Serialize for Cpumask {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_cpulist())
+    }
+}
+
+/// Deserialization fails if the CPU list contains a CPU which doesn't fit
+/// in the Cpumask width of the running system.
+impl<'de> Deserialize<'de> for Cpumask {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let cpulist = String::deserialize(deserializer)?;
+        let mut mask = Cpumask::new();
+        if cpulist.trim() != "none" {
+            for cpu in read_cpulist(&cpulist).map_err(D::Error::custom)? {
+                mask.set_cpu(cpu).map_err(D::Error::custom)?;
+            }
+        }
+        Ok(mask)
+    }
+}
+
 impl BitAndAssign<&Self> for Cpumask {
     fn bitand_assign(&mut self, rhs: &Self) {
         self.mask &= &rhs.mask;
@@ -547,4 +594,45 @@ mod tests {
         let mask = Cpumask::from_cpulist(original).unwrap();
         assert_eq!(mask.to_cpulist(), original);
     }
+
+    #[test]
+    fn test_serde_roundtrip() {
+        set_cpumask_test_width(32);
+        let mask = Cpumask::from_cpulist("0-3,8-11,16").unwrap();
+        let json = serde_json::to_string(&mask).unwrap();
+        assert_eq!(json, "\"0-3,8-11,16\"");
+        assert_eq!(serde_json::from_str::<Cpumask>(&json).unwrap(), mask);
+
+        let empty = Cpumask::new();
+        let json = serde_json::to_string(&empty).unwrap();
+        assert_eq!(serde_json::from_str::<Cpumask>(&json).unwrap(), empty);
+
+        assert!(serde_json::from_str::<Cpumask>("\"0-32\"").is_err());
+    }
+
+    #[test]
+    fn test_with_cpumask_width() {
+        set_cpumask_test_width(16);
+        let wide = with_cpumask_width(72, || Cpumask::from_cpulist("0,70").unwrap());
+        assert_eq!(Cpumask::new().len(), 16);
+
+        // The mask keeps its own width after the override is restored.
+        assert_eq!(wide.len(), 72);
+        assert_eq!(wide.to_cpulist(), "0,70");
+        assert_eq!(format!("{wide:x}"), "40,00000000,00000001");
+        assert!(wide.test_cpu(70));
+
+        let res = std::panic::catch_unwind(|| with_cpumask_width(72, || panic!()));
+        assert!(res.is_err());
+        assert_eq!(Cpumask::new().len(), 16);
+    }
+
+    #[test]
+    fn test_from_vec() {
+        set_cpumask_test_width(16);
+        let mask = Cpumask::from_vec(vec![0x1_0001, 0]);
+        assert_eq!(mask.len(), 16);
+        assert_eq!(mask.to_cpulist(), "0");
+        assert_eq!(format!("{mask:x}"), "0001");
+    }
 }
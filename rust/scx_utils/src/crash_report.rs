@@ -0,0 +1,638 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+//! # Crash Reports
+//!
+//! When a scheduler exits with an error, `UserExitInfo::report()` prints the
+//! exit reason and the kernel's debug dump to stderr. This module parses the
+//! dump into per-CPU state, runnable tasks, DSQ contents and the backtrace of
+//! the exit, and saves it together with the scheduler version, command line
+//! and host topology as a JSON report in a crash directory.
+//!
+//! Reports are saved automatically by `uei_report!()` on error exits. The
+//! directory defaults to `/var/lib/scx/crash` and can be changed with
+//! `SCX_CRASH_DIR`, set to an empty string to disable crash reports. Only the
+//! newest `SCX_CRASH_MAX_REPORTS` (default 16) reports are kept.
+//!
+//! Use the `scx_crash` tool to list, show and diff the saved reports.
+
+use crate::Topology;
+use crate::NR_CPU_IDS;
+use anyhow::Context;
+use anyhow::Result;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+/// Version of the report format, bumped on incompatible changes.
+pub const CRASH_REPORT_VERSION: u32 = 1;
+
+/// Default crash report directory.
+pub const CRASH_DIR: &str = "/var/lib/scx/crash";
+
+/// Default number of reports kept in the crash directory.
+pub const DEFAULT_MAX_REPORTS: usize = 16;
+
+/// A task from the runnable list of a CPU in the debug dump.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TaskState {
+    pub pid: i32,
+    pub comm: String,
+    /// Task state character, e.g. 'R'.
+    pub state: char,
+    /// Whether this is the task currently running on the CPU.
+    pub curr: bool,
+    /// Milliseconds since the task became runnable, negative if it has been
+    /// waiting.
+    pub runnable_ms: i64,
+    /// The key=value fields of the task, e.g. "dsq_id" or "scx_state/flags".
+    pub fields: BTreeMap<String, String>,
+    pub backtrace: Vec<String>,
+    /// Any other lines, e.g. the output of ops.dump_task().
+    pub extra: Vec<String>,
+}
+
+impl TaskState {
+    /// The DSQ the task is queued on, None if it isn't queued.
+    pub fn dsq_id(&self) -> Option<&str> {
+        self.fields
+            .get("dsq_id")
+            .map(|s| s.as_str())
+            .filter(|s| s.starts_with("0x"))
+    }
+}
+
+/// The state of a CPU in the debug dump.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CpuState {
+    pub cpu: u32,
+    /// The key=value fields of the CPU, e.g. "nr_run" or "flags".
+    pub fields: BTreeMap<String, String>,
+    pub curr_pid: i32,
+    pub curr_comm: String,
+    pub curr_class: String,
+    pub tasks: Vec<TaskState>,
+    /// Any other lines, e.g. cpus_to_kick or the output of ops.dump_cpu().
+    pub extra: Vec<String>,
+}
+
+/// A task queued on a DSQ.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DsqTask {
+    pub cpu: u32,
+    pub pid: i32,
+    pub comm: String,
+    pub runnable_ms: i64,
+}
+
+/// The tasks queued on a DSQ, collected from the runnable tasks of all CPUs.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DsqState {
+    pub dsq_id: String,
+    pub tasks: Vec<DsqTask>,
+}
+
+/// Parsed sched_ext debug dump. Lines which couldn't be parsed are only kept
+/// in `raw`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CrashDump {
+    /// The "comm[pid] triggered exit kind N:" line.
+    pub trigger: Option<String>,
+    /// Backtrace of the exit, e.g. the BPF program calling scx_bpf_error().
+    pub backtrace: Vec<String>,
+    /// Output of the scheduler's ops.dump().
+    pub sched_dump: Vec<String>,
+    pub cpus: Vec<CpuState>,
+    pub dsqs: Vec<DsqState>,
+    pub event_counters: BTreeMap<String, u64>,
+    pub raw: String,
+}
+
+#[derive(PartialEq)]
+enum Section {
+    Header,
+    Backtrace,
+    SchedDump,
+    Cpus,
+    Events,
+}
+
+/// Returns the key=value pairs of a line, None if any token isn't one.
+fn parse_fields(line: &str) -> Option<Vec<(String, String)>> {
+    line.split_whitespace()
+        .map(|tok| {
+            tok.split_once('=')
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+        })
+        .collect()
+}
+
+/// Parses "comm[pid]" at the end of @s.
+fn parse_comm_pid(s: &str) -> Option<(String, i32)> {
+    let s = s.strip_suffix(']')?;
+    let (comm, pid) = s.rsplit_once('[')?;
+    Some((comm.to_string(), pid.parse().ok()?))
+}
+
+/// Parses a task header, e.g. " *R comm[pid] +0ms".
+fn parse_task_header(line: &str) -> Option<TaskState> {
+    let rest = line.strip_prefix(' ')?;
+    let mut chars = rest.chars();
+    let curr = match chars.next()? {
+        '*' => true,
+        ' ' => false,
+        _ => return None,
+    };
+    let state = chars.next().filter(|c| c.is_ascii_alphabetic())?;
+    let rest = chars.as_str().strip_prefix(' ')?;
+    let (task, ms) = rest.rsplit_once(' ')?;
+    let runnable_ms = ms.strip_suffix("ms")?.parse().ok()?;
+    let (comm, pid) = parse_comm_pid(task)?;
+    Some(TaskState {
+        pid,
+        comm,
+        state,
+        curr,
+        runnable_ms,
+        ..Default::default()
+    })
+}
+
+/// Parses a CPU header, e.g. "CPU 3   : nr_run=1 flags=0x1 ...".
+fn parse_cpu_header(line: &str) -> Option<CpuState> {
+    let (cpu, fields) = line.strip_prefix("CPU ")?.split_once(':')?;
+    Some(CpuState {
+        cpu: cpu.trim().parse().ok()?,
+        fields: parse_fields(fields)?.into_iter().collect(),
+        ..Default::default()
+    })
+}
+
+/// Parses the "curr=comm[pid] class=..." line of a CPU.
+fn parse_cpu_curr(line: &str, cpu: &mut CpuState) -> bool {
+    let Some((curr, class)) = line
+        .trim()
+        .strip_prefix("curr=")
+        .and_then(|s| s.rsplit_once(" class="))
+    else {
+        return false;
+    };
+    let Some((comm, pid)) = parse_comm_pid(curr) else {
+        return false;
+    };
+    cpu.curr_comm = comm;
+    cpu.curr_pid = pid;
+    cpu.curr_class = class.to_string();
+    true
+}
+
+/// Returns whether the line looks like a stack frame, e.g.
+/// "  scx_watchdog_workfn+0x154/0x1e0".
+fn is_stack_frame(line: &str) -> bool {
+    line.split_whitespace().next().is_some_and(|sym| {
+        sym.rsplit_once('+')
+            .is_some_and(|(_, off)| off.starts_with("0x") && off.contains('/'))
+    })
+}
+
+impl CrashDump {
+    /// Parses the debug dump text of a sched_ext exit.
+    pub fn parse(raw: &str) -> Self {
+        let mut dump = CrashDump {
+            raw: raw.to_string(),
+            ..Default::default()
+        };
+        let mut section = Section::Header;
+        let mut cpu: Option<CpuState> = None;
+        let mut task: Option<TaskState> = None;
+
+        for line in raw.lines() {
+            let trimmed = line.trim();
+
+            match trimmed {
+                "Backtrace:" => {
+                    section = Section::Backtrace;
+                    continue;
+                }
+                "CPU states" => {
+                    section = Section::Cpus;
+                    continue;
+                }
+                "Event counters" => {
+                    Self::finish_cpu(&mut dump, &mut cpu, &mut task);
+                    section = Section::Events;
+                    continue;
+                }
+                _ if !trimmed.is_empty() && trimmed.chars().all(|c| c == '-') => continue,
+                _ => {}
+            }
+
+            match section {
+                Section::Header => {
+                    if dump.trigger.is_none() && trimmed.contains("triggered exit kind") {
+                        dump.trigger = Some(trimmed.to_string());
+                    }
+                }
+                Section::Backtrace => {
+                    if line.starts_with(' ') && !trimmed.is_empty() {
+                        dump.backtrace.push(trimmed.to_string());
+                    } else if !dump.backtrace.is_empty() {
+                        section = Section::SchedDump;
+                        if !trimmed.is_empty() {
+                            dump.sched_dump.push(line.to_string());
+                        }
+                    }
+                }
+                Section::SchedDump => {
+                    if !trimmed.is_empty() {
+                        dump.sched_dump.push(line.to_string());
+                    }
+                }
+                Section::Cpus => {
+                    if trimmed.is_empty() {
+                        continue;
+                    }
+                    if let Some(new_cpu) = parse_cpu_header(line) {
+                        Self::finish_cpu(&mut dump, &mut cpu, &mut task);
+                        cpu = Some(new_cpu);
+                        continue;
+                    }
+                    let Some(cur_cpu) = cpu.as_mut() else {
+                        continue;
+                    };
+                    if let Some(new_task) = parse_task_header(line) {
+                        if let Some(t) = task.take() {
+                            cur_cpu.tasks.push(t);
+                        }
+                        task = Some(new_task);
+                    } else if let Some(t) = task.as_mut() {
+                        if is_stack_frame(trimmed) {
+                            t.backtrace.push(trimmed.to_string());
+                        } else if let Some(fields) = parse_fields(trimmed) {
+                            t.fields.extend(fields);
+                        } else {
+                            t.extra.push(trimmed.to_string());
+                        }
+                    } else if !parse_cpu_curr(line, cur_cpu) {
+                        cur_cpu.extra.push(trimmed.to_string());
+                    }
+                }
+                Section::Events => {
+                    if let Some((name, val)) = trimmed.split_once(':') {
+                        if let Ok(val) = val.trim().parse() {
+                            dump.event_counters.insert(name.trim().to_string(), val);
+                        }
+                    }
+                }
+            }
+        }
+        Self::finish_cpu(&mut dump, &mut cpu, &mut task);
+
+        let mut dsqs: BTreeMap<String, Vec<DsqTask>> = BTreeMap::new();
+        for cpu in dump.cpus.iter() {
+            for task in cpu.tasks.iter() {
+                if let Some(dsq_id) = task.dsq_id() {
+                    dsqs.entry(dsq_id.to_string()).or_default().push(DsqTask {
+                        cpu: cpu.cpu,
+                        pid: task.pid,
+                        comm: task.comm.clone(),
+                        runnable_ms: task.runnable_ms,
+                    });
+                }
+            }
+        }
+        dump.dsqs = dsqs
+            .into_iter()
+            .map(|(dsq_id, tasks)| DsqState { dsq_id, tasks })
+            .collect();
+
+        dump
+    }
+
+    fn finish_cpu(dump: &mut CrashDump, cpu: &mut Option<CpuState>, task: &mut Option<TaskState>) {
+        if let Some(mut c) = cpu.take() {
+            if let Some(t) = task.take() {
+                c.tasks.push(t);
+            }
+            dump.cpus.push(c);
+        }
+    }
+}
+
+/// A crash report of a scheduler exit.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CrashReport {
+    pub format_version: u32,
+    /// Seconds since the epoch.
+    pub timestamp: u64,
+    pub hostname: String,
+    pub kernel: String,
+    pub scheduler: String,
+    pub version: String,
+    pub pid: u32,
+    pub cmdline: Vec<String>,
+    pub exit_kind: i32,
+    pub exit_kind_name: String,
+    pub exit_code: i64,
+    pub reason: Option<String>,
+    pub msg: Option<String>,
+    /// Serialized Topology, see topology().
+    pub topology: Option<serde_json::Value>,
+    /// Cpumask width of the host, used to load the topology.
+    pub nr_cpu_ids: usize,
+    pub dump: Option<CrashDump>,
+}
+
+fn read_proc_string(path: &str) -> String {
+    std::fs::read_to_string(path)
+        .map(|s| s.trim().to_string())
+        .unwrap_or_default()
+}
+
+impl CrashReport {
+    /// Creates a report for the current process without exit information.
+    /// The host topology is included if it can be read.
+    pub fn new(scheduler: &str, version: &str) -> Self {
+        Self {
+            format_version: CRASH_REPORT_VERSION,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            hostname: read_proc_string("/proc/sys/kernel/hostname"),
+            kernel: read_proc_string("/proc/sys/kernel/osrelease"),
+            scheduler: scheduler.to_string(),
+            version: version.to_string(),
+            pid: std::process::id(),
+            cmdline: std::env::args().collect(),
+            exit_kind: 0,
+            exit_kind_name: String::new(),
+            exit_code: 0,
+            reason: None,
+            msg: None,
+            topology: Topology::new()
+                .ok()
+                .and_then(|topo| serde_json::to_value(&topo).ok()),
+            nr_cpu_ids: *NR_CPU_IDS,
+            dump: None,
+        }
+    }
+
+    /// Loads a report saved by CrashStore::save().
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = std::fs::read_to_string(path).with_context(|| format!("reading {path:?}"))?;
+        let report: Self =
+            serde_json::from_str(&json).with_context(|| format!("parsing {path:?}"))?;
+        if report.format_version > CRASH_REPORT_VERSION {
+            anyhow::bail!(
+                "{:?} has report format version {}, only up to {} is supported",
+                path,
+                report.format_version,
+                CRASH_REPORT_VERSION
+            );
+        }
+        Ok(report)
+    }
+
+    /// Returns the host topology at the time of the exit. The topology may be
+    /// of a machine other than the running one.
+    pub fn topology(&self) -> Result<Option<Topology>> {
+        self.topology
+            .as_ref()
+            .map(|v| Topology::from_json_with_width(&v.to_string(), self.nr_cpu_ids))
+            .transpose()
+    }
+
+    /// The file name of the report in a crash directory. Names sort by time.
+    pub fn file_name(&self) -> String {
+        let sched: String = self
+            .scheduler
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        format!("{:010}-{}-{}.json", self.timestamp, sched, self.pid)
+    }
+}
+
+/// A crash directory keeping the newest reports.
+#[derive(Clone, Debug)]
+pub struct CrashStore {
+    dir: PathBuf,
+    max_reports: usize,
+}
+
+impl CrashStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            max_reports: DEFAULT_MAX_REPORTS,
+        }
+    }
+
+    /// Keep at most @max_reports reports, 0 keeps all of them.
+    pub fn max_reports(mut self, max_reports: usize) -> Self {
+        self.max_reports = max_reports;
+        self
+    }
+
+    /// The crash store configured by SCX_CRASH_DIR and SCX_CRASH_MAX_REPORTS.
+    /// Returns None if crash reports are disabled.
+    pub fn from_env() -> Option<Self> {
+        let dir = std::env::var("SCX_CRASH_DIR").unwrap_or_else(|_| CRASH_DIR.to_string());
+        if dir.is_empty() {
+            return None;
+        }
+        let max_reports = std::env::var("SCX_CRASH_MAX_REPORTS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_REPORTS);
+        Some(Self::new(dir).max_reports(max_reports))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Writes the report into the crash directory, removing the oldest
+    /// reports beyond the limit. Returns the path of the report.
+    pub fn save(&self, report: &CrashReport) -> Result<PathBuf> {
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("creating crash directory {:?}", self.dir))?;
+
+        let path = self.dir.join(report.file_name());
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(report)?)
+            .with_context(|| format!("writing {tmp:?}"))?;
+        std::fs::rename(&tmp, &path).with_context(|| format!("renaming {tmp:?}"))?;
+
+        self.rotate()?;
+        Ok(path)
+    }
+
+    /// Returns the paths of all reports, oldest first.
+    pub fn list(&self) -> Result<Vec<PathBuf>> {
+        let mut paths = vec![];
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(paths),
+            Err(e) => return Err(e).with_context(|| format!("reading {:?}", self.dir)),
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        Ok(paths)
+    }
+
+    fn rotate(&self) -> Result<()> {
+        if self.max_reports == 0 {
+            return Ok(());
+        }
+        let paths = self.list()?;
+        let nr_old = paths.len().saturating_sub(self.max_reports);
+        for path in &paths[..nr_old] {
+            std::fs::remove_file(path).with_context(|| format!("removing {path:?}"))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DUMP: &str = "\
+kworker/u16:3[123] triggered exit kind 1026:
+  runnable task stall (stress[45] failed to run for 30.512s)
+
+Backtrace:
+  scx_watchdog_workfn+0x154/0x1e0
+  process_one_work+0x18a/0x350
+
+my_sched: nr_queued=3
+
+CPU states
+----------
+
+CPU 0   : nr_run=2 flags=0x1 cpu_rel=0 ops_qseq=1234 pnt_seq=567
+          curr=spinner[999] class=ext_sched_class
+  cpus_to_kick   : 0002
+
+ *R spinner[999] +0ms
+      scx_state/flags=3/0x5 dsq_flags=0x0 ops_state/qseq=0/0
+      sticky/holding_cpu=-1/-1 dsq_id=(n/a)
+      dsq_vtime=0 slice=20000000 weight=100
+      cpus=ff
+
+  R stress[45] -30512ms
+      scx_state/flags=3/0x1 dsq_flags=0x1 ops_state/qseq=0/0
+      sticky/holding_cpu=-1/-1 dsq_id=0x8000000000000002
+      dsq_vtime=0 slice=20000000 weight=100
+      cpus=01
+
+    __schedule+0x3c4/0xa50
+    schedule+0x28/0x90
+
+CPU 1   : nr_run=1 flags=0x0 cpu_rel=0 ops_qseq=88 pnt_seq=90
+          curr=swapper/1[0] class=idle_sched_class
+
+  R worker thread[77] -12ms
+      sticky/holding_cpu=-1/-1 dsq_id=0x8000000000000002
+      cpus=02
+
+Event counters
+--------------
+  SCX_EV_SELECT_CPU_FALLBACK: 3
+  SCX_EV_DISPATCH_KEEP_LAST: 0
+";
+
+    #[test]
+    fn test_parse_dump() {
+        let dump = CrashDump::parse(DUMP);
+
+        assert_eq!(
+            dump.trigger.as_deref(),
+            Some("kworker/u16:3[123] triggered exit kind 1026:")
+        );
+        assert_eq!(
+            dump.backtrace,
+            vec![
+                "scx_watchdog_workfn+0x154/0x1e0",
+                "process_one_work+0x18a/0x350"
+            ]
+        );
+        assert_eq!(dump.sched_dump, vec!["my_sched: nr_queued=3"]);
+
+        assert_eq!(dump.cpus.len(), 2);
+        let cpu0 = &dump.cpus[0];
+        assert_eq!(cpu0.cpu, 0);
+        assert_eq!(cpu0.fields["nr_run"], "2");
+        assert_eq!(cpu0.curr_comm, "spinner");
+        assert_eq!(cpu0.curr_pid, 999);
+        assert_eq!(cpu0.curr_class, "ext_sched_class");
+        assert_eq!(cpu0.extra, vec!["cpus_to_kick   : 0002"]);
+        assert_eq!(cpu0.tasks.len(), 2);
+        assert!(cpu0.tasks[0].curr);
+        assert_eq!(cpu0.tasks[0].dsq_id(), None);
+
+        let stress = &cpu0.tasks[1];
+        assert!(!stress.curr);
+        assert_eq!(stress.state, 'R');
+        assert_eq!(stress.pid, 45);
+        assert_eq!(stress.runnable_ms, -30512);
+        assert_eq!(stress.fields["scx_state/flags"], "3/0x1");
+        assert_eq!(stress.fields["cpus"], "01");
+        assert_eq!(
+            stress.backtrace,
+            vec!["__schedule+0x3c4/0xa50", "schedule+0x28/0x90"]
+        );
+
+        assert_eq!(dump.cpus[1].curr_comm, "swapper/1");
+        assert_eq!(dump.cpus[1].tasks[0].comm, "worker thread");
+
+        assert_eq!(dump.dsqs.len(), 1);
+        assert_eq!(dump.dsqs[0].dsq_id, "0x8000000000000002");
+        let pids: Vec<i32> = dump.dsqs[0].tasks.iter().map(|t| t.pid).collect();
+        assert_eq!(pids, vec![45, 77]);
+
+        assert_eq!(dump.event_counters["SCX_EV_SELECT_CPU_FALLBACK"], 3);
+        assert_eq!(dump.event_counters.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_garbage() {
+        let dump = CrashDump::parse("not a dump\nCPU states\nCPU x: y\n  R a[b] c\n");
+        assert!(dump.cpus.is_empty());
+        assert!(dump.trigger.is_none());
+    }
+
+    #[test]
+    fn test_store_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = CrashStore::new(dir.path()).max_reports(2);
+
+        for ts in 0..3 {
+            let mut report = CrashReport::new("scx_test", "1.0.0");
+            report.dump = Some(CrashDump::parse(DUMP));
+            report.timestamp = 1000 + ts;
+            store.save(&report).unwrap();
+        }
+
+        let paths = store.list().unwrap();
+        assert_eq!(paths.len(), 2);
+        let loaded = CrashReport::load(&paths[0]).unwrap();
+        assert_eq!(loaded.timestamp, 1001);
+        assert_eq!(loaded.scheduler, "scx_test");
+        assert_eq!(loaded.dump.unwrap().dsqs[0].tasks.len(), 2);
+        assert_eq!(CrashReport::load(&paths[1]).unwrap().timestamp, 1002);
+    }
+}
@@ -10,15 +10,44 @@
 //!
 //! A crate that allows schedulers to inspect and model the host's energy model,
 //! which is loaded from debugfs.
+//!
+//! Most x86 servers and virtual machines don't register an energy model. On
+//! those, [`EnergyModel::synthesize()`] builds one from the cpufreq policies,
+//! the CPU capacities and a [`PowerCurve`], and [`EnergyModel::from_json()`]
+//! loads one supplied by the user:
+//!
+//!```json
+//!     {
+//!       "perf_domains": [
+//!         {
+//!           "cpus": "0-3",
+//!           "perf_states": [
+//!             { "frequency": 1000000, "performance": 256, "power": 120000 },
+//!             { "frequency": 2000000, "performance": 512, "power": 600000 }
+//!           ]
+//!         }
+//!       ]
+//!     }
+//!```
+//!
+//! Either way, [`EnergyModel::placement_cost()`] estimates the energy cost of
+//! running a CPU at a given utilization, so that schedulers can compare
+//! placements the same way on all hardware.
 
 use crate::compat;
+use crate::compat::root_prefix;
 use crate::compat::ROOT_PREFIX;
 use crate::misc::read_from_file;
+use crate::read_cpulist;
 use crate::Cpumask;
+use crate::Topology;
 use anyhow::bail;
+use anyhow::Context;
 use anyhow::Result;
 use glob::glob;
 use num::clamp;
+use serde::Deserialize;
+use serde::Serialize;
 use std::collections::BTreeMap;
 use std::fmt;
 use std::path::Path;
@@ -43,10 +72,64 @@ pub struct PerfDomain {
     pub perf_table: BTreeMap<usize, Arc<PerfState>>,
 }
 
+/// Number of performance states synthesized for a cpufreq policy without
+/// scaling_available_frequencies, e.g. with intel_pstate or amd-pstate.
+const NR_SYNTH_PERF_STATES: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EnergyModelSource {
+    /// Loaded from the kernel energy model in debugfs.
+    Kernel,
+    /// Synthesized from cpufreq and the CPU capacities.
+    Synthesized,
+    /// Supplied by the user as JSON.
+    Json,
+}
+
+/// Power curve used to synthesize an energy model. The power of a CPU
+/// running at a frequency is
+///
+/// ```text
+/// max_power * (capacity / 1024) * (frequency / max_frequency) ^ exponent
+/// ```
+///
+/// where capacity is the capacity of the CPU and max_frequency the highest
+/// maximum frequency of all the CPUs. Power grows linearly with the size of
+/// the core, and with the frequency and voltage of the performance state.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PowerCurve {
+    /// Power of a CPU with a capacity of 1024 at the highest frequency, in
+    /// microwatts.
+    pub max_power: usize,
+    /// Exponent of the curve. Dynamic power grows with f * V^2, and the
+    /// voltage roughly with the frequency.
+    pub exponent: f64,
+}
+
+impl Default for PowerCurve {
+    fn default() -> Self {
+        Self {
+            max_power: 3_000_000,
+            exponent: 2.5,
+        }
+    }
+}
+
+impl PowerCurve {
+    fn power(&self, capacity: usize, freq_ratio: f64) -> usize {
+        let power =
+            self.max_power as f64 * (capacity as f64 / 1024.0) * freq_ratio.powf(self.exponent);
+        (power as usize).max(1)
+    }
+}
+
 #[derive(Debug)]
 pub struct EnergyModel {
     /// Performance domains indexed by domain id
     pub perf_doms: BTreeMap<usize, Arc<PerfDomain>>,
+    /// Where the model comes from.
+    pub source: EnergyModelSource,
 }
 
 impl EnergyModel {
@@ -69,7 +152,183 @@ impl EnergyModel {
             perf_doms.insert(pd.id, pd.into());
         }
 
-        Ok(EnergyModel { perf_doms })
+        Ok(EnergyModel {
+            perf_doms,
+            source: EnergyModelSource::Kernel,
+        })
+    }
+
+    /// Synthesize an EnergyModel from the cpufreq policies of the CPUs of
+    /// @topo. Each policy becomes a performance domain, with a performance
+    /// state per available frequency and the power given by @curve. CPUs
+    /// without cpufreq are grouped by capacity.
+    pub fn synthesize(topo: &Topology, curve: &PowerCurve) -> Result<EnergyModel> {
+        // Group the CPUs by policy, or by capacity and frequency range if the
+        // policy is unknown.
+        let mut groups: BTreeMap<(Option<String>, usize, usize, usize), Vec<usize>> =
+            BTreeMap::new();
+        for cpu in topo.all_cpus.values() {
+            let related = read_cpufreq_file(cpu.id, "related_cpus")
+                .ok()
+                .map(|cpus| cpus.trim().to_string());
+            let key = match related {
+                Some(cpus) => (Some(cpus), 0, 0, 0),
+                None => (None, cpu.cpu_capacity, cpu.min_freq, cpu.max_freq),
+            };
+            groups.entry(key).or_default().push(cpu.id);
+        }
+        if groups.is_empty() {
+            bail!("There is no CPU to synthesize an energy model for.");
+        }
+
+        let mut pd_cpus: Vec<Vec<usize>> = groups.into_values().collect();
+        pd_cpus.sort();
+
+        let max_freq = topo.all_cpus.values().map(|cpu| cpu.max_freq).max();
+
+        let mut perf_doms = BTreeMap::new();
+        for (id, cpus) in pd_cpus.into_iter().enumerate() {
+            let mut span = Cpumask::new();
+            for &cpu in cpus.iter() {
+                span.set_cpu(cpu)?;
+            }
+
+            let first = &topo.all_cpus[&cpus[0]];
+            let capacity = cpus
+                .iter()
+                .map(|cpu| topo.all_cpus[cpu].cpu_capacity)
+                .max()
+                .unwrap_or(1024);
+            let states = synthesize_freqs(first.id, first.min_freq, first.max_freq)
+                .into_iter()
+                .map(|freq| {
+                    let perf = match first.max_freq {
+                        0 => capacity,
+                        cpu_max_freq => (capacity * freq / cpu_max_freq).max(1),
+                    };
+                    let freq_ratio = match max_freq {
+                        Some(max_freq) if freq > 0 && max_freq > 0 => freq as f64 / max_freq as f64,
+                        _ => 1.0,
+                    };
+                    (freq, perf, curve.power(capacity, freq_ratio))
+                })
+                .collect();
+
+            let pd = PerfDomain::from_states(id, span, states)?;
+            perf_doms.insert(pd.id, pd.into());
+        }
+
+        Ok(EnergyModel {
+            perf_doms,
+            source: EnergyModelSource::Synthesized,
+        })
+    }
+
+    /// Load the kernel EnergyModel, or synthesize one with @curve if the
+    /// kernel doesn't provide it.
+    pub fn with_fallback(topo: &Topology, curve: &PowerCurve) -> Result<EnergyModel> {
+        match Self::new() {
+            Ok(em) => Ok(em),
+            Err(_) => Self::synthesize(topo, curve),
+        }
+    }
+
+    /// Merge the performance domains of the CPUs with the same capacity, i.e.
+    /// whose fastest performance states have the same performance. Each
+    /// merged domain keeps the performance states of its first domain.
+    ///
+    /// A synthesized model has a domain per cpufreq policy, which often
+    /// means one per CPU on x86. Merging them keeps the number of domains
+    /// down to the number of CPU types for users enumerating combinations
+    /// of domains.
+    pub fn merge_by_capacity(&self) -> EnergyModel {
+        let mut groups: BTreeMap<usize, PerfDomain> = BTreeMap::new();
+        for pd in self.perf_doms.values() {
+            let Some((&capacity, _)) = pd.perf_table.last_key_value() else {
+                continue;
+            };
+            match groups.get_mut(&capacity) {
+                Some(merged) => merged.span |= &pd.span,
+                None => {
+                    groups.insert(capacity, pd.as_ref().clone());
+                }
+            }
+        }
+
+        let mut pds: Vec<PerfDomain> = groups.into_values().collect();
+        pds.sort_by_key(|pd| pd.span.iter().next());
+
+        let mut perf_doms = BTreeMap::new();
+        for (id, mut pd) in pds.into_iter().enumerate() {
+            pd.id = id;
+            perf_doms.insert(id, pd.into());
+        }
+
+        EnergyModel {
+            perf_doms,
+            source: self.source,
+        }
+    }
+
+    /// Build an EnergyModel from its JSON description, see the module
+    /// documentation. The costs and inefficient states are computed from the
+    /// performance and power of the states.
+    pub fn from_json(json: &str) -> Result<EnergyModel> {
+        let repr: EnergyModelRepr = serde_json::from_str(json)?;
+        if repr.perf_domains.is_empty() {
+            bail!("There is no performance domain.");
+        }
+
+        let mut perf_doms = BTreeMap::new();
+        let mut all = Cpumask::new();
+        for (id, pd) in repr.perf_domains.into_iter().enumerate() {
+            let mut span = Cpumask::new();
+            for cpu in read_cpulist(&pd.cpus)? {
+                span.set_cpu(cpu)?;
+            }
+            if !span.and(&all).is_empty() {
+                bail!("CPUs {} are in more than one performance domain", pd.cpus);
+            }
+            all |= &span;
+
+            let states = pd
+                .perf_states
+                .into_iter()
+                .map(|ps| (ps.frequency, ps.performance, ps.power))
+                .collect();
+            let pd = PerfDomain::from_states(id, span, states)
+                .with_context(|| format!("performance domain {}", pd.cpus))?;
+            perf_doms.insert(pd.id, pd.into());
+        }
+
+        Ok(EnergyModel {
+            perf_doms,
+            source: EnergyModelSource::Json,
+        })
+    }
+
+    /// Describe the EnergyModel in JSON, e.g. to tune a synthesized model
+    /// and load it back with from_json().
+    pub fn to_json(&self) -> Result<String> {
+        let repr = EnergyModelRepr {
+            perf_domains: self
+                .perf_doms
+                .values()
+                .map(|pd| PerfDomainRepr {
+                    cpus: pd.span.to_cpulist(),
+                    perf_states: pd
+                        .perf_table
+                        .values()
+                        .map(|ps| PerfStateRepr {
+                            frequency: ps.frequency,
+                            performance: ps.performance,
+                            power: ps.power,
+                        })
+                        .collect(),
+                })
+                .collect(),
+        };
+        Ok(serde_json::to_string_pretty(&repr)?)
     }
 
     pub fn get_pd_by_cpu_id(&self, cpu_id: usize) -> Option<&PerfDomain> {
@@ -79,6 +338,21 @@ impl EnergyModel {
             .map(|c| c as _)
     }
 
+    /// Estimated energy cost of running @cpu at a utilization of @util, in
+    /// the capacity scale (1024 is the performance of the fastest CPU at its
+    /// maximum frequency). As in the kernel's em_cpu_energy(), the CPU runs
+    /// at the lowest efficient performance state fitting @util and the cost
+    /// is the power of the state weighted by the time the CPU is busy.
+    /// Utilization beyond the capacity of the CPU is ignored.
+    ///
+    /// Returns None if @cpu isn't part of the model.
+    pub fn placement_cost(&self, cpu: usize, util: usize) -> Option<usize> {
+        let pd = self.get_pd_by_cpu_id(cpu)?;
+        let (&max_perf, _) = pd.perf_table.last_key_value()?;
+        let ps = pd.select_perf_state_by_util(util)?;
+        Some(ps.cost * util.min(max_perf) / max_perf)
+    }
+
     pub fn perf_total(&self) -> usize {
         let mut total = 0;
 
@@ -109,6 +383,51 @@ impl PerfDomain {
         })
     }
 
+    /// Build a PerfDomain from (frequency, performance, power) tuples,
+    /// computing the cost and efficiency of each state like the kernel.
+    fn from_states(id: usize, span: Cpumask, states: Vec<(usize, usize, usize)>) -> Result<Self> {
+        let mut states: BTreeMap<usize, (usize, usize)> = states
+            .into_iter()
+            .map(|(freq, perf, power)| (perf, (freq, power)))
+            .collect();
+        states.remove(&0);
+        let Some((&max_perf, _)) = states.last_key_value() else {
+            bail!("There is no performance state.");
+        };
+
+        // A state is inefficient if a faster one costs no more.
+        let mut perf_table = BTreeMap::new();
+        let mut min_cost = usize::MAX;
+        for (&performance, &(frequency, power)) in states.iter().rev() {
+            let cost = power * max_perf / performance;
+            let inefficient = (cost >= min_cost) as usize;
+            min_cost = min_cost.min(cost);
+            let ps = PerfState {
+                cost,
+                frequency,
+                inefficient,
+                performance,
+                power,
+            };
+            perf_table.insert(performance, ps.into());
+        }
+
+        Ok(PerfDomain {
+            id,
+            span,
+            perf_table,
+        })
+    }
+
+    /// Lookup the lowest efficient performance state providing @util, in the
+    /// capacity scale, or the fastest state if none does.
+    pub fn select_perf_state_by_util(&self, util: usize) -> Option<&Arc<PerfState>> {
+        self.perf_table
+            .values()
+            .find(|ps| ps.inefficient == 0 && ps.performance >= util)
+            .or_else(|| self.perf_table.values().last())
+    }
+
     /// Lookup a performance state by a given CPU utilization.
     /// @util is in %, ranging [0, 100].
     pub fn select_perf_state(&self, util: f32) -> Option<&Arc<PerfState>> {
@@ -198,6 +517,66 @@ impl fmt::Display for PerfState {
 /*********************************************************
  * Helper structs/functions for creating the EnergyModel *
  *********************************************************/
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct PerfStateRepr {
+    #[serde(default)]
+    frequency: usize,
+    performance: usize,
+    power: usize,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct PerfDomainRepr {
+    cpus: String,
+    perf_states: Vec<PerfStateRepr>,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct EnergyModelRepr {
+    perf_domains: Vec<PerfDomainRepr>,
+}
+
+fn read_cpufreq_file(cpu: usize, file: &str) -> Result<String> {
+    let path = format!(
+        "{}/sys/devices/system/cpu/cpu{}/cpufreq/{}",
+        root_prefix(),
+        cpu,
+        file
+    );
+    Ok(std::fs::read_to_string(path)?)
+}
+
+/// Frequencies of the synthesized performance states of @cpu, in kHz: the
+/// available frequencies of its cpufreq policy if the driver lists them,
+/// otherwise NR_SYNTH_PERF_STATES evenly spaced frequencies between
+/// @min_freq and @max_freq.
+fn synthesize_freqs(cpu: usize, min_freq: usize, max_freq: usize) -> Vec<usize> {
+    if let Ok(freqs) = read_cpufreq_file(cpu, "scaling_available_frequencies") {
+        let freqs: Vec<usize> = freqs
+            .split_whitespace()
+            .filter_map(|f| f.parse().ok())
+            .filter(|&f| f > 0 && (max_freq == 0 || f <= max_freq))
+            .collect();
+        if !freqs.is_empty() {
+            return freqs;
+        }
+    }
+
+    if max_freq == 0 || min_freq == 0 || min_freq >= max_freq {
+        return vec![max_freq];
+    }
+    let step = (max_freq - min_freq) / (NR_SYNTH_PERF_STATES - 1);
+    (0..NR_SYNTH_PERF_STATES)
+        .map(|i| match i {
+            i if i == NR_SYNTH_PERF_STATES - 1 => max_freq,
+            i => min_freq + step * i,
+        })
+        .collect()
+}
+
 fn get_ps_paths(root: String) -> Result<Vec<String>> {
     let ps_paths = glob(&(root.clone() + "/ps:[0-9]*"))?;
     let mut ps_vec = vec![];
@@ -241,3 +620,180 @@ fn get_em_root() -> Result<String> {
         Ok(root)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_sysfs::{FakeCore, FakeLlc, FakeNode, FakeSysfsSpec};
+    use crate::set_cpumask_test_width;
+
+    fn big_little_sysfs() -> crate::fake_sysfs::FakeSysfs {
+        // CPUs 0-3 are two big SMT2 cores, 4-7 four little cores.
+        let sysfs = FakeSysfsSpec::new()
+            .capacity_file("cpu_capacity")
+            .add_node(
+                FakeNode::new()
+                    .add_llc(FakeLlc::new().add_cores(2, FakeCore::new(2).freq(1000000, 3000000)))
+                    .add_llc(
+                        FakeLlc::new()
+                            .add_cores(4, FakeCore::new(1).capacity(512).freq(1000000, 2000000)),
+                    ),
+            )
+            .build()
+            .unwrap();
+        sysfs.install();
+        sysfs
+    }
+
+    #[test]
+    fn test_synthesize() {
+        let sysfs = big_little_sysfs();
+        for cpu in 0..8 {
+            let related = if cpu < 4 { "0-3" } else { "4-7" };
+            sysfs
+                .write_cpu_file(&format!("cpu{cpu}/cpufreq/related_cpus"), related)
+                .unwrap();
+            if cpu >= 4 {
+                sysfs
+                    .write_cpu_file(
+                        &format!("cpu{cpu}/cpufreq/scaling_available_frequencies"),
+                        "2000000 1500000 1000000 ",
+                    )
+                    .unwrap();
+            }
+        }
+
+        let topo = Topology::new().unwrap();
+        let em = EnergyModel::synthesize(&topo, &PowerCurve::default()).unwrap();
+        assert_eq!(em.source, EnergyModelSource::Synthesized);
+        assert_eq!(em.perf_doms.len(), 2);
+
+        let big = em.get_pd_by_cpu_id(0).unwrap();
+        assert_eq!(big.span.weight(), 4);
+        assert_eq!(big.perf_table.len(), NR_SYNTH_PERF_STATES);
+        let (_, ps) = big.perf_table.last_key_value().unwrap();
+        assert_eq!((ps.frequency, ps.performance), (3000000, 1024));
+        assert_eq!(ps.power, 3_000_000);
+
+        let little = em.get_pd_by_cpu_id(7).unwrap();
+        let perfs: Vec<usize> = little.perf_table.keys().copied().collect();
+        assert_eq!(perfs, [256, 384, 512]);
+        assert!(little.perf_table.values().all(|ps| ps.inefficient == 0));
+
+        // Power is linear in the capacity and follows the curve in the
+        // frequency, so a little core at the same frequency as a big one
+        // draws half the power.
+        let big_1ghz = &big.perf_table[&341];
+        let little_1ghz = &little.perf_table[&256];
+        assert_eq!(big_1ghz.frequency, 1000000);
+        assert_eq!(little_1ghz.frequency, 1000000);
+        assert_eq!(big_1ghz.power, 192450);
+        assert_eq!(little_1ghz.power, 96225);
+        assert_eq!(little.perf_table[&512].power, 544331);
+
+        // Small tasks are cheaper on little cores, and utilization beyond
+        // the capacity of a CPU is capped.
+        let cost_little = em.placement_cost(4, 200).unwrap();
+        let cost_big = em.placement_cost(0, 200).unwrap();
+        assert!(cost_little < cost_big);
+        assert_eq!(em.placement_cost(0, 2048), em.placement_cost(0, 1024));
+        assert_eq!(em.placement_cost(0, 1024), Some(3_000_000));
+        assert_eq!(em.placement_cost(8, 100), None);
+    }
+
+    #[test]
+    fn test_synthesize_without_policies() {
+        let _sysfs = big_little_sysfs();
+        let topo = Topology::new().unwrap();
+        let em = EnergyModel::synthesize(&topo, &PowerCurve::default()).unwrap();
+
+        // CPUs are grouped by capacity and frequency range.
+        assert_eq!(em.perf_doms.len(), 2);
+        assert_eq!(em.get_pd_by_cpu_id(3).unwrap().id, 0);
+        assert_eq!(em.get_pd_by_cpu_id(4).unwrap().id, 1);
+    }
+
+    #[test]
+    fn test_merge_by_capacity() {
+        let sysfs = big_little_sysfs();
+        for cpu in 0..8 {
+            sysfs
+                .write_cpu_file(&format!("cpu{cpu}/cpufreq/related_cpus"), &cpu.to_string())
+                .unwrap();
+        }
+
+        let topo = Topology::new().unwrap();
+        let em = EnergyModel::synthesize(&topo, &PowerCurve::default()).unwrap();
+        assert_eq!(em.perf_doms.len(), 8);
+
+        // One domain per CPU becomes one per capacity, numbered by first CPU.
+        let merged = em.merge_by_capacity();
+        assert_eq!(merged.source, EnergyModelSource::Synthesized);
+        assert_eq!(merged.perf_doms.len(), 2);
+        let big = merged.get_pd_by_cpu_id(0).unwrap();
+        assert_eq!((big.id, big.span.to_cpulist()), (0, "0-3".to_string()));
+        assert_eq!(big.perf_table, em.get_pd_by_cpu_id(0).unwrap().perf_table);
+        let little = merged.get_pd_by_cpu_id(7).unwrap();
+        assert_eq!(
+            (little.id, little.span.to_cpulist()),
+            (1, "4-7".to_string())
+        );
+        assert_eq!(merged.perf_total(), em.perf_total());
+    }
+
+    #[test]
+    fn test_from_json() {
+        set_cpumask_test_width(8);
+
+        let em = EnergyModel::from_json(
+            r#"{
+                "perf_domains": [
+                    {
+                        "cpus": "0-3",
+                        "perf_states": [
+                            { "frequency": 1000000, "performance": 256, "power": 200000 },
+                            { "frequency": 1500000, "performance": 384, "power": 250000 },
+                            { "frequency": 2000000, "performance": 512, "power": 600000 }
+                        ]
+                    },
+                    {
+                        "cpus": "4-5",
+                        "perf_states": [ { "performance": 1024, "power": 3000000 } ]
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(em.source, EnergyModelSource::Json);
+
+        // The 256 state costs more than the 384 one and is skipped.
+        let pd = em.get_pd_by_cpu_id(2).unwrap();
+        assert_eq!(pd.perf_table[&256].inefficient, 1);
+        assert_eq!(pd.perf_table[&384].inefficient, 0);
+        assert_eq!(pd.perf_table[&512].cost, 600000);
+        assert_eq!(pd.select_perf_state_by_util(100).unwrap().performance, 384);
+        assert_eq!(pd.select_perf_state_by_util(600).unwrap().performance, 512);
+
+        let em2 = EnergyModel::from_json(&em.to_json().unwrap()).unwrap();
+        assert_eq!(em.perf_doms, em2.perf_doms);
+
+        assert!(EnergyModel::from_json(r#"{ "perf_domains": [] }"#).is_err());
+        assert!(EnergyModel::from_json(
+            r#"{ "perf_domains": [
+                { "cpus": "0-3", "perf_states": [ { "performance": 1024, "power": 1 } ] },
+                { "cpus": "3", "perf_states": [ { "performance": 1024, "power": 1 } ] }
+            ] }"#
+        )
+        .is_err());
+        assert!(EnergyModel::from_json(
+            r#"{ "perf_domains": [ { "cpus": "0", "perf_states": [] } ] }"#
+        )
+        .is_err());
+        assert!(EnergyModel::from_json(
+            r#"{ "perf_domains": [ { "cpus": "8", "perf_states": [ { "performance": 1, "power": 1 } ] } ] }"#
+        )
+        .is_err());
+
+        set_cpumask_test_width(0);
+    }
+}
@@ -0,0 +1,515 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+//! # Fake sysfs trees for topology testing
+//!
+//! [`crate::testutils::make_test_topo()`] builds [`crate::Topology`]
+//! structs directly, which skips the sysfs parsing paths. This module
+//! materializes a temporary `/sys/devices/system/{cpu,node}` tree from a
+//! declarative spec so that [`crate::Topology::new()`] and friends can be
+//! tested against it:
+//!
+//!```
+//!     use scx_utils::fake_sysfs::{FakeCore, FakeLlc, FakeNode, FakeSysfsSpec};
+//!     use scx_utils::Topology;
+//!
+//!     // One node with an 8-core SMT2 LLC and a 4-core little LLC.
+//!     let spec = FakeSysfsSpec::new()
+//!         .capacity_file("cpu_capacity")
+//!         .add_node(
+//!             FakeNode::new()
+//!                 .add_llc(FakeLlc::new().add_cores(8, FakeCore::new(2)))
+//!                 .add_llc(FakeLlc::new().add_cores(4, FakeCore::new(1).capacity(512))),
+//!         );
+//!     let sysfs = spec.build().unwrap();
+//!     sysfs.install();
+//!
+//!     let topo = Topology::new().unwrap();
+//!     assert_eq!(topo.all_cpus.len(), 20);
+//!     assert!(topo.has_little_cores());
+//!```
+//!
+//! [`FakeSysfs::install()`] overrides the sysfs root prefix and the Cpumask
+//! width for the current thread only, so tests can run in parallel. The
+//! overrides are cleared and the tree is removed when the [`FakeSysfs`] is
+//! dropped.
+//!
+//! CPU IDs are assigned sequentially in node, LLC, core order with SMT
+//! siblings adjacent. Each node maps to its own physical package. Enable
+//! via the `testutils` feature of `scx_utils`.
+
+use crate::compat::set_root_prefix_test_override;
+use crate::cpumask::set_cpumask_test_width;
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+/// A physical core.
+#[derive(Clone, Debug)]
+pub struct FakeCore {
+    /// Number of hardware threads.
+    pub smt: usize,
+    /// Raw capacity written to the capacity file of each thread.
+    pub capacity: usize,
+    /// Minimum and maximum frequencies in kHz.
+    pub min_freq: usize,
+    pub max_freq: usize,
+}
+
+impl FakeCore {
+    pub fn new(smt: usize) -> Self {
+        Self {
+            smt,
+            capacity: 1024,
+            min_freq: 400000,
+            max_freq: 3000000,
+        }
+    }
+
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    pub fn freq(mut self, min_freq: usize, max_freq: usize) -> Self {
+        self.min_freq = min_freq;
+        self.max_freq = max_freq;
+        self
+    }
+}
+
+/// A last level cache and the cores sharing it.
+#[derive(Clone, Debug)]
+pub struct FakeLlc {
+    pub cores: Vec<FakeCore>,
+    /// Value of the L3 `id` file. Sequentially assigned if None.
+    pub cache_id: Option<usize>,
+    /// L3 size in bytes.
+    pub size: usize,
+}
+
+impl Default for FakeLlc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FakeLlc {
+    pub fn new() -> Self {
+        Self {
+            cores: vec![],
+            cache_id: None,
+            size: 32 << 20,
+        }
+    }
+
+    pub fn add_core(mut self, core: FakeCore) -> Self {
+        self.cores.push(core);
+        self
+    }
+
+    /// Add @nr copies of @core.
+    pub fn add_cores(mut self, nr: usize, core: FakeCore) -> Self {
+        self.cores.extend(std::iter::repeat_n(core, nr));
+        self
+    }
+
+    pub fn cache_id(mut self, id: usize) -> Self {
+        self.cache_id = Some(id);
+        self
+    }
+}
+
+/// A NUMA node.
+#[derive(Clone, Debug, Default)]
+pub struct FakeNode {
+    pub llcs: Vec<FakeLlc>,
+}
+
+impl FakeNode {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_llc(mut self, llc: FakeLlc) -> Self {
+        self.llcs.push(llc);
+        self
+    }
+}
+
+/// Declarative description of a fake sysfs tree.
+#[derive(Clone, Debug)]
+pub struct FakeSysfsSpec {
+    pub nodes: Vec<FakeNode>,
+    /// NUMA distance matrix. Defaults to 10 local and 20 remote.
+    pub distances: Option<Vec<Vec<usize>>>,
+    /// Create /sys/devices/system/node. Without it, all CPUs end up in a
+    /// single default node.
+    pub numa: bool,
+    /// Path under cpuX where FakeCore::capacity is written, e.g.
+    /// "cpu_capacity" or "acpi_cppc/highest_perf". If None, only the
+    /// frequencies are written and capacities are derived from
+    /// cpuinfo_max_freq.
+    pub capacity_file: Option<String>,
+    /// CPUs to leave out of /sys/devices/system/cpu/online.
+    pub offline: Vec<usize>,
+}
+
+impl Default for FakeSysfsSpec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FakeSysfsSpec {
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![],
+            distances: None,
+            numa: true,
+            capacity_file: None,
+            offline: vec![],
+        }
+    }
+
+    /// Homogeneous machine with the specified dimensions.
+    pub fn uniform(
+        nr_nodes: usize,
+        llcs_per_node: usize,
+        cores_per_llc: usize,
+        hts_per_core: usize,
+    ) -> Self {
+        let llc = FakeLlc::new().add_cores(cores_per_llc, FakeCore::new(hts_per_core));
+        let node = FakeNode {
+            llcs: vec![llc; llcs_per_node],
+        };
+        Self {
+            nodes: vec![node; nr_nodes],
+            ..Self::new()
+        }
+    }
+
+    pub fn add_node(mut self, node: FakeNode) -> Self {
+        self.nodes.push(node);
+        self
+    }
+
+    pub fn distances(mut self, distances: Vec<Vec<usize>>) -> Self {
+        self.distances = Some(distances);
+        self
+    }
+
+    pub fn numa(mut self, numa: bool) -> Self {
+        self.numa = numa;
+        self
+    }
+
+    pub fn capacity_file(mut self, path: &str) -> Self {
+        self.capacity_file = Some(path.to_string());
+        self
+    }
+
+    pub fn offline(mut self, cpus: &[usize]) -> Self {
+        self.offline.extend_from_slice(cpus);
+        self
+    }
+
+    pub fn nr_cpus(&self) -> usize {
+        self.nodes
+            .iter()
+            .flat_map(|node| node.llcs.iter())
+            .flat_map(|llc| llc.cores.iter())
+            .map(|core| core.smt)
+            .sum()
+    }
+
+    fn cpulist(cpus: &[usize]) -> String {
+        cpus.iter()
+            .map(|cpu| cpu.to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    fn write(path: &Path, val: impl ToString) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("creating {dir:?}"))?;
+        }
+        fs::write(path, format!("{}\n", val.to_string()))
+            .with_context(|| format!("writing {path:?}"))
+    }
+
+    /// Materialize the tree in a new temporary directory.
+    pub fn build(&self) -> Result<FakeSysfs> {
+        let nr_nodes = self.nodes.len();
+        if let Some(distances) = self.distances.as_ref() {
+            if distances.len() != nr_nodes || distances.iter().any(|row| row.len() != nr_nodes) {
+                bail!("distance matrix must be {}x{}", nr_nodes, nr_nodes);
+            }
+        }
+
+        let dir = tempfile::Builder::new()
+            .prefix("scx-fake-sysfs")
+            .tempdir()?;
+        let cpu_root = dir.path().join("sys/devices/system/cpu");
+        let node_root = dir.path().join("sys/devices/system/node");
+
+        let nr_cpus = self.nr_cpus();
+        let mut cpu = 0;
+        let mut llc_idx = 0;
+        let mut l2_idx = 0;
+        let mut smt_active = false;
+
+        for (node_id, node) in self.nodes.iter().enumerate() {
+            let node_dir = node_root.join(format!("node{node_id}"));
+            if self.numa {
+                let distance: Vec<String> = (0..nr_nodes)
+                    .map(|to| match self.distances.as_ref() {
+                        Some(d) => d[node_id][to].to_string(),
+                        None if to == node_id => "10".into(),
+                        None => "20".into(),
+                    })
+                    .collect();
+                Self::write(&node_dir.join("distance"), distance.join(" "))?;
+            }
+
+            let mut core_kernel_id = 0;
+            for llc in node.llcs.iter() {
+                let llc_cpus: Vec<usize> =
+                    (cpu..cpu + llc.cores.iter().map(|c| c.smt).sum::<usize>()).collect();
+                let llc_id = llc.cache_id.unwrap_or(llc_idx);
+
+                for core in llc.cores.iter() {
+                    let core_cpus: Vec<usize> = (cpu..cpu + core.smt).collect();
+                    smt_active |= core.smt > 1;
+
+                    for &id in core_cpus.iter() {
+                        let cpu_dir = cpu_root.join(format!("cpu{id}"));
+                        let top = cpu_dir.join("topology");
+                        Self::write(&top.join("core_id"), core_kernel_id)?;
+                        Self::write(&top.join("physical_package_id"), node_id)?;
+                        Self::write(&top.join("cluster_id"), llc_idx)?;
+                        Self::write(&top.join("thread_siblings_list"), Self::cpulist(&core_cpus))?;
+
+                        let l2 = cpu_dir.join("cache/index2");
+                        Self::write(&l2.join("level"), 2)?;
+                        Self::write(&l2.join("id"), l2_idx)?;
+                        Self::write(&l2.join("size"), "1024K")?;
+                        Self::write(&l2.join("shared_cpu_list"), Self::cpulist(&core_cpus))?;
+
+                        let l3 = cpu_dir.join("cache/index3");
+                        Self::write(&l3.join("level"), 3)?;
+                        Self::write(&l3.join("id"), llc_id)?;
+                        Self::write(&l3.join("size"), format!("{}K", llc.size >> 10))?;
+                        Self::write(&l3.join("shared_cpu_list"), Self::cpulist(&llc_cpus))?;
+
+                        let freq = cpu_dir.join("cpufreq");
+                        Self::write(&freq.join("scaling_min_freq"), core.min_freq)?;
+                        Self::write(&freq.join("scaling_max_freq"), core.max_freq)?;
+                        Self::write(&freq.join("cpuinfo_max_freq"), core.max_freq)?;
+                        Self::write(&freq.join("cpuinfo_transition_latency"), 0)?;
+                        if let Some(file) = self.capacity_file.as_ref() {
+                            Self::write(&cpu_dir.join(file), core.capacity)?;
+                        }
+
+                        Self::write(&cpu_dir.join("power/pm_qos_resume_latency_us"), 0)?;
+                        Self::write(&cpu_dir.join("online"), !self.offline.contains(&id) as u8)?;
+
+                        if self.numa {
+                            fs::create_dir_all(&node_dir)?;
+                            std::os::unix::fs::symlink(
+                                &cpu_dir,
+                                node_dir.join(format!("cpu{id}")),
+                            )?;
+                        }
+                    }
+
+                    cpu += core.smt;
+                    core_kernel_id += 1;
+                    l2_idx += 1;
+                }
+                llc_idx += 1;
+            }
+        }
+
+        let online: Vec<usize> = (0..nr_cpus).filter(|c| !self.offline.contains(c)).collect();
+        let all: Vec<usize> = (0..nr_cpus).collect();
+        Self::write(&cpu_root.join("online"), Self::cpulist(&online))?;
+        Self::write(&cpu_root.join("possible"), Self::cpulist(&all))?;
+        Self::write(&cpu_root.join("present"), Self::cpulist(&all))?;
+        Self::write(&cpu_root.join("smt/active"), smt_active as u8)?;
+
+        Ok(FakeSysfs {
+            dir,
+            nr_cpus,
+            installed: false.into(),
+        })
+    }
+}
+
+/// A materialized fake sysfs tree. The tree is removed on drop.
+pub struct FakeSysfs {
+    dir: TempDir,
+    nr_cpus: usize,
+    installed: std::cell::Cell<bool>,
+}
+
+impl FakeSysfs {
+    /// The directory to use as the sysfs root prefix.
+    pub fn prefix(&self) -> PathBuf {
+        self.dir.path().to_path_buf()
+    }
+
+    pub fn nr_cpus(&self) -> usize {
+        self.nr_cpus
+    }
+
+    /// Point the sysfs root prefix and the Cpumask width of the current
+    /// thread at this tree.
+    pub fn install(&self) {
+        set_root_prefix_test_override(Some(self.dir.path().to_str().unwrap()));
+        set_cpumask_test_width(self.nr_cpus);
+        self.installed.set(true);
+    }
+
+    /// Overwrite @path, relative to `/sys/devices/system/cpu`, e.g. to
+    /// simulate hotplug or frequency changes.
+    pub fn write_cpu_file(&self, path: &str, val: impl ToString) -> Result<()> {
+        FakeSysfsSpec::write(
+            &self.dir.path().join("sys/devices/system/cpu").join(path),
+            val,
+        )
+    }
+}
+
+impl Drop for FakeSysfs {
+    fn drop(&mut self) {
+        if self.installed.get() {
+            set_root_prefix_test_override(None);
+            set_cpumask_test_width(0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CoreType;
+    use crate::Topology;
+    use crate::TopologyWatcher;
+
+    #[test]
+    fn test_uniform_numa() {
+        let sysfs = FakeSysfsSpec::uniform(2, 2, 4, 2)
+            .distances(vec![vec![10, 32], vec![32, 10]])
+            .build()
+            .unwrap();
+        sysfs.install();
+
+        let topo = Topology::new().unwrap();
+        assert_eq!(topo.nodes.len(), 2);
+        assert_eq!(topo.all_llcs.len(), 4);
+        assert_eq!(topo.all_cores.len(), 16);
+        assert_eq!(topo.all_cpus.len(), 32);
+        assert!(topo.smt_enabled);
+        assert_eq!(topo.nodes[&1].distance, vec![32, 10]);
+        assert_eq!(topo.nodes[&1].span.to_cpulist(), "16-31");
+        assert_eq!(topo.all_llcs[&3].span.to_cpulist(), "24-31");
+        for core in topo.all_cores.values() {
+            assert_eq!(core.cpus.len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_no_numa() {
+        let sysfs = FakeSysfsSpec::uniform(1, 2, 2, 1)
+            .numa(false)
+            .build()
+            .unwrap();
+        sysfs.install();
+
+        let topo = Topology::new().unwrap();
+        assert_eq!(topo.nodes.len(), 1);
+        assert_eq!(topo.all_llcs.len(), 2);
+        assert!(!topo.smt_enabled);
+
+        let topo = Topology::with_flattened_llc_node().unwrap();
+        assert_eq!(topo.all_llcs.len(), 1);
+    }
+
+    #[test]
+    fn test_hybrid_capacity() {
+        let sysfs = FakeSysfsSpec::new()
+            .capacity_file("cpu_capacity")
+            .add_node(
+                FakeNode::new().add_llc(
+                    FakeLlc::new()
+                        .add_cores(2, FakeCore::new(2).capacity(1024))
+                        .add_cores(4, FakeCore::new(1).capacity(400)),
+                ),
+            )
+            .build()
+            .unwrap();
+        sysfs.install();
+
+        let topo = Topology::new().unwrap();
+        assert!(topo.has_little_cores());
+        assert_eq!(topo.all_cpus[&0].cpu_capacity, 1024);
+        assert_eq!(topo.all_cpus[&4].cpu_capacity, 400);
+        assert_eq!(topo.all_cpus[&4].core_type, CoreType::Little);
+        assert_ne!(topo.all_cpus[&0].core_type, CoreType::Little);
+    }
+
+    #[test]
+    fn test_offline_cpus() {
+        let sysfs = FakeSysfsSpec::uniform(1, 1, 4, 2)
+            .offline(&[6, 7])
+            .build()
+            .unwrap();
+        sysfs.install();
+
+        let topo = Topology::new().unwrap();
+        assert_eq!(topo.all_cpus.len(), 6);
+        assert_eq!(topo.all_cores.len(), 3);
+        assert_eq!(topo.span.to_cpulist(), "0-5");
+    }
+
+    #[test]
+    fn test_virt_llcs() {
+        let sysfs = FakeSysfsSpec::uniform(2, 1, 8, 2).build().unwrap();
+        sysfs.install();
+
+        let topo = Topology::with_virt_llcs(Some((4, 8))).unwrap();
+        assert_eq!(topo.all_llcs.len(), 4);
+        for llc in topo.all_llcs.values() {
+            assert_eq!(llc.cores.len(), 4);
+        }
+    }
+
+    #[test]
+    fn test_watcher_hotplug() {
+        let sysfs = FakeSysfsSpec::uniform(1, 2, 2, 2).build().unwrap();
+        sysfs.install();
+
+        let mut watcher = TopologyWatcher::new().unwrap();
+        assert!(watcher.poll().unwrap().is_none());
+
+        sysfs.write_cpu_file("online", "0-5").unwrap();
+        let change = watcher.poll().unwrap().unwrap();
+        assert_eq!(change.diff.cpus_removed, vec![6, 7]);
+        assert_eq!(change.diff.cores_removed, vec![3]);
+        assert!(change.diff.llcs_removed.is_empty());
+
+        sysfs
+            .write_cpu_file("cpu0/cpufreq/scaling_max_freq", 2000000)
+            .unwrap();
+        let change = watcher.poll().unwrap().unwrap();
+        assert!(!change.diff.hotplugged());
+        assert_eq!(change.diff.cpus_capacity_changed, vec![0]);
+    }
+}
@@ -6,15 +6,16 @@ use nvml_wrapper::bitmasks::InitFlags;
 use nvml_wrapper::enum_wrappers::device::{Clock, PerformanceState, TopologyLevel};
 use nvml_wrapper::Nvml;
 use nvml_wrapper_sys::bindings::NVML_AFFINITY_SCOPE_NODE;
+use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::path::Path;
 
-#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialOrd, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialOrd, PartialEq, Serialize, Deserialize)]
 pub enum GpuIndex {
     Nvidia { nvml_id: u32 },
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Gpu {
     pub index: GpuIndex,
     pub node_id: usize,
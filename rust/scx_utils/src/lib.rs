@@ -50,9 +50,15 @@ pub use user_exit_info::SCX_ECODE_ACT_RESTART;
 pub use user_exit_info::SCX_ECODE_RSN_HOTPLUG;
 pub use user_exit_info::UEI_DUMP_PTR_MUTEX;
 
+pub mod crash_report;
+pub use crash_report::CrashReport;
+pub use crash_report::CrashStore;
+
 pub mod build_id;
 pub mod compat;
 pub use compat::ksym_exists;
+#[cfg(any(test, feature = "testutils"))]
+pub use compat::set_root_prefix_test_override;
 pub use compat::ROOT_PREFIX;
 
 mod libbpf_logger;
@@ -73,10 +79,23 @@ pub use topology::Topology;
 pub use topology::NR_CPUS_POSSIBLE;
 pub use topology::NR_CPU_IDS;
 
+#[cfg(any(test, feature = "testutils"))]
+pub mod fake_sysfs;
+
+mod topology_watcher;
+pub use topology_watcher::TopologyChange;
+pub use topology_watcher::TopologyDiff;
+pub use topology_watcher::TopologyWatcher;
+
 mod energy_model;
 pub use energy_model::EnergyModel;
+pub use energy_model::EnergyModelSource;
 pub use energy_model::PerfDomain;
 pub use energy_model::PerfState;
+pub use energy_model::PowerCurve;
+
+mod cpu_domain;
+pub use cpu_domain::CpuDomain;
 
 mod cpumask;
 pub use cpumask::read_cpulist;
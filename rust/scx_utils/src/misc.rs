@@ -41,8 +41,28 @@ where
         };
         retry_cnt = 0;
 
+        // Prefer having the server push the stats. Older servers don't know
+        // about subscriptions, fall back to polling for them. Rejections for
+        // other reasons, e.g. invalid @stats_args, won't go away by retrying.
+        let subscribed = match client.subscribe(intv, stats_args.to_owned()) {
+            Ok(()) => true,
+            Err(e) => match e.downcast_ref::<StatsErrno>() {
+                Some(errno) if errno.0 == libc::EOPNOTSUPP => false,
+                Some(_) => Err(e).context("subscribing to stats_server")?,
+                None => {
+                    warn!("Failed to subscribe to stats_server: {e}");
+                    sleep(Duration::from_secs(1));
+                    continue;
+                }
+            },
+        };
+
         while !should_exit() {
-            let stats = match client.request::<T>("stats", stats_args.to_owned()) {
+            let res = match subscribed {
+                true => client.recv::<T>(),
+                false => client.request::<T>("stats", stats_args.to_owned()),
+            };
+            let stats = match res {
                 Ok(v) => v,
                 Err(e) => {
                     if let Some(ioe) = e.downcast_ref::<std::io::Error>() {
@@ -55,7 +75,9 @@ where
                 }
             };
             output(stats)?;
-            sleep(intv);
+            if !subscribed {
+                sleep(intv);
+            }
         }
     }
 
@@ -67,10 +67,13 @@
 //! With a created Topology, you can query the topological hierarchy using the
 //! set of accessor functions defined below. All objects in the topological
 //! hierarchy are entirely read-only. If the host topology were to change (due
-//! to e.g. hotplug), a new Topology object should be created.
+//! to e.g. hotplug), a new Topology object should be created. Use
+//! [`crate::TopologyWatcher`] to get notified of such changes along with a
+//! diff against the previous Topology.
 
-use crate::compat::ROOT_PREFIX;
+use crate::compat::root_prefix;
 use crate::cpumask::read_cpulist;
+use crate::cpumask::with_cpumask_width;
 use crate::misc::find_best_split_size;
 use crate::misc::read_file_byte;
 use crate::misc::read_file_usize_vec;
@@ -81,6 +84,8 @@ use anyhow::Result;
 use glob::glob;
 use log::info;
 use log::warn;
+use serde::Deserialize;
+use serde::Serialize;
 use sscanf::sscanf;
 use std::cmp::min;
 use std::collections::BTreeMap;
@@ -117,13 +122,13 @@ lazy_static::lazy_static! {
     pub static ref NR_PARTITION_MAX_CORES: usize = 8;
 }
 
-#[derive(Debug, Clone, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub enum CoreType {
     Big { turbo: bool },
     Little,
 }
 
-#[derive(Debug, Clone, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct Cpu {
     pub id: usize,
     pub min_freq: usize,
@@ -151,7 +156,7 @@ pub struct Cpu {
     pub cluster_id: isize,
 }
 
-#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct Core {
     /// Monotonically increasing unique id
     pub id: usize,
@@ -168,7 +173,7 @@ pub struct Core {
     pub node_id: usize,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Llc {
     /// Monotonically increasing unique id
     pub id: usize,
@@ -182,10 +187,11 @@ pub struct Llc {
     pub node_id: usize,
 
     /// Skip indices to access lower level members easily.
+    #[serde(skip)]
     pub all_cpus: BTreeMap<usize, Arc<Cpu>>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Node {
     pub id: usize,
     pub distance: Vec<usize>,
@@ -194,14 +200,45 @@ pub struct Node {
     pub span: Cpumask,
 
     /// Skip indices to access lower level members easily.
+    #[serde(skip)]
     pub all_cores: BTreeMap<usize, Arc<Core>>,
+    #[serde(skip)]
     pub all_cpus: BTreeMap<usize, Arc<Cpu>>,
 
     #[cfg(feature = "gpu-topology")]
+    #[serde(with = "gpus_serde")]
     pub gpus: BTreeMap<GpuIndex, Gpu>,
 }
 
-#[derive(Debug)]
+/// JSON object keys must be strings. Serialize GPUs as a list instead and
+/// rebuild the map from each GPU's index.
+#[cfg(feature = "gpu-topology")]
+mod gpus_serde {
+    use super::*;
+    use serde::Deserializer;
+    use serde::Serializer;
+
+    pub fn serialize<S: Serializer>(
+        gpus: &BTreeMap<GpuIndex, Gpu>,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_seq(gpus.values())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<BTreeMap<GpuIndex, Gpu>, D::Error> {
+        Ok(Vec::<Gpu>::deserialize(deserializer)?
+            .into_iter()
+            .map(|gpu| (gpu.index, gpu))
+            .collect())
+    }
+}
+
+/// Serializes into the node hierarchy only. The skip indices are rebuilt on
+/// deserialization.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(try_from = "TopologyRepr")]
 pub struct Topology {
     pub nodes: BTreeMap<usize, Node>,
     /// Cpumask all CPUs in the system.
@@ -210,11 +247,31 @@ pub struct Topology {
     pub smt_enabled: bool,
 
     /// Skip indices to access lower level members easily.
+    #[serde(skip)]
     pub all_llcs: BTreeMap<usize, Arc<Llc>>,
+    #[serde(skip)]
     pub all_cores: BTreeMap<usize, Arc<Core>>,
+    #[serde(skip)]
     pub all_cpus: BTreeMap<usize, Arc<Cpu>>,
 }
 
+#[derive(Deserialize)]
+struct TopologyRepr {
+    nodes: BTreeMap<usize, Node>,
+    span: Cpumask,
+    smt_enabled: bool,
+}
+
+impl TryFrom<TopologyRepr> for Topology {
+    type Error = anyhow::Error;
+
+    fn try_from(repr: TopologyRepr) -> Result<Self> {
+        let mut topo = Self::instantiate(repr.span, repr.nodes)?;
+        topo.smt_enabled = repr.smt_enabled;
+        Ok(topo)
+    }
+}
+
 impl Topology {
     fn instantiate(span: Cpumask, mut nodes: BTreeMap<usize, Node>) -> Result<Self> {
         // Build skip indices prefixed with all_ for easy lookups. As Arc
@@ -290,7 +347,7 @@ impl Topology {
         // If the kernel is compiled with CONFIG_NUMA, then build a topology
         // from the NUMA hierarchy in sysfs. Otherwise, just make a single
         // default node of ID 0 which contains all cores.
-        let path = format!("{}/sys/devices/system/node", *ROOT_PREFIX);
+        let path = format!("{}/sys/devices/system/node", root_prefix());
         let nodes = if Path::new(&path).exists() {
             create_numa_nodes(&span, &mut topo_ctx, nr_cores_per_vllc)?
         } else {
@@ -307,6 +364,30 @@ impl Topology {
         Self::instantiate(span, nodes)
     }
 
+    /// Build a Topology from JSON produced by serializing a Topology, e.g.
+    /// with `scx_topo dump`. This allows running topology dependent code
+    /// against captured topologies of other machines.
+    ///
+    /// All CPU IDs must fit in the Cpumask width. When loading a topology
+    /// of a larger machine in tests, set the width first with
+    /// `set_cpumask_test_width()`.
+    pub fn from_json(json: &str) -> Result<Topology> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Like from_json(), but sizes the Cpumasks to the `nr_cpu_ids` of the
+    /// machine the JSON was captured on rather than the running system.
+    /// This allows loading the topology of another machine, e.g. to display
+    /// data recorded there. The Cpumasks of the returned Topology may differ
+    /// in width from those of the running system and shouldn't be combined
+    /// with them.
+    pub fn from_json_with_width(json: &str, nr_cpu_ids: usize) -> Result<Topology> {
+        if nr_cpu_ids == 0 {
+            bail!("nr_cpu_ids must be positive");
+        }
+        with_cpumask_width(nr_cpu_ids, || Self::from_json(json))
+    }
+
     /// Build a topology with configuration from CLI arguments.
     /// This method integrates with the TopologyArgs from the cli module to
     /// create a topology based on command line parameters.
@@ -524,8 +605,8 @@ impl TopoCtx {
     }
 }
 
-fn cpus_online() -> Result<Cpumask> {
-    let path = format!("{}/sys/devices/system/cpu/online", *ROOT_PREFIX);
+pub(crate) fn cpus_online() -> Result<Cpumask> {
+    let path = format!("{}/sys/devices/system/cpu/online", root_prefix());
     let online = std::fs::read_to_string(path)?;
     Cpumask::from_cpulist(&online)
 }
@@ -592,14 +673,14 @@ fn create_insert_cpu(
     cs: &CapacitySource,
     flatten_llc: bool,
 ) -> Result<()> {
-    // CPU is offline. The Topology hierarchy is read-only, and assumes
-    // that hotplug will cause the scheduler to restart. Thus, we can
-    // just skip this CPU altogether.
+    // CPU is offline. The Topology hierarchy is read-only. Hotplug either
+    // causes the scheduler to restart or a new Topology to be built by
+    // TopologyWatcher. Thus, we can just skip this CPU altogether.
     if !online_mask.test_cpu(id) {
         return Ok(());
     }
 
-    let cpu_str = format!("{}/sys/devices/system/cpu/cpu{}", *ROOT_PREFIX, id);
+    let cpu_str = format!("{}/sys/devices/system/cpu/cpu{}", root_prefix(), id);
     let cpu_path = Path::new(&cpu_str);
 
     // Physical core ID
@@ -728,11 +809,11 @@ fn create_insert_cpu(
 
 fn read_cpu_ids() -> Result<Vec<usize>> {
     let mut cpu_ids = vec![];
-    let path = format!("{}/sys/devices/system/cpu/cpu[0-9]*", *ROOT_PREFIX);
+    let path = format!("{}/sys/devices/system/cpu/cpu[0-9]*", root_prefix());
     let cpu_paths = glob(&path)?;
     for cpu_path in cpu_paths.filter_map(Result::ok) {
         let cpu_str = cpu_path.to_str().unwrap().trim();
-        if ROOT_PREFIX.is_empty() {
+        if root_prefix().is_empty() {
             match sscanf!(cpu_str, "/sys/devices/system/cpu/cpu{usize}") {
                 Some(val) => cpu_ids.push(val),
                 None => {
@@ -775,7 +856,7 @@ fn get_capacity_source() -> Option<CapacitySource> {
     ];
 
     // Find the most precise source for cpu_capacity estimation.
-    let prefix = format!("{}/sys/devices/system/cpu/cpu0", *ROOT_PREFIX);
+    let prefix = format!("{}/sys/devices/system/cpu/cpu0", root_prefix());
     let mut raw_capacity;
     let mut suffix = sources[sources.len() - 1];
     'outer: for src in sources {
@@ -786,7 +867,7 @@ fn get_capacity_source() -> Option<CapacitySource> {
             // It would be an okay source...
             suffix = src;
             // But double-check if the source has meaningful information.
-            let path = format!("{}/sys/devices/system/cpu/cpu[0-9]*", *ROOT_PREFIX);
+            let path = format!("{}/sys/devices/system/cpu/cpu[0-9]*", root_prefix());
             let cpu_paths = glob(&path).ok()?;
             for cpu_path in cpu_paths.filter_map(Result::ok) {
                 let raw_capacity2 = read_from_file(&cpu_path.join(suffix)).unwrap_or(0_usize);
@@ -807,7 +888,7 @@ fn get_capacity_source() -> Option<CapacitySource> {
     let mut avg_rcap = 0;
     let mut nr_cpus = 0;
     let mut has_biglittle = false;
-    let path = format!("{}/sys/devices/system/cpu/cpu[0-9]*", *ROOT_PREFIX);
+    let path = format!("{}/sys/devices/system/cpu/cpu[0-9]*", root_prefix());
     let cpu_paths = glob(&path).ok()?;
     for cpu_path in cpu_paths.filter_map(Result::ok) {
         let rcap = read_from_file(&cpu_path.join(suffix)).unwrap_or(0_usize);
@@ -848,7 +929,7 @@ fn get_capacity_source() -> Option<CapacitySource> {
 }
 
 fn is_smt_active() -> Option<bool> {
-    let path = format!("{}/sys/devices/system/cpu/smt/active", *ROOT_PREFIX);
+    let path = format!("{}/sys/devices/system/cpu/smt/active", root_prefix());
     let smt_on: u8 = read_from_file(Path::new(&path)).ok()?;
     Some(smt_on == 1)
 }
@@ -1004,7 +1085,7 @@ fn create_default_node(
         }
     }
 
-    let path = format!("{}/sys/devices/system/cpu", *ROOT_PREFIX);
+    let path = format!("{}/sys/devices/system/cpu", root_prefix());
     if !Path::new(&path).exists() {
         bail!("/sys/devices/system/cpu sysfs node not found");
     }
@@ -1035,11 +1116,11 @@ fn create_numa_nodes(
     #[cfg(feature = "gpu-topology")]
     let system_gpus = create_gpus();
 
-    let path = format!("{}/sys/devices/system/node/node*", *ROOT_PREFIX);
+    let path = format!("{}/sys/devices/system/node/node*", root_prefix());
     let numa_paths = glob(&path)?;
     for numa_path in numa_paths.filter_map(Result::ok) {
         let numa_str = numa_path.to_str().unwrap().trim();
-        let node_id = if ROOT_PREFIX.is_empty() {
+        let node_id = if root_prefix().is_empty() {
             match sscanf!(numa_str, "/sys/devices/system/node/node{usize}") {
                 Some(val) => val,
                 None => {
@@ -1058,7 +1139,8 @@ fn create_numa_nodes(
         let distance = read_file_usize_vec(
             Path::new(&format!(
                 "{}/sys/devices/system/node/node{}/distance",
-                *ROOT_PREFIX, node_id
+                root_prefix(),
+                node_id
             )),
             ' ',
         )?;
@@ -1090,7 +1172,7 @@ fn create_numa_nodes(
         let mut cpu_ids = vec![];
         for cpu_path in cpu_paths.filter_map(Result::ok) {
             let cpu_str = cpu_path.to_str().unwrap().trim();
-            let cpu_id = if ROOT_PREFIX.is_empty() {
+            let cpu_id = if root_prefix().is_empty() {
                 match sscanf!(cpu_str, "/sys/devices/system/node/node{usize}/cpu{usize}") {
                     Some((_, val)) => val,
                     None => {
@@ -1432,4 +1514,56 @@ mod tests {
         assert!(header.contains("cpus=  3(  2c)"));
         assert!(header.contains("[  5, 10]"));
     }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let (topo, total) = make_test_topo(2, 2, 2, 2);
+        let json = serde_json::to_string(&topo).unwrap();
+        let loaded = Topology::from_json(&json).unwrap();
+
+        assert_eq!(loaded.span, topo.span);
+        assert_eq!(loaded.smt_enabled, topo.smt_enabled);
+        assert_eq!(loaded.all_cpus.len(), total);
+        assert_eq!(loaded.all_cores.len(), topo.all_cores.len());
+        assert_eq!(loaded.all_llcs.len(), topo.all_llcs.len());
+        for (id, cpu) in topo.all_cpus.iter() {
+            assert_eq!(loaded.all_cpus[id], *cpu);
+        }
+        for (id, node) in topo.nodes.iter() {
+            assert_eq!(loaded.nodes[id].distance, node.distance);
+            assert_eq!(loaded.nodes[id].all_cpus.len(), node.all_cpus.len());
+        }
+        assert_eq!(serde_json::to_string(&loaded).unwrap(), json);
+    }
+
+    #[test]
+    fn test_json_too_wide() {
+        let (topo, _) = make_test_topo(1, 1, 4, 2);
+        let json = serde_json::to_string(&topo).unwrap();
+        crate::set_cpumask_test_width(4);
+        assert!(Topology::from_json(&json).is_err());
+    }
+
+    #[test]
+    fn test_json_with_width() {
+        let (topo, _) = make_test_topo(2, 2, 2, 2);
+        let json = serde_json::to_string(&topo).unwrap();
+        crate::set_cpumask_test_width(4);
+
+        let loaded = Topology::from_json_with_width(&json, 16).unwrap();
+        assert_eq!(loaded.all_cpus.len(), 16);
+        assert_eq!(loaded.span.weight(), 16);
+        assert_eq!(loaded.span.len(), 16);
+        assert_eq!(serde_json::to_string(&loaded).unwrap(), json);
+
+        // The override is restored afterwards, also on errors
+        assert!(Cpumask::new().set_cpu(4).is_err());
+        assert!(Topology::from_json_with_width(&json, 8).is_err());
+        assert!(Topology::from_json_with_width(&json, 0).is_err());
+        assert!(Cpumask::new().set_cpu(4).is_err());
+
+        let wider = Topology::from_json_with_width(&json, 64).unwrap();
+        assert_eq!(wider.span.len(), 64);
+        assert_eq!(wider.span.weight(), 16);
+    }
 }
@@ -0,0 +1,385 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+//! # SCX Topology Watcher
+//!
+//! [`Topology`] is a read-only snapshot of the host. [`TopologyWatcher`]
+//! monitors CPU online/offline events and cpufreq capacity changes, and
+//! produces a freshly built [`Topology`] along with a [`TopologyDiff`]
+//! describing what changed. This allows schedulers to rebuild their
+//! scheduling domains in place instead of restarting on hotplug.
+//!
+//!```no_run
+//!     use scx_utils::TopologyWatcher;
+//!     use std::time::Duration;
+//!
+//!     let mut watcher = TopologyWatcher::new().unwrap();
+//!     loop {
+//!         if let Some(change) = watcher.poll().unwrap() {
+//!             println!("topology changed: {}", change.diff);
+//!         }
+//!         std::thread::sleep(Duration::from_secs(1));
+//!     }
+//!```
+//!
+//! Note that sysfs doesn't support inotify, so the watcher polls a cheap
+//! fingerprint of the relevant sysfs files and only rebuilds the Topology
+//! when the fingerprint changes.
+
+use crate::compat::root_prefix;
+use crate::topology::cpus_online;
+use crate::topology::Topology;
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Per-CPU sysfs files which affect the capacity and frequency values of
+/// [`crate::Cpu`]. This is a superset of the capacity sources considered
+/// by [`Topology::new()`].
+const CPU_CAPACITY_FILES: &[&str] = &[
+    "cpufreq/amd_pstate_prefcore_ranking",
+    "cpufreq/amd_pstate_highest_perf",
+    "acpi_cppc/highest_perf",
+    "cpu_capacity",
+    "cpufreq/cpuinfo_max_freq",
+    "cpufreq/scaling_min_freq",
+    "cpufreq/scaling_max_freq",
+];
+
+/// Differences between two [`Topology`] instances.
+///
+/// Core and LLC IDs are assigned sequentially while building a Topology and
+/// may be renumbered when CPUs come and go. Cores and LLCs are thus matched
+/// by their kernel IDs. IDs of removed objects refer to the old Topology
+/// while IDs of added objects refer to the new one.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TopologyDiff {
+    pub cpus_added: Vec<usize>,
+    pub cpus_removed: Vec<usize>,
+    pub cores_added: Vec<usize>,
+    pub cores_removed: Vec<usize>,
+    pub llcs_added: Vec<usize>,
+    pub llcs_removed: Vec<usize>,
+    pub nodes_added: Vec<usize>,
+    pub nodes_removed: Vec<usize>,
+    /// CPUs present in both whose capacity, frequencies or core type changed.
+    pub cpus_capacity_changed: Vec<usize>,
+}
+
+impl TopologyDiff {
+    /// Compute the differences from @old to @new.
+    pub fn between(old: &Topology, new: &Topology) -> Self {
+        let core_keys = |topo: &Topology| -> BTreeMap<(usize, usize, usize), usize> {
+            topo.all_cores
+                .values()
+                .map(|core| {
+                    let package_id = core
+                        .cpus
+                        .values()
+                        .next()
+                        .map(|cpu| cpu.package_id)
+                        .unwrap_or(0);
+                    ((core.node_id, package_id, core.kernel_id), core.id)
+                })
+                .collect()
+        };
+        let llc_keys = |topo: &Topology| -> BTreeMap<(usize, usize), usize> {
+            topo.all_llcs
+                .values()
+                .map(|llc| ((llc.node_id, llc.kernel_id), llc.id))
+                .collect()
+        };
+
+        let (cpus_added, cpus_removed) = Self::added_removed(
+            &old.all_cpus.keys().map(|&id| (id, id)).collect(),
+            &new.all_cpus.keys().map(|&id| (id, id)).collect(),
+        );
+        let (cores_added, cores_removed) = Self::added_removed(&core_keys(old), &core_keys(new));
+        let (llcs_added, llcs_removed) = Self::added_removed(&llc_keys(old), &llc_keys(new));
+        let (nodes_added, nodes_removed) = Self::added_removed(
+            &old.nodes
+                .iter()
+                .filter(|(_, node)| !node.all_cpus.is_empty())
+                .map(|(&id, _)| (id, id))
+                .collect(),
+            &new.nodes
+                .iter()
+                .filter(|(_, node)| !node.all_cpus.is_empty())
+                .map(|(&id, _)| (id, id))
+                .collect(),
+        );
+
+        let cpus_capacity_changed = new
+            .all_cpus
+            .iter()
+            .filter_map(|(id, cpu)| {
+                let prev = old.all_cpus.get(id)?;
+                (prev.cpu_capacity != cpu.cpu_capacity
+                    || prev.min_freq != cpu.min_freq
+                    || prev.max_freq != cpu.max_freq
+                    || prev.core_type != cpu.core_type)
+                    .then_some(*id)
+            })
+            .collect();
+
+        Self {
+            cpus_added,
+            cpus_removed,
+            cores_added,
+            cores_removed,
+            llcs_added,
+            llcs_removed,
+            nodes_added,
+            nodes_removed,
+            cpus_capacity_changed,
+        }
+    }
+
+    fn added_removed<K: Ord>(
+        old: &BTreeMap<K, usize>,
+        new: &BTreeMap<K, usize>,
+    ) -> (Vec<usize>, Vec<usize>) {
+        let added: BTreeSet<usize> = new
+            .iter()
+            .filter(|(key, _)| !old.contains_key(key))
+            .map(|(_, &id)| id)
+            .collect();
+        let removed: BTreeSet<usize> = old
+            .iter()
+            .filter(|(key, _)| !new.contains_key(key))
+            .map(|(_, &id)| id)
+            .collect();
+        (added.into_iter().collect(), removed.into_iter().collect())
+    }
+
+    /// True if the set of CPUs, cores, LLCs or nodes changed. Schedulers
+    /// usually need to rebuild their domains in this case.
+    pub fn hotplugged(&self) -> bool {
+        !(self.cpus_added.is_empty()
+            && self.cpus_removed.is_empty()
+            && self.cores_added.is_empty()
+            && self.cores_removed.is_empty()
+            && self.llcs_added.is_empty()
+            && self.llcs_removed.is_empty()
+            && self.nodes_added.is_empty()
+            && self.nodes_removed.is_empty())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.hotplugged() && self.cpus_capacity_changed.is_empty()
+    }
+}
+
+impl fmt::Display for TopologyDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fields = [
+            ("cpus+", &self.cpus_added),
+            ("cpus-", &self.cpus_removed),
+            ("cores+", &self.cores_added),
+            ("cores-", &self.cores_removed),
+            ("llcs+", &self.llcs_added),
+            ("llcs-", &self.llcs_removed),
+            ("nodes+", &self.nodes_added),
+            ("nodes-", &self.nodes_removed),
+            ("capacity", &self.cpus_capacity_changed),
+        ];
+
+        let mut first = true;
+        for (name, ids) in fields.iter().filter(|(_, ids)| !ids.is_empty()) {
+            if !first {
+                write!(f, " ")?;
+            }
+            write!(f, "{}={:?}", name, ids)?;
+            first = false;
+        }
+        if first {
+            write!(f, "none")?;
+        }
+        Ok(())
+    }
+}
+
+/// A new [`Topology`] and how it differs from the previous one.
+#[derive(Debug)]
+pub struct TopologyChange {
+    pub topo: Arc<Topology>,
+    pub diff: TopologyDiff,
+}
+
+type TopologyBuilder = Box<dyn Fn() -> Result<Topology> + Send>;
+
+/// Watches the host for CPU hotplug and capacity changes.
+pub struct TopologyWatcher {
+    build: TopologyBuilder,
+    topo: Arc<Topology>,
+    fingerprint: String,
+}
+
+impl TopologyWatcher {
+    /// Create a watcher which builds Topology objects with
+    /// [`Topology::new()`].
+    pub fn new() -> Result<Self> {
+        Self::with_builder(Topology::new)
+    }
+
+    /// Create a watcher which builds Topology objects by calling @build,
+    /// e.g. `|| Topology::with_args(&args)`, so that the scheduler's
+    /// topology configuration is preserved across changes.
+    pub fn with_builder<F>(build: F) -> Result<Self>
+    where
+        F: Fn() -> Result<Topology> + Send + 'static,
+    {
+        let fingerprint = Self::read_fingerprint()?;
+        let topo = Arc::new(build()?);
+        Ok(Self {
+            build: Box::new(build),
+            topo,
+            fingerprint,
+        })
+    }
+
+    /// The most recently built Topology.
+    pub fn topology(&self) -> Arc<Topology> {
+        self.topo.clone()
+    }
+
+    fn read_fingerprint() -> Result<String> {
+        let online = cpus_online()?;
+        let mut fp = online.to_cpulist();
+
+        for cpu in online.iter() {
+            let cpu_path = format!("{}/sys/devices/system/cpu/cpu{}", root_prefix(), cpu);
+            fp.push_str(&format!("\n{}:", cpu));
+            for file in CPU_CAPACITY_FILES.iter() {
+                let val =
+                    std::fs::read_to_string(format!("{}/{}", cpu_path, file)).unwrap_or_default();
+                fp.push_str(val.trim());
+                fp.push(',');
+            }
+        }
+
+        Ok(fp)
+    }
+
+    /// Check whether the host topology changed since the last call. If so,
+    /// a new Topology is built and returned along with the diff against the
+    /// previous one. Returns `None` if nothing relevant changed.
+    pub fn poll(&mut self) -> Result<Option<TopologyChange>> {
+        let fingerprint = Self::read_fingerprint()?;
+        if fingerprint == self.fingerprint {
+            return Ok(None);
+        }
+
+        // CPUs may be in the middle of going on or offline and building
+        // can fail transiently. Leave the fingerprint alone so that the
+        // next poll retries.
+        let topo = Arc::new((self.build)()?);
+        let diff = TopologyDiff::between(&self.topo, &topo);
+        self.fingerprint = fingerprint;
+
+        if diff.is_empty() {
+            return Ok(None);
+        }
+
+        self.topo = topo.clone();
+        Ok(Some(TopologyChange { topo, diff }))
+    }
+
+    /// Poll every @intv from a background thread and send changes through
+    /// the returned channel. The thread exits when @shutdown is set or the
+    /// receiver is dropped. Polling errors are logged and retried.
+    pub fn spawn(
+        mut self,
+        intv: Duration,
+        shutdown: Arc<AtomicBool>,
+    ) -> mpsc::Receiver<TopologyChange> {
+        let (tx, rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            while !shutdown.load(Ordering::Relaxed) {
+                match self.poll() {
+                    Ok(Some(change)) => {
+                        if tx.send(change).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => log::warn!("Failed to update topology ({:?})", &e),
+                }
+                thread::sleep(intv);
+            }
+        });
+
+        rx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::*;
+    use crate::Cpu;
+
+    #[test]
+    fn test_diff_identical() {
+        let (a, _) = make_test_topo(2, 2, 2, 2);
+        let (b, _) = make_test_topo(2, 2, 2, 2);
+        let diff = TopologyDiff::between(&a, &b);
+        assert!(diff.is_empty());
+        assert_eq!(format!("{}", diff), "none");
+    }
+
+    #[test]
+    fn test_diff_core_removed() {
+        let (old, _) = make_test_topo(1, 1, 4, 2);
+        let (new, _) = make_test_topo(1, 1, 3, 2);
+        let diff = TopologyDiff::between(&old, &new);
+        assert_eq!(diff.cpus_removed, vec![6, 7]);
+        assert_eq!(diff.cores_removed, vec![3]);
+        assert!(diff.cpus_added.is_empty());
+        assert!(diff.cores_added.is_empty());
+        assert!(diff.llcs_removed.is_empty());
+        assert!(diff.hotplugged());
+    }
+
+    #[test]
+    fn test_diff_llcs_and_nodes_added() {
+        let (old, _) = make_test_topo(1, 1, 2, 2);
+        let (new, _) = make_test_topo(2, 1, 2, 2);
+        let diff = TopologyDiff::between(&old, &new);
+        assert_eq!(diff.cpus_added, vec![4, 5, 6, 7]);
+        assert_eq!(diff.cores_added, vec![2, 3]);
+        assert_eq!(diff.llcs_added, vec![1]);
+        assert_eq!(diff.nodes_added, vec![1]);
+        assert!(diff.cpus_removed.is_empty());
+        assert_eq!(
+            format!("{}", diff),
+            "cpus+=[4, 5, 6, 7] cores+=[2, 3] llcs+=[1] nodes+=[1]"
+        );
+    }
+
+    #[test]
+    fn test_diff_capacity_changed() {
+        let (old, _) = make_test_topo(1, 1, 2, 2);
+        let (mut new, _) = make_test_topo(1, 1, 2, 2);
+        let cpu = Cpu {
+            cpu_capacity: 512,
+            ..(*new.all_cpus[&1]).clone()
+        };
+        new.all_cpus.insert(1, Arc::new(cpu));
+
+        let diff = TopologyDiff::between(&old, &new);
+        assert_eq!(diff.cpus_capacity_changed, vec![1]);
+        assert!(!diff.hotplugged());
+        assert!(!diff.is_empty());
+    }
+}
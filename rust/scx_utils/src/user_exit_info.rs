@@ -4,8 +4,13 @@
 // GNU General Public License version 2.
 use crate::bindings;
 use crate::compat;
+use crate::crash_report::CrashDump;
+use crate::crash_report::CrashReport;
+use crate::crash_report::CrashStore;
 use anyhow::bail;
 use anyhow::Result;
+use log::info;
+use log::warn;
 use std::ffi::CStr;
 use std::os::raw::c_char;
 use std::sync::Mutex;
@@ -107,11 +112,16 @@ macro_rules! uei_exited {
 }
 
 /// Takes a reference to C struct user_exit_info, reads, invokes
-/// UserExitInfo::report() on and then returns Ok(uei). See UserExitInfo.
+/// UserExitInfo::report() on and then returns Ok(uei). Error exits are also
+/// saved as crash reports, see UserExitInfo::save_crash_report().
 #[macro_export]
 macro_rules! uei_report {
     ($skel: expr, $uei:ident) => {{
         let uei = scx_utils::uei_read!($skel, $uei);
+        uei.save_crash_report(
+            env!("CARGO_PKG_NAME"),
+            &scx_utils::build_id::full_version(env!("CARGO_PKG_VERSION")),
+        );
         uei.report().and_then(|_| Ok(uei))
     }};
 }
@@ -227,6 +237,53 @@ impl UserExitInfo {
             .then_some(self.exit_code)
     }
 
+    /// Name of the exit kind, e.g. "ErrorStall".
+    pub fn kind_name(&self) -> String {
+        let names = [
+            (ScxExitKind::None as i32, "None"),
+            (ScxExitKind::Done as i32, "Done"),
+            (ScxExitKind::Unreg as i32, "Unreg"),
+            (ScxExitKind::UnregBPF as i32, "UnregBPF"),
+            (ScxExitKind::UnregKern as i32, "UnregKern"),
+            (ScxExitKind::SysRq as i32, "SysRq"),
+            (ScxExitKind::Error as i32, "Error"),
+            (ScxExitKind::ErrorBPF as i32, "ErrorBPF"),
+            (ScxExitKind::ErrorStall as i32, "ErrorStall"),
+        ];
+        match names.iter().find(|(kind, _)| *kind == self.kind) {
+            Some((_, name)) => name.to_string(),
+            None => format!("Unknown({})", self.kind),
+        }
+    }
+
+    /// Build a crash report of the exit, see crash_report.
+    pub fn crash_report(&self, scheduler: &str, version: &str) -> CrashReport {
+        let mut report = CrashReport::new(scheduler, version);
+        report.exit_kind = self.kind;
+        report.exit_kind_name = self.kind_name();
+        report.exit_code = self.exit_code;
+        report.reason = self.reason.clone();
+        report.msg = self.msg.clone();
+        report.dump = self.dump.as_deref().map(CrashDump::parse);
+        report
+    }
+
+    /// Save a crash report into the crash directory if the scheduler exited
+    /// with an error. Failures are only logged so that they don't hide the
+    /// exit itself.
+    pub fn save_crash_report(&self, scheduler: &str, version: &str) {
+        if self.kind <= ScxExitKind::UnregKern as i32 {
+            return;
+        }
+        let Some(store) = CrashStore::from_env() else {
+            return;
+        };
+        match store.save(&self.crash_report(scheduler, version)) {
+            Ok(path) => info!("Crash report saved to {}", path.display()),
+            Err(e) => warn!("Failed to save crash report: {:#}", e),
+        }
+    }
+
     /// Test whether the BPF scheduler requested restart.
     pub fn should_restart(&self) -> bool {
         match self.exit_code() {
@@ -71,6 +71,19 @@
 //!                        // directly to the underlying BPF dispatcher
 //! }
 //!
+//! ### trait `SchedPolicy`
+//!
+//! Instead of consuming and dispatching the tasks by hand, the scheduling policy implements the
+//! `SchedPolicy` trait and a `Runner` drives it on top of the `BpfScheduler`:
+//!
+//! - `on_enqueue(task: QueuedTask)`: A task wants to run
+//! - `pick_next()`: Return the next task to dispatch (None = nothing to dispatch)
+//! - `nr_pending()`: Amount of tasks still held by the policy
+//!
+//! `Runner::run_once()` runs a single scheduling cycle: it drains all the queued tasks, dispatches
+//! the tasks returned by pick_next() on an idle CPU (or on the first CPU available), retries the
+//! tasks that failed to dispatch and finally calls `notify_complete()`.
+//!
 //! Other internal statistics that can be used to implement better scheduling policies:
 //!
 //!  let n: u64 = *self.bpf.nr_online_cpus_mut();       // amount of online CPUs
@@ -90,26 +103,70 @@ pub mod bpf_intf;
 
 #[rustfmt::skip]
 mod bpf;
+use std::collections::VecDeque;
 use std::mem::MaybeUninit;
 use std::time::SystemTime;
 
 use anyhow::Result;
 use bpf::*;
 use libbpf_rs::OpenObject;
+use scx_rustland_core::Runner;
+use scx_rustland_core::SchedPolicy;
 use scx_utils::libbpf_clap_opts::LibbpfOpts;
 use scx_utils::UserExitInfo;
 
 // Maximum time slice (in nanoseconds) that a task can use before it is re-enqueued.
 const SLICE_NS: u64 = 5_000_000;
 
+// Round-Robin policy: tasks are dispatched in FIFO order with a time slice inversely
+// proportional to the number of tasks waiting to be scheduled.
+#[derive(Default)]
+struct RoundRobin {
+    queue: VecDeque<QueuedTask>,
+}
+
+impl SchedPolicy for RoundRobin {
+    fn on_enqueue(&mut self, task: QueuedTask) {
+        self.queue.push_back(task);
+    }
+
+    fn pick_next(&mut self) -> Option<DispatchedTask> {
+        // Get the amount of tasks that are waiting to be scheduled.
+        let nr_waiting = self.queue.len() as u64;
+
+        // Create a new task to be dispatched from the received enqueued task.
+        //
+        // The runner decides where the task needs to run: the most suitable idle CPU for the
+        // task, prioritizing its previously used CPU (task.cpu), or the first CPU available if
+        // there are no idle CPUs.
+        let task = self.queue.pop_front()?;
+        let mut dispatched_task = DispatchedTask::new(&task);
+
+        // Determine the task's time slice: assign value inversely proportional to the number of
+        // tasks waiting to be scheduled.
+        dispatched_task.slice_ns = SLICE_NS / (nr_waiting + 1);
+
+        Some(dispatched_task)
+    }
+
+    fn on_task_exit(&mut self, pid: i32) {
+        self.queue.retain(|task| task.pid != pid);
+    }
+
+    fn nr_pending(&self) -> u64 {
+        self.queue.len() as u64
+    }
+}
+
 struct Scheduler<'a> {
-    bpf: BpfScheduler<'a>, // Connector to the sched_ext BPF backend
+    bpf: BpfScheduler<'a>,      // Connector to the sched_ext BPF backend
+    runner: Runner<RoundRobin>, // Scheduling loop driving the Round-Robin policy
 }
 
 impl<'a> Scheduler<'a> {
     fn init(open_object: &'a mut MaybeUninit<OpenObject>) -> Result<Self> {
         let open_opts = LibbpfOpts::default();
-        let bpf = BpfScheduler::init(
+        let mut bpf = BpfScheduler::init(
             open_object,
             open_opts.clone().into_bpf_open_opts(),
             0,        // exit_dump_len (buffer size of exit info, 0 = default)
@@ -120,40 +177,14 @@ impl<'a> Scheduler<'a> {
             SLICE_NS, // default time slice (for tasks automatically dispatched by the backend)
             "rlfifo", // name of the scx ops
         )?;
-        Ok(Self { bpf })
-    }
-
-    fn dispatch_tasks(&mut self) {
-        // Get the amount of tasks that are waiting to be scheduled.
-        let nr_waiting = *self.bpf.nr_queued_mut();
-
-        // Start consuming and dispatching tasks, until all the CPUs are busy or there are no more
-        // tasks to be dispatched.
-        while let Ok(Some(task)) = self.bpf.dequeue_task() {
-            // Create a new task to be dispatched from the received enqueued task.
-            let mut dispatched_task = DispatchedTask::new(&task);
-
-            // Decide where the task needs to run (pick a target CPU).
-            //
-            // A call to select_cpu() will return the most suitable idle CPU for the task,
-            // prioritizing its previously used CPU (task.cpu).
-            //
-            // If we can't find any idle CPU, run on the first CPU available.
-            let cpu = self.bpf.select_cpu(task.pid, task.cpu, task.flags);
-            dispatched_task.cpu = if cpu >= 0 { cpu } else { RL_CPU_ANY };
 
-            // Determine the task's time slice: assign value inversely proportional to the number
-            // of tasks waiting to be scheduled.
-            dispatched_task.slice_ns = SLICE_NS / (nr_waiting + 1);
+        // Drop the tasks held by the policy when they exit.
+        bpf.set_notify_exit(true);
 
-            // Dispatch the task.
-            self.bpf.dispatch_task(&dispatched_task).unwrap();
-        }
-
-        // Notify the BPF component that tasks have been dispatched.
-        //
-        // This function will put the scheduler to sleep, until another task needs to run.
-        self.bpf.notify_complete(0);
+        Ok(Self {
+            bpf,
+            runner: Runner::new(RoundRobin::default()),
+        })
     }
 
     fn print_stats(&mut self) {
@@ -187,7 +218,11 @@ impl<'a> Scheduler<'a> {
         let mut prev_ts = Self::now();
 
         while !self.bpf.exited() {
-            self.dispatch_tasks();
+            // Consume and dispatch all the tasks, then notify the BPF component that tasks have
+            // been dispatched.
+            //
+            // This will put the scheduler to sleep, until another task needs to run.
+            self.runner.run_once(&mut self.bpf);
 
             let curr_ts = Self::now();
             if curr_ts > prev_ts {
@@ -232,3 +267,32 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(pid: i32) -> QueuedTask {
+        QueuedTask {
+            pid,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let mut policy = RoundRobin::default();
+        for pid in 1..=3 {
+            policy.on_enqueue(task(pid));
+        }
+        policy.on_task_exit(2);
+        assert_eq!(policy.nr_pending(), 2);
+
+        // Tasks are dispatched in FIFO order, the slice grows as the queue drains.
+        let t = policy.pick_next().unwrap();
+        assert_eq!((t.pid, t.slice_ns), (1, SLICE_NS / 3));
+        let t = policy.pick_next().unwrap();
+        assert_eq!((t.pid, t.slice_ns), (3, SLICE_NS / 2));
+        assert!(policy.pick_next().is_none());
+    }
+}
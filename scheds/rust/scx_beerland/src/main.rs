@@ -11,35 +11,32 @@ pub mod bpf_intf;
 pub use bpf_intf::*;
 
 mod stats;
-use std::collections::HashSet;
 use std::ffi::{c_int, c_ulong};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::mem::MaybeUninit;
 use std::sync::atomic::AtomicBool;
-use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use anyhow::bail;
-use anyhow::Context;
 use anyhow::Result;
 use clap::Parser;
-use crossbeam::channel::RecvTimeoutError;
 use libbpf_rs::OpenObject;
 use libbpf_rs::ProgramInput;
-use log::{debug, info, warn};
+use log::info;
+use scx_runtime::RuntimeOpts;
+use scx_runtime::SchedulerOpts;
 use scx_stats::prelude::*;
 use scx_utils::build_id;
 use scx_utils::compat;
-use scx_utils::libbpf_clap_opts::LibbpfOpts;
 use scx_utils::scx_ops_attach;
 use scx_utils::scx_ops_load;
 use scx_utils::scx_ops_open;
 use scx_utils::try_set_rlimit_infinity;
 use scx_utils::uei_exited;
 use scx_utils::uei_report;
-use scx_utils::CoreType;
+use scx_utils::CpuDomain;
 use scx_utils::Topology;
 use scx_utils::UserExitInfo;
 use scx_utils::NR_CPU_IDS;
@@ -55,10 +52,6 @@ const SCHEDULER_NAME: &str = "scx_beerland";
     about = "Scheduler designed to prioritize locality and scalability."
 )]
 struct Opts {
-    /// Exit debug dump buffer length. 0 indicates default.
-    #[clap(long, default_value = "0")]
-    exit_dump_len: u32,
-
     /// Maximum scheduling slice duration in microseconds.
     #[clap(short = 's', long, default_value = "1000")]
     slice_us: u64,
@@ -88,20 +81,6 @@ struct Opts {
     #[clap(short = 'p', long, default_value = "250")]
     polling_ms: u64,
 
-    /// Specifies a list of CPUs to prioritize.
-    ///
-    /// Accepts a comma-separated list of CPUs or ranges (i.e., 0-3,12-15) or the following special
-    /// keywords:
-    ///
-    /// "turbo" = automatically detect and prioritize the CPUs with the highest max frequency,
-    /// "performance" = automatically detect and prioritize the fastest CPUs,
-    /// "powersave" = automatically detect and prioritize the slowest CPUs,
-    /// "all" = all CPUs assigned to the primary domain.
-    ///
-    /// By default "all" CPUs are used.
-    #[clap(short = 'm', long)]
-    primary_domain: Option<String>,
-
     /// Enable preferred idle CPU scanning.
     ///
     /// With this option enabled, the scheduler will prioritize assigning tasks to higher-ranked
@@ -109,133 +88,8 @@ struct Opts {
     #[clap(short = 'P', long, action = clap::ArgAction::SetTrue)]
     preferred_idle_scan: bool,
 
-    /// Enable stats monitoring with the specified interval.
-    #[clap(long)]
-    stats: Option<f64>,
-
-    /// Run in stats monitoring mode with the specified interval. Scheduler
-    /// is not launched.
-    #[clap(long)]
-    monitor: Option<f64>,
-
-    /// Enable verbose output, including libbpf details.
-    #[clap(short = 'v', long, action = clap::ArgAction::SetTrue)]
-    verbose: bool,
-
-    /// Print scheduler version and exit.
-    #[clap(short = 'V', long, action = clap::ArgAction::SetTrue)]
-    version: bool,
-
-    /// Show descriptions for statistics.
-    #[clap(long)]
-    help_stats: bool,
-
-    #[clap(flatten, next_help_heading = "Libbpf Options")]
-    pub libbpf: LibbpfOpts,
-}
-
-#[derive(PartialEq)]
-enum Powermode {
-    Turbo,
-    Performance,
-    Powersave,
-    Any,
-}
-
-/*
- * TODO: this code is shared between scx_bpfland, scx_flash and scx_cosmos; consder to move it to
- * scx_utils.
- */
-fn get_primary_cpus(mode: Powermode) -> std::io::Result<Vec<usize>> {
-    let cpus: Vec<usize> = Topology::new()
-        .unwrap()
-        .all_cores
-        .values()
-        .flat_map(|core| &core.cpus)
-        .filter_map(|(cpu_id, cpu)| match (&mode, &cpu.core_type) {
-            // Turbo mode: prioritize CPUs with the highest max frequency
-            (Powermode::Turbo, CoreType::Big { turbo: true }) |
-            // Performance mode: add all the Big CPUs (either Turbo or non-Turbo)
-            (Powermode::Performance, CoreType::Big { .. }) |
-            // Powersave mode: add all the Little CPUs
-            (Powermode::Powersave, CoreType::Little) => Some(*cpu_id),
-            (Powermode::Any, ..) => Some(*cpu_id),
-            _ => None,
-        })
-        .collect();
-
-    Ok(cpus)
-}
-
-pub fn parse_cpu_list(optarg: &str) -> Result<Vec<usize>, String> {
-    let mut cpus = Vec::new();
-    let mut seen = HashSet::new();
-
-    // Handle special keywords
-    if let Some(mode) = match optarg {
-        "powersave" => Some(Powermode::Powersave),
-        "performance" => Some(Powermode::Performance),
-        "turbo" => Some(Powermode::Turbo),
-        "all" => Some(Powermode::Any),
-        _ => None,
-    } {
-        return get_primary_cpus(mode).map_err(|e| e.to_string());
-    }
-
-    // Validate input characters
-    if optarg
-        .chars()
-        .any(|c| !c.is_ascii_digit() && c != '-' && c != ',' && !c.is_whitespace())
-    {
-        return Err("Invalid character in CPU list".to_string());
-    }
-
-    // Replace all whitespace with tab (or just trim later)
-    let cleaned = optarg.replace(' ', "\t");
-
-    for token in cleaned.split(',') {
-        let token = token.trim_matches(|c: char| c.is_whitespace());
-
-        if token.is_empty() {
-            continue;
-        }
-
-        if let Some((start_str, end_str)) = token.split_once('-') {
-            let start = start_str
-                .trim()
-                .parse::<usize>()
-                .map_err(|_| "Invalid range start")?;
-            let end = end_str
-                .trim()
-                .parse::<usize>()
-                .map_err(|_| "Invalid range end")?;
-
-            if start > end {
-                return Err(format!("Invalid CPU range: {}-{}", start, end));
-            }
-
-            for i in start..=end {
-                if cpus.len() >= *NR_CPU_IDS {
-                    return Err(format!("Too many CPUs specified (max {})", *NR_CPU_IDS));
-                }
-                if seen.insert(i) {
-                    cpus.push(i);
-                }
-            }
-        } else {
-            let cpu = token
-                .parse::<usize>()
-                .map_err(|_| format!("Invalid CPU: {}", token))?;
-            if cpus.len() >= *NR_CPU_IDS {
-                return Err(format!("Too many CPUs specified (max {})", *NR_CPU_IDS));
-            }
-            if seen.insert(cpu) {
-                cpus.push(cpu);
-            }
-        }
-    }
-
-    Ok(cpus)
+    #[clap(flatten)]
+    runtime: RuntimeOpts,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -249,7 +103,8 @@ struct Scheduler<'a> {
     skel: BpfSkel<'a>,
     opts: &'a Opts,
     struct_ops: Option<libbpf_rs::Link>,
-    stats_server: StatsServer<(), Metrics>,
+    prev_cputime: CpuTimes,
+    last_update: Instant,
 }
 
 impl<'a> Scheduler<'a> {
@@ -277,11 +132,11 @@ impl<'a> Scheduler<'a> {
 
         // Initialize BPF connector.
         let mut skel_builder = BpfSkelBuilder::default();
-        skel_builder.obj_builder.debug(opts.verbose);
-        let open_opts = opts.libbpf.clone().into_bpf_open_opts();
+        skel_builder.obj_builder.debug(opts.runtime.verbose);
+        let open_opts = opts.runtime.libbpf.clone().into_bpf_open_opts();
         let mut skel = scx_ops_open!(skel_builder, open_object, beerland_ops, open_opts)?;
 
-        skel.struct_ops.beerland_ops_mut().exit_dump_len = opts.exit_dump_len;
+        skel.struct_ops.beerland_ops_mut().exit_dump_len = opts.runtime.exit_dump_len;
 
         // Override default BPF scheduling parameters.
         let rodata = skel.maps.rodata_data.as_mut().unwrap();
@@ -293,13 +148,9 @@ impl<'a> Scheduler<'a> {
         rodata.busy_threshold = opts.cpu_busy_thresh * 1024 / 100;
 
         // Define the primary scheduling domain.
-        let primary_cpus = if let Some(ref domain) = opts.primary_domain {
-            match parse_cpu_list(domain) {
-                Ok(cpus) => cpus,
-                Err(e) => bail!("Error parsing primary domain: {}", e),
-            }
-        } else {
-            (0..*NR_CPU_IDS).collect()
+        let primary_cpus: Vec<usize> = match opts.runtime.primary_domain {
+            Some(ref domain) => domain.parse::<CpuDomain>()?.eval(&topo)?.iter().collect(),
+            None => (0..*NR_CPU_IDS).collect(),
         };
         if primary_cpus.len() < *NR_CPU_IDS {
             info!("Primary CPUs: {:?}", primary_cpus);
@@ -350,15 +201,14 @@ impl<'a> Scheduler<'a> {
             }
         }
 
-        // Attach the scheduler.
-        let struct_ops = Some(scx_ops_attach!(skel, beerland_ops)?);
-        let stats_server = StatsServer::new(stats::server_data()).launch()?;
+        let prev_cputime = Self::read_cpu_times().expect("Failed to read initial CPU stats");
 
         Ok(Self {
             skel,
             opts,
-            struct_ops,
-            stats_server,
+            struct_ops: None,
+            prev_cputime,
+            last_update: Instant::now(),
         })
     }
 
@@ -431,10 +281,6 @@ impl<'a> Scheduler<'a> {
         }
     }
 
-    pub fn exited(&mut self) -> bool {
-        uei_exited!(&self.skel, uei)
-    }
-
     fn compute_user_cpu_pct(prev: &CpuTimes, curr: &CpuTimes) -> Option<u64> {
         // Evaluate total user CPU time as user + nice.
         let user_diff = (curr.user + curr.nice).saturating_sub(prev.user + prev.nice);
@@ -478,120 +324,83 @@ impl<'a> Scheduler<'a> {
         None
     }
 
-    fn run(&mut self, shutdown: Arc<AtomicBool>) -> Result<UserExitInfo> {
-        let (res_ch, req_ch) = self.stats_server.channels();
+    fn polling_time(&self) -> Duration {
+        Duration::from_millis(self.opts.polling_ms).min(Duration::from_secs(1))
+    }
+}
+
+impl scx_runtime::Scheduler for Scheduler<'_> {
+    type Metrics = Metrics;
+
+    fn attach(&mut self) -> Result<()> {
+        self.struct_ops = Some(scx_ops_attach!(self.skel, beerland_ops)?);
+        Ok(())
+    }
+
+    fn exited(&mut self) -> bool {
+        uei_exited!(&self.skel, uei)
+    }
 
+    fn on_interval(&mut self) -> Result<bool> {
         // Periodically evaluate user CPU utilization from user-space and update a global variable
         // in BPF.
         //
         // The BPF scheduler can use this value to determine when the system is busy or idle.
-        let polling_time = Duration::from_millis(self.opts.polling_ms).min(Duration::from_secs(1));
-        let mut prev_cputime = Self::read_cpu_times().expect("Failed to read initial CPU stats");
-        let mut last_update = Instant::now();
-
-        while !shutdown.load(Ordering::Relaxed) && !self.exited() {
-            // Update CPU utilization.
-            if !polling_time.is_zero() && last_update.elapsed() >= polling_time {
-                if let Some(curr_cputime) = Self::read_cpu_times() {
-                    Self::compute_user_cpu_pct(&prev_cputime, &curr_cputime)
-                        .map(|util| self.skel.maps.bss_data.as_mut().unwrap().cpu_util = util);
-                    prev_cputime = curr_cputime;
-                }
-                last_update = Instant::now();
+        let polling_time = self.polling_time();
+        if !polling_time.is_zero() && self.last_update.elapsed() >= polling_time {
+            if let Some(curr_cputime) = Self::read_cpu_times() {
+                Self::compute_user_cpu_pct(&self.prev_cputime, &curr_cputime)
+                    .map(|util| self.skel.maps.bss_data.as_mut().unwrap().cpu_util = util);
+                self.prev_cputime = curr_cputime;
             }
+            self.last_update = Instant::now();
+        }
 
-            // Update statistics and check for exit condition.
-            let timeout = if polling_time.is_zero() {
-                Duration::from_secs(1)
-            } else {
-                polling_time
-            };
-            match req_ch.recv_timeout(timeout) {
-                Ok(()) => res_ch.send(self.get_metrics())?,
-                Err(RecvTimeoutError::Timeout) => {}
-                Err(e) => Err(e)?,
-            }
+        Ok(false)
+    }
+
+    fn interval(&self) -> Duration {
+        let polling_time = self.polling_time();
+        if polling_time.is_zero() {
+            Duration::from_secs(1)
+        } else {
+            polling_time
         }
+    }
 
-        let _ = self.struct_ops.take();
-        uei_report!(&self.skel, uei)
+    fn metrics(&mut self) -> Metrics {
+        self.get_metrics()
     }
-}
 
-impl Drop for Scheduler<'_> {
-    fn drop(&mut self) {
-        info!("Unregister {SCHEDULER_NAME} scheduler");
+    fn exit_report(&mut self) -> Result<UserExitInfo> {
+        let _ = self.struct_ops.take();
+        uei_report!(&self.skel, uei)
     }
 }
 
-fn main() -> Result<()> {
-    let opts = Opts::parse();
+impl SchedulerOpts for Opts {
+    const NAME: &'static str = SCHEDULER_NAME;
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+    type Metrics = Metrics;
+    type Sched<'a> = Scheduler<'a>;
 
-    if opts.version {
-        println!(
-            "{} {}",
-            SCHEDULER_NAME,
-            build_id::full_version(env!("CARGO_PKG_VERSION"))
-        );
-        return Ok(());
+    fn runtime(&self) -> &RuntimeOpts {
+        &self.runtime
     }
 
-    if opts.help_stats {
-        stats::server_data().describe_meta(&mut std::io::stdout(), None)?;
-        return Ok(());
+    fn init<'a>(&'a self, open_object: &'a mut MaybeUninit<OpenObject>) -> Result<Scheduler<'a>> {
+        Scheduler::init(self, open_object)
     }
 
-    let loglevel = simplelog::LevelFilter::Info;
-
-    let mut lcfg = simplelog::ConfigBuilder::new();
-    lcfg.set_time_offset_to_local()
-        .expect("Failed to set local time offset")
-        .set_time_level(simplelog::LevelFilter::Error)
-        .set_location_level(simplelog::LevelFilter::Off)
-        .set_target_level(simplelog::LevelFilter::Off)
-        .set_thread_level(simplelog::LevelFilter::Off);
-    simplelog::TermLogger::init(
-        loglevel,
-        lcfg.build(),
-        simplelog::TerminalMode::Stderr,
-        simplelog::ColorChoice::Auto,
-    )?;
-
-    let shutdown = Arc::new(AtomicBool::new(false));
-    let shutdown_clone = shutdown.clone();
-    ctrlc::set_handler(move || {
-        shutdown_clone.store(true, Ordering::Relaxed);
-    })
-    .context("Error setting Ctrl-C handler")?;
-
-    if let Some(intv) = opts.monitor.or(opts.stats) {
-        let shutdown_copy = shutdown.clone();
-        let jh = std::thread::spawn(move || {
-            match stats::monitor(Duration::from_secs_f64(intv), shutdown_copy) {
-                Ok(_) => {
-                    debug!("stats monitor thread finished successfully")
-                }
-                Err(error_object) => {
-                    warn!(
-                        "stats monitor thread finished because of an error {}",
-                        error_object
-                    )
-                }
-            }
-        });
-        if opts.monitor.is_some() {
-            let _ = jh.join();
-            return Ok(());
-        }
+    fn stats_server_data() -> StatsServerData<(), Metrics> {
+        stats::server_data()
     }
 
-    let mut open_object = MaybeUninit::uninit();
-    loop {
-        let mut sched = Scheduler::init(&opts, &mut open_object)?;
-        if !sched.run(shutdown.clone())?.should_restart() {
-            break;
-        }
+    fn monitor(intv: Duration, shutdown: Arc<AtomicBool>) -> Result<()> {
+        stats::monitor(intv, shutdown)
     }
+}
 
-    Ok(())
+fn main() -> Result<()> {
+    scx_runtime::run(&Opts::parse())
 }
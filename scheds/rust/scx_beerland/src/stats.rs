@@ -15,11 +15,11 @@ use serde::Serialize;
 #[derive(Clone, Debug, Default, Serialize, Deserialize, Stats)]
 #[stat(top)]
 pub struct Metrics {
-    #[stat(desc = "local dispatches")]
+    #[stat(desc = "local dispatches", _counter)]
     pub nr_local_dispatch: u64,
-    #[stat(desc = "remote dispatches")]
+    #[stat(desc = "remote dispatches", _counter)]
     pub nr_remote_dispatch: u64,
-    #[stat(desc = "keep running events")]
+    #[stat(desc = "keep running events", _counter)]
     pub nr_keep_running: u64,
 }
 
@@ -35,15 +35,6 @@ impl Metrics {
         )?;
         Ok(())
     }
-
-    fn delta(&self, rhs: &Self) -> Self {
-        Self {
-            nr_local_dispatch: self.nr_local_dispatch - rhs.nr_local_dispatch,
-            nr_remote_dispatch: self.nr_remote_dispatch - rhs.nr_remote_dispatch,
-            nr_keep_running: self.nr_keep_running - rhs.nr_keep_running,
-            ..self.clone()
-        }
-    }
 }
 
 pub fn server_data() -> StatsServerData<(), Metrics> {
@@ -47,8 +47,11 @@ const volatile bool debug;
 
 /*
  * Default task time slice.
+ *
+ * Not read-only, as it can be changed at runtime through the slice_us stats
+ * tunable.
  */
-const volatile u64 slice_max = 1ULL * NSEC_PER_MSEC;
+volatile u64 slice_max;
 
 /*
  * Default minimum time slice.
@@ -1,9 +1,11 @@
 use std::io::Write;
 use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::Duration;
 
+use anyhow::anyhow;
 use anyhow::Result;
 use scx_stats::prelude::*;
 use scx_stats_derive::stat_doc;
@@ -15,72 +17,72 @@ use serde::Serialize;
 #[derive(Clone, Debug, Default, Serialize, Deserialize, Stats)]
 #[stat(top)]
 pub struct Metrics {
-    #[stat(desc = "Number of running tasks")]
+    #[stat(desc = "Number of running tasks", _gauge)]
     pub nr_running: u64,
-    #[stat(desc = "Number of online CPUs")]
+    #[stat(desc = "Number of online CPUs", _gauge)]
     pub nr_cpus: u64,
-    #[stat(desc = "Number of kthread direct dispatches")]
+    #[stat(desc = "Number of kthread direct dispatches", _counter)]
     pub nr_kthread_dispatches: u64,
-    #[stat(desc = "Number of task direct dispatches")]
+    #[stat(desc = "Number of task direct dispatches", _counter)]
     pub nr_direct_dispatches: u64,
-    #[stat(desc = "Number of regular task dispatches")]
+    #[stat(desc = "Number of regular task dispatches", _counter)]
     pub nr_shared_dispatches: u64,
     // TIMELY stats (zero when timely mode is disabled)
-    #[stat(desc = "Number of delay recovery dispatches")]
+    #[stat(desc = "Number of delay recovery dispatches", _counter)]
     pub nr_delay_recovery_dispatches: u64,
-    #[stat(desc = "Number of delay middle add dispatches")]
+    #[stat(desc = "Number of delay middle add dispatches", _counter)]
     pub nr_delay_middle_add_dispatches: u64,
-    #[stat(desc = "Number of delay fast recovery dispatches")]
+    #[stat(desc = "Number of delay fast recovery dispatches", _counter)]
     pub nr_delay_fast_recovery_dispatches: u64,
-    #[stat(desc = "Number of delay rate-limited dispatches")]
+    #[stat(desc = "Number of delay rate-limited dispatches", _counter)]
     pub nr_delay_rate_limited_dispatches: u64,
-    #[stat(desc = "Number of gain floor dispatches")]
+    #[stat(desc = "Number of gain floor dispatches", _counter)]
     pub nr_gain_floor_dispatches: u64,
-    #[stat(desc = "Number of gain ceiling dispatches")]
+    #[stat(desc = "Number of gain ceiling dispatches", _counter)]
     pub nr_gain_ceiling_dispatches: u64,
-    #[stat(desc = "Number of delay low region samples")]
+    #[stat(desc = "Number of delay low region samples", _counter)]
     pub nr_delay_low_region_samples: u64,
-    #[stat(desc = "Number of delay mid region samples")]
+    #[stat(desc = "Number of delay mid region samples", _counter)]
     pub nr_delay_mid_region_samples: u64,
-    #[stat(desc = "Number of delay high region samples")]
+    #[stat(desc = "Number of delay high region samples", _counter)]
     pub nr_delay_high_region_samples: u64,
-    #[stat(desc = "Number of gain floor resident samples")]
+    #[stat(desc = "Number of gain floor resident samples", _counter)]
     pub nr_gain_floor_resident_samples: u64,
-    #[stat(desc = "Number of gain mid resident samples")]
+    #[stat(desc = "Number of gain mid resident samples", _counter)]
     pub nr_gain_mid_resident_samples: u64,
-    #[stat(desc = "Number of gain ceiling resident samples")]
+    #[stat(desc = "Number of gain ceiling resident samples", _counter)]
     pub nr_gain_ceiling_resident_samples: u64,
-    #[stat(desc = "Number of idle select path picks")]
+    #[stat(desc = "Number of idle select path picks", _counter)]
     pub nr_idle_select_path_picks: u64,
-    #[stat(desc = "Number of idle enqueue path picks")]
+    #[stat(desc = "Number of idle enqueue path picks", _counter)]
     pub nr_idle_enqueue_path_picks: u64,
-    #[stat(desc = "Number of idle prev CPU picks")]
+    #[stat(desc = "Number of idle prev CPU picks", _counter)]
     pub nr_idle_prev_cpu_picks: u64,
-    #[stat(desc = "Number of idle primary picks")]
+    #[stat(desc = "Number of idle primary picks", _counter)]
     pub nr_idle_primary_picks: u64,
-    #[stat(desc = "Number of idle spill picks")]
+    #[stat(desc = "Number of idle spill picks", _counter)]
     pub nr_idle_spill_picks: u64,
-    #[stat(desc = "Number of idle pick failures")]
+    #[stat(desc = "Number of idle pick failures", _counter)]
     pub nr_idle_pick_failures: u64,
-    #[stat(desc = "Number of idle primary domain misses")]
+    #[stat(desc = "Number of idle primary domain misses", _counter)]
     pub nr_idle_primary_domain_misses: u64,
-    #[stat(desc = "Number of idle global misses")]
+    #[stat(desc = "Number of idle global misses", _counter)]
     pub nr_idle_global_misses: u64,
-    #[stat(desc = "Number of waker CPU biases")]
+    #[stat(desc = "Number of waker CPU biases", _counter)]
     pub nr_waker_cpu_biases: u64,
-    #[stat(desc = "Number of keep running reuses")]
+    #[stat(desc = "Number of keep running reuses", _counter)]
     pub nr_keep_running_reuses: u64,
-    #[stat(desc = "Number of keep running queue empty")]
+    #[stat(desc = "Number of keep running queue empty", _counter)]
     pub nr_keep_running_queue_empty: u64,
-    #[stat(desc = "Number of keep running SMT blocked")]
+    #[stat(desc = "Number of keep running SMT blocked", _counter)]
     pub nr_keep_running_smt_blocked: u64,
-    #[stat(desc = "Number of keep running queued work")]
+    #[stat(desc = "Number of keep running queued work", _counter)]
     pub nr_keep_running_queued_work: u64,
-    #[stat(desc = "Number of dispatch CPU DSQ consumes")]
+    #[stat(desc = "Number of dispatch CPU DSQ consumes", _counter)]
     pub nr_dispatch_cpu_dsq_consumes: u64,
-    #[stat(desc = "Number of dispatch node DSQ consumes")]
+    #[stat(desc = "Number of dispatch node DSQ consumes", _counter)]
     pub nr_dispatch_node_dsq_consumes: u64,
-    #[stat(desc = "Number of CPU release reenqueues")]
+    #[stat(desc = "Number of CPU release reenqueues", _counter)]
     pub nr_cpu_release_reenqueue: u64,
 }
 
@@ -103,62 +105,6 @@ impl Metrics {
         )?;
         Ok(())
     }
-
-    fn delta(&self, rhs: &Self) -> Self {
-        Self {
-            nr_kthread_dispatches: self.nr_kthread_dispatches - rhs.nr_kthread_dispatches,
-            nr_direct_dispatches: self.nr_direct_dispatches - rhs.nr_direct_dispatches,
-            nr_shared_dispatches: self.nr_shared_dispatches - rhs.nr_shared_dispatches,
-            nr_delay_recovery_dispatches: self.nr_delay_recovery_dispatches
-                - rhs.nr_delay_recovery_dispatches,
-            nr_delay_middle_add_dispatches: self.nr_delay_middle_add_dispatches
-                - rhs.nr_delay_middle_add_dispatches,
-            nr_delay_fast_recovery_dispatches: self.nr_delay_fast_recovery_dispatches
-                - rhs.nr_delay_fast_recovery_dispatches,
-            nr_delay_rate_limited_dispatches: self.nr_delay_rate_limited_dispatches
-                - rhs.nr_delay_rate_limited_dispatches,
-            nr_gain_floor_dispatches: self.nr_gain_floor_dispatches - rhs.nr_gain_floor_dispatches,
-            nr_gain_ceiling_dispatches: self.nr_gain_ceiling_dispatches
-                - rhs.nr_gain_ceiling_dispatches,
-            nr_delay_low_region_samples: self.nr_delay_low_region_samples
-                - rhs.nr_delay_low_region_samples,
-            nr_delay_mid_region_samples: self.nr_delay_mid_region_samples
-                - rhs.nr_delay_mid_region_samples,
-            nr_delay_high_region_samples: self.nr_delay_high_region_samples
-                - rhs.nr_delay_high_region_samples,
-            nr_gain_floor_resident_samples: self.nr_gain_floor_resident_samples
-                - rhs.nr_gain_floor_resident_samples,
-            nr_gain_mid_resident_samples: self.nr_gain_mid_resident_samples
-                - rhs.nr_gain_mid_resident_samples,
-            nr_gain_ceiling_resident_samples: self.nr_gain_ceiling_resident_samples
-                - rhs.nr_gain_ceiling_resident_samples,
-            nr_idle_select_path_picks: self.nr_idle_select_path_picks
-                - rhs.nr_idle_select_path_picks,
-            nr_idle_enqueue_path_picks: self.nr_idle_enqueue_path_picks
-                - rhs.nr_idle_enqueue_path_picks,
-            nr_idle_prev_cpu_picks: self.nr_idle_prev_cpu_picks - rhs.nr_idle_prev_cpu_picks,
-            nr_idle_primary_picks: self.nr_idle_primary_picks - rhs.nr_idle_primary_picks,
-            nr_idle_spill_picks: self.nr_idle_spill_picks - rhs.nr_idle_spill_picks,
-            nr_idle_pick_failures: self.nr_idle_pick_failures - rhs.nr_idle_pick_failures,
-            nr_idle_primary_domain_misses: self.nr_idle_primary_domain_misses
-                - rhs.nr_idle_primary_domain_misses,
-            nr_idle_global_misses: self.nr_idle_global_misses - rhs.nr_idle_global_misses,
-            nr_waker_cpu_biases: self.nr_waker_cpu_biases - rhs.nr_waker_cpu_biases,
-            nr_keep_running_reuses: self.nr_keep_running_reuses - rhs.nr_keep_running_reuses,
-            nr_keep_running_queue_empty: self.nr_keep_running_queue_empty
-                - rhs.nr_keep_running_queue_empty,
-            nr_keep_running_smt_blocked: self.nr_keep_running_smt_blocked
-                - rhs.nr_keep_running_smt_blocked,
-            nr_keep_running_queued_work: self.nr_keep_running_queued_work
-                - rhs.nr_keep_running_queued_work,
-            nr_dispatch_cpu_dsq_consumes: self.nr_dispatch_cpu_dsq_consumes
-                - rhs.nr_dispatch_cpu_dsq_consumes,
-            nr_dispatch_node_dsq_consumes: self.nr_dispatch_node_dsq_consumes
-                - rhs.nr_dispatch_node_dsq_consumes,
-            nr_cpu_release_reenqueue: self.nr_cpu_release_reenqueue - rhs.nr_cpu_release_reenqueue,
-            ..self.clone()
-        }
-    }
 }
 
 pub fn server_data() -> StatsServerData<(), Metrics> {
@@ -182,6 +128,34 @@ pub fn server_data() -> StatsServerData<(), Metrics> {
         .add_ops("top", StatsOps { open, close: None })
 }
 
+/// Upper bound of the slice_us tunable.
+const SLICE_US_MAX: u64 = 1_000_000;
+
+/// Register the "slice_us" tunable on @data. @slice_us is updated when the
+/// tunable is changed and can't go below @slice_min_us.
+pub fn add_tunables(
+    data: StatsServerData<(), Metrics>,
+    slice_us: Arc<AtomicU64>,
+    slice_min_us: u64,
+) -> StatsServerData<(), Metrics> {
+    let slice_us_meta =
+        StatsTunableMeta::new(StatsKind::U64, slice_us.load(Ordering::Relaxed).into())
+            .desc("Maximum scheduling slice duration in microseconds")
+            .range(slice_min_us.max(1) as f64, SLICE_US_MAX as f64);
+
+    data.add_tunable(
+        "slice_us",
+        slice_us_meta,
+        Box::new(move |val, _chan| {
+            let val = val
+                .as_u64()
+                .ok_or_else(|| anyhow!("invalid slice_us {}", val))?;
+            slice_us.store(val, Ordering::Relaxed);
+            Ok(())
+        }),
+    )
+}
+
 pub fn monitor(intv: Duration, shutdown: Arc<AtomicBool>) -> Result<()> {
     scx_utils::monitor_stats::<Metrics>(
         &[],
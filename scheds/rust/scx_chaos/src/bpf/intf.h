@@ -7,6 +7,7 @@
 
 #ifndef __KERNEL__
 typedef unsigned long long u64;
+typedef unsigned int u32;
 #endif
 
 enum chaos_consts {
@@ -16,6 +17,12 @@ enum chaos_consts {
 	CHAOS_NUM_PPIDS_CHECK	= 1 << 20,
 
 	CHAOS_MAX_RAND_ATTEMPTS = 512,
+
+	CHAOS_WATCHDOG_TIMEOUT_MS = 30000,
+
+	CHAOS_COMM_LEN		= 16,
+	CHAOS_DECISIONS_SIZE	= 1 << 22,
+	CHAOS_MAX_SPAWN_COMMS	= 1 << 14,
 };
 
 enum chaos_match {
@@ -33,6 +40,10 @@ enum chaos_trait_kind {
 	CHAOS_TRAIT_CPU_FREQ,
 	CHAOS_TRAIT_DEGRADATION,
 	CHAOS_TRAIT_KPROBE_RANDOM_DELAYS,
+	CHAOS_TRAIT_FORCED_MIGRATION,
+	CHAOS_TRAIT_STARVATION,
+	CHAOS_TRAIT_SLICE_JITTER,
+	CHAOS_TRAIT_WAKEUP_REORDER,
 	CHAOS_TRAIT_MAX,
 };
 
@@ -44,6 +55,21 @@ struct chaos_task_ctx {
 	enum chaos_trait_kind pending_trait;
 	u64		      enq_flags;
 	u64		      p2dq_vtime;
+
+	u64		      rng_state;
+	/* initial rng_state, identifies the decision stream of the task */
+	u64		      stream;
+};
+
+/* an injected chaos event, streamed to userspace for the decision log */
+struct chaos_decision {
+	u64 ts;
+	u64 param;
+	u64 stream;
+	u32 pid;
+	u32 tgid;
+	u32 kind;
+	char comm[CHAOS_COMM_LEN];
 };
 
 enum chaos_stat_idx {
@@ -54,6 +80,11 @@ enum chaos_stat_idx {
 	CHAOS_STAT_CHAOS_SKIPPED,
 	CHAOS_STAT_KPROBE_RANDOM_DELAYS,
 	CHAOS_STAT_TIMER_KICKS,
+	CHAOS_STAT_DECISIONS_DROPPED,
+	CHAOS_STAT_TRAIT_FORCED_MIGRATION,
+	CHAOS_STAT_TRAIT_STARVATION,
+	CHAOS_STAT_TRAIT_SLICE_JITTER,
+	CHAOS_STAT_TRAIT_WAKEUP_REORDER,
 	CHAOS_NR_STATS,
 };
 
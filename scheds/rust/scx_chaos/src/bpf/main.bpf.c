@@ -16,6 +16,11 @@ const volatile int  ppid_targeting_ppid = 1;
 const volatile bool ppid_targeting_inclusive =
 	false; /* include ppid_targeting_ppid in chaos */
 
+/* additional task filters, set per scenario phase */
+const volatile int  target_tgid = -1;
+const volatile u64  target_cgid = 0;
+const volatile char target_comm[TASK_COMM_LEN];
+
 const volatile u64 chaos_timer_check_queues_min_ns   = 500000;
 const volatile u64 chaos_timer_check_queues_max_ns   = 2000000;
 const volatile u64 chaos_timer_check_queues_slack_ns = 2500000;
@@ -31,10 +36,24 @@ const volatile u32 cpu_freq_max		     = SCX_CPUPERF_ONE;
 const volatile u32 degradation_freq_frac32   = 1;
 const volatile u64 degradation_frac7	     = 0;
 
+const volatile bool forced_migration_numa    = false;
+
+const volatile u64 starvation_ns	     = 1;
+
+const volatile u64 slice_jitter_min_ns	     = 1;
+const volatile u64 slice_jitter_max_ns	     = 2;
+
+const volatile u64 wakeup_reorder_window_ns  = 1;
+
 const volatile u32 kprobe_delays_freq_frac32 = 1;
 const volatile u64 kprobe_delays_min_ns	     = 1;
 const volatile u64 kprobe_delays_max_ns	     = 2;
 
+/* make chaos decisions from a per-task stream seeded with chaos_seed */
+const volatile bool chaos_seeded	     = false;
+const volatile u64  chaos_seed		     = 0;
+const volatile bool decision_log_enabled     = false;
+
 #define MIN(x, y) ((x) < (y) ? (x) : (y))
 #define MAX(x, y) ((x) > (y) ? (x) : (y))
 
@@ -64,13 +83,110 @@ struct {
 	__type(value, u64);
 } chaos_stats	       SEC(".maps");
 
+struct {
+	__uint(type, BPF_MAP_TYPE_RINGBUF);
+	__uint(max_entries, CHAOS_DECISIONS_SIZE);
+} chaos_decisions      SEC(".maps");
+
+/* number of tasks seeded so far, keyed by the comm hash */
+struct {
+	__uint(type, BPF_MAP_TYPE_LRU_HASH);
+	__uint(max_entries, CHAOS_MAX_SPAWN_COMMS);
+	__type(key, u64);
+	__type(value, u64);
+} chaos_spawn_counts   SEC(".maps");
+
 struct chaos_task_ctx *lookup_create_chaos_task_ctx(struct task_struct *p)
 {
 	return bpf_task_storage_get(&chaos_task_ctxs, p, NULL,
 				    BPF_LOCAL_STORAGE_GET_F_CREATE);
 }
 
-static __always_inline u64 chaos_get_prandom_u64_limit(u64 s)
+static __always_inline void chaos_stat_inc(enum chaos_stat_idx stat)
+{
+	u64 *cnt_p = bpf_map_lookup_elem(&chaos_stats, &stat);
+	if (cnt_p)
+		(*cnt_p)++;
+}
+
+/*
+ * Return how many tasks with the comm hashing to @hash were seeded before.
+ */
+static __always_inline u64 chaos_spawn_idx(u64 hash)
+{
+	u64 zero = 0, *cnt;
+
+	bpf_map_update_elem(&chaos_spawn_counts, &hash, &zero, BPF_NOEXIST);
+	if (!(cnt = bpf_map_lookup_elem(&chaos_spawn_counts, &hash)))
+		return 0;
+	return __sync_fetch_and_add(cnt, 1);
+}
+
+/*
+ * Seed a task's decision stream. PIDs differ between runs, so the stream is
+ * keyed on the comm and on how many tasks with the same comm were spawned
+ * before it instead. This keeps decisions stable across runs as long as the
+ * workload spawns tasks in the same order.
+ *
+ * Must match task_stream() in decision_log.rs.
+ */
+static __always_inline void chaos_seed_task(struct task_struct	  *p,
+					    struct chaos_task_ctx *taskc)
+{
+	u64 hash = 0xcbf29ce484222325ULL; /* FNV-1a */
+	int i;
+
+#pragma unroll
+	for (i = 0; i < CHAOS_COMM_LEN; i++) {
+		hash ^= (u8)p->comm[i];
+		hash *= 0x100000001b3ULL;
+	}
+
+	taskc->stream = chaos_seed ^ hash ^ (chaos_spawn_idx(hash) << 32);
+	taskc->rng_state = taskc->stream;
+}
+
+/* splitmix64, must match splitmix64() in decision_log.rs */
+static __always_inline u64 chaos_rand_u64(struct chaos_task_ctx *taskc)
+{
+	u64 z;
+
+	if (!chaos_seeded)
+		return get_prandom_u64();
+
+	z = (taskc->rng_state += 0x9e3779b97f4a7c15ULL);
+	z = (z ^ (z >> 30)) * 0xbf58476d1ce4e5b9ULL;
+	z = (z ^ (z >> 27)) * 0x94d049bb133111ebULL;
+	return z ^ (z >> 31);
+}
+
+static __always_inline void chaos_log_decision(struct task_struct	     *p,
+					       struct chaos_task_ctx *taskc,
+					       enum chaos_trait_kind kind,
+					       u64 param)
+{
+	struct chaos_decision *d;
+
+	if (!decision_log_enabled)
+		return;
+
+	if (!(d = bpf_ringbuf_reserve(&chaos_decisions, sizeof(*d), 0))) {
+		chaos_stat_inc(CHAOS_STAT_DECISIONS_DROPPED);
+		return;
+	}
+
+	d->ts	 = bpf_ktime_get_ns();
+	d->param = param;
+	d->stream = taskc->stream;
+	d->pid	 = p->pid;
+	d->tgid	 = p->tgid;
+	d->kind	 = kind;
+	__builtin_memcpy(d->comm, p->comm, CHAOS_COMM_LEN);
+	bpf_ringbuf_submit(d, 0);
+}
+
+static __always_inline u64
+chaos_get_prandom_u64_limit(struct chaos_task_ctx *taskc, u64 s)
 {
 	// Implementation of Lemire's algorithm 5 without 128-bit arithmetic.
 	// See https://arxiv.org/pdf/1805.10941v2 for details.
@@ -79,7 +195,7 @@ static __always_inline u64 chaos_get_prandom_u64_limit(u64 s)
 	u64 x, m_low, m_high;
 	u64 t;
 
-	x = get_prandom_u64();
+	x = chaos_rand_u64(taskc);
 
 	// Compute 64-bit multiplication high and low parts
 	// m = x * s, split into m_high and m_low
@@ -95,7 +211,7 @@ static __always_inline u64 chaos_get_prandom_u64_limit(u64 s)
 			if (m_low >= t)
 				break;
 
-			x      = get_prandom_u64();
+			x      = chaos_rand_u64(taskc);
 			m_high = ((x >> 32) * (s >> 32)) +
 				 (((x & 0xFFFFFFFF) * (s >> 32)) >> 32) +
 				 (((x >> 32) * (s & 0xFFFFFFFF)) >> 32);
@@ -106,27 +222,57 @@ static __always_inline u64 chaos_get_prandom_u64_limit(u64 s)
 	return m_high;
 }
 
-static __always_inline u64 chaos_get_uniform_u64(u64 min, u64 max)
+static __always_inline u64 chaos_get_uniform_u64(struct chaos_task_ctx *taskc,
+						u64 min, u64 max)
 {
-	return min + chaos_get_prandom_u64_limit(max - min + 1);
+	return min + chaos_get_prandom_u64_limit(taskc, max - min + 1);
 }
 
-static __always_inline void chaos_stat_inc(enum chaos_stat_idx stat)
+/*
+ * Check the tgid, comm and cgroup filters. Unlike the ppid match these are
+ * evaluated every time as comm and cgroup can change over a task's lifetime.
+ */
+static __always_inline bool chaos_task_filter_matches(struct task_struct *p)
 {
-	u64 *cnt_p = bpf_map_lookup_elem(&chaos_stats, &stat);
-	if (cnt_p)
-		(*cnt_p)++;
+	struct cgroup *cgrp;
+	bool	       under;
+	int	       i;
+
+	if (target_tgid >= 0 && p->tgid != target_tgid)
+		return false;
+
+	if (target_comm[0]) {
+#pragma unroll
+		for (i = 0; i < TASK_COMM_LEN; i++) {
+			if (p->comm[i] != target_comm[i])
+				return false;
+			if (!target_comm[i])
+				break;
+		}
+	}
+
+	if (target_cgid) {
+		if (!(cgrp = bpf_cgroup_from_id(target_cgid)))
+			return false;
+		under = bpf_task_under_cgroup(p, cgrp);
+		bpf_cgroup_release(cgrp);
+		if (!under)
+			return false;
+	}
+
+	return true;
 }
 
 static __always_inline enum chaos_trait_kind
-choose_chaos(struct chaos_task_ctx *taskc)
+choose_chaos(struct task_struct *p, struct chaos_task_ctx *taskc)
 {
-	if (taskc->match & CHAOS_MATCH_EXCLUDED) {
+	if (taskc->match & CHAOS_MATCH_EXCLUDED ||
+	    !chaos_task_filter_matches(p)) {
 		chaos_stat_inc(CHAOS_STAT_CHAOS_EXCLUDED);
 		return CHAOS_TRAIT_NONE;
 	}
 
-	u32 roll = bpf_get_prandom_u32();
+	u32 roll = (u32)chaos_rand_u64(taskc);
 
 #pragma unroll
 	for (int i = 0; i < CHAOS_TRAIT_MAX; ++i) {
@@ -142,7 +288,42 @@ static __always_inline bool
 chaos_trait_skips_select_cpu(struct chaos_task_ctx *taskc)
 {
 	return taskc->next_trait == CHAOS_TRAIT_RANDOM_DELAYS ||
-	       taskc->next_trait == CHAOS_TRAIT_KPROBE_RANDOM_DELAYS;
+	       taskc->next_trait == CHAOS_TRAIT_KPROBE_RANDOM_DELAYS ||
+	       taskc->next_trait == CHAOS_TRAIT_STARVATION;
+}
+
+/*
+ * Pick a random CPU the task may run on outside of prev_cpu's LLC, or its NUMA
+ * node if forced_migration_numa is set. Returns -ENOENT if there is none.
+ */
+static __always_inline s32 chaos_pick_migration_cpu(struct task_struct *p,
+						    struct chaos_task_ctx *taskc,
+						    s32 prev_cpu)
+{
+	struct cpu_ctx *prev_cpuc, *cpuc;
+	u32		nr_cpus = topo_config.nr_cpus;
+	u32		start, i;
+	s32		cpu;
+
+	if (!nr_cpus || !(prev_cpuc = lookup_cpu_ctx(prev_cpu)))
+		return -ENOENT;
+
+	start = chaos_get_prandom_u64_limit(taskc, nr_cpus);
+
+	bpf_for(i, 0, nr_cpus)
+	{
+		cpu = (start + i) % nr_cpus;
+		if (!bpf_cpumask_test_cpu(cpu, p->cpus_ptr))
+			continue;
+		if (!(cpuc = lookup_cpu_ctx(cpu)))
+			return -ENOENT;
+
+		if (forced_migration_numa ? cpuc->node_id != prev_cpuc->node_id :
+					    cpuc->llc_id != prev_cpuc->llc_id)
+			return cpu;
+	}
+
+	return -ENOENT;
 }
 
 static __always_inline u64 get_cpu_delay_dsq(int cpu_idx)
@@ -286,7 +467,10 @@ __weak s32 enqueue_random_delay(struct task_struct *p	     __arg_trusted,
 				struct chaos_task_ctx *taskc __arg_nonnull,
 				u64 min_ns, u64 max_ns)
 {
-	u64 vtime = bpf_ktime_get_ns() + chaos_get_uniform_u64(min_ns, max_ns);
+	u64 delay = chaos_get_uniform_u64(taskc, min_ns, max_ns);
+	u64 vtime = bpf_ktime_get_ns() + delay;
+
+	chaos_log_decision(p, taskc, taskc->next_trait, delay);
 	scx_bpf_dsq_insert_vtime(p, get_cpu_delay_dsq(-1), 0, vtime, enq_flags);
 	return true;
 }
@@ -309,12 +493,20 @@ __weak s32 enqueue_chaotic(struct task_struct *p __arg_trusted, u64 enq_flags,
 					   random_delays_max_ns);
 		chaos_stat_inc(CHAOS_STAT_TRAIT_RANDOM_DELAYS);
 		break;
+	case CHAOS_TRAIT_STARVATION:
+		out = enqueue_random_delay(p, enq_flags, taskc, starvation_ns,
+					   starvation_ns);
+		chaos_stat_inc(CHAOS_STAT_TRAIT_STARVATION);
+		break;
 	case CHAOS_TRAIT_NONE:
 		chaos_stat_inc(CHAOS_STAT_CHAOS_SKIPPED);
 		out = false;
 		break;
 	case CHAOS_TRAIT_CPU_FREQ:
 	case CHAOS_TRAIT_DEGRADATION:
+	case CHAOS_TRAIT_FORCED_MIGRATION:
+	case CHAOS_TRAIT_SLICE_JITTER:
+	case CHAOS_TRAIT_WAKEUP_REORDER:
 	case CHAOS_TRAIT_MAX:
 		out = false;
 		break;
@@ -555,7 +747,8 @@ void BPF_STRUCT_OPS(chaos_enqueue, struct task_struct *p __arg_trusted,
 		goto cleanup;
 
 	if ((taskc->next_trait == CHAOS_TRAIT_RANDOM_DELAYS ||
-	     taskc->next_trait == CHAOS_TRAIT_KPROBE_RANDOM_DELAYS) &&
+	     taskc->next_trait == CHAOS_TRAIT_KPROBE_RANDOM_DELAYS ||
+	     taskc->next_trait == CHAOS_TRAIT_STARVATION) &&
 	    enqueue_chaotic(p, enq_flags, taskc))
 		goto cleanup;
 
@@ -569,6 +762,8 @@ void BPF_STRUCT_OPS(chaos_enqueue, struct task_struct *p __arg_trusted,
 			dbg("CHAOS[degradation][%d] slice_ns: %llu", p,
 			    promise.fifo.slice_ns);
 			chaos_stat_inc(CHAOS_STAT_TRAIT_DEGRADATION);
+			chaos_log_decision(p, taskc, CHAOS_TRAIT_DEGRADATION,
+					   degradation_frac7);
 		}
 		if (promise.kind == P2DQ_ENQUEUE_PROMISE_VTIME) {
 			promise.vtime.vtime += ((degradation_frac7 << 7) *
@@ -580,6 +775,44 @@ void BPF_STRUCT_OPS(chaos_enqueue, struct task_struct *p __arg_trusted,
 			dbg("CHAOS[degradation][%d] vtime: %llu slice_ns: %llu",
 			    p, promise.vtime.vtime, promise.vtime.slice_ns);
 			chaos_stat_inc(CHAOS_STAT_TRAIT_DEGRADATION);
+			chaos_log_decision(p, taskc, CHAOS_TRAIT_DEGRADATION,
+					   degradation_frac7);
+		}
+	}
+
+	if (taskc->next_trait == CHAOS_TRAIT_SLICE_JITTER &&
+	    (promise.kind == P2DQ_ENQUEUE_PROMISE_FIFO ||
+	     promise.kind == P2DQ_ENQUEUE_PROMISE_VTIME)) {
+		u64 slice_ns = chaos_get_uniform_u64(taskc, slice_jitter_min_ns,
+						     slice_jitter_max_ns);
+
+		if (promise.kind == P2DQ_ENQUEUE_PROMISE_FIFO)
+			promise.fifo.slice_ns = slice_ns;
+		else
+			promise.vtime.slice_ns = slice_ns;
+		dbg("CHAOS[slice_jitter][%d] slice_ns: %llu", p->pid, slice_ns);
+		chaos_stat_inc(CHAOS_STAT_TRAIT_SLICE_JITTER);
+		chaos_log_decision(p, taskc, CHAOS_TRAIT_SLICE_JITTER, slice_ns);
+	}
+
+	/*
+	 * Reorder the DSQ by jumping the task ahead of others: FIFO tasks go to
+	 * the head and vtime tasks are pulled back by up to the window.
+	 */
+	if (taskc->next_trait == CHAOS_TRAIT_WAKEUP_REORDER) {
+		if (promise.kind == P2DQ_ENQUEUE_PROMISE_FIFO) {
+			promise.fifo.enq_flags |= SCX_ENQ_HEAD;
+			chaos_stat_inc(CHAOS_STAT_TRAIT_WAKEUP_REORDER);
+			chaos_log_decision(p, taskc, CHAOS_TRAIT_WAKEUP_REORDER, 0);
+		}
+		if (promise.kind == P2DQ_ENQUEUE_PROMISE_VTIME) {
+			u64 delta = chaos_get_uniform_u64(
+				taskc, 0, wakeup_reorder_window_ns);
+
+			promise.vtime.vtime -= MIN(delta, promise.vtime.vtime);
+			chaos_stat_inc(CHAOS_STAT_TRAIT_WAKEUP_REORDER);
+			chaos_log_decision(p, taskc, CHAOS_TRAIT_WAKEUP_REORDER,
+					   delta);
 		}
 	}
 
@@ -605,7 +838,7 @@ void BPF_STRUCT_OPS(chaos_runnable, struct task_struct *p, u64 enq_flags)
 		return;
 	}
 
-	wakee_ctx->next_trait = choose_chaos(wakee_ctx);
+	wakee_ctx->next_trait = choose_chaos(p, wakee_ctx);
 }
 
 void BPF_STRUCT_OPS(chaos_running, struct task_struct *p)
@@ -627,6 +860,8 @@ void BPF_STRUCT_OPS(chaos_running, struct task_struct *p)
 			dbg("CHAOS[freq][%d] freq: %d", p->pid, cpu_freq_min);
 			scx_bpf_cpuperf_set(task_cpu, cpu_freq_min);
 			chaos_stat_inc(CHAOS_STAT_TRAIT_CPU_FREQ);
+			chaos_log_decision(p, taskc, CHAOS_TRAIT_CPU_FREQ,
+					   cpu_freq_min);
 		}
 	} else {
 		if (cpu_freq_max > 0)
@@ -638,6 +873,8 @@ s32 BPF_STRUCT_OPS(chaos_select_cpu, struct task_struct *p, s32 prev_cpu,
 		   u64 wake_flags)
 {
 	struct chaos_task_ctx *wakee_ctx;
+	s32		       cpu;
+
 	if (!(wakee_ctx = lookup_create_chaos_task_ctx(p)))
 		goto p2dq;
 
@@ -645,6 +882,19 @@ s32 BPF_STRUCT_OPS(chaos_select_cpu, struct task_struct *p, s32 prev_cpu,
 	if (chaos_trait_skips_select_cpu(wakee_ctx))
 		return prev_cpu;
 
+	if (wakee_ctx->next_trait == CHAOS_TRAIT_FORCED_MIGRATION) {
+		wakee_ctx->next_trait = CHAOS_TRAIT_NONE;
+
+		cpu = chaos_pick_migration_cpu(p, wakee_ctx, prev_cpu);
+		if (cpu >= 0) {
+			scx_bpf_dsq_insert(p, SCX_DSQ_LOCAL, SCX_SLICE_DFL, 0);
+			chaos_stat_inc(CHAOS_STAT_TRAIT_FORCED_MIGRATION);
+			chaos_log_decision(p, wakee_ctx, CHAOS_TRAIT_FORCED_MIGRATION,
+					   cpu);
+			return cpu;
+		}
+	}
+
 p2dq:
 	return p2dq_select_cpu_impl(p, prev_cpu, wake_flags);
 }
@@ -662,7 +912,8 @@ void BPF_STRUCT_OPS(chaos_tick, struct task_struct *p)
 s32 BPF_STRUCT_OPS_SLEEPABLE(chaos_init_task, struct task_struct *p,
 			     struct scx_init_task_args *args)
 {
-	s32 ret = p2dq_init_task_impl(p, args);
+	struct chaos_task_ctx *taskc;
+	s32		       ret = p2dq_init_task_impl(p, args);
 	if (ret)
 		return ret;
 
@@ -670,6 +921,12 @@ s32 BPF_STRUCT_OPS_SLEEPABLE(chaos_init_task, struct task_struct *p,
 	if (ret)
 		return ret;
 
+	if (chaos_seeded) {
+		if (!(taskc = lookup_create_chaos_task_ctx(p)))
+			return -ENOMEM;
+		chaos_seed_task(p, taskc);
+	}
+
 	return 0;
 }
 
@@ -686,7 +943,7 @@ int generic(struct pt_regs *ctx)
 	if (!(taskc = lookup_create_chaos_task_ctx(p)))
 		return -EINVAL;
 
-	u32 roll = bpf_get_prandom_u32();
+	u32 roll = (u32)chaos_rand_u64(taskc);
 	if (roll <= kprobe_delays_freq_frac32) {
 		taskc->pending_trait = CHAOS_TRAIT_KPROBE_RANDOM_DELAYS;
 		dbg("GENERIC: setting pending_trait to RANDOM_DELAYS - task[%d]",
@@ -713,6 +970,6 @@ SCX_OPS_DEFINE(chaos,
 	       .stopping		= (void *)p2dq_stopping,
 	       .set_cpumask		= (void *)p2dq_set_cpumask,
 
-	       .timeout_ms		= 30000,
+	       .timeout_ms		= CHAOS_WATCHDOG_TIMEOUT_MS,
 	       .name			= "chaos");
 // clang-format on
@@ -0,0 +1,510 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+//! Decision log for seeded chaos runs.
+//!
+//! The first line of a log is a JSON header, prefixed with "# ", holding the
+//! seed and the command line of the run. Every following line is one injected
+//! event, with tab separated fields:
+//!
+//! ```text
+//! <timestamp_ns> <pid> <tgid> <comm> <trait> <param> <stream>
+//! ```
+//!
+//! The param is the delay in ns for random delays and starvation, the CPU
+//! performance level for cpu_freq, the degradation fraction for
+//! perf_degradation, the target CPU for forced_migration, the slice in ns for
+//! slice_jitter and the vtime pulled back in ns for wakeup_reorder.
+//!
+//! The stream identifies the random stream the decision was drawn from. Each
+//! task's stream is seeded from the run's seed, its comm and how many tasks
+//! with the same comm were spawned before it (see task_stream()), so it stays
+//! the same across runs while PIDs don't.
+//!
+//! Replaying a log re-runs its command line with its seed, which makes the BPF
+//! side take the same decisions again as long as the workload spawns its tasks
+//! in the same order. The decisions of the replay are checked against the log
+//! with a ReplayCheck.
+use crate::bpf_intf;
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use serde::Deserialize;
+use serde::Serialize;
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
+
+const HEADER_PREFIX: &str = "# ";
+const LOG_VERSION: u32 = 2;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LogHeader {
+    pub version: u32,
+    pub seed: u64,
+    /// Command line of the recorded run, including the program name.
+    pub argv: Vec<String>,
+}
+
+impl LogHeader {
+    pub fn new(seed: u64, argv: Vec<String>) -> Self {
+        Self {
+            version: LOG_VERSION,
+            seed,
+            argv,
+        }
+    }
+
+    /// Read the header of the decision log at @path.
+    pub fn read(path: &Path) -> Result<Self> {
+        let file =
+            File::open(path).with_context(|| format!("Failed to open decision log {:?}", path))?;
+        let mut line = String::new();
+        BufReader::new(file)
+            .read_line(&mut line)
+            .with_context(|| format!("Failed to read decision log {:?}", path))?;
+
+        let Some(json) = line.trim_end().strip_prefix(HEADER_PREFIX) else {
+            bail!("{:?} is not a decision log", path);
+        };
+        let header: Self = serde_json::from_str(json)
+            .with_context(|| format!("Failed to parse decision log header in {:?}", path))?;
+        if header.version != LOG_VERSION {
+            bail!(
+                "Unsupported decision log version {} in {:?}",
+                header.version,
+                path
+            );
+        }
+        Ok(header)
+    }
+}
+
+/// One step of splitmix64, as used by the BPF side to draw chaos decisions.
+pub fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// Initial state of the decision stream of the @spawn_idx'th task seeded with
+/// @comm in a run with @seed, as computed by chaos_seed_task() in BPF.
+pub fn task_stream(seed: u64, comm: &[u8], spawn_idx: u64) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325; // FNV-1a
+    for i in 0..bpf_intf::chaos_consts_CHAOS_COMM_LEN as usize {
+        hash ^= comm.get(i).copied().unwrap_or(0) as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    seed ^ hash ^ (spawn_idx << 32)
+}
+
+/// An event of the decision log.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Decision {
+    pub ts: u64,
+    pub pid: u32,
+    pub tgid: u32,
+    pub comm: String,
+    pub kind: String,
+    pub param: u64,
+    pub stream: u64,
+}
+
+impl Decision {
+    fn from_bytes(data: &[u8]) -> Result<Self> {
+        if data.len() < std::mem::size_of::<bpf_intf::chaos_decision>() {
+            bail!("short chaos_decision record ({} bytes)", data.len());
+        }
+        let d = unsafe {
+            // SAFETY: the length is checked above and chaos_decision is plain old data.
+            std::ptr::read_unaligned(data.as_ptr() as *const bpf_intf::chaos_decision)
+        };
+
+        let comm: String = d
+            .comm
+            .iter()
+            .take_while(|&&c| c != 0)
+            .map(|&c| c as u8 as char)
+            .map(|c| if c.is_whitespace() { '_' } else { c })
+            .collect();
+
+        Ok(Self {
+            ts: d.ts,
+            pid: d.pid,
+            tgid: d.tgid,
+            comm,
+            kind: trait_name(d.kind).to_string(),
+            param: d.param,
+            stream: d.stream,
+        })
+    }
+
+    fn parse(line: &str) -> Result<Self> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let [ts, pid, tgid, comm, kind, param, stream] = fields[..] else {
+            bail!("expected 7 fields, got {}", fields.len());
+        };
+        Ok(Self {
+            ts: ts.parse()?,
+            pid: pid.parse()?,
+            tgid: tgid.parse()?,
+            comm: comm.to_string(),
+            kind: kind.to_string(),
+            param: param.parse()?,
+            stream: u64::from_str_radix(stream, 16)?,
+        })
+    }
+
+    /// Whether the param is drawn from the task's stream or fixed by the
+    /// config, rather than depending on the state of the system.
+    fn param_is_seeded(&self) -> bool {
+        !matches!(self.kind.as_str(), "forced_migration" | "wakeup_reorder")
+    }
+
+    fn matches(&self, other: &Decision) -> bool {
+        self.kind == other.kind && (!self.param_is_seeded() || self.param == other.param)
+    }
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{:016x}",
+            self.ts, self.pid, self.tgid, self.comm, self.kind, self.param, self.stream
+        )
+    }
+}
+
+/// Checks the decisions taken while replaying a log against the recorded
+/// ones. Decisions are compared in order within each task's stream, as the
+/// interleaving of the tasks differs between runs.
+#[derive(Debug, Default)]
+pub struct ReplayCheck {
+    expected: HashMap<u64, VecDeque<Decision>>,
+    nr_recorded: u64,
+    nr_matched: u64,
+    nr_diverged: u64,
+    first_divergence: Option<String>,
+}
+
+impl ReplayCheck {
+    /// Load the decisions recorded in the log at @path.
+    pub fn read(path: &Path) -> Result<Self> {
+        LogHeader::read(path)?;
+        let file =
+            File::open(path).with_context(|| format!("Failed to open decision log {:?}", path))?;
+
+        let mut check = Self::default();
+        for (idx, line) in BufReader::new(file).lines().enumerate().skip(1) {
+            let line = line.with_context(|| format!("Failed to read decision log {:?}", path))?;
+            let d = Decision::parse(&line)
+                .with_context(|| format!("Invalid decision at {:?}:{}", path, idx + 1))?;
+            check.expected.entry(d.stream).or_default().push_back(d);
+            check.nr_recorded += 1;
+        }
+        Ok(check)
+    }
+
+    fn check(&mut self, d: &Decision) {
+        let expected = self
+            .expected
+            .get_mut(&d.stream)
+            .and_then(|stream| stream.pop_front());
+        match expected {
+            Some(e) if e.matches(d) => self.nr_matched += 1,
+            expected => {
+                self.nr_diverged += 1;
+                if self.first_divergence.is_none() {
+                    self.first_divergence = Some(match expected {
+                        Some(e) => format!("expected \"{}\", got \"{}\"", e, d),
+                        None => format!("unexpected \"{}\"", d),
+                    });
+                }
+            }
+        }
+    }
+
+    pub fn summary(&self) -> ReplaySummary {
+        ReplaySummary {
+            nr_recorded: self.nr_recorded,
+            nr_matched: self.nr_matched,
+            nr_diverged: self.nr_diverged,
+            nr_missing: self.expected.values().map(|s| s.len() as u64).sum(),
+            first_divergence: self.first_divergence.clone(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ReplaySummary {
+    pub nr_recorded: u64,
+    pub nr_matched: u64,
+    /// Replayed decisions which differ from the recorded ones.
+    pub nr_diverged: u64,
+    /// Recorded decisions which weren't taken again.
+    pub nr_missing: u64,
+    pub first_divergence: Option<String>,
+}
+
+impl ReplaySummary {
+    pub fn is_exact(&self) -> bool {
+        self.nr_diverged == 0 && self.nr_missing == 0
+    }
+}
+
+impl fmt::Display for ReplaySummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} recorded decisions replayed, {} diverged, {} missing",
+            self.nr_matched, self.nr_recorded, self.nr_diverged, self.nr_missing
+        )?;
+        if let Some(first) = &self.first_divergence {
+            write!(f, " (first divergence: {})", first)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct DecisionLog {
+    out: Option<BufWriter<File>>,
+    replay: Option<ReplayCheck>,
+    nr_events: u64,
+}
+
+fn trait_name(kind: u32) -> &'static str {
+    match kind {
+        bpf_intf::chaos_trait_kind_CHAOS_TRAIT_RANDOM_DELAYS => "random_delays",
+        bpf_intf::chaos_trait_kind_CHAOS_TRAIT_CPU_FREQ => "cpu_freq",
+        bpf_intf::chaos_trait_kind_CHAOS_TRAIT_DEGRADATION => "perf_degradation",
+        bpf_intf::chaos_trait_kind_CHAOS_TRAIT_KPROBE_RANDOM_DELAYS => "kprobe_random_delays",
+        bpf_intf::chaos_trait_kind_CHAOS_TRAIT_FORCED_MIGRATION => "forced_migration",
+        bpf_intf::chaos_trait_kind_CHAOS_TRAIT_STARVATION => "starvation",
+        bpf_intf::chaos_trait_kind_CHAOS_TRAIT_SLICE_JITTER => "slice_jitter",
+        bpf_intf::chaos_trait_kind_CHAOS_TRAIT_WAKEUP_REORDER => "wakeup_reorder",
+        _ => "unknown",
+    }
+}
+
+impl DecisionLog {
+    pub fn create(path: &Path, header: &LogHeader) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create decision log {:?}", path))?;
+        let mut out = BufWriter::new(file);
+        writeln!(out, "{}{}", HEADER_PREFIX, serde_json::to_string(header)?)?;
+        out.flush()?;
+        Ok(Self {
+            out: Some(out),
+            replay: None,
+            nr_events: 0,
+        })
+    }
+
+    /// Check the recorded events against @check without writing them anywhere.
+    pub fn replay(check: ReplayCheck) -> Self {
+        Self {
+            out: None,
+            replay: Some(check),
+            nr_events: 0,
+        }
+    }
+
+    /// Also check the recorded events against @check.
+    pub fn with_replay(mut self, check: Option<ReplayCheck>) -> Self {
+        self.replay = check;
+        self
+    }
+
+    /// Record an event received from the chaos_decisions ring buffer.
+    pub fn record(&mut self, data: &[u8]) -> Result<()> {
+        let d = Decision::from_bytes(data)?;
+        if let Some(out) = &mut self.out {
+            writeln!(out, "{}", d)?;
+        }
+        if let Some(replay) = &mut self.replay {
+            replay.check(&d);
+        }
+        self.nr_events += 1;
+        Ok(())
+    }
+
+    pub fn replay_summary(&self) -> Option<ReplaySummary> {
+        self.replay.as_ref().map(|replay| replay.summary())
+    }
+
+    pub fn nr_events(&self) -> u64 {
+        self.nr_events
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        if let Some(out) = &mut self.out {
+            out.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("scx_chaos_{}.{}", name, std::process::id()))
+    }
+
+    fn decision(stream: u64, kind: u32, param: u64) -> Vec<u8> {
+        let mut comm = [0; bpf_intf::chaos_consts_CHAOS_COMM_LEN as usize];
+        for (dst, src) in comm.iter_mut().zip(b"my app".iter()) {
+            *dst = *src as _;
+        }
+        let d = bpf_intf::chaos_decision {
+            ts: 1,
+            param,
+            stream,
+            pid: 3,
+            tgid: 3,
+            kind,
+            comm,
+        };
+        unsafe {
+            // SAFETY: chaos_decision is plain old data.
+            std::slice::from_raw_parts(
+                &d as *const _ as *const u8,
+                std::mem::size_of::<bpf_intf::chaos_decision>(),
+            )
+        }
+        .to_vec()
+    }
+
+    #[test]
+    fn test_splitmix64() {
+        // Reference outputs of splitmix64 seeded with 0 and 1234567.
+        let mut state = 0;
+        let out: Vec<u64> = (0..3).map(|_| splitmix64(&mut state)).collect();
+        assert_eq!(
+            out,
+            vec![0xe220a8397b1dcdaf, 0x6e789e6aa1b965f4, 0x06c45d188009454f]
+        );
+
+        let mut state = 1234567;
+        let out: Vec<u64> = (0..3).map(|_| splitmix64(&mut state)).collect();
+        assert_eq!(
+            out,
+            vec![
+                6457827717110365317,
+                3203168211198807973,
+                9817491932198370423
+            ]
+        );
+    }
+
+    #[test]
+    fn test_task_stream() {
+        // FNV-1a over the whole zero-padded comm.
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for &b in b"app\0\0\0\0\0\0\0\0\0\0\0\0\0" {
+            hash ^= b as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        assert_eq!(task_stream(0, b"app", 0), hash);
+        assert_eq!(task_stream(0, b"app", 0), task_stream(0, b"app\0\0", 0));
+        assert_eq!(task_stream(7, b"app", 0), hash ^ 7);
+
+        // The spawn index only changes the upper half, the comm everything.
+        let (a, b) = (task_stream(7, b"app", 0), task_stream(7, b"app", 3));
+        assert_eq!(a ^ b, 3 << 32);
+        assert_ne!(task_stream(7, b"app", 0), task_stream(7, b"ap", 0));
+    }
+
+    #[test]
+    fn test_log_roundtrip() {
+        let path = log_path("roundtrip.log");
+        let header = LogHeader::new(7, vec!["scx_chaos".into(), "--seed".into()]);
+        let mut log = DecisionLog::create(&path, &header).unwrap();
+        log.record(&decision(0xabc, 1, 500)).unwrap();
+        log.record(&decision(0xdef, 5, 2)).unwrap();
+        assert!(log.record(&[0; 4]).is_err());
+        log.flush().unwrap();
+        assert_eq!(log.nr_events(), 2);
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[1],
+            "1\t3\t3\tmy_app\trandom_delays\t500\t0000000000000abc"
+        );
+
+        let read = LogHeader::read(&path).unwrap();
+        assert_eq!((read.seed, read.argv), (7, header.argv));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_replay_check() {
+        let path = log_path("replay.log");
+        let mut log = DecisionLog::create(&path, &LogHeader::new(7, vec![])).unwrap();
+        log.record(&decision(1, 1, 500)).unwrap();
+        log.record(&decision(2, 1, 600)).unwrap();
+        log.record(&decision(1, 1, 700)).unwrap();
+        log.record(&decision(2, 5, 3)).unwrap();
+        log.flush().unwrap();
+
+        // Same decisions within each stream, interleaved differently, and a forced
+        // migration to another CPU.
+        let mut replay = DecisionLog::replay(ReplayCheck::read(&path).unwrap());
+        for d in [
+            decision(2, 1, 600),
+            decision(1, 1, 500),
+            decision(2, 5, 4),
+            decision(1, 1, 700),
+        ] {
+            replay.record(&d).unwrap();
+        }
+        let summary = replay.replay_summary().unwrap();
+        assert!(summary.is_exact(), "{}", summary);
+        assert_eq!(summary.nr_matched, 4);
+
+        // Different delay, a missing decision and a decision on an unknown stream.
+        let mut replay = DecisionLog::replay(ReplayCheck::read(&path).unwrap());
+        for d in [
+            decision(1, 1, 500),
+            decision(1, 1, 800),
+            decision(2, 1, 600),
+            decision(3, 1, 600),
+        ] {
+            replay.record(&d).unwrap();
+        }
+        let summary = replay.replay_summary().unwrap();
+        assert!(!summary.is_exact());
+        assert_eq!(
+            (summary.nr_recorded, summary.nr_matched),
+            (4, 2),
+            "{}",
+            summary
+        );
+        assert_eq!((summary.nr_diverged, summary.nr_missing), (2, 1));
+        assert!(summary
+            .first_divergence
+            .unwrap()
+            .contains("random_delays\t700"));
+
+        // Not a decision log.
+        std::fs::write(&path, "1\t2\n").unwrap();
+        assert!(ReplayCheck::read(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -4,9 +4,16 @@
 // GNU General Public License version 2.
 mod bpf_intf;
 mod bpf_skel;
+pub mod decision_log;
+pub mod scenario;
 pub mod stats;
 
 use bpf_skel::BpfSkel;
+use decision_log::DecisionLog;
+use decision_log::LogHeader;
+use decision_log::ReplayCheck;
+use scenario::PhaseInfo;
+use scenario::Scenario;
 use stats::Metrics;
 
 use log::warn;
@@ -41,18 +48,26 @@ use libbpf_rs::AsRawLibbpf;
 use libbpf_rs::Link;
 use libbpf_rs::MapCore as _;
 use libbpf_rs::OpenObject;
+use libbpf_rs::RingBuffer;
+use libbpf_rs::RingBufferBuilder;
 use log::debug;
 use log::info;
 use nix::unistd::Pid;
 use scx_stats::prelude::*;
+use serde::Deserialize;
 
 use std::alloc::Layout;
+use std::collections::hash_map::RandomState;
 use std::collections::HashSet;
 use std::fs::File;
+use std::hash::BuildHasher;
 use std::io::{BufRead, BufReader};
 use std::marker::PhantomPinned;
 use std::mem::MaybeUninit;
+use std::os::unix::fs::MetadataExt;
 use std::panic;
+use std::path::Path;
+use std::path::PathBuf;
 use std::pin::Pin;
 use std::process::Command;
 use std::ptr::NonNull;
@@ -64,6 +79,7 @@ use std::sync::RwLock;
 use std::thread;
 use std::time::Duration;
 use std::time::Instant;
+use std::time::SystemTime;
 
 const SCHEDULER_NAME: &str = "scx_chaos";
 struct ArenaAllocator(Pin<Rc<SkelWithObject>>);
@@ -95,7 +111,8 @@ unsafe impl Allocator for ArenaAllocator {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
 pub enum Trait {
     RandomDelays {
         frequency: f64,
@@ -111,14 +128,41 @@ pub enum Trait {
         frequency: f64,
         degradation_frac7: u64,
     },
+    ForcedMigration {
+        frequency: f64,
+        #[serde(default)]
+        cross_numa: bool,
+    },
+    Starvation {
+        frequency: f64,
+        duration_us: u64,
+    },
+    SliceJitter {
+        frequency: f64,
+        min_us: u64,
+        max_us: u64,
+    },
+    WakeupReorder {
+        frequency: f64,
+        window_us: u64,
+    },
 }
 
+/// Longest a task may be starved for. Half of the sched_ext watchdog timeout, leaving room for
+/// the task to wait its turn in the DSQ once it is released.
+pub const MAX_STARVATION_US: u64 =
+    bpf_intf::chaos_consts_CHAOS_WATCHDOG_TIMEOUT_MS as u64 * 1000 / 2;
+
 impl Trait {
     pub fn kind(&self) -> u32 {
         match self {
             Self::RandomDelays { .. } => bpf_intf::chaos_trait_kind_CHAOS_TRAIT_RANDOM_DELAYS,
             Self::CpuFreq { .. } => bpf_intf::chaos_trait_kind_CHAOS_TRAIT_CPU_FREQ,
             Self::PerfDegradation { .. } => bpf_intf::chaos_trait_kind_CHAOS_TRAIT_DEGRADATION,
+            Self::ForcedMigration { .. } => bpf_intf::chaos_trait_kind_CHAOS_TRAIT_FORCED_MIGRATION,
+            Self::Starvation { .. } => bpf_intf::chaos_trait_kind_CHAOS_TRAIT_STARVATION,
+            Self::SliceJitter { .. } => bpf_intf::chaos_trait_kind_CHAOS_TRAIT_SLICE_JITTER,
+            Self::WakeupReorder { .. } => bpf_intf::chaos_trait_kind_CHAOS_TRAIT_WAKEUP_REORDER,
         }
     }
 
@@ -127,7 +171,165 @@ impl Trait {
             Self::RandomDelays { frequency, .. } => *frequency,
             Self::CpuFreq { frequency, .. } => *frequency,
             Self::PerfDegradation { frequency, .. } => *frequency,
+            Self::ForcedMigration { frequency, .. } => *frequency,
+            Self::Starvation { frequency, .. } => *frequency,
+            Self::SliceJitter { frequency, .. } => *frequency,
+            Self::WakeupReorder { frequency, .. } => *frequency,
+        }
+    }
+
+    /// Check the trait's parameters, independently of any other traits.
+    pub fn validate(&self) -> Result<()> {
+        check_frequency("trait", self.frequency())?;
+
+        match self {
+            Self::RandomDelays { min_us, max_us, .. } if min_us > max_us => {
+                bail!("random_delays min_us {} > max_us {}", min_us, max_us);
+            }
+            Self::CpuFreq {
+                min_freq, max_freq, ..
+            } if min_freq > max_freq => {
+                bail!("cpu_freq min_freq {} > max_freq {}", min_freq, max_freq);
+            }
+            Self::PerfDegradation {
+                degradation_frac7, ..
+            } if *degradation_frac7 > 128 => {
+                bail!("degradation_frac7 {} must be <= 128", degradation_frac7);
+            }
+            Self::Starvation { duration_us, .. } if *duration_us > MAX_STARVATION_US => {
+                bail!(
+                    "starvation duration_us {} must be <= {}",
+                    duration_us,
+                    MAX_STARVATION_US
+                );
+            }
+            Self::SliceJitter { min_us, max_us, .. } if min_us > max_us => {
+                bail!("slice_jitter min_us {} > max_us {}", min_us, max_us);
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+pub(crate) fn check_frequency(what: &str, frequency: f64) -> Result<()> {
+    if !(0.0..=1.0).contains(&frequency) {
+        bail!("{} frequency {} must be between 0 and 1", what, frequency);
+    }
+    Ok(())
+}
+
+/// Trait parameters as written to the BPF rodata.
+#[derive(Debug, Default, PartialEq)]
+pub struct TraitParams {
+    /// Cumulative trait frequencies in 32-bit fixed point, indexed by trait kind.
+    pub freq_frac32: [u32; bpf_intf::chaos_trait_kind_CHAOS_TRAIT_MAX as usize],
+    pub random_delays_min_ns: u64,
+    pub random_delays_max_ns: u64,
+    /// Minimum and maximum frequency, if CPU frequency chaos is enabled.
+    pub cpu_freq: Option<(u32, u32)>,
+    pub degradation_freq_frac32: u32,
+    pub degradation_frac7: u64,
+    pub forced_migration_numa: bool,
+    pub starvation_ns: u64,
+    pub slice_jitter_min_ns: u64,
+    pub slice_jitter_max_ns: u64,
+    pub wakeup_reorder_window_ns: u64,
+}
+
+impl TraitParams {
+    pub fn new(traits: &[Trait]) -> Result<Self> {
+        let mut params = Self::default();
+
+        // Set up the frequency array. The first element means nothing, so should be what's
+        // required to add up to 100%. The rest should be cumulative frequencies.
+        let freq_array = &mut params.freq_frac32;
+        for tr in traits {
+            let kind = tr.kind();
+            if freq_array[kind as usize] != 0 {
+                bail!("trait of kind {} specified multiple times!", kind);
+            }
+
+            let fixed_point = (tr.frequency() * 2_f64.powf(32_f64)) as u32;
+            freq_array[kind as usize] = fixed_point;
+        }
+        let total = freq_array
+            .iter()
+            .try_fold(0u32, |acc, &freq| acc.checked_add(freq));
+        freq_array[bpf_intf::chaos_trait_kind_CHAOS_TRAIT_NONE as usize] =
+            total.map_or(0, |total| u32::MAX - total);
+        for i in 1..freq_array.len() {
+            freq_array[i] = freq_array[i]
+                .checked_add(freq_array[i - 1])
+                .ok_or_else(|| {
+                    let err =
+                        concat!("frequencies overflowed! please ensure that frequencies sum to",
+                    " <=1. as these are floating point numbers, you may have to decrease by",
+                    " slightly more than you expect.");
+                    anyhow::anyhow!(err)
+                })?;
+        }
+
+        for tr in traits {
+            match tr {
+                Trait::RandomDelays {
+                    frequency: _,
+                    min_us,
+                    max_us,
+                } => {
+                    params.random_delays_min_ns = min_us * 1000;
+                    params.random_delays_max_ns = max_us * 1000;
+                }
+                Trait::CpuFreq {
+                    frequency: _,
+                    min_freq,
+                    max_freq,
+                } => {
+                    params.cpu_freq = Some((*min_freq, *max_freq));
+                }
+                Trait::PerfDegradation {
+                    frequency,
+                    degradation_frac7,
+                } => {
+                    params.degradation_freq_frac32 = (frequency * 2_f64.powf(32_f64)) as u32;
+                    params.degradation_frac7 = *degradation_frac7;
+                }
+                Trait::ForcedMigration {
+                    frequency: _,
+                    cross_numa,
+                } => {
+                    params.forced_migration_numa = *cross_numa;
+                }
+                Trait::Starvation {
+                    frequency: _,
+                    duration_us,
+                } => {
+                    if *duration_us > MAX_STARVATION_US {
+                        bail!(
+                            "starvation duration {}us exceeds the {}us limit",
+                            duration_us,
+                            MAX_STARVATION_US
+                        );
+                    }
+                    params.starvation_ns = duration_us * 1000;
+                }
+                Trait::SliceJitter {
+                    frequency: _,
+                    min_us,
+                    max_us,
+                } => {
+                    params.slice_jitter_min_ns = min_us * 1000;
+                    params.slice_jitter_max_ns = max_us * 1000;
+                }
+                Trait::WakeupReorder {
+                    frequency: _,
+                    window_us,
+                } => {
+                    params.wakeup_reorder_window_ns = window_us * 1000;
+                }
+            }
         }
+
+        Ok(params)
     }
 }
 
@@ -137,7 +339,8 @@ pub enum RequiresPpid {
     IncludeParent(Pid),
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct KprobeRandomDelays {
     pub kprobes: Vec<String>,
     pub freq: f64,
@@ -145,6 +348,15 @@ pub struct KprobeRandomDelays {
     pub max_us: u64,
 }
 
+/// Further restricts which tasks chaos applies to. All set fields must match.
+#[derive(Debug, Default)]
+pub struct TaskFilter {
+    pub tgid: Option<Pid>,
+    /// Cgroup path relative to /sys/fs/cgroup. Descendant cgroups match too.
+    pub cgroup: Option<String>,
+    pub comm: Option<String>,
+}
+
 #[derive(Debug)]
 /// State required to build a Scheduler configuration.
 pub struct Builder<'a> {
@@ -153,6 +365,13 @@ pub struct Builder<'a> {
     pub kprobe_random_delays: Option<KprobeRandomDelays>,
     pub p2dq_opts: &'a P2dqOpts,
     pub requires_ppid: Option<RequiresPpid>,
+    pub task_filter: TaskFilter,
+    /// Set when running a scenario phase.
+    pub phase: Option<PhaseInfo>,
+    /// Seed for deterministic chaos decisions.
+    pub seed: Option<u64>,
+    /// Where to record injected events. Shared across scenario phases.
+    pub decision_log: Option<Arc<Mutex<DecisionLog>>>,
 }
 
 pub struct SkelWithObject {
@@ -167,7 +386,10 @@ pub struct Scheduler {
     _arena: HeapAllocator<ArenaAllocator>,
     _struct_ops: libbpf_rs::Link,
     _links: Vec<Link>,
+    decisions: Option<RingBuffer<'static>>,
     stats_server: StatsServer<(), Metrics>,
+    phase: Option<PhaseInfo>,
+    phase_started_at: SystemTime,
 
     // Fields are dropped in declaration order, this must be last as arena holds a reference to the
     // skel
@@ -202,9 +424,35 @@ impl Scheduler {
             kprobe_random_delays: stats
                 [bpf_intf::chaos_stat_idx_CHAOS_STAT_KPROBE_RANDOM_DELAYS as usize],
             timer_kicks: stats[bpf_intf::chaos_stat_idx_CHAOS_STAT_TIMER_KICKS as usize],
+            trait_forced_migration: stats
+                [bpf_intf::chaos_stat_idx_CHAOS_STAT_TRAIT_FORCED_MIGRATION as usize],
+            trait_starvation: stats[bpf_intf::chaos_stat_idx_CHAOS_STAT_TRAIT_STARVATION as usize],
+            trait_slice_jitter: stats
+                [bpf_intf::chaos_stat_idx_CHAOS_STAT_TRAIT_SLICE_JITTER as usize],
+            trait_wakeup_reorder: stats
+                [bpf_intf::chaos_stat_idx_CHAOS_STAT_TRAIT_WAKEUP_REORDER as usize],
+            decisions_dropped: stats
+                [bpf_intf::chaos_stat_idx_CHAOS_STAT_DECISIONS_DROPPED as usize],
+            phase_idx: self.phase.as_ref().map_or(0, |p| p.idx as u64),
+            phase: self
+                .phase
+                .as_ref()
+                .map(|p| p.name.clone())
+                .unwrap_or_default(),
+            phase_started_at_ms: self
+                .phase_started_at
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |d| d.as_millis() as u64),
         }
     }
 
+    fn consume_decisions(&self) -> Result<()> {
+        if let Some(rb) = &self.decisions {
+            rb.consume().context("Failed to consume chaos decisions")?;
+        }
+        Ok(())
+    }
+
     pub fn observe(
         &self,
         shutdown: &(Mutex<bool>, Condvar),
@@ -219,7 +467,10 @@ impl Scheduler {
         while !*guard {
             let skel = &self.skel.skel.read().unwrap();
 
+            self.consume_decisions()?;
+
             if uei_exited!(&skel, uei) {
+                self.consume_decisions()?;
                 return uei_report!(&skel, uei)
                     .and_then(|_| Err(anyhow::anyhow!("scheduler exited unexpectedly")));
             }
@@ -242,7 +493,7 @@ impl Scheduler {
                 .0;
         }
 
-        Ok(())
+        self.consume_decisions()
     }
 }
 
@@ -369,6 +620,29 @@ impl Builder<'_> {
             }
         };
 
+        if let Some(tgid) = self.task_filter.tgid {
+            rodata.target_tgid = tgid.as_raw();
+        } else {
+            rodata.target_tgid = -1;
+        }
+        if let Some(cgroup) = &self.task_filter.cgroup {
+            rodata.target_cgid = cgroup_id(cgroup)?;
+        }
+        if let Some(comm) = &self.task_filter.comm {
+            if comm.len() > scenario::MAX_COMM_LEN {
+                bail!("target comm {:?} is too long", comm);
+            }
+            for (dst, src) in rodata.target_comm.iter_mut().zip(comm.bytes()) {
+                *dst = src as _;
+            }
+        }
+
+        if let Some(seed) = self.seed {
+            rodata.chaos_seeded = true;
+            rodata.chaos_seed = seed;
+        }
+        rodata.decision_log_enabled = self.decision_log.is_some();
+
         if let Some(kprobe_random_delays) = &self.kprobe_random_delays {
             rodata.kprobe_delays_freq_frac32 =
                 (kprobe_random_delays.freq * 2_f64.powf(32_f64)) as u32;
@@ -376,67 +650,24 @@ impl Builder<'_> {
             rodata.kprobe_delays_max_ns = kprobe_random_delays.max_us * 1000;
         }
 
-        // Set up the frequency array. The first element means nothing, so should be what's
-        // required to add up to 100%. The rest should be cumulative frequencies.
-        let freq_array = &mut rodata.trait_delay_freq_frac32;
-        freq_array.fill(0);
-        for tr in &self.traits {
-            let kind = tr.kind();
-            if freq_array[kind as usize] != 0 {
-                bail!("trait of kind {} specified multiple times!", kind);
-            }
-
-            let fixed_point = (tr.frequency() * 2_f64.powf(32_f64)) as u32;
-            freq_array[kind as usize] = fixed_point;
-        }
-        freq_array[bpf_intf::chaos_trait_kind_CHAOS_TRAIT_NONE as usize] =
-            u32::MAX - freq_array.iter().sum::<u32>();
-        for i in 1..freq_array.len() {
-            freq_array[i] = freq_array[i]
-                .checked_add(freq_array[i - 1])
-                .ok_or_else(|| {
-                    let err =
-                        concat!("frequencies overflowed! please ensure that frequencies sum to",
-                    " <=1. as these are floating point numbers, you may have to decrease by",
-                    " slightly more than you expect.");
-                    anyhow::anyhow!(err)
-                })?;
-        }
-
-        debug!(
-            "frequencies calculated as: {:?}",
-            rodata.trait_delay_freq_frac32
-        );
-
-        for tr in &self.traits {
-            match tr {
-                Trait::RandomDelays {
-                    frequency: _,
-                    min_us,
-                    max_us,
-                } => {
-                    rodata.random_delays_min_ns = min_us * 1000;
-                    rodata.random_delays_max_ns = max_us * 1000;
-                }
-                Trait::CpuFreq {
-                    frequency: _,
-                    min_freq,
-                    max_freq,
-                } => {
-                    rodata.cpu_freq_min = *min_freq;
-                    rodata.cpu_freq_max = *max_freq;
-                    // Don't let p2dq control frequency
-                    rodata.p2dq_config.freq_control = MaybeUninit::new(false);
-                }
-                Trait::PerfDegradation {
-                    frequency,
-                    degradation_frac7,
-                } => {
-                    rodata.degradation_freq_frac32 = (frequency * 2_f64.powf(32_f64)) as u32;
-                    rodata.degradation_frac7 = *degradation_frac7;
-                }
-            }
+        let params = TraitParams::new(&self.traits)?;
+        debug!("frequencies calculated as: {:?}", params.freq_frac32);
+        rodata.trait_delay_freq_frac32 = params.freq_frac32;
+        rodata.random_delays_min_ns = params.random_delays_min_ns;
+        rodata.random_delays_max_ns = params.random_delays_max_ns;
+        if let Some((min_freq, max_freq)) = params.cpu_freq {
+            rodata.cpu_freq_min = min_freq;
+            rodata.cpu_freq_max = max_freq;
+            // Don't let p2dq control frequency
+            rodata.p2dq_config.freq_control = MaybeUninit::new(false);
         }
+        rodata.degradation_freq_frac32 = params.degradation_freq_frac32;
+        rodata.degradation_frac7 = params.degradation_frac7;
+        rodata.forced_migration_numa = params.forced_migration_numa;
+        rodata.starvation_ns = params.starvation_ns;
+        rodata.slice_jitter_min_ns = params.slice_jitter_min_ns;
+        rodata.slice_jitter_max_ns = params.slice_jitter_max_ns;
+        rodata.wakeup_reorder_window_ns = params.wakeup_reorder_window_ns;
 
         // For now, we'll do it in this way. However, once we upgrade to libbpf_rs 0.25.0,
         // we can use the set_autoattach method on the OpenProgramImpl to do this.
@@ -471,14 +702,31 @@ impl<'a> TryFrom<Builder<'a>> for Scheduler {
 
     fn try_from(b: Builder<'a>) -> Result<Scheduler> {
         let skel = b.load_skel()?;
+        let phase_started_at = SystemTime::now();
 
         let arena = HeapAllocator::new(ArenaAllocator(skel.clone()));
         let stats_server = StatsServer::new(stats::server_data()).launch()?;
-        let (links, struct_ops) = {
+        let (links, struct_ops, decisions) = {
             let mut skel_guard = skel.skel.write().unwrap();
+
+            let decisions = match &b.decision_log {
+                Some(log) => {
+                    let log = log.clone();
+                    let mut builder = RingBufferBuilder::new();
+                    builder.add(&skel_guard.maps.chaos_decisions, move |data| {
+                        if let Err(e) = log.lock().unwrap().record(data) {
+                            warn!("Failed to record chaos decision: {e:#}");
+                        }
+                        0
+                    })?;
+                    Some(builder.build()?)
+                }
+                None => None,
+            };
+
             let struct_ops = scx_ops_attach!(skel_guard, chaos)?;
             let links = b.attach_kprobes(&mut skel_guard)?;
-            (links, struct_ops)
+            (links, struct_ops, decisions)
         };
         debug!("scx_chaos scheduler started");
 
@@ -486,7 +734,10 @@ impl<'a> TryFrom<Builder<'a>> for Scheduler {
             _arena: arena,
             _struct_ops: struct_ops,
             _links: links,
+            decisions,
             stats_server,
+            phase: b.phase,
+            phase_started_at,
             skel,
         })
     }
@@ -536,6 +787,60 @@ pub struct PerfDegradationArgs {
     pub degradation_frac7: u64,
 }
 
+/// Migrate a waking process to a CPU in another LLC.
+#[derive(Debug, Parser)]
+pub struct ForcedMigrationArgs {
+    /// Chance of migrating a waking process away from its previous LLC.
+    #[clap(long)]
+    pub forced_migration_frequency: Option<f64>,
+
+    /// Migrate across NUMA nodes instead of LLCs.
+    #[clap(long, action = clap::ArgAction::SetTrue, requires = "forced_migration_frequency")]
+    pub forced_migration_numa: bool,
+}
+
+/// Withhold a process from running.
+#[derive(Debug, Parser)]
+pub struct StarvationArgs {
+    /// Chance of starving a process.
+    #[clap(long, requires = "starvation_duration_us")]
+    pub starvation_frequency: Option<f64>,
+
+    /// How long to starve a process for. Limited to half the watchdog timeout.
+    #[clap(long, requires = "starvation_frequency",
+           value_parser = clap::value_parser!(u64).range(1..=MAX_STARVATION_US))]
+    pub starvation_duration_us: Option<u64>,
+}
+
+/// Randomise the time slice of a process.
+#[derive(Debug, Parser)]
+pub struct SliceJitterArgs {
+    /// Chance of randomising the slice of a process.
+    #[clap(long, requires = "slice_jitter_min_us")]
+    pub slice_jitter_frequency: Option<f64>,
+
+    /// Minimum slice length.
+    #[clap(long, requires = "slice_jitter_max_us")]
+    pub slice_jitter_min_us: Option<u64>,
+
+    /// Maximum slice length.
+    #[clap(long, requires = "slice_jitter_frequency")]
+    pub slice_jitter_max_us: Option<u64>,
+}
+
+/// Reorder runnable processes within a DSQ.
+#[derive(Debug, Parser)]
+pub struct WakeupReorderArgs {
+    /// Chance of moving a waking process ahead of others in its DSQ.
+    #[clap(long)]
+    pub wakeup_reorder_frequency: Option<f64>,
+
+    /// How far ahead a process may jump in a vtime ordered DSQ. FIFO DSQs always put the
+    /// process at the head.
+    #[clap(long, default_value = "1000", requires = "wakeup_reorder_frequency")]
+    pub wakeup_reorder_window_us: u64,
+}
+
 /// Delay a process when a kprobe is hit.
 #[derive(Debug, Parser)]
 pub struct KprobeArgs {
@@ -604,6 +909,55 @@ pub struct Args {
     #[clap(long)]
     pub monitor: Option<f64>,
 
+    /// Run the timed phases in a scenario file (TOML if the name ends in ".toml", JSON
+    /// otherwise). Each phase has its own traits and targets, and the scheduler detaches
+    /// after the last phase unless the scenario repeats.
+    #[clap(
+        long,
+        conflicts_with_all = [
+            "random_delay_frequency",
+            "cpufreq_frequency",
+            "degradation_frequency",
+            "kprobes_for_random_delays",
+            "forced_migration_frequency",
+            "starvation_frequency",
+            "slice_jitter_frequency",
+            "wakeup_reorder_frequency",
+        ]
+    )]
+    pub scenario: Option<PathBuf>,
+
+    /// Seed for chaos decisions. With a seed, whether and how each task is perturbed is
+    /// decided by a per-task pseudo-random stream, so runs of the same workload see the
+    /// same perturbations.
+    #[clap(long)]
+    pub seed: Option<u64>,
+
+    /// Record every injected chaos event to this file. A seed is picked and recorded if
+    /// --seed is not given.
+    #[clap(long)]
+    pub decision_log: Option<PathBuf>,
+
+    /// Replay a decision log: rerun its recorded command line with its seed and check the
+    /// decisions taken against the logged ones. The command under test can be overridden by
+    /// passing a new one.
+    #[clap(
+        long,
+        conflicts_with_all = [
+            "seed",
+            "scenario",
+            "random_delay_frequency",
+            "cpufreq_frequency",
+            "degradation_frequency",
+            "kprobes_for_random_delays",
+            "forced_migration_frequency",
+            "starvation_frequency",
+            "slice_jitter_frequency",
+            "wakeup_reorder_frequency",
+        ]
+    )]
+    pub replay: Option<PathBuf>,
+
     #[command(flatten, next_help_heading = "Random Delays")]
     pub random_delay: RandomDelayArgs,
 
@@ -613,6 +967,18 @@ pub struct Args {
     #[command(flatten, next_help_heading = "CPU Frequency")]
     pub cpu_freq: CpuFreqArgs,
 
+    #[command(flatten, next_help_heading = "Forced Migration")]
+    pub forced_migration: ForcedMigrationArgs,
+
+    #[command(flatten, next_help_heading = "Starvation")]
+    pub starvation: StarvationArgs,
+
+    #[command(flatten, next_help_heading = "Slice Jitter")]
+    pub slice_jitter: SliceJitterArgs,
+
+    #[command(flatten, next_help_heading = "Wakeup Reorder")]
+    pub wakeup_reorder: WakeupReorderArgs,
+
     #[command(flatten, next_help_heading = "Kprobe Random Delays")]
     pub kprobe_random_delays: KprobeArgs,
 
@@ -640,89 +1006,215 @@ pub struct Args {
     pub args: Vec<String>,
 }
 
+/// Convert the trait options on the command line to traits, checking their parameters.
+fn args_traits(args: &Args) -> Result<Vec<Trait>> {
+    let mut traits = vec![];
+
+    if let RandomDelayArgs {
+        random_delay_frequency: Some(frequency),
+        random_delay_min_us: Some(min_us),
+        random_delay_max_us: Some(max_us),
+    } = args.random_delay
+    {
+        traits.push(Trait::RandomDelays {
+            frequency,
+            min_us,
+            max_us,
+        });
+    };
+    if let CpuFreqArgs {
+        cpufreq_frequency: Some(frequency),
+        cpufreq_min: Some(min_freq),
+        cpufreq_max: Some(max_freq),
+    } = args.cpu_freq
+    {
+        traits.push(Trait::CpuFreq {
+            frequency,
+            min_freq,
+            max_freq,
+        });
+    };
+    if let ForcedMigrationArgs {
+        forced_migration_frequency: Some(frequency),
+        forced_migration_numa: cross_numa,
+    } = args.forced_migration
+    {
+        traits.push(Trait::ForcedMigration {
+            frequency,
+            cross_numa,
+        });
+    };
+    if let StarvationArgs {
+        starvation_frequency: Some(frequency),
+        starvation_duration_us: Some(duration_us),
+    } = args.starvation
+    {
+        traits.push(Trait::Starvation {
+            frequency,
+            duration_us,
+        });
+    };
+    if let SliceJitterArgs {
+        slice_jitter_frequency: Some(frequency),
+        slice_jitter_min_us: Some(min_us),
+        slice_jitter_max_us: Some(max_us),
+    } = args.slice_jitter
+    {
+        traits.push(Trait::SliceJitter {
+            frequency,
+            min_us,
+            max_us,
+        });
+    };
+    if let WakeupReorderArgs {
+        wakeup_reorder_frequency: Some(frequency),
+        wakeup_reorder_window_us: window_us,
+    } = args.wakeup_reorder
+    {
+        traits.push(Trait::WakeupReorder {
+            frequency,
+            window_us,
+        });
+    };
+
+    for tr in &traits {
+        tr.validate()
+            .with_context(|| format!("Invalid {:?} options", tr))?;
+    }
+
+    Ok(traits)
+}
+
 struct BuilderIterator<'a> {
     args: &'a Args,
+    scenario: Option<&'a Scenario>,
+    decision_log: Option<Arc<Mutex<DecisionLog>>>,
     idx: u32,
 }
 
-impl<'a> From<&'a Args> for BuilderIterator<'a> {
-    fn from(args: &'a Args) -> BuilderIterator<'a> {
-        BuilderIterator { args, idx: 0 }
+impl<'a> BuilderIterator<'a> {
+    fn new(
+        args: &'a Args,
+        scenario: Option<&'a Scenario>,
+        decision_log: Option<Arc<Mutex<DecisionLog>>>,
+    ) -> BuilderIterator<'a> {
+        BuilderIterator {
+            args,
+            scenario,
+            decision_log,
+            idx: 0,
+        }
+    }
+
+    fn default_requires_ppid(&self) -> Option<RequiresPpid> {
+        if self.args.ppid_targeting {
+            if let Some(p) = self.args.pid {
+                Some(RequiresPpid::IncludeParent(Pid::from_raw(p)))
+            } else if !self.args.args.is_empty() {
+                Some(RequiresPpid::ExcludeParent(Pid::this()))
+            } else {
+                None
+            }
+        } else {
+            None
+        }
+    }
+
+    fn args_builder(&self) -> Result<Builder<'a>> {
+        let traits = args_traits(self.args)?;
+
+        let kprobe_random_delays = match &self.args.kprobe_random_delays {
+            KprobeArgs {
+                kprobes_for_random_delays,
+                kprobe_random_delay_frequency,
+                kprobe_random_delay_min_us: Some(min_us),
+                kprobe_random_delay_max_us: Some(max_us),
+            } if !kprobes_for_random_delays.is_empty() => Some(KprobeRandomDelays {
+                kprobes: kprobes_for_random_delays.clone(),
+                freq: kprobe_random_delay_frequency.unwrap_or(0.1),
+                min_us: *min_us,
+                max_us: *max_us,
+            }),
+            _ => None,
+        };
+
+        Ok(Builder {
+            traits,
+            verbose: self.args.verbose,
+            kprobe_random_delays,
+            p2dq_opts: &self.args.p2dq,
+            requires_ppid: self.default_requires_ppid(),
+            task_filter: TaskFilter::default(),
+            phase: None,
+            seed: self.args.seed,
+            decision_log: self.decision_log.clone(),
+        })
+    }
+
+    fn phase_builder(&self, idx: usize, phase: &scenario::Phase) -> Builder<'a> {
+        let target = &phase.target;
+
+        // A phase with its own target replaces the command line targeting.
+        let requires_ppid = if target.is_empty() {
+            self.default_requires_ppid()
+        } else {
+            target
+                .ppid
+                .map(|p| RequiresPpid::IncludeParent(Pid::from_raw(p)))
+        };
+
+        Builder {
+            traits: phase.traits.clone(),
+            verbose: self.args.verbose,
+            kprobe_random_delays: phase.kprobe_random_delays.clone(),
+            p2dq_opts: &self.args.p2dq,
+            requires_ppid,
+            task_filter: TaskFilter {
+                tgid: target.pid.map(Pid::from_raw),
+                cgroup: target.cgroup.clone(),
+                comm: target.comm.clone(),
+            },
+            phase: Some(PhaseInfo {
+                idx,
+                name: phase.name.clone(),
+                duration: phase.duration(),
+            }),
+            seed: self.args.seed,
+            decision_log: self.decision_log.clone(),
+        }
     }
 }
 
 impl<'a> Iterator for BuilderIterator<'a> {
-    type Item = Builder<'a>;
+    type Item = Result<Builder<'a>>;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.idx += 1;
 
-        if self.idx > 1 {
-            None
-        } else {
-            let mut traits = vec![];
-
-            if let RandomDelayArgs {
-                random_delay_frequency: Some(frequency),
-                random_delay_min_us: Some(min_us),
-                random_delay_max_us: Some(max_us),
-            } = self.args.random_delay
-            {
-                traits.push(Trait::RandomDelays {
-                    frequency,
-                    min_us,
-                    max_us,
-                });
-            };
-            if let CpuFreqArgs {
-                cpufreq_frequency: Some(frequency),
-                cpufreq_min: Some(min_freq),
-                cpufreq_max: Some(max_freq),
-            } = self.args.cpu_freq
-            {
-                traits.push(Trait::CpuFreq {
-                    frequency,
-                    min_freq,
-                    max_freq,
-                });
-            };
+        let Some(scenario) = self.scenario else {
+            return (self.idx == 1).then(|| self.args_builder());
+        };
 
-            let requires_ppid = if self.args.ppid_targeting {
-                if let Some(p) = self.args.pid {
-                    Some(RequiresPpid::IncludeParent(Pid::from_raw(p)))
-                } else if !self.args.args.is_empty() {
-                    Some(RequiresPpid::ExcludeParent(Pid::this()))
-                } else {
-                    None
-                }
-            } else {
-                None
-            };
+        let nr_phases = scenario.phases.len();
+        let step = self.idx as usize - 1;
+        if step >= nr_phases && !scenario.repeat {
+            return None;
+        }
 
-            let kprobe_random_delays = match &self.args.kprobe_random_delays {
-                KprobeArgs {
-                    kprobes_for_random_delays,
-                    kprobe_random_delay_frequency,
-                    kprobe_random_delay_min_us: Some(min_us),
-                    kprobe_random_delay_max_us: Some(max_us),
-                } if !kprobes_for_random_delays.is_empty() => Some(KprobeRandomDelays {
-                    kprobes: kprobes_for_random_delays.clone(),
-                    freq: kprobe_random_delay_frequency.unwrap_or(0.1),
-                    min_us: *min_us,
-                    max_us: *max_us,
-                }),
-                _ => None,
-            };
+        let idx = step % nr_phases;
+        Some(Ok(self.phase_builder(idx, &scenario.phases[idx])))
+    }
+}
 
-            Some(Builder {
-                traits,
-                verbose: self.args.verbose,
-                kprobe_random_delays,
-                p2dq_opts: &self.args.p2dq,
-                requires_ppid,
-            })
-        }
+/// Resolve a cgroup path relative to /sys/fs/cgroup to its cgroup ID.
+fn cgroup_id(cgroup: &str) -> Result<u64> {
+    let path = Path::new("/sys/fs/cgroup").join(cgroup.trim_start_matches('/'));
+    let meta = std::fs::metadata(&path)
+        .with_context(|| format!("Failed to find target cgroup {:?}", path))?;
+    if !meta.is_dir() {
+        bail!("target cgroup {:?} is not a directory", path);
     }
+    Ok(meta.ino())
 }
 
 pub fn validate_kprobes(kprobes: &[String]) -> Result<()> {
@@ -750,6 +1242,28 @@ pub fn validate_kprobes(kprobes: &[String]) -> Result<()> {
     Ok(())
 }
 
+/// Rebuild the arguments of the run recorded in the decision log at @path. Output
+/// options and the command under test are taken from @args.
+fn replay_args(path: &Path, args: Args) -> Result<(Args, LogHeader)> {
+    let header = LogHeader::read(path)?;
+    let mut replayed = Args::try_parse_from(&header.argv)
+        .with_context(|| format!("Failed to parse command line recorded in {:?}", path))?;
+
+    replayed.seed = Some(header.seed);
+    replayed.decision_log = args.decision_log;
+    replayed.verbose = args.verbose;
+    replayed.stats = args.stats;
+    if !args.args.is_empty() {
+        replayed.args = args.args;
+    }
+
+    info!(
+        "Replaying {:?} with seed {}: {:?}",
+        path, header.seed, header.argv
+    );
+    Ok((replayed, header))
+}
+
 pub fn run(args: Args) -> Result<()> {
     if args.version {
         println!(
@@ -759,6 +1273,35 @@ pub fn run(args: Args) -> Result<()> {
         return Ok(());
     }
 
+    // The recorded command line of a replayed log is kept as-is so that replays of
+    // replays still point back at the original run.
+    let (mut args, argv, replay) = match args.replay.clone() {
+        Some(path) => {
+            let (args, header) = replay_args(&path, args)?;
+            (args, header.argv, Some(ReplayCheck::read(&path)?))
+        }
+        None => (args, std::env::args().collect(), None),
+    };
+
+    let decision_log = match (&args.decision_log, replay) {
+        (Some(path), replay) => {
+            let seed = *args
+                .seed
+                .get_or_insert_with(|| RandomState::new().hash_one(Instant::now()));
+            let log = DecisionLog::create(path, &LogHeader::new(seed, argv))?;
+            info!("Recording chaos decisions to {:?} (seed {})", path, seed);
+            Some(Arc::new(Mutex::new(log.with_replay(replay))))
+        }
+        (None, Some(replay)) => Some(Arc::new(Mutex::new(DecisionLog::replay(replay)))),
+        (None, None) => None,
+    };
+
+    let scenario = args
+        .scenario
+        .as_deref()
+        .map(Scenario::load)
+        .transpose()?
+        .map(Arc::new);
     let args = Arc::new(args);
 
     let shutdown = Arc::new((Mutex::new(false), Condvar::new()));
@@ -792,15 +1335,30 @@ pub fn run(args: Args) -> Result<()> {
 
     let scheduler_thread = thread::spawn({
         let args = args.clone();
+        let scenario = scenario.clone();
+        let decision_log = decision_log.clone();
         let shutdown = shutdown.clone();
 
         move || -> Result<()> {
-            for builder in BuilderIterator::from(&*args) {
+            for builder in BuilderIterator::new(&args, scenario.as_deref(), decision_log) {
+                if *shutdown.0.lock().unwrap() {
+                    break;
+                }
+
+                let builder = builder?;
                 info!("{:?}", &builder);
 
+                let timeout = builder.phase.as_ref().map(|phase| {
+                    info!(
+                        "Entering scenario phase {} ({:?}) for {:?}",
+                        phase.idx, phase.name, phase.duration
+                    );
+                    phase.duration
+                });
+
                 let sched: Scheduler = builder.try_into()?;
 
-                sched.observe(&shutdown, None)?;
+                sched.observe(&shutdown, timeout)?;
             }
 
             Ok(())
@@ -853,6 +1411,15 @@ pub fn run(args: Args) -> Result<()> {
                     should_run_app = false;
                 } else {
                     info!("TODO: report what the scheduler was doing when it crashed");
+                    if let (Some(log), Some(path)) = (&decision_log, &args.decision_log) {
+                        let mut log = log.lock().unwrap();
+                        log.flush()?;
+                        info!(
+                            "app under test failed after {} chaos decisions, reproduce with: scx_chaos --replay {:?}",
+                            log.nr_events(),
+                            path
+                        );
+                    }
                     should_run_app &= !*shutdown.0.lock().unwrap() && args.repeat_failure;
                 };
 
@@ -863,8 +1430,15 @@ pub fn run(args: Args) -> Result<()> {
         }
     }
 
-    // Notify shutdown if we're exiting due to args or pid termination
-    if !args.args.is_empty() || args.pid.is_some() {
+    // A scenario without a command under test runs until its last phase ends
+    if scenario.is_some() && args.args.is_empty() && args.pid.is_none() {
+        while !scheduler_thread.is_finished() {
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    // Notify shutdown if we're exiting due to args or pid termination, or the end of a scenario
+    if !args.args.is_empty() || args.pid.is_some() || scenario.is_some() {
         let (lock, cvar) = &*shutdown;
         *lock.lock().unwrap() = true;
         cvar.notify_all();
@@ -876,6 +1450,17 @@ pub fn run(args: Args) -> Result<()> {
         Err(e) => panic::resume_unwind(e),
     }
 
+    if let Some(summary) = decision_log
+        .as_ref()
+        .and_then(|log| log.lock().unwrap().replay_summary())
+    {
+        if summary.is_exact() {
+            info!("Replay matched: {}", summary);
+        } else {
+            warn!("Replay diverged: {}", summary);
+        }
+    }
+
     match stats_thread.map(|t| t.join()).unwrap_or(Ok(Ok(()))) {
         Ok(Ok(())) => {}
         Ok(Err(e)) => return Err(e),
@@ -884,3 +1469,203 @@ pub fn run(args: Args) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_replay_args() {
+        let dir = std::env::temp_dir().join(format!("scx_chaos_replay.{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let recorded = dir.join("recorded.log");
+        let argv = to_strings(&[
+            "scx_chaos",
+            "--random-delay-frequency",
+            "0.1",
+            "--random-delay-min-us",
+            "10",
+            "--random-delay-max-us",
+            "20",
+            "--decision-log",
+            "/nonexistent/recorded.log",
+            "--",
+            "stress",
+            "-c",
+            "1",
+        ]);
+        DecisionLog::create(&recorded, &LogHeader::new(42, argv.clone())).unwrap();
+        let recorded_str = recorded.to_str().unwrap();
+
+        // The recorded options and command line are replayed with the recorded seed,
+        // output options come from the replaying command line.
+        let args = Args::try_parse_from([
+            "scx_chaos",
+            "--replay",
+            recorded_str,
+            "--decision-log",
+            "/nonexistent/replay.log",
+        ])
+        .unwrap();
+        let (replayed, header) = replay_args(&recorded, args).unwrap();
+        assert_eq!((header.seed, &header.argv), (42, &argv));
+        assert_eq!(replayed.seed, Some(42));
+        assert_eq!(replayed.replay, None);
+        assert_eq!(
+            replayed.decision_log,
+            Some(PathBuf::from("/nonexistent/replay.log"))
+        );
+        assert_eq!(replayed.random_delay.random_delay_frequency, Some(0.1));
+        assert_eq!(replayed.random_delay.random_delay_min_us, Some(10));
+        assert_eq!(replayed.random_delay.random_delay_max_us, Some(20));
+        assert_eq!(replayed.args, to_strings(&["stress", "-c", "1"]));
+
+        // The command under test can be overridden, and nothing is recorded unless asked.
+        let args = Args::try_parse_from(["scx_chaos", "--replay", recorded_str, "true"]).unwrap();
+        let (replayed, _) = replay_args(&recorded, args).unwrap();
+        assert_eq!(replayed.decision_log, None);
+        assert_eq!(replayed.args, to_strings(&["true"]));
+
+        // The seed can't be overridden when replaying.
+        assert!(
+            Args::try_parse_from(["scx_chaos", "--replay", recorded_str, "--seed", "1"]).is_err()
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn parse_traits(args: &[&str]) -> Result<Vec<Trait>> {
+        let args = Args::try_parse_from(std::iter::once("scx_chaos").chain(args.iter().copied()))?;
+        args_traits(&args)
+    }
+
+    #[test]
+    fn test_args_traits() {
+        assert_eq!(parse_traits(&[]).unwrap(), vec![]);
+
+        let traits = parse_traits(&[
+            "--forced-migration-frequency",
+            "0.1",
+            "--forced-migration-numa",
+            "--starvation-frequency",
+            "0.2",
+            "--starvation-duration-us",
+            "500",
+            "--slice-jitter-frequency",
+            "0.3",
+            "--slice-jitter-min-us",
+            "100",
+            "--slice-jitter-max-us",
+            "100",
+            "--wakeup-reorder-frequency",
+            "0.05",
+        ])
+        .unwrap();
+        assert_eq!(
+            traits,
+            vec![
+                Trait::ForcedMigration {
+                    frequency: 0.1,
+                    cross_numa: true,
+                },
+                Trait::Starvation {
+                    frequency: 0.2,
+                    duration_us: 500,
+                },
+                Trait::SliceJitter {
+                    frequency: 0.3,
+                    min_us: 100,
+                    max_us: 100,
+                },
+                Trait::WakeupReorder {
+                    frequency: 0.05,
+                    window_us: 1000,
+                },
+            ]
+        );
+
+        let traits = parse_traits(&[
+            "--forced-migration-frequency",
+            "1",
+            "--wakeup-reorder-frequency",
+            "0",
+            "--wakeup-reorder-window-us",
+            "20",
+        ])
+        .unwrap();
+        assert_eq!(
+            traits,
+            vec![
+                Trait::ForcedMigration {
+                    frequency: 1.0,
+                    cross_numa: false,
+                },
+                Trait::WakeupReorder {
+                    frequency: 0.0,
+                    window_us: 20,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_args_traits_invalid() {
+        let err = |args: &[&str]| format!("{:#}", parse_traits(args).unwrap_err());
+
+        // Rejected by the parameter checks.
+        assert!(err(&["--forced-migration-frequency", "1.5"]).contains("between 0 and 1"));
+        assert!(err(&[
+            "--starvation-frequency=-0.1",
+            "--starvation-duration-us",
+            "10"
+        ])
+        .contains("between 0 and 1"));
+        assert!(err(&[
+            "--slice-jitter-frequency",
+            "0.1",
+            "--slice-jitter-min-us",
+            "200",
+            "--slice-jitter-max-us",
+            "100",
+        ])
+        .contains("slice_jitter min_us 200 > max_us 100"));
+        assert!(err(&["--wakeup-reorder-frequency", "NaN"]).contains("between 0 and 1"));
+
+        // Rejected by the argument parser.
+        for args in [
+            &["--forced-migration-numa"][..],
+            &["--starvation-frequency", "0.1"],
+            &[
+                "--starvation-frequency",
+                "0.1",
+                "--starvation-duration-us",
+                "0",
+            ],
+            &[
+                "--starvation-frequency",
+                "0.1",
+                "--starvation-duration-us",
+                &(MAX_STARVATION_US + 1).to_string(),
+            ],
+            &[
+                "--slice-jitter-frequency",
+                "0.1",
+                "--slice-jitter-min-us",
+                "1",
+            ],
+            &["--slice-jitter-min-us", "1", "--slice-jitter-max-us", "2"],
+            &["--wakeup-reorder-window-us", "10"],
+        ] {
+            assert!(
+                Args::try_parse_from(std::iter::once("scx_chaos").chain(args.iter().copied()))
+                    .is_err(),
+                "{:?}",
+                args
+            );
+        }
+    }
+}
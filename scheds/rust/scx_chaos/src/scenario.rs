@@ -0,0 +1,400 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+//! Declarative chaos scenarios.
+//!
+//! A scenario is a list of timed phases loaded from a TOML or JSON file. Each
+//! phase carries its own traits, kprobe delays and target selector. Trait
+//! parameters live in rodata, so the scheduler is reloaded at every phase
+//! boundary.
+use crate::check_frequency;
+use crate::KprobeRandomDelays;
+use crate::Trait;
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use serde::Deserialize;
+
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::Duration;
+
+/// Maximum length of a comm selector, excluding the terminating NUL.
+pub const MAX_COMM_LEN: usize = 15;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    /// Start over from the first phase after the last one ends.
+    #[serde(default)]
+    pub repeat: bool,
+
+    pub phases: Vec<Phase>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Phase {
+    pub name: String,
+
+    /// How long the phase lasts, in seconds.
+    pub duration_s: f64,
+
+    /// Traits to apply during the phase. An empty list gives a baseline phase.
+    #[serde(default)]
+    pub traits: Vec<Trait>,
+
+    #[serde(default)]
+    pub kprobe_random_delays: Option<KprobeRandomDelays>,
+
+    /// Tasks the phase applies to. Selectors are ANDed together and an empty
+    /// target falls back to the command line targeting.
+    #[serde(default)]
+    pub target: Target,
+}
+
+/// Selects the tasks a phase applies chaos to.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Target {
+    /// Only the threads of this process.
+    pub pid: Option<libc::pid_t>,
+
+    /// This process and all of its descendants.
+    pub ppid: Option<libc::pid_t>,
+
+    /// Tasks in this cgroup or below it, relative to /sys/fs/cgroup.
+    pub cgroup: Option<String>,
+
+    /// Tasks whose comm is exactly this.
+    pub comm: Option<String>,
+}
+
+/// The phase a Builder was created for, carried through to the stats.
+#[derive(Clone, Debug)]
+pub struct PhaseInfo {
+    pub idx: usize,
+    pub name: String,
+    pub duration: Duration,
+}
+
+impl Scenario {
+    /// Load a scenario from @path. Files ending in ".toml" are parsed as TOML,
+    /// everything else as JSON.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read scenario {:?}", path))?;
+
+        Self::parse(&content, path.extension().is_some_and(|ext| ext == "toml"))
+            .with_context(|| format!("Invalid scenario {:?}", path))
+    }
+
+    /// Parse and validate a scenario from @content, in TOML if @is_toml is set
+    /// and JSON otherwise.
+    fn parse(content: &str, is_toml: bool) -> Result<Self> {
+        let scenario: Self = if is_toml {
+            toml::from_str(content).context("Failed to parse TOML")?
+        } else {
+            serde_json::from_str(content).context("Failed to parse JSON")?
+        };
+
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.phases.is_empty() {
+            bail!("scenario has no phases");
+        }
+        for (idx, phase) in self.phases.iter().enumerate() {
+            phase
+                .validate()
+                .with_context(|| format!("phase {} ({:?})", idx, phase.name))?;
+        }
+        Ok(())
+    }
+}
+
+impl Phase {
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.duration_s)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if !self.duration_s.is_finite() || self.duration_s <= 0.0 {
+            bail!("duration_s must be positive, got {}", self.duration_s);
+        }
+
+        let mut kinds = HashSet::new();
+        let mut total_frequency = 0.0;
+        for tr in &self.traits {
+            if !kinds.insert(tr.kind()) {
+                bail!("trait {:?} specified multiple times", tr);
+            }
+            tr.validate()?;
+            total_frequency += tr.frequency();
+        }
+        if total_frequency > 1.0 {
+            bail!("trait frequencies sum to {} which is > 1", total_frequency);
+        }
+
+        if let Some(kd) = &self.kprobe_random_delays {
+            if kd.kprobes.is_empty() {
+                bail!("kprobe_random_delays has no kprobes");
+            }
+            check_frequency("kprobe_random_delays", kd.freq)?;
+            if kd.min_us > kd.max_us {
+                bail!(
+                    "kprobe_random_delays min_us {} > max_us {}",
+                    kd.min_us,
+                    kd.max_us
+                );
+            }
+        }
+
+        self.target.validate()
+    }
+}
+
+impl Target {
+    pub fn is_empty(&self) -> bool {
+        self.pid.is_none() && self.ppid.is_none() && self.cgroup.is_none() && self.comm.is_none()
+    }
+
+    fn validate(&self) -> Result<()> {
+        for (what, pid) in [("pid", self.pid), ("ppid", self.ppid)] {
+            if pid.is_some_and(|pid| pid <= 0) {
+                bail!("target {} must be positive", what);
+            }
+        }
+        if let Some(comm) = &self.comm {
+            if comm.is_empty() || comm.len() > MAX_COMM_LEN {
+                bail!(
+                    "target comm {:?} must be 1 to {} bytes long",
+                    comm,
+                    MAX_COMM_LEN
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bpf_intf;
+    use crate::TraitParams;
+
+    const SCENARIO: &str = r#"
+repeat = true
+
+[[phases]]
+name = "baseline"
+duration_s = 30
+
+[[phases]]
+name = "db"
+duration_s = 0.5
+target = { cgroup = "workload.slice/db", comm = "postgres" }
+traits = [
+    { kind = "random_delays", frequency = 0.25, min_us = 500, max_us = 5000 },
+    { kind = "slice_jitter", frequency = 0.5, min_us = 100, max_us = 200 },
+]
+
+[[phases]]
+name = "throttle"
+duration_s = 60
+traits = [{ kind = "cpu_freq", frequency = 0.5, min_freq = 256, max_freq = 512 }]
+kprobe_random_delays = { kprobes = ["do_exit"], freq = 0.1, min_us = 1, max_us = 2 }
+"#;
+
+    fn err(content: &str, is_toml: bool) -> String {
+        format!("{:#}", Scenario::parse(content, is_toml).unwrap_err())
+    }
+
+    fn phase(body: &str) -> String {
+        format!("[[phases]]\nname = \"p\"\n{}", body)
+    }
+
+    #[test]
+    fn test_parse_toml() {
+        let scenario = Scenario::parse(SCENARIO, true).unwrap();
+        assert!(scenario.repeat);
+        let names: Vec<&str> = scenario.phases.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["baseline", "db", "throttle"]);
+
+        let baseline = &scenario.phases[0];
+        assert_eq!(baseline.duration(), Duration::from_secs(30));
+        assert!(baseline.traits.is_empty());
+        assert!(baseline.target.is_empty());
+        assert!(baseline.kprobe_random_delays.is_none());
+
+        let db = &scenario.phases[1];
+        assert_eq!(db.duration(), Duration::from_millis(500));
+        assert_eq!(db.traits.len(), 2);
+        assert_eq!(db.target.cgroup.as_deref(), Some("workload.slice/db"));
+        assert_eq!(db.target.comm.as_deref(), Some("postgres"));
+        assert!(db.target.pid.is_none());
+
+        let throttle = &scenario.phases[2];
+        let kd = throttle.kprobe_random_delays.as_ref().unwrap();
+        assert_eq!(kd.kprobes, vec!["do_exit".to_string()]);
+        assert_eq!((kd.min_us, kd.max_us), (1, 2));
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let scenario = Scenario::parse(
+            r#"{"phases": [{"name": "x", "duration_s": 1, "target": {"ppid": 42},
+                "traits": [{"kind": "forced_migration", "frequency": 0.1}]}]}"#,
+            false,
+        )
+        .unwrap();
+        assert!(!scenario.repeat);
+        assert_eq!(scenario.phases[0].target.ppid, Some(42));
+        assert!(matches!(
+            scenario.phases[0].traits[0],
+            Trait::ForcedMigration {
+                cross_numa: false,
+                ..
+            }
+        ));
+
+        // TOML is only used for files ending in ".toml".
+        assert!(err(SCENARIO, false).contains("Failed to parse JSON"));
+    }
+
+    #[test]
+    fn test_load() {
+        let dir = std::env::temp_dir().join(format!("scx_chaos_scenario.{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("scenario.toml");
+        std::fs::write(&path, SCENARIO).unwrap();
+        assert_eq!(Scenario::load(&path).unwrap().phases.len(), 3);
+
+        std::fs::write(&path, "phases = []").unwrap();
+        let e = format!("{:#}", Scenario::load(&path).unwrap_err());
+        assert!(e.contains("Invalid scenario") && e.contains("no phases"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_empty() {
+        assert!(err("phases = []", true).contains("no phases"));
+        assert!(err("repeat = true", true).contains("missing field `phases`"));
+        assert!(err(&phase("traits = []"), true).contains("missing field `duration_s`"));
+        assert!(err(&phase("duration_s = 1\nbogus = 1"), true).contains("unknown field"));
+
+        // A phase without traits is a baseline phase.
+        let scenario = Scenario::parse(&phase("duration_s = 1\ntraits = []"), true).unwrap();
+        assert!(scenario.phases[0].traits.is_empty());
+    }
+
+    #[test]
+    fn test_overlapping_traits() {
+        let traits = |traits: &str| phase(&format!("duration_s = 1\ntraits = [{}]", traits));
+
+        assert!(err(
+            &traits(
+                r#"{ kind = "random_delays", frequency = 0.1, min_us = 1, max_us = 2 },
+                   { kind = "random_delays", frequency = 0.1, min_us = 3, max_us = 4 }"#
+            ),
+            true
+        )
+        .contains("specified multiple times"));
+        assert!(err(
+            &traits(
+                r#"{ kind = "forced_migration", frequency = 0.6 },
+                   { kind = "wakeup_reorder", frequency = 0.6, window_us = 10 }"#
+            ),
+            true
+        )
+        .contains("> 1"));
+        assert!(err(
+            &traits(r#"{ kind = "starvation", frequency = 1.5, duration_us = 10 }"#),
+            true
+        )
+        .contains("between 0 and 1"));
+        assert!(err(
+            &traits(r#"{ kind = "slice_jitter", frequency = 0.1, min_us = 5, max_us = 1 }"#),
+            true
+        )
+        .contains("min_us 5 > max_us 1"));
+    }
+
+    #[test]
+    fn test_bad_duration() {
+        for duration in ["0", "-1", "inf", "nan"] {
+            let e = err(&phase(&format!("duration_s = {}", duration)), true);
+            assert!(
+                e.contains("duration_s must be positive"),
+                "{}: {}",
+                duration,
+                e
+            );
+        }
+        assert!(err(&phase("duration_s = \"10s\""), true).contains("Failed to parse TOML"));
+    }
+
+    #[test]
+    fn test_bad_target() {
+        for (target, msg) in [
+            ("{ pid = 0 }", "target pid must be positive"),
+            ("{ ppid = -1 }", "target ppid must be positive"),
+            ("{ comm = \"\" }", "must be 1 to 15 bytes long"),
+            (
+                "{ comm = \"a_very_long_command\" }",
+                "must be 1 to 15 bytes long",
+            ),
+        ] {
+            let e = err(
+                &phase(&format!("duration_s = 1\ntarget = {}", target)),
+                true,
+            );
+            assert!(e.contains(msg), "{}: {}", target, e);
+        }
+    }
+
+    #[test]
+    fn test_phase_rodata() {
+        let scenario = Scenario::parse(SCENARIO, true).unwrap();
+        let params: Vec<TraitParams> = scenario
+            .phases
+            .iter()
+            .map(|phase| TraitParams::new(&phase.traits).unwrap())
+            .collect();
+
+        // Baseline: every roll falls on CHAOS_TRAIT_NONE.
+        assert!(params[0].freq_frac32.iter().all(|&freq| freq == u32::MAX));
+        assert_eq!(params[0].cpu_freq, None);
+
+        // db: 25% random delays, 50% slice jitter, the rest none.
+        let db = &params[1];
+        let none = bpf_intf::chaos_trait_kind_CHAOS_TRAIT_NONE as usize;
+        let delays = bpf_intf::chaos_trait_kind_CHAOS_TRAIT_RANDOM_DELAYS as usize;
+        let jitter = bpf_intf::chaos_trait_kind_CHAOS_TRAIT_SLICE_JITTER as usize;
+        assert_eq!(db.freq_frac32[none], u32::MAX - (3 << 30));
+        assert_eq!(db.freq_frac32[delays] - db.freq_frac32[none], 1 << 30);
+        assert_eq!(db.freq_frac32[jitter] - db.freq_frac32[jitter - 1], 1 << 31);
+        assert_eq!(*db.freq_frac32.last().unwrap(), u32::MAX);
+        assert_eq!(
+            (db.random_delays_min_ns, db.random_delays_max_ns),
+            (500_000, 5_000_000)
+        );
+        assert_eq!(
+            (db.slice_jitter_min_ns, db.slice_jitter_max_ns),
+            (100_000, 200_000)
+        );
+        assert_eq!(db.cpu_freq, None);
+
+        // throttle: only CPU frequency chaos, nothing left over from db.
+        let throttle = &params[2];
+        assert_eq!(throttle.cpu_freq, Some((256, 512)));
+        assert_eq!(throttle.random_delays_max_ns, 0);
+        assert_eq!(throttle.slice_jitter_max_ns, 0);
+    }
+}
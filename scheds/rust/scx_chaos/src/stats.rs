@@ -15,48 +15,99 @@ use serde::Serialize;
 #[derive(Clone, Debug, Default, Serialize, Deserialize, Stats)]
 #[stat(top)]
 pub struct Metrics {
-    #[stat(desc = "Number of times random delay chaos trait was applied")]
+    #[stat(
+        desc = "Number of times random delay chaos trait was applied",
+        _counter
+    )]
     pub trait_random_delays: u64,
-    #[stat(desc = "Number of times CPU frequency chaos trait was applied")]
+    #[stat(
+        desc = "Number of times CPU frequency chaos trait was applied",
+        _counter
+    )]
     pub trait_cpu_freq: u64,
-    #[stat(desc = "Number of times performance degradation chaos trait was applied")]
+    #[stat(
+        desc = "Number of times performance degradation chaos trait was applied",
+        _counter
+    )]
     pub trait_degradation: u64,
-    #[stat(desc = "Number of times chaos was excluded due to task matching")]
+    #[stat(
+        desc = "Number of times forced migration chaos trait was applied",
+        _counter
+    )]
+    pub trait_forced_migration: u64,
+    #[stat(desc = "Number of times starvation chaos trait was applied", _counter)]
+    pub trait_starvation: u64,
+    #[stat(
+        desc = "Number of times slice jitter chaos trait was applied",
+        _counter
+    )]
+    pub trait_slice_jitter: u64,
+    #[stat(
+        desc = "Number of times wakeup reorder chaos trait was applied",
+        _counter
+    )]
+    pub trait_wakeup_reorder: u64,
+    #[stat(
+        desc = "Number of times chaos was excluded due to task matching",
+        _counter
+    )]
     pub chaos_excluded: u64,
-    #[stat(desc = "Number of times chaos was skipped (TRAIT_NONE selected)")]
+    #[stat(
+        desc = "Number of times chaos was skipped (TRAIT_NONE selected)",
+        _counter
+    )]
     pub chaos_skipped: u64,
-    #[stat(desc = "Number of timer-based CPU kicks for delayed tasks")]
+    #[stat(desc = "Number of timer-based CPU kicks for delayed tasks", _counter)]
     pub timer_kicks: u64,
-    #[stat(desc = "Number of times a kprobe caused a random delay to be applied")]
+    #[stat(
+        desc = "Number of times a kprobe caused a random delay to be applied",
+        _counter
+    )]
     pub kprobe_random_delays: u64,
+    #[stat(
+        desc = "Number of chaos decisions dropped from the decision log because the ring buffer was full",
+        _counter
+    )]
+    pub decisions_dropped: u64,
+    #[stat(desc = "Index of the current scenario phase", _gauge)]
+    pub phase_idx: u64,
+    #[stat(desc = "Name of the current scenario phase")]
+    pub phase: String,
+    #[stat(
+        desc = "Wall-clock time the current scenario phase started (ms since epoch)",
+        _gauge
+    )]
+    pub phase_started_at_ms: u64,
 }
 
 impl Metrics {
     fn format<W: Write>(&self, w: &mut W) -> Result<()> {
         writeln!(
             w,
-            "chaos traits: random_delays/cpu_freq/degradation {}/{}/{}\n\tchaos excluded/skipped {}/{}\n\tkprobe_random_delays {}\n\ttimer kicks: {}",
+            "chaos traits: random_delays/cpu_freq/degradation {}/{}/{}\n\tforced_migration/starvation/slice_jitter/wakeup_reorder {}/{}/{}/{}\n\tchaos excluded/skipped {}/{}\n\tkprobe_random_delays {}\n\ttimer kicks: {}",
             self.trait_random_delays,
             self.trait_cpu_freq,
             self.trait_degradation,
+            self.trait_forced_migration,
+            self.trait_starvation,
+            self.trait_slice_jitter,
+            self.trait_wakeup_reorder,
             self.chaos_excluded,
             self.chaos_skipped,
             self.kprobe_random_delays,
             self.timer_kicks,
         )?;
-        Ok(())
-    }
-
-    fn delta(&self, rhs: &Self) -> Self {
-        Self {
-            trait_random_delays: self.trait_random_delays - rhs.trait_random_delays,
-            trait_cpu_freq: self.trait_cpu_freq - rhs.trait_cpu_freq,
-            trait_degradation: self.trait_degradation - rhs.trait_degradation,
-            chaos_excluded: self.chaos_excluded - rhs.chaos_excluded,
-            chaos_skipped: self.chaos_skipped - rhs.chaos_skipped,
-            kprobe_random_delays: self.kprobe_random_delays - rhs.kprobe_random_delays,
-            timer_kicks: self.timer_kicks - rhs.timer_kicks,
+        if self.decisions_dropped > 0 {
+            writeln!(w, "\tdecision log drops: {}", self.decisions_dropped)?;
         }
+        if !self.phase.is_empty() {
+            writeln!(
+                w,
+                "\tphase: {} ({}) started at {}ms",
+                self.phase_idx, self.phase, self.phase_started_at_ms,
+            )?;
+        }
+        Ok(())
     }
 }
 
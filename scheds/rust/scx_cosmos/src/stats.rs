@@ -15,11 +15,11 @@ use serde::Serialize;
 #[derive(Clone, Debug, Default, Serialize, Deserialize, Stats)]
 #[stat(top)]
 pub struct Metrics {
-    #[stat(desc = "Direct dispatch due to high perf events (migration)")]
+    #[stat(desc = "Direct dispatch due to high perf events (migration)", _counter)]
     pub nr_event_dispatches: u64,
-    #[stat(desc = "Kept on same CPU due to perf sticky threshold")]
+    #[stat(desc = "Kept on same CPU due to perf sticky threshold", _counter)]
     pub nr_ev_sticky_dispatches: u64,
-    #[stat(desc = "Direct dispatch due to GPU affinity")]
+    #[stat(desc = "Direct dispatch due to GPU affinity", _counter)]
     pub nr_gpu_dispatches: u64,
 }
 
@@ -35,14 +35,6 @@ impl Metrics {
         )?;
         Ok(())
     }
-
-    fn delta(&self, rhs: &Self) -> Self {
-        Self {
-            nr_event_dispatches: self.nr_event_dispatches - rhs.nr_event_dispatches,
-            nr_ev_sticky_dispatches: self.nr_ev_sticky_dispatches - rhs.nr_ev_sticky_dispatches,
-            nr_gpu_dispatches: self.nr_gpu_dispatches - rhs.nr_gpu_dispatches,
-        }
-    }
 }
 
 pub fn server_data() -> StatsServerData<(), Metrics> {
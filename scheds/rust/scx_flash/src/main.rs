@@ -12,27 +12,24 @@ pub use bpf_intf::*;
 
 mod stats;
 use std::ffi::c_int;
-use std::fmt::Write;
 use std::mem::MaybeUninit;
 use std::sync::atomic::AtomicBool;
-use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::anyhow;
 use anyhow::bail;
-use anyhow::Context;
 use anyhow::Result;
 use clap::Parser;
-use crossbeam::channel::RecvTimeoutError;
 use libbpf_rs::OpenObject;
 use libbpf_rs::ProgramInput;
-use log::{debug, info, warn};
+use log::{info, warn};
+use scx_runtime::RuntimeOpts;
+use scx_runtime::SchedulerOpts;
 use scx_stats::prelude::*;
 use scx_utils::autopower::{fetch_power_profile, PowerProfile};
 use scx_utils::build_id;
 use scx_utils::compat;
-use scx_utils::libbpf_clap_opts::LibbpfOpts;
 use scx_utils::pm::{cpu_idle_resume_latency_supported, update_cpu_idle_resume_latency};
 use scx_utils::scx_ops_attach;
 use scx_utils::scx_ops_load;
@@ -40,7 +37,7 @@ use scx_utils::scx_ops_open;
 use scx_utils::try_set_rlimit_infinity;
 use scx_utils::uei_exited;
 use scx_utils::uei_report;
-use scx_utils::CoreType;
+use scx_utils::CpuDomain;
 use scx_utils::Cpumask;
 use scx_utils::Topology;
 use scx_utils::UserExitInfo;
@@ -49,64 +46,6 @@ use stats::Metrics;
 
 const SCHEDULER_NAME: &str = "scx_flash";
 
-#[derive(PartialEq)]
-enum Powermode {
-    Turbo,
-    Performance,
-    Powersave,
-    Any,
-}
-
-fn get_primary_cpus(mode: Powermode) -> std::io::Result<Vec<usize>> {
-    let topo = Topology::new().unwrap();
-
-    let cpus: Vec<usize> = topo
-        .all_cores
-        .values()
-        .flat_map(|core| &core.cpus)
-        .filter_map(|(cpu_id, cpu)| match (&mode, &cpu.core_type) {
-            // Turbo mode: prioritize CPUs with the highest max frequency
-            (Powermode::Turbo, CoreType::Big { turbo: true }) |
-            // Performance mode: add all the Big CPUs (either Turbo or non-Turbo)
-            (Powermode::Performance, CoreType::Big { .. }) |
-            // Powersave mode: add all the Little CPUs
-            (Powermode::Powersave, CoreType::Little) => Some(*cpu_id),
-            (Powermode::Any, ..) => Some(*cpu_id),
-            _ => None,
-        })
-        .collect();
-
-    Ok(cpus)
-}
-
-// Convert an array of CPUs to the corresponding cpumask of any arbitrary size.
-fn cpus_to_cpumask(cpus: &Vec<usize>) -> String {
-    if cpus.is_empty() {
-        return String::from("none");
-    }
-
-    // Determine the maximum CPU ID to create a sufficiently large byte vector.
-    let max_cpu_id = *cpus.iter().max().unwrap();
-
-    // Create a byte vector with enough bytes to cover all CPU IDs.
-    let mut bitmask = vec![0u8; (max_cpu_id + 1 + 7) / 8];
-
-    // Set the appropriate bits for each CPU ID.
-    for cpu_id in cpus {
-        let byte_index = cpu_id / 8;
-        let bit_index = cpu_id % 8;
-        bitmask[byte_index] |= 1 << bit_index;
-    }
-
-    // Convert the byte vector to a hexadecimal string.
-    let hex_str: String = bitmask.iter().rev().fold(String::new(), |mut f, byte| {
-        let _ = write!(&mut f, "{:02x}", byte);
-        f
-    });
-
-    format!("0x{}", hex_str)
-}
-
 #[derive(Debug, clap::Parser)]
 #[command(
     name = "scx_flash",
@@ -149,10 +88,6 @@ saturated or overcommitted.
 "#
 )]
 struct Opts {
-    /// Exit debug dump buffer length. 0 indicates default.
-    #[clap(long, default_value = "0")]
-    exit_dump_len: u32,
-
     /// Maximum scheduling slice duration in microseconds.
     #[clap(short = 's', long, default_value = "700")]
     slice_us: u64,
@@ -194,20 +129,6 @@ struct Opts {
     #[clap(short = 'R', long, action = clap::ArgAction::SetTrue)]
     rr_sched: bool,
 
-    /// Specifies the initial set of CPUs, represented as a bitmask in hex (e.g., 0xff), that the
-    /// scheduler will use to dispatch tasks, until the system becomes saturated, at which point
-    /// tasks may overflow to other available CPUs.
-    ///
-    /// Special values:
-    ///  - "auto" = automatically detect the CPUs based on the active power profile
-    ///  - "turbo" = automatically detect and prioritize the CPUs with the highest max frequency
-    ///  - "performance" = automatically detect and prioritize the fastest CPUs
-    ///  - "powersave" = automatically detect and prioritize the slowest CPUs
-    ///  - "all" = all CPUs assigned to the primary domain
-    ///  - "none" = no prioritization, tasks are dispatched on the first CPU available
-    #[clap(short = 'm', long, default_value = "auto")]
-    primary_domain: String,
-
     /// Disable SMT awareness.
     #[clap(long, action = clap::ArgAction::SetTrue)]
     disable_smt: bool,
@@ -222,33 +143,12 @@ struct Opts {
     #[clap(short = 'f', long, action = clap::ArgAction::SetTrue)]
     cpufreq: bool,
 
-    /// Enable stats monitoring with the specified interval.
-    #[clap(long)]
-    stats: Option<f64>,
-
-    /// Run in stats monitoring mode with the specified interval. Scheduler
-    /// is not launched.
-    #[clap(long)]
-    monitor: Option<f64>,
-
     /// Enable BPF debugging via /sys/kernel/tracing/trace_pipe.
     #[clap(short = 'd', long, action = clap::ArgAction::SetTrue)]
     debug: bool,
 
-    /// Enable verbose output, including libbpf details.
-    #[clap(short = 'v', long, action = clap::ArgAction::SetTrue)]
-    verbose: bool,
-
-    /// Print scheduler version and exit.
-    #[clap(short = 'V', long, action = clap::ArgAction::SetTrue)]
-    version: bool,
-
-    /// Show descriptions for statistics.
-    #[clap(long)]
-    help_stats: bool,
-
-    #[clap(flatten, next_help_heading = "Libbpf Options")]
-    pub libbpf: LibbpfOpts,
+    #[clap(flatten)]
+    runtime: RuntimeOpts,
 }
 
 struct Scheduler<'a> {
@@ -257,8 +157,6 @@ struct Scheduler<'a> {
     opts: &'a Opts,
     topo: Topology,
     power_profile: PowerProfile,
-    stats_server: StatsServer<(), Metrics>,
-    user_restart: bool,
 }
 
 impl<'a> Scheduler<'a> {
@@ -313,23 +211,24 @@ impl<'a> Scheduler<'a> {
         }
 
         // Determine the primary scheduling domain.
+        let primary_domain = opts.runtime.primary_domain_or("auto");
         let power_profile = Self::power_profile();
         let domain =
-            Self::resolve_energy_domain(&opts.primary_domain, power_profile).map_err(|err| {
+            Self::resolve_energy_domain(&topo, primary_domain, power_profile).map_err(|err| {
                 anyhow!(
                     "failed to resolve primary domain '{}': {}",
-                    &opts.primary_domain,
+                    primary_domain,
                     err
                 )
             })?;
 
         // Initialize BPF connector.
         let mut skel_builder = BpfSkelBuilder::default();
-        skel_builder.obj_builder.debug(opts.verbose);
-        let open_opts = opts.libbpf.clone().into_bpf_open_opts();
+        skel_builder.obj_builder.debug(opts.runtime.verbose);
+        let open_opts = opts.runtime.libbpf.clone().into_bpf_open_opts();
         let mut skel = scx_ops_open!(skel_builder, open_object, flash_ops, open_opts)?;
 
-        skel.struct_ops.flash_ops_mut().exit_dump_len = opts.exit_dump_len;
+        skel.struct_ops.flash_ops_mut().exit_dump_len = opts.runtime.exit_dump_len;
 
         // Override default BPF scheduling parameters.
         let rodata = skel.maps.rodata_data.as_mut().unwrap();
@@ -370,7 +269,7 @@ impl<'a> Scheduler<'a> {
             )
         })?;
 
-        if let Err(err) = Self::init_cpufreq_perf(&mut skel, &opts.primary_domain, opts.cpufreq) {
+        if let Err(err) = Self::init_cpufreq_perf(&mut skel, primary_domain, opts.cpufreq) {
             bail!(
                 "failed to initialize cpufreq performance level: error {}",
                 err
@@ -382,18 +281,12 @@ impl<'a> Scheduler<'a> {
             Self::init_smt_domains(&mut skel, &topo)?;
         }
 
-        // Attach the scheduler.
-        let struct_ops = Some(scx_ops_attach!(skel, flash_ops)?);
-        let stats_server = StatsServer::new(stats::server_data()).launch()?;
-
         Ok(Self {
             skel,
-            struct_ops,
+            struct_ops: None,
             opts,
             topo,
             power_profile,
-            stats_server,
-            user_restart: false,
         })
     }
 
@@ -419,31 +312,28 @@ impl<'a> Scheduler<'a> {
         Ok(())
     }
 
-    fn epp_to_cpumask(profile: Powermode) -> Result<Cpumask> {
-        let mut cpus = get_primary_cpus(profile).unwrap_or_default();
-        if cpus.is_empty() {
-            cpus = get_primary_cpus(Powermode::Any).unwrap_or_default();
-        }
-        Cpumask::from_str(&cpus_to_cpumask(&cpus))
-    }
-
-    fn resolve_energy_domain(primary_domain: &str, power_profile: PowerProfile) -> Result<Cpumask> {
-        let domain = match primary_domain {
-            "powersave" => Self::epp_to_cpumask(Powermode::Powersave)?,
-            "performance" => Self::epp_to_cpumask(Powermode::Performance)?,
-            "turbo" => Self::epp_to_cpumask(Powermode::Turbo)?,
+    fn resolve_energy_domain(
+        topo: &Topology,
+        primary_domain: &str,
+        power_profile: PowerProfile,
+    ) -> Result<Cpumask> {
+        let expr = match primary_domain {
             "auto" => match power_profile {
-                PowerProfile::Powersave => Self::epp_to_cpumask(Powermode::Powersave)?,
-                PowerProfile::Balanced { power: true } => {
-                    Self::epp_to_cpumask(Powermode::Powersave)?
-                }
+                PowerProfile::Powersave | PowerProfile::Balanced { power: true } => "powersave",
                 PowerProfile::Balanced { power: false }
                 | PowerProfile::Performance
-                | PowerProfile::Unknown => Self::epp_to_cpumask(Powermode::Any)?,
+                | PowerProfile::Unknown => "all",
             },
-            "all" => Self::epp_to_cpumask(Powermode::Any)?,
-            &_ => Cpumask::from_str(primary_domain)?,
+            expr => expr,
         };
+        let domain = expr.parse::<CpuDomain>()?.eval(topo)?;
+
+        // Fall back to all the CPUs if a power profile keyword (or "auto") doesn't match any CPU,
+        // e.g., "powersave" on a system without little cores. Other expressions are used as is,
+        // so an empty cpumask like 0x0 is an empty domain, the same as "none".
+        if domain.is_empty() && matches!(expr, "performance" | "powersave") {
+            return Ok(topo.span.clone());
+        }
 
         Ok(domain)
     }
@@ -469,14 +359,10 @@ impl<'a> Scheduler<'a> {
     }
 
     // Update hint for the cpufreq governor.
-    fn init_cpufreq_perf(
-        skel: &mut BpfSkel<'_>,
-        primary_domain: &String,
-        auto: bool,
-    ) -> Result<()> {
+    fn init_cpufreq_perf(skel: &mut BpfSkel<'_>, primary_domain: &str, auto: bool) -> Result<()> {
         // If we are using the powersave profile always scale the CPU frequency to the minimum,
         // otherwise use the maximum, unless automatic frequency scaling is enabled.
-        let perf_lvl: i64 = match primary_domain.as_str() {
+        let perf_lvl: i64 = match primary_domain {
             "powersave" => 0,
             _ if auto => -1,
             _ => 1024,
@@ -510,14 +396,13 @@ impl<'a> Scheduler<'a> {
             if power_profile != self.power_profile {
                 self.power_profile = power_profile;
 
-                if self.opts.primary_domain == "auto" {
+                let primary_domain = self.opts.runtime.primary_domain_or("auto");
+                if primary_domain == "auto" {
                     return true;
                 }
-                if let Err(err) = Self::init_cpufreq_perf(
-                    &mut self.skel,
-                    &self.opts.primary_domain,
-                    self.opts.cpufreq,
-                ) {
+                if let Err(err) =
+                    Self::init_cpufreq_perf(&mut self.skel, primary_domain, self.opts.cpufreq)
+                {
                     warn!("failed to refresh cpufreq performance level: error {}", err);
                 }
             }
@@ -576,27 +461,29 @@ impl<'a> Scheduler<'a> {
             nr_shared_dispatches: bss_data.nr_shared_dispatches,
         }
     }
+}
 
-    pub fn exited(&mut self) -> bool {
-        uei_exited!(&self.skel, uei)
+impl scx_runtime::Scheduler for Scheduler<'_> {
+    type Metrics = Metrics;
+
+    fn attach(&mut self) -> Result<()> {
+        self.struct_ops = Some(scx_ops_attach!(self.skel, flash_ops)?);
+        Ok(())
     }
 
-    fn run(&mut self, shutdown: Arc<AtomicBool>) -> Result<UserExitInfo> {
-        let (res_ch, req_ch) = self.stats_server.channels();
+    fn exited(&mut self) -> bool {
+        uei_exited!(&self.skel, uei)
+    }
 
-        while !shutdown.load(Ordering::Relaxed) && !self.exited() {
-            if self.refresh_sched_domain() {
-                self.user_restart = true;
-                break;
-            }
+    fn on_interval(&mut self) -> Result<bool> {
+        Ok(self.refresh_sched_domain())
+    }
 
-            match req_ch.recv_timeout(Duration::from_secs(1)) {
-                Ok(()) => res_ch.send(self.get_metrics())?,
-                Err(RecvTimeoutError::Timeout) => {}
-                Err(e) => Err(e)?,
-            }
-        }
+    fn metrics(&mut self) -> Metrics {
+        self.get_metrics()
+    }
 
+    fn exit_report(&mut self) -> Result<UserExitInfo> {
         let _ = self.struct_ops.take();
         uei_report!(&self.skel, uei)
     }
@@ -604,8 +491,6 @@ impl<'a> Scheduler<'a> {
 
 impl Drop for Scheduler<'_> {
     fn drop(&mut self) {
-        info!("Unregister {SCHEDULER_NAME} scheduler");
-
         // Restore default CPU idle QoS resume latency.
         if self.opts.idle_resume_us >= 0 {
             if cpu_idle_resume_latency_supported() {
@@ -618,77 +503,29 @@ impl Drop for Scheduler<'_> {
     }
 }
 
-fn main() -> Result<()> {
-    let opts = Opts::parse();
+impl SchedulerOpts for Opts {
+    const NAME: &'static str = SCHEDULER_NAME;
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+    type Metrics = Metrics;
+    type Sched<'a> = Scheduler<'a>;
 
-    if opts.version {
-        println!(
-            "{} {}",
-            SCHEDULER_NAME,
-            build_id::full_version(env!("CARGO_PKG_VERSION"))
-        );
-        return Ok(());
+    fn runtime(&self) -> &RuntimeOpts {
+        &self.runtime
     }
 
-    if opts.help_stats {
-        stats::server_data().describe_meta(&mut std::io::stdout(), None)?;
-        return Ok(());
+    fn init<'a>(&'a self, open_object: &'a mut MaybeUninit<OpenObject>) -> Result<Scheduler<'a>> {
+        Scheduler::init(self, open_object)
     }
 
-    let loglevel = simplelog::LevelFilter::Info;
-
-    let mut lcfg = simplelog::ConfigBuilder::new();
-    lcfg.set_time_offset_to_local()
-        .expect("Failed to set local time offset")
-        .set_time_level(simplelog::LevelFilter::Error)
-        .set_location_level(simplelog::LevelFilter::Off)
-        .set_target_level(simplelog::LevelFilter::Off)
-        .set_thread_level(simplelog::LevelFilter::Off);
-    simplelog::TermLogger::init(
-        loglevel,
-        lcfg.build(),
-        simplelog::TerminalMode::Stderr,
-        simplelog::ColorChoice::Auto,
-    )?;
-
-    let shutdown = Arc::new(AtomicBool::new(false));
-    let shutdown_clone = shutdown.clone();
-    ctrlc::set_handler(move || {
-        shutdown_clone.store(true, Ordering::Relaxed);
-    })
-    .context("Error setting Ctrl-C handler")?;
-
-    if let Some(intv) = opts.monitor.or(opts.stats) {
-        let shutdown_copy = shutdown.clone();
-        let jh = std::thread::spawn(move || {
-            match stats::monitor(Duration::from_secs_f64(intv), shutdown_copy) {
-                Ok(_) => {
-                    debug!("stats monitor thread finished successfully")
-                }
-                Err(error_object) => {
-                    warn!(
-                        "stats monitor thread finished because of an error {}",
-                        error_object
-                    )
-                }
-            }
-        });
-        if opts.monitor.is_some() {
-            let _ = jh.join();
-            return Ok(());
-        }
+    fn stats_server_data() -> StatsServerData<(), Metrics> {
+        stats::server_data()
     }
 
-    let mut open_object = MaybeUninit::uninit();
-    loop {
-        let mut sched = Scheduler::init(&opts, &mut open_object)?;
-        if !sched.run(shutdown.clone())?.should_restart() {
-            if sched.user_restart {
-                continue;
-            }
-            break;
-        }
+    fn monitor(intv: Duration, shutdown: Arc<AtomicBool>) -> Result<()> {
+        stats::monitor(intv, shutdown)
     }
+}
 
-    Ok(())
+fn main() -> Result<()> {
+    scx_runtime::run(&Opts::parse())
 }
@@ -15,15 +15,15 @@ use serde::Serialize;
 #[derive(Clone, Debug, Default, Serialize, Deserialize, Stats)]
 #[stat(top)]
 pub struct Metrics {
-    #[stat(desc = "Number of running tasks")]
+    #[stat(desc = "Number of running tasks", _gauge)]
     pub nr_running: u64,
-    #[stat(desc = "Number of online CPUs")]
+    #[stat(desc = "Number of online CPUs", _gauge)]
     pub nr_cpus: u64,
-    #[stat(desc = "Number of kthread direct dispatches")]
+    #[stat(desc = "Number of kthread direct dispatches", _counter)]
     pub nr_kthread_dispatches: u64,
-    #[stat(desc = "Number of task direct dispatches")]
+    #[stat(desc = "Number of task direct dispatches", _counter)]
     pub nr_direct_dispatches: u64,
-    #[stat(desc = "Number of regular task dispatches")]
+    #[stat(desc = "Number of regular task dispatches", _counter)]
     pub nr_shared_dispatches: u64,
 }
 
@@ -41,15 +41,6 @@ impl Metrics {
         )?;
         Ok(())
     }
-
-    fn delta(&self, rhs: &Self) -> Self {
-        Self {
-            nr_kthread_dispatches: self.nr_kthread_dispatches - rhs.nr_kthread_dispatches,
-            nr_direct_dispatches: self.nr_direct_dispatches - rhs.nr_direct_dispatches,
-            nr_shared_dispatches: self.nr_shared_dispatches - rhs.nr_shared_dispatches,
-            ..self.clone()
-        }
-    }
 }
 
 pub fn server_data() -> StatsServerData<(), Metrics> {
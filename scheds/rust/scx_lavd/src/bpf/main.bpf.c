@@ -208,10 +208,11 @@ static u64		cur_svc_time_iwgt;
 
 
 /*
- * The minimum and maximum of time slice
+ * The minimum and maximum of time slice. The maximum can be changed at
+ * runtime through the slice_max_us stats tunable.
  */
 const volatile u64	slice_min_ns = LAVD_SLICE_MIN_NS_DFL;
-const volatile u64	slice_max_ns = LAVD_SLICE_MAX_NS_DFL;
+volatile u64		slice_max_ns;
 
 /*
  * Migration delta threshold percentage (0-100)
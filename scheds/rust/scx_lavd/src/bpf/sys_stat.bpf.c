@@ -23,7 +23,7 @@ volatile u64		__weak performance_mode_ns;
 volatile u64		__weak balanced_mode_ns;
 volatile u64		__weak powersave_mode_ns;
 extern const volatile u64	slice_min_ns;
-extern const volatile u64	slice_max_ns;
+extern volatile u64		slice_max_ns;
 extern volatile bool		__weak no_core_compaction;
 extern volatile bool		__weak reinit_cpumask_for_performance;
 const volatile bool	__weak is_autopilot_on;
@@ -6,14 +6,17 @@
 // This software may be used and distributed according to the terms of the
 // GNU General Public License version 2.
 
+use anyhow::bail;
 use anyhow::Result;
 use combinations::Combinations;
 use itertools::iproduct;
 use scx_utils::CoreType;
 use scx_utils::Cpumask;
 use scx_utils::EnergyModel;
+use scx_utils::EnergyModelSource;
 use scx_utils::PerfDomain;
 use scx_utils::PerfState;
+use scx_utils::PowerCurve;
 use scx_utils::Topology;
 use scx_utils::NR_CPU_IDS;
 use std::cell::Cell;
@@ -25,6 +28,11 @@ use std::fmt;
 use std::hash::{Hash, Hasher};
 use tracing::debug;
 
+/// Maximum number of combinations of CPUs of the performance domains for
+/// which the energy model is used. Beyond this, generating the performance
+/// CPU order table takes too long.
+const EM_MAX_PD_COMBINATIONS: usize = 1 << 14;
+
 #[derive(Debug, Clone)]
 pub struct CpuId {
     // - *_adx: an absolute index within a system scope
@@ -143,7 +151,7 @@ impl CpuOrderCtx {
             None => Topology::new()?,
         };
 
-        let em = EnergyModel::new();
+        let em = Self::load_energy_model(&topo);
         let smt_enabled = topo.smt_enabled;
         let has_biglittle = topo.has_little_cores();
         let has_energy_model = em.is_ok();
@@ -160,6 +168,31 @@ impl CpuOrderCtx {
         })
     }
 
+    /// Load the kernel energy model, or synthesize one if the kernel doesn't
+    /// provide it. A synthesized model usually has a performance domain per
+    /// CPU, so its domains are merged per capacity to keep the number of
+    /// combinations of CPUs of the domains small.
+    fn load_energy_model(topo: &Topology) -> Result<EnergyModel> {
+        let em = EnergyModel::with_fallback(topo, &PowerCurve::default())?;
+        let em = match em.source {
+            EnergyModelSource::Synthesized => em.merge_by_capacity(),
+            _ => em,
+        };
+
+        let nr_cmbs = em
+            .perf_doms
+            .values()
+            .fold(1usize, |n, pd| n.saturating_mul(pd.span.weight() + 1));
+        if nr_cmbs > EM_MAX_PD_COMBINATIONS {
+            bail!(
+                "Too many combinations of CPUs in the energy model ({})",
+                nr_cmbs
+            );
+        }
+
+        Ok(em)
+    }
+
     /// Build a CPU preference order based on its optimization target
     fn build_topo_order(&self, prefer_powersave: bool) -> Option<Vec<CpuId>> {
         let mut cpu_ids = Vec::new();
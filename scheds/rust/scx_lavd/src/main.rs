@@ -55,7 +55,6 @@ use scx_utils::scx_ops_open;
 use scx_utils::try_set_rlimit_infinity;
 use scx_utils::uei_exited;
 use scx_utils::uei_report;
-use scx_utils::EnergyModel;
 use scx_utils::TopologyArgs;
 use scx_utils::UserExitInfo;
 use scx_utils::NR_CPU_IDS;
@@ -324,6 +323,12 @@ impl Opts {
             && self.no_core_compaction == false
     }
 
+    /// Lower bound of the slice_max_us tunable. Like --slice-max-us, it
+    /// can't go below --slice-min-us or --pinned-slice-us.
+    fn slice_max_us_min(&self) -> u64 {
+        self.slice_min_us.max(self.pinned_slice_us.unwrap_or(0))
+    }
+
     fn proc(&mut self) -> Option<&mut Self> {
         if !self.autopilot {
             self.autopilot = self.can_autopilot();
@@ -372,7 +377,7 @@ impl Opts {
             self.no_core_compaction = false;
         }
 
-        if !EnergyModel::has_energy_model() || !self.cpu_pref_order.is_empty() {
+        if !self.cpu_pref_order.is_empty() {
             self.no_use_em = true;
             info!("Energy model won't be used for CPU preference order.");
         }
@@ -483,6 +488,9 @@ impl<'a> Scheduler<'a> {
 
         // Initialize CPU topology with CLI arguments
         let order = CpuOrder::new(opts.topology.as_ref()).unwrap();
+        if !order.has_energy_model && !opts.no_use_em {
+            info!("Energy model won't be used for CPU preference order.");
+        }
         Self::init_cpus(&mut skel, &order);
         Self::init_cpdoms(&mut skel, &order);
 
@@ -503,7 +511,12 @@ impl<'a> Scheduler<'a> {
 
         // Attach.
         let struct_ops = Some(scx_ops_attach!(skel, lavd_ops)?);
-        let stats_server = StatsServer::new(stats::server_data(*NR_CPU_IDS as u64)).launch()?;
+        let stats_server = StatsServer::new(stats::server_data(
+            *NR_CPU_IDS as u64,
+            opts.slice_max_us,
+            opts.slice_max_us_min(),
+        ))
+        .launch()?;
 
         // Build a ring buffer for instrumentation
         let (intrspc_tx, intrspc_rx) = channel::bounded(65536);
@@ -677,13 +690,13 @@ impl<'a> Scheduler<'a> {
         bss_data.no_core_compaction = opts.no_core_compaction;
         bss_data.no_freq_scaling = opts.no_freq_scaling;
         bss_data.is_powersave_mode = opts.powersave;
+        bss_data.slice_max_ns = opts.slice_max_us * 1000;
         let rodata = skel.maps.rodata_data.as_mut().unwrap();
         rodata.nr_llcs = order.nr_llcs as u64;
         rodata.nr_cpu_ids = *NR_CPU_IDS as u32;
         rodata.is_smt_active = order.smt_enabled;
         rodata.is_autopilot_on = opts.autopilot;
         rodata.verbose = debug_level;
-        rodata.slice_max_ns = opts.slice_max_us * 1000;
         rodata.slice_min_ns = opts.slice_min_us * 1000;
         rodata.pinned_slice_ns = opts.pinned_slice_us.map(|v| v * 1000).unwrap_or(0);
         rodata.preempt_shift = opts.preempt_shift;
@@ -691,7 +704,7 @@ impl<'a> Scheduler<'a> {
         rodata.mig_delta_pct = opts.mig_delta_pct;
         rodata.lb_low_util_wall = ((opts.lb_low_util_pct as u64) << 10) / 100;
         rodata.lb_local_dsq_util_wall = ((opts.lb_local_dsq_util_pct as u64) << 10) / 100;
-        rodata.no_use_em = opts.no_use_em as u8;
+        rodata.no_use_em = (opts.no_use_em || !order.has_energy_model) as u8;
         rodata.no_fast_lb = opts.no_fast_lb as u8;
         rodata.no_wake_sync = opts.no_wake_sync;
         rodata.no_slice_boost = opts.no_slice_boost;
@@ -892,6 +905,11 @@ impl<'a> Scheduler<'a> {
 
                 StatsRes::SchedSamples(SchedSamples { samples })
             }
+            StatsReq::SetSliceMaxUs(slice_max_us) => {
+                info!("Set the maximum time slice to {} us.", slice_max_us);
+                self.skel.maps.bss_data.as_mut().unwrap().slice_max_ns = slice_max_us * 1000;
+                StatsRes::Ack
+            }
         })
     }
 
@@ -1043,7 +1061,8 @@ fn main(mut opts: Opts) -> Result<()> {
             sys_stats_meta_name.as_str(),
             sched_sample_meta_name.as_str(),
         ];
-        stats::server_data(0).describe_meta(&mut std::io::stdout(), Some(&stats_meta_names))?;
+        stats::server_data(0, opts.slice_max_us, opts.slice_max_us_min())
+            .describe_meta(&mut std::io::stdout(), Some(&stats_meta_names))?;
         return Ok(());
     }
 
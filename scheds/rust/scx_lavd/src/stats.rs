@@ -6,6 +6,7 @@ use std::sync::Arc;
 use std::thread::ThreadId;
 use std::time::Duration;
 
+use anyhow::anyhow;
 use anyhow::bail;
 use anyhow::{Context, Result};
 use gpoint::GPoint;
@@ -15,6 +16,9 @@ use scx_stats_derive::Stats;
 use serde::Deserialize;
 use serde::Serialize;
 
+// The BPF side periodically decays its counters, e.g. nr_sched, instead of
+// accumulating them. Mark the fields as gauges so that deltas and OpenMetrics
+// exports report them as-is.
 #[stat_doc]
 #[derive(Clone, Debug, Default, Serialize, Deserialize, Stats)]
 #[stat(top)]
@@ -22,49 +26,52 @@ pub struct SysStats {
     #[stat(desc = "Sequence ID of this message")]
     pub mseq: u64,
 
-    #[stat(desc = "Number of runnable tasks in runqueues")]
+    #[stat(desc = "Number of runnable tasks in runqueues", _gauge)]
     pub nr_queued_task: u64,
 
-    #[stat(desc = "Number of active CPUs when core compaction is enabled")]
+    #[stat(desc = "Number of active CPUs when core compaction is enabled", _gauge)]
     pub nr_active: u32,
 
-    #[stat(desc = "Number of context switches")]
+    #[stat(desc = "Number of context switches", _gauge)]
     pub nr_sched: u64,
 
-    #[stat(desc = "Number of task preemption triggered")]
+    #[stat(desc = "Number of task preemption triggered", _gauge)]
     pub nr_preempt: u64,
 
-    #[stat(desc = "% of performance-critical tasks")]
+    #[stat(desc = "% of performance-critical tasks", _gauge)]
     pub pc_pc: f64,
 
-    #[stat(desc = "% of latency-critical tasks")]
+    #[stat(desc = "% of latency-critical tasks", _gauge)]
     pub pc_lc: f64,
 
-    #[stat(desc = "% of cross domain task migration")]
+    #[stat(desc = "% of cross domain task migration", _gauge)]
     pub pc_x_migration: f64,
 
-    #[stat(desc = "Number of stealee domains")]
+    #[stat(desc = "Number of stealee domains", _gauge)]
     pub nr_stealee: u32,
 
-    #[stat(desc = "% of tasks scheduled on big cores")]
+    #[stat(desc = "% of tasks scheduled on big cores", _gauge)]
     pub pc_big: f64,
 
-    #[stat(desc = "% of performance-critical tasks scheduled on big cores")]
+    #[stat(
+        desc = "% of performance-critical tasks scheduled on big cores",
+        _gauge
+    )]
     pub pc_pc_on_big: f64,
 
-    #[stat(desc = "% of latency-critical tasks scheduled on big cores")]
+    #[stat(desc = "% of latency-critical tasks scheduled on big cores", _gauge)]
     pub pc_lc_on_big: f64,
 
     #[stat(desc = "Current power mode")]
     pub power_mode: String,
 
-    #[stat(desc = "% of performance mode")]
+    #[stat(desc = "% of performance mode", _gauge)]
     pub pc_performance: f64,
 
-    #[stat(desc = "% of balanced mode")]
+    #[stat(desc = "% of balanced mode", _gauge)]
     pub pc_balanced: f64,
 
-    #[stat(desc = "% of powersave mode")]
+    #[stat(desc = "% of powersave mode", _gauge)]
     pub pc_powersave: f64,
 }
 
@@ -320,6 +327,7 @@ pub enum StatsReq {
         nr_samples: u64,
         interval_ms: u64,
     },
+    SetSliceMaxUs(u64),
 }
 
 impl StatsReq {
@@ -361,7 +369,16 @@ pub enum StatsRes {
     SchedSamples(SchedSamples),
 }
 
-pub fn server_data(nr_cpus_onln: u64) -> StatsServerData<StatsReq, StatsRes> {
+/// Upper bound of the slice_max_us tunable.
+const SLICE_US_MAX: u64 = 1_000_000;
+
+/// Stats server data. The "slice_max_us" tunable starts at @slice_max_us and
+/// can't go below @slice_max_us_min.
+pub fn server_data(
+    nr_cpus_onln: u64,
+    slice_max_us: u64,
+    slice_max_us_min: u64,
+) -> StatsServerData<StatsReq, StatsRes> {
     let open: Box<dyn StatsOpener<StatsReq, StatsRes>> = Box::new(move |(req_ch, res_ch)| {
         let tid = std::thread::current().id();
         req_ch.send(StatsReq::NewSampler(tid))?;
@@ -411,6 +428,10 @@ pub fn server_data(nr_cpus_onln: u64) -> StatsServerData<StatsReq, StatsRes> {
             Ok(read)
         });
 
+    let slice_max_us_meta = StatsTunableMeta::new(StatsKind::U64, slice_max_us.into())
+        .desc("Maximum scheduling slice duration in microseconds")
+        .range(slice_max_us_min as f64, SLICE_US_MAX as f64);
+
     StatsServerData::new()
         .add_meta(SysStats::meta())
         .add_ops("top", StatsOps { open, close: None })
@@ -422,6 +443,20 @@ pub fn server_data(nr_cpus_onln: u64) -> StatsServerData<StatsReq, StatsRes> {
                 close: None,
             },
         )
+        .add_tunable(
+            "slice_max_us",
+            slice_max_us_meta,
+            Box::new(move |val, (req_ch, res_ch)| {
+                let val = val
+                    .as_u64()
+                    .ok_or_else(|| anyhow!("invalid slice_max_us {}", val))?;
+                req_ch.send(StatsReq::SetSliceMaxUs(val))?;
+                match res_ch.recv()? {
+                    StatsRes::Ack => Ok(()),
+                    res => bail!("invalid response: {:?}", res),
+                }
+            }),
+        )
 }
 
 pub fn monitor_sched_samples(nr_samples: u64, shutdown: Arc<AtomicBool>) -> Result<()> {
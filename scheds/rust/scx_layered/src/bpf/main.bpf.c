@@ -61,6 +61,11 @@ const volatile bool percpu_kthread_preempt = true;
 const volatile bool percpu_kthread_preempt_all = false;
 const volatile u64 membw_event = 0;
 volatile u64 layer_refresh_seq_avgruntime;
+/*
+ * Bumped by userspace when layer configs are reloaded. Odd while the new
+ * configs are being copied into layers[].
+ */
+volatile u64 layer_config_seq;
 
 /* Flag to enable or disable antistall feature */
 const volatile bool enable_antistall = true;
@@ -709,6 +714,7 @@ struct task_ctx {
 
 	char 			join_layer[SCXCMD_COMLEN];
 	u64			layer_refresh_seq;
+	u64			layer_config_seq;
 
 	u64			recheck_layer_membership;
 };
@@ -3290,12 +3296,21 @@ static void switch_to_layer(struct task_struct *p, struct task_ctx *taskc, u64 l
 static void maybe_refresh_layer(struct task_struct *p __arg_trusted, struct task_ctx *taskc, u64 now)
 {
 	const char *cgrp_path;
-	bool matched = false;
+	bool matched = false, torn;
 	u64 layer_id;	// XXX - int makes verifier unhappy
+	u64 config_seq;
 
 	if (!taskc->refresh_layer)
 		return;
 
+	/*
+	 * Layer configs are being reloaded, retry once they are published
+	 * unless the task still needs its initial assignment.
+	 */
+	config_seq = READ_ONCE(layer_config_seq);
+	if ((config_seq & 1) && taskc->layer_id != MAX_LAYERS)
+		return;
+
 	/*
 	 * If cgroup regex matching is configured, check if the cgroup bitmap
 	 * entry is ready. If not, return without clearing refresh_layer so we
@@ -3329,9 +3344,22 @@ static void maybe_refresh_layer(struct task_struct *p __arg_trusted, struct task
 		}
 	}
 
+	/*
+	 * The matches may have been evaluated against partially copied
+	 * configs, keep the result for now and evaluate them again later.
+	 */
+	torn = (config_seq & 1) || READ_ONCE(layer_config_seq) != config_seq;
+	if (torn) {
+		taskc->refresh_layer = true;
+		if (!matched && taskc->layer_id == MAX_LAYERS) {
+			layer_id = nr_layers - 1;
+			matched = true;
+		}
+	}
+
 	if (matched) {
 		switch_to_layer(p, taskc, layer_id, now);
-	} else {
+	} else if (!torn) {
 		scx_bpf_error("[%s]%d didn't match any layer", p->comm, p->pid);
 	}
 
@@ -3514,6 +3542,12 @@ void BPF_STRUCT_OPS(layered_runnable, struct task_struct *p, u64 enq_flags)
 		return;
 
 	taskc->runnable_at = now;
+
+	/* layer configs were reloaded, re-evaluate the matches */
+	if (taskc->layer_config_seq != layer_config_seq) {
+		taskc->layer_config_seq = layer_config_seq;
+		taskc->refresh_layer = true;
+	}
 	maybe_refresh_layer(p, taskc, now);
 
 	if (enq_flags & SCX_ENQ_WAKEUP)
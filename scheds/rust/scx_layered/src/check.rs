@@ -0,0 +1,762 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+//! Offline validation of layer specs for `scx_layered check`.
+//!
+//! Layer matches are evaluated in userspace against a snapshot of the tasks
+//! in the system following the same rules as match_layer() in BPF. Matches
+//! which depend on runtime state, e.g. AvgRuntime or task hints, can't be
+//! decided offline and make the resulting assignment uncertain.
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use regex::Regex;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::LayerKind;
+use crate::LayerMatch;
+use crate::LayerSpec;
+
+const PF_KTHREAD: u64 = 0x00200000;
+
+/// The attributes of a task which layer matches look at.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TaskSnapshot {
+    pub tid: u32,
+    pub tgid: u32,
+    pub ppid: u32,
+    pub comm: String,
+    /// comm of the thread group leader.
+    pub pcomm: String,
+    /// cgroup2 path as shown in /proc/PID/cgroup, e.g. "/system.slice/foo.service".
+    pub cgroup: String,
+    pub nice: i32,
+    pub euid: u32,
+    pub egid: u32,
+    /// Inode number of the task's PID namespace.
+    pub pid_ns: Option<u64>,
+    /// TID inside @pid_ns.
+    pub ns_tid: Option<u32>,
+    pub is_kthread: bool,
+}
+
+impl TaskSnapshot {
+    /// The cgroup path as formatted by format_cgrp_path() in BPF, relative
+    /// to the root and terminated with '/'.
+    pub fn cgrp_path(&self) -> String {
+        match self.cgroup.trim_matches('/') {
+            "" => "/".into(),
+            path => format!("{}/", path),
+        }
+    }
+
+    /// The cgroup path the cgroup watcher tests CgroupRegex rules against.
+    fn cgrp_fs_path(&self) -> String {
+        format!("/sys/fs/cgroup{}", self.cgroup.trim_end_matches('/'))
+    }
+
+    fn read(task_dir: &Path, tgid: u32, pcomm: &str) -> Result<Self> {
+        let tid = task_dir
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.parse::<u32>().ok())
+            .ok_or_else(|| anyhow!("invalid task dir {:?}", task_dir))?;
+        let (comm, ppid, flags, nice) = parse_stat(&fs::read_to_string(task_dir.join("stat"))?)?;
+        let (euid, egid, ns_tid) = parse_status(&fs::read_to_string(task_dir.join("status"))?)?;
+        let cgroup = fs::read_to_string(task_dir.join("cgroup"))?
+            .lines()
+            .find_map(|line| line.strip_prefix("0::"))
+            .unwrap_or("/")
+            .to_string();
+        let pid_ns = fs::read_link(task_dir.join("ns/pid"))
+            .ok()
+            .and_then(|link| parse_ns_link(&link.to_string_lossy()));
+
+        Ok(Self {
+            tid,
+            tgid,
+            ppid,
+            pcomm: if tid == tgid {
+                comm.clone()
+            } else {
+                pcomm.into()
+            },
+            comm,
+            cgroup,
+            nice,
+            euid,
+            egid,
+            pid_ns,
+            ns_tid,
+            is_kthread: flags & PF_KTHREAD != 0,
+        })
+    }
+}
+
+/// Parse /proc/PID/stat into (comm, ppid, flags, nice).
+fn parse_stat(stat: &str) -> Result<(String, u32, u64, i32)> {
+    let (open, close) = match (stat.find('('), stat.rfind(')')) {
+        (Some(open), Some(close)) if open < close => (open, close),
+        _ => return Err(anyhow!("malformed stat {:?}", stat)),
+    };
+    let comm = stat[open + 1..close].to_string();
+    // Fields after comm, starting from state (3).
+    let fields: Vec<&str> = stat[close + 1..].split_whitespace().collect();
+    let field = |nr: usize| {
+        fields
+            .get(nr - 3)
+            .copied()
+            .ok_or_else(|| anyhow!("stat field {} missing", nr))
+    };
+    Ok((
+        comm,
+        field(4)?.parse()?,
+        field(9)?.parse()?,
+        field(19)?.parse()?,
+    ))
+}
+
+/// Parse /proc/PID/status into (euid, egid, TID in the innermost PID
+/// namespace).
+fn parse_status(status: &str) -> Result<(u32, u32, Option<u32>)> {
+    let mut euid = None;
+    let mut egid = None;
+    let mut ns_tid = None;
+    for line in status.lines() {
+        let Some((key, val)) = line.split_once(':') else {
+            continue;
+        };
+        let mut vals = val.split_whitespace();
+        match key {
+            "Uid" => euid = vals.nth(1).map(str::parse).transpose()?,
+            "Gid" => egid = vals.nth(1).map(str::parse).transpose()?,
+            "NSpid" => ns_tid = vals.last().map(str::parse).transpose()?,
+            _ => {}
+        }
+    }
+    Ok((
+        euid.context("Uid missing in status")?,
+        egid.context("Gid missing in status")?,
+        ns_tid,
+    ))
+}
+
+/// Parse the inode number out of a namespace link, e.g. "pid:[4026531836]".
+fn parse_ns_link(link: &str) -> Option<u64> {
+    link.split_once('[')?.1.strip_suffix(']')?.parse().ok()
+}
+
+/// Capture the tasks in the system from /proc. Only thread group leaders
+/// are captured unless @threads is set.
+pub fn capture_tasks(threads: bool) -> Result<Vec<TaskSnapshot>> {
+    let mut tasks = vec![];
+    for entry in fs::read_dir("/proc").context("Failed to read /proc")? {
+        let entry = entry?;
+        let Some(tgid) = entry.file_name().to_str().and_then(|n| n.parse().ok()) else {
+            continue;
+        };
+
+        // Tasks may exit while we're walking /proc, skip those.
+        let Ok(leader) = TaskSnapshot::read(&entry.path(), tgid, "") else {
+            continue;
+        };
+        if !threads {
+            tasks.push(leader);
+            continue;
+        }
+
+        let Ok(task_dirs) = fs::read_dir(entry.path().join("task")) else {
+            continue;
+        };
+        for task_dir in task_dirs.flatten() {
+            if let Ok(task) = TaskSnapshot::read(&task_dir.path(), tgid, &leader.comm) {
+                tasks.push(task);
+            }
+        }
+    }
+    tasks.sort_by_key(|task| task.tid);
+    Ok(tasks)
+}
+
+/// The outcome of evaluating the layer matches against a task.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Assignment {
+    /// The first layer whose matches are known to succeed.
+    pub layer: Option<usize>,
+    /// Earlier layers whose matches couldn't be decided offline. The task
+    /// may land in one of these instead.
+    pub maybe: Vec<usize>,
+}
+
+/// Evaluates layer matches against task snapshots.
+pub struct LayerMatcher<'a> {
+    specs: &'a [LayerSpec],
+    regexes: HashMap<&'a str, Regex>,
+}
+
+impl<'a> LayerMatcher<'a> {
+    pub fn new(specs: &'a [LayerSpec]) -> Result<Self> {
+        let mut regexes = HashMap::new();
+        for spec in specs.iter() {
+            for mt in spec.matches.iter().flatten() {
+                if let LayerMatch::CgroupRegex(expr) = mt {
+                    let regex = Regex::new(expr).with_context(|| {
+                        format!("Invalid regex '{}' in layer '{}'", expr, spec.name)
+                    })?;
+                    regexes.insert(expr.as_str(), regex);
+                }
+            }
+        }
+        Ok(Self { specs, regexes })
+    }
+
+    /// Evaluate @mt against @task. None if it can't be decided offline.
+    fn match_one(&self, mt: &LayerMatch, task: &TaskSnapshot, cgrp_path: &str) -> Option<bool> {
+        Some(match mt {
+            LayerMatch::CgroupPrefix(prefix) => cgrp_path.starts_with(prefix.as_str()),
+            LayerMatch::CgroupSuffix(suffix) => cgrp_path.ends_with(suffix.as_str()),
+            LayerMatch::CgroupContains(substr) => cgrp_path.contains(substr.as_str()),
+            LayerMatch::CgroupRegex(expr) => {
+                self.regexes[expr.as_str()].is_match(&task.cgrp_fs_path())
+            }
+            LayerMatch::CommPrefix(prefix) => task.comm.starts_with(prefix.as_str()),
+            LayerMatch::CommPrefixExclude(prefix) => !task.comm.starts_with(prefix.as_str()),
+            LayerMatch::PcommPrefix(prefix) => task.pcomm.starts_with(prefix.as_str()),
+            LayerMatch::PcommPrefixExclude(prefix) => !task.pcomm.starts_with(prefix.as_str()),
+            LayerMatch::NiceAbove(nice) => task.nice > *nice,
+            LayerMatch::NiceBelow(nice) => task.nice < *nice,
+            LayerMatch::NiceEquals(nice) => task.nice == *nice,
+            LayerMatch::UIDEquals(user_id) => task.euid == *user_id,
+            LayerMatch::GIDEquals(group_id) => task.egid == *group_id,
+            LayerMatch::PIDEquals(pid) => task.tid == *pid,
+            LayerMatch::PPIDEquals(ppid) => task.ppid == *ppid,
+            LayerMatch::TGIDEquals(tgid) => task.tgid == *tgid,
+            LayerMatch::NSPIDEquals(nsid, pid) => task.pid_ns? == *nsid && task.ns_tid? == *pid,
+            LayerMatch::NSEquals(nsid) => task.pid_ns? == *nsid as u64,
+            LayerMatch::IsGroupLeader(polarity) => (task.tid == task.tgid) == *polarity,
+            // Like the BPF side, this only tests whether the task is a kthread.
+            LayerMatch::IsKthread(_) => task.is_kthread,
+            LayerMatch::CmdJoin(_)
+            | LayerMatch::UsedGpuTid(_)
+            | LayerMatch::UsedGpuPid(_)
+            | LayerMatch::AvgRuntime(_, _)
+            | LayerMatch::HintEquals(_)
+            | LayerMatch::SystemCpuUtilBelow(_)
+            | LayerMatch::DsqInsertBelow(_)
+            | LayerMatch::NumaNode(_) => return None,
+        })
+    }
+
+    fn match_layer(&self, spec: &LayerSpec, task: &TaskSnapshot, cgrp_path: &str) -> Option<bool> {
+        let mut result = Some(false);
+        for ands in spec.matches.iter() {
+            let mut matched = Some(true);
+            for mt in ands.iter() {
+                match self.match_one(mt, task, cgrp_path) {
+                    Some(false) => {
+                        matched = Some(false);
+                        break;
+                    }
+                    None => matched = None,
+                    Some(true) => {}
+                }
+            }
+            match matched {
+                Some(true) => return Some(true),
+                None => result = None,
+                Some(false) => {}
+            }
+        }
+        result
+    }
+
+    /// Find the layer @task would be assigned to.
+    pub fn assign(&self, task: &TaskSnapshot) -> Assignment {
+        let cgrp_path = task.cgrp_path();
+        let mut assignment = Assignment::default();
+        for (idx, spec) in self.specs.iter().enumerate() {
+            match self.match_layer(spec, task, &cgrp_path) {
+                Some(true) => {
+                    assignment.layer = Some(idx);
+                    break;
+                }
+                None => assignment.maybe.push(idx),
+                Some(false) => {}
+            }
+        }
+        assignment
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LintLevel {
+    Warning,
+    Error,
+}
+
+#[derive(Clone, Debug)]
+pub struct Lint {
+    pub level: LintLevel,
+    /// The layer the lint is about, None if it's about the whole config.
+    pub layer: Option<String>,
+    pub msg: String,
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self.level {
+            LintLevel::Warning => "warning",
+            LintLevel::Error => "error",
+        };
+        match &self.layer {
+            Some(layer) => write!(f, "{}: layer {:?}: {}", level, layer, self.msg),
+            None => write!(f, "{}: {}", level, self.msg),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum StrField {
+    Cgroup,
+    Comm,
+    Pcomm,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum StrOp {
+    Prefix,
+    Suffix,
+    Contains,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum IdField {
+    Uid,
+    Gid,
+    Pid,
+    Ppid,
+    Tgid,
+    PidNs,
+    GroupLeader,
+    Kthread,
+}
+
+/// A match normalized for comparison against other matches.
+#[derive(Clone, Debug, PartialEq)]
+enum Cond<'a> {
+    Str(StrField, StrOp, &'a str, bool),
+    Nice(i64, i64),
+    Id(IdField, u64),
+    Opaque(std::mem::Discriminant<LayerMatch>, String),
+}
+
+impl<'a> Cond<'a> {
+    fn new(mt: &'a LayerMatch) -> Self {
+        use Cond::*;
+        use IdField::*;
+        use StrField::*;
+        use StrOp::*;
+
+        match mt {
+            LayerMatch::CgroupPrefix(v) => Str(Cgroup, Prefix, v, false),
+            LayerMatch::CgroupSuffix(v) => Str(Cgroup, Suffix, v, false),
+            LayerMatch::CgroupContains(v) => Str(Cgroup, Contains, v, false),
+            LayerMatch::CommPrefix(v) => Str(Comm, Prefix, v, false),
+            LayerMatch::CommPrefixExclude(v) => Str(Comm, Prefix, v, true),
+            LayerMatch::PcommPrefix(v) => Str(Pcomm, Prefix, v, false),
+            LayerMatch::PcommPrefixExclude(v) => Str(Pcomm, Prefix, v, true),
+            LayerMatch::NiceAbove(v) => Nice(*v as i64 + 1, i64::MAX),
+            LayerMatch::NiceBelow(v) => Nice(i64::MIN, *v as i64 - 1),
+            LayerMatch::NiceEquals(v) => Nice(*v as i64, *v as i64),
+            LayerMatch::UIDEquals(v) => Id(Uid, *v as u64),
+            LayerMatch::GIDEquals(v) => Id(Gid, *v as u64),
+            LayerMatch::PIDEquals(v) => Id(Pid, *v as u64),
+            LayerMatch::PPIDEquals(v) => Id(Ppid, *v as u64),
+            LayerMatch::TGIDEquals(v) => Id(Tgid, *v as u64),
+            LayerMatch::NSEquals(v) => Id(PidNs, *v as u64),
+            LayerMatch::IsGroupLeader(v) => Id(GroupLeader, *v as u64),
+            LayerMatch::IsKthread(_) => Id(Kthread, 1),
+            _ => Opaque(std::mem::discriminant(mt), format!("{:?}", mt)),
+        }
+    }
+
+    /// Whether @self and @other test the same attribute of a task.
+    fn same_field(&self, other: &Cond) -> bool {
+        match (self, other) {
+            (Cond::Str(a, ..), Cond::Str(b, ..)) => a == b,
+            (Cond::Nice(..), Cond::Nice(..)) => true,
+            (Cond::Id(a, _), Cond::Id(b, _)) => a == b,
+            (Cond::Opaque(a, _), Cond::Opaque(b, _)) => a == b,
+            _ => false,
+        }
+    }
+
+    /// Whether every task matching @self also matches @other.
+    fn implies(&self, other: &Cond) -> bool {
+        use StrOp::*;
+
+        if self == other {
+            return true;
+        }
+        match (self, other) {
+            (Cond::Str(fa, opa, a, nega), Cond::Str(fb, opb, b, negb)) if fa == fb => {
+                match (nega, negb, opa, opb) {
+                    (false, false, Prefix, Prefix) => a.starts_with(b),
+                    (false, false, Suffix, Suffix) => a.ends_with(b),
+                    (false, false, _, Contains) => a.contains(b),
+                    (true, true, Prefix, Prefix) => b.starts_with(a),
+                    _ => false,
+                }
+            }
+            (Cond::Nice(alo, ahi), Cond::Nice(blo, bhi)) => blo <= alo && ahi <= bhi,
+            _ => false,
+        }
+    }
+
+    /// Whether no task can match both @self and @other.
+    fn contradicts(&self, other: &Cond) -> bool {
+        use StrOp::*;
+
+        match (self, other) {
+            (Cond::Str(fa, opa, a, nega), Cond::Str(fb, opb, b, negb)) if fa == fb => {
+                match (nega, negb, opa, opb) {
+                    (false, false, Prefix, Prefix) => !a.starts_with(b) && !b.starts_with(a),
+                    (false, false, Suffix, Suffix) => !a.ends_with(b) && !b.ends_with(a),
+                    (false, true, Prefix, Prefix) => a.starts_with(b),
+                    (true, false, Prefix, Prefix) => b.starts_with(a),
+                    _ => false,
+                }
+            }
+            (Cond::Nice(alo, ahi), Cond::Nice(blo, bhi)) => ahi < blo || bhi < alo,
+            (Cond::Id(fa, a), Cond::Id(fb, b)) => fa == fb && a != b,
+            _ => false,
+        }
+    }
+}
+
+fn conds(ands: &[LayerMatch]) -> Vec<Cond<'_>> {
+    ands.iter().map(Cond::new).collect()
+}
+
+/// Every task matching @b also matches @a.
+fn subsumes(a: &[Cond], b: &[Cond]) -> bool {
+    a.iter().all(|ca| b.iter().any(|cb| cb.implies(ca)))
+}
+
+/// Some task can match both @a and @b and @a only tests attributes which
+/// @b also tests, so the overlap is likely intentional rather than
+/// incidental.
+fn overlaps(a: &[Cond], b: &[Cond]) -> bool {
+    !a.is_empty()
+        && a.iter().all(|ca| b.iter().any(|cb| ca.same_field(cb)))
+        && !a.iter().any(|ca| b.iter().any(|cb| ca.contradicts(cb)))
+}
+
+fn never_matches(ands: &[Cond]) -> bool {
+    ands.iter()
+        .enumerate()
+        .any(|(i, a)| ands[i + 1..].iter().any(|b| a.contradicts(b)))
+}
+
+/// Lint @specs for mistakes which verify_layer_specs() doesn't reject but
+/// which are unlikely to be intended: unreachable and overlapping matches,
+/// empty cpusets and invalid util_ranges.
+pub fn lint_layer_specs(specs: &[LayerSpec]) -> Vec<Lint> {
+    let mut lints = vec![];
+    let mut lint = |level, spec: &LayerSpec, msg: String| {
+        lints.push(Lint {
+            level,
+            layer: Some(spec.name.clone()),
+            msg,
+        })
+    };
+
+    let all_conds: Vec<Vec<Vec<Cond>>> = specs
+        .iter()
+        .map(|spec| spec.matches.iter().map(|ands| conds(ands)).collect())
+        .collect();
+
+    for (idx, spec) in specs.iter().enumerate() {
+        if let Some((lo, hi)) = spec.kind.util_range() {
+            if lo < 0.0 || hi < 0.0 || lo >= hi {
+                lint(
+                    LintLevel::Error,
+                    spec,
+                    format!("invalid util_range ({}, {})", lo, hi),
+                );
+            } else if hi > 1.0 && !spec.kind.util_includes_open_cputime() {
+                lint(
+                    LintLevel::Warning,
+                    spec,
+                    format!("util_range upper bound {} above 1.0 is never reached", hi),
+                );
+            }
+        }
+
+        if let Some(cpuset) = &spec.cpuset {
+            if cpuset.is_empty() {
+                lint(LintLevel::Error, spec, "empty cpuset".into());
+            }
+        }
+
+        if let LayerKind::Confined {
+            cpus_range: Some((min, max)),
+            ..
+        }
+        | LayerKind::Grouped {
+            cpus_range: Some((min, max)),
+            ..
+        } = spec.kind
+        {
+            if max == 0 || min > max {
+                lint(
+                    LintLevel::Error,
+                    spec,
+                    format!("cpus_range ({}, {}) allows no CPUs", min, max),
+                );
+            }
+        }
+
+        let mut nr_dead = 0;
+        for (or_i, ands) in all_conds[idx].iter().enumerate() {
+            if never_matches(ands) {
+                lint(
+                    LintLevel::Warning,
+                    spec,
+                    format!("match block {} contains contradicting matches", or_i),
+                );
+                nr_dead += 1;
+                continue;
+            }
+
+            let shadowed_by = (0..idx).find(|&prev| {
+                all_conds[prev]
+                    .iter()
+                    .any(|prev_ands| subsumes(prev_ands, ands))
+            });
+            if let Some(prev) = shadowed_by {
+                lint(
+                    LintLevel::Warning,
+                    spec,
+                    format!(
+                        "match block {} is shadowed by layer {:?}",
+                        or_i, specs[prev].name
+                    ),
+                );
+                nr_dead += 1;
+                continue;
+            }
+
+            for prev in 0..idx {
+                for (prev_or_i, prev_ands) in all_conds[prev].iter().enumerate() {
+                    if overlaps(prev_ands, ands) {
+                        lint(
+                            LintLevel::Warning,
+                            spec,
+                            format!(
+                                "match block {} overlaps with match block {} of layer {:?} \
+                                 which takes precedence",
+                                or_i, prev_or_i, specs[prev].name
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+
+        if nr_dead > 0 && nr_dead == spec.matches.len() {
+            lint(LintLevel::Error, spec, "layer is unreachable".into());
+        }
+    }
+
+    lints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LayerConfig;
+
+    fn specs(json: &str) -> Vec<LayerSpec> {
+        serde_json::from_str::<LayerConfig>(json).unwrap().specs
+    }
+
+    fn task(comm: &str, cgroup: &str) -> TaskSnapshot {
+        TaskSnapshot {
+            tid: 100,
+            tgid: 100,
+            ppid: 1,
+            comm: comm.into(),
+            pcomm: comm.into(),
+            cgroup: cgroup.into(),
+            ..Default::default()
+        }
+    }
+
+    fn lints(specs: &[LayerSpec]) -> Vec<String> {
+        lint_layer_specs(specs)
+            .iter()
+            .map(|lint| lint.to_string())
+            .collect()
+    }
+
+    const OPEN: &str = r#"{"Open": {}}"#;
+
+    fn layer(name: &str, matches: &str) -> String {
+        format!(
+            r#"{{"name": "{}", "matches": {}, "kind": {}}}"#,
+            name, matches, OPEN
+        )
+    }
+
+    #[test]
+    fn test_cgrp_path() {
+        assert_eq!(task("a", "/").cgrp_path(), "/");
+        assert_eq!(
+            task("a", "/system.slice/foo.service").cgrp_path(),
+            "system.slice/foo.service/"
+        );
+        assert_eq!(
+            task("a", "/system.slice").cgrp_fs_path(),
+            "/sys/fs/cgroup/system.slice"
+        );
+    }
+
+    #[test]
+    fn test_parse_proc() {
+        let stat = "1234 (kworker/0:1 (x)) S 2 0 0 0 -1 69238880 0 0 0 0 0 0 0 0 20 -5 1";
+        let (comm, ppid, flags, nice) = parse_stat(stat).unwrap();
+        assert_eq!(comm, "kworker/0:1 (x)");
+        assert_eq!(ppid, 2);
+        assert!(flags & PF_KTHREAD != 0);
+        assert_eq!(nice, -5);
+
+        let status = "Name:\tbash\nUid:\t1000\t1001\t1000\t1000\n\
+                      Gid:\t100\t101\t100\t100\nNSpid:\t4321\t7\n";
+        assert_eq!(parse_status(status).unwrap(), (1001, 101, Some(7)));
+        assert_eq!(parse_ns_link("pid:[4026531836]"), Some(4026531836));
+    }
+
+    #[test]
+    fn test_assign() {
+        let specs = specs(&format!(
+            "[{}, {}, {}, {}]",
+            layer("sys", r#"[[{"CgroupPrefix": "system.slice/"}]]"#),
+            layer(
+                "runtime",
+                r#"[[{"CommPrefix": "java"}, {"AvgRuntime": [0, 1000]}]]"#
+            ),
+            layer(
+                "java",
+                r#"[[{"CommPrefix": "java"}], [{"PcommPrefix": "jvm"}]]"#
+            ),
+            layer("normal", "[[]]"),
+        ));
+        let matcher = LayerMatcher::new(&specs).unwrap();
+
+        let assign = |comm, cgroup| matcher.assign(&task(comm, cgroup));
+        assert_eq!(
+            assign("bash", "/system.slice/sshd.service"),
+            Assignment {
+                layer: Some(0),
+                maybe: vec![]
+            }
+        );
+        assert_eq!(
+            assign("bash", "/user.slice"),
+            Assignment {
+                layer: Some(3),
+                maybe: vec![]
+            }
+        );
+        assert_eq!(
+            assign("java", "/user.slice"),
+            Assignment {
+                layer: Some(2),
+                maybe: vec![1]
+            }
+        );
+    }
+
+    #[test]
+    fn test_lint_unreachable() {
+        let specs = specs(&format!(
+            "[{}, {}, {}]",
+            layer("a", r#"[[{"CommPrefix": "foo"}]]"#),
+            layer(
+                "b",
+                r#"[[{"CommPrefix": "foobar"}, {"NiceBelow": 0}], [{"CommPrefix": "foo"}]]"#
+            ),
+            layer("normal", "[[]]"),
+        ));
+        let lints = lints(&specs);
+        assert!(lints
+            .iter()
+            .any(|l| l.contains("match block 0 is shadowed by layer \"a\"")));
+        assert!(lints
+            .iter()
+            .any(|l| l.starts_with("error: layer \"b\": layer is unreachable")));
+        assert!(!lints.iter().any(|l| l.contains("\"normal\"")));
+    }
+
+    #[test]
+    fn test_lint_overlap() {
+        let specs = specs(&format!(
+            "[{}, {}, {}, {}]",
+            layer("a", r#"[[{"CommPrefix": "foobar"}]]"#),
+            layer("b", r#"[[{"CommPrefix": "foo"}]]"#),
+            layer("c", r#"[[{"CommPrefix": "bar"}, {"NiceAbove": 0}]]"#),
+            layer("normal", "[[]]"),
+        ));
+        let lints = lints(&specs);
+        assert_eq!(lints.len(), 1);
+        assert!(lints[0].contains("layer \"b\": match block 0 overlaps with match block 0"));
+    }
+
+    #[test]
+    fn test_lint_contradiction() {
+        let specs = specs(&format!(
+            "[{}, {}]",
+            layer(
+                "a",
+                r#"[[{"NiceAbove": 5}, {"NiceBelow": 0}], [{"UIDEquals": 0}]]"#
+            ),
+            layer("normal", "[[]]"),
+        ));
+        let lints = lints(&specs);
+        assert_eq!(lints.len(), 1);
+        assert!(lints[0].contains("match block 0 contains contradicting matches"));
+    }
+
+    #[test]
+    fn test_lint_kind() {
+        let specs = specs(
+            r#"[
+                {
+                    "name": "a",
+                    "matches": [[{"CommPrefix": "a"}]],
+                    "kind": {"Confined": {"util_range": [0.9, 0.8], "cpus_range": [0, 0]}}
+                },
+                {
+                    "name": "b",
+                    "matches": [[{"CommPrefix": "b"}]],
+                    "kind": {"Grouped": {"util_range": [0.5, 1.5]}}
+                },
+                {"name": "normal", "matches": [[]], "kind": {"Open": {}}}
+            ]"#,
+        );
+        let lints = lints(&specs);
+        assert_eq!(lints.len(), 3);
+        assert!(lints[0].starts_with("error: layer \"a\": invalid util_range"));
+        assert!(lints[1].starts_with("error: layer \"a\": cpus_range (0, 0)"));
+        assert!(lints[2].starts_with("warning: layer \"b\": util_range upper bound"));
+    }
+}
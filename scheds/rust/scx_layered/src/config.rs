@@ -5,6 +5,7 @@
 use std::fs;
 use std::io::Read;
 
+use anyhow::bail;
 use anyhow::Result;
 use serde::Deserialize;
 use serde::Serialize;
@@ -260,3 +261,171 @@ impl LayerKind {
         }
     }
 }
+
+impl LayerKind {
+    fn membw_gb(&self) -> Option<f64> {
+        match self {
+            LayerKind::Confined { membw_gb, .. } | LayerKind::Grouped { membw_gb, .. } => *membw_gb,
+            LayerKind::Open { .. } => None,
+        }
+    }
+}
+
+/// Match blocks whose effects are set up once at load time: CgroupRegex
+/// rules feed the cgroup watcher and HintEquals blocks populate
+/// hint_to_layer_id_map.
+fn load_time_matches(spec: &LayerSpec) -> Vec<&Vec<LayerMatch>> {
+    spec.matches
+        .iter()
+        .filter(|ands| {
+            ands.iter()
+                .any(|m| matches!(m, LayerMatch::CgroupRegex(_) | LayerMatch::HintEquals(_)))
+        })
+        .collect()
+}
+
+/// Compare a reloaded layer config against the running one and return the
+/// indices of the layers whose specs changed. Only changes which can be
+/// applied by rewriting the per-layer BPF state in place are allowed. If
+/// anything that is baked into rodata, the CPU allocation topology or the
+/// cgroup and hint maps at load time differs, the whole reload is refused.
+pub fn diff_layer_specs(cur: &[LayerSpec], new: &[LayerSpec]) -> Result<Vec<usize>> {
+    if cur.len() != new.len() {
+        bail!(
+            "number of layers changed from {} to {}",
+            cur.len(),
+            new.len()
+        );
+    }
+
+    let mut changed = vec![];
+    for (idx, (c, n)) in cur.iter().zip(new.iter()).enumerate() {
+        if c.name != n.name {
+            bail!("layer {} renamed from {:?} to {:?}", idx, c.name, n.name);
+        }
+
+        let (cc, nc) = (c.kind.common(), n.kind.common());
+        let open = matches!(c.kind, LayerKind::Open { .. });
+        for (what, same) in [
+            ("kind", c.kind.as_bpf_enum() == n.kind.as_bpf_enum()),
+            ("preempt", cc.preempt == nc.preempt),
+            ("exclusive", cc.exclusive == nc.exclusive),
+            ("weight", cc.weight == nc.weight),
+            ("nodes", cc.nodes == nc.nodes),
+            ("llcs", cc.llcs == nc.llcs),
+            ("cpuset", c.cpuset == n.cpuset),
+            (
+                "membw_gb",
+                c.kind.membw_gb().is_some() == n.kind.membw_gb().is_some(),
+            ),
+            (
+                "disallow_open_after_us",
+                !open || cc.disallow_open_after_us == nc.disallow_open_after_us,
+            ),
+            (
+                "disallow_preempt_after_us",
+                !open || cc.disallow_preempt_after_us == nc.disallow_preempt_after_us,
+            ),
+            (
+                "CgroupRegex/HintEquals matches",
+                serde_json::to_value(load_time_matches(c))?
+                    == serde_json::to_value(load_time_matches(n))?,
+            ),
+        ] {
+            if !same {
+                bail!(
+                    "layer {:?}: {} can't be changed without a restart",
+                    c.name,
+                    what
+                );
+            }
+        }
+
+        if serde_json::to_value(c)? != serde_json::to_value(n)? {
+            changed.push(idx);
+        }
+    }
+
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn specs(json: &str) -> Vec<LayerSpec> {
+        serde_json::from_str::<LayerConfig>(json).unwrap().specs
+    }
+
+    const BASE: &str = r#"[
+        {
+            "name": "batch",
+            "matches": [[{"CommPrefix": "batch"}]],
+            "kind": {"Confined": {"util_range": [0.8, 0.9], "slice_us": 20000}}
+        },
+        {
+            "name": "normal",
+            "matches": [[]],
+            "kind": {"Open": {"disallow_open_after_us": 1000}}
+        }
+    ]"#;
+
+    #[test]
+    fn test_diff_unchanged() {
+        assert!(diff_layer_specs(&specs(BASE), &specs(BASE))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_diff_compatible() {
+        let new = BASE.replace(r#""batch"}"#, r#""worker"}"#).replace(
+            r#"[0.8, 0.9], "slice_us": 20000"#,
+            r#"[0.5, 0.6], "slice_us": 5000"#,
+        );
+        let changed = diff_layer_specs(&specs(BASE), &specs(&new)).unwrap();
+        assert_eq!(changed, vec![0]);
+
+        let new = BASE.replace(
+            r#""slice_us": 20000"#,
+            r#""slice_us": 20000, "growth_algo": "Reverse""#,
+        );
+        assert_eq!(
+            diff_layer_specs(&specs(BASE), &specs(&new)).unwrap(),
+            vec![0]
+        );
+    }
+
+    #[test]
+    fn test_diff_incompatible() {
+        let cur = specs(BASE);
+        let err = |new: String| {
+            diff_layer_specs(&cur, &specs(&new))
+                .unwrap_err()
+                .to_string()
+        };
+
+        let fewer = r#"[{"name": "normal", "matches": [[]], "kind": {"Open": {}}}]"#;
+        assert!(err(fewer.into()).contains("number of layers"));
+        assert!(err(BASE.replace(r#""name": "batch""#, r#""name": "bulk""#)).contains("renamed"));
+        assert!(
+            err(BASE.replace(r#""slice_us": 20000"#, r#""slice_us": 20000, "weight": 50"#))
+                .contains("weight")
+        );
+        assert!(err(BASE.replace(
+            r#"{"Open": {"disallow_open_after_us": 1000}}"#,
+            r#"{"Open": {"disallow_open_after_us": 2000}}"#
+        ))
+        .contains("disallow_open_after_us"));
+        assert!(err(BASE.replace(
+            r#"{"CommPrefix": "batch"}"#,
+            r#"{"CgroupRegex": "batch.*"}"#
+        ))
+        .contains("CgroupRegex"));
+        assert!(err(BASE.replace(
+            r#"{"Confined": {"util_range": [0.8, 0.9], "slice_us": 20000}}"#,
+            r#"{"Grouped": {"util_range": [0.8, 0.9], "slice_us": 20000}}"#
+        ))
+        .contains("kind"));
+    }
+}
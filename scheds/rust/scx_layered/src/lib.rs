@@ -3,6 +3,7 @@
 // This software may be used and distributed according to the terms of the
 // GNU General Public License version 2.
 pub mod alloc;
+pub mod check;
 mod config;
 pub mod layer_core_growth;
 
@@ -19,6 +20,7 @@ use std::collections::BTreeMap;
 
 use anyhow::bail;
 use anyhow::Result;
+pub use config::diff_layer_specs;
 pub use config::LayerCommon;
 pub use config::LayerConfig;
 pub use config::LayerKind;
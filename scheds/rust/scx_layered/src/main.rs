@@ -16,6 +16,7 @@ use std::mem::MaybeUninit;
 use std::ops::Sub;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::atomic::fence;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
@@ -429,6 +430,22 @@ lazy_static! {
 ///   ...
 ///   $ scx_layered f:example.json
 ///
+/// Checking a configuration
+/// ========================
+///
+/// `scx_layered check` lints the configuration without loading the
+/// scheduler and shows which layer each task in the system would land in.
+/// Use --snapshot to evaluate against tasks captured earlier with
+/// --save-snapshot instead of the live system.
+///
+///   $ scx_layered check f:example.json
+///   $ scx_layered check --save-snapshot tasks.json f:example.json
+///   $ scx_layered check --snapshot tasks.json f:new.json
+///
+/// Matches which depend on runtime state such as AvgRuntime or task hints
+/// can't be evaluated offline. Tasks which may be claimed by such layers
+/// are marked with the candidate layers.
+///
 /// Monitoring Statistics
 /// =====================
 ///
@@ -456,7 +473,7 @@ lazy_static! {
 /// Per-layer statistics: see [`LayerStats`]
 ///
 #[derive(Debug, Parser)]
-#[command(verbatim_doc_comment)]
+#[command(verbatim_doc_comment, args_conflicts_with_subcommands = true)]
 struct Opts {
     /// Deprecated, noop, use RUST_LOG or --log-level instead.
     #[clap(short = 'v', long, action = clap::ArgAction::Count)]
@@ -636,6 +653,9 @@ struct Opts {
     /// Layer specification. See --help.
     specs: Vec<String>,
 
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Periodically force tasks in layers using the AvgRuntime match rule to reevaluate which layer they belong to. Default period of 2s.
     /// turns this off.
     #[clap(long, default_value = "2000")]
@@ -649,6 +669,14 @@ struct Opts {
     #[clap(long, default_value = "false")]
     print_and_exit: bool,
 
+    /// Watch the layer config files given with the f: or file: prefix and
+    /// apply changes without restarting. Only changes which can be applied
+    /// to the running layers in place, e.g. matches, util_range, slice_us
+    /// or growth_algo, are accepted. Other changes such as adding or
+    /// removing layers are refused and the running config is kept.
+    #[clap(long, default_value = "false")]
+    watch_config: bool,
+
     /// Enable affinitized task to use hi fallback queue to get more CPU time.
     #[clap(long, default_value = "")]
     hi_fb_thread_name: String,
@@ -660,6 +688,36 @@ struct Opts {
     pub libbpf: LibbpfOpts,
 }
 
+#[derive(Debug, clap::Subcommand)]
+enum Command {
+    /// Lint the layer specification and show which layer each task would
+    /// be assigned to without loading the scheduler.
+    Check(CheckOpts),
+}
+
+#[derive(Debug, clap::Args)]
+struct CheckOpts {
+    /// Evaluate the matches against the tasks in a snapshot file written by
+    /// --save-snapshot instead of the live system.
+    #[clap(long)]
+    snapshot: Option<String>,
+
+    /// Save the evaluated tasks into the specified file.
+    #[clap(long)]
+    save_snapshot: Option<String>,
+
+    /// Capture all threads instead of only thread group leaders.
+    #[clap(long)]
+    threads: bool,
+
+    /// Only lint the specification, don't show the task assignments.
+    #[clap(long)]
+    lint_only: bool,
+
+    /// Layer specification. See scx_layered --help.
+    specs: Vec<String>,
+}
+
 // Cgroup event types for inter-thread communication
 #[derive(Debug, Clone)]
 enum CgroupEvent {
@@ -1792,9 +1850,11 @@ impl GpuTaskAffinitizer {
 }
 
 struct Scheduler<'a> {
+    opts: &'a Opts,
     skel: BpfSkel<'a>,
     struct_ops: Option<libbpf_rs::Link>,
     layer_specs: Vec<LayerSpec>,
+    disable_topology: bool,
 
     sched_intv: Duration,
     layer_refresh_intv: Duration,
@@ -1954,272 +2014,293 @@ impl<'a> Scheduler<'a> {
 
         for (spec_i, spec) in specs.iter().enumerate() {
             let layer = &mut skel.maps.bss_data.as_mut().unwrap().layers[spec_i];
+            Self::init_layer(layer, spec, topo, &mut cgroup_regex_id, &mut cgroup_regexes)?;
+            layer_weights.push(layer.weight.try_into().unwrap());
+            perf_set |= layer.perf > 0;
+        }
 
-            for (or_i, or) in spec.matches.iter().enumerate() {
-                for (and_i, and) in or.iter().enumerate() {
-                    let mt = &mut layer.matches[or_i].matches[and_i];
-
-                    // Rules are allowlist-based by default
-                    mt.exclude.write(false);
+        layer_iteration_order.sort_by(|i, j| layer_weights[*i].cmp(&layer_weights[*j]));
+        for (idx, layer_idx) in layer_iteration_order.iter().enumerate() {
+            skel.maps
+                .rodata_data
+                .as_mut()
+                .unwrap()
+                .layer_iteration_order[idx] = *layer_idx as u32;
+        }
 
-                    match and {
-                        LayerMatch::CgroupPrefix(prefix) => {
-                            mt.kind = bpf_intf::layer_match_kind_MATCH_CGROUP_PREFIX as i32;
-                            copy_into_cstr(&mut mt.cgroup_prefix, prefix.as_str());
-                        }
-                        LayerMatch::CgroupSuffix(suffix) => {
-                            mt.kind = bpf_intf::layer_match_kind_MATCH_CGROUP_SUFFIX as i32;
-                            copy_into_cstr(&mut mt.cgroup_suffix, suffix.as_str());
-                        }
-                        LayerMatch::CgroupRegex(regex_str) => {
-                            if cgroup_regex_id >= bpf_intf::consts_MAX_CGROUP_REGEXES {
-                                bail!(
-                                    "Too many cgroup regex rules. Maximum allowed: {}",
-                                    bpf_intf::consts_MAX_CGROUP_REGEXES
-                                );
-                            }
+        if perf_set && !compat::ksym_exists("scx_bpf_cpuperf_set")? {
+            warn!("cpufreq support not available, ignoring perf configurations");
+        }
 
-                            // CgroupRegex matching handled in userspace via cgroup watcher
-                            mt.kind = bpf_intf::layer_match_kind_MATCH_CGROUP_REGEX as i32;
-                            mt.cgroup_regex_id = cgroup_regex_id;
+        Ok(cgroup_regexes)
+    }
 
-                            let regex = Regex::new(regex_str).with_context(|| {
-                                format!("Invalid regex '{}' in layer '{}'", regex_str, spec.name)
-                            })?;
-                            cgroup_regexes.insert(cgroup_regex_id, regex);
-                            cgroup_regex_id += 1;
-                        }
-                        LayerMatch::CgroupContains(substr) => {
-                            mt.kind = bpf_intf::layer_match_kind_MATCH_CGROUP_CONTAINS as i32;
-                            copy_into_cstr(&mut mt.cgroup_substr, substr.as_str());
-                        }
-                        LayerMatch::CommPrefix(prefix) => {
-                            mt.kind = bpf_intf::layer_match_kind_MATCH_COMM_PREFIX as i32;
-                            copy_into_cstr(&mut mt.comm_prefix, prefix.as_str());
-                        }
-                        LayerMatch::CommPrefixExclude(prefix) => {
-                            mt.kind = bpf_intf::layer_match_kind_MATCH_COMM_PREFIX as i32;
-                            mt.exclude.write(true);
-                            copy_into_cstr(&mut mt.comm_prefix, prefix.as_str());
-                        }
-                        LayerMatch::PcommPrefix(prefix) => {
-                            mt.kind = bpf_intf::layer_match_kind_MATCH_PCOMM_PREFIX as i32;
-                            copy_into_cstr(&mut mt.pcomm_prefix, prefix.as_str());
-                        }
-                        LayerMatch::PcommPrefixExclude(prefix) => {
-                            mt.kind = bpf_intf::layer_match_kind_MATCH_PCOMM_PREFIX as i32;
-                            mt.exclude.write(true);
-                            copy_into_cstr(&mut mt.pcomm_prefix, prefix.as_str());
-                        }
-                        LayerMatch::NiceAbove(nice) => {
-                            mt.kind = bpf_intf::layer_match_kind_MATCH_NICE_ABOVE as i32;
-                            mt.nice = *nice;
-                        }
-                        LayerMatch::NiceBelow(nice) => {
-                            mt.kind = bpf_intf::layer_match_kind_MATCH_NICE_BELOW as i32;
-                            mt.nice = *nice;
-                        }
-                        LayerMatch::NiceEquals(nice) => {
-                            mt.kind = bpf_intf::layer_match_kind_MATCH_NICE_EQUALS as i32;
-                            mt.nice = *nice;
-                        }
-                        LayerMatch::UIDEquals(user_id) => {
-                            mt.kind = bpf_intf::layer_match_kind_MATCH_USER_ID_EQUALS as i32;
-                            mt.user_id = *user_id;
-                        }
-                        LayerMatch::GIDEquals(group_id) => {
-                            mt.kind = bpf_intf::layer_match_kind_MATCH_GROUP_ID_EQUALS as i32;
-                            mt.group_id = *group_id;
-                        }
-                        LayerMatch::PIDEquals(pid) => {
-                            mt.kind = bpf_intf::layer_match_kind_MATCH_PID_EQUALS as i32;
-                            mt.pid = *pid;
-                        }
-                        LayerMatch::PPIDEquals(ppid) => {
-                            mt.kind = bpf_intf::layer_match_kind_MATCH_PPID_EQUALS as i32;
-                            mt.ppid = *ppid;
-                        }
-                        LayerMatch::TGIDEquals(tgid) => {
-                            mt.kind = bpf_intf::layer_match_kind_MATCH_TGID_EQUALS as i32;
-                            mt.tgid = *tgid;
-                        }
-                        LayerMatch::NSPIDEquals(nsid, pid) => {
-                            mt.kind = bpf_intf::layer_match_kind_MATCH_NSPID_EQUALS as i32;
-                            mt.nsid = *nsid;
-                            mt.pid = *pid;
-                        }
-                        LayerMatch::NSEquals(nsid) => {
-                            mt.kind = bpf_intf::layer_match_kind_MATCH_NS_EQUALS as i32;
-                            mt.nsid = *nsid as u64;
-                        }
-                        LayerMatch::CmdJoin(joincmd) => {
-                            mt.kind = bpf_intf::layer_match_kind_MATCH_SCXCMD_JOIN as i32;
-                            copy_into_cstr(&mut mt.comm_prefix, joincmd);
-                        }
-                        LayerMatch::IsGroupLeader(polarity) => {
-                            mt.kind = bpf_intf::layer_match_kind_MATCH_IS_GROUP_LEADER as i32;
-                            mt.is_group_leader.write(*polarity);
-                        }
-                        LayerMatch::IsKthread(polarity) => {
-                            mt.kind = bpf_intf::layer_match_kind_MATCH_IS_KTHREAD as i32;
-                            mt.is_kthread.write(*polarity);
-                        }
-                        LayerMatch::UsedGpuTid(polarity) => {
-                            mt.kind = bpf_intf::layer_match_kind_MATCH_USED_GPU_TID as i32;
-                            mt.used_gpu_tid.write(*polarity);
-                        }
-                        LayerMatch::UsedGpuPid(polarity) => {
-                            mt.kind = bpf_intf::layer_match_kind_MATCH_USED_GPU_PID as i32;
-                            mt.used_gpu_pid.write(*polarity);
-                        }
-                        LayerMatch::AvgRuntime(min, max) => {
-                            mt.kind = bpf_intf::layer_match_kind_MATCH_AVG_RUNTIME as i32;
-                            mt.min_avg_runtime_us = *min;
-                            mt.max_avg_runtime_us = *max;
-                        }
-                        LayerMatch::HintEquals(hint) => {
-                            mt.kind = bpf_intf::layer_match_kind_MATCH_HINT_EQUALS as i32;
-                            mt.hint = *hint;
-                        }
-                        LayerMatch::SystemCpuUtilBelow(threshold) => {
-                            mt.kind = bpf_intf::layer_match_kind_MATCH_SYSTEM_CPU_UTIL_BELOW as i32;
-                            mt.system_cpu_util_below = (*threshold * 10000.0) as u64;
-                        }
-                        LayerMatch::DsqInsertBelow(threshold) => {
-                            mt.kind = bpf_intf::layer_match_kind_MATCH_DSQ_INSERT_BELOW as i32;
-                            mt.dsq_insert_below = (*threshold * 10000.0) as u64;
-                        }
-                        LayerMatch::NumaNode(node_id) => {
-                            if *node_id as usize >= topo.nodes.len() {
-                                bail!(
-                                    "Spec {:?} has invalid NUMA node ID {} (available nodes: 0-{})",
-                                    spec.name,
-                                    node_id,
-                                    topo.nodes.len() - 1
-                                );
-                            }
-                            mt.kind = bpf_intf::layer_match_kind_MATCH_NUMA_NODE as i32;
-                            mt.numa_node_id = *node_id;
-                        }
-                    }
+    /// Checks on @spec which need the topology. init_layer() fails early on
+    /// these before writing anything.
+    fn check_layer_spec(spec: &LayerSpec, topo: &Topology) -> Result<()> {
+        for mt in spec.matches.iter().flatten() {
+            if let LayerMatch::NumaNode(node_id) = mt {
+                if *node_id as usize >= topo.nodes.len() {
+                    bail!(
+                        "Spec {:?} has invalid NUMA node ID {} (available nodes: 0-{})",
+                        spec.name,
+                        node_id,
+                        topo.nodes.len() - 1
+                    );
                 }
-                layer.matches[or_i].nr_match_ands = or.len() as i32;
             }
+        }
+        u32::try_from(spec.kind.common().perf)
+            .with_context(|| format!("Spec {:?} has invalid perf", spec.name))?;
+        Ok(())
+    }
 
-            layer.nr_match_ors = spec.matches.len() as u32;
-            layer.kind = spec.kind.as_bpf_enum();
+    /// Write @spec's configuration into the BPF @layer. Runtime state in
+    /// @layer is left alone so that this can also be used to update a
+    /// running layer on config reload.
+    fn init_layer(
+        layer: &mut types::layer,
+        spec: &LayerSpec,
+        topo: &Topology,
+        cgroup_regex_id: &mut u32,
+        cgroup_regexes: &mut HashMap<u32, Regex>,
+    ) -> Result<()> {
+        Self::check_layer_spec(spec, topo)?;
 
-            {
-                let LayerCommon {
-                    min_exec_us,
-                    yield_ignore,
-                    perf,
-                    preempt,
-                    preempt_first,
-                    exclusive,
-                    skip_remote_node,
-                    prev_over_idle_core,
-                    growth_algo,
-                    slice_us,
-                    fifo,
-                    weight,
-                    disallow_open_after_us,
-                    disallow_preempt_after_us,
-                    xllc_mig_min_us,
-                    placement,
-                    member_expire_ms,
-                    ..
-                } = spec.kind.common();
-
-                layer.slice_ns = *slice_us * 1000;
-                layer.fifo.write(*fifo);
-                layer.min_exec_ns = min_exec_us * 1000;
-                layer.yield_step_ns = if *yield_ignore > 0.999 {
-                    0
-                } else if *yield_ignore < 0.001 {
-                    layer.slice_ns
-                } else {
-                    (layer.slice_ns as f64 * (1.0 - *yield_ignore)) as u64
-                };
-                let mut layer_name: String = spec.name.clone();
-                layer_name.truncate(MAX_LAYER_NAME);
-                copy_into_cstr(&mut layer.name, layer_name.as_str());
-                layer.preempt.write(*preempt);
-                layer.preempt_first.write(*preempt_first);
-                layer.excl.write(*exclusive);
-                layer.skip_remote_node.write(*skip_remote_node);
-                layer.prev_over_idle_core.write(*prev_over_idle_core);
-                layer.growth_algo = growth_algo.as_bpf_enum();
-                layer.weight = *weight;
-                layer.member_expire_ms = *member_expire_ms;
-                layer.disallow_open_after_ns = match disallow_open_after_us.unwrap() {
-                    v if v == u64::MAX => v,
-                    v => v * 1000,
-                };
-                layer.disallow_preempt_after_ns = match disallow_preempt_after_us.unwrap() {
-                    v if v == u64::MAX => v,
-                    v => v * 1000,
-                };
-                layer.xllc_mig_min_ns = (xllc_mig_min_us * 1000.0) as u64;
-                layer_weights.push(layer.weight.try_into().unwrap());
-                layer.perf = u32::try_from(*perf)?;
-
-                let task_place = |place: u32| crate::types::layer_task_place(place);
-                layer.task_place = match placement {
-                    LayerPlacement::Standard => {
-                        task_place(bpf_intf::layer_task_place_PLACEMENT_STD)
-                    }
-                    LayerPlacement::Sticky => {
-                        task_place(bpf_intf::layer_task_place_PLACEMENT_STICK)
+        for (or_i, or) in spec.matches.iter().enumerate() {
+            for (and_i, and) in or.iter().enumerate() {
+                let mt = &mut layer.matches[or_i].matches[and_i];
+
+                // Rules are allowlist-based by default
+                mt.exclude.write(false);
+
+                match and {
+                    LayerMatch::CgroupPrefix(prefix) => {
+                        mt.kind = bpf_intf::layer_match_kind_MATCH_CGROUP_PREFIX as i32;
+                        copy_into_cstr(&mut mt.cgroup_prefix, prefix.as_str());
                     }
-                    LayerPlacement::Floating => {
-                        task_place(bpf_intf::layer_task_place_PLACEMENT_FLOAT)
+                    LayerMatch::CgroupSuffix(suffix) => {
+                        mt.kind = bpf_intf::layer_match_kind_MATCH_CGROUP_SUFFIX as i32;
+                        copy_into_cstr(&mut mt.cgroup_suffix, suffix.as_str());
                     }
-                };
-            }
-
-            layer.is_protected.write(match spec.kind {
-                LayerKind::Open { .. } => false,
-                LayerKind::Confined { protected, .. } | LayerKind::Grouped { protected, .. } => {
-                    protected
-                }
-            });
+                    LayerMatch::CgroupRegex(regex_str) => {
+                        if *cgroup_regex_id >= bpf_intf::consts_MAX_CGROUP_REGEXES {
+                            bail!(
+                                "Too many cgroup regex rules. Maximum allowed: {}",
+                                bpf_intf::consts_MAX_CGROUP_REGEXES
+                            );
+                        }
 
-            layer.idle_confined.write(match spec.kind {
-                LayerKind::Grouped { idle_confined, .. } => idle_confined,
-                _ => false,
-            });
+                        // CgroupRegex matching handled in userspace via cgroup watcher
+                        mt.kind = bpf_intf::layer_match_kind_MATCH_CGROUP_REGEX as i32;
+                        mt.cgroup_regex_id = *cgroup_regex_id;
 
-            match &spec.cpuset {
-                Some(mask) => {
-                    Self::update_cpumask(mask, &mut layer.cpuset);
-                    layer.has_cpuset.write(true);
-                }
-                None => {
-                    for i in 0..layer.cpuset.len() {
-                        layer.cpuset[i] = u8::MAX;
+                        let regex = Regex::new(regex_str).with_context(|| {
+                            format!("Invalid regex '{}' in layer '{}'", regex_str, spec.name)
+                        })?;
+                        cgroup_regexes.insert(*cgroup_regex_id, regex);
+                        *cgroup_regex_id += 1;
+                    }
+                    LayerMatch::CgroupContains(substr) => {
+                        mt.kind = bpf_intf::layer_match_kind_MATCH_CGROUP_CONTAINS as i32;
+                        copy_into_cstr(&mut mt.cgroup_substr, substr.as_str());
+                    }
+                    LayerMatch::CommPrefix(prefix) => {
+                        mt.kind = bpf_intf::layer_match_kind_MATCH_COMM_PREFIX as i32;
+                        copy_into_cstr(&mut mt.comm_prefix, prefix.as_str());
+                    }
+                    LayerMatch::CommPrefixExclude(prefix) => {
+                        mt.kind = bpf_intf::layer_match_kind_MATCH_COMM_PREFIX as i32;
+                        mt.exclude.write(true);
+                        copy_into_cstr(&mut mt.comm_prefix, prefix.as_str());
+                    }
+                    LayerMatch::PcommPrefix(prefix) => {
+                        mt.kind = bpf_intf::layer_match_kind_MATCH_PCOMM_PREFIX as i32;
+                        copy_into_cstr(&mut mt.pcomm_prefix, prefix.as_str());
+                    }
+                    LayerMatch::PcommPrefixExclude(prefix) => {
+                        mt.kind = bpf_intf::layer_match_kind_MATCH_PCOMM_PREFIX as i32;
+                        mt.exclude.write(true);
+                        copy_into_cstr(&mut mt.pcomm_prefix, prefix.as_str());
+                    }
+                    LayerMatch::NiceAbove(nice) => {
+                        mt.kind = bpf_intf::layer_match_kind_MATCH_NICE_ABOVE as i32;
+                        mt.nice = *nice;
+                    }
+                    LayerMatch::NiceBelow(nice) => {
+                        mt.kind = bpf_intf::layer_match_kind_MATCH_NICE_BELOW as i32;
+                        mt.nice = *nice;
+                    }
+                    LayerMatch::NiceEquals(nice) => {
+                        mt.kind = bpf_intf::layer_match_kind_MATCH_NICE_EQUALS as i32;
+                        mt.nice = *nice;
+                    }
+                    LayerMatch::UIDEquals(user_id) => {
+                        mt.kind = bpf_intf::layer_match_kind_MATCH_USER_ID_EQUALS as i32;
+                        mt.user_id = *user_id;
+                    }
+                    LayerMatch::GIDEquals(group_id) => {
+                        mt.kind = bpf_intf::layer_match_kind_MATCH_GROUP_ID_EQUALS as i32;
+                        mt.group_id = *group_id;
+                    }
+                    LayerMatch::PIDEquals(pid) => {
+                        mt.kind = bpf_intf::layer_match_kind_MATCH_PID_EQUALS as i32;
+                        mt.pid = *pid;
+                    }
+                    LayerMatch::PPIDEquals(ppid) => {
+                        mt.kind = bpf_intf::layer_match_kind_MATCH_PPID_EQUALS as i32;
+                        mt.ppid = *ppid;
+                    }
+                    LayerMatch::TGIDEquals(tgid) => {
+                        mt.kind = bpf_intf::layer_match_kind_MATCH_TGID_EQUALS as i32;
+                        mt.tgid = *tgid;
+                    }
+                    LayerMatch::NSPIDEquals(nsid, pid) => {
+                        mt.kind = bpf_intf::layer_match_kind_MATCH_NSPID_EQUALS as i32;
+                        mt.nsid = *nsid;
+                        mt.pid = *pid;
+                    }
+                    LayerMatch::NSEquals(nsid) => {
+                        mt.kind = bpf_intf::layer_match_kind_MATCH_NS_EQUALS as i32;
+                        mt.nsid = *nsid as u64;
+                    }
+                    LayerMatch::CmdJoin(joincmd) => {
+                        mt.kind = bpf_intf::layer_match_kind_MATCH_SCXCMD_JOIN as i32;
+                        copy_into_cstr(&mut mt.comm_prefix, joincmd);
+                    }
+                    LayerMatch::IsGroupLeader(polarity) => {
+                        mt.kind = bpf_intf::layer_match_kind_MATCH_IS_GROUP_LEADER as i32;
+                        mt.is_group_leader.write(*polarity);
+                    }
+                    LayerMatch::IsKthread(polarity) => {
+                        mt.kind = bpf_intf::layer_match_kind_MATCH_IS_KTHREAD as i32;
+                        mt.is_kthread.write(*polarity);
+                    }
+                    LayerMatch::UsedGpuTid(polarity) => {
+                        mt.kind = bpf_intf::layer_match_kind_MATCH_USED_GPU_TID as i32;
+                        mt.used_gpu_tid.write(*polarity);
+                    }
+                    LayerMatch::UsedGpuPid(polarity) => {
+                        mt.kind = bpf_intf::layer_match_kind_MATCH_USED_GPU_PID as i32;
+                        mt.used_gpu_pid.write(*polarity);
+                    }
+                    LayerMatch::AvgRuntime(min, max) => {
+                        mt.kind = bpf_intf::layer_match_kind_MATCH_AVG_RUNTIME as i32;
+                        mt.min_avg_runtime_us = *min;
+                        mt.max_avg_runtime_us = *max;
+                    }
+                    LayerMatch::HintEquals(hint) => {
+                        mt.kind = bpf_intf::layer_match_kind_MATCH_HINT_EQUALS as i32;
+                        mt.hint = *hint;
+                    }
+                    LayerMatch::SystemCpuUtilBelow(threshold) => {
+                        mt.kind = bpf_intf::layer_match_kind_MATCH_SYSTEM_CPU_UTIL_BELOW as i32;
+                        mt.system_cpu_util_below = (*threshold * 10000.0) as u64;
+                    }
+                    LayerMatch::DsqInsertBelow(threshold) => {
+                        mt.kind = bpf_intf::layer_match_kind_MATCH_DSQ_INSERT_BELOW as i32;
+                        mt.dsq_insert_below = (*threshold * 10000.0) as u64;
+                    }
+                    LayerMatch::NumaNode(node_id) => {
+                        mt.kind = bpf_intf::layer_match_kind_MATCH_NUMA_NODE as i32;
+                        mt.numa_node_id = *node_id;
                     }
-                    layer.has_cpuset.write(false);
                 }
-            };
-
-            perf_set |= layer.perf > 0;
+            }
+            layer.matches[or_i].nr_match_ands = or.len() as i32;
         }
 
-        layer_iteration_order.sort_by(|i, j| layer_weights[*i].cmp(&layer_weights[*j]));
-        for (idx, layer_idx) in layer_iteration_order.iter().enumerate() {
-            skel.maps
-                .rodata_data
-                .as_mut()
-                .unwrap()
-                .layer_iteration_order[idx] = *layer_idx as u32;
-        }
+        layer.nr_match_ors = spec.matches.len() as u32;
+        layer.kind = spec.kind.as_bpf_enum();
 
-        if perf_set && !compat::ksym_exists("scx_bpf_cpuperf_set")? {
-            warn!("cpufreq support not available, ignoring perf configurations");
+        {
+            let LayerCommon {
+                min_exec_us,
+                yield_ignore,
+                perf,
+                preempt,
+                preempt_first,
+                exclusive,
+                skip_remote_node,
+                prev_over_idle_core,
+                growth_algo,
+                slice_us,
+                fifo,
+                weight,
+                disallow_open_after_us,
+                disallow_preempt_after_us,
+                xllc_mig_min_us,
+                placement,
+                member_expire_ms,
+                ..
+            } = spec.kind.common();
+
+            layer.slice_ns = *slice_us * 1000;
+            layer.fifo.write(*fifo);
+            layer.min_exec_ns = min_exec_us * 1000;
+            layer.yield_step_ns = if *yield_ignore > 0.999 {
+                0
+            } else if *yield_ignore < 0.001 {
+                layer.slice_ns
+            } else {
+                (layer.slice_ns as f64 * (1.0 - *yield_ignore)) as u64
+            };
+            let mut layer_name: String = spec.name.clone();
+            layer_name.truncate(MAX_LAYER_NAME);
+            copy_into_cstr(&mut layer.name, layer_name.as_str());
+            layer.preempt.write(*preempt);
+            layer.preempt_first.write(*preempt_first);
+            layer.excl.write(*exclusive);
+            layer.skip_remote_node.write(*skip_remote_node);
+            layer.prev_over_idle_core.write(*prev_over_idle_core);
+            layer.growth_algo = growth_algo.as_bpf_enum();
+            layer.weight = *weight;
+            layer.member_expire_ms = *member_expire_ms;
+            layer.disallow_open_after_ns = match disallow_open_after_us.unwrap() {
+                v if v == u64::MAX => v,
+                v => v * 1000,
+            };
+            layer.disallow_preempt_after_ns = match disallow_preempt_after_us.unwrap() {
+                v if v == u64::MAX => v,
+                v => v * 1000,
+            };
+            layer.xllc_mig_min_ns = (xllc_mig_min_us * 1000.0) as u64;
+            layer.perf = u32::try_from(*perf)?;
+
+            let task_place = |place: u32| crate::types::layer_task_place(place);
+            layer.task_place = match placement {
+                LayerPlacement::Standard => task_place(bpf_intf::layer_task_place_PLACEMENT_STD),
+                LayerPlacement::Sticky => task_place(bpf_intf::layer_task_place_PLACEMENT_STICK),
+                LayerPlacement::Floating => task_place(bpf_intf::layer_task_place_PLACEMENT_FLOAT),
+            };
         }
 
-        Ok(cgroup_regexes)
+        layer.is_protected.write(match spec.kind {
+            LayerKind::Open { .. } => false,
+            LayerKind::Confined { protected, .. } | LayerKind::Grouped { protected, .. } => {
+                protected
+            }
+        });
+
+        layer.idle_confined.write(match spec.kind {
+            LayerKind::Grouped { idle_confined, .. } => idle_confined,
+            _ => false,
+        });
+
+        match &spec.cpuset {
+            Some(mask) => {
+                Self::update_cpumask(mask, &mut layer.cpuset);
+                layer.has_cpuset.write(true);
+            }
+            None => {
+                for i in 0..layer.cpuset.len() {
+                    layer.cpuset[i] = u8::MAX;
+                }
+                layer.has_cpuset.write(false);
+            }
+        };
+
+        Ok(())
     }
 
     fn init_nodes(skel: &mut OpenBpfSkel, _opts: &Opts, topo: &Topology) {
@@ -3041,8 +3122,10 @@ impl<'a> Scheduler<'a> {
         gpu_task_handler.init(topo.clone());
 
         let sched = Self {
+            opts,
             struct_ops: Some(struct_ops),
             layer_specs,
+            disable_topology,
 
             sched_intv: Duration::from_secs_f64(opts.interval),
             layer_refresh_intv: Duration::from_millis(opts.layer_refresh_ms_avgruntime),
@@ -4388,78 +4471,317 @@ impl<'a> Scheduler<'a> {
         Ok(receiver)
     }
 
-    fn add_recursive_watches(
-        inotify: &mut Inotify,
-        wd_to_path: &mut HashMap<inotify::WatchDescriptor, PathBuf>,
-        path: &Path,
-    ) -> Result<()> {
-        for entry in WalkDir::new(path)
-            .into_iter()
-            .filter_map(|e| e.ok())
-            .filter(|e| e.file_type().is_dir())
-            .skip(1)
-        {
-            let entry_path = entry.path();
-            // Add watch for this directory
-            match inotify
+    fn start_config_watcher(
+        shutdown: Arc<AtomicBool>,
+        specs: &[String],
+    ) -> Result<Option<Receiver<()>>> {
+        let files: Vec<PathBuf> = specs
+            .iter()
+            .filter(|input| input.starts_with("f:") || input.starts_with("file:"))
+            .map(|input| PathBuf::from(input.split_once(':').unwrap().1))
+            .collect();
+        if files.is_empty() {
+            warn!("--watch-config specified but no layer config file to watch");
+            return Ok(None);
+        }
+
+        // Editors often replace the file instead of writing into it. Watch
+        // the parent directories and filter by name.
+        let mut inotify = Inotify::init().context("Failed to initialize inotify")?;
+        let mut wd_to_names = HashMap::<inotify::WatchDescriptor, Vec<std::ffi::OsString>>::new();
+        for file in files.iter() {
+            let (dir, name) = match (file.parent(), file.file_name()) {
+                (Some(dir), Some(name)) => (dir, name),
+                _ => bail!("Invalid layer config path {:?}", file),
+            };
+            let dir = if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            };
+            let wd = inotify
                 .watches()
-                .add(entry_path, WatchMask::CREATE | WatchMask::DELETE)
-            {
-                Ok(wd) => {
-                    wd_to_path.insert(wd, entry_path.to_path_buf());
-                }
-                Err(e) => {
-                    debug!("Failed to add watch for {}: {}", entry_path.display(), e);
-                }
-            }
+                .add(dir, WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO)
+                .with_context(|| format!("Failed to add watch for {}", dir.display()))?;
+            wd_to_names.entry(wd).or_default().push(name.to_owned());
+            info!("Watching {} for layer config changes", file.display());
         }
-        Ok(())
-    }
 
-    fn run(&mut self, shutdown: Arc<AtomicBool>) -> Result<UserExitInfo> {
-        let (res_ch, req_ch) = self.stats_server.channels();
-        let mut next_sched_at = Instant::now() + self.sched_intv;
-        let enable_layer_refresh = !self.layer_refresh_intv.is_zero();
-        let mut next_layer_refresh_at = Instant::now() + self.layer_refresh_intv;
-        let mut cpus_ranges = HashMap::<ThreadId, Vec<(usize, usize)>>::new();
+        // Reload requests are coalesced, one pending request is enough.
+        let (sender, receiver) = crossbeam::channel::bounded::<()>(1);
 
-        // Start the cgroup watcher only if there are CgroupRegex rules
-        let cgroup_regexes = self.cgroup_regexes.take().unwrap();
-        let cgroup_event_rx = if !cgroup_regexes.is_empty() {
-            Some(Self::start_cgroup_watcher(
-                shutdown.clone(),
-                cgroup_regexes,
-            )?)
-        } else {
-            None
-        };
+        std::thread::spawn(move || {
+            let mut buffer = [0; 4096];
+            let inotify_fd = inotify.as_raw_fd();
 
-        while !shutdown.load(Ordering::Relaxed) && !uei_exited!(&self.skel, uei) {
-            let now = Instant::now();
+            while !shutdown.load(Ordering::Relaxed) {
+                // Use select to wait for events with a 100ms timeout
+                let ready = unsafe {
+                    let mut read_fds: libc::fd_set = std::mem::zeroed();
+                    libc::FD_ZERO(&mut read_fds);
+                    libc::FD_SET(inotify_fd, &mut read_fds);
 
-            if now >= next_sched_at {
-                self.step()?;
-                while next_sched_at < now {
-                    next_sched_at += self.sched_intv;
-                }
-            }
+                    let mut timeout = libc::timeval {
+                        tv_sec: 0,
+                        tv_usec: 100_000, // 100ms
+                    };
 
-            if enable_layer_refresh && now >= next_layer_refresh_at {
-                self.skel
-                    .maps
-                    .bss_data
-                    .as_mut()
-                    .unwrap()
-                    .layer_refresh_seq_avgruntime += 1;
-                while next_layer_refresh_at < now {
-                    next_layer_refresh_at += self.layer_refresh_intv;
-                }
-            }
+                    libc::select(
+                        inotify_fd + 1,
+                        &mut read_fds,
+                        std::ptr::null_mut(),
+                        std::ptr::null_mut(),
+                        &mut timeout,
+                    )
+                };
+
+                if ready <= 0 {
+                    continue;
+                }
+
+                let mut events = match inotify.read_events(&mut buffer) {
+                    Ok(events) => events,
+                    Err(e) => {
+                        error!("Error reading inotify events: {}", e);
+                        break;
+                    }
+                };
+
+                let changed = events.any(|event| match (wd_to_names.get(&event.wd), event.name) {
+                    (Some(names), Some(name)) => names.iter().any(|n| n == name),
+                    _ => false,
+                });
+                if changed {
+                    let _ = sender.try_send(());
+                }
+            }
+        });
+
+        Ok(Some(receiver))
+    }
+
+    /// Re-read the layer config and apply it to the running layers. The new
+    /// config goes through the same validation as on startup and is then
+    /// compared against the running one with diff_layer_specs(). If any
+    /// change can't be applied in place, nothing is touched and an error is
+    /// returned.
+    fn reload_layer_config(&mut self) -> Result<()> {
+        let mut specs = load_layer_config(self.opts, &self.opts.specs)?.specs;
+        if self.disable_topology {
+            for spec in specs.iter_mut() {
+                spec.nodes_mut().clear();
+                spec.llcs_mut().clear();
+            }
+        }
+        verify_layer_specs(&specs)?;
+
+        let changed = diff_layer_specs(&self.layer_specs, &specs)?;
+        if changed.is_empty() {
+            info!("Layer config reloaded, no changes");
+            return Ok(());
+        }
+
+        // Build and validate everything before touching the running layers.
+        let core_orders = LayerGrowthAlgo::layer_core_orders(&self.cpu_pool, &specs, &self.topo)?;
+        let mut new_layers = vec![];
+        for &idx in changed.iter() {
+            let growth_order = core_orders
+                .get(&idx)
+                .with_context(|| "layer has no growth order".to_string())?;
+            new_layers.push(Layer::new(&specs[idx], &self.topo, growth_order)?);
+        }
+
+        let bss_data = self.skel.maps.bss_data.as_mut().unwrap();
+        let staged = Self::stage_layers(&bss_data.layers, &specs, &changed, &self.topo)?;
+
+        // Nothing can fail from here on, publish the new configs all at once.
+        Self::publish_layers(
+            &mut bss_data.layers,
+            &mut bss_data.layer_config_seq,
+            &changed,
+            &staged,
+        );
+        for (&idx, new_layer) in changed.iter().zip(new_layers) {
+            let layer = &mut self.layers[idx];
+            layer.kind = new_layer.kind;
+            layer.growth_algo = new_layer.growth_algo;
+            layer.core_order = new_layer.core_order;
+        }
+
+        info!(
+            "Layer config reloaded, updated layers: {}",
+            changed
+                .iter()
+                .map(|&idx| specs[idx].name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        self.layer_specs = specs;
+        Ok(())
+    }
+
+    /// Build the BPF state of the @changed layers from @specs on top of a
+    /// copy of the running @layers. The running layers are not touched, so
+    /// a failure leaves the current config in place.
+    fn stage_layers(
+        layers: &[types::layer],
+        specs: &[LayerSpec],
+        changed: &[usize],
+        topo: &Topology,
+    ) -> Result<Vec<types::layer>> {
+        // CgroupRegex rules are unchanged and keep their IDs.
+        let cgroup_regex_base = |idx: usize| {
+            specs[..idx]
+                .iter()
+                .flat_map(|spec| spec.matches.iter().flatten())
+                .filter(|m| matches!(m, LayerMatch::CgroupRegex(_)))
+                .count() as u32
+        };
+
+        changed
+            .iter()
+            .map(|&idx| {
+                let mut layer = layers[idx];
+                Self::init_layer(
+                    &mut layer,
+                    &specs[idx],
+                    topo,
+                    &mut cgroup_regex_base(idx),
+                    &mut HashMap::new(),
+                )?;
+                Ok(layer)
+            })
+            .collect()
+    }
+
+    /// Copy the configs written by init_layer() from @staged into the
+    /// running @layers. The runtime state maintained by BPF is preserved.
+    ///
+    /// @config_seq is odd while the copy is in progress and is bumped again
+    /// when it's done, so BPF can detect that it may have seen torn configs
+    /// and re-evaluate the layer matches.
+    fn publish_layers(
+        layers: &mut [types::layer],
+        config_seq: &mut u64,
+        changed: &[usize],
+        staged: &[types::layer],
+    ) {
+        let seq = unsafe { std::ptr::read_volatile(config_seq) };
+        unsafe { std::ptr::write_volatile(config_seq, seq | 1) };
+        fence(Ordering::SeqCst);
+
+        for (&idx, new) in changed.iter().zip(staged) {
+            let layer = &mut layers[idx];
+            layer.matches = new.matches;
+            layer.nr_match_ors = new.nr_match_ors;
+            layer.kind = new.kind;
+            layer.min_exec_ns = new.min_exec_ns;
+            layer.yield_step_ns = new.yield_step_ns;
+            layer.slice_ns = new.slice_ns;
+            layer.fifo = new.fifo;
+            layer.weight = new.weight;
+            layer.disallow_open_after_ns = new.disallow_open_after_ns;
+            layer.disallow_preempt_after_ns = new.disallow_preempt_after_ns;
+            layer.xllc_mig_min_ns = new.xllc_mig_min_ns;
+            layer.preempt = new.preempt;
+            layer.preempt_first = new.preempt_first;
+            layer.excl = new.excl;
+            layer.has_cpuset = new.has_cpuset;
+            layer.skip_remote_node = new.skip_remote_node;
+            layer.prev_over_idle_core = new.prev_over_idle_core;
+            layer.idle_confined = new.idle_confined;
+            layer.growth_algo = new.growth_algo;
+            layer.perf = new.perf;
+            layer.task_place = new.task_place;
+            layer.name = new.name;
+            layer.is_protected = new.is_protected;
+            layer.cpuset = new.cpuset;
+            layer.member_expire_ms = new.member_expire_ms;
+        }
+
+        fence(Ordering::SeqCst);
+        unsafe { std::ptr::write_volatile(config_seq, (seq | 1) + 1) };
+    }
+
+    fn add_recursive_watches(
+        inotify: &mut Inotify,
+        wd_to_path: &mut HashMap<inotify::WatchDescriptor, PathBuf>,
+        path: &Path,
+    ) -> Result<()> {
+        for entry in WalkDir::new(path)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_dir())
+            .skip(1)
+        {
+            let entry_path = entry.path();
+            // Add watch for this directory
+            match inotify
+                .watches()
+                .add(entry_path, WatchMask::CREATE | WatchMask::DELETE)
+            {
+                Ok(wd) => {
+                    wd_to_path.insert(wd, entry_path.to_path_buf());
+                }
+                Err(e) => {
+                    debug!("Failed to add watch for {}: {}", entry_path.display(), e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn run(&mut self, shutdown: Arc<AtomicBool>) -> Result<UserExitInfo> {
+        let (res_ch, req_ch) = self.stats_server.channels();
+        let mut next_sched_at = Instant::now() + self.sched_intv;
+        let enable_layer_refresh = !self.layer_refresh_intv.is_zero();
+        let mut next_layer_refresh_at = Instant::now() + self.layer_refresh_intv;
+        let mut cpus_ranges = HashMap::<ThreadId, Vec<(usize, usize)>>::new();
+
+        // Start the cgroup watcher only if there are CgroupRegex rules
+        let cgroup_regexes = self.cgroup_regexes.take().unwrap();
+        let cgroup_event_rx = if !cgroup_regexes.is_empty() {
+            Some(Self::start_cgroup_watcher(
+                shutdown.clone(),
+                cgroup_regexes,
+            )?)
+        } else {
+            None
+        };
+        let config_event_rx = if self.opts.watch_config {
+            Self::start_config_watcher(shutdown.clone(), &self.opts.specs)?
+        } else {
+            None
+        };
+
+        while !shutdown.load(Ordering::Relaxed) && !uei_exited!(&self.skel, uei) {
+            let now = Instant::now();
+
+            if now >= next_sched_at {
+                self.step()?;
+                while next_sched_at < now {
+                    next_sched_at += self.sched_intv;
+                }
+            }
+
+            if enable_layer_refresh && now >= next_layer_refresh_at {
+                self.skel
+                    .maps
+                    .bss_data
+                    .as_mut()
+                    .unwrap()
+                    .layer_refresh_seq_avgruntime += 1;
+                while next_layer_refresh_at < now {
+                    next_layer_refresh_at += self.layer_refresh_intv;
+                }
+            }
 
             // Handle both stats requests and cgroup events with timeout
             let timeout_duration = next_sched_at.saturating_duration_since(Instant::now());
             let never_rx = crossbeam::channel::never();
             let cgroup_rx = cgroup_event_rx.as_ref().unwrap_or(&never_rx);
+            let never_config_rx = crossbeam::channel::never();
+            let config_rx = config_event_rx.as_ref().unwrap_or(&never_config_rx);
 
             select! {
                 recv(req_ch) -> msg => match msg {
@@ -4531,6 +4853,12 @@ impl<'a> Scheduler<'a> {
                     }
                 },
 
+                recv(config_rx) -> event => if event.is_ok() {
+                    if let Err(e) = self.reload_layer_config() {
+                        warn!("Layer config reload refused, keeping the running config: {:#}", e);
+                    }
+                },
+
                 recv(crossbeam::channel::after(timeout_duration)) -> _ => {
                     // Timeout - continue main loop
                 }
@@ -4575,188 +4903,211 @@ struct HintLayerInfo {
     dsq_insert_below: Option<f64>,
 }
 
-fn verify_layer_specs(specs: &[LayerSpec]) -> Result<HashMap<u64, HintLayerInfo>> {
-    let mut hint_to_layer_map = HashMap::<u64, (usize, String, Option<f64>, Option<f64>)>::new();
+type HintToLayerMap = HashMap<u64, (usize, String, Option<f64>, Option<f64>)>;
 
-    let nr_specs = specs.len();
-    if nr_specs == 0 {
+fn verify_nr_layer_specs(specs: &[LayerSpec]) -> Result<()> {
+    if specs.is_empty() {
         bail!("No layer spec");
     }
-    if nr_specs > MAX_LAYERS {
+    if specs.len() > MAX_LAYERS {
         bail!("Too many layer specs");
     }
+    Ok(())
+}
 
-    for (idx, spec) in specs.iter().enumerate() {
-        if idx < nr_specs - 1 {
-            if spec.matches.is_empty() {
-                bail!("Non-terminal spec {:?} has NULL matches", spec.name);
-            }
-        } else if spec.matches.len() != 1 || !spec.matches[0].is_empty() {
-            bail!("Terminal spec {:?} must have an empty match", spec.name);
+/// Verify the matches of the @idx'th spec of @specs and record its hints in
+/// @hint_to_layer_map.
+fn verify_layer_matches(
+    specs: &[LayerSpec],
+    idx: usize,
+    hint_to_layer_map: &mut HintToLayerMap,
+) -> Result<()> {
+    let nr_specs = specs.len();
+    let spec = &specs[idx];
+    if idx < nr_specs - 1 {
+        if spec.matches.is_empty() {
+            bail!("Non-terminal spec {:?} has NULL matches", spec.name);
         }
+    } else if spec.matches.len() != 1 || !spec.matches[0].is_empty() {
+        bail!("Terminal spec {:?} must have an empty match", spec.name);
+    }
+
+    if spec.matches.len() > MAX_LAYER_MATCH_ORS {
+        bail!(
+            "Spec {:?} has too many ({}) OR match blocks",
+            spec.name,
+            spec.matches.len()
+        );
+    }
 
-        if spec.matches.len() > MAX_LAYER_MATCH_ORS {
+    for (ands_idx, ands) in spec.matches.iter().enumerate() {
+        if ands.len() > NR_LAYER_MATCH_KINDS {
             bail!(
-                "Spec {:?} has too many ({}) OR match blocks",
+                "Spec {:?}'s {}th OR block has too many ({}) match conditions",
                 spec.name,
-                spec.matches.len()
+                ands_idx,
+                ands.len()
             );
         }
-
-        for (ands_idx, ands) in spec.matches.iter().enumerate() {
-            if ands.len() > NR_LAYER_MATCH_KINDS {
-                bail!(
-                    "Spec {:?}'s {}th OR block has too many ({}) match conditions",
-                    spec.name,
-                    ands_idx,
-                    ands.len()
-                );
-            }
-            let mut hint_equals_cnt = 0;
-            let mut system_cpu_util_below_cnt = 0;
-            let mut dsq_insert_below_cnt = 0;
-            let mut hint_value: Option<u64> = None;
-            let mut system_cpu_util_threshold: Option<f64> = None;
-            let mut dsq_insert_threshold: Option<f64> = None;
-            for one in ands.iter() {
-                match one {
-                    LayerMatch::CgroupPrefix(prefix) => {
-                        if prefix.len() > MAX_PATH {
-                            bail!("Spec {:?} has too long a cgroup prefix", spec.name);
-                        }
+        let mut hint_equals_cnt = 0;
+        let mut system_cpu_util_below_cnt = 0;
+        let mut dsq_insert_below_cnt = 0;
+        let mut hint_value: Option<u64> = None;
+        let mut system_cpu_util_threshold: Option<f64> = None;
+        let mut dsq_insert_threshold: Option<f64> = None;
+        for one in ands.iter() {
+            match one {
+                LayerMatch::CgroupPrefix(prefix) => {
+                    if prefix.len() > MAX_PATH {
+                        bail!("Spec {:?} has too long a cgroup prefix", spec.name);
                     }
-                    LayerMatch::CgroupSuffix(suffix) => {
-                        if suffix.len() > MAX_PATH {
-                            bail!("Spec {:?} has too long a cgroup suffix", spec.name);
-                        }
+                }
+                LayerMatch::CgroupSuffix(suffix) => {
+                    if suffix.len() > MAX_PATH {
+                        bail!("Spec {:?} has too long a cgroup suffix", spec.name);
                     }
-                    LayerMatch::CgroupContains(substr) => {
-                        if substr.len() > MAX_PATH {
-                            bail!("Spec {:?} has too long a cgroup substr", spec.name);
-                        }
+                }
+                LayerMatch::CgroupContains(substr) => {
+                    if substr.len() > MAX_PATH {
+                        bail!("Spec {:?} has too long a cgroup substr", spec.name);
                     }
-                    LayerMatch::CommPrefix(prefix) => {
-                        if prefix.len() > MAX_COMM {
-                            bail!("Spec {:?} has too long a comm prefix", spec.name);
-                        }
+                }
+                LayerMatch::CommPrefix(prefix) => {
+                    if prefix.len() > MAX_COMM {
+                        bail!("Spec {:?} has too long a comm prefix", spec.name);
                     }
-                    LayerMatch::PcommPrefix(prefix) => {
-                        if prefix.len() > MAX_COMM {
-                            bail!("Spec {:?} has too long a process name prefix", spec.name);
-                        }
+                }
+                LayerMatch::PcommPrefix(prefix) => {
+                    if prefix.len() > MAX_COMM {
+                        bail!("Spec {:?} has too long a process name prefix", spec.name);
                     }
-                    LayerMatch::SystemCpuUtilBelow(threshold) => {
-                        if *threshold < 0.0 || *threshold > 1.0 {
-                            bail!(
-                                "Spec {:?} has SystemCpuUtilBelow threshold outside the range [0.0, 1.0]",
-                                spec.name
-                            );
-                        }
-                        system_cpu_util_threshold = Some(*threshold);
-                        system_cpu_util_below_cnt += 1;
+                }
+                LayerMatch::SystemCpuUtilBelow(threshold) => {
+                    if *threshold < 0.0 || *threshold > 1.0 {
+                        bail!(
+                            "Spec {:?} has SystemCpuUtilBelow threshold outside the range [0.0, 1.0]",
+                            spec.name
+                        );
                     }
-                    LayerMatch::DsqInsertBelow(threshold) => {
-                        if *threshold < 0.0 || *threshold > 1.0 {
-                            bail!(
-                                "Spec {:?} has DsqInsertBelow threshold outside the range [0.0, 1.0]",
-                                spec.name
-                            );
-                        }
-                        dsq_insert_threshold = Some(*threshold);
-                        dsq_insert_below_cnt += 1;
+                    system_cpu_util_threshold = Some(*threshold);
+                    system_cpu_util_below_cnt += 1;
+                }
+                LayerMatch::DsqInsertBelow(threshold) => {
+                    if *threshold < 0.0 || *threshold > 1.0 {
+                        bail!(
+                            "Spec {:?} has DsqInsertBelow threshold outside the range [0.0, 1.0]",
+                            spec.name
+                        );
                     }
-                    LayerMatch::HintEquals(hint) => {
-                        if *hint > 1024 {
-                            bail!(
-                                "Spec {:?} has hint value outside the range [0, 1024]",
-                                spec.name
-                            );
-                        }
-                        hint_value = Some(*hint);
-                        hint_equals_cnt += 1;
+                    dsq_insert_threshold = Some(*threshold);
+                    dsq_insert_below_cnt += 1;
+                }
+                LayerMatch::HintEquals(hint) => {
+                    if *hint > 1024 {
+                        bail!(
+                            "Spec {:?} has hint value outside the range [0, 1024]",
+                            spec.name
+                        );
                     }
-                    _ => {}
+                    hint_value = Some(*hint);
+                    hint_equals_cnt += 1;
                 }
+                _ => {}
             }
-            if hint_equals_cnt > 1 {
-                bail!("Only 1 HintEquals match permitted per AND block");
+        }
+        if hint_equals_cnt > 1 {
+            bail!("Only 1 HintEquals match permitted per AND block");
+        }
+        let high_freq_matcher_cnt = system_cpu_util_below_cnt + dsq_insert_below_cnt;
+        if high_freq_matcher_cnt > 0 {
+            if hint_equals_cnt != 1 {
+                bail!("High-frequency matchers (SystemCpuUtilBelow, DsqInsertBelow) must be used with one HintEquals");
             }
-            let high_freq_matcher_cnt = system_cpu_util_below_cnt + dsq_insert_below_cnt;
-            if high_freq_matcher_cnt > 0 {
-                if hint_equals_cnt != 1 {
-                    bail!("High-frequency matchers (SystemCpuUtilBelow, DsqInsertBelow) must be used with one HintEquals");
-                }
-                if system_cpu_util_below_cnt > 1 {
-                    bail!("Only 1 SystemCpuUtilBelow match permitted per AND block");
-                }
-                if dsq_insert_below_cnt > 1 {
-                    bail!("Only 1 DsqInsertBelow match permitted per AND block");
-                }
-                if ands.len() != hint_equals_cnt + system_cpu_util_below_cnt + dsq_insert_below_cnt
-                {
-                    bail!("High-frequency matchers must be used only with HintEquals (no other matchers)");
-                }
-            } else if hint_equals_cnt == 1 && ands.len() != 1 {
-                bail!("HintEquals match cannot be in conjunction with other matches");
+            if system_cpu_util_below_cnt > 1 {
+                bail!("Only 1 SystemCpuUtilBelow match permitted per AND block");
+            }
+            if dsq_insert_below_cnt > 1 {
+                bail!("Only 1 DsqInsertBelow match permitted per AND block");
+            }
+            if ands.len() != hint_equals_cnt + system_cpu_util_below_cnt + dsq_insert_below_cnt {
+                bail!(
+                    "High-frequency matchers must be used only with HintEquals (no other matchers)"
+                );
             }
+        } else if hint_equals_cnt == 1 && ands.len() != 1 {
+            bail!("HintEquals match cannot be in conjunction with other matches");
+        }
 
-            // Insert hint into map if present
-            if let Some(hint) = hint_value {
-                if let Some((layer_id, name, _, _)) = hint_to_layer_map.get(&hint) {
-                    if *layer_id != idx {
-                        bail!(
-                            "Spec {:?} has hint value ({}) that is already mapped to Spec {:?}",
-                            spec.name,
-                            hint,
-                            name
-                        );
-                    }
-                } else {
-                    hint_to_layer_map.insert(
+        // Insert hint into map if present
+        if let Some(hint) = hint_value {
+            if let Some((layer_id, name, _, _)) = hint_to_layer_map.get(&hint) {
+                if *layer_id != idx {
+                    bail!(
+                        "Spec {:?} has hint value ({}) that is already mapped to Spec {:?}",
+                        spec.name,
                         hint,
-                        (
-                            idx,
-                            spec.name.clone(),
-                            system_cpu_util_threshold,
-                            dsq_insert_threshold,
-                        ),
+                        name
                     );
                 }
+            } else {
+                hint_to_layer_map.insert(
+                    hint,
+                    (
+                        idx,
+                        spec.name.clone(),
+                        system_cpu_util_threshold,
+                        dsq_insert_threshold,
+                    ),
+                );
             }
         }
+    }
+    Ok(())
+}
 
-        match spec.kind {
-            LayerKind::Confined {
-                cpus_range,
-                util_range,
-                ..
-            }
-            | LayerKind::Grouped {
-                cpus_range,
-                util_range,
-                ..
-            } => {
-                if let Some((cpus_min, cpus_max)) = cpus_range {
-                    if cpus_min > cpus_max {
-                        bail!(
-                            "Spec {:?} has invalid cpus_range({}, {})",
-                            spec.name,
-                            cpus_min,
-                            cpus_max
-                        );
-                    }
-                }
-                if util_range.0 >= util_range.1 {
+fn verify_layer_kind(spec: &LayerSpec) -> Result<()> {
+    match spec.kind {
+        LayerKind::Confined {
+            cpus_range,
+            util_range,
+            ..
+        }
+        | LayerKind::Grouped {
+            cpus_range,
+            util_range,
+            ..
+        } => {
+            if let Some((cpus_min, cpus_max)) = cpus_range {
+                if cpus_min > cpus_max {
                     bail!(
-                        "Spec {:?} has invalid util_range ({}, {})",
+                        "Spec {:?} has invalid cpus_range({}, {})",
                         spec.name,
-                        util_range.0,
-                        util_range.1
+                        cpus_min,
+                        cpus_max
                     );
                 }
             }
-            _ => {}
+            if util_range.0 >= util_range.1 {
+                bail!(
+                    "Spec {:?} has invalid util_range ({}, {})",
+                    spec.name,
+                    util_range.0,
+                    util_range.1
+                );
+            }
         }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn verify_layer_specs(specs: &[LayerSpec]) -> Result<HashMap<u64, HintLayerInfo>> {
+    let mut hint_to_layer_map = HintToLayerMap::new();
+
+    verify_nr_layer_specs(specs)?;
+    for (idx, spec) in specs.iter().enumerate() {
+        verify_layer_matches(specs, idx, &mut hint_to_layer_map)?;
+        verify_layer_kind(spec)?;
     }
 
     Ok(hint_to_layer_map
@@ -4941,6 +5292,208 @@ fn setup_membw_tracking(skel: &mut OpenBpfSkel) -> Result<u64> {
     Ok(config)
 }
 
+/// Build the layer config from @inputs: parse the specs, expand templates
+/// and fill in the defaults from @opts. Also used to re-read the config on live reload.
+fn load_layer_config(opts: &Opts, inputs: &[String]) -> Result<LayerConfig> {
+    let mut layer_config = match opts.run_example {
+        true => EXAMPLE_CONFIG.clone(),
+        false => LayerConfig { specs: vec![] },
+    };
+
+    for (idx, input) in inputs.iter().enumerate() {
+        let specs = LayerSpec::parse(input)
+            .context(format!("Failed to parse specs[{}] ({:?})", idx, input))?;
+
+        for spec in specs {
+            match spec.template {
+                Some(ref rule) => {
+                    let matches = expand_template(rule)?;
+                    // in the absence of matching cgroups, have template layers
+                    // behave as non-template layers do.
+                    if matches.is_empty() {
+                        layer_config.specs.push(spec);
+                    } else {
+                        for (mt, mask) in matches {
+                            let mut genspec = spec.clone();
+
+                            genspec.cpuset = Some(mask);
+
+                            // Push the new "and" rule into each "or" term.
+                            for orterm in &mut genspec.matches {
+                                orterm.push(mt.clone());
+                            }
+
+                            match &mt {
+                                LayerMatch::CgroupSuffix(cgroup) => genspec.name.push_str(cgroup),
+                                _ => bail!("Template match has unexpected type"),
+                            }
+
+                            // Push the generated layer into the config
+                            layer_config.specs.push(genspec);
+                        }
+                    }
+                }
+
+                None => {
+                    layer_config.specs.push(spec);
+                }
+            }
+        }
+    }
+
+    for spec in layer_config.specs.iter_mut() {
+        let common = spec.kind.common_mut();
+
+        if common.slice_us == 0 {
+            common.slice_us = opts.slice_us;
+        }
+
+        if common.weight == 0 {
+            common.weight = DEFAULT_LAYER_WEIGHT;
+        }
+        common.weight = common.weight.clamp(MIN_LAYER_WEIGHT, MAX_LAYER_WEIGHT);
+
+        if common.preempt {
+            if common.disallow_open_after_us.is_some() {
+                warn!(
+                    "Preempt layer {} has non-null disallow_open_after_us, ignored",
+                    &spec.name
+                );
+            }
+            if common.disallow_preempt_after_us.is_some() {
+                warn!(
+                    "Preempt layer {} has non-null disallow_preempt_after_us, ignored",
+                    &spec.name
+                );
+            }
+            common.disallow_open_after_us = Some(u64::MAX);
+            common.disallow_preempt_after_us = Some(u64::MAX);
+        } else {
+            if common.disallow_open_after_us.is_none() {
+                common.disallow_open_after_us = Some(*DFL_DISALLOW_OPEN_AFTER_US);
+            }
+
+            if common.disallow_preempt_after_us.is_none() {
+                common.disallow_preempt_after_us = Some(*DFL_DISALLOW_PREEMPT_AFTER_US);
+            }
+        }
+
+        if common.idle_smt.is_some() {
+            warn!("Layer {} has deprecated flag \"idle_smt\"", &spec.name);
+        }
+
+        if common.allow_node_aligned.is_some() {
+            warn!("Layer {} has deprecated flag \"allow_node_aligned\", node-aligned tasks are now always dispatched on layer DSQs", &spec.name);
+        }
+    }
+
+    Ok(layer_config)
+}
+
+/// Report everything verify_layer_specs() would reject @specs for as lint
+/// errors, instead of only the first problem. The cpus_range and util_range
+/// checks are left to check::lint_layer_specs(), which reports them in more
+/// detail.
+fn verify_layer_specs_lints(specs: &[LayerSpec]) -> Vec<check::Lint> {
+    let mut lints = vec![];
+    if let Err(e) = verify_nr_layer_specs(specs) {
+        lints.push(check::Lint {
+            level: check::LintLevel::Error,
+            layer: None,
+            msg: format!("{:#}", e),
+        });
+        if specs.is_empty() {
+            return lints;
+        }
+    }
+
+    let mut hint_to_layer_map = HintToLayerMap::new();
+    for (idx, spec) in specs.iter().enumerate() {
+        if let Err(e) = verify_layer_matches(specs, idx, &mut hint_to_layer_map) {
+            lints.push(check::Lint {
+                level: check::LintLevel::Error,
+                layer: Some(spec.name.clone()),
+                msg: format!("{:#}", e),
+            });
+        }
+    }
+    lints
+}
+
+fn run_check(opts: &Opts, check_opts: &CheckOpts) -> Result<()> {
+    let tasks = match &check_opts.snapshot {
+        Some(path) => serde_json::from_str::<Vec<check::TaskSnapshot>>(
+            &fs::read_to_string(path)
+                .with_context(|| format!("Failed to read snapshot {}", path))?,
+        )
+        .with_context(|| format!("Failed to parse snapshot {}", path))?,
+        None => check::capture_tasks(check_opts.threads)?,
+    };
+    if let Some(path) = &check_opts.save_snapshot {
+        fs::write(path, serde_json::to_string_pretty(&tasks)?)
+            .with_context(|| format!("Failed to write snapshot {}", path))?;
+    }
+
+    let layer_config = load_layer_config(opts, &check_opts.specs)?;
+    let specs = &layer_config.specs;
+
+    let mut lints = check::lint_layer_specs(specs);
+    lints.extend(verify_layer_specs_lints(specs));
+    for lint in lints.iter() {
+        println!("{}", lint);
+    }
+    let nr_errors = lints
+        .iter()
+        .filter(|lint| lint.level == check::LintLevel::Error)
+        .count();
+
+    if !check_opts.lint_only {
+        let matcher = check::LayerMatcher::new(specs)?;
+        let name_width = specs.iter().map(|spec| spec.name.len()).max().unwrap_or(0);
+        let mut nr_tasks = vec![0; specs.len()];
+
+        if !lints.is_empty() {
+            println!();
+        }
+        println!(
+            "{:>8} {:>8} {:<16} {:<name_width$} CGROUP",
+            "TID", "TGID", "COMM", "LAYER"
+        );
+        for task in tasks.iter() {
+            let assignment = matcher.assign(task);
+            let mut layer = match assignment.layer {
+                Some(idx) => {
+                    nr_tasks[idx] += 1;
+                    specs[idx].name.clone()
+                }
+                None => "-".into(),
+            };
+            if !assignment.maybe.is_empty() {
+                let maybe: Vec<&str> = assignment
+                    .maybe
+                    .iter()
+                    .map(|&idx| specs[idx].name.as_str())
+                    .collect();
+                layer = format!("{} (maybe {})", layer, maybe.join(", "));
+            }
+            println!(
+                "{:>8} {:>8} {:<16} {:<name_width$} {}",
+                task.tid, task.tgid, task.comm, layer, task.cgroup
+            );
+        }
+
+        println!();
+        for (spec, nr) in specs.iter().zip(nr_tasks.iter()) {
+            println!("{:<name_width$} {:>8} tasks", spec.name, nr);
+        }
+    }
+
+    if nr_errors > 0 {
+        bail!("{} error(s) found in the layer specification", nr_errors);
+    }
+    Ok(())
+}
+
 #[clap_main::clap_main]
 fn main(opts: Opts) -> Result<()> {
     if opts.version {
@@ -5000,6 +5553,10 @@ fn main(opts: Opts) -> Result<()> {
 
     debug!("opts={:?}", &opts);
 
+    if let Some(Command::Check(check_opts)) = &opts.command {
+        return run_check(&opts, check_opts);
+    }
+
     if let Some(run_id) = opts.run_id {
         info!("scx_layered run_id: {}", run_id);
     }
@@ -5044,97 +5601,7 @@ fn main(opts: Opts) -> Result<()> {
         return Ok(());
     }
 
-    let mut layer_config = match opts.run_example {
-        true => EXAMPLE_CONFIG.clone(),
-        false => LayerConfig { specs: vec![] },
-    };
-
-    for (idx, input) in opts.specs.iter().enumerate() {
-        let specs = LayerSpec::parse(input)
-            .context(format!("Failed to parse specs[{}] ({:?})", idx, input))?;
-
-        for spec in specs {
-            match spec.template {
-                Some(ref rule) => {
-                    let matches = expand_template(rule)?;
-                    // in the absence of matching cgroups, have template layers
-                    // behave as non-template layers do.
-                    if matches.is_empty() {
-                        layer_config.specs.push(spec);
-                    } else {
-                        for (mt, mask) in matches {
-                            let mut genspec = spec.clone();
-
-                            genspec.cpuset = Some(mask);
-
-                            // Push the new "and" rule into each "or" term.
-                            for orterm in &mut genspec.matches {
-                                orterm.push(mt.clone());
-                            }
-
-                            match &mt {
-                                LayerMatch::CgroupSuffix(cgroup) => genspec.name.push_str(cgroup),
-                                _ => bail!("Template match has unexpected type"),
-                            }
-
-                            // Push the generated layer into the config
-                            layer_config.specs.push(genspec);
-                        }
-                    }
-                }
-
-                None => {
-                    layer_config.specs.push(spec);
-                }
-            }
-        }
-    }
-
-    for spec in layer_config.specs.iter_mut() {
-        let common = spec.kind.common_mut();
-
-        if common.slice_us == 0 {
-            common.slice_us = opts.slice_us;
-        }
-
-        if common.weight == 0 {
-            common.weight = DEFAULT_LAYER_WEIGHT;
-        }
-        common.weight = common.weight.clamp(MIN_LAYER_WEIGHT, MAX_LAYER_WEIGHT);
-
-        if common.preempt {
-            if common.disallow_open_after_us.is_some() {
-                warn!(
-                    "Preempt layer {} has non-null disallow_open_after_us, ignored",
-                    &spec.name
-                );
-            }
-            if common.disallow_preempt_after_us.is_some() {
-                warn!(
-                    "Preempt layer {} has non-null disallow_preempt_after_us, ignored",
-                    &spec.name
-                );
-            }
-            common.disallow_open_after_us = Some(u64::MAX);
-            common.disallow_preempt_after_us = Some(u64::MAX);
-        } else {
-            if common.disallow_open_after_us.is_none() {
-                common.disallow_open_after_us = Some(*DFL_DISALLOW_OPEN_AFTER_US);
-            }
-
-            if common.disallow_preempt_after_us.is_none() {
-                common.disallow_preempt_after_us = Some(*DFL_DISALLOW_PREEMPT_AFTER_US);
-            }
-        }
-
-        if common.idle_smt.is_some() {
-            warn!("Layer {} has deprecated flag \"idle_smt\"", &spec.name);
-        }
-
-        if common.allow_node_aligned.is_some() {
-            warn!("Layer {} has deprecated flag \"allow_node_aligned\", node-aligned tasks are now always dispatched on layer DSQs", &spec.name);
-        }
-    }
+    let layer_config = load_layer_config(&opts, &opts.specs)?;
 
     let membw_required = layer_config.specs.iter().any(|spec| match spec.kind {
         LayerKind::Confined { membw_gb, .. } | LayerKind::Grouped { membw_gb, .. } => {
@@ -5168,6 +5635,151 @@ fn main(opts: Opts) -> Result<()> {
     Ok(())
 }
 
+#[cfg(test)]
+mod reload_tests {
+    use super::*;
+    use scx_utils::testutils::make_test_topo;
+
+    fn specs(json: &str) -> Vec<LayerSpec> {
+        serde_json::from_str::<LayerConfig>(json).unwrap().specs
+    }
+
+    // load_layer_config() fills in the disallow_*_after_us defaults, which
+    // init_layer() expects, from the kernel. Set them explicitly instead.
+    const CUR: &str = r#"[
+        {
+            "name": "batch",
+            "matches": [[{"CommPrefix": "batch"}]],
+            "kind": {"Confined": {
+                "util_range": [0.8, 0.9],
+                "slice_us": 20000,
+                "disallow_open_after_us": 40000,
+                "disallow_preempt_after_us": 80000
+            }}
+        },
+        {
+            "name": "normal",
+            "matches": [[]],
+            "kind": {"Open": {
+                "disallow_open_after_us": 40000,
+                "disallow_preempt_after_us": 80000
+            }}
+        }
+    ]"#;
+
+    fn running_layers(specs: &[LayerSpec], topo: &Topology) -> Vec<types::layer> {
+        specs
+            .iter()
+            .map(|spec| {
+                let mut layer = types::layer::default();
+                Scheduler::init_layer(&mut layer, spec, topo, &mut 0, &mut HashMap::new()).unwrap();
+                layer
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_stage_and_publish() {
+        let (topo, _) = make_test_topo(1, 1, 2, 1);
+        let cur = specs(CUR);
+        let new = specs(
+            &CUR.replace(r#""batch"}"#, r#""worker"}"#)
+                .replace(r#""slice_us": 20000"#, r#""slice_us": 5000"#),
+        );
+        let changed = diff_layer_specs(&cur, &new).unwrap();
+        assert_eq!(changed, vec![0]);
+
+        let mut layers = running_layers(&cur, &topo);
+        let old_prefix = layers[0].matches[0].matches[0].comm_prefix;
+
+        // Runtime state maintained by BPF.
+        layers[0].nr_tasks = 3;
+        layers[0].nr_cpus = 2;
+
+        // Staging doesn't touch the running layers.
+        let staged = Scheduler::stage_layers(&layers, &new, &changed, &topo).unwrap();
+        assert_eq!(staged.len(), 1);
+        assert_eq!(staged[0].slice_ns, 5_000_000);
+        assert_eq!(layers[0].slice_ns, 20_000_000);
+        assert_eq!(layers[0].matches[0].matches[0].comm_prefix, old_prefix);
+
+        let mut seq = 2;
+        Scheduler::publish_layers(&mut layers, &mut seq, &changed, &staged);
+        assert_eq!(seq, 4);
+        assert_eq!(layers[0].slice_ns, 5_000_000);
+        assert_eq!(
+            layers[0].matches[0].matches[0].comm_prefix,
+            staged[0].matches[0].matches[0].comm_prefix
+        );
+        assert_ne!(layers[0].matches[0].matches[0].comm_prefix, old_prefix);
+        assert_eq!(layers[0].nr_tasks, 3);
+        assert_eq!(layers[0].nr_cpus, 2);
+        assert_eq!(layers[1].slice_ns, running_layers(&cur, &topo)[1].slice_ns);
+    }
+
+    #[test]
+    fn test_stage_invalid() {
+        let (topo, _) = make_test_topo(1, 1, 2, 1);
+        let cur = specs(CUR);
+
+        // Both layers change, the second one refers to a missing NUMA node:
+        // nothing is staged.
+        let new = specs(
+            &CUR.replace(r#""slice_us": 20000"#, r#""slice_us": 5000"#)
+                .replace(r#""matches": [[]]"#, r#""matches": [[{"NumaNode": 4}]]"#),
+        );
+        let changed = diff_layer_specs(&cur, &new).unwrap();
+        assert_eq!(changed, vec![0, 1]);
+
+        let layers = running_layers(&cur, &topo);
+        let err = Scheduler::stage_layers(&layers, &new, &changed, &topo).unwrap_err();
+        assert!(err.to_string().contains("invalid NUMA node ID"));
+    }
+}
+
+#[cfg(test)]
+mod check_tests {
+    use super::*;
+
+    fn check_lints(json: &str) -> Vec<String> {
+        let specs = serde_json::from_str::<LayerConfig>(json).unwrap().specs;
+        let mut lints = check::lint_layer_specs(&specs);
+        lints.extend(verify_layer_specs_lints(&specs));
+        lints.iter().map(|lint| lint.to_string()).collect()
+    }
+
+    #[test]
+    fn test_verify_lints() {
+        // Every problem is reported, and the invalid ranges are left to the
+        // lints.
+        let lints = check_lints(
+            r#"[
+                {
+                    "name": "a",
+                    "matches": [[{"CommPrefix": "a"}]],
+                    "kind": {"Confined": {"util_range": [0.9, 0.8], "cpus_range": [4, 2]}}
+                },
+                {"name": "b", "matches": [], "kind": {"Open": {}}},
+                {
+                    "name": "c",
+                    "matches": [[{"HintEquals": 1}, {"CommPrefix": "c"}]],
+                    "kind": {"Open": {}}
+                },
+                {"name": "normal", "matches": [[{"CommPrefix": "x"}]], "kind": {"Open": {}}}
+            ]"#,
+        );
+        assert_eq!(lints.len(), 5, "{:?}", lints);
+        assert!(lints[0].starts_with("error: layer \"a\": invalid util_range"));
+        assert!(lints[1].starts_with("error: layer \"a\": cpus_range (4, 2)"));
+        assert!(lints[2].starts_with("error: layer \"b\": Non-terminal spec"));
+        assert!(lints[3].starts_with("error: layer \"c\": HintEquals match cannot"));
+        assert!(lints[4].starts_with("error: layer \"normal\": Terminal spec"));
+
+        let lints = check_lints(r#"[]"#);
+        assert_eq!(lints, ["error: No layer spec"]);
+    }
+}
+
 #[cfg(test)]
 mod xnuma_tests {
     use super::*;
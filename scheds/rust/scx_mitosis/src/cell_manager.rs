@@ -15,9 +15,15 @@ use std::os::unix::io::{AsFd, BorrowedFd};
 use std::path::{Path, PathBuf};
 
 use anyhow::{bail, Context, Result};
-use inotify::{Inotify, WatchMask};
+use inotify::{Inotify, WatchDescriptor, WatchMask};
 use scx_utils::Cpumask;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
+
+/// Default cgroup v2 cpu.weight. Cells with this weight get an equal share.
+const CPU_WEIGHT_DEFAULT: u64 = 100;
+
+/// Cgroup v2 files that influence cell sizing and are watched for changes.
+const CPU_CONTROL_FILES: [&str; 3] = ["cpu.weight", "cpu.max", "cpuset.cpus.exclusive"];
 
 /// Information about a cell created for a cgroup
 #[derive(Debug)]
@@ -27,6 +33,115 @@ pub struct CellInfo {
     pub cgid: Option<u64>,
     /// Optional cpuset mask if the cgroup has cpuset.cpus configured
     pub cpuset: Option<Cpumask>,
+    /// CPU controls read from the cgroup's cpu and cpuset controller files
+    pub controls: CpuControls,
+}
+
+/// Cgroup v2 CPU controls that shape a cell's CPU allocation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CpuControls {
+    /// cpu.weight (1-10000). Scales the cell's share of CPUs relative to
+    /// other cells.
+    pub weight: u64,
+    /// cpu.max quota rounded up to whole CPUs. The cell never receives more
+    /// primary CPUs than this, apart from its exclusive CPUs.
+    pub max_cpus: Option<usize>,
+    /// cpuset.cpus.exclusive. These CPUs always go to this cell and are
+    /// never given to, or borrowed by, other cells.
+    pub exclusive: Option<Cpumask>,
+}
+
+impl Default for CpuControls {
+    fn default() -> Self {
+        Self {
+            weight: CPU_WEIGHT_DEFAULT,
+            max_cpus: None,
+            exclusive: None,
+        }
+    }
+}
+
+impl CpuControls {
+    /// Multiplier applied to the cell's sizing weight.
+    fn weight_factor(&self) -> f64 {
+        self.weight as f64 / CPU_WEIGHT_DEFAULT as f64
+    }
+}
+
+/// Parse the contents of cpu.weight.
+fn parse_cpu_weight(content: &str) -> Result<u64> {
+    let content = content.trim();
+    let weight: u64 = content
+        .parse()
+        .with_context(|| format!("invalid cpu.weight '{}'", content))?;
+    if !(1..=10000).contains(&weight) {
+        bail!("cpu.weight {} out of range [1, 10000]", weight);
+    }
+    Ok(weight)
+}
+
+/// Parse the contents of cpu.max ("$MAX $PERIOD") into a CPU count cap.
+/// Returns None if the quota is "max" (unlimited).
+fn parse_cpu_max(content: &str) -> Result<Option<usize>> {
+    let mut fields = content.split_whitespace();
+    let (Some(quota), Some(period), None) = (fields.next(), fields.next(), fields.next()) else {
+        bail!("invalid cpu.max '{}'", content.trim());
+    };
+    if quota == "max" {
+        return Ok(None);
+    }
+    let quota: u64 = quota
+        .parse()
+        .with_context(|| format!("invalid cpu.max quota '{}'", quota))?;
+    let period: u64 = period
+        .parse()
+        .with_context(|| format!("invalid cpu.max period '{}'", period))?;
+    if quota == 0 || period == 0 {
+        bail!("invalid cpu.max '{} {}'", quota, period);
+    }
+    Ok(Some(quota.div_ceil(period) as usize))
+}
+
+/// Lower per-cell targets to their caps and hand the excess to uncapped cells.
+///
+/// Excess CPUs are given out one at a time to the eligible cell with the
+/// lowest target-to-weight ratio. Cell 0 is never capped, so the excess always
+/// has somewhere to go.
+fn cap_targets(
+    targets: &mut HashMap<u32, usize>,
+    cells: &[(u32, f64)],
+    caps: &HashMap<u32, usize>,
+) {
+    let mut excess = 0;
+    for (cell_id, target) in targets.iter_mut() {
+        if let Some(&cap) = caps.get(cell_id) {
+            if *target > cap {
+                excess += *target - cap;
+                *target = cap;
+            }
+        }
+    }
+
+    while excess > 0 {
+        let Some(cell_id) = cells
+            .iter()
+            .filter(|(cell_id, _)| caps.get(cell_id).is_none_or(|&cap| targets[cell_id] < cap))
+            .min_by(|(a_id, a_w), (b_id, b_w)| {
+                let a = (targets[a_id] + 1) as f64 / a_w.max(f64::EPSILON);
+                let b = (targets[b_id] + 1) as f64 / b_w.max(f64::EPSILON);
+                a.partial_cmp(&b)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then(a_id.cmp(b_id))
+            })
+            .map(|(cell_id, _)| *cell_id)
+        else {
+            break;
+        };
+        *targets
+            .get_mut(&cell_id)
+            .expect("BUG: cell missing from targets") += 1;
+        excess -= 1;
+    }
 }
 
 /// Compute the global target CPU count for each cell.
@@ -204,10 +319,24 @@ pub struct CpuAssignment {
     pub borrowable: Option<Cpumask>,
 }
 
+/// Changes observed by a call to [`CellManager::process_events`].
+#[derive(Debug, Default)]
+pub struct CellEvents {
+    /// (cgid, cell_id) for newly created cells
+    pub new_cells: Vec<(u64, u32)>,
+    /// Cell IDs of destroyed cells
+    pub destroyed_cells: Vec<u32>,
+    /// Whether any existing cell's CPU controls changed
+    pub controls_changed: bool,
+}
+
 /// Manages cells for direct child cgroups of a specified parent
 pub struct CellManager {
     cell_parent_path: PathBuf,
     inotify: Inotify,
+    /// Watch on the parent directory. Events on any other watch come from
+    /// a cell's CPU control files.
+    parent_wd: WatchDescriptor,
     /// Maps cgroup ID to cell info
     cells: HashMap<u64, CellInfo>,
     /// Maps cell ID to cgroup ID (for reverse lookup)
@@ -243,7 +372,7 @@ impl CellManager {
         exclude: HashSet<String>,
     ) -> Result<Self> {
         let inotify = Inotify::init().context("Failed to initialize inotify")?;
-        inotify
+        let parent_wd = inotify
             .watches()
             .add(&path, WatchMask::CREATE | WatchMask::DELETE)
             .context("Failed to add inotify watch")?;
@@ -251,6 +380,7 @@ impl CellManager {
         let mut mgr = Self {
             cell_parent_path: path.clone(),
             inotify,
+            parent_wd,
             cells: HashMap::new(),
             cell_id_to_cgid: HashMap::new(),
             free_cell_ids: Vec::new(),
@@ -269,6 +399,7 @@ impl CellManager {
                 cgroup_path: None,
                 cgid: None,
                 cpuset: None,
+                controls: CpuControls::default(),
             },
         );
         mgr.cell_id_to_cgid.insert(0, 0);
@@ -325,23 +456,31 @@ impl CellManager {
         Ok(assignments)
     }
 
-    /// Process pending inotify events. Returns list of (cgid, cell_id) for new cells
-    /// and list of cell_ids that were destroyed.
+    /// Process pending inotify events. Returns the cells created and destroyed, and
+    /// whether any cell's CPU controls changed.
     ///
-    /// Rather than processing individual events, we simply check if any events occurred
-    /// and then rescan the directory to reconcile state. This is simpler and handles
-    /// edge cases like inotify queue overflow gracefully.
-    pub fn process_events(&mut self) -> Result<(Vec<(u64, u32)>, Vec<u32>)> {
+    /// Rather than processing individual events, we simply check which watches fired
+    /// and then rescan the directory or re-read the control files to reconcile state.
+    /// This is simpler and handles edge cases like inotify queue overflow gracefully.
+    pub fn process_events(&mut self) -> Result<CellEvents> {
         let mut buffer = [0; 1024];
-        let mut has_events = false;
+        let mut dir_changed = false;
+        let mut controls_touched = false;
 
         // Drain all pending events
         loop {
             match self.inotify.read_events(&mut buffer) {
                 Ok(events) => {
-                    if events.into_iter().next().is_some() {
-                        has_events = true;
-                    } else {
+                    let mut any = false;
+                    for event in events {
+                        any = true;
+                        if event.wd == self.parent_wd {
+                            dir_changed = true;
+                        } else {
+                            controls_touched = true;
+                        }
+                    }
+                    if !any {
                         break;
                     }
                 }
@@ -352,12 +491,19 @@ impl CellManager {
             }
         }
 
-        if !has_events {
-            return Ok((Vec::new(), Vec::new()));
+        let mut result = CellEvents::default();
+        if dir_changed {
+            // Rescan directory and reconcile with our tracked state
+            let (new_cells, destroyed_cells) = self.reconcile_cells()?;
+            result.new_cells = new_cells;
+            result.destroyed_cells = destroyed_cells;
         }
-
-        // Rescan directory and reconcile with our tracked state
-        self.reconcile_cells()
+        if controls_touched {
+            result.controls_changed = self
+                .refresh_cpu_controls()
+                .context("refreshing cell CPU controls")?;
+        }
+        Ok(result)
     }
 
     /// Reconcile our tracked cells with the actual cgroup directory contents.
@@ -469,6 +615,15 @@ impl CellManager {
             );
         }
 
+        // Watch control files before reading them so a write racing with
+        // cell creation is picked up by the next process_events().
+        self.watch_cpu_controls(path);
+        let controls = Self::read_cpu_controls(path)
+            .with_context(|| format!("reading CPU controls for cgroup {}", path.display()))?;
+        if controls != CpuControls::default() {
+            debug!("Cell {} has CPU controls: {:?}", cell_id, controls);
+        }
+
         self.cells.insert(
             cgid,
             CellInfo {
@@ -476,6 +631,7 @@ impl CellManager {
                 cgroup_path: Some(path.to_path_buf()),
                 cgid: Some(cgid),
                 cpuset,
+                controls,
             },
         );
         self.cell_id_to_cgid.insert(cell_id, cgid);
@@ -492,8 +648,12 @@ impl CellManager {
 
     /// Read cpuset.cpus from a cgroup path. Returns None if empty or unavailable.
     fn read_cpuset(cgroup_path: &Path) -> Result<Option<Cpumask>> {
-        let cpuset_path = cgroup_path.join("cpuset.cpus");
-        match std::fs::read_to_string(&cpuset_path) {
+        Self::read_cpulist(&cgroup_path.join("cpuset.cpus"))
+    }
+
+    /// Read a cpulist file. Returns None if empty or unavailable.
+    fn read_cpulist(cpuset_path: &Path) -> Result<Option<Cpumask>> {
+        match std::fs::read_to_string(cpuset_path) {
             Ok(content) => {
                 let content = content.trim();
                 if content.is_empty() {
@@ -514,6 +674,41 @@ impl CellManager {
         }
     }
 
+    /// Read cpu.weight, cpu.max and cpuset.cpus.exclusive from a cgroup path.
+    /// Missing files (controller not enabled) fall back to the defaults.
+    fn read_cpu_controls(cgroup_path: &Path) -> Result<CpuControls> {
+        let mut controls = CpuControls::default();
+
+        let weight_path = cgroup_path.join("cpu.weight");
+        if let Ok(content) = std::fs::read_to_string(&weight_path) {
+            controls.weight = parse_cpu_weight(&content)
+                .with_context(|| format!("parsing {}", weight_path.display()))?;
+        }
+
+        let max_path = cgroup_path.join("cpu.max");
+        if let Ok(content) = std::fs::read_to_string(&max_path) {
+            controls.max_cpus = parse_cpu_max(&content)
+                .with_context(|| format!("parsing {}", max_path.display()))?;
+        }
+
+        controls.exclusive = Self::read_cpulist(&cgroup_path.join("cpuset.cpus.exclusive"))?;
+        Ok(controls)
+    }
+
+    /// Add inotify watches on a cgroup's CPU control files. Watches are dropped
+    /// by the kernel when the cgroup is removed.
+    fn watch_cpu_controls(&self, cgroup_path: &Path) {
+        for name in CPU_CONTROL_FILES {
+            let file = cgroup_path.join(name);
+            if !file.exists() {
+                continue;
+            }
+            if let Err(e) = self.inotify.watches().add(&file, WatchMask::MODIFY) {
+                warn!("Failed to watch {}: {}", file.display(), e);
+            }
+        }
+    }
+
     fn allocate_cell_id(&mut self) -> Result<u32> {
         // Prefer reusing freed IDs to keep cell ID space compact
         if let Some(id) = self.free_cell_ids.pop() {
@@ -575,18 +770,36 @@ impl CellManager {
             }
         }
 
-        // Phase 1: Build contention map - for each CPU, track which cells claim it
+        // Phase 1: Build contention map - for each CPU, track which cells claim it.
+        // CPUs in a cell's cpuset.cpus.exclusive belong to that cell alone and
+        // are kept out of the contention map. Sibling cgroups can't have
+        // overlapping exclusive CPUs, but if they do, the lowest cell ID wins.
+        let mut exclusive_owner: HashMap<usize, u32> = HashMap::new();
+        let mut sorted_cells: Vec<&CellInfo> = self.cells.values().collect();
+        sorted_cells.sort_by_key(|info| info.cell_id);
+        for cell_info in &sorted_cells {
+            if let Some(ref exclusive) = cell_info.controls.exclusive {
+                for cpu in exclusive.iter() {
+                    exclusive_owner.entry(cpu).or_insert(cell_info.cell_id);
+                }
+            }
+        }
+
         let mut contention: HashMap<usize, Vec<u32>> = HashMap::new();
         for cell_info in self.cells.values() {
             if let Some(ref cpuset) = cell_info.cpuset {
                 for cpu in cpuset.iter() {
+                    if exclusive_owner.contains_key(&cpu) {
+                        continue;
+                    }
                     contention.entry(cpu).or_default().push(cell_info.cell_id);
                 }
             }
         }
 
         // Phase 2: Categorize CPUs and build initial assignments
-        // - Exclusive: claimed by exactly 1 cell -> assigned directly
+        // - Exclusive: in a cell's cpuset.cpus.exclusive or claimed by exactly
+        //   1 cell -> assigned directly
         // - Contested: claimed by 2+ cells -> distributed by weight
         // - Unclaimed: no cpuset claims it -> shared among cell 0 + unpinned cells
         let mut cell_cpus: HashMap<u32, Cpumask> = HashMap::new();
@@ -594,6 +807,14 @@ impl CellManager {
         let mut unclaimed_cpus: Vec<usize> = Vec::new();
 
         for cpu in self.all_cpus.iter() {
+            if let Some(&cell_id) = exclusive_owner.get(&cpu) {
+                cell_cpus
+                    .entry(cell_id)
+                    .or_insert_with(Cpumask::new)
+                    .set_cpu(cpu)
+                    .ok();
+                continue;
+            }
             match contention.get(&cpu) {
                 None => unclaimed_cpus.push(cpu),
                 Some(claimants) if claimants.len() == 1 => {
@@ -608,7 +829,9 @@ impl CellManager {
             }
         }
 
-        // Compute global targets and initialize running count of CPUs assigned per cell
+        // Compute global targets and initialize running count of CPUs assigned per cell.
+        // Demand (or the equal default) is scaled by the cgroup's cpu.weight, and
+        // targets are then clamped to cpu.max.
         let total_cpu_count = self.all_cpus.weight();
         let mut all_cells_with_weights: Vec<(u32, f64)> = self
             .cells
@@ -620,14 +843,21 @@ impl CellManager {
                     })?,
                     None => 1.0,
                 };
-                Ok((info.cell_id, weight))
+                Ok((info.cell_id, weight * info.controls.weight_factor()))
             })
             .collect::<Result<Vec<_>>>()
             .context("building cell demand weights map")?;
         all_cells_with_weights.sort_by_key(|(cell_id, _)| *cell_id);
 
-        let targets = compute_targets(total_cpu_count, &all_cells_with_weights)
+        let caps: HashMap<u32, usize> = self
+            .cells
+            .values()
+            .filter_map(|info| info.controls.max_cpus.map(|cap| (info.cell_id, cap)))
+            .collect();
+
+        let mut targets = compute_targets(total_cpu_count, &all_cells_with_weights)
             .context("computing per-cell CPU targets")?;
+        cap_targets(&mut targets, &all_cells_with_weights, &caps);
 
         // Seed assigned_count from exclusive assignments
         let mut assigned_count: HashMap<u32, usize> = HashMap::new();
@@ -755,7 +985,40 @@ impl CellManager {
             }
         }
 
-        // Phase 5: Verify all cells have at least one CPU assigned
+        // Phase 5: Enforce cpu.max caps. The deficit weights above keep most
+        // cells within their cap, but the equal-weight fallbacks and pinned
+        // cpusets can overshoot. Move the excess to cell 0, highest CPUs first,
+        // and never take away a cell's exclusive CPUs.
+        let mut caps_sorted: Vec<(u32, usize)> = caps.iter().map(|(&id, &cap)| (id, cap)).collect();
+        caps_sorted.sort_by_key(|(cell_id, _)| *cell_id);
+        for (cell_id, cap) in caps_sorted {
+            let Some(mask) = cell_cpus.get_mut(&cell_id) else {
+                continue;
+            };
+            let mut over = mask.weight().saturating_sub(cap);
+            if over == 0 {
+                continue;
+            }
+            let mut trimmed = Vec::new();
+            let cpus: Vec<usize> = mask.iter().collect();
+            for &cpu in cpus.iter().rev() {
+                if over == 0 {
+                    break;
+                }
+                if exclusive_owner.get(&cpu) == Some(&cell_id) {
+                    continue;
+                }
+                mask.clear_cpu(cpu).ok();
+                trimmed.push(cpu);
+                over -= 1;
+            }
+            let cell0 = cell_cpus.entry(0).or_insert_with(Cpumask::new);
+            for cpu in trimmed {
+                cell0.set_cpu(cpu).ok();
+            }
+        }
+
+        // Phase 6: Verify all cells have at least one CPU assigned
         for info in self.cells.values() {
             if !cell_cpus.contains_key(&info.cell_id)
                 || cell_cpus
@@ -771,13 +1034,20 @@ impl CellManager {
             }
         }
 
-        // Phase 6: Build CpuAssignment results, optionally computing borrowable masks
+        // Phase 7: Build CpuAssignment results, optionally computing borrowable masks
         let assignments: Vec<CpuAssignment> = cell_cpus
             .into_iter()
             .map(|(cell_id, primary)| {
                 let borrowable = if compute_borrowable {
                     let mut borrow_mask = self.all_cpus.and(&primary.not());
 
+                    // Other cells' exclusive CPUs are never lent out
+                    for (&cpu, &owner) in &exclusive_owner {
+                        if owner != cell_id {
+                            borrow_mask.clear_cpu(cpu).ok();
+                        }
+                    }
+
                     // If this cell has a cpuset, restrict borrowable to it
                     if let Some(cell_info) = self.cells.values().find(|c| c.cell_id == cell_id) {
                         if let Some(ref cpuset) = cell_info.cpuset {
@@ -844,6 +1114,35 @@ impl CellManager {
         parts.join(" ")
     }
 
+    /// Re-read cpu.weight, cpu.max and cpuset.cpus.exclusive for all cells and
+    /// update stored controls. Returns true if any cell's controls changed.
+    pub fn refresh_cpu_controls(&mut self) -> Result<bool> {
+        let mut changed = false;
+        for info in self.cells.values_mut() {
+            let Some(ref cgroup_path) = info.cgroup_path else {
+                continue; // cell 0 has no cgroup
+            };
+            if !cgroup_path.exists() {
+                continue; // being removed; reconcile_cells() will drop it
+            }
+            let new_controls = Self::read_cpu_controls(cgroup_path).with_context(|| {
+                format!("reading CPU controls for cgroup {}", cgroup_path.display())
+            })?;
+            if new_controls != info.controls {
+                info!(
+                    "Cell {} CPU controls changed: {:?} -> {:?} ({})",
+                    info.cell_id,
+                    info.controls,
+                    new_controls,
+                    cgroup_path.display(),
+                );
+                info.controls = new_controls;
+                changed = true;
+            }
+        }
+        Ok(changed)
+    }
+
     /// Re-read cpuset.cpus for all cells and update stored cpusets.
     /// Returns true if any cell's cpuset changed.
     pub fn refresh_cpusets(&mut self) -> Result<bool> {
@@ -2645,4 +2944,217 @@ mod tests {
         let total: usize = assignments.iter().map(|a| a.primary.weight()).sum();
         assert_eq!(total, 56, "All CPUs must be assigned");
     }
+
+    // ==================== cgroup CPU control tests ====================
+
+    #[test]
+    fn test_parse_cpu_controls() {
+        assert_eq!(parse_cpu_weight("100\n").unwrap(), 100);
+        assert!(parse_cpu_weight("0").is_err());
+        assert!(parse_cpu_weight("10001").is_err());
+
+        assert_eq!(parse_cpu_max("max 100000\n").unwrap(), None);
+        assert_eq!(parse_cpu_max("400000 100000\n").unwrap(), Some(4));
+        // Fractional quotas round up to whole CPUs
+        assert_eq!(parse_cpu_max("150000 100000").unwrap(), Some(2));
+        assert_eq!(parse_cpu_max("1000 100000").unwrap(), Some(1));
+        assert!(parse_cpu_max("max").is_err());
+        assert!(parse_cpu_max("0 100000").is_err());
+    }
+
+    #[test]
+    fn test_cpu_weight_scales_targets() {
+        let tmp = TempDir::new().unwrap();
+        let heavy = tmp.path().join("heavy");
+        std::fs::create_dir(&heavy).unwrap();
+        std::fs::write(heavy.join("cpu.weight"), "300\n").unwrap();
+        let light = tmp.path().join("light");
+        std::fs::create_dir(&light).unwrap();
+        std::fs::write(light.join("cpu.weight"), "100\n").unwrap();
+
+        let mgr = CellManager::new_with_path(
+            tmp.path().to_path_buf(),
+            256,
+            cpumask_for_range(22),
+            HashSet::new(),
+        )
+        .unwrap();
+
+        let heavy_id = mgr.find_cell_by_name("heavy").unwrap().cell_id;
+        let light_id = mgr.find_cell_by_name("light").unwrap().cell_id;
+        let assignments = mgr.compute_cpu_assignments(false).unwrap();
+        let count = |id| {
+            assignments
+                .iter()
+                .find(|a| a.cell_id == id)
+                .unwrap()
+                .primary
+                .weight()
+        };
+
+        // Weights 100:300:100 over 22 CPUs with a 1-CPU floor each
+        assert_eq!(count(0), 5);
+        assert_eq!(count(heavy_id), 12);
+        assert_eq!(count(light_id), 5);
+    }
+
+    #[test]
+    fn test_cpu_max_caps_cell() {
+        let tmp = TempDir::new().unwrap();
+        let capped = tmp.path().join("capped");
+        std::fs::create_dir(&capped).unwrap();
+        std::fs::write(capped.join("cpu.max"), "200000 100000\n").unwrap();
+        std::fs::create_dir(tmp.path().join("free")).unwrap();
+
+        let mgr = CellManager::new_with_path(
+            tmp.path().to_path_buf(),
+            256,
+            cpumask_for_range(12),
+            HashSet::new(),
+        )
+        .unwrap();
+
+        let capped_id = mgr.find_cell_by_name("capped").unwrap().cell_id;
+        let free_id = mgr.find_cell_by_name("free").unwrap().cell_id;
+        let assignments = mgr.compute_cpu_assignments(false).unwrap();
+        let count = |id| {
+            assignments
+                .iter()
+                .find(|a| a.cell_id == id)
+                .unwrap()
+                .primary
+                .weight()
+        };
+
+        // The capped cell's excess is split between the other two cells
+        assert_eq!(count(capped_id), 2);
+        assert_eq!(count(0), 5);
+        assert_eq!(count(free_id), 5);
+    }
+
+    #[test]
+    fn test_cpu_max_caps_pinned_cell() {
+        let tmp = TempDir::new().unwrap();
+        let capped = tmp.path().join("capped");
+        std::fs::create_dir(&capped).unwrap();
+        std::fs::write(capped.join("cpuset.cpus"), "0-7\n").unwrap();
+        std::fs::write(capped.join("cpu.max"), "300000 100000\n").unwrap();
+
+        let mgr = CellManager::new_with_path(
+            tmp.path().to_path_buf(),
+            256,
+            cpumask_for_range(16),
+            HashSet::new(),
+        )
+        .unwrap();
+
+        let capped_id = mgr.find_cell_by_name("capped").unwrap().cell_id;
+        let assignments = mgr.compute_cpu_assignments(false).unwrap();
+        let capped_cell = assignments.iter().find(|a| a.cell_id == capped_id).unwrap();
+        let cell0 = assignments.iter().find(|a| a.cell_id == 0).unwrap();
+
+        // The cpuset's lowest CPUs are kept, the rest go to cell 0
+        assert_eq!(capped_cell.primary.weight(), 3);
+        for cpu in 0..3 {
+            assert!(capped_cell.primary.test_cpu(cpu));
+        }
+        assert_eq!(cell0.primary.weight(), 13);
+    }
+
+    #[test]
+    fn test_cpuset_exclusive_not_shared() {
+        let tmp = TempDir::new().unwrap();
+
+        // Both cells claim 0-7, but cell1 owns 0-3 exclusively
+        let cell1_path = tmp.path().join("cell1");
+        std::fs::create_dir(&cell1_path).unwrap();
+        std::fs::write(cell1_path.join("cpuset.cpus"), "0-7\n").unwrap();
+        std::fs::write(cell1_path.join("cpuset.cpus.exclusive"), "0-3\n").unwrap();
+        let cell2_path = tmp.path().join("cell2");
+        std::fs::create_dir(&cell2_path).unwrap();
+        std::fs::write(cell2_path.join("cpuset.cpus"), "0-7\n").unwrap();
+
+        let mgr = CellManager::new_with_path(
+            tmp.path().to_path_buf(),
+            256,
+            cpumask_for_range(16),
+            HashSet::new(),
+        )
+        .unwrap();
+
+        let cell1_id = mgr.find_cell_by_name("cell1").unwrap().cell_id;
+        let cell2_id = mgr.find_cell_by_name("cell2").unwrap().cell_id;
+        let assignments = mgr.compute_cpu_assignments(true).unwrap();
+        let cell1 = assignments.iter().find(|a| a.cell_id == cell1_id).unwrap();
+        let cell2 = assignments.iter().find(|a| a.cell_id == cell2_id).unwrap();
+
+        for cpu in 0..4 {
+            assert!(cell1.primary.test_cpu(cpu));
+            assert!(!cell2.primary.test_cpu(cpu));
+            // cell2 can't borrow cell1's exclusive CPUs
+            assert!(!cell2.borrowable.as_ref().unwrap().test_cpu(cpu));
+        }
+        // Only the non-exclusive part of the cpusets is contested
+        for cpu in 4..8 {
+            assert!(cell1.primary.test_cpu(cpu) ^ cell2.primary.test_cpu(cpu));
+        }
+        assert!(cell2.primary.weight() >= 1);
+    }
+
+    #[test]
+    fn test_refresh_cpu_controls() {
+        let tmp = TempDir::new().unwrap();
+        let cell_path = tmp.path().join("cell1");
+        std::fs::create_dir(&cell_path).unwrap();
+        std::fs::write(cell_path.join("cpu.weight"), "100\n").unwrap();
+        std::fs::write(cell_path.join("cpu.max"), "max 100000\n").unwrap();
+
+        let mut mgr = CellManager::new_with_path(
+            tmp.path().to_path_buf(),
+            256,
+            cpumask_for_range(16),
+            HashSet::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            mgr.find_cell_by_name("cell1").unwrap().controls,
+            CpuControls::default()
+        );
+
+        // Unchanged files are not reported as a change
+        assert!(!mgr.refresh_cpu_controls().unwrap());
+
+        std::fs::write(cell_path.join("cpu.weight"), "200\n").unwrap();
+        std::fs::write(cell_path.join("cpu.max"), "50000 100000\n").unwrap();
+        assert!(mgr.refresh_cpu_controls().unwrap());
+
+        let controls = &mgr.find_cell_by_name("cell1").unwrap().controls;
+        assert_eq!(controls.weight, 200);
+        assert_eq!(controls.max_cpus, Some(1));
+    }
+
+    #[test]
+    fn test_process_events_reports_control_changes() {
+        let tmp = TempDir::new().unwrap();
+        let cell_path = tmp.path().join("cell1");
+        std::fs::create_dir(&cell_path).unwrap();
+        std::fs::write(cell_path.join("cpu.weight"), "100\n").unwrap();
+
+        let mut mgr = CellManager::new_with_path(
+            tmp.path().to_path_buf(),
+            256,
+            cpumask_for_range(16),
+            HashSet::new(),
+        )
+        .unwrap();
+
+        // The inotify fd is non-blocking. The event is queued by the write itself, so it's
+        // already pending when process_events() reads it.
+        std::fs::write(cell_path.join("cpu.weight"), "500\n").unwrap();
+        let events = mgr.process_events().unwrap();
+        assert!(events.new_cells.is_empty());
+        assert!(events.destroyed_cells.is_empty());
+        assert!(events.controls_changed);
+        assert_eq!(mgr.find_cell_by_name("cell1").unwrap().controls.weight, 500);
+    }
 }
@@ -151,7 +151,8 @@ struct Opts {
 
     /// Parent cgroup path whose direct children become cells.
     /// When specified, cells are created for each direct child cgroup of this parent,
-    /// with CPUs divided equally among cells. Example: --cell-parent-cgroup /workloads
+    /// with CPUs divided among cells by their cgroup cpu.weight, capped by cpu.max and
+    /// always including their cpuset.cpus.exclusive. Example: --cell-parent-cgroup /workloads
     #[clap(long)]
     cell_parent_cgroup: Option<String>,
 
@@ -597,27 +598,35 @@ impl<'a> Scheduler<'a> {
         Ok(())
     }
 
-    /// Process cell manager events (new/destroyed cgroups)
+    /// Process cell manager events (new/destroyed cgroups, CPU control changes)
     fn process_cell_events(&mut self) -> Result<()> {
-        let (num_new, num_destroyed, new_cell_ids, destroyed_cell_ids) = {
+        let (num_new, num_destroyed, new_cell_ids, destroyed_cell_ids, controls_changed) = {
             let Some(ref mut cell_manager) = self.cell_manager else {
                 return Ok(());
             };
 
-            let (new_cells, destroyed_cells) = cell_manager
+            let events = cell_manager
                 .process_events()
                 .context("processing inotify events")?;
 
-            if new_cells.is_empty() && destroyed_cells.is_empty() {
+            if events.new_cells.is_empty()
+                && events.destroyed_cells.is_empty()
+                && !events.controls_changed
+            {
                 return Ok(());
             }
 
-            let new_ids: Vec<u32> = new_cells.iter().map(|(_, cell_id)| *cell_id).collect();
+            let new_ids: Vec<u32> = events
+                .new_cells
+                .iter()
+                .map(|(_, cell_id)| *cell_id)
+                .collect();
             (
-                new_cells.len(),
-                destroyed_cells.len(),
+                events.new_cells.len(),
+                events.destroyed_cells.len(),
                 new_ids,
-                destroyed_cells,
+                events.destroyed_cells,
+                events.controls_changed,
             )
         };
 
@@ -629,16 +638,21 @@ impl<'a> Scheduler<'a> {
 
         let cpu_assignments = self
             .compute_and_apply_cell_config(&new_cell_ids)
-            .context("recomputing cell configuration for new cgroups")?;
+            .context("recomputing cell configuration after cgroup events")?;
 
         let cell_manager = self
             .cell_manager
             .as_ref()
             .expect("BUG: cell_manager missing in process_cell_events");
         info!(
-            "Cell config updated ({} new, {} destroyed): {}",
+            "Cell config updated ({} new, {} destroyed{}): {}",
             num_new,
             num_destroyed,
+            if controls_changed {
+                ", CPU controls changed"
+            } else {
+                ""
+            },
             cell_manager.format_cell_config(&cpu_assignments)
         );
 
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use scx_utils::{EnergyModel as KernelEnergyModel, Topology};
+use scx_utils::{EnergyModel as KernelEnergyModel, PowerCurve, Topology};
 use std::collections::BTreeMap;
 use tracing::info;
 
@@ -34,23 +34,29 @@ pub struct EnergyModel {
 
 impl EnergyModel {
     /// Create new energy model from system topology
-    /// Tries to use kernel energy model first, falls back to heuristics
+    /// Uses the kernel energy model if it covers all the CPUs, otherwise one synthesized from
+    /// cpufreq and the CPU capacities
     pub fn new(topo: &Topology) -> Result<Self> {
-        let mut cpu_profiles = BTreeMap::new();
-
-        // Try to use kernel energy model if available
-        if let Ok(kernel_em) = KernelEnergyModel::new() {
-            info!("Using kernel energy model from /sys/kernel/debug/energy_model");
-
-            for cpu in topo.all_cpus.values() {
-                let profile = Self::create_profile_from_kernel_em(cpu, &kernel_em);
-                cpu_profiles.insert(cpu.id, profile);
+        let kernel_em = KernelEnergyModel::new().ok().filter(|em| {
+            topo.all_cpus
+                .keys()
+                .all(|&cpu| em.get_pd_by_cpu_id(cpu).is_some())
+        });
+
+        let em = match kernel_em {
+            Some(em) => {
+                info!("Using kernel energy model from /sys/kernel/debug/energy_model");
+                em
+            }
+            None => {
+                info!("Kernel energy model not available, synthesizing one from cpufreq");
+                KernelEnergyModel::synthesize(topo, &PowerCurve::default())?
             }
-        } else {
-            info!("Kernel energy model not available, using frequency-based estimates");
+        };
 
-            for cpu in topo.all_cpus.values() {
-                let profile = Self::create_profile_from_heuristics(cpu, topo);
+        let mut cpu_profiles = BTreeMap::new();
+        for cpu in topo.all_cpus.values() {
+            if let Some(profile) = Self::create_profile(cpu, &em) {
                 cpu_profiles.insert(cpu.id, profile);
             }
         }
@@ -92,86 +98,23 @@ impl EnergyModel {
         (small_thresh, large_thresh)
     }
 
-    /// Create energy profile from kernel energy model
-    fn create_profile_from_kernel_em(
-        cpu: &scx_utils::Cpu,
-        kernel_em: &KernelEnergyModel,
-    ) -> CpuEnergyProfile {
-        if let Some(pd) = kernel_em.get_pd_by_cpu_id(cpu.id) {
-            // Use highest performance state (max frequency) for power estimates
-            if let Some((_, ps)) = pd.perf_table.last_key_value() {
-                // Kernel provides power in microwatts, convert to milliwatts
-                let dynamic_power_mw = (ps.power / 1000) as u32;
-
-                // Estimate idle power as ~2-5% of dynamic power
-                let base_power_mw = (dynamic_power_mw / 30).max(10);
-
-                return CpuEnergyProfile {
-                    capacity: cpu.cpu_capacity as u32,
-                    base_power_mw,
-                    dynamic_power_mw,
-                    efficiency: (cpu.cpu_capacity as f32) / (dynamic_power_mw as f32),
-                };
-            }
-        }
-
-        // Fallback if we can't find this CPU in the energy model
-        Self::create_profile_from_heuristics(cpu, &Topology::new().unwrap())
-    }
-
-    /// Create energy profile based on CPU characteristics using heuristics
-    /// Uses frequency and capacity to estimate power consumption
-    fn create_profile_from_heuristics(cpu: &scx_utils::Cpu, topo: &Topology) -> CpuEnergyProfile {
-        // Find max capacity in the system to determine core type
-        let max_capacity = topo
-            .all_cpus
-            .values()
-            .map(|c| c.cpu_capacity)
-            .max()
-            .unwrap_or(1024);
-
-        // Determine if this is a big or little core
-        // Consider it "big" if capacity is >= 78% of max capacity
-        let is_big_core = cpu.cpu_capacity >= (max_capacity * 78) / 100;
-
-        // Power scales roughly with frequency and voltage
-        // P ≈ C * V^2 * f, and V ≈ f for modern CPUs
-        // So P ≈ k * f^3 (simplified)
-
-        let freq_ratio = if cpu.max_freq > 0 {
-            cpu.max_freq as f64 / 2500000.0 // Normalize to ~2.5GHz baseline
-        } else {
-            1.0
-        };
+    /// Create energy profile from the energy model
+    fn create_profile(cpu: &scx_utils::Cpu, em: &KernelEnergyModel) -> Option<CpuEnergyProfile> {
+        // Use highest performance state (max frequency) for power estimates
+        let (_, ps) = em.get_pd_by_cpu_id(cpu.id)?.perf_table.last_key_value()?;
 
-        let capacity_ratio = cpu.cpu_capacity as f64 / 1024.0;
+        // Energy model provides power in microwatts, convert to milliwatts
+        let dynamic_power_mw = (ps.power / 1000) as u32;
 
-        if is_big_core {
-            // Big core - scale power based on frequency
-            let base_dynamic_power = 3000.0; // 3W baseline for 2.5GHz big core
-            let dynamic_power_mw = (base_dynamic_power * freq_ratio.powf(2.5)) as u32;
-            let base_power_mw = (dynamic_power_mw / 60).max(30); // ~1.6-3% of dynamic
+        // Estimate idle power as ~2-5% of dynamic power
+        let base_power_mw = (dynamic_power_mw / 30).max(10);
 
-            CpuEnergyProfile {
-                capacity: cpu.cpu_capacity as u32,
-                base_power_mw,
-                dynamic_power_mw,
-                efficiency: (cpu.cpu_capacity as f32) / (dynamic_power_mw as f32),
-            }
-        } else {
-            // Little core - more efficient, lower power
-            let base_dynamic_power = 1200.0; // 1.2W baseline for little core
-            let dynamic_power_mw =
-                (base_dynamic_power * freq_ratio.powf(2.5) * capacity_ratio) as u32;
-            let base_power_mw = (dynamic_power_mw / 50).max(15); // ~2% of dynamic
-
-            CpuEnergyProfile {
-                capacity: cpu.cpu_capacity as u32,
-                base_power_mw,
-                dynamic_power_mw,
-                efficiency: (cpu.cpu_capacity as f32) / (dynamic_power_mw as f32),
-            }
-        }
+        Some(CpuEnergyProfile {
+            capacity: cpu.cpu_capacity as u32,
+            base_power_mw,
+            dynamic_power_mw,
+            efficiency: (cpu.cpu_capacity as f32) / (dynamic_power_mw as f32),
+        })
     }
 
     /// Get energy cost for a CPU
@@ -48,59 +48,95 @@ pub fn is_atq_enabled() -> bool {
 #[derive(Clone, Debug, Default, Serialize, Deserialize, Stats)]
 #[stat(top)]
 pub struct Metrics {
-    #[stat(desc = "Number of times a task was enqueued to a ATQ")]
+    #[stat(desc = "Number of times a task was enqueued to a ATQ", _counter)]
     pub atq_enq: u64,
-    #[stat(desc = "Number of times a task was re-enqueued to a ATQ")]
+    #[stat(desc = "Number of times a task was re-enqueued to a ATQ", _counter)]
     pub atq_reenq: u64,
-    #[stat(desc = "Number of times tasks have switched DSQs")]
+    #[stat(desc = "Number of times tasks have switched DSQs", _counter)]
     pub dsq_change: u64,
-    #[stat(desc = "Number of times tasks have stayed on the same DSQ")]
+    #[stat(desc = "Number of times tasks have stayed on the same DSQ", _counter)]
     pub same_dsq: u64,
-    #[stat(desc = "Number of times a task kept running")]
+    #[stat(desc = "Number of times a task kept running", _counter)]
     pub keep: u64,
-    #[stat(desc = "Number of times a task was enqueued to CPUC DSQ")]
+    #[stat(desc = "Number of times a task was enqueued to CPUC DSQ", _counter)]
     pub enq_cpu: u64,
-    #[stat(desc = "Number of times a task was enqueued to LLC DSQ")]
+    #[stat(desc = "Number of times a task was enqueued to LLC DSQ", _counter)]
     pub enq_llc: u64,
-    #[stat(desc = "Number of times a task was enqueued to interactive DSQ")]
+    #[stat(
+        desc = "Number of times a task was enqueued to interactive DSQ",
+        _counter
+    )]
     pub enq_intr: u64,
-    #[stat(desc = "Number of times a task was enqueued to migration DSQ")]
+    #[stat(
+        desc = "Number of times a task was enqueued to migration DSQ",
+        _counter
+    )]
     pub enq_mig: u64,
-    #[stat(desc = "Number of times a select_cpu pick 2 load balancing occurred")]
+    #[stat(
+        desc = "Number of times a select_cpu pick 2 load balancing occurred",
+        _counter
+    )]
     pub select_pick2: u64,
-    #[stat(desc = "Number of times a dispatch pick 2 load balancing occurred")]
+    #[stat(
+        desc = "Number of times a dispatch pick 2 load balancing occurred",
+        _counter
+    )]
     pub dispatch_pick2: u64,
-    #[stat(desc = "Number of times a task migrated LLCs")]
+    #[stat(desc = "Number of times a task migrated LLCs", _counter)]
     pub llc_migrations: u64,
-    #[stat(desc = "Number of times a task migrated NUMA nodes")]
+    #[stat(desc = "Number of times a task migrated NUMA nodes", _counter)]
     pub node_migrations: u64,
-    #[stat(desc = "Number of times tasks have directly been dispatched to local per CPU DSQs")]
+    #[stat(
+        desc = "Number of times tasks have directly been dispatched to local per CPU DSQs",
+        _counter
+    )]
     pub direct: u64,
-    #[stat(desc = "Number of times tasks have dispatched to an idle local per CPU DSQs")]
+    #[stat(
+        desc = "Number of times tasks have dispatched to an idle local per CPU DSQs",
+        _counter
+    )]
     pub idle: u64,
-    #[stat(desc = "Number of times tasks have been woken to the previous CPU")]
+    #[stat(
+        desc = "Number of times tasks have been woken to the previous CPU",
+        _counter
+    )]
     pub wake_prev: u64,
-    #[stat(desc = "Number of times tasks have been woken to the previous llc")]
+    #[stat(
+        desc = "Number of times tasks have been woken to the previous llc",
+        _counter
+    )]
     pub wake_llc: u64,
-    #[stat(desc = "Number of times tasks have been woken and migrated llc")]
+    #[stat(
+        desc = "Number of times tasks have been woken and migrated llc",
+        _counter
+    )]
     pub wake_mig: u64,
-    #[stat(desc = "Number of times fork balancing migrated to different LLC")]
+    #[stat(
+        desc = "Number of times fork balancing migrated to different LLC",
+        _counter
+    )]
     pub fork_balance: u64,
-    #[stat(desc = "Number of times exec balancing migrated to different LLC")]
+    #[stat(
+        desc = "Number of times exec balancing migrated to different LLC",
+        _counter
+    )]
     pub exec_balance: u64,
-    #[stat(desc = "Number of times fork stayed on same LLC")]
+    #[stat(desc = "Number of times fork stayed on same LLC", _counter)]
     pub fork_same_llc: u64,
-    #[stat(desc = "Number of times exec stayed on same LLC")]
+    #[stat(desc = "Number of times exec stayed on same LLC", _counter)]
     pub exec_same_llc: u64,
-    #[stat(desc = "Number of CPU kicks due to thermal pressure")]
+    #[stat(desc = "Number of CPU kicks due to thermal pressure", _counter)]
     pub thermal_kick: u64,
-    #[stat(desc = "Number of times throttled CPUs were avoided")]
+    #[stat(desc = "Number of times throttled CPUs were avoided", _counter)]
     pub thermal_avoid: u64,
-    #[stat(desc = "Number of times EAS placed task on little core")]
+    #[stat(desc = "Number of times EAS placed task on little core", _counter)]
     pub eas_little_select: u64,
-    #[stat(desc = "Number of times EAS placed task on big core")]
+    #[stat(desc = "Number of times EAS placed task on big core", _counter)]
     pub eas_big_select: u64,
-    #[stat(desc = "Number of times EAS fell back to non-preferred core type")]
+    #[stat(
+        desc = "Number of times EAS fell back to non-preferred core type",
+        _counter
+    )]
     pub eas_fallback: u64,
 }
 
@@ -168,38 +204,6 @@ impl Metrics {
         writeln!(w, "{}", stats_line)?;
         Ok(())
     }
-
-    fn delta(&self, rhs: &Self) -> Self {
-        Self {
-            atq_enq: self.atq_enq - rhs.atq_enq,
-            atq_reenq: self.atq_reenq - rhs.atq_reenq,
-            direct: self.direct - rhs.direct,
-            idle: self.idle - rhs.idle,
-            dsq_change: self.dsq_change - rhs.dsq_change,
-            same_dsq: self.same_dsq - rhs.same_dsq,
-            keep: self.keep - rhs.keep,
-            enq_cpu: self.enq_cpu - rhs.enq_cpu,
-            enq_llc: self.enq_llc - rhs.enq_llc,
-            enq_intr: self.enq_intr - rhs.enq_intr,
-            enq_mig: self.enq_mig - rhs.enq_mig,
-            select_pick2: self.select_pick2 - rhs.select_pick2,
-            dispatch_pick2: self.dispatch_pick2 - rhs.dispatch_pick2,
-            llc_migrations: self.llc_migrations - rhs.llc_migrations,
-            node_migrations: self.node_migrations - rhs.node_migrations,
-            wake_prev: self.wake_prev - rhs.wake_prev,
-            wake_llc: self.wake_llc - rhs.wake_llc,
-            wake_mig: self.wake_mig - rhs.wake_mig,
-            fork_balance: self.fork_balance - rhs.fork_balance,
-            exec_balance: self.exec_balance - rhs.exec_balance,
-            fork_same_llc: self.fork_same_llc - rhs.fork_same_llc,
-            exec_same_llc: self.exec_same_llc - rhs.exec_same_llc,
-            thermal_kick: self.thermal_kick - rhs.thermal_kick,
-            thermal_avoid: self.thermal_avoid - rhs.thermal_avoid,
-            eas_little_select: self.eas_little_select - rhs.eas_little_select,
-            eas_big_select: self.eas_big_select - rhs.eas_big_select,
-            eas_fallback: self.eas_fallback - rhs.eas_fallback,
-        }
-    }
 }
 pub fn server_data() -> StatsServerData<(), Metrics> {
     let open: Box<dyn StatsOpener<(), Metrics>> = Box::new(move |(req_ch, res_ch)| {
@@ -14,6 +14,9 @@ mod stats;
 use std::collections::BTreeSet;
 use std::io::{self};
 use std::mem::MaybeUninit;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
 use std::time::Duration;
 use std::time::SystemTime;
 
@@ -161,13 +164,16 @@ struct Scheduler<'a> {
     tasks: BTreeSet<Task>,                  // tasks ordered by deadline
     vruntime_now: u64,     // Tracks the latest observed (max) vruntime across tasks
     init_page_faults: u64, // Initial page faults counter
+    slice_us: Arc<AtomicU64>, // Default time slice (in us), tunable at runtime
     slice_ns: u64,         // Default time slice (in ns)
     slice_ns_min: u64,     // Minimum time slice (in ns)
 }
 
 impl<'a> Scheduler<'a> {
     fn init(opts: &'a Opts, open_object: &'a mut MaybeUninit<OpenObject>) -> Result<Self> {
-        let stats_server = StatsServer::new(stats::server_data()).launch()?;
+        let slice_us = Arc::new(AtomicU64::new(opts.slice_us));
+        let stats_server =
+            StatsServer::new(stats::server_data(slice_us.clone(), opts.slice_us_min)).launch()?;
 
         let slice_ns = opts.slice_us * NSEC_PER_USEC;
         let slice_ns_min = opts.slice_us_min * NSEC_PER_USEC;
@@ -200,6 +206,7 @@ impl<'a> Scheduler<'a> {
             tasks: BTreeSet::new(),
             vruntime_now: 0,
             init_page_faults: 0,
+            slice_us,
             slice_ns,
             slice_ns_min,
         })
@@ -347,6 +354,9 @@ impl<'a> Scheduler<'a> {
     // Main scheduling function (called in a loop to periodically drain tasks from the queued list
     // and dispatch them to the BPF part via the dispatched list).
     fn schedule(&mut self) {
+        // Pick up changes to the slice_us tunable.
+        self.slice_ns = self.slice_us.load(Ordering::Relaxed) * NSEC_PER_USEC;
+
         self.drain_queued_tasks();
         self.dispatch_task();
 
@@ -400,7 +410,8 @@ fn main() -> Result<()> {
     }
 
     if opts.help_stats {
-        stats::server_data().describe_meta(&mut std::io::stdout(), None)?;
+        stats::server_data(Arc::new(AtomicU64::new(opts.slice_us)), opts.slice_us_min)
+            .describe_meta(&mut std::io::stdout(), None)?;
         return Ok(());
     }
 
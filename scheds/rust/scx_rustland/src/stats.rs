@@ -1,6 +1,10 @@
 use std::io::Write;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
 use std::time::Duration;
 
+use anyhow::anyhow;
 use anyhow::Result;
 use scx_stats::prelude::*;
 use scx_stats_derive::stat_doc;
@@ -12,27 +16,36 @@ use serde::Serialize;
 #[derive(Clone, Debug, Default, Serialize, Deserialize, Stats)]
 #[stat(top)]
 pub struct Metrics {
-    #[stat(desc = "Number of online CPUs")]
+    #[stat(desc = "Number of online CPUs", _gauge)]
     pub nr_cpus: u64,
-    #[stat(desc = "Amount of tasks currently running")]
+    #[stat(desc = "Amount of tasks currently running", _gauge)]
     pub nr_running: u64,
-    #[stat(desc = "Amount of tasks queued to the user-space scheduler")]
+    #[stat(desc = "Amount of tasks queued to the user-space scheduler", _gauge)]
     pub nr_queued: u64,
-    #[stat(desc = "Amount of tasks in the user-space scheduler waiting to be dispatched")]
+    #[stat(
+        desc = "Amount of tasks in the user-space scheduler waiting to be dispatched",
+        _gauge
+    )]
     pub nr_scheduled: u64,
-    #[stat(desc = "Amount of user-space scheduler's page faults (should be always 0)")]
+    #[stat(
+        desc = "Amount of user-space scheduler's page faults (should be always 0)",
+        _gauge
+    )]
     pub nr_page_faults: u64,
-    #[stat(desc = "Number of task dispatched by the user-space scheduler")]
+    #[stat(
+        desc = "Number of task dispatched by the user-space scheduler",
+        _counter
+    )]
     pub nr_user_dispatches: u64,
-    #[stat(desc = "Number of task dispatched directly by the kernel")]
+    #[stat(desc = "Number of task dispatched directly by the kernel", _counter)]
     pub nr_kernel_dispatches: u64,
-    #[stat(desc = "Number of cancelled dispatches")]
+    #[stat(desc = "Number of cancelled dispatches", _counter)]
     pub nr_cancel_dispatches: u64,
-    #[stat(desc = "Number of dispatches bounced to another DSQ")]
+    #[stat(desc = "Number of dispatches bounced to another DSQ", _counter)]
     pub nr_bounce_dispatches: u64,
-    #[stat(desc = "Number of failed dispatches")]
+    #[stat(desc = "Number of failed dispatches", _counter)]
     pub nr_failed_dispatches: u64,
-    #[stat(desc = "Number of scheduler congestion events")]
+    #[stat(desc = "Number of scheduler congestion events", _counter)]
     pub nr_sched_congested: u64,
 }
 
@@ -56,21 +69,14 @@ impl Metrics {
         )?;
         Ok(())
     }
-
-    fn delta(&self, rhs: &Self) -> Self {
-        Self {
-            nr_user_dispatches: self.nr_user_dispatches - rhs.nr_user_dispatches,
-            nr_kernel_dispatches: self.nr_kernel_dispatches - rhs.nr_kernel_dispatches,
-            nr_cancel_dispatches: self.nr_cancel_dispatches - rhs.nr_cancel_dispatches,
-            nr_bounce_dispatches: self.nr_bounce_dispatches - rhs.nr_bounce_dispatches,
-            nr_failed_dispatches: self.nr_failed_dispatches - rhs.nr_failed_dispatches,
-            nr_sched_congested: self.nr_sched_congested - rhs.nr_sched_congested,
-            ..self.clone()
-        }
-    }
 }
 
-pub fn server_data() -> StatsServerData<(), Metrics> {
+/// Upper bound of the slice_us tunable.
+const SLICE_US_MAX: u64 = 1_000_000;
+
+/// Stats server data. @slice_us is updated when the "slice_us" tunable is
+/// changed and must be at least @slice_us_min.
+pub fn server_data(slice_us: Arc<AtomicU64>, slice_us_min: u64) -> StatsServerData<(), Metrics> {
     let open: Box<dyn StatsOpener<(), Metrics>> = Box::new(move |(req_ch, res_ch)| {
         req_ch.send(())?;
         let mut prev = res_ch.recv()?;
@@ -86,9 +92,25 @@ pub fn server_data() -> StatsServerData<(), Metrics> {
         Ok(read)
     });
 
+    let slice_us_meta =
+        StatsTunableMeta::new(StatsKind::U64, slice_us.load(Ordering::Relaxed).into())
+            .desc("Scheduling slice duration in microseconds")
+            .range(slice_us_min as f64, SLICE_US_MAX as f64);
+
     StatsServerData::new()
         .add_meta(Metrics::meta())
         .add_ops("top", StatsOps { open, close: None })
+        .add_tunable(
+            "slice_us",
+            slice_us_meta,
+            Box::new(move |val, _chan| {
+                let val = val
+                    .as_u64()
+                    .ok_or_else(|| anyhow!("invalid slice_us {}", val))?;
+                slice_us.store(val, Ordering::Relaxed);
+                Ok(())
+            }),
+        )
 }
 
 pub fn monitor(intv: Duration) -> Result<()> {
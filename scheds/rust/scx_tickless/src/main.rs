@@ -15,30 +15,29 @@ use std::ffi::c_int;
 use std::fs;
 use std::mem::MaybeUninit;
 use std::sync::atomic::AtomicBool;
-use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::Duration;
 
 use affinity::set_thread_affinity;
 use anyhow::bail;
-use anyhow::Context;
 use anyhow::Result;
 use clap::Parser;
-use crossbeam::channel::RecvTimeoutError;
 use libbpf_rs::OpenObject;
 use libbpf_rs::ProgramInput;
+use log::info;
 use log::warn;
-use log::{debug, info};
+use scx_runtime::RuntimeOpts;
+use scx_runtime::SchedulerOpts;
 use scx_stats::prelude::*;
 use scx_utils::build_id;
 use scx_utils::compat;
-use scx_utils::libbpf_clap_opts::LibbpfOpts;
 use scx_utils::scx_ops_attach;
 use scx_utils::scx_ops_load;
 use scx_utils::scx_ops_open;
 use scx_utils::try_set_rlimit_infinity;
 use scx_utils::uei_exited;
 use scx_utils::uei_report;
+use scx_utils::CpuDomain;
 use scx_utils::Cpumask;
 use scx_utils::Topology;
 use scx_utils::UserExitInfo;
@@ -49,17 +48,6 @@ const SCHEDULER_NAME: &str = "scx_tickless";
 
 #[derive(Debug, Parser)]
 struct Opts {
-    /// Exit debug dump buffer length. 0 indicates default.
-    #[clap(long, default_value = "0")]
-    exit_dump_len: u32,
-
-    /// Define the set of CPUs, represented as a bitmask in hex (e.g., 0xff), dedicated to process
-    /// scheduling events.
-    ///
-    /// 0x0 = autodetect the slowest CPU to process scheduling events.
-    #[clap(short = 'm', long, default_value = "0")]
-    primary_domain: String,
-
     /// Maximum scheduling slice duration in microseconds (applied only when multiple tasks are
     /// contending the same CPU).
     #[clap(short = 's', long, default_value = "20000")]
@@ -77,29 +65,8 @@ struct Opts {
     #[clap(short = 'n', long, action = clap::ArgAction::SetTrue)]
     nosmt: bool,
 
-    /// Enable stats monitoring with the specified interval.
-    #[clap(long)]
-    stats: Option<f64>,
-
-    /// Run in stats monitoring mode with the specified interval. Scheduler
-    /// is not launched.
-    #[clap(long)]
-    monitor: Option<f64>,
-
-    /// Enable verbose output, including libbpf details.
-    #[clap(short = 'v', long, action = clap::ArgAction::SetTrue)]
-    verbose: bool,
-
-    /// Print scheduler version and exit.
-    #[clap(short = 'V', long, action = clap::ArgAction::SetTrue)]
-    version: bool,
-
-    /// Show descriptions for statistics.
-    #[clap(long)]
-    help_stats: bool,
-
-    #[clap(flatten, next_help_heading = "Libbpf Options")]
-    pub libbpf: LibbpfOpts,
+    #[clap(flatten)]
+    runtime: RuntimeOpts,
 }
 
 pub fn is_nohz_enabled() -> bool {
@@ -113,7 +80,7 @@ pub fn is_nohz_enabled() -> bool {
 struct Scheduler<'a> {
     skel: BpfSkel<'a>,
     struct_ops: Option<libbpf_rs::Link>,
-    stats_server: StatsServer<(), Metrics>,
+    timer_cpu: usize,
 }
 
 impl<'a> Scheduler<'a> {
@@ -139,8 +106,13 @@ impl<'a> Scheduler<'a> {
         let mut cpus: Vec<_> = topo.all_cpus.values().collect();
         cpus.sort_by_key(|cpu| std::cmp::Reverse(cpu.cpu_capacity));
 
-        // Process the domain of primary CPUs.
-        let mut domain = Cpumask::from_str(&opts.primary_domain)?;
+        // Process the domain of primary CPUs. The default empty domain (0x0) autodetects the
+        // slowest CPU.
+        let mut domain = opts
+            .runtime
+            .primary_domain_or("0x0")
+            .parse::<CpuDomain>()?
+            .eval(&topo)?;
         if domain.is_empty() {
             if let Some(cpu) = cpus.last() {
                 domain = Cpumask::from_str(&format!("{:x}", 1 << cpu.id).to_string())?;
@@ -150,10 +122,10 @@ impl<'a> Scheduler<'a> {
 
         // Initialize BPF connector.
         let mut skel_builder = BpfSkelBuilder::default();
-        skel_builder.obj_builder.debug(opts.verbose);
-        let open_opts = opts.libbpf.clone().into_bpf_open_opts();
+        skel_builder.obj_builder.debug(opts.runtime.verbose);
+        let open_opts = opts.runtime.libbpf.clone().into_bpf_open_opts();
         let mut skel = scx_ops_open!(skel_builder, open_object, tickless_ops, open_opts)?;
-        skel.struct_ops.tickless_ops_mut().exit_dump_len = opts.exit_dump_len;
+        skel.struct_ops.tickless_ops_mut().exit_dump_len = opts.runtime.exit_dump_len;
 
         let rodata = skel.maps.rodata_data.as_mut().unwrap();
 
@@ -180,34 +152,19 @@ impl<'a> Scheduler<'a> {
         // Load the BPF program for validation.
         let mut skel = scx_ops_load!(skel, tickless_ops, uei)?;
 
-        // Set task affinity to the first primary CPU: this is required to start the scheduler's
-        // timer on a primary CPU.
-        let timer_cpu = domain.iter().next();
-        if timer_cpu.is_none() {
+        let Some(timer_cpu) = domain.iter().next() else {
             bail!("primary cpumask is empty");
-        }
-        if let Err(e) = set_thread_affinity(&[timer_cpu.unwrap() as usize]) {
-            bail!("cannot set central CPU affinity: {}", e);
-        }
+        };
 
         // Initialize the group of primary CPUs.
         if let Err(err) = Self::init_primary_domain(&mut skel, &domain) {
             warn!("failed to initialize primary domain: error {}", err);
         }
 
-        // Attach the scheduler.
-        let struct_ops = Some(scx_ops_attach!(skel, tickless_ops)?);
-        let stats_server = StatsServer::new(stats::server_data()).launch()?;
-
-        // Reset task affinity.
-        if let Err(e) = set_thread_affinity((0..*NR_CPU_IDS).collect::<Vec<usize>>()) {
-            bail!("cannot reset CPU affinity: {}", e);
-        }
-
         Ok(Self {
             skel,
-            struct_ops,
-            stats_server,
+            struct_ops: None,
+            timer_cpu,
         })
     }
 
@@ -260,100 +217,66 @@ impl<'a> Scheduler<'a> {
             nr_timer_dispatches: bss_data.nr_timer_dispatches,
         }
     }
+}
+
+impl scx_runtime::Scheduler for Scheduler<'_> {
+    type Metrics = Metrics;
+
+    fn attach(&mut self) -> Result<()> {
+        // Set task affinity to the first primary CPU: this is required to start the scheduler's
+        // timer on a primary CPU.
+        if let Err(e) = set_thread_affinity(&[self.timer_cpu]) {
+            bail!("cannot set central CPU affinity: {}", e);
+        }
+
+        // Attach the scheduler.
+        self.struct_ops = Some(scx_ops_attach!(self.skel, tickless_ops)?);
+
+        // Reset task affinity.
+        if let Err(e) = set_thread_affinity((0..*NR_CPU_IDS).collect::<Vec<usize>>()) {
+            bail!("cannot reset CPU affinity: {}", e);
+        }
+
+        Ok(())
+    }
 
-    pub fn exited(&mut self) -> bool {
+    fn exited(&mut self) -> bool {
         uei_exited!(&self.skel, uei)
     }
 
-    fn run(&mut self, shutdown: Arc<AtomicBool>) -> Result<UserExitInfo> {
-        let (res_ch, req_ch) = self.stats_server.channels();
-        while !shutdown.load(Ordering::Relaxed) && !self.exited() {
-            match req_ch.recv_timeout(Duration::from_secs(1)) {
-                Ok(()) => res_ch.send(self.get_metrics())?,
-                Err(RecvTimeoutError::Timeout) => {}
-                Err(e) => Err(e)?,
-            }
-        }
+    fn metrics(&mut self) -> Metrics {
+        self.get_metrics()
+    }
 
+    fn exit_report(&mut self) -> Result<UserExitInfo> {
         let _ = self.struct_ops.take();
         uei_report!(&self.skel, uei)
     }
 }
 
-impl Drop for Scheduler<'_> {
-    fn drop(&mut self) {
-        info!("Unregister {SCHEDULER_NAME} scheduler");
-    }
-}
-
-fn main() -> Result<()> {
-    let opts = Opts::parse();
+impl SchedulerOpts for Opts {
+    const NAME: &'static str = SCHEDULER_NAME;
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+    type Metrics = Metrics;
+    type Sched<'a> = Scheduler<'a>;
 
-    if opts.version {
-        println!(
-            "{} {}",
-            SCHEDULER_NAME,
-            build_id::full_version(env!("CARGO_PKG_VERSION"))
-        );
-        return Ok(());
+    fn runtime(&self) -> &RuntimeOpts {
+        &self.runtime
     }
 
-    if opts.help_stats {
-        stats::server_data().describe_meta(&mut std::io::stdout(), None)?;
-        return Ok(());
+    fn init<'a>(&'a self, open_object: &'a mut MaybeUninit<OpenObject>) -> Result<Scheduler<'a>> {
+        Scheduler::init(self, open_object)
     }
 
-    let loglevel = simplelog::LevelFilter::Info;
-
-    let mut lcfg = simplelog::ConfigBuilder::new();
-    lcfg.set_time_offset_to_local()
-        .expect("Failed to set local time offset")
-        .set_time_level(simplelog::LevelFilter::Error)
-        .set_location_level(simplelog::LevelFilter::Off)
-        .set_target_level(simplelog::LevelFilter::Off)
-        .set_thread_level(simplelog::LevelFilter::Off);
-    simplelog::TermLogger::init(
-        loglevel,
-        lcfg.build(),
-        simplelog::TerminalMode::Stderr,
-        simplelog::ColorChoice::Auto,
-    )?;
-
-    let shutdown = Arc::new(AtomicBool::new(false));
-    let shutdown_clone = shutdown.clone();
-    ctrlc::set_handler(move || {
-        shutdown_clone.store(true, Ordering::Relaxed);
-    })
-    .context("Error setting Ctrl-C handler")?;
-
-    if let Some(intv) = opts.monitor.or(opts.stats) {
-        let shutdown_copy = shutdown.clone();
-        let jh = std::thread::spawn(move || {
-            match stats::monitor(Duration::from_secs_f64(intv), shutdown_copy) {
-                Ok(_) => {
-                    debug!("stats monitor thread finished successfully")
-                }
-                Err(error_object) => {
-                    warn!(
-                        "stats monitor thread finished because of an error {}",
-                        error_object
-                    )
-                }
-            }
-        });
-        if opts.monitor.is_some() {
-            let _ = jh.join();
-            return Ok(());
-        }
+    fn stats_server_data() -> StatsServerData<(), Metrics> {
+        stats::server_data()
     }
 
-    let mut open_object = MaybeUninit::uninit();
-    loop {
-        let mut sched = Scheduler::init(&opts, &mut open_object)?;
-        if !sched.run(shutdown.clone())?.should_restart() {
-            break;
-        }
+    fn monitor(intv: Duration, shutdown: Arc<AtomicBool>) -> Result<()> {
+        stats::monitor(intv, shutdown)
     }
+}
 
-    Ok(())
+fn main() -> Result<()> {
+    scx_runtime::run(&Opts::parse())
 }
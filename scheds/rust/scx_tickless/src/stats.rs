@@ -22,15 +22,21 @@ use serde::Serialize;
 #[derive(Clone, Debug, Default, Serialize, Deserialize, Stats)]
 #[stat(top)]
 pub struct Metrics {
-    #[stat(desc = "Number of ticks")]
+    #[stat(desc = "Number of ticks", _counter)]
     pub nr_ticks: u64,
-    #[stat(desc = "Number of preemption events")]
+    #[stat(desc = "Number of preemption events", _counter)]
     pub nr_preemptions: u64,
-    #[stat(desc = "Number of dispatches directly consumed from the shared queue")]
+    #[stat(
+        desc = "Number of dispatches directly consumed from the shared queue",
+        _counter
+    )]
     pub nr_direct_dispatches: u64,
-    #[stat(desc = "Number of dispatches routed by the primary CPUs")]
+    #[stat(desc = "Number of dispatches routed by the primary CPUs", _counter)]
     pub nr_primary_dispatches: u64,
-    #[stat(desc = "Number of dispatches routed by the primary CPU timers")]
+    #[stat(
+        desc = "Number of dispatches routed by the primary CPU timers",
+        _counter
+    )]
     pub nr_timer_dispatches: u64,
 }
 
@@ -48,17 +54,6 @@ impl Metrics {
         )?;
         Ok(())
     }
-
-    fn delta(&self, rhs: &Self) -> Self {
-        Self {
-            nr_ticks: self.nr_ticks - rhs.nr_ticks,
-            nr_preemptions: self.nr_preemptions - rhs.nr_preemptions,
-            nr_direct_dispatches: self.nr_direct_dispatches - rhs.nr_direct_dispatches,
-            nr_primary_dispatches: self.nr_primary_dispatches - rhs.nr_primary_dispatches,
-            nr_timer_dispatches: self.nr_timer_dispatches - rhs.nr_timer_dispatches,
-            ..self.clone()
-        }
-    }
 }
 
 pub fn server_data() -> StatsServerData<(), Metrics> {
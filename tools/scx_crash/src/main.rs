@@ -0,0 +1,339 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+use anyhow::bail;
+use anyhow::Result;
+use chrono::DateTime;
+use chrono::Local;
+use clap::Parser;
+use clap::Subcommand;
+use scx_utils::crash_report::CrashDump;
+use scx_utils::crash_report::CRASH_DIR;
+use scx_utils::CrashReport;
+use scx_utils::CrashStore;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::UNIX_EPOCH;
+
+/// Inspect crash reports saved by sched_ext schedulers.
+///
+/// Schedulers built with scx_utils save a JSON report with the parsed debug
+/// dump, scheduler version, command line and host topology whenever they
+/// exit with an error. A report can be given as a path, a file name in the
+/// crash directory, an index from `list` or `latest`.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Opts {
+    /// Crash report directory.
+    #[clap(short, long, env = "SCX_CRASH_DIR", default_value = CRASH_DIR)]
+    dir: PathBuf,
+
+    #[command(subcommand)]
+    cmd: Cmd,
+}
+
+#[derive(Debug, Subcommand)]
+enum Cmd {
+    /// List saved reports, oldest first.
+    List,
+    /// Show a report.
+    Show {
+        report: String,
+
+        /// Print the report as JSON.
+        #[clap(long, conflicts_with = "raw")]
+        json: bool,
+
+        /// Print the raw debug dump.
+        #[clap(long)]
+        raw: bool,
+    },
+    /// Compare two reports.
+    Diff { a: String, b: String },
+}
+
+fn format_time(timestamp: u64) -> String {
+    DateTime::<Local>::from(UNIX_EPOCH + Duration::from_secs(timestamp))
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+fn resolve(store: &CrashStore, report: &str) -> Result<PathBuf> {
+    let path = PathBuf::from(report);
+    if path.exists() {
+        return Ok(path);
+    }
+
+    let paths = store.list()?;
+    let found = if report == "latest" {
+        paths.last().cloned()
+    } else if let Ok(idx) = report.parse::<usize>() {
+        paths.get(idx).cloned()
+    } else {
+        Some(store.dir().join(report)).filter(|p| p.exists())
+    };
+    match found {
+        Some(path) => Ok(path),
+        None => bail!("no crash report {:?} in {:?}", report, store.dir()),
+    }
+}
+
+fn topology_summary(report: &CrashReport) -> String {
+    match report.topology() {
+        Ok(Some(topo)) => format!(
+            "cpus={} cores={} llcs={} nodes={}",
+            topo.all_cpus.len(),
+            topo.all_cores.len(),
+            topo.all_llcs.len(),
+            topo.nodes.len()
+        ),
+        Ok(None) => "-".to_string(),
+        Err(e) => format!("invalid ({e})"),
+    }
+}
+
+fn list(store: &CrashStore) -> Result<()> {
+    println!(
+        "{:<4} {:<19} {:<16} {:<12} REASON",
+        "IDX", "TIME", "SCHEDULER", "KIND"
+    );
+    for (idx, path) in store.list()?.iter().enumerate() {
+        match CrashReport::load(path) {
+            Ok(report) => println!(
+                "{:<4} {:<19} {:<16} {:<12} {}",
+                idx,
+                format_time(report.timestamp),
+                report.scheduler,
+                report.exit_kind_name,
+                report.reason.as_deref().unwrap_or("-")
+            ),
+            Err(e) => println!("{idx:<4} {e:#}"),
+        }
+    }
+    Ok(())
+}
+
+fn show_dump(dump: &CrashDump) {
+    if let Some(trigger) = &dump.trigger {
+        println!("\n{trigger}");
+    }
+
+    if !dump.backtrace.is_empty() {
+        println!("\nBacktrace:");
+        for frame in &dump.backtrace {
+            println!("  {frame}");
+        }
+    }
+
+    if !dump.sched_dump.is_empty() {
+        println!("\nScheduler dump:");
+        for line in &dump.sched_dump {
+            println!("  {}", line.trim_end());
+        }
+    }
+
+    if !dump.dsqs.is_empty() {
+        println!("\nDSQs:");
+        for dsq in &dump.dsqs {
+            let oldest = dsq.tasks.iter().min_by_key(|t| t.runnable_ms);
+            print!("  {:<20} nr_tasks={:<4}", dsq.dsq_id, dsq.tasks.len());
+            if let Some(t) = oldest {
+                print!(
+                    " oldest={}[{}] cpu={} {}ms",
+                    t.comm, t.pid, t.cpu, t.runnable_ms
+                );
+            }
+            println!();
+        }
+    }
+
+    if !dump.cpus.is_empty() {
+        println!("\nCPUs:");
+        for cpu in &dump.cpus {
+            println!(
+                "  cpu{:<4} nr_run={:<3} curr={}[{}] class={}",
+                cpu.cpu,
+                cpu.fields.get("nr_run").map(|s| s.as_str()).unwrap_or("?"),
+                cpu.curr_comm,
+                cpu.curr_pid,
+                cpu.curr_class
+            );
+            for line in &cpu.extra {
+                println!("      {line}");
+            }
+            for task in &cpu.tasks {
+                println!(
+                    "    {}{} {}[{}] {:+}ms dsq_id={}",
+                    if task.curr { '*' } else { ' ' },
+                    task.state,
+                    task.comm,
+                    task.pid,
+                    task.runnable_ms,
+                    task.fields.get("dsq_id").map(|s| s.as_str()).unwrap_or("-")
+                );
+            }
+        }
+    }
+
+    let counters: Vec<_> = dump
+        .event_counters
+        .iter()
+        .filter(|(_, &v)| v != 0)
+        .collect();
+    if !counters.is_empty() {
+        println!("\nEvent counters:");
+        for (name, val) in counters {
+            println!("  {name}: {val}");
+        }
+    }
+}
+
+fn show(store: &CrashStore, report: &str, json: bool, raw: bool) -> Result<()> {
+    let path = resolve(store, report)?;
+    let report = CrashReport::load(&path)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    if raw {
+        match &report.dump {
+            Some(dump) => println!("{}", dump.raw),
+            None => bail!("{:?} has no debug dump", path),
+        }
+        return Ok(());
+    }
+
+    println!("report:    {}", path.display());
+    println!("time:      {}", format_time(report.timestamp));
+    println!("scheduler: {} {}", report.scheduler, report.version);
+    println!("host:      {} (kernel {})", report.hostname, report.kernel);
+    println!("cmdline:   {}", report.cmdline.join(" "));
+    println!("topology:  {}", topology_summary(&report));
+    println!(
+        "exit:      {} ({}) code={:#x}",
+        report.exit_kind_name, report.exit_kind, report.exit_code
+    );
+    println!("reason:    {}", report.reason.as_deref().unwrap_or("-"));
+    println!("msg:       {}", report.msg.as_deref().unwrap_or("-"));
+
+    if let Some(dump) = &report.dump {
+        show_dump(dump);
+    }
+    Ok(())
+}
+
+fn diff_line(name: &str, a: &str, b: &str) {
+    if a != b {
+        println!("{name}:");
+        println!("  - {a}");
+        println!("  + {b}");
+    }
+}
+
+/// Prints the lines only found in one of @a and @b.
+fn diff_lines(name: &str, a: &[String], b: &[String]) {
+    let a_set: BTreeSet<_> = a.iter().collect();
+    let b_set: BTreeSet<_> = b.iter().collect();
+    if a_set == b_set {
+        return;
+    }
+    println!("{name}:");
+    for line in a.iter().filter(|l| !b_set.contains(l)) {
+        println!("  - {line}");
+    }
+    for line in b.iter().filter(|l| !a_set.contains(l)) {
+        println!("  + {line}");
+    }
+}
+
+/// Prints the keys whose values differ between @a and @b.
+fn diff_counts<T: PartialEq + std::fmt::Display>(
+    name: &str,
+    a: &BTreeMap<String, T>,
+    b: &BTreeMap<String, T>,
+) {
+    let keys: BTreeSet<_> = a.keys().chain(b.keys()).collect();
+    let fmt = |v: Option<&T>| v.map(|v| v.to_string()).unwrap_or("-".into());
+    let mut header = false;
+    for key in keys {
+        let (va, vb) = (a.get(key), b.get(key));
+        if va == vb {
+            continue;
+        }
+        if !header {
+            println!("{name}:");
+            header = true;
+        }
+        println!("  {:<32} {} -> {}", key, fmt(va), fmt(vb));
+    }
+}
+
+fn dump_summary(dump: &CrashDump) -> BTreeMap<String, usize> {
+    let mut summary = BTreeMap::new();
+    summary.insert(
+        "runnable tasks".to_string(),
+        dump.cpus.iter().map(|c| c.tasks.len()).sum(),
+    );
+    summary.insert(
+        "cpus with runnable tasks".to_string(),
+        dump.cpus.iter().filter(|c| !c.tasks.is_empty()).count(),
+    );
+    for dsq in &dump.dsqs {
+        summary.insert(format!("dsq {} nr_tasks", dsq.dsq_id), dsq.tasks.len());
+    }
+    summary
+}
+
+fn diff(store: &CrashStore, a: &str, b: &str) -> Result<()> {
+    let a = CrashReport::load(&resolve(store, a)?)?;
+    let b = CrashReport::load(&resolve(store, b)?)?;
+
+    diff_line("scheduler", &a.scheduler, &b.scheduler);
+    diff_line("version", &a.version, &b.version);
+    diff_line("host", &a.hostname, &b.hostname);
+    diff_line("kernel", &a.kernel, &b.kernel);
+    diff_line("cmdline", &a.cmdline.join(" "), &b.cmdline.join(" "));
+    diff_line("topology", &topology_summary(&a), &topology_summary(&b));
+    diff_line(
+        "exit",
+        &format!("{} code={:#x}", a.exit_kind_name, a.exit_code),
+        &format!("{} code={:#x}", b.exit_kind_name, b.exit_code),
+    );
+    diff_line(
+        "reason",
+        a.reason.as_deref().unwrap_or("-"),
+        b.reason.as_deref().unwrap_or("-"),
+    );
+    diff_line(
+        "msg",
+        a.msg.as_deref().unwrap_or("-"),
+        b.msg.as_deref().unwrap_or("-"),
+    );
+
+    let empty = CrashDump::default();
+    let (da, db) = (
+        a.dump.as_ref().unwrap_or(&empty),
+        b.dump.as_ref().unwrap_or(&empty),
+    );
+    diff_lines("backtrace", &da.backtrace, &db.backtrace);
+    diff_lines("scheduler dump", &da.sched_dump, &db.sched_dump);
+    diff_counts("tasks", &dump_summary(da), &dump_summary(db));
+    diff_counts("event counters", &da.event_counters, &db.event_counters);
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let opts = Opts::parse();
+    let store = CrashStore::new(opts.dir);
+
+    match opts.cmd {
+        Cmd::List => list(&store),
+        Cmd::Show { report, json, raw } => show(&store, &report, json, raw),
+        Cmd::Diff { a, b } => diff(&store, &a, &b),
+    }
+}
@@ -0,0 +1,288 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+pub const CONFIG_PATH: &str = "/etc/scx/supervisor.toml";
+pub const LEGACY_CONFIG_PATH: &str = "/etc/default/scx";
+pub const SOCKET_PATH: &str = "/run/scx/supervisor.sock";
+
+/// Name of the profile used when none is given.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Flags and profiles of a scheduler.
+///
+/// ```text
+/// [schedulers.scx_lavd]
+/// flags = ["--autopower"]
+///
+/// [schedulers.scx_lavd.profiles]
+/// powersave = ["--powersave"]
+/// performance = ["--performance"]
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SchedulerConfig {
+    /// Flags of the default profile.
+    pub flags: Vec<String>,
+    /// Flags of named profiles, used instead of the default flags.
+    pub profiles: BTreeMap<String, Vec<String>>,
+}
+
+/// `scx_supervisor` configuration, read from /etc/scx/supervisor.toml.
+///
+/// ```text
+/// scheduler = "scx_lavd"
+/// fallback = "scx_bpfland"
+/// max_failures = 3
+///
+/// [schedulers.scx_bpfland]
+/// flags = ["-s", "20000"]
+/// ```
+///
+/// Without a config file, the scheduler and flags are taken from
+/// SCX_SCHEDULER and SCX_FLAGS in /etc/default/scx.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Scheduler started by the supervisor.
+    pub scheduler: Option<String>,
+    /// Profile of the scheduler.
+    pub profile: Option<String>,
+    /// Scheduler started after the scheduler failed max_failures times in a
+    /// row. Without one, the kernel's default scheduler is left in place.
+    pub fallback: Option<String>,
+    /// Profile of the fallback scheduler.
+    pub fallback_profile: Option<String>,
+    /// Number of consecutive failures before falling back.
+    pub max_failures: u32,
+    /// Delay before the first restart after a failure, doubled on each
+    /// consecutive failure.
+    pub backoff_ms: u64,
+    /// Maximum delay between restarts.
+    pub max_backoff_ms: u64,
+    /// A scheduler running this long is considered healthy again and its
+    /// failure count is reset.
+    pub stable_secs: u64,
+    /// Time given to a scheduler to exit after SIGINT before it's killed.
+    pub stop_timeout_ms: u64,
+    /// Control socket path.
+    pub socket: String,
+    /// Flags and profiles by scheduler name.
+    pub schedulers: BTreeMap<String, SchedulerConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            scheduler: None,
+            profile: None,
+            fallback: None,
+            fallback_profile: None,
+            max_failures: 3,
+            backoff_ms: 1000,
+            max_backoff_ms: 60000,
+            stable_secs: 60,
+            stop_timeout_ms: 10000,
+            socket: SOCKET_PATH.to_string(),
+            schedulers: BTreeMap::new(),
+        }
+    }
+}
+
+/// Parses a shell variable assignment value, stripping one level of quotes.
+fn unquote(val: &str) -> &str {
+    let val = val.trim();
+    for q in ['\'', '"'] {
+        if let Some(v) = val.strip_prefix(q).and_then(|v| v.strip_suffix(q)) {
+            return v;
+        }
+    }
+    val
+}
+
+/// Schedulers are looked up in PATH, so only accept plain scx_* names.
+pub fn check_scheduler_name(sched: &str) -> Result<()> {
+    if !sched.starts_with("scx_") || sched.contains('/') {
+        bail!("invalid scheduler name {:?}", sched);
+    }
+    Ok(())
+}
+
+impl Config {
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        let config: Self = toml::from_str(toml)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Builds a config from the legacy /etc/default/scx environment file.
+    pub fn from_legacy(env: &str) -> Result<Self> {
+        let mut vars = BTreeMap::new();
+        for line in env.lines() {
+            let line = line.trim();
+            if line.starts_with('#') {
+                continue;
+            }
+            if let Some((key, val)) = line.split_once('=') {
+                vars.insert(key.trim(), unquote(val));
+            }
+        }
+
+        let Some(scheduler) = vars.get("SCX_SCHEDULER").filter(|s| !s.is_empty()) else {
+            bail!("SCX_SCHEDULER is not set");
+        };
+        let flags = vars
+            .get("SCX_FLAGS")
+            .map(|f| f.split_whitespace().map(String::from).collect())
+            .unwrap_or_default();
+
+        let mut config = Self {
+            scheduler: Some(scheduler.to_string()),
+            ..Default::default()
+        };
+        config.schedulers.insert(
+            scheduler.to_string(),
+            SchedulerConfig {
+                flags,
+                ..Default::default()
+            },
+        );
+        Ok(config)
+    }
+
+    /// Loads @path, or the legacy environment file if @path doesn't exist.
+    pub fn load(path: &Path) -> Result<Self> {
+        if path.exists() {
+            let toml =
+                std::fs::read_to_string(path).with_context(|| format!("reading {path:?}"))?;
+            return Self::from_toml(&toml).with_context(|| format!("parsing {path:?}"));
+        }
+        match std::fs::read_to_string(LEGACY_CONFIG_PATH) {
+            Ok(env) => {
+                Self::from_legacy(&env).with_context(|| format!("parsing {LEGACY_CONFIG_PATH:?}"))
+            }
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        for (sched, profile) in [
+            (&self.scheduler, &self.profile),
+            (&self.fallback, &self.fallback_profile),
+        ] {
+            if let Some(sched) = sched {
+                check_scheduler_name(sched)?;
+            }
+            match (sched, profile) {
+                (Some(sched), Some(profile)) => {
+                    self.flags(sched, Some(profile))?;
+                }
+                (None, Some(profile)) => bail!("profile {:?} without a scheduler", profile),
+                _ => {}
+            }
+        }
+        if self.max_failures == 0 {
+            bail!("max_failures must be at least 1");
+        }
+        Ok(())
+    }
+
+    /// Returns the flags of @sched for @profile. Schedulers without a config
+    /// run without flags under the default profile.
+    pub fn flags(&self, sched: &str, profile: Option<&str>) -> Result<Vec<String>> {
+        let sched_config = self.schedulers.get(sched);
+        match profile.filter(|p| *p != DEFAULT_PROFILE) {
+            None => Ok(sched_config.map(|c| c.flags.clone()).unwrap_or_default()),
+            Some(profile) => match sched_config.and_then(|c| c.profiles.get(profile)) {
+                Some(flags) => Ok(flags.clone()),
+                None => bail!("{} has no profile {:?}", sched, profile),
+            },
+        }
+    }
+
+    /// Delay before restarting after @failures consecutive failures.
+    pub fn backoff(&self, failures: u32) -> Duration {
+        let shift = failures.saturating_sub(1).min(32);
+        let ms = self.backoff_ms.saturating_mul(1 << shift);
+        Duration::from_millis(ms.min(self.max_backoff_ms))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_toml() {
+        let config = Config::from_toml(
+            r#"
+            scheduler = "scx_lavd"
+            profile = "powersave"
+            fallback = "scx_bpfland"
+
+            [schedulers.scx_lavd]
+            flags = ["--autopower"]
+
+            [schedulers.scx_lavd.profiles]
+            powersave = ["--powersave"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.max_failures, 3);
+        assert_eq!(config.flags("scx_lavd", None).unwrap(), ["--autopower"]);
+        assert_eq!(
+            config.flags("scx_lavd", Some("default")).unwrap(),
+            ["--autopower"]
+        );
+        assert_eq!(
+            config.flags("scx_lavd", Some("powersave")).unwrap(),
+            ["--powersave"]
+        );
+        assert!(config.flags("scx_bpfland", None).unwrap().is_empty());
+        assert!(config.flags("scx_lavd", Some("turbo")).is_err());
+
+        assert!(Config::from_toml("scheduler = \"scx_lavd\"\nprofile = \"x\"").is_err());
+        assert!(Config::from_toml("schedular = \"scx_lavd\"").is_err());
+        assert!(Config::from_toml("scheduler = \"/bin/sh\"").is_err());
+    }
+
+    #[test]
+    fn test_from_legacy() {
+        let config = Config::from_legacy(
+            "# List of scx_schedulers: scx_bpfland\nSCX_SCHEDULER=scx_cosmos\n\nSCX_FLAGS='-s 700 -S'\n",
+        )
+        .unwrap();
+        assert_eq!(config.scheduler.as_deref(), Some("scx_cosmos"));
+        assert_eq!(
+            config.flags("scx_cosmos", None).unwrap(),
+            ["-s", "700", "-S"]
+        );
+
+        assert!(Config::from_legacy("SCX_FLAGS=-k").is_err());
+    }
+
+    #[test]
+    fn test_backoff() {
+        let config = Config {
+            backoff_ms: 500,
+            max_backoff_ms: 3000,
+            ..Default::default()
+        };
+        assert_eq!(config.backoff(1), Duration::from_millis(500));
+        assert_eq!(config.backoff(2), Duration::from_millis(1000));
+        assert_eq!(config.backoff(3), Duration::from_millis(2000));
+        assert_eq!(config.backoff(4), Duration::from_millis(3000));
+        assert_eq!(config.backoff(100), Duration::from_millis(3000));
+    }
+}
@@ -0,0 +1,225 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+//! Control API of the supervisor. Clients connect to the UNIX socket and send
+//! one JSON request per line, e.g. `{"cmd":"switch","scheduler":"scx_lavd",
+//! "profile":"powersave"}`, and receive one JSON response line for each.
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use log::warn;
+use serde::Deserialize;
+use serde::Serialize;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::os::unix::net::UnixListener;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::Duration;
+
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum Request {
+    Status,
+    /// Stop the scheduler, leaving the kernel's default scheduler in place.
+    Stop,
+    /// Stop the current scheduler and start @scheduler with @profile.
+    Switch {
+        scheduler: String,
+        profile: Option<String>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum State {
+    /// No scheduler is configured or it was stopped.
+    Stopped,
+    Running,
+    /// Waiting for the previous scheduler to exit. The configured scheduler
+    /// is started once it's gone.
+    Stopping,
+    /// Waiting to restart the scheduler after a failure.
+    Backoff,
+    /// The scheduler exited successfully and wasn't restarted.
+    Exited,
+    /// The scheduler and its fallback failed too many times.
+    Failed,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Status {
+    pub state: State,
+    pub scheduler: Option<String>,
+    pub profile: Option<String>,
+    pub flags: Vec<String>,
+    /// Whether the scheduler is the fallback of the configured one.
+    pub fallback: bool,
+    pub pid: Option<u32>,
+    pub uptime_secs: Option<u64>,
+    /// Consecutive failures of the scheduler.
+    pub failures: u32,
+    /// Number of restarts after failures.
+    pub restarts: u32,
+    pub last_exit: Option<String>,
+    pub last_crash_report: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Response {
+    Ok(Status),
+    Error(String),
+}
+
+/// A request along with the channel to send the response to.
+pub type Command = (Request, mpsc::Sender<Response>);
+
+fn serve_client(stream: UnixStream, tx: mpsc::Sender<Command>) -> Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let resp = match serde_json::from_str::<Request>(&line) {
+            Ok(req) => {
+                let (resp_tx, resp_rx) = mpsc::channel();
+                tx.send((req, resp_tx))?;
+                resp_rx.recv()?
+            }
+            Err(e) => Response::Error(format!("invalid request: {e}")),
+        };
+        let mut out = serde_json::to_string(&resp)?;
+        out.push('\n');
+        writer.write_all(out.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Creates the control socket at @path, which is only accessible to root,
+/// and returns the channel receiving the client requests.
+pub fn listen(path: &Path) -> Result<mpsc::Receiver<Command>> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).with_context(|| format!("creating {dir:?}"))?;
+    }
+    if UnixStream::connect(path).is_ok() {
+        bail!("{:?} is in use, is another supervisor running?", path);
+    }
+    let _ = std::fs::remove_file(path);
+
+    // Create the socket with the right permissions rather than changing them
+    // after binding, which would leave it open to everyone in between.
+    let old_umask = unsafe { libc::umask(0o177) };
+    let listener = UnixListener::bind(path);
+    unsafe { libc::umask(old_umask) };
+    let listener = listener.with_context(|| format!("creating UNIX socket {path:?}"))?;
+
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let tx = tx.clone();
+                    std::thread::spawn(move || {
+                        if let Err(e) = serve_client(stream, tx) {
+                            warn!("control client: {:#}", e);
+                        }
+                    });
+                }
+                Err(e) => warn!("control socket: {}", e),
+            }
+        }
+    });
+    Ok(rx)
+}
+
+/// Sends @req to the supervisor listening on @path.
+pub fn request(path: &Path, req: &Request) -> Result<Status> {
+    let mut stream = UnixStream::connect(path)
+        .with_context(|| format!("connecting to {path:?}, is scx_supervisor running?"))?;
+    let mut out = serde_json::to_string(req)?;
+    out.push('\n');
+    stream.write_all(out.as_bytes())?;
+
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    match serde_json::from_str(&line).context("invalid response")? {
+        Response::Ok(status) => Ok(status),
+        Response::Error(e) => bail!("{}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_request_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sock");
+        let rx = listen(&path).unwrap();
+
+        let status = Status {
+            state: State::Running,
+            scheduler: Some("scx_lavd".into()),
+            profile: Some("powersave".into()),
+            flags: vec!["--powersave".into()],
+            fallback: false,
+            pid: Some(42),
+            uptime_secs: Some(1),
+            failures: 0,
+            restarts: 0,
+            last_exit: None,
+            last_crash_report: None,
+        };
+        let expected = status.clone();
+        let server = std::thread::spawn(move || {
+            let (req, tx) = rx.recv().unwrap();
+            assert_eq!(
+                req,
+                Request::Switch {
+                    scheduler: "scx_lavd".into(),
+                    profile: Some("powersave".into())
+                }
+            );
+            tx.send(Response::Ok(status)).unwrap();
+
+            let (req, tx) = rx.recv().unwrap();
+            assert_eq!(req, Request::Stop);
+            tx.send(Response::Error("nope".into())).unwrap();
+        });
+
+        let req = Request::Switch {
+            scheduler: "scx_lavd".into(),
+            profile: Some("powersave".into()),
+        };
+        assert_eq!(request(&path, &req).unwrap(), expected);
+        assert_eq!(
+            request(&path, &Request::Stop).unwrap_err().to_string(),
+            "nope"
+        );
+        server.join().unwrap();
+
+        assert!(listen(&path).is_err());
+    }
+
+    #[test]
+    fn test_socket_permissions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sock");
+        let _rx = listen(&path).unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
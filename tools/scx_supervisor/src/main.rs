@@ -0,0 +1,140 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+mod config;
+mod control;
+mod supervisor;
+
+use anyhow::Context;
+use anyhow::Result;
+use clap::Parser;
+use clap::Subcommand;
+use config::Config;
+use config::CONFIG_PATH;
+use control::Request;
+use control::Status;
+use log::info;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use supervisor::Supervisor;
+
+/// Supervise a sched_ext scheduler.
+///
+/// `run` starts the scheduler configured in /etc/scx/supervisor.toml (or
+/// SCX_SCHEDULER and SCX_FLAGS in /etc/default/scx), restarts it with
+/// exponential backoff when it fails and switches to the fallback scheduler
+/// after too many consecutive failures. The other subcommands control a
+/// running supervisor through its UNIX socket.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Opts {
+    /// Config file.
+    #[clap(short, long, default_value = CONFIG_PATH)]
+    config: PathBuf,
+
+    /// Control socket, overrides the config.
+    #[clap(short, long)]
+    socket: Option<PathBuf>,
+
+    #[command(subcommand)]
+    cmd: Option<Cmd>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Cmd {
+    /// Run the supervisor (default).
+    Run,
+    /// Show the state of the scheduler.
+    Status {
+        /// Print the status as JSON.
+        #[clap(long)]
+        json: bool,
+    },
+    /// Stop the scheduler, leaving the kernel's default scheduler in place.
+    Stop,
+    /// Stop the current scheduler and start another one.
+    Switch {
+        /// Scheduler, e.g. scx_lavd.
+        scheduler: String,
+        /// Flag profile of the scheduler.
+        profile: Option<String>,
+    },
+}
+
+fn print_status(status: &Status) {
+    let opt = |v: &Option<String>| v.clone().unwrap_or("-".into());
+    println!("state:       {:?}", status.state);
+    println!(
+        "scheduler:   {}{}",
+        opt(&status.scheduler),
+        if status.fallback { " (fallback)" } else { "" }
+    );
+    println!("profile:     {}", opt(&status.profile));
+    println!("flags:       {}", status.flags.join(" "));
+    if let (Some(pid), Some(uptime)) = (status.pid, status.uptime_secs) {
+        println!("pid:         {pid}");
+        println!("uptime:      {uptime}s");
+    }
+    println!("failures:    {}", status.failures);
+    println!("restarts:    {}", status.restarts);
+    println!("last exit:   {}", opt(&status.last_exit));
+    println!("last crash:  {}", opt(&status.last_crash_report));
+}
+
+fn run(config: Config, socket: PathBuf) -> Result<()> {
+    let mut lcfg = simplelog::ConfigBuilder::new();
+    lcfg.set_time_offset_to_local()
+        .expect("Failed to set local time offset")
+        .set_time_level(simplelog::LevelFilter::Error)
+        .set_location_level(simplelog::LevelFilter::Off)
+        .set_target_level(simplelog::LevelFilter::Off)
+        .set_thread_level(simplelog::LevelFilter::Off);
+    simplelog::TermLogger::init(
+        simplelog::LevelFilter::Info,
+        lcfg.build(),
+        simplelog::TerminalMode::Stderr,
+        simplelog::ColorChoice::Auto,
+    )?;
+
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let shutdown_clone = shutdown.clone();
+    ctrlc::set_handler(move || {
+        shutdown_clone.store(true, Ordering::Relaxed);
+    })
+    .context("Error setting Ctrl-C handler")?;
+
+    let commands = control::listen(&socket)?;
+    info!("listening on {}", socket.display());
+
+    let mut supervisor = Supervisor::new(config)?;
+    supervisor.run(commands, shutdown);
+    let _ = std::fs::remove_file(&socket);
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let opts = Opts::parse();
+    let config = Config::load(&opts.config)?;
+    let socket = opts.socket.unwrap_or(PathBuf::from(&config.socket));
+
+    let req = match opts.cmd.unwrap_or(Cmd::Run) {
+        Cmd::Run => return run(config, socket),
+        Cmd::Status { json } => {
+            let status = control::request(&socket, &Request::Status)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&status)?);
+            } else {
+                print_status(&status);
+            }
+            return Ok(());
+        }
+        Cmd::Stop => Request::Stop,
+        Cmd::Switch { scheduler, profile } => Request::Switch { scheduler, profile },
+    };
+    print_status(&control::request(&socket, &req)?);
+    Ok(())
+}
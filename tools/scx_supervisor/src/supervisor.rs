@@ -0,0 +1,461 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+use crate::config::check_scheduler_name;
+use crate::config::Config;
+use crate::control::Command;
+use crate::control::Request;
+use crate::control::Response;
+use crate::control::State;
+use crate::control::Status;
+use anyhow::Result;
+use log::error;
+use log::info;
+use log::warn;
+use scx_utils::CrashReport;
+use scx_utils::CrashStore;
+use std::process::Child;
+use std::process::ExitStatus;
+use std::process::Stdio;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A scheduler that was asked to exit.
+struct Stopping {
+    child: Child,
+    /// When it's killed if it's still running.
+    deadline: Instant,
+}
+
+#[derive(Clone, Debug)]
+struct Target {
+    scheduler: String,
+    profile: Option<String>,
+    flags: Vec<String>,
+    fallback: bool,
+}
+
+/// Runs the configured scheduler as a child process. Failed schedulers are
+/// restarted with exponential backoff and replaced by the fallback scheduler
+/// after too many consecutive failures. Schedulers restart themselves when
+/// the BPF scheduler requests it (UserExitInfo::should_restart()), so a
+/// successful exit means the scheduler is done and it isn't restarted.
+pub struct Supervisor {
+    config: Config,
+    target: Option<Target>,
+    child: Option<Child>,
+    stopping: Option<Stopping>,
+    /// Whether to start the target once the stopping scheduler is gone.
+    start_after_stop: bool,
+    started_at: Instant,
+    state: State,
+    restart_at: Option<Instant>,
+    failures: u32,
+    restarts: u32,
+    last_exit: Option<String>,
+    last_crash_report: Option<String>,
+}
+
+impl Supervisor {
+    pub fn new(config: Config) -> Result<Self> {
+        let target = match &config.scheduler {
+            Some(sched) => Some(Target {
+                scheduler: sched.clone(),
+                profile: config.profile.clone(),
+                flags: config.flags(sched, config.profile.as_deref())?,
+                fallback: false,
+            }),
+            None => None,
+        };
+        Ok(Self {
+            config,
+            target,
+            child: None,
+            stopping: None,
+            start_after_stop: false,
+            started_at: Instant::now(),
+            state: State::Stopped,
+            restart_at: None,
+            failures: 0,
+            restarts: 0,
+            last_exit: None,
+            last_crash_report: None,
+        })
+    }
+
+    pub fn status(&self) -> Status {
+        let running = self.child.is_some();
+        Status {
+            state: self.state,
+            scheduler: self.target.as_ref().map(|t| t.scheduler.clone()),
+            profile: self.target.as_ref().and_then(|t| t.profile.clone()),
+            flags: self
+                .target
+                .as_ref()
+                .map(|t| t.flags.clone())
+                .unwrap_or_default(),
+            fallback: self.target.as_ref().is_some_and(|t| t.fallback),
+            pid: self.child.as_ref().map(|c| c.id()),
+            uptime_secs: running.then(|| self.started_at.elapsed().as_secs()),
+            failures: self.failures,
+            restarts: self.restarts,
+            last_exit: self.last_exit.clone(),
+            last_crash_report: self.last_crash_report.clone(),
+        }
+    }
+
+    fn start(&mut self) {
+        self.restart_at = None;
+        // Only one sched_ext scheduler can be loaded at a time.
+        if self.stopping.is_some() {
+            self.start_after_stop = true;
+            self.state = State::Stopping;
+            return;
+        }
+        let Some(target) = self.target.clone() else {
+            self.state = State::Stopped;
+            return;
+        };
+
+        info!("starting {} {:?}", target.scheduler, target.flags);
+        self.started_at = Instant::now();
+        match std::process::Command::new(&target.scheduler)
+            .args(&target.flags)
+            .stdin(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => {
+                self.child = Some(child);
+                self.state = State::Running;
+            }
+            Err(e) => {
+                self.last_exit = Some(format!("failed to start: {e}"));
+                error!("failed to start {}: {}", target.scheduler, e);
+                self.on_failure(None);
+            }
+        }
+    }
+
+    /// Asks the scheduler to exit with SIGINT. poll_stopping() waits for it
+    /// and kills it if it doesn't exit in time.
+    fn stop_child(&mut self) {
+        let Some(child) = self.child.take() else {
+            return;
+        };
+        info!("stopping {}", child.id());
+        unsafe {
+            libc::kill(child.id() as libc::pid_t, libc::SIGINT);
+        }
+
+        self.state = State::Stopping;
+        self.stopping = Some(Stopping {
+            child,
+            deadline: Instant::now() + Duration::from_millis(self.config.stop_timeout_ms),
+        });
+    }
+
+    /// Checks whether the stopping scheduler exited, killing it once its
+    /// deadline passed, and starts the target afterwards if requested.
+    fn poll_stopping(&mut self) {
+        let Some(stopping) = self.stopping.as_mut() else {
+            return;
+        };
+        let status = match stopping.child.try_wait() {
+            Ok(Some(status)) => Some(status),
+            Ok(None) if Instant::now() < stopping.deadline => return,
+            _ => {
+                warn!("{} didn't exit in time, killing it", stopping.child.id());
+                let _ = stopping.child.kill();
+                stopping.child.wait().ok()
+            }
+        };
+        self.stopping = None;
+        self.last_exit = status.map(|s| s.to_string());
+
+        if std::mem::take(&mut self.start_after_stop) {
+            self.start();
+        } else {
+            self.state = State::Stopped;
+        }
+    }
+
+    /// Stops the scheduler and waits for it to exit.
+    fn stop_and_wait(&mut self) {
+        self.stop_child();
+        self.start_after_stop = false;
+        while self.stopping.is_some() {
+            self.poll_stopping();
+            if self.stopping.is_some() {
+                std::thread::sleep(POLL_INTERVAL);
+            }
+        }
+    }
+
+    /// Returns the path of the crash report saved by @pid, if any.
+    fn find_crash_report(pid: u32) -> Option<String> {
+        let store = CrashStore::from_env()?;
+        let path = store.list().ok()?.pop()?;
+        let report = CrashReport::load(&path).ok()?;
+        (report.pid == pid).then(|| path.display().to_string())
+    }
+
+    fn on_exit(&mut self, pid: u32, status: ExitStatus) {
+        let sched = self
+            .target
+            .as_ref()
+            .map(|t| t.scheduler.clone())
+            .unwrap_or_default();
+        self.last_exit = Some(status.to_string());
+
+        if status.success() {
+            info!("{} exited successfully, not restarting", sched);
+            self.state = State::Exited;
+            return;
+        }
+
+        self.last_crash_report = Self::find_crash_report(pid);
+        match &self.last_crash_report {
+            Some(path) => error!("{} failed with {}, crash report {}", sched, status, path),
+            None => error!("{} failed with {}", sched, status),
+        }
+        self.on_failure(Some(self.started_at.elapsed()));
+    }
+
+    /// Schedules a restart after a failure or falls back if the scheduler
+    /// failed too many times in a row. @ran is how long the scheduler ran.
+    fn on_failure(&mut self, ran: Option<Duration>) {
+        if ran.is_some_and(|ran| ran.as_secs() >= self.config.stable_secs) {
+            self.failures = 0;
+        }
+        self.failures += 1;
+
+        let Some(target) = self.target.clone() else {
+            return;
+        };
+        if self.failures < self.config.max_failures {
+            let delay = self.config.backoff(self.failures);
+            warn!(
+                "restarting {} in {:?} ({}/{} failures)",
+                target.scheduler, delay, self.failures, self.config.max_failures
+            );
+            self.state = State::Backoff;
+            self.restart_at = Some(Instant::now() + delay);
+            return;
+        }
+
+        let fallback = match (&self.config.fallback, target.fallback) {
+            (Some(fallback), false) => self
+                .config
+                .flags(fallback, self.config.fallback_profile.as_deref())
+                .map(|flags| (fallback.clone(), flags)),
+            _ => {
+                error!(
+                    "{} failed {} times in a row, leaving the kernel's default scheduler in place",
+                    target.scheduler, self.failures
+                );
+                self.state = State::Failed;
+                return;
+            }
+        };
+        match fallback {
+            Ok((fallback, flags)) => {
+                warn!(
+                    "{} failed {} times in a row, falling back to {}",
+                    target.scheduler, self.failures, fallback
+                );
+                self.target = Some(Target {
+                    scheduler: fallback,
+                    profile: self.config.fallback_profile.clone(),
+                    flags,
+                    fallback: true,
+                });
+                self.failures = 0;
+                self.start();
+            }
+            Err(e) => {
+                error!("invalid fallback: {:#}", e);
+                self.state = State::Failed;
+            }
+        }
+    }
+
+    fn switch(&mut self, scheduler: String, profile: Option<String>) -> Result<()> {
+        check_scheduler_name(&scheduler)?;
+        let flags = self.config.flags(&scheduler, profile.as_deref())?;
+
+        self.stop_child();
+        info!(
+            "switching to {} (profile {})",
+            scheduler,
+            profile.as_deref().unwrap_or("default")
+        );
+        self.target = Some(Target {
+            scheduler,
+            profile,
+            flags,
+            fallback: false,
+        });
+        self.failures = 0;
+        self.last_crash_report = None;
+        self.start();
+        Ok(())
+    }
+
+    fn handle(&mut self, req: Request) -> Response {
+        match req {
+            Request::Status => {}
+            Request::Stop => {
+                self.stop_child();
+                self.restart_at = None;
+                self.start_after_stop = false;
+                if self.stopping.is_none() {
+                    self.state = State::Stopped;
+                }
+            }
+            Request::Switch { scheduler, profile } => {
+                if let Err(e) = self.switch(scheduler, profile) {
+                    return Response::Error(format!("{e:#}"));
+                }
+            }
+        }
+        Response::Ok(self.status())
+    }
+
+    fn poll_child(&mut self) {
+        let Some(child) = self.child.as_mut() else {
+            return;
+        };
+        let pid = child.id();
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                self.child = None;
+                self.on_exit(pid, status);
+            }
+            Ok(None) => {
+                if self.failures > 0
+                    && self.started_at.elapsed().as_secs() >= self.config.stable_secs
+                {
+                    self.failures = 0;
+                }
+            }
+            Err(e) => warn!("failed to wait for {}: {}", pid, e),
+        }
+    }
+
+    /// Supervises the scheduler and serves @commands until @shutdown is set.
+    /// Requests are served while a scheduler is being stopped. The scheduler
+    /// is stopped before returning.
+    pub fn run(&mut self, commands: Receiver<Command>, shutdown: Arc<AtomicBool>) {
+        self.start();
+
+        while !shutdown.load(Ordering::Relaxed) {
+            match commands.recv_timeout(POLL_INTERVAL) {
+                Ok((req, tx)) => {
+                    let _ = tx.send(self.handle(req));
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => std::thread::sleep(POLL_INTERVAL),
+            }
+
+            self.poll_stopping();
+            self.poll_child();
+            if self.restart_at.is_some_and(|at| Instant::now() >= at) {
+                self.restarts += 1;
+                self.start();
+            }
+        }
+
+        self.stop_and_wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SchedulerConfig;
+
+    /// A supervisor of a "scheduler" ignoring SIGINT, which has to be killed.
+    fn stubborn_supervisor(stop_timeout_ms: u64) -> Supervisor {
+        let mut config = Config {
+            scheduler: Some("sh".into()),
+            stop_timeout_ms,
+            ..Default::default()
+        };
+        config.schedulers.insert(
+            "sh".into(),
+            SchedulerConfig {
+                flags: vec!["-c".into(), "trap '' INT; sleep 60".into()],
+                ..Default::default()
+            },
+        );
+        Supervisor::new(config).unwrap()
+    }
+
+    fn wait_for(sup: &mut Supervisor, state: State) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while sup.state != state {
+            assert!(Instant::now() < deadline, "stuck in {:?}", sup.state);
+            sup.poll_stopping();
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_stop_doesnt_block() {
+        let mut sup = stubborn_supervisor(60_000);
+        sup.start();
+        assert_eq!(sup.state, State::Running);
+
+        let start = Instant::now();
+        let Response::Ok(status) = sup.handle(Request::Stop) else {
+            panic!("stop failed");
+        };
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert_eq!(status.state, State::Stopping);
+        assert_eq!(status.pid, None);
+
+        // Requests are still served while the scheduler is being stopped.
+        let Response::Ok(status) = sup.handle(Request::Status) else {
+            panic!("status failed");
+        };
+        assert_eq!(status.state, State::Stopping);
+
+        sup.stopping.as_mut().unwrap().deadline = Instant::now();
+        wait_for(&mut sup, State::Stopped);
+        assert!(sup.stopping.is_none());
+        assert!(sup.child.is_none());
+        assert!(sup.last_exit.is_some());
+    }
+
+    #[test]
+    fn test_start_after_stop() {
+        let mut sup = stubborn_supervisor(0);
+        sup.start();
+        let pid = sup.child.as_ref().unwrap().id();
+
+        // The next scheduler only starts once the previous one is gone.
+        sup.stop_child();
+        sup.start();
+        assert_eq!(sup.state, State::Stopping);
+        assert!(sup.child.is_none());
+
+        wait_for(&mut sup, State::Running);
+        assert_ne!(sup.child.as_ref().unwrap().id(), pid);
+
+        // A stop request cancels the pending start.
+        sup.stop_child();
+        sup.start();
+        sup.handle(Request::Stop);
+        wait_for(&mut sup, State::Stopped);
+        assert!(sup.child.is_none());
+    }
+}
@@ -0,0 +1,119 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+use anyhow::Context;
+use anyhow::Result;
+use clap::Parser;
+use clap::Subcommand;
+use scx_utils::Topology;
+use scx_utils::TopologyArgs;
+use std::io::Write;
+
+/// Capture and inspect host topologies as seen by sched_ext schedulers.
+///
+/// `dump` serializes the topology built by scx_utils into JSON which can be
+/// loaded back with `Topology::from_json()`, e.g. to check topologies of
+/// production machines into tests. Set SCX_SYSFS_PREFIX to build the
+/// topology from a copy of sysfs.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Opts {
+    #[command(subcommand)]
+    cmd: Cmd,
+}
+
+#[derive(Debug, Subcommand)]
+enum Cmd {
+    /// Dump the host topology as JSON.
+    Dump {
+        /// Write to FILE instead of stdout.
+        #[clap(short, long)]
+        output: Option<String>,
+
+        /// Don't pretty-print the output.
+        #[clap(short, long)]
+        compact: bool,
+
+        #[clap(flatten)]
+        topology: TopologyArgs,
+    },
+    /// Load a topology dump and print a summary of it.
+    Show {
+        /// JSON file produced by `dump`.
+        file: String,
+    },
+}
+
+fn dump(output: Option<String>, compact: bool, args: &TopologyArgs) -> Result<()> {
+    let topo = Topology::with_args(args)?;
+    let mut json = if compact {
+        serde_json::to_string(&topo)?
+    } else {
+        serde_json::to_string_pretty(&topo)?
+    };
+    json.push('\n');
+
+    match output {
+        Some(path) => std::fs::write(&path, json).with_context(|| format!("writing {path:?}"))?,
+        None => std::io::stdout().write_all(json.as_bytes())?,
+    }
+    Ok(())
+}
+
+fn show(file: &str) -> Result<()> {
+    let json = std::fs::read_to_string(file).with_context(|| format!("reading {file:?}"))?;
+    let topo = Topology::from_json(&json)?;
+
+    println!(
+        "cpus={} cores={} llcs={} nodes={} smt={} span={}",
+        topo.all_cpus.len(),
+        topo.all_cores.len(),
+        topo.all_llcs.len(),
+        topo.nodes.len(),
+        topo.smt_enabled,
+        topo.span.to_cpulist()
+    );
+    for node in topo.nodes.values() {
+        println!(
+            "node{:<3} cpus={:<16} distance={:?}",
+            node.id,
+            node.span.to_cpulist(),
+            node.distance
+        );
+        for llc in node.llcs.values() {
+            println!(
+                "  llc{:<4} cpus={:<16} cores={}",
+                llc.id,
+                llc.span.to_cpulist(),
+                llc.cores.len()
+            );
+            for core in llc.cores.values() {
+                let cpu = core.cpus.values().next();
+                println!(
+                    "    core{:<4} cpus={:<12} type={:?} capacity={} max_freq={}",
+                    core.id,
+                    core.span.to_cpulist(),
+                    core.core_type,
+                    cpu.map(|c| c.cpu_capacity).unwrap_or(0),
+                    cpu.map(|c| c.max_freq).unwrap_or(0)
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let opts = Opts::parse();
+
+    match opts.cmd {
+        Cmd::Dump {
+            output,
+            compact,
+            topology,
+        } => dump(output, compact, &topology),
+        Cmd::Show { file } => show(&file),
+    }
+}
@@ -17,6 +17,9 @@ use crate::columns::{
 use crate::config::get_config_path;
 use crate::config::Config;
 use crate::get_default_events;
+use crate::recording::{
+    fill_event_data, latest_samples, ProcSample, RecordedFrame, Recording, ReplayState,
+};
 use crate::render::bpf_programs::{ProgramDetailParams, ProgramsListParams};
 use crate::render::scheduler::{DsqSummaryParams, ProcessLatencyParams, SchedulerViewParams};
 use crate::render::{
@@ -223,6 +226,9 @@ pub struct App<'a> {
 
     // capability warnings for non-root users
     capability_warnings: Vec<String>,
+
+    // playback of a recording instead of live data
+    replay: Option<ReplayState>,
 }
 
 impl<'a> App<'a> {
@@ -470,6 +476,7 @@ impl<'a> App<'a> {
             perf_top_table_state: TableState::default(),
             perf_top_filtered_symbols: Vec::new(),
             capability_warnings: Vec::new(),
+            replay: None,
         };
 
         // Set the initial filter state
@@ -734,6 +741,7 @@ impl<'a> App<'a> {
             perf_top_table_state: TableState::default(),
             perf_top_filtered_symbols: Vec::new(),
             capability_warnings: Vec::new(),
+            replay: None,
         };
 
         // Set the initial filter state
@@ -745,6 +753,206 @@ impl<'a> App<'a> {
         Ok(app)
     }
 
+    /// Creates an application that plays back a recording instead of
+    /// collecting data from the running system.
+    pub fn new_replay(
+        config: Config,
+        recording: Recording,
+        max_cpu_events: usize,
+        action_tx: UnboundedSender<Action>,
+    ) -> Result<Self> {
+        let Recording {
+            header,
+            topology,
+            frames,
+        } = recording;
+        let replay = ReplayState::new(header, frames)?;
+        let mut app =
+            Self::new_without_bpf(config, replay.title(), max_cpu_events, -1, false, action_tx)?;
+
+        let default_events: Vec<String> = app
+            .available_events
+            .iter()
+            .map(|event| event.event_name().to_string())
+            .collect();
+        let default_events_str: Vec<&str> = default_events.iter().map(|e| e.as_str()).collect();
+
+        app.cpu_data.clear();
+        app.llc_data.clear();
+        app.node_data.clear();
+        app.active_prof_events.clear();
+        for cpu in topology.all_cpus.values() {
+            // Recorded CPUs may not exist here, the events are never read.
+            app.active_prof_events
+                .insert(cpu.id, app.active_event.clone());
+            let mut data =
+                CpuData::new(cpu.id, cpu.core_id, cpu.llc_id, cpu.node_id, max_cpu_events);
+            data.initialize_events(&default_events_str);
+            app.cpu_data.insert(cpu.id, data);
+        }
+        for llc in topology.all_llcs.values() {
+            let mut data = LlcData::new(llc.id, llc.node_id, llc.all_cpus.len(), max_cpu_events);
+            data.initialize_events(&default_events_str);
+            app.llc_data.insert(llc.id, data);
+        }
+        for node in topology.nodes.values() {
+            let mut data = NodeData::new(node.id, node.all_cpus.len(), max_cpu_events);
+            data.initialize_events(&default_events_str);
+            app.node_data.insert(node.id, data);
+        }
+        app.proc_data.clear();
+        app.large_core_count = topology.all_cpus.len() >= 128;
+        app.topo = topology;
+        app.collect_uncore_freq = false;
+        app.replay = Some(replay);
+        app.apply_replay_frame();
+
+        Ok(app)
+    }
+
+    /// Returns the topology the data is shown for.
+    pub fn topology(&self) -> &Topology {
+        &self.topo
+    }
+
+    /// Returns true if the application is playing back a recording.
+    pub fn is_replay(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    /// Samples the currently collected data for a recording.
+    pub fn record_frame(&mut self, ts_ms: u64) -> Result<RecordedFrame> {
+        self.update_power_data()?;
+
+        fn samples<K: Copy + Ord>(
+            data: impl Iterator<Item = (K, &EventData)>,
+        ) -> BTreeMap<K, crate::recording::Samples> {
+            data.map(|(key, data)| (key, latest_samples(data)))
+                .filter(|(_, samples)| !samples.is_empty())
+                .collect()
+        }
+
+        Ok(RecordedFrame {
+            ts_ms,
+            cpus: samples(self.cpu_data.iter().map(|(id, data)| (*id, &data.data))),
+            llcs: samples(self.llc_data.iter().map(|(id, data)| (*id, &data.data))),
+            nodes: samples(self.node_data.iter().map(|(id, data)| (*id, &data.data))),
+            dsqs: samples(self.dsq_data.iter().map(|(id, data)| (*id, data))),
+            procs: self
+                .proc_data
+                .iter()
+                .map(|(tgid, data)| (*tgid, ProcSample::from_proc_data(data)))
+                .collect(),
+            mem: Some(self.mem_info.clone()),
+            power: Some(self.power_snapshot.current.clone())
+                .filter(|power| !power.cores.is_empty()),
+        })
+    }
+
+    /// Replay: plays the recording for one tick.
+    fn on_tick_replay(&mut self) -> Result<()> {
+        let tick_rate_ms = self.config.tick_rate_ms() as u64;
+        if let Some(replay) = self.replay.as_mut() {
+            replay.advance(tick_rate_ms);
+        }
+        self.apply_replay_frame();
+        Ok(())
+    }
+
+    /// Replay: rebuilds the displayed data from the frames up to the current
+    /// playback position.
+    fn apply_replay_frame(&mut self) {
+        let Some(replay) = self.replay.as_mut() else {
+            return;
+        };
+        if !replay.take_changed() {
+            return;
+        }
+        let replay = self.replay.as_ref().unwrap();
+        let window = replay.window(self.max_cpu_events);
+        let frame = replay.frame();
+
+        for (cpu, data) in self.cpu_data.iter_mut() {
+            let samples: Vec<_> = window.iter().map(|f| f.cpus.get(cpu)).collect();
+            fill_event_data(&mut data.data, &samples);
+        }
+        for (llc, data) in self.llc_data.iter_mut() {
+            let samples: Vec<_> = window.iter().map(|f| f.llcs.get(llc)).collect();
+            fill_event_data(&mut data.data, &samples);
+        }
+        for (node, data) in self.node_data.iter_mut() {
+            let samples: Vec<_> = window.iter().map(|f| f.nodes.get(node)).collect();
+            fill_event_data(&mut data.data, &samples);
+        }
+
+        self.dsq_data.clear();
+        for dsq in window.iter().flat_map(|f| f.dsqs.keys()) {
+            if let Entry::Vacant(entry) = self.dsq_data.entry(*dsq) {
+                let samples: Vec<_> = window.iter().map(|f| f.dsqs.get(dsq)).collect();
+                let mut data = EventData::new(self.max_sched_events);
+                fill_event_data(&mut data, &samples);
+                entry.insert(data);
+            }
+        }
+
+        self.proc_data.clear();
+        for (tgid, proc_sample) in &frame.procs {
+            let mut data = proc_sample.to_proc_data(*tgid, self.max_cpu_events);
+            let samples: Vec<_> = window
+                .iter()
+                .map(|f| f.procs.get(tgid).map(|p| &p.events))
+                .collect();
+            fill_event_data(&mut data.data, &samples);
+            self.proc_data.insert(*tgid, data);
+        }
+        if self
+            .selected_process
+            .is_some_and(|tgid| !self.proc_data.contains_key(&tgid))
+        {
+            self.selected_process = None;
+        }
+
+        if let Some(mem) = &frame.mem {
+            self.mem_info = mem.clone();
+        }
+        self.power_snapshot = crate::PowerSnapshot::new();
+        for power in window.iter().filter_map(|f| f.power.as_ref()) {
+            self.power_snapshot.update(power.clone());
+        }
+
+        self.scheduler = replay.title();
+        if self.state != AppState::BpfPrograms {
+            self.filter_events();
+        }
+    }
+
+    /// Replay: handles an action, ignoring those that act on the running system.
+    fn handle_replay_action(&mut self, action: &Action) -> Result<bool> {
+        let Some(replay) = self.replay.as_mut() else {
+            return Ok(false);
+        };
+        const SEEK_MS: i64 = 10_000;
+        match action {
+            Action::Tick => self.on_tick_replay()?,
+            Action::ReplayTogglePlay => replay.toggle_play(),
+            Action::ReplaySeekBackward => replay.seek_by(-SEEK_MS),
+            Action::ReplaySeekForward => replay.seek_by(SEEK_MS),
+            Action::ReplaySeekStart => replay.seek_start(),
+            Action::ReplaySeekEnd => replay.seek_end(),
+            Action::NextEvent
+            | Action::PrevEvent
+            | Action::ClearEvent
+            | Action::RequestTrace
+            | Action::ReloadStatsClient
+            | Action::ToggleBpfPerfSampling
+            | Action::IncBpfSampleRate
+            | Action::DecBpfSampleRate => {}
+            _ => return Ok(false),
+        }
+        self.apply_replay_frame();
+        Ok(true)
+    }
+
     /// Returns the state of the application.
     pub fn state(&self) -> AppState {
         self.state.clone()
@@ -1086,6 +1294,9 @@ impl<'a> App<'a> {
             cpu_data.data.set_max_size(max_events);
         }
         self.max_cpu_events = max_events;
+        if let Some(replay) = self.replay.as_mut() {
+            replay.mark_changed();
+        }
     }
 
     /// Saves the current config.
@@ -2650,7 +2861,7 @@ impl<'a> App<'a> {
         if pause == " " {
             pause = "Space".to_string();
         }
-        let text = vec![
+        let mut text = vec![
             Line::from(Span::styled(
                 LICENSE,
                 Style::default().add_modifier(Modifier::ITALIC),
@@ -2985,6 +3196,34 @@ impl<'a> App<'a> {
                 Style::default(),
             )),
         ];
+        if self.replay.is_some() {
+            // Show the replay bindings above the bug reporting footer
+            let footer = text.split_off(text.len() - 3);
+            text.push(Line::from(Span::styled(
+                "Replay Key Bindings:",
+                Style::default(),
+            )));
+            text.push(Line::from(Span::styled(
+                "Space: play/pause",
+                Style::default(),
+            )));
+            for (action, desc) in [
+                (Action::ReplaySeekBackward, "seek backward"),
+                (Action::ReplaySeekForward, "seek forward"),
+                (Action::ReplaySeekStart, "seek to start"),
+                (Action::ReplaySeekEnd, "seek to end"),
+            ] {
+                text.push(Line::from(Span::styled(
+                    format!(
+                        "{}: {desc}",
+                        self.config.active_keymap.action_keys_string(action)
+                    ),
+                    Style::default(),
+                )));
+            }
+            text.push("\n".into());
+            text.extend(footer);
+        }
         frame.render_widget(
             Paragraph::new(text)
                 .block(
@@ -4312,11 +4551,14 @@ impl<'a> App<'a> {
                 self.filtering = false;
                 self.event_input_buffer.clear();
 
-                if let Some(proc_data) = self.selected_proc_data() {
-                    proc_data.init_threads()?;
+                // Threads aren't recorded
+                if self.replay.is_none() {
+                    if let Some(proc_data) = self.selected_proc_data() {
+                        proc_data.init_threads()?;
 
-                    // Kick off thread view
-                    self.in_thread_view = true;
+                        // Kick off thread view
+                        self.in_thread_view = true;
+                    }
                 }
 
                 self.filter_events();
@@ -5466,6 +5708,9 @@ impl<'a> App<'a> {
 
     /// Handles the action and updates application states.
     pub fn handle_action(&mut self, action: &Action) -> Result<()> {
+        if self.handle_replay_action(action)? {
+            return Ok(());
+        }
         match action {
             Action::Tick => {
                 self.on_tick()?;
@@ -2,6 +2,7 @@
 //
 // This software may be used and distributed according to the terms of the
 // GNU General Public License version 2.
+use crate::flight_recorder::StatCondition;
 use crate::APP;
 use crate::STATS_SOCKET_PATH;
 use crate::TRACE_FILE_PREFIX;
@@ -123,6 +124,84 @@ pub struct TraceArgs {
     /// Collect system statistics (CPU, memory, etc).
     #[clap(short = 's', long)]
     pub system_stats: bool,
+    /// Poll the running scheduler's stats and add every numeric field as a counter track.
+    #[clap(long)]
+    pub sched_stats: bool,
+    /// Interval for polling scheduler stats for --sched-stats in ms.
+    #[clap(long, default_value_t = 100, requires = "sched_stats")]
+    pub sched_stats_interval_ms: u64,
+    /// Stats unix socket path for --sched-stats and --trigger-stat.
+    #[clap(long, default_value = STATS_SOCKET_PATH)]
+    pub stats_socket_path: String,
+
+    /// Run as a flight recorder: keep the most recent events in memory and write a trace
+    /// whenever a trigger fires, instead of tracing for --trace-ms. SIGUSR1 always triggers.
+    #[clap(long, help_heading = "Flight Recorder")]
+    pub flight_recorder: bool,
+    /// Amount of history written before the trigger in ms.
+    #[clap(
+        long,
+        default_value_t = 10000,
+        requires = "flight_recorder",
+        help_heading = "Flight Recorder"
+    )]
+    pub window_ms: u64,
+    /// Time to keep recording after a trigger in ms.
+    #[clap(
+        long,
+        default_value_t = 1000,
+        requires = "flight_recorder",
+        help_heading = "Flight Recorder"
+    )]
+    pub post_trigger_ms: u64,
+    /// Number of traces to write before exiting, 0 to run until interrupted.
+    #[clap(
+        long,
+        default_value_t = 1,
+        requires = "flight_recorder",
+        help_heading = "Flight Recorder"
+    )]
+    pub max_dumps: u32,
+    /// Maximum number of events held in memory.
+    #[clap(
+        long,
+        default_value_t = 4_000_000,
+        requires = "flight_recorder",
+        help_heading = "Flight Recorder"
+    )]
+    pub max_buffered_events: usize,
+    /// Trigger when a task takes longer than this many us from waking to running.
+    #[clap(long, requires = "flight_recorder", help_heading = "Flight Recorder")]
+    pub trigger_wakeup_latency_us: Option<u64>,
+    /// Only apply the wakeup latency trigger to this pid or tgid.
+    #[clap(
+        long,
+        requires = "trigger_wakeup_latency_us",
+        help_heading = "Flight Recorder"
+    )]
+    pub trigger_pid: Option<u32>,
+    /// Only apply the wakeup latency trigger to tasks with this comm.
+    #[clap(
+        long,
+        requires = "trigger_wakeup_latency_us",
+        help_heading = "Flight Recorder"
+    )]
+    pub trigger_comm: Option<String>,
+    /// Trigger on a sched_hang event.
+    #[clap(long, requires = "flight_recorder", help_heading = "Flight Recorder")]
+    pub trigger_sched_hang: bool,
+    /// Trigger when a scheduler stats field crosses a value, e.g. "layers.batch.util>90".
+    /// Can be specified multiple times.
+    #[clap(long, requires = "flight_recorder", help_heading = "Flight Recorder")]
+    pub trigger_stat: Vec<StatCondition>,
+    /// Interval for polling scheduler stats for --trigger-stat in ms.
+    #[clap(
+        long,
+        default_value_t = 250,
+        requires = "trigger_stat",
+        help_heading = "Flight Recorder"
+    )]
+    pub trigger_stat_interval_ms: u64,
 }
 
 #[derive(Clone, Parser, Debug)]
@@ -154,6 +233,62 @@ pub struct McpArgs {
     pub layered: bool,
 }
 
+#[derive(Clone, Parser, Debug)]
+#[command(about = "Compares two perfetto traces")]
+pub struct DiffArgs {
+    /// Baseline (A) trace file.
+    pub baseline: PathBuf,
+
+    /// Candidate (B) trace file.
+    pub candidate: PathBuf,
+
+    /// False discovery rate for the statistical tests, applied to Benjamini-Hochberg adjusted
+    /// p-values.
+    #[arg(long, default_value_t = 0.05)]
+    pub alpha: f64,
+
+    /// Minimum number of samples on each side before a difference is tested.
+    #[arg(long, default_value_t = 20)]
+    pub min_samples: usize,
+
+    /// Maximum number of entries shown per section.
+    #[arg(short = 'n', long, default_value_t = 20)]
+    pub limit: usize,
+
+    /// Print the comparison as JSON.
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Clone, Parser, Debug)]
+#[command(about = "Records the data shown by the TUI without a terminal")]
+pub struct RecordArgs {
+    /// Recording output file.
+    #[arg(short = 'o', long)]
+    pub output: PathBuf,
+
+    /// Sampling interval in milliseconds.
+    #[arg(short = 'i', long, default_value_t = 1000, value_parser = clap::value_parser!(u64).range(10..))]
+    pub interval_ms: u64,
+
+    /// Stop recording after this many seconds, otherwise record until interrupted.
+    #[arg(long)]
+    pub duration_s: Option<u64>,
+
+    #[clap(flatten)]
+    pub tui: TuiArgs,
+}
+
+#[derive(Clone, Parser, Debug)]
+#[command(about = "Plays back a recording in the TUI")]
+pub struct ReplayArgs {
+    /// Recording to play back.
+    pub file: PathBuf,
+
+    #[clap(flatten)]
+    pub tui: TuiArgs,
+}
+
 #[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 pub enum Commands {
@@ -166,6 +301,15 @@ pub enum Commands {
     /// Runs the MCP (Model Context Protocol) server.
     Mcp(McpArgs),
 
+    /// Compares two perfetto traces (A/B).
+    Diff(DiffArgs),
+
+    /// Records the data shown by the TUI to a file.
+    Record(RecordArgs),
+
+    /// Plays back a recording in the TUI.
+    Replay(ReplayArgs),
+
     #[clap(hide = true)]
     GenerateCompletions {
         /// The shell type
@@ -0,0 +1,748 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+use crate::edm::ActionHandler;
+use crate::util::lookup_json_number;
+use crate::Action;
+use crate::PerfettoTraceManager;
+
+use anyhow::{anyhow, bail, Result};
+use log::{info, warn};
+use scx_stats::prelude::StatsClient;
+use serde_json::Value as JsonValue;
+use tokio::sync::mpsc::UnboundedSender;
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Comparison used by a scheduler stats trigger.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StatCmp {
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+impl StatCmp {
+    fn holds(&self, lhs: f64, rhs: f64) -> bool {
+        match self {
+            StatCmp::Gt => lhs > rhs,
+            StatCmp::Ge => lhs >= rhs,
+            StatCmp::Lt => lhs < rhs,
+            StatCmp::Le => lhs <= rhs,
+        }
+    }
+}
+
+impl fmt::Display for StatCmp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StatCmp::Gt => write!(f, ">"),
+            StatCmp::Ge => write!(f, ">="),
+            StatCmp::Lt => write!(f, "<"),
+            StatCmp::Le => write!(f, "<="),
+        }
+    }
+}
+
+/// A condition on a scheduler stats field, written as "<field><op><value>", e.g.
+/// "layers.batch.util>90". Nested fields and array elements are separated by dots.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatCondition {
+    pub field: String,
+    pub cmp: StatCmp,
+    pub value: f64,
+}
+
+impl FromStr for StatCondition {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let Some(pos) = s.find(['<', '>']) else {
+            bail!(
+                "stat condition {:?} has no comparison, expected e.g. \"util>90\"",
+                s
+            );
+        };
+        let (field, rest) = s.split_at(pos);
+        let (cmp, value) = if let Some(v) = rest.strip_prefix(">=") {
+            (StatCmp::Ge, v)
+        } else if let Some(v) = rest.strip_prefix("<=") {
+            (StatCmp::Le, v)
+        } else if let Some(v) = rest.strip_prefix('>') {
+            (StatCmp::Gt, v)
+        } else {
+            (StatCmp::Lt, &rest[1..])
+        };
+
+        let field = field.trim();
+        if field.is_empty() {
+            bail!("stat condition {:?} has no field", s);
+        }
+        let value = value
+            .trim()
+            .parse::<f64>()
+            .map_err(|e| anyhow!("stat condition {:?} has an invalid value: {}", s, e))?;
+
+        Ok(Self {
+            field: field.to_string(),
+            cmp,
+            value,
+        })
+    }
+}
+
+impl fmt::Display for StatCondition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}{}", self.field, self.cmp, self.value)
+    }
+}
+
+impl StatCondition {
+    /// Looks up the field in a stats response, returning None if it is missing or not a number.
+    pub fn lookup(&self, stats: &JsonValue) -> Option<f64> {
+        lookup_json_number(stats, &self.field)
+    }
+
+    /// Returns whether the condition holds for a stats response.
+    pub fn holds(&self, stats: &JsonValue) -> bool {
+        self.lookup(stats)
+            .is_some_and(|v| self.cmp.holds(v, self.value))
+    }
+}
+
+/// Wakeup latency trigger, optionally limited to a pid/tgid or comm.
+#[derive(Clone, Debug, Default)]
+pub struct WakeupLatencyTrigger {
+    pub threshold_ns: u64,
+    pub pid: Option<u32>,
+    pub comm: Option<String>,
+}
+
+impl WakeupLatencyTrigger {
+    fn matches(&self, pid: u32, tgid: u32, comm: &str) -> bool {
+        self.pid.is_none_or(|p| p == pid || p == tgid)
+            && self.comm.as_ref().is_none_or(|c| c == comm)
+    }
+}
+
+/// Triggers evaluated against the recorded events.
+#[derive(Clone, Debug, Default)]
+pub struct EventTriggers {
+    pub wakeup_latency: Option<WakeupLatencyTrigger>,
+    pub sched_hang: bool,
+}
+
+/// Why a flight recorder dump was taken.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TriggerReason {
+    WakeupLatency {
+        pid: u32,
+        comm: String,
+        latency_ns: u64,
+    },
+    SchedHang {
+        pid: u32,
+        comm: String,
+    },
+    Stat {
+        condition: String,
+        value: f64,
+    },
+    Signal,
+}
+
+impl fmt::Display for TriggerReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TriggerReason::WakeupLatency {
+                pid,
+                comm,
+                latency_ns,
+            } => write!(
+                f,
+                "wakeup latency of {}us for {}[{}]",
+                latency_ns / 1000,
+                comm,
+                pid
+            ),
+            TriggerReason::SchedHang { pid, comm } => write!(f, "sched_hang for {}[{}]", comm, pid),
+            TriggerReason::Stat { condition, value } => {
+                write!(f, "scheduler stat {} (value {})", condition, value)
+            }
+            TriggerReason::Signal => write!(f, "signal"),
+        }
+    }
+}
+
+/// A window of events to be written out as a perfetto trace.
+pub struct FlightDump {
+    pub reason: TriggerReason,
+    pub trigger_ts: u64,
+    pub events: Vec<Action>,
+}
+
+impl FlightDump {
+    /// Writes the dump to a perfetto trace file.
+    pub fn write(
+        &self,
+        trace_manager: &mut PerfettoTraceManager,
+        output_file: String,
+    ) -> Result<()> {
+        trace_manager.start()?;
+        for action in &self.events {
+            trace_manager.on_action(action)?;
+        }
+        trace_manager.stop(Some(output_file), None)
+    }
+}
+
+struct PendingDump {
+    reason: TriggerReason,
+    trigger_ts: u64,
+    stop_ts: u64,
+}
+
+/// Maximum number of wakeups waiting for their sched_switch. Wakeups are kept until matched
+/// regardless of the window, so long latencies still fire the trigger.
+const MAX_PENDING_WAKEUPS: usize = 1 << 16;
+
+/// Returns the timestamp of an action that is recorded into perfetto traces.
+fn recorded_ts(action: &Action) -> Option<u64> {
+    match action {
+        Action::SchedSwitch(a) => Some(a.ts),
+        Action::SchedWakeup(a) => Some(a.ts),
+        Action::SchedWaking(a) => Some(a.ts),
+        Action::SoftIRQ(a) => Some(a.entry_ts),
+        Action::IPI(a) => Some(a.ts),
+        Action::Exec(a) => Some(a.ts),
+        Action::Fork(a) => Some(a.ts),
+        Action::GpuMem(a) => Some(a.ts),
+        Action::Exit(a) => Some(a.ts),
+        Action::CpuhpEnter(a) => Some(a.ts),
+        Action::CpuhpExit(a) => Some(a.ts),
+        Action::Kprobe(a) => Some(a.ts),
+        Action::SystemStat(a) => Some(a.ts),
+        Action::SchedStatsSample(a) => Some(a.ts),
+        _ => None,
+    }
+}
+
+/// Continuously keeps the last window of trace events in memory and cuts a dump of them when a
+/// trigger fires. Events keep being recorded for the post-trigger window before the dump is
+/// handed out, so the trace shows what happened right after the trigger as well.
+///
+/// All timestamps, both of the recorded actions and passed in by the caller, must be
+/// CLOCK_MONOTONIC, which is what the BPF events are stamped with.
+pub struct FlightRecorder {
+    events: VecDeque<(u64, Action)>,
+    window_ns: u64,
+    post_trigger_ns: u64,
+    max_events: usize,
+    triggers: EventTriggers,
+    // Waking timestamps of tasks matched by the wakeup latency trigger.
+    wakeups: HashMap<u32, u64>,
+    max_wakeups: usize,
+    latest_ts: u64,
+    pending: Option<PendingDump>,
+    nr_dropped: u64,
+}
+
+impl FlightRecorder {
+    pub fn new(
+        window_ns: u64,
+        post_trigger_ns: u64,
+        max_events: usize,
+        triggers: EventTriggers,
+    ) -> Self {
+        Self {
+            events: VecDeque::new(),
+            window_ns,
+            post_trigger_ns,
+            max_events,
+            triggers,
+            wakeups: HashMap::new(),
+            max_wakeups: MAX_PENDING_WAKEUPS,
+            latest_ts: 0,
+            pending: None,
+            nr_dropped: 0,
+        }
+    }
+
+    /// Number of events evicted early because the buffer hit its event limit.
+    pub fn nr_dropped(&self) -> u64 {
+        self.nr_dropped
+    }
+
+    /// Number of buffered events.
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Returns whether a trigger fired and its dump is waiting for the post-trigger window.
+    pub fn is_triggered(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Records an action, returning the reason if it fired a trigger. Only actions that end up
+    /// in perfetto traces are buffered, the rest are just checked against the triggers.
+    pub fn record(&mut self, action: &Action) -> Option<TriggerReason> {
+        let fired = self
+            .check_triggers(action)
+            .filter(|(ts, reason)| self.trigger(*ts, reason.clone()));
+
+        if let Some(ts) = recorded_ts(action) {
+            self.latest_ts = self.latest_ts.max(ts);
+            if self.events.len() >= self.max_events {
+                self.events.pop_front();
+                self.nr_dropped += 1;
+            }
+            self.events.push_back((ts, action.clone()));
+            self.expire();
+        }
+
+        fired.map(|(_, reason)| reason)
+    }
+
+    /// Fires a trigger at @ts. Ignored while an earlier trigger is waiting for its post-trigger
+    /// window. Returns whether the trigger was taken.
+    pub fn trigger(&mut self, ts: u64, reason: TriggerReason) -> bool {
+        if self.pending.is_some() {
+            return false;
+        }
+        self.pending = Some(PendingDump {
+            reason,
+            trigger_ts: ts,
+            stop_ts: ts.saturating_add(self.post_trigger_ns),
+        });
+        true
+    }
+
+    /// Returns the dump once the post-trigger window has passed at @now_ns.
+    pub fn take_dump(&mut self, now_ns: u64) -> Option<FlightDump> {
+        let now_ns = now_ns.max(self.latest_ts);
+        if self.pending.as_ref()?.stop_ts > now_ns {
+            return None;
+        }
+        let pending = self.pending.take()?;
+        let start_ts = pending.trigger_ts.saturating_sub(self.window_ns);
+
+        let mut events: Vec<(u64, Action)> = self
+            .events
+            .iter()
+            .filter(|(ts, _)| *ts >= start_ts && *ts <= pending.stop_ts)
+            .cloned()
+            .collect();
+        // Events from different ring buffers arrive slightly out of order.
+        events.sort_by_key(|(ts, _)| *ts);
+
+        Some(FlightDump {
+            reason: pending.reason,
+            trigger_ts: pending.trigger_ts,
+            events: events.into_iter().map(|(_, a)| a).collect(),
+        })
+    }
+
+    fn check_triggers(&mut self, action: &Action) -> Option<(u64, TriggerReason)> {
+        match action {
+            Action::SchedWaking(a) | Action::SchedWakeup(a) => {
+                let trigger = self.triggers.wakeup_latency.as_ref()?;
+                if trigger.matches(a.pid, a.tgid, &a.comm) {
+                    if self.wakeups.len() >= self.max_wakeups && !self.wakeups.contains_key(&a.pid)
+                    {
+                        self.shrink_wakeups();
+                    }
+                    // Keep the earliest of waking and wakeup.
+                    self.wakeups.entry(a.pid).or_insert(a.ts);
+                }
+                None
+            }
+            Action::SchedSwitch(a) => {
+                let trigger = self.triggers.wakeup_latency.as_ref()?;
+                let waking_ts = self.wakeups.remove(&a.next_pid)?;
+                let latency_ns = a.ts.saturating_sub(waking_ts);
+                (latency_ns > trigger.threshold_ns).then(|| {
+                    let reason = TriggerReason::WakeupLatency {
+                        pid: a.next_pid,
+                        comm: a.next_comm.to_string(),
+                        latency_ns,
+                    };
+                    (a.ts, reason)
+                })
+            }
+            Action::SchedHang(a) if self.triggers.sched_hang => Some((
+                a.ts,
+                TriggerReason::SchedHang {
+                    pid: a.pid,
+                    comm: a.comm.to_string(),
+                },
+            )),
+            Action::Exit(a) => {
+                self.wakeups.remove(&a.pid);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Drops events that are older than the window, keeping those a pending dump still needs.
+    fn expire(&mut self) {
+        let mut horizon = self.latest_ts.saturating_sub(self.window_ns);
+        if let Some(pending) = &self.pending {
+            horizon = horizon.min(pending.trigger_ts.saturating_sub(self.window_ns));
+        }
+        while self.events.front().is_some_and(|(ts, _)| *ts < horizon) {
+            self.events.pop_front();
+        }
+    }
+
+    /// Drops the older half of the pending wakeups once they hit their limit.
+    fn shrink_wakeups(&mut self) {
+        let mut ts: Vec<u64> = self.wakeups.values().copied().collect();
+        let mid = ts.len() / 2;
+        let (_, &mut cutoff, _) = ts.select_nth_unstable(mid);
+        self.wakeups.retain(|_, ts| *ts > cutoff);
+    }
+}
+
+/// Polls the scheduler stats socket until @shutdown is set and sends a trigger whenever one of
+/// the conditions goes from not holding to holding. Reconnects if the scheduler restarts.
+pub fn poll_stat_triggers(
+    socket_path: String,
+    conditions: Vec<StatCondition>,
+    interval: Duration,
+    trigger_tx: UnboundedSender<TriggerReason>,
+    shutdown: Arc<AtomicBool>,
+) {
+    let mut client: Option<StatsClient> = None;
+    let mut held = vec![false; conditions.len()];
+    let mut warned = false;
+
+    while !shutdown.load(Ordering::Relaxed) {
+        if client.is_none() {
+            match StatsClient::new().set_path(&socket_path).connect(None) {
+                Ok(c) => {
+                    info!("flight recorder: connected to stats socket {}", socket_path);
+                    client = Some(c);
+                    warned = false;
+                }
+                Err(e) if !warned => {
+                    warn!(
+                        "flight recorder: failed to connect to {}: {}",
+                        socket_path, e
+                    );
+                    warned = true;
+                }
+                Err(_) => {}
+            }
+        }
+
+        if let Some(c) = client.as_mut() {
+            match c.request::<JsonValue>("stats", vec![]) {
+                Ok(stats) => {
+                    for (cond, held) in conditions.iter().zip(held.iter_mut()) {
+                        let value = cond.lookup(&stats);
+                        let holds = value.is_some_and(|v| cond.cmp.holds(v, cond.value));
+                        if holds && !*held {
+                            let reason = TriggerReason::Stat {
+                                condition: cond.to_string(),
+                                value: value.unwrap_or_default(),
+                            };
+                            if trigger_tx.send(reason).is_err() {
+                                return;
+                            }
+                        }
+                        *held = holds;
+                    }
+                }
+                Err(e) => {
+                    warn!("flight recorder: stats request failed: {}", e);
+                    client = None;
+                }
+            }
+        }
+
+        std::thread::sleep(interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ExitAction, SchedHangAction, SchedSwitchAction, SchedWakeActionCtx};
+
+    fn waking(ts: u64, pid: u32, comm: &str) -> Action {
+        Action::SchedWaking(SchedWakeActionCtx {
+            ts,
+            cpu: 0,
+            pid,
+            tgid: pid,
+            prio: 120,
+            comm: comm.into(),
+            waker_pid: 1,
+            waker_comm: "waker".into(),
+        })
+    }
+
+    fn switch(ts: u64, next_pid: u32, next_comm: &str) -> Action {
+        Action::SchedSwitch(SchedSwitchAction {
+            ts,
+            cpu: 0,
+            preempt: false,
+            next_dsq_id: 0,
+            next_dsq_lat_us: 0,
+            next_dsq_nr_queued: 0,
+            next_dsq_vtime: 0,
+            next_slice_ns: 0,
+            next_pid,
+            next_tgid: next_pid,
+            next_prio: 120,
+            next_layer_id: -1,
+            next_comm: next_comm.into(),
+            prev_dsq_id: 0,
+            prev_used_slice_ns: 0,
+            prev_slice_ns: 0,
+            prev_pid: 0,
+            prev_tgid: 0,
+            prev_prio: 120,
+            prev_comm: "swapper".into(),
+            prev_state: 0,
+            prev_layer_id: -1,
+        })
+    }
+
+    fn latency_recorder(threshold_ns: u64, comm: Option<&str>) -> FlightRecorder {
+        FlightRecorder::new(
+            1_000,
+            100,
+            1_000,
+            EventTriggers {
+                wakeup_latency: Some(WakeupLatencyTrigger {
+                    threshold_ns,
+                    pid: None,
+                    comm: comm.map(String::from),
+                }),
+                sched_hang: false,
+            },
+        )
+    }
+
+    #[test]
+    fn test_stat_condition_parse() {
+        let cond: StatCondition = "layers.batch.util>=90.5".parse().unwrap();
+        assert_eq!(cond.field, "layers.batch.util");
+        assert_eq!(cond.cmp, StatCmp::Ge);
+        assert_eq!(cond.value, 90.5);
+
+        let cond: StatCondition = "nr_stalls < 1".parse().unwrap();
+        assert_eq!(cond.field, "nr_stalls");
+        assert_eq!(cond.cmp, StatCmp::Lt);
+        assert_eq!(cond.value, 1.0);
+
+        assert!("util".parse::<StatCondition>().is_err());
+        assert!(">5".parse::<StatCondition>().is_err());
+        assert!("util>abc".parse::<StatCondition>().is_err());
+    }
+
+    #[test]
+    fn test_stat_condition_lookup() {
+        let stats = serde_json::json!({
+            "busy": 42.0,
+            "layers": {"batch": {"util": 95}},
+            "cpus": [{"load": 1.5}, {"load": 3}],
+        });
+        let cond: StatCondition = "layers.batch.util>90".parse().unwrap();
+        assert!(cond.holds(&stats));
+        let cond: StatCondition = "cpus.1.load<=2".parse().unwrap();
+        assert_eq!(cond.lookup(&stats), Some(3.0));
+        assert!(!cond.holds(&stats));
+        let cond: StatCondition = "layers.missing>0".parse().unwrap();
+        assert!(!cond.holds(&stats));
+    }
+
+    #[test]
+    fn test_wakeup_latency_trigger() {
+        let mut rec = latency_recorder(50, Some("victim"));
+
+        assert_eq!(rec.record(&waking(100, 10, "victim")), None);
+        assert_eq!(rec.record(&waking(100, 11, "other")), None);
+        // Under the threshold.
+        assert_eq!(rec.record(&switch(140, 10, "victim")), None);
+        // Over the threshold, but filtered out by comm.
+        assert_eq!(rec.record(&switch(300, 11, "other")), None);
+        assert!(!rec.is_triggered());
+
+        assert_eq!(rec.record(&waking(400, 10, "victim")), None);
+        let reason = rec.record(&switch(500, 10, "victim"));
+        assert_eq!(
+            reason,
+            Some(TriggerReason::WakeupLatency {
+                pid: 10,
+                comm: "victim".to_string(),
+                latency_ns: 100,
+            })
+        );
+        assert!(rec.is_triggered());
+    }
+
+    #[test]
+    fn test_wakeup_latency_longer_than_window() {
+        let mut rec = latency_recorder(50, None);
+
+        rec.record(&waking(0, 10, "victim"));
+        // Other events push the waking out of the 1000ns window.
+        for ts in [1_000, 2_000, 3_000] {
+            rec.record(&switch(ts, 1, "a"));
+        }
+        assert_eq!(
+            rec.record(&switch(3_500, 10, "victim")),
+            Some(TriggerReason::WakeupLatency {
+                pid: 10,
+                comm: "victim".to_string(),
+                latency_ns: 3_500,
+            })
+        );
+    }
+
+    #[test]
+    fn test_pending_wakeup_limit() {
+        let mut rec = latency_recorder(50, None);
+        rec.max_wakeups = 4;
+
+        for pid in 1..=4 {
+            rec.record(&waking(pid as u64 * 100, pid, "victim"));
+        }
+        assert_eq!(rec.wakeups.len(), 4);
+        // A repeated wakeup of a pending task doesn't count against the limit.
+        rec.record(&waking(1_000, 4, "victim"));
+        assert_eq!(rec.wakeups.len(), 4);
+
+        // A new one drops the older half.
+        rec.record(&waking(1_000, 5, "victim"));
+        let mut pids: Vec<u32> = rec.wakeups.keys().copied().collect();
+        pids.sort();
+        assert_eq!(pids, vec![4, 5]);
+        assert_eq!(rec.record(&switch(1_100, 1, "victim")), None);
+        assert!(rec.record(&switch(1_100, 4, "victim")).is_some());
+    }
+
+    #[test]
+    fn test_dump_waits_for_post_trigger_window() {
+        let mut rec = latency_recorder(50, None);
+
+        rec.record(&waking(0, 10, "victim"));
+        rec.record(&switch(2_000, 10, "victim"));
+        assert!(rec.is_triggered());
+        assert!(rec.take_dump(2_050).is_none());
+
+        rec.record(&switch(2_080, 1, "a"));
+        // Past the post-trigger window, this event is not part of the dump.
+        rec.record(&switch(2_200, 2, "b"));
+        let dump = rec.take_dump(2_200).unwrap();
+        assert_eq!(dump.trigger_ts, 2_000);
+        // The waking at ts 0 is outside the 1000ns window.
+        assert_eq!(dump.events.len(), 2);
+        assert!(!rec.is_triggered());
+        // Buffered events are kept for later dumps.
+        assert!(!rec.is_empty());
+    }
+
+    #[test]
+    fn test_window_expiry_and_event_limit() {
+        let mut rec = FlightRecorder::new(1_000, 0, 100, EventTriggers::default());
+        for ts in [0, 500, 1_000, 1_500, 2_000] {
+            rec.record(&switch(ts, 1, "a"));
+        }
+        // Only events within 1000ns of the latest one remain.
+        assert_eq!(rec.len(), 3);
+        assert_eq!(rec.nr_dropped(), 0);
+
+        let mut rec = FlightRecorder::new(1_000_000, 0, 3, EventTriggers::default());
+        for ts in [0, 100, 200, 300, 400] {
+            rec.record(&switch(ts, 1, "a"));
+        }
+        assert_eq!(rec.len(), 3);
+        assert_eq!(rec.nr_dropped(), 2);
+    }
+
+    #[test]
+    fn test_sched_hang_trigger() {
+        let hang = Action::SchedHang(SchedHangAction {
+            ts: 150,
+            cpu: 0,
+            comm: "stuck".into(),
+            pid: 7,
+        });
+
+        let mut rec = FlightRecorder::new(1_000, 0, 100, EventTriggers::default());
+        assert_eq!(rec.record(&hang), None);
+
+        let mut rec = FlightRecorder::new(
+            1_000,
+            0,
+            100,
+            EventTriggers {
+                wakeup_latency: None,
+                sched_hang: true,
+            },
+        );
+        rec.record(&switch(100, 7, "stuck"));
+        assert_eq!(
+            rec.record(&hang),
+            Some(TriggerReason::SchedHang {
+                pid: 7,
+                comm: "stuck".to_string(),
+            })
+        );
+        let dump = rec.take_dump(150).unwrap();
+        assert_eq!(dump.trigger_ts, 150);
+        assert_eq!(dump.events.len(), 1);
+    }
+
+    #[test]
+    fn test_external_triggers() {
+        let mut rec = FlightRecorder::new(1_000, 0, 100, EventTriggers::default());
+        rec.record(&switch(100, 1, "a"));
+        // Actions outside of perfetto traces are not recorded.
+        rec.record(&Action::SchedHang(SchedHangAction {
+            ts: 150,
+            cpu: 0,
+            comm: "a".into(),
+            pid: 1,
+        }));
+        assert_eq!(rec.len(), 1);
+
+        assert!(rec.trigger(200, TriggerReason::Signal));
+        assert!(!rec.trigger(
+            200,
+            TriggerReason::Stat {
+                condition: "util>90".to_string(),
+                value: 91.0,
+            }
+        ));
+        let dump = rec.take_dump(200).unwrap();
+        assert_eq!(dump.reason, TriggerReason::Signal);
+        assert_eq!(dump.events.len(), 1);
+
+        rec.record(&Action::Exit(ExitAction {
+            ts: 300,
+            cpu: 0,
+            pid: 1,
+            tgid: 1,
+            prio: 120,
+            comm: "a".into(),
+        }));
+        assert_eq!(rec.len(), 2);
+    }
+}
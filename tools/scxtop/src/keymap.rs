@@ -78,6 +78,10 @@ impl Default for KeyMap {
         bindings.insert(Key::Code(KeyCode::Enter), Action::Enter);
         bindings.insert(Key::Code(KeyCode::Esc), Action::Esc);
         bindings.insert(Key::Code(KeyCode::Backspace), Action::Backspace);
+        bindings.insert(Key::Code(KeyCode::Left), Action::ReplaySeekBackward);
+        bindings.insert(Key::Code(KeyCode::Right), Action::ReplaySeekForward);
+        bindings.insert(Key::Code(KeyCode::Home), Action::ReplaySeekStart);
+        bindings.insert(Key::Code(KeyCode::End), Action::ReplaySeekEnd);
 
         Self { bindings }
     }
@@ -406,6 +410,11 @@ pub fn parse_action(action_str: &str) -> Result<Action> {
         "Enter" => Ok(Action::Enter),
         "Esc" => Ok(Action::Esc),
         "Backspace" => Ok(Action::Backspace),
+        "ReplayTogglePlay" => Ok(Action::ReplayTogglePlay),
+        "ReplaySeekBackward" => Ok(Action::ReplaySeekBackward),
+        "ReplaySeekForward" => Ok(Action::ReplaySeekForward),
+        "ReplaySeekStart" => Ok(Action::ReplaySeekStart),
+        "ReplaySeekEnd" => Ok(Action::ReplaySeekEnd),
         _ => Err(anyhow!("Invalid action: {}", action_str)),
     }
 }
@@ -15,6 +15,7 @@ mod cpu_data;
 mod cpu_stats;
 pub mod edm;
 mod event_data;
+pub mod flight_recorder;
 mod keymap;
 pub mod layered_util;
 mod llc_data;
@@ -27,6 +28,7 @@ mod perfetto_trace;
 mod power_data;
 mod proc_data;
 pub mod profiling_events;
+pub mod recording;
 pub mod render;
 pub mod search;
 mod stats;
@@ -52,7 +54,7 @@ pub use llc_data::LlcData;
 pub use mem_stats::MemStatSnapshot;
 pub use network_stats::NetworkStatSnapshot;
 pub use node_data::NodeData;
-pub use perfetto_trace::PerfettoTraceManager;
+pub use perfetto_trace::{poll_sched_stats, PerfettoTraceManager};
 pub use power_data::{
     CStateInfo, CorePowerData, PowerDataCollector, PowerSnapshot, SystemPowerData,
 };
@@ -408,6 +410,13 @@ pub struct SystemStatAction {
     pub mem_info: MemStatSnapshot,
 }
 
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct SchedStatsSampleAction {
+    pub ts: u64,
+    /// Raw JSON response of the scheduler's stats socket.
+    pub stats: String,
+}
+
 #[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct UpdateColVisibilityAction {
     pub table: String,
@@ -482,12 +491,18 @@ pub enum Action {
     TraceStarted(TraceStartedAction),
     TraceStopped(TraceStoppedAction),
     ReloadStatsClient,
+    ReplaySeekBackward,
+    ReplaySeekEnd,
+    ReplaySeekForward,
+    ReplaySeekStart,
+    ReplayTogglePlay,
     SaveConfig,
     SchedCpuPerfSet(SchedCpuPerfSetAction),
     SchedHang(SchedHangAction),
     SchedMigrateTask(SchedMigrateTaskAction),
     SchedReg,
     SchedStats(String),
+    SchedStatsSample(SchedStatsSampleAction),
     SchedSwitch(SchedSwitchAction),
     SchedUnreg,
     SchedWakeupNew(SchedWakeupNewAction),
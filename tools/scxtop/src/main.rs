@@ -4,12 +4,22 @@
 // GNU General Public License version 2.
 
 use scx_utils::compat;
+use scx_utils::Topology;
+use scx_utils::NR_CPU_IDS;
 use scxtop::bpf_skel::types::bpf_event;
-use scxtop::cli::{generate_completions, Cli, Commands, TraceArgs, TuiArgs};
+use scxtop::cli::{
+    generate_completions, Cli, Commands, DiffArgs, RecordArgs, ReplayArgs, TraceArgs, TuiArgs,
+};
 use scxtop::config::Config;
 use scxtop::edm::{ActionHandler, BpfEventActionPublisher, BpfEventHandler, EventDispatchManager};
+use scxtop::flight_recorder::{
+    poll_stat_triggers, EventTriggers, FlightDump, FlightRecorder, TriggerReason,
+    WakeupLatencyTrigger,
+};
 use scxtop::layered_util;
 use scxtop::mangoapp::poll_mangoapp;
+use scxtop::poll_sched_stats;
+use scxtop::recording::{Recorder, Recording, RecordingHeader, RECORDING_VERSION};
 use scxtop::search;
 use scxtop::tracer::Tracer;
 use scxtop::util::{
@@ -58,7 +68,7 @@ use std::str::FromStr;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use sysinfo::System;
 use tokio::sync::mpsc;
 
@@ -105,6 +115,8 @@ fn handle_key_event(app: &App, keymap: &KeyMap, key: KeyEvent) -> Action {
                 match (app.state(), c) {
                     // In BPF program detail view, 'p' toggles perf sampling
                     (AppState::BpfProgramDetail, 'p') => Action::ToggleBpfPerfSampling,
+                    // When replaying, space plays and pauses the recording
+                    (_, ' ') if app.is_replay() => Action::ReplayTogglePlay,
                     // Fall back to global keymap for all other cases
                     _ => keymap.action(&Key::Char(c)),
                 }
@@ -247,6 +259,150 @@ fn attach_progs_selective(
     Ok((links, warnings))
 }
 
+/// Creates a PerfettoTraceManager with the topology embedded for cross-machine analysis.
+fn new_trace_manager(trace_file_prefix: String) -> PerfettoTraceManager {
+    let mut trace_manager = PerfettoTraceManager::new(trace_file_prefix, None);
+
+    if let Ok(topo) = Topology::new() {
+        let mut cpu_to_llc = std::collections::HashMap::new();
+        let mut cpu_to_numa = std::collections::HashMap::new();
+        let mut cpu_to_core = std::collections::HashMap::new();
+        for cpu in topo.all_cpus.values() {
+            cpu_to_llc.insert(cpu.id as u32, cpu.llc_id as u32);
+            cpu_to_numa.insert(cpu.id as u32, cpu.node_id as u32);
+            cpu_to_core.insert(cpu.id as u32, cpu.core_id as u32);
+        }
+        trace_manager.set_topology(cpu_to_llc, cpu_to_numa, cpu_to_core);
+    }
+
+    trace_manager
+}
+
+/// Runs the trace task of flight recorder mode. Events are buffered until a trigger fires and
+/// each dump is written as its own trace. Returns once shutdown is requested, after notifying
+/// @done when --max-dumps traces have been written.
+async fn run_flight_recorder(
+    trace_args: TraceArgs,
+    trace_file_prefix: String,
+    mut action_rx: mpsc::UnboundedReceiver<Action>,
+    mut trigger_rx: mpsc::UnboundedReceiver<TriggerReason>,
+    sigusr1: Arc<AtomicBool>,
+    shutdown: Arc<AtomicBool>,
+    done: Arc<tokio::sync::Notify>,
+) {
+    let mut recorder = FlightRecorder::new(
+        trace_args.window_ms * 1_000_000,
+        trace_args.post_trigger_ms * 1_000_000,
+        trace_args.max_buffered_events,
+        EventTriggers {
+            wakeup_latency: trace_args
+                .trigger_wakeup_latency_us
+                .map(|us| WakeupLatencyTrigger {
+                    threshold_ns: us * 1000,
+                    pid: trace_args.trigger_pid,
+                    comm: trace_args.trigger_comm.clone(),
+                }),
+            sched_hang: trace_args.trigger_sched_hang,
+        },
+    );
+    let base = trace_args
+        .output_file
+        .as_deref()
+        .map(|f| f.trim_end_matches(".proto").to_string())
+        .unwrap_or_else(|| trace_file_prefix.clone());
+    let mut nr_dumps = 0;
+    let mut dump_handles = Vec::new();
+
+    let mut write_dump = |dump: FlightDump, nr_dumps: &mut u32| {
+        let output_file = format!("{}_flight_{}.proto", base, nr_dumps);
+        info!(
+            "flight recorder: writing {} events to {} ({})",
+            dump.events.len(),
+            output_file,
+            dump.reason
+        );
+        *nr_dumps += 1;
+        let trace_file_prefix = trace_file_prefix.clone();
+        dump_handles.push(tokio::task::spawn_blocking(move || {
+            let mut trace_manager = new_trace_manager(trace_file_prefix);
+            if let Err(e) = dump.write(&mut trace_manager, output_file) {
+                eprintln!("Failed to write flight recorder trace: {e}");
+            }
+        }));
+    };
+
+    info!(
+        "flight recorder: keeping {}ms of events, send SIGUSR1 to trigger a trace",
+        trace_args.window_ms
+    );
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_millis(100)) => {
+                if shutdown.load(Ordering::Relaxed) {
+                    break;
+                }
+            }
+            reason = trigger_rx.recv() => {
+                if let Some(reason) = reason {
+                    let now = get_clock_value(libc::CLOCK_MONOTONIC);
+                    if recorder.trigger(now, reason.clone()) {
+                        info!("flight recorder: triggered by {}", reason);
+                    }
+                }
+            }
+            action = action_rx.recv() => {
+                match action {
+                    Some(a) => {
+                        if let Some(reason) = recorder.record(&a) {
+                            info!("flight recorder: triggered by {}", reason);
+                        }
+                    }
+                    None => break,
+                }
+            }
+        }
+
+        let max_reached = trace_args.max_dumps > 0 && nr_dumps >= trace_args.max_dumps;
+        if max_reached {
+            continue;
+        }
+
+        let now = get_clock_value(libc::CLOCK_MONOTONIC);
+        if sigusr1.swap(false, Ordering::Relaxed) && recorder.trigger(now, TriggerReason::Signal) {
+            info!("flight recorder: triggered by {}", TriggerReason::Signal);
+        }
+        if let Some(dump) = recorder.take_dump(now) {
+            write_dump(dump, &mut nr_dumps);
+            if trace_args.max_dumps > 0 && nr_dumps >= trace_args.max_dumps {
+                done.notify_one();
+            }
+        }
+    }
+
+    // Write out a trigger that fired right before shutdown with what was recorded so far.
+    while let Ok(a) = action_rx.try_recv() {
+        recorder.record(&a);
+    }
+    if trace_args.max_dumps == 0 || nr_dumps < trace_args.max_dumps {
+        if let Some(dump) = recorder.take_dump(u64::MAX) {
+            write_dump(dump, &mut nr_dumps);
+        }
+    }
+    if recorder.nr_dropped() > 0 {
+        info!(
+            "flight recorder: {} events dropped at the --max-buffered-events limit",
+            recorder.nr_dropped()
+        );
+    }
+
+    for result in join_all(dump_handles).await {
+        if let Err(e) = result {
+            eprintln!("Flight recorder dump task panicked: {e}");
+        }
+    }
+    info!("flight recorder: wrote {} traces", nr_dumps);
+}
+
 fn run_trace(trace_args: &TraceArgs) -> Result<()> {
     // Trace function always requires root privileges
     if !is_root() {
@@ -603,7 +759,7 @@ fn run_trace(trace_args: &TraceArgs) -> Result<()> {
                             info!("stats task received shutdown after {} samples", stats_count);
                             break;
                         }
-                        let ts = get_clock_value(libc::CLOCK_BOOTTIME);
+                        let ts = get_clock_value(libc::CLOCK_MONOTONIC);
 
                         cpu_stat_tracker
                             .update(&mut system)
@@ -628,26 +784,88 @@ fn run_trace(trace_args: &TraceArgs) -> Result<()> {
                 }));
             }
 
+            if trace_args.sched_stats {
+                let socket_path = trace_args.stats_socket_path.clone();
+                let interval = Duration::from_millis(trace_args.sched_stats_interval_ms);
+                let action_tx_clone = action_tx.clone();
+                let stop_sched_stats = shutdown.clone();
+                producer_handles.push(tokio::task::spawn_blocking(move || {
+                    poll_sched_stats(socket_path, interval, action_tx_clone, stop_sched_stats)
+                }));
+            }
+
             let trace_file_prefix = config.trace_file_prefix().to_string();
             let trace_file = trace_args.output_file.clone();
-            let mut trace_manager = PerfettoTraceManager::new(trace_file_prefix, None);
-
-            // Embed topology metadata in traces for cross-machine analysis
-            {
-                use scx_utils::Topology;
-                if let Ok(topo) = Topology::new() {
-                    let mut cpu_to_llc = std::collections::HashMap::new();
-                    let mut cpu_to_numa = std::collections::HashMap::new();
-                    let mut cpu_to_core = std::collections::HashMap::new();
-                    for cpu in topo.all_cpus.values() {
-                        cpu_to_llc.insert(cpu.id as u32, cpu.llc_id as u32);
-                        cpu_to_numa.insert(cpu.id as u32, cpu.node_id as u32);
-                        cpu_to_core.insert(cpu.id as u32, cpu.core_id as u32);
+
+            if trace_args.flight_recorder {
+                let (trigger_tx, trigger_rx) = mpsc::unbounded_channel();
+                let sigusr1 = Arc::new(AtomicBool::new(false));
+                signal_hook::flag::register(signal_hook::consts::SIGUSR1, sigusr1.clone())?;
+
+                if !trace_args.trigger_stat.is_empty() {
+                    let socket_path = trace_args.stats_socket_path.clone();
+                    let conditions = trace_args.trigger_stat.clone();
+                    let interval = Duration::from_millis(trace_args.trigger_stat_interval_ms);
+                    let stop_stat_triggers = shutdown.clone();
+                    producer_handles.push(tokio::task::spawn_blocking(move || {
+                        poll_stat_triggers(
+                            socket_path,
+                            conditions,
+                            interval,
+                            trigger_tx,
+                            stop_stat_triggers,
+                        )
+                    }));
+                }
+
+                let mut tracer = Tracer::new(skel);
+                tracer.trace(&trace_args.kprobes)?;
+
+                let done = Arc::new(tokio::sync::Notify::new());
+                let trace_handle = tokio::spawn(run_flight_recorder(
+                    trace_args.clone(),
+                    trace_file_prefix,
+                    action_rx,
+                    trigger_rx,
+                    sigusr1,
+                    shutdown.clone(),
+                    done.clone(),
+                ));
+
+                tokio::select! {
+                    _ = done.notified() => {
+                        info!("flight recorder: --max-dumps reached, beginning shutdown");
+                    }
+                    _ = tokio::signal::ctrl_c() => {
+                        info!("flight recorder: interrupted, beginning shutdown");
+                    }
+                }
+
+                tracer.clear_links()?;
+                drop(links);
+                shutdown.store(true, Ordering::Relaxed);
+                for result in join_all(ringbuffer_handles).await {
+                    if let Err(e) = result {
+                        eprintln!("Ringbuffer task panicked: {e}");
                     }
-                    trace_manager.set_topology(cpu_to_llc, cpu_to_numa, cpu_to_core);
                 }
+                for result in join_all(producer_handles).await {
+                    if let Err(e) = result {
+                        eprintln!("Producer task panicked: {e}");
+                    }
+                }
+                drop(action_tx);
+                if let Err(e) = trace_handle.await {
+                    eprintln!("Flight recorder task panicked: {e}");
+                }
+
+                let stats = tracer.stats()?;
+                info!("shutdown: {stats:?}");
+                return Ok(());
             }
 
+            let mut trace_manager = new_trace_manager(trace_file_prefix);
+
             info!("starting trace for {}ms", trace_args.trace_ms);
             trace_manager.start()?;
             let mut tracer = Tracer::new(skel);
@@ -774,7 +992,64 @@ fn run_trace(trace_args: &TraceArgs) -> Result<()> {
         })
 }
 
-fn run_tui(tui_args: &TuiArgs) -> Result<()> {
+/// Samples the app data into a recording until interrupted or the duration
+/// has passed.
+async fn run_recorder(
+    app: &mut App<'_>,
+    record_args: &RecordArgs,
+    action_rx: &mut mpsc::UnboundedReceiver<Action>,
+) -> Result<()> {
+    let header = RecordingHeader {
+        version: RECORDING_VERSION,
+        hostname: System::host_name().unwrap_or_default(),
+        scheduler: read_file_string(SCHED_NAME_PATH).unwrap_or_default(),
+        start_unix_ms: SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64,
+        interval_ms: record_args.interval_ms,
+        nr_cpu_ids: *NR_CPU_IDS,
+    };
+    let mut recorder = Recorder::create(&record_args.output, &header, app.topology())?;
+    info!("recording to {}", record_args.output.display());
+
+    let start = Instant::now();
+    let deadline = record_args
+        .duration_s
+        .map(|secs| start + Duration::from_secs(secs));
+    let mut interval = tokio::time::interval(Duration::from_millis(record_args.interval_ms));
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    let mut frames = 0;
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                // Collect everything the default view shows, then sample it
+                app.handle_action(&Action::Tick)?;
+                let frame = app.record_frame(start.elapsed().as_millis() as u64)?;
+                recorder.write_frame(frame)?;
+                // Keep an interrupted recording usable
+                recorder.flush()?;
+                frames += 1;
+                if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                    break;
+                }
+            }
+            _ = &mut ctrl_c => break,
+            ac = action_rx.recv() => {
+                let ac = ac.ok_or(anyhow!("actions channel closed"))?;
+                app.handle_action(&ac)?;
+            }
+        }
+    }
+    recorder.flush()?;
+    app.should_quit.store(true, Ordering::Relaxed);
+    info!(
+        "recorded {} frames to {}",
+        frames,
+        record_args.output.display()
+    );
+    Ok(())
+}
+
+fn run_tui(tui_args: &TuiArgs, record: Option<&RecordArgs>) -> Result<()> {
     if let Ok(log_path) = std::env::var("RUST_LOG_PATH") {
         let log_level = match std::env::var("RUST_LOG") {
             Ok(v) => LevelFilter::from_str(&v)?,
@@ -790,6 +1065,13 @@ fn run_tui(tui_args: &TuiArgs) -> Result<()> {
         log_panics::Config::new()
             .backtrace_mode(log_panics::BacktraceMode::Resolved)
             .install_panic_hook();
+    } else if record.is_some() {
+        TermLogger::init(
+            LevelFilter::Info,
+            SimplelogConfig::default(),
+            TerminalMode::Mixed,
+            ColorChoice::Auto,
+        )?;
     };
 
     let config = Config::merge([
@@ -1056,7 +1338,8 @@ fn run_tui(tui_args: &TuiArgs) -> Result<()> {
                 }
             }
 
-            let mut tui = Tui::new(keymap.clone(), config.tick_rate_ms(), config.frame_rate_ms())?;
+            let tick_rate_ms = config.tick_rate_ms();
+            let frame_rate_ms = config.frame_rate_ms();
             let scheduler = read_file_string(SCHED_NAME_PATH).unwrap_or("".to_string());
 
             // Create app with or without BPF skeleton
@@ -1087,8 +1370,6 @@ fn run_tui(tui_args: &TuiArgs) -> Result<()> {
                 app.set_capability_warnings(capability_warnings);
             }
 
-            tui.enter()?;
-
             // Start BPF event polling only if we have ringbuffer data
             let shutdown = app.should_quit.clone();
             let mut ringbuffer_handles = Vec::new();
@@ -1196,38 +1477,45 @@ fn run_tui(tui_args: &TuiArgs) -> Result<()> {
                 });
             }
 
-            loop {
-                tokio::select! {
-                    ev = tui.next() => {
-                        let ev = ev?;
-                        match ev {
-                            Event::Quit => { action_tx.send(Action::Quit)?; },
-                            Event::Tick => action_tx.send(Action::Tick)?,
-                            Event::TickRateChange(tick_rate_ms) => action_tx.send(
-                                Action::TickRateChange(std::time::Duration::from_millis(tick_rate_ms)),
-                            )?,
-                            Event::Render => {
-                                if app.should_quit.load(Ordering::Relaxed) {
-                                    break;
+            if let Some(record_args) = record {
+                run_recorder(&mut app, record_args, &mut action_rx).await?;
+            } else {
+                let mut tui = Tui::new(keymap.clone(), tick_rate_ms, frame_rate_ms)?;
+                tui.enter()?;
+
+                loop {
+                    tokio::select! {
+                        ev = tui.next() => {
+                            let ev = ev?;
+                            match ev {
+                                Event::Quit => { action_tx.send(Action::Quit)?; },
+                                Event::Tick => action_tx.send(Action::Tick)?,
+                                Event::TickRateChange(tick_rate_ms) => action_tx.send(
+                                    Action::TickRateChange(std::time::Duration::from_millis(tick_rate_ms)),
+                                )?,
+                                Event::Render => {
+                                    if app.should_quit.load(Ordering::Relaxed) {
+                                        break;
+                                    }
+                                    if app.state() != AppState::Pause {
+                                        tui.draw(|f| app.render(f).expect("Failed to render application"))?;
+                                    }
                                 }
-                                if app.state() != AppState::Pause {
-                                    tui.draw(|f| app.render(f).expect("Failed to render application"))?;
+                                Event::Key(_) => {
+                                    let action = get_action(&app, &keymap, ev);
+                                    action_tx.send(action)?;
                                 }
-                            }
-                            Event::Key(_) => {
-                                let action = get_action(&app, &keymap, ev);
-                                action_tx.send(action)?;
-                            }
-                            _ => {}
-                    }}
+                                _ => {}
+                        }}
 
-                    ac = action_rx.recv() => {
-                        let ac = ac.ok_or(anyhow!("actions channel closed"))?;
-                        app.handle_action(&ac)?;
+                        ac = action_rx.recv() => {
+                            let ac = ac.ok_or(anyhow!("actions channel closed"))?;
+                            app.handle_action(&ac)?;
+                        }
                     }
                 }
+                tui.exit()?;
             }
-            tui.exit()?;
 
             // Wait for all ringbuffer tasks to finish consuming remaining events
             log::debug!("waiting for {} ringbuffer tasks to complete", ringbuffer_handles.len());
@@ -1896,12 +2184,85 @@ fn run_mcp(mcp_args: &scxtop::cli::McpArgs) -> Result<()> {
     }
 }
 
+fn run_diff(diff_args: &DiffArgs) -> Result<()> {
+    use scxtop::mcp::{AnalyzerRegistry, PerfettoTrace, TraceComparator};
+
+    let baseline = Arc::new(PerfettoTrace::from_file(&diff_args.baseline)?);
+    let candidate = Arc::new(PerfettoTrace::from_file(&diff_args.candidate)?);
+
+    let registry = AnalyzerRegistry::with_builtins();
+    let mut comparison = TraceComparator::new(diff_args.alpha, diff_args.min_samples)
+        .compare(baseline, candidate, &registry);
+    comparison.truncate(diff_args.limit);
+
+    if diff_args.json {
+        println!("{}", serde_json::to_string_pretty(&comparison)?);
+    } else {
+        print!("{}", comparison.format_text());
+    }
+    Ok(())
+}
+
+fn run_replay(replay_args: &ReplayArgs) -> Result<()> {
+    let recording = Recording::load(&replay_args.file)?;
+    let config = Config::merge([
+        Config::from(replay_args.tui.clone()),
+        Config::load_or_default().expect("Failed to load config or load default config"),
+    ]);
+    let keymap = config.active_keymap.clone();
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            let (action_tx, mut action_rx) = mpsc::unbounded_channel();
+            let mut tui = Tui::new(keymap.clone(), config.tick_rate_ms(), config.frame_rate_ms())?;
+            let mut app = App::new_replay(config, recording, 100, action_tx.clone())?;
+            tui.enter()?;
+
+            loop {
+                tokio::select! {
+                    ev = tui.next() => {
+                        let ev = ev?;
+                        match ev {
+                            Event::Quit => action_tx.send(Action::Quit)?,
+                            Event::Tick => action_tx.send(Action::Tick)?,
+                            Event::TickRateChange(tick_rate_ms) => action_tx.send(
+                                Action::TickRateChange(std::time::Duration::from_millis(tick_rate_ms)),
+                            )?,
+                            Event::Render => {
+                                if app.should_quit.load(Ordering::Relaxed) {
+                                    break;
+                                }
+                                tui.draw(|f| app.render(f).expect("Failed to render application"))?;
+                            }
+                            Event::Key(_) => {
+                                let action = get_action(&app, &keymap, ev);
+                                action_tx.send(action)?;
+                            }
+                            _ => {}
+                        }
+                    }
+
+                    ac = action_rx.recv() => {
+                        let ac = ac.ok_or(anyhow!("actions channel closed"))?;
+                        app.handle_action(&ac)?;
+                    }
+                }
+            }
+            tui.exit()?;
+
+            Ok(())
+        })
+}
+
 fn main() -> Result<()> {
     let args = Cli::parse();
 
     match &args.command.unwrap_or(Commands::Tui(args.tui)) {
         Commands::Tui(tui_args) => {
-            run_tui(tui_args)?;
+            run_tui(tui_args, None)?;
         }
         Commands::Trace(trace_args) => {
             run_trace(trace_args)?;
@@ -1909,6 +2270,15 @@ fn main() -> Result<()> {
         Commands::Mcp(mcp_args) => {
             run_mcp(mcp_args)?;
         }
+        Commands::Diff(diff_args) => {
+            run_diff(diff_args)?;
+        }
+        Commands::Record(record_args) => {
+            run_tui(&record_args.tui, Some(record_args))?;
+        }
+        Commands::Replay(replay_args) => {
+            run_replay(replay_args)?;
+        }
         Commands::GenerateCompletions { shell, output } => {
             generate_completions(Cli::command(), *shell, output.clone())
                 .unwrap_or_else(|_| panic!("Failed to generate completions for {shell}"));
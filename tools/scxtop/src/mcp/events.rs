@@ -244,6 +244,12 @@ pub fn action_to_mcp_event(action: &Action) -> Option<Value> {
             "data": s,
         })),
 
+        Action::SchedStatsSample(a) => Some(json!({
+            "type": "sched_stats_sample",
+            "ts": a.ts,
+            "data": a.stats,
+        })),
+
         // Ignore internal actions that aren't actual events
         Action::Tick
         | Action::Quit
@@ -272,6 +278,11 @@ pub fn action_to_mcp_event(action: &Action) -> Option<Value> {
         | Action::PrevEvent
         | Action::RequestTrace
         | Action::ReloadStatsClient
+        | Action::ReplaySeekBackward
+        | Action::ReplaySeekEnd
+        | Action::ReplaySeekForward
+        | Action::ReplaySeekStart
+        | Action::ReplayTogglePlay
         | Action::SaveConfig
         | Action::SchedReg
         | Action::SchedUnreg
@@ -27,6 +27,7 @@ pub mod perfetto_outlier_analyzer;
 pub mod perfetto_parser;
 pub mod perfetto_parser_enhanced;
 pub mod perfetto_query;
+pub mod perfetto_trace_diff;
 pub mod perfetto_track_event_types;
 mod prompts;
 mod protocol;
@@ -114,6 +115,9 @@ pub use perfetto_parser_enhanced::{
 pub use perfetto_query::{
     Aggregator, FieldFilter, FilterOperator, FilterValue, QueryBuilder, QueryEvent, QueryResult,
 };
+pub use perfetto_trace_diff::{
+    MetricDelta, RateComparison, SampleComparison, TraceComparator, TraceComparison,
+};
 pub use perfetto_track_event_types::{
     get_annotation_int, get_annotation_string, get_annotation_uint, Annotation, AnnotationValue,
     InterruptEventType, InterruptTrackEvent, OncpuSliceEvent, ParsedTrackEvent,
@@ -0,0 +1,787 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+//! A/B comparison of two perfetto traces
+//!
+//! Compares a baseline trace against a candidate trace, typically the same
+//! workload run under two schedulers or two scheduler configurations.
+//! Processes are matched by comm rather than pid so that separate runs line
+//! up. Latency distributions are compared with a Mann-Whitney U test and
+//! event rates with a Poisson rate test. As every process and DSQ is tested
+//! separately, the p-values are adjusted with the Benjamini-Hochberg
+//! procedure before being compared against alpha. The numeric outputs of
+//! every registered analyzer are also diffed.
+
+use super::perfetto_analyzer_registry::AnalyzerRegistry;
+use super::perfetto_analyzers::CorrelationAnalyzer;
+use super::perfetto_parser::{Percentiles, PerfettoTrace};
+use crate::util::flatten_json_numbers;
+use perfetto_protos::ftrace_event::ftrace_event;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::Arc;
+
+/// Key used for the distribution and rate covering the whole trace.
+pub const ALL_KEY: &str = "<all>";
+
+/// Comparison of one latency distribution between the two traces
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SampleComparison {
+    pub key: String,
+    pub baseline: Percentiles,
+    pub candidate: Percentiles,
+    pub p50_delta_pct: f64,
+    pub p99_delta_pct: f64,
+    /// Two-sided Mann-Whitney U p-value, None if either side has too few samples
+    pub p_value: Option<f64>,
+    /// Benjamini-Hochberg adjusted p-value
+    pub adjusted_p_value: Option<f64>,
+    pub significant: bool,
+}
+
+/// Comparison of one event rate between the two traces
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateComparison {
+    pub key: String,
+    pub baseline_count: u64,
+    pub candidate_count: u64,
+    pub baseline_per_sec: f64,
+    pub candidate_per_sec: f64,
+    pub delta_pct: f64,
+    /// Two-sided Poisson rate test p-value, None if there are too few events
+    pub p_value: Option<f64>,
+    /// Benjamini-Hochberg adjusted p-value
+    pub adjusted_p_value: Option<f64>,
+    pub significant: bool,
+}
+
+/// Change in a numeric field reported by a registered analyzer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricDelta {
+    pub analyzer: String,
+    pub metric: String,
+    pub baseline: f64,
+    pub candidate: f64,
+    pub delta: f64,
+    pub delta_pct: f64,
+}
+
+/// Result of comparing two traces
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraceComparison {
+    pub baseline_duration_ns: u64,
+    pub candidate_duration_ns: u64,
+    pub alpha: f64,
+    /// Number of tests the p-values were adjusted for
+    pub nr_tests: usize,
+    /// Wakeup latency per comm, in nanoseconds
+    pub wakeup_latency: Vec<SampleComparison>,
+    /// Time spent queued per DSQ, in nanoseconds
+    pub dsq_latency: Vec<SampleComparison>,
+    /// Migrations per second per comm
+    pub migration_rates: Vec<RateComparison>,
+    pub analyzer_deltas: Vec<MetricDelta>,
+}
+
+/// Compares two traces
+pub struct TraceComparator {
+    /// False discovery rate the adjusted p-values are compared against
+    pub alpha: f64,
+    /// Minimum number of samples on each side, or events in total, before testing
+    pub min_samples: usize,
+}
+
+impl Default for TraceComparator {
+    fn default() -> Self {
+        Self {
+            alpha: 0.05,
+            min_samples: 20,
+        }
+    }
+}
+
+impl TraceComparator {
+    pub fn new(alpha: f64, min_samples: usize) -> Self {
+        Self { alpha, min_samples }
+    }
+
+    /// Compare the candidate trace against the baseline trace
+    pub fn compare(
+        &self,
+        baseline: Arc<PerfettoTrace>,
+        candidate: Arc<PerfettoTrace>,
+        registry: &AnalyzerRegistry,
+    ) -> TraceComparison {
+        let base_wakeups = wakeup_latencies_by_comm(&baseline);
+        let cand_wakeups = wakeup_latencies_by_comm(&candidate);
+        let base_dsqs = dsq_latencies(&baseline);
+        let cand_dsqs = dsq_latencies(&candidate);
+        let base_migrations = migrations_by_comm(&baseline);
+        let cand_migrations = migrations_by_comm(&candidate);
+
+        let baseline_duration_ns = duration_ns(&baseline);
+        let candidate_duration_ns = duration_ns(&candidate);
+
+        let mut wakeup_latency = self.compare_samples(&base_wakeups, &cand_wakeups);
+        let mut dsq_latency = self.compare_samples(&base_dsqs, &cand_dsqs);
+        let mut migration_rates = self.compare_rates(
+            &base_migrations,
+            baseline_duration_ns,
+            &cand_migrations,
+            candidate_duration_ns,
+        );
+        let nr_tests = adjust_p_values(
+            self.alpha,
+            &mut wakeup_latency,
+            &mut dsq_latency,
+            &mut migration_rates,
+        );
+
+        wakeup_latency.sort_by(|a, b| {
+            b.significant
+                .cmp(&a.significant)
+                .then(b.p99_delta_pct.abs().total_cmp(&a.p99_delta_pct.abs()))
+        });
+        dsq_latency.sort_by(|a, b| {
+            b.significant
+                .cmp(&a.significant)
+                .then(b.p99_delta_pct.abs().total_cmp(&a.p99_delta_pct.abs()))
+        });
+        migration_rates.sort_by(|a, b| {
+            b.significant
+                .cmp(&a.significant)
+                .then(b.delta_pct.abs().total_cmp(&a.delta_pct.abs()))
+        });
+
+        let analyzer_deltas = analyzer_deltas(
+            &analyzer_metrics(registry, baseline),
+            &analyzer_metrics(registry, candidate),
+        );
+
+        TraceComparison {
+            baseline_duration_ns,
+            candidate_duration_ns,
+            alpha: self.alpha,
+            nr_tests,
+            wakeup_latency,
+            dsq_latency,
+            migration_rates,
+            analyzer_deltas,
+        }
+    }
+
+    fn compare_samples(
+        &self,
+        baseline: &HashMap<String, Vec<u64>>,
+        candidate: &HashMap<String, Vec<u64>>,
+    ) -> Vec<SampleComparison> {
+        baseline
+            .iter()
+            .filter_map(|(key, base)| {
+                let cand = candidate.get(key)?;
+                let base_pct = PerfettoTrace::calculate_percentiles(base);
+                let cand_pct = PerfettoTrace::calculate_percentiles(cand);
+                let p_value = if base.len() >= self.min_samples && cand.len() >= self.min_samples {
+                    Some(mann_whitney_u(base, cand))
+                } else {
+                    None
+                };
+
+                Some(SampleComparison {
+                    key: key.clone(),
+                    p50_delta_pct: delta_pct(base_pct.median as f64, cand_pct.median as f64),
+                    p99_delta_pct: delta_pct(base_pct.p99 as f64, cand_pct.p99 as f64),
+                    baseline: base_pct,
+                    candidate: cand_pct,
+                    p_value,
+                    adjusted_p_value: None,
+                    significant: false,
+                })
+            })
+            .collect()
+    }
+
+    fn compare_rates(
+        &self,
+        baseline: &HashMap<String, u64>,
+        baseline_ns: u64,
+        candidate: &HashMap<String, u64>,
+        candidate_ns: u64,
+    ) -> Vec<RateComparison> {
+        if baseline_ns == 0 || candidate_ns == 0 {
+            return Vec::new();
+        }
+        let base_secs = baseline_ns as f64 / 1e9;
+        let cand_secs = candidate_ns as f64 / 1e9;
+
+        let mut keys: Vec<&String> = baseline.keys().chain(candidate.keys()).collect();
+        keys.sort();
+        keys.dedup();
+
+        keys.into_iter()
+            .map(|key| {
+                let base = baseline.get(key).copied().unwrap_or(0);
+                let cand = candidate.get(key).copied().unwrap_or(0);
+                let baseline_per_sec = base as f64 / base_secs;
+                let candidate_per_sec = cand as f64 / cand_secs;
+                let p_value = if (base + cand) as usize >= self.min_samples {
+                    Some(poisson_rate_test(base, base_secs, cand, cand_secs))
+                } else {
+                    None
+                };
+
+                RateComparison {
+                    key: key.clone(),
+                    baseline_count: base,
+                    candidate_count: cand,
+                    baseline_per_sec,
+                    candidate_per_sec,
+                    delta_pct: delta_pct(baseline_per_sec, candidate_per_sec),
+                    p_value,
+                    adjusted_p_value: None,
+                    significant: false,
+                }
+            })
+            .collect()
+    }
+}
+
+impl TraceComparison {
+    /// Keep at most `limit` entries in each per-key list
+    pub fn truncate(&mut self, limit: usize) {
+        self.wakeup_latency.truncate(limit);
+        self.dsq_latency.truncate(limit);
+        self.migration_rates.truncate(limit);
+        self.analyzer_deltas.truncate(limit);
+    }
+
+    /// Human readable report
+    pub fn format_text(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "Trace comparison (baseline {:.2}s, candidate {:.2}s, alpha {})",
+            self.baseline_duration_ns as f64 / 1e9,
+            self.candidate_duration_ns as f64 / 1e9,
+            self.alpha
+        );
+
+        for (title, samples) in [
+            ("Wakeup latency (us)", &self.wakeup_latency),
+            ("DSQ latency (us)", &self.dsq_latency),
+        ] {
+            let _ = writeln!(out, "\n{title}:");
+            if samples.is_empty() {
+                let _ = writeln!(out, "  no data in both traces");
+                continue;
+            }
+            let _ = writeln!(
+                out,
+                "  {:<24} {:>8} {:>8} {:>10} {:>10} {:>8} {:>10} {:>10} {:>8} {:>10}",
+                "key",
+                "n(a)",
+                "n(b)",
+                "p50(a)",
+                "p50(b)",
+                "p50 %",
+                "p99(a)",
+                "p99(b)",
+                "p99 %",
+                "adj p"
+            );
+            for s in samples {
+                let _ = writeln!(
+                    out,
+                    "  {:<24} {:>8} {:>8} {:>10.1} {:>10.1} {:>+8.1} {:>10.1} {:>10.1} {:>+8.1} {:>10}{}",
+                    truncate_key(&s.key, 24),
+                    s.baseline.count,
+                    s.candidate.count,
+                    s.baseline.median as f64 / 1000.0,
+                    s.candidate.median as f64 / 1000.0,
+                    s.p50_delta_pct,
+                    s.baseline.p99 as f64 / 1000.0,
+                    s.candidate.p99 as f64 / 1000.0,
+                    s.p99_delta_pct,
+                    format_p_value(s.adjusted_p_value),
+                    if s.significant { " *" } else { "" }
+                );
+            }
+        }
+
+        let _ = writeln!(out, "\nMigrations (per second):");
+        if self.migration_rates.is_empty() {
+            let _ = writeln!(out, "  no data in both traces");
+        } else {
+            let _ = writeln!(
+                out,
+                "  {:<24} {:>8} {:>8} {:>10} {:>10} {:>8} {:>10}",
+                "key", "n(a)", "n(b)", "rate(a)", "rate(b)", "delta %", "adj p"
+            );
+            for r in &self.migration_rates {
+                let _ = writeln!(
+                    out,
+                    "  {:<24} {:>8} {:>8} {:>10.2} {:>10.2} {:>+8.1} {:>10}{}",
+                    truncate_key(&r.key, 24),
+                    r.baseline_count,
+                    r.candidate_count,
+                    r.baseline_per_sec,
+                    r.candidate_per_sec,
+                    r.delta_pct,
+                    format_p_value(r.adjusted_p_value),
+                    if r.significant { " *" } else { "" }
+                );
+            }
+        }
+
+        let _ = writeln!(out, "\nAnalyzer metrics:");
+        if self.analyzer_deltas.is_empty() {
+            let _ = writeln!(out, "  no changed metrics");
+        } else {
+            for d in &self.analyzer_deltas {
+                let _ = writeln!(
+                    out,
+                    "  {}.{}: {} -> {} ({:+.1}%)",
+                    d.analyzer, d.metric, d.baseline, d.candidate, d.delta_pct
+                );
+            }
+        }
+
+        let _ = writeln!(
+            out,
+            "\n* significant at alpha {} after Benjamini-Hochberg adjustment over {} tests",
+            self.alpha, self.nr_tests
+        );
+        out
+    }
+}
+
+/// Sets the Benjamini-Hochberg adjusted p-values of all tested comparisons and marks those
+/// below @alpha as significant. Returns the number of tests.
+fn adjust_p_values(
+    alpha: f64,
+    wakeup_latency: &mut [SampleComparison],
+    dsq_latency: &mut [SampleComparison],
+    migration_rates: &mut [RateComparison],
+) -> usize {
+    let mut tests: Vec<(f64, &mut Option<f64>, &mut bool)> = wakeup_latency
+        .iter_mut()
+        .chain(dsq_latency.iter_mut())
+        .filter_map(|s| Some((s.p_value?, &mut s.adjusted_p_value, &mut s.significant)))
+        .chain(
+            migration_rates
+                .iter_mut()
+                .filter_map(|r| Some((r.p_value?, &mut r.adjusted_p_value, &mut r.significant))),
+        )
+        .collect();
+
+    let p_values: Vec<f64> = tests.iter().map(|(p, _, _)| *p).collect();
+    for ((_, adjusted, significant), q) in tests.iter_mut().zip(benjamini_hochberg(&p_values)) {
+        **adjusted = Some(q);
+        **significant = q < alpha;
+    }
+    tests.len()
+}
+
+/// Benjamini-Hochberg adjusted p-values, in the order of @p_values. Comparing them against
+/// alpha controls the false discovery rate across all tests at alpha.
+pub fn benjamini_hochberg(p_values: &[f64]) -> Vec<f64> {
+    let m = p_values.len();
+    let mut order: Vec<usize> = (0..m).collect();
+    order.sort_by(|&a, &b| p_values[a].total_cmp(&p_values[b]));
+
+    let mut adjusted = vec![1.0; m];
+    let mut min = 1.0_f64;
+    for (rank, &i) in order.iter().enumerate().rev() {
+        min = min.min(p_values[i] * m as f64 / (rank + 1) as f64);
+        adjusted[i] = min;
+    }
+    adjusted
+}
+
+fn truncate_key(key: &str, width: usize) -> String {
+    if key.chars().count() <= width {
+        key.to_string()
+    } else {
+        key.chars().take(width - 1).chain(['~']).collect()
+    }
+}
+
+fn format_p_value(p_value: Option<f64>) -> String {
+    match p_value {
+        Some(p) if p < 0.0001 => "<0.0001".to_string(),
+        Some(p) => format!("{p:.4}"),
+        None => "-".to_string(),
+    }
+}
+
+fn duration_ns(trace: &PerfettoTrace) -> u64 {
+    let (start, end) = trace.time_range();
+    end.saturating_sub(start)
+}
+
+/// Percentage change from `baseline` to `candidate`
+fn delta_pct(baseline: f64, candidate: f64) -> f64 {
+    if baseline == 0.0 {
+        if candidate == 0.0 {
+            0.0
+        } else {
+            f64::INFINITY.copysign(candidate)
+        }
+    } else {
+        (candidate - baseline) / baseline.abs() * 100.0
+    }
+}
+
+fn wakeup_latencies_by_comm(trace: &Arc<PerfettoTrace>) -> HashMap<String, Vec<u64>> {
+    let correlations = CorrelationAnalyzer::new(trace.clone()).correlate_wakeup_to_schedule(None);
+
+    let mut comms: HashMap<i32, String> = HashMap::new();
+    let mut by_comm: HashMap<String, Vec<u64>> = HashMap::new();
+    let mut all = Vec::with_capacity(correlations.len());
+    for c in correlations {
+        let comm = comms.entry(c.pid).or_insert_with(|| {
+            trace
+                .get_thread_info(c.pid)
+                .and_then(|t| t.name.clone())
+                .or_else(|| {
+                    trace
+                        .get_processes()
+                        .get(&c.pid)
+                        .and_then(|p| p.name.clone())
+                })
+                .unwrap_or_else(|| format!("pid {}", c.pid))
+        });
+        by_comm
+            .entry(comm.clone())
+            .or_default()
+            .push(c.wakeup_latency_ns);
+        all.push(c.wakeup_latency_ns);
+    }
+    if !all.is_empty() {
+        by_comm.insert(ALL_KEY.to_string(), all);
+    }
+    by_comm
+}
+
+fn dsq_latencies(trace: &PerfettoTrace) -> HashMap<String, Vec<u64>> {
+    let mut by_dsq: HashMap<String, Vec<u64>> = HashMap::new();
+    let mut all = Vec::new();
+    for (dsq_id, events) in trace.get_all_dsq_events() {
+        let latencies: Vec<u64> = events
+            .iter()
+            .filter_map(|e| e.latency_us)
+            .filter(|&us| us >= 0)
+            .map(|us| us as u64 * 1000)
+            .collect();
+        if latencies.is_empty() {
+            continue;
+        }
+        all.extend_from_slice(&latencies);
+        by_dsq.insert(format!("dsq {dsq_id:#x}"), latencies);
+    }
+    if !all.is_empty() {
+        by_dsq.insert(ALL_KEY.to_string(), all);
+    }
+    by_dsq
+}
+
+fn migrations_by_comm(trace: &PerfettoTrace) -> HashMap<String, u64> {
+    let mut by_comm: HashMap<String, u64> = HashMap::new();
+    let mut total = 0;
+    for event in trace.get_events_by_type("sched_migrate") {
+        if let Some(ftrace_event::Event::SchedMigrateTask(migrate)) = &event.event {
+            let comm = migrate
+                .comm
+                .clone()
+                .unwrap_or_else(|| format!("pid {}", migrate.pid.unwrap_or(0)));
+            *by_comm.entry(comm).or_insert(0) += 1;
+            total += 1;
+        }
+    }
+    if total > 0 {
+        by_comm.insert(ALL_KEY.to_string(), total);
+    }
+    by_comm
+}
+
+/// Run every applicable analyzer and flatten its numeric output, keyed by
+/// analyzer id and then by dotted field path.
+fn analyzer_metrics(
+    registry: &AnalyzerRegistry,
+    trace: Arc<PerfettoTrace>,
+) -> BTreeMap<String, BTreeMap<String, f64>> {
+    registry
+        .analyze_all(trace)
+        .into_iter()
+        .filter(|r| r.success)
+        .map(|r| {
+            // Arrays are skipped as their elements (top-N lists and the like) don't
+            // line up between traces.
+            let metrics = flatten_json_numbers(&r.data, false).into_iter().collect();
+            (r.analyzer_id, metrics)
+        })
+        .collect()
+}
+
+/// Metrics present in both traces that changed, largest relative change first
+fn analyzer_deltas(
+    baseline: &BTreeMap<String, BTreeMap<String, f64>>,
+    candidate: &BTreeMap<String, BTreeMap<String, f64>>,
+) -> Vec<MetricDelta> {
+    let mut deltas: Vec<MetricDelta> = baseline
+        .iter()
+        .filter_map(|(analyzer, base)| Some((analyzer, base, candidate.get(analyzer)?)))
+        .flat_map(|(analyzer, base, cand)| {
+            base.iter().filter_map(move |(metric, &b)| {
+                let c = *cand.get(metric)?;
+                if b == c {
+                    return None;
+                }
+                Some(MetricDelta {
+                    analyzer: analyzer.clone(),
+                    metric: metric.clone(),
+                    baseline: b,
+                    candidate: c,
+                    delta: c - b,
+                    delta_pct: delta_pct(b, c),
+                })
+            })
+        })
+        .collect();
+
+    deltas.sort_by(|a, b| b.delta_pct.abs().total_cmp(&a.delta_pct.abs()));
+    deltas
+}
+
+/// Two-sided p-value of the Mann-Whitney U test using the normal
+/// approximation with tie and continuity correction.
+pub fn mann_whitney_u(a: &[u64], b: &[u64]) -> f64 {
+    let n1 = a.len() as f64;
+    let n2 = b.len() as f64;
+    if a.is_empty() || b.is_empty() {
+        return 1.0;
+    }
+
+    let mut combined: Vec<(u64, bool)> = a
+        .iter()
+        .map(|&v| (v, true))
+        .chain(b.iter().map(|&v| (v, false)))
+        .collect();
+    combined.sort_unstable_by_key(|&(v, _)| v);
+
+    // Assign average ranks to ties
+    let n = combined.len();
+    let mut rank_sum_a = 0.0;
+    let mut tie_term = 0.0;
+    let mut i = 0;
+    while i < n {
+        let mut j = i;
+        while j + 1 < n && combined[j + 1].0 == combined[i].0 {
+            j += 1;
+        }
+        let avg_rank = (i + j) as f64 / 2.0 + 1.0;
+        let t = (j - i + 1) as f64;
+        tie_term += t * t * t - t;
+        rank_sum_a += avg_rank * combined[i..=j].iter().filter(|(_, in_a)| *in_a).count() as f64;
+        i = j + 1;
+    }
+
+    let u1 = rank_sum_a - n1 * (n1 + 1.0) / 2.0;
+    let mean = n1 * n2 / 2.0;
+    let nf = n as f64;
+    let variance = n1 * n2 / 12.0 * ((nf + 1.0) - tie_term / (nf * (nf - 1.0)));
+    if variance <= 0.0 {
+        return 1.0;
+    }
+
+    let z = ((u1 - mean).abs() - 0.5).max(0.0) / variance.sqrt();
+    two_sided_p(z)
+}
+
+/// Two-sided p-value for equal Poisson rates, given `c1` events over `t1`
+/// and `c2` events over `t2`. Conditional on the total, `c1` is binomial
+/// with p = t1 / (t1 + t2); this uses the normal approximation.
+pub fn poisson_rate_test(c1: u64, t1: f64, c2: u64, t2: f64) -> f64 {
+    let total = (c1 + c2) as f64;
+    if total == 0.0 || t1 <= 0.0 || t2 <= 0.0 {
+        return 1.0;
+    }
+    let p = t1 / (t1 + t2);
+    let expected = total * p;
+    let sd = (total * p * (1.0 - p)).sqrt();
+    let z = ((c1 as f64 - expected).abs() - 0.5).max(0.0) / sd;
+    two_sided_p(z)
+}
+
+fn two_sided_p(z: f64) -> f64 {
+    erfc(z.abs() / std::f64::consts::SQRT_2).min(1.0)
+}
+
+/// Complementary error function, accurate to about 1.2e-7
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let r = t
+        * (-z * z - 1.26551223
+            + t * (1.00002368
+                + t * (0.37409196
+                    + t * (0.09678418
+                        + t * (-0.18628806
+                            + t * (0.27886807
+                                + t * (-1.13520398
+                                    + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277)))))))))
+            .exp();
+    if x >= 0.0 {
+        r
+    } else {
+        2.0 - r
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_erfc() {
+        assert!((erfc(0.0) - 1.0).abs() < 1e-6);
+        assert!((erfc(1.0) - 0.157299207).abs() < 1e-6);
+        assert!((erfc(-1.0) - 1.842700793).abs() < 1e-6);
+        // z = 1.96 is the usual 5% two-sided cutoff
+        assert!((two_sided_p(1.96) - 0.05).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_mann_whitney_u() {
+        let a: Vec<u64> = (0..50).collect();
+        assert!(mann_whitney_u(&a, &a) > 0.9);
+
+        let b: Vec<u64> = (100..150).collect();
+        assert!(mann_whitney_u(&a, &b) < 1e-6);
+
+        // Interleaved samples are indistinguishable
+        let even: Vec<u64> = (0..100).step_by(2).collect();
+        let odd: Vec<u64> = (1..100).step_by(2).collect();
+        assert!(mann_whitney_u(&even, &odd) > 0.5);
+
+        // All ties
+        assert_eq!(mann_whitney_u(&[5; 30], &[5; 30]), 1.0);
+        assert_eq!(mann_whitney_u(&[], &[1, 2, 3]), 1.0);
+    }
+
+    #[test]
+    fn test_poisson_rate_test() {
+        assert!(poisson_rate_test(100, 1.0, 100, 1.0) > 0.9);
+        assert!(poisson_rate_test(100, 1.0, 200, 2.0) > 0.9);
+        assert!(poisson_rate_test(100, 1.0, 300, 1.0) < 1e-6);
+        assert_eq!(poisson_rate_test(0, 1.0, 0, 1.0), 1.0);
+    }
+
+    #[test]
+    fn test_benjamini_hochberg() {
+        let adjusted = benjamini_hochberg(&[0.01, 0.04, 0.03, 0.005]);
+        let expected = [0.02, 0.04, 0.04, 0.02];
+        for (q, e) in adjusted.iter().zip(expected) {
+            assert!((q - e).abs() < 1e-12, "{adjusted:?}");
+        }
+        assert_eq!(benjamini_hochberg(&[0.9, 0.8]), vec![0.9, 0.9]);
+        assert_eq!(benjamini_hochberg(&[0.5; 3]), vec![0.5; 3]);
+        assert!(benjamini_hochberg(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_adjust_p_values() {
+        let rate = |key: &str, p_value: Option<f64>| RateComparison {
+            key: key.to_string(),
+            baseline_count: 0,
+            candidate_count: 0,
+            baseline_per_sec: 0.0,
+            candidate_per_sec: 0.0,
+            delta_pct: 0.0,
+            p_value,
+            adjusted_p_value: None,
+            significant: false,
+        };
+        // The ones at 0.04 would be significant on their own, but not after the adjustment
+        // over 20 tests.
+        let mut rates: Vec<RateComparison> = (0..20)
+            .map(|i| {
+                let p_value = match i {
+                    0..2 => 0.001,
+                    2..10 => 0.04,
+                    _ => 0.5,
+                };
+                rate(&i.to_string(), Some(p_value))
+            })
+            .chain([rate("untested", None)])
+            .collect();
+
+        assert_eq!(adjust_p_values(0.05, &mut [], &mut [], &mut rates), 20);
+        assert!(rates[..2].iter().all(|r| r.significant));
+        assert!((rates[0].adjusted_p_value.unwrap() - 0.01).abs() < 1e-12);
+        assert!(rates[2..].iter().all(|r| !r.significant));
+        assert!((rates[2].adjusted_p_value.unwrap() - 0.08).abs() < 1e-12);
+        assert_eq!(rates[20].adjusted_p_value, None);
+    }
+
+    #[test]
+    fn test_delta_pct() {
+        assert_eq!(delta_pct(100.0, 150.0), 50.0);
+        assert_eq!(delta_pct(100.0, 50.0), -50.0);
+        assert_eq!(delta_pct(0.0, 0.0), 0.0);
+        assert_eq!(delta_pct(0.0, 1.0), f64::INFINITY);
+    }
+
+    #[test]
+    fn test_analyzer_deltas() {
+        let base =
+            json!({"total": 10, "nested": {"avg": 2.0, "same": 1}, "top": [1, 2], "name": "x"});
+        let cand = json!({"total": 20, "nested": {"avg": 1.0, "same": 1}, "top": [3, 4]});
+
+        let base_metrics: BTreeMap<_, _> = flatten_json_numbers(&base, false).into_iter().collect();
+        let cand_metrics: BTreeMap<_, _> = flatten_json_numbers(&cand, false).into_iter().collect();
+        assert_eq!(base_metrics.len(), 3);
+
+        let deltas = analyzer_deltas(
+            &BTreeMap::from([("a".to_string(), base_metrics)]),
+            &BTreeMap::from([("a".to_string(), cand_metrics)]),
+        );
+        assert_eq!(deltas.len(), 2);
+        assert_eq!(deltas[0].metric, "total");
+        assert_eq!(deltas[0].delta, 10.0);
+        assert_eq!(deltas[1].metric, "nested.avg");
+        assert_eq!(deltas[1].delta_pct, -50.0);
+    }
+
+    #[test]
+    fn test_compare_samples() {
+        let comparator = TraceComparator::new(0.05, 20);
+        let base = HashMap::from([
+            ("fast".to_string(), (0..100).collect::<Vec<u64>>()),
+            ("few".to_string(), vec![1, 2, 3]),
+            ("gone".to_string(), vec![1, 2, 3]),
+        ]);
+        let cand = HashMap::from([
+            ("fast".to_string(), (1000..1100).collect::<Vec<u64>>()),
+            ("few".to_string(), vec![100, 200, 300]),
+        ]);
+
+        let mut result = comparator.compare_samples(&base, &cand);
+        assert_eq!(adjust_p_values(0.05, &mut result, &mut [], &mut []), 1);
+        result.sort_by(|a, b| a.key.cmp(&b.key));
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].key, "fast");
+        assert!(result[0].significant);
+        // A single test is not adjusted.
+        assert_eq!(result[0].adjusted_p_value, result[0].p_value);
+        assert!(result[0].p50_delta_pct > 0.0);
+        assert_eq!(result[1].key, "few");
+        assert!(result[1].p_value.is_none());
+        assert!(result[1].adjusted_p_value.is_none());
+        assert!(!result[1].significant);
+    }
+}
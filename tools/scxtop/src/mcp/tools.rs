@@ -714,6 +714,41 @@ impl McpTools {
                     "required": ["trace_id"]
                 }),
             },
+            McpTool {
+                name: "compare_traces".to_string(),
+                description: "Compare two loaded traces (A/B): per-process wakeup latency percentiles, per-DSQ latency and migration rates with significance tests, plus deltas of all analyzer metrics".to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "baseline_trace_id": {
+                            "type": "string",
+                            "description": "Trace ID of the baseline (A) trace"
+                        },
+                        "candidate_trace_id": {
+                            "type": "string",
+                            "description": "Trace ID of the candidate (B) trace"
+                        },
+                        "alpha": {
+                            "type": "number",
+                            "description": "False discovery rate, applied to Benjamini-Hochberg adjusted p-values (default: 0.05)",
+                            "default": 0.05
+                        },
+                        "min_samples": {
+                            "type": "integer",
+                            "description": "Minimum samples per side before a difference is tested (default: 20)",
+                            "default": 20
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "Maximum number of entries per section (default: 20)",
+                            "default": 20,
+                            "minimum": 1,
+                            "maximum": 1000
+                        }
+                    },
+                    "required": ["baseline_trace_id", "candidate_trace_id"]
+                }),
+            },
             McpTool {
                 name: "detect_outliers".to_string(),
                 description: "Detect outliers in trace data across latency, runtime, and CPU metrics using statistical methods".to_string(),
@@ -1205,6 +1240,7 @@ impl McpTools {
             "discover_analyzers" => self.tool_discover_analyzers(arguments),
             "get_trace_summary" => self.tool_get_trace_summary(arguments),
             "run_all_analyzers" => self.tool_run_all_analyzers(arguments),
+            "compare_traces" => self.tool_compare_traces(arguments),
             "detect_outliers" => self.tool_detect_outliers(arguments),
             // Dedicated analyzer tools
             "analyze_cpu_utilization" => self.tool_analyze_cpu_utilization(arguments),
@@ -3840,6 +3876,65 @@ impl McpTools {
         }))
     }
 
+    fn tool_compare_traces(&self, args: &Value) -> Result<Value> {
+        use super::perfetto_analyzer_registry::AnalyzerRegistry;
+        use super::perfetto_trace_diff::TraceComparator;
+
+        let cache = self
+            .trace_cache
+            .as_ref()
+            .ok_or_else(|| anyhow!("Trace cache not available"))?;
+
+        let baseline_id = args
+            .get("baseline_trace_id")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow!("Missing baseline_trace_id parameter"))?;
+        let candidate_id = args
+            .get("candidate_trace_id")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow!("Missing candidate_trace_id parameter"))?;
+
+        let alpha = args.get("alpha").and_then(|v| v.as_f64()).unwrap_or(0.05);
+        let min_samples = args
+            .get("min_samples")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(20) as usize;
+        let limit = args
+            .get("limit")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(20)
+            .clamp(1, 1000) as usize;
+
+        // Get traces from cache
+        let cache_lock = cache.lock().unwrap();
+        let baseline = cache_lock
+            .get(baseline_id)
+            .ok_or_else(|| anyhow!("Trace '{}' not found", baseline_id))?
+            .clone();
+        let candidate = cache_lock
+            .get(candidate_id)
+            .ok_or_else(|| anyhow!("Trace '{}' not found", candidate_id))?
+            .clone();
+        drop(cache_lock);
+
+        let registry = AnalyzerRegistry::with_builtins();
+        let mut comparison =
+            TraceComparator::new(alpha, min_samples).compare(baseline, candidate, &registry);
+        comparison.truncate(limit);
+
+        Ok(json!({
+            "content": [{
+                "type": "text",
+                "text": format!(
+                    "{}\n{}",
+                    comparison.format_text(),
+                    serde_json::to_string_pretty(&comparison)
+                        .unwrap_or_else(|_| "Failed to serialize results".to_string())
+                )
+            }]
+        }))
+    }
+
     fn tool_detect_outliers(&self, args: &Value) -> Result<Value> {
         use super::outlier_detection::OutlierMethod;
         use super::perfetto_outlier_analyzer::PerfettoOutlierAnalyzer;
@@ -5,10 +5,11 @@
 
 use anyhow::Result;
 use procfs::{Current as _, Meminfo};
+use serde::{Deserialize, Serialize};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
-#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct MemStatSnapshot {
     pub total_kb: u64,
     pub free_kb: u64,
@@ -4,22 +4,29 @@
 // GNU General Public License version 2.
 
 use anyhow::Result;
+use log::{info, warn};
 use procfs::process::Process as ProcProcess;
 use rand::rngs::StdRng;
 use rand::Rng;
 use rand::SeedableRng;
+use scx_stats::prelude::StatsClient;
 use scx_utils::scx_enums;
+use serde_json::Value as JsonValue;
+use tokio::sync::mpsc::UnboundedSender;
 
 use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use crate::edm::ActionHandler;
-use crate::util::get_clock_value;
+use crate::util::{flatten_json_numbers, get_clock_value};
 use crate::{
     Action, CpuhpEnterAction, CpuhpExitAction, ExecAction, ExitAction, ForkAction, GpuMemAction,
-    IPIAction, KprobeAction, SchedHangAction, SchedMigrateTaskAction, SchedSwitchAction,
-    SchedWakeupAction, SchedWakingAction, SoftIRQAction, SystemStatAction, WaitAction,
+    IPIAction, KprobeAction, SchedHangAction, SchedMigrateTaskAction, SchedStatsSampleAction,
+    SchedSwitchAction, SchedWakeupAction, SchedWakingAction, SoftIRQAction, SystemStatAction,
+    WaitAction,
 };
 
 use perfetto_protos::{
@@ -50,6 +57,67 @@ use perfetto_protos::{
 };
 use protobuf::{EnumOrUnknown, Message, SpecialFields};
 
+/// Prefix of the names of scheduler stats counter tracks.
+pub const SCHED_STATS_TRACK_PREFIX: &str = "sched_stats.";
+
+/// Builds a stats sample of a stats response. It is stamped with CLOCK_MONOTONIC like the BPF
+/// events, so the counters line up with the sched events in the trace.
+fn sched_stats_sample(stats: &JsonValue) -> SchedStatsSampleAction {
+    SchedStatsSampleAction {
+        ts: get_clock_value(libc::CLOCK_MONOTONIC),
+        stats: stats.to_string(),
+    }
+}
+
+/// Polls the scheduler stats socket every @interval until @shutdown is set and sends each
+/// response as a stats sample. Reconnects when the scheduler restarts.
+pub fn poll_sched_stats(
+    socket_path: String,
+    interval: Duration,
+    action_tx: UnboundedSender<Action>,
+    shutdown: Arc<AtomicBool>,
+) {
+    let mut client: Option<StatsClient> = None;
+    let mut warned = false;
+    let mut nr_samples = 0;
+
+    while !shutdown.load(Ordering::Relaxed) {
+        if client.is_none() {
+            match StatsClient::new().set_path(&socket_path).connect(None) {
+                Ok(c) => {
+                    info!("sched stats: connected to stats socket {}", socket_path);
+                    client = Some(c);
+                    warned = false;
+                }
+                Err(e) if !warned => {
+                    warn!("sched stats: failed to connect to {}: {}", socket_path, e);
+                    warned = true;
+                }
+                Err(_) => {}
+            }
+        }
+
+        if let Some(c) = client.as_mut() {
+            match c.request::<JsonValue>("stats", vec![]) {
+                Ok(stats) => {
+                    let action = Action::SchedStatsSample(sched_stats_sample(&stats));
+                    if action_tx.send(action).is_err() {
+                        break;
+                    }
+                    nr_samples += 1;
+                }
+                Err(e) => {
+                    warn!("sched stats: stats request failed: {}", e);
+                    client = None;
+                }
+            }
+        }
+
+        std::thread::sleep(interval);
+    }
+    info!("sched stats: collected {} samples", nr_samples);
+}
+
 /// Handler for perfetto traces. For details on data flow in perfetto see:
 /// https://perfetto.dev/docs/concepts/buffers and
 /// https://perfetto.dev/docs/reference/trace-packet-proto
@@ -74,6 +142,9 @@ pub struct PerfettoTraceManager {
     sys_stats: BTreeMap<u64, Vec<SysStats>>,
     mem_events: BTreeMap<String, Vec<TrackEvent>>,
     mem_uuids: HashMap<String, u64>,
+    // scheduler stats counter tracks keyed by field
+    sched_stat_events: BTreeMap<String, Vec<TrackEvent>>,
+    sched_stat_uuids: BTreeMap<String, u64>,
     // Topology metadata to embed in trace for cross-machine analysis
     topology_json: Option<String>,
 }
@@ -110,6 +181,8 @@ impl PerfettoTraceManager {
             sys_stats: BTreeMap::new(),
             mem_events: BTreeMap::new(),
             mem_uuids,
+            sched_stat_events: BTreeMap::new(),
+            sched_stat_uuids: BTreeMap::new(),
             topology_json: None,
         }
     }
@@ -158,6 +231,8 @@ impl PerfettoTraceManager {
         self.ftrace_events.clear();
         self.dsq_lat_events.clear();
         self.dsq_uuids.clear();
+        self.sched_stat_events.clear();
+        self.sched_stat_uuids.clear();
     }
 
     /// Returns the trace file.
@@ -228,6 +303,25 @@ impl PerfettoTraceManager {
             );
         }
 
+        for (field, &uuid) in &self.sched_stat_uuids {
+            let name = format!("{SCHED_STATS_TRACK_PREFIX}{field}");
+            desc_map.insert(
+                uuid,
+                vec![TrackDescriptor {
+                    uuid: Some(uuid),
+                    counter: Some(CounterDescriptor {
+                        unit: Some(UNIT_COUNT.into()),
+                        unit_name: Some(field.to_string()),
+                        is_incremental: Some(false),
+                        ..CounterDescriptor::default()
+                    })
+                    .into(),
+                    static_or_dynamic_name: Some(Static_or_dynamic_name::StaticName(name)),
+                    ..TrackDescriptor::default()
+                }],
+            );
+        }
+
         desc_map
     }
 
@@ -377,6 +471,9 @@ impl PerfettoTraceManager {
             self.ftrace_events
                 .iter_mut()
                 .for_each(|(_, v)| v.retain(|e| e.timestamp.unwrap_or(0) < ns));
+            self.sched_stat_events
+                .iter_mut()
+                .for_each(|(_, v)| v.retain(|e| timestamp_absolute_us(e) * 1000 < signed_ns));
         };
 
         for (_, process) in self.process_descriptors.drain() {
@@ -520,6 +617,24 @@ impl PerfettoTraceManager {
             }
         }
 
+        // scheduler stats events
+        for events in self.sched_stat_events.values_mut() {
+            let sched_stat_sequence_id = self.rng.next_u32();
+            for sched_stat_event in events.drain(..) {
+                let ts: u64 = timestamp_absolute_us(&sched_stat_event) as u64 * 1_000;
+                self.trace.packet.push(TracePacket {
+                    data: Some(trace_packet::Data::TrackEvent(sched_stat_event)),
+                    timestamp: Some(ts),
+                    optional_trusted_packet_sequence_id: Some(
+                        trace_packet::Optional_trusted_packet_sequence_id::TrustedPacketSequenceId(
+                            sched_stat_sequence_id,
+                        ),
+                    ),
+                    ..TracePacket::default()
+                });
+            }
+        }
+
         // ftrace events
         for cpu in &trace_cpus {
             self.trace.packet.push(TracePacket {
@@ -1032,6 +1147,36 @@ impl PerfettoTraceManager {
             });
     }
 
+    /// Adds a counter event for every numeric field of a scheduler stats sample.
+    pub fn on_sched_stats(&mut self, action: &SchedStatsSampleAction) {
+        let SchedStatsSampleAction { ts, stats } = action;
+
+        let Ok(stats) = serde_json::from_str::<JsonValue>(stats) else {
+            return;
+        };
+
+        for (field, value) in flatten_json_numbers(&stats, true) {
+            let uuid = *self
+                .sched_stat_uuids
+                .entry(field.clone())
+                .or_insert_with(|| self.rng.next_u64());
+            self.sched_stat_events
+                .entry(field)
+                .or_default()
+                .push(TrackEvent {
+                    type_: Some(track_event::Type::TYPE_COUNTER.into()),
+                    track_uuid: Some(uuid),
+                    counter_value_field: Some(
+                        track_event::Counter_value_field::DoubleCounterValue(value),
+                    ),
+                    timestamp: Some(track_event::Timestamp::TimestampAbsoluteUs(
+                        (*ts) as i64 / 1000,
+                    )),
+                    ..TrackEvent::default()
+                });
+        }
+    }
+
     /// Adds events for the sched_switch event.
     pub fn on_sched_switch(&mut self, action: &SchedSwitchAction) {
         let SchedSwitchAction {
@@ -1165,9 +1310,28 @@ impl ActionHandler for PerfettoTraceManager {
             Action::SystemStat(a) => {
                 self.on_sys_stat(a);
             }
+            Action::SchedStatsSample(a) => {
+                self.on_sched_stats(a);
+            }
             _ => {}
         }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sched_stats_sample_clock() {
+        let stats = serde_json::json!({"busy": 12.5});
+        let before = get_clock_value(libc::CLOCK_MONOTONIC);
+        let sample = sched_stats_sample(&stats);
+        let after = get_clock_value(libc::CLOCK_MONOTONIC);
+
+        assert!((before..=after).contains(&sample.ts));
+        assert_eq!(sample.stats, r#"{"busy":12.5}"#);
+    }
+}
@@ -0,0 +1,600 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+//! Headless recording and replay of the data shown by the TUI.
+//!
+//! A recording is a JSON Lines file. The first line is the [`RecordingHeader`]
+//! together with the topology of the recorded machine, every following line is
+//! a [`RecordedFrame`] sampled at the recording interval. Event values are the
+//! most recent sample of each event at the time the frame was taken, zero
+//! values are left out.
+
+use crate::EventData;
+use crate::MemStatSnapshot;
+use crate::ProcData;
+use crate::SystemPowerData;
+
+use anyhow::{anyhow, Context, Result};
+use procfs::process::ProcState;
+use scx_utils::Topology;
+use serde::{Deserialize, Serialize};
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+pub const RECORDING_VERSION: u32 = 1;
+
+/// Most recent value of each event, keyed by event name.
+pub type Samples = BTreeMap<String, u64>;
+
+/// Metadata describing a recording.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordingHeader {
+    pub version: u32,
+    pub hostname: String,
+    pub scheduler: String,
+    pub start_unix_ms: u64,
+    pub interval_ms: u64,
+    /// Cpumask width of the recorded machine, used to load the topology.
+    pub nr_cpu_ids: usize,
+}
+
+#[derive(Serialize)]
+struct HeaderLineRef<'a> {
+    #[serde(flatten)]
+    header: &'a RecordingHeader,
+    topology: &'a Topology,
+}
+
+#[derive(Deserialize)]
+struct HeaderLine {
+    #[serde(flatten)]
+    header: RecordingHeader,
+    topology: serde_json::Value,
+}
+
+/// Sampled state of a single process.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProcSample {
+    pub name: String,
+    /// Only written the first time a process is recorded, filled in on load.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cmdline: Vec<String>,
+    pub cpu: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub llc: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dsq: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layer_id: Option<i32>,
+    pub util: f64,
+    pub state: char,
+    pub threads: i64,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub events: Samples,
+}
+
+impl ProcSample {
+    /// Samples the current state of a process.
+    pub fn from_proc_data(data: &ProcData) -> Self {
+        Self {
+            name: data.process_name.clone(),
+            cmdline: data.cmdline.clone(),
+            cpu: data.cpu,
+            llc: data.llc,
+            node: data.node,
+            dsq: data.dsq,
+            layer_id: data.layer_id,
+            util: data.cpu_util_perc,
+            state: proc_state_char(data.state),
+            threads: data.num_threads,
+            events: latest_samples(&data.data),
+        }
+    }
+
+    /// Creates the ProcData shown for a recorded process.
+    pub fn to_proc_data(&self, tgid: i32, max_data_size: usize) -> ProcData {
+        ProcData {
+            tgid,
+            process_name: self.name.clone(),
+            cpu: self.cpu,
+            llc: self.llc,
+            node: self.node,
+            dsq: self.dsq,
+            layer_id: self.layer_id,
+            prev_cpu_time: 0,
+            current_cpu_time: 0,
+            cpu_util_perc: self.util,
+            state: ProcState::from_char(self.state).unwrap_or(ProcState::Sleeping),
+            cmdline: self.cmdline.clone(),
+            threads: BTreeMap::new(),
+            num_threads: self.threads,
+            data: EventData::new(max_data_size),
+            max_data_size,
+        }
+    }
+}
+
+/// Data sampled at one point of a recording.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    /// Milliseconds since the start of the recording.
+    pub ts_ms: u64,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub cpus: BTreeMap<usize, Samples>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub llcs: BTreeMap<usize, Samples>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub nodes: BTreeMap<usize, Samples>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dsqs: BTreeMap<u64, Samples>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub procs: BTreeMap<i32, ProcSample>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mem: Option<MemStatSnapshot>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub power: Option<SystemPowerData>,
+}
+
+/// Returns the most recent non-zero value of every event.
+pub fn latest_samples(data: &EventData) -> Samples {
+    data.data
+        .iter()
+        .filter_map(|(event, values)| match values.back() {
+            Some(&val) if val != 0 => Some((event.clone(), val)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Rebuilds the history of every event from per frame samples, oldest first.
+/// Events missing from a frame are recorded as zero.
+pub fn fill_event_data(data: &mut EventData, samples: &[Option<&Samples>]) {
+    let mut events: BTreeSet<String> = data.data.keys().cloned().collect();
+    for frame_samples in samples.iter().flatten() {
+        events.extend(frame_samples.keys().cloned());
+    }
+
+    for event in events {
+        data.zero_event(&event);
+        for frame_samples in samples {
+            let val = frame_samples.and_then(|s| s.get(&event)).copied();
+            data.add_event_data(&event, val.unwrap_or(0));
+        }
+    }
+}
+
+fn proc_state_char(state: ProcState) -> char {
+    match state {
+        ProcState::Running => 'R',
+        ProcState::Sleeping => 'S',
+        ProcState::Waiting => 'D',
+        ProcState::Zombie => 'Z',
+        ProcState::Stopped => 'T',
+        ProcState::Tracing => 't',
+        ProcState::Dead => 'X',
+        ProcState::Wakekill => 'K',
+        ProcState::Waking => 'W',
+        ProcState::Parked => 'P',
+        ProcState::Idle => 'I',
+    }
+}
+
+/// Writes a recording to a file.
+pub struct Recorder {
+    writer: BufWriter<File>,
+    seen_tgids: HashSet<i32>,
+}
+
+impl Recorder {
+    /// Creates the recording file and writes the header.
+    pub fn create(path: &Path, header: &RecordingHeader, topo: &Topology) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create recording {}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer(
+            &mut writer,
+            &HeaderLineRef {
+                header,
+                topology: topo,
+            },
+        )?;
+        writer.write_all(b"\n")?;
+
+        Ok(Self {
+            writer,
+            seen_tgids: HashSet::new(),
+        })
+    }
+
+    /// Appends a frame to the recording.
+    pub fn write_frame(&mut self, mut frame: RecordedFrame) -> Result<()> {
+        for (tgid, proc_sample) in frame.procs.iter_mut() {
+            if !self.seen_tgids.insert(*tgid) {
+                proc_sample.cmdline.clear();
+            }
+        }
+        self.seen_tgids
+            .retain(|tgid| frame.procs.contains_key(tgid));
+
+        serde_json::to_writer(&mut self.writer, &frame)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// A recording loaded from a file.
+#[derive(Debug)]
+pub struct Recording {
+    pub header: RecordingHeader,
+    pub topology: Topology,
+    pub frames: Vec<RecordedFrame>,
+}
+
+impl Recording {
+    /// Loads a recording. A truncated last line, as left behind by an
+    /// interrupted recorder, is ignored.
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open recording {}", path.display()))?;
+        Self::from_reader(BufReader::new(file))
+    }
+
+    pub fn from_reader(reader: impl BufRead) -> Result<Self> {
+        let mut lines = reader.lines();
+        let header_line = lines
+            .next()
+            .ok_or_else(|| anyhow!("Recording is empty"))??;
+        let HeaderLine { header, topology } =
+            serde_json::from_str(&header_line).context("Invalid recording header")?;
+        if header.version > RECORDING_VERSION {
+            return Err(anyhow!(
+                "Recording version {} is newer than supported version {}",
+                header.version,
+                RECORDING_VERSION
+            ));
+        }
+        let topology = Topology::from_json_with_width(&topology.to_string(), header.nr_cpu_ids)
+            .context("Invalid recording topology")?;
+
+        let lines: Vec<String> = lines.collect::<std::io::Result<_>>()?;
+        let mut frames = Vec::with_capacity(lines.len());
+        let mut cmdlines: HashMap<i32, Vec<String>> = HashMap::new();
+        for (i, line) in lines.iter().enumerate() {
+            let mut frame: RecordedFrame = match serde_json::from_str(line) {
+                Ok(frame) => frame,
+                Err(_) if i + 1 == lines.len() => break,
+                Err(e) => return Err(e).context(format!("Invalid frame on line {}", i + 2)),
+            };
+            cmdlines.retain(|tgid, _| frame.procs.contains_key(tgid));
+            for (tgid, proc_sample) in frame.procs.iter_mut() {
+                if proc_sample.cmdline.is_empty() {
+                    if let Some(cmdline) = cmdlines.get(tgid) {
+                        proc_sample.cmdline = cmdline.clone();
+                    }
+                } else {
+                    cmdlines.insert(*tgid, proc_sample.cmdline.clone());
+                }
+            }
+            frames.push(frame);
+        }
+
+        Ok(Self {
+            header,
+            topology,
+            frames,
+        })
+    }
+}
+
+/// Playback position within a recording.
+#[derive(Clone, Debug)]
+pub struct ReplayState {
+    pub header: RecordingHeader,
+    pub frames: Vec<RecordedFrame>,
+    pos: usize,
+    playing: bool,
+    /// Time played since the current frame was reached.
+    clock_ms: u64,
+    /// Set when the position or play state changed since the last
+    /// take_changed().
+    changed: bool,
+}
+
+impl ReplayState {
+    pub fn new(header: RecordingHeader, frames: Vec<RecordedFrame>) -> Result<Self> {
+        if frames.is_empty() {
+            return Err(anyhow!("Recording has no frames"));
+        }
+        Ok(Self {
+            header,
+            frames,
+            pos: 0,
+            playing: true,
+            clock_ms: 0,
+            changed: true,
+        })
+    }
+
+    /// Returns the index of the current frame.
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    pub fn playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Returns the current frame.
+    pub fn frame(&self) -> &RecordedFrame {
+        &self.frames[self.pos]
+    }
+
+    /// Returns up to `len` frames ending with the current one.
+    pub fn window(&self, len: usize) -> &[RecordedFrame] {
+        let start = (self.pos + 1).saturating_sub(len);
+        &self.frames[start..=self.pos]
+    }
+
+    /// Returns the index of the last frame taken at or before `ts_ms`.
+    pub fn index_at(&self, ts_ms: u64) -> usize {
+        self.frames
+            .partition_point(|frame| frame.ts_ms <= ts_ms)
+            .saturating_sub(1)
+    }
+
+    /// Returns whether the displayed data needs to be rebuilt and clears the
+    /// flag.
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+
+    /// Forces the displayed data to be rebuilt, e.g. after a resize.
+    pub fn mark_changed(&mut self) {
+        self.changed = true;
+    }
+
+    /// Plays `elapsed_ms` of the recording. Playback stops at the last frame.
+    pub fn advance(&mut self, elapsed_ms: u64) {
+        if !self.playing {
+            return;
+        }
+        let prev = self.pos;
+        self.clock_ms += elapsed_ms;
+        while let Some(next) = self.frames.get(self.pos + 1) {
+            let delta = next.ts_ms.saturating_sub(self.frames[self.pos].ts_ms);
+            if delta > self.clock_ms {
+                break;
+            }
+            self.clock_ms -= delta;
+            self.pos += 1;
+        }
+        if self.pos + 1 == self.frames.len() {
+            self.playing = false;
+            self.clock_ms = 0;
+            self.changed = true;
+        }
+        self.changed |= self.pos != prev;
+    }
+
+    /// Pauses or resumes playback, restarting from the beginning when resumed
+    /// at the last frame.
+    pub fn toggle_play(&mut self) {
+        if !self.playing && self.pos + 1 == self.frames.len() {
+            self.pos = 0;
+        }
+        self.playing = !self.playing;
+        self.clock_ms = 0;
+        self.changed = true;
+    }
+
+    /// Moves the playback position by `delta_ms`. Seeking forward moves at
+    /// least one frame, even if the next frame is further away.
+    pub fn seek_by(&mut self, delta_ms: i64) {
+        let pos = self.index_at(self.frame().ts_ms.saturating_add_signed(delta_ms));
+        self.pos = if delta_ms > 0 {
+            pos.max((self.pos + 1).min(self.frames.len() - 1))
+        } else {
+            pos
+        };
+        self.clock_ms = 0;
+        self.changed = true;
+    }
+
+    pub fn seek_to(&mut self, ts_ms: u64) {
+        self.pos = self.index_at(ts_ms);
+        self.clock_ms = 0;
+        self.changed = true;
+    }
+
+    pub fn seek_start(&mut self) {
+        self.seek_to(0);
+    }
+
+    pub fn seek_end(&mut self) {
+        self.pos = self.frames.len() - 1;
+        self.clock_ms = 0;
+        self.playing = false;
+        self.changed = true;
+    }
+
+    /// Returns the timestamp of the last frame.
+    pub fn end_ms(&self) -> u64 {
+        self.frames.last().map_or(0, |frame| frame.ts_ms)
+    }
+
+    /// Returns a one line description of the playback state.
+    pub fn title(&self) -> String {
+        let scheduler = if self.header.scheduler.is_empty() {
+            "no scheduler"
+        } else {
+            &self.header.scheduler
+        };
+        format!(
+            "replay {} ({}) {} {}/{}",
+            self.header.hostname,
+            scheduler,
+            if self.playing { "playing" } else { "paused" },
+            format_ms(self.frame().ts_ms),
+            format_ms(self.end_ms()),
+        )
+    }
+}
+
+fn format_ms(ms: u64) -> String {
+    let secs = ms / 1000;
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scx_utils::NR_CPU_IDS;
+
+    fn header() -> RecordingHeader {
+        RecordingHeader {
+            version: RECORDING_VERSION,
+            hostname: "host".to_string(),
+            scheduler: "scx_test".to_string(),
+            start_unix_ms: 1_700_000_000_000,
+            interval_ms: 1000,
+            nr_cpu_ids: *NR_CPU_IDS,
+        }
+    }
+
+    fn frames(n: u64) -> Vec<RecordedFrame> {
+        (0..n)
+            .map(|i| RecordedFrame {
+                ts_ms: i * 1000,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn samples(vals: &[(&str, u64)]) -> Samples {
+        vals.iter().map(|(k, v)| (k.to_string(), *v)).collect()
+    }
+
+    #[test]
+    fn test_latest_and_fill_event_data() {
+        let mut data = EventData::new(4);
+        data.add_event_data("cpu_freq", 1000);
+        data.add_event_data("cpu_freq", 2000);
+        data.add_event_data("perf", 0);
+        assert_eq!(latest_samples(&data), samples(&[("cpu_freq", 2000)]));
+
+        let a = samples(&[("cpu_freq", 1)]);
+        let b = samples(&[("perf", 5)]);
+        let c = samples(&[("cpu_freq", 3)]);
+        fill_event_data(&mut data, &[Some(&a), None, Some(&b), Some(&c)]);
+        assert_eq!(data.event_data_immut("cpu_freq"), vec![1, 0, 0, 3]);
+        assert_eq!(data.event_data_immut("perf"), vec![0, 0, 5, 0]);
+    }
+
+    #[test]
+    fn test_recording_roundtrip() {
+        let topo = Topology::new().unwrap();
+        let file = tempfile::NamedTempFile::new().unwrap();
+
+        let proc_sample = ProcSample {
+            name: "worker".to_string(),
+            cmdline: vec!["worker".to_string(), "--fast".to_string()],
+            cpu: 1,
+            util: 12.5,
+            state: 'R',
+            threads: 4,
+            events: samples(&[("lat_us", 40)]),
+            ..Default::default()
+        };
+        let mut recorder = Recorder::create(file.path(), &header(), &topo).unwrap();
+        for mut frame in frames(3) {
+            frame
+                .cpus
+                .insert(0, samples(&[("cpu_freq", frame.ts_ms + 1)]));
+            frame.procs.insert(42, proc_sample.clone());
+            recorder.write_frame(frame).unwrap();
+        }
+        recorder.flush().unwrap();
+
+        // Only the first frame carries the command line
+        let contents = std::fs::read_to_string(file.path()).unwrap();
+        assert_eq!(contents.matches("--fast").count(), 1);
+
+        // A partially written last frame is dropped
+        let mut truncated = contents.clone();
+        truncated.push_str("{\"ts_ms\":30");
+        let recording = Recording::from_reader(truncated.as_bytes()).unwrap();
+        assert_eq!(recording.header, header());
+        assert_eq!(recording.topology.all_cpus.len(), topo.all_cpus.len());
+        assert_eq!(recording.frames.len(), 3);
+        for frame in &recording.frames {
+            assert_eq!(frame.procs[&42], proc_sample);
+        }
+        assert_eq!(recording.frames[2].cpus[&0]["cpu_freq"], 2001);
+
+        let state = proc_sample.to_proc_data(42, 10);
+        assert_eq!(state.state, ProcState::Running);
+        assert_eq!(ProcSample::from_proc_data(&state).events, Samples::new());
+    }
+
+    #[test]
+    fn test_replay_advance() {
+        let mut replay = ReplayState::new(header(), frames(5)).unwrap();
+        assert!(replay.playing());
+        assert!(replay.take_changed());
+        replay.advance(500);
+        assert!(!replay.take_changed());
+        replay.advance(600);
+        assert!(replay.take_changed());
+        assert_eq!(replay.pos(), 1);
+        replay.advance(2000);
+        assert_eq!(replay.pos(), 3);
+        assert_eq!(replay.window(2).len(), 2);
+        assert_eq!(replay.window(10).len(), 4);
+
+        replay.advance(5000);
+        assert_eq!(replay.pos(), 4);
+        assert!(!replay.playing());
+
+        // Resuming at the end restarts playback
+        replay.toggle_play();
+        assert!(replay.playing());
+        assert_eq!(replay.pos(), 0);
+    }
+
+    #[test]
+    fn test_replay_seek() {
+        let mut replay = ReplayState::new(header(), frames(10)).unwrap();
+        replay.seek_by(3500);
+        assert_eq!(replay.pos(), 3);
+        replay.seek_by(-10_000);
+        assert_eq!(replay.pos(), 0);
+        replay.seek_end();
+        assert_eq!(replay.pos(), 9);
+        assert!(!replay.playing());
+        replay.seek_start();
+        assert_eq!(replay.pos(), 0);
+        assert_eq!(replay.index_at(4999), 4);
+        assert!(replay.title().contains("00:00:00/00:00:09"));
+    }
+
+    #[test]
+    fn test_replay_seek_sparse() {
+        let mut frames = frames(2);
+        frames[1].ts_ms = 60_000;
+        let mut replay = ReplayState::new(header(), frames).unwrap();
+        replay.seek_by(10_000);
+        assert_eq!(replay.pos(), 1);
+    }
+}
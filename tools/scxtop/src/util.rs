@@ -7,6 +7,7 @@ use crate::bpf_intf;
 use anyhow::Result;
 use nix::time::{clock_gettime, ClockId};
 use nix::unistd::{getuid, Uid};
+use serde_json::Value as JsonValue;
 use std::fs;
 use std::io::Read;
 use std::os::unix::fs::PermissionsExt;
@@ -224,6 +225,69 @@ pub fn default_scxtop_sched_ext_stats() -> bpf_intf::scxtop_sched_ext_stats {
     }
 }
 
+/// Returns the numeric value of a JSON leaf. Booleans are reported as 0 or 1.
+fn json_leaf_number(value: &JsonValue) -> Option<f64> {
+    match value {
+        JsonValue::Number(n) => n.as_f64(),
+        JsonValue::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+        _ => None,
+    }
+}
+
+/// Flattens a JSON value into its numeric leaves keyed by their dotted path, e.g.
+/// "layers.batch.util". Array elements are keyed by their index if `arrays` is set and
+/// skipped otherwise. A top-level scalar has no path and is skipped.
+pub fn flatten_json_numbers(value: &JsonValue, arrays: bool) -> Vec<(String, f64)> {
+    fn walk(value: &JsonValue, arrays: bool, path: &mut String, out: &mut Vec<(String, f64)>) {
+        let mut descend = |key: &str, child: &JsonValue, path: &mut String| {
+            let len = path.len();
+            if !path.is_empty() {
+                path.push('.');
+            }
+            path.push_str(key);
+            walk(child, arrays, path, out);
+            path.truncate(len);
+        };
+
+        match value {
+            JsonValue::Object(map) => {
+                for (key, child) in map {
+                    descend(key, child, path);
+                }
+            }
+            JsonValue::Array(arr) if arrays => {
+                for (idx, child) in arr.iter().enumerate() {
+                    descend(&idx.to_string(), child, path);
+                }
+            }
+            _ if !path.is_empty() => {
+                if let Some(v) = json_leaf_number(value) {
+                    out.push((path.clone(), v));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut out = vec![];
+    walk(value, arrays, &mut String::new(), &mut out);
+    out
+}
+
+/// Looks up the numeric leaf at a dotted path as produced by flatten_json_numbers(),
+/// returning None if it is missing or not a number.
+pub fn lookup_json_number(value: &JsonValue, path: &str) -> Option<f64> {
+    let mut cur = value;
+    for part in path.split('.') {
+        cur = match cur {
+            JsonValue::Object(map) => map.get(part)?,
+            JsonValue::Array(arr) => arr.get(part.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+    json_leaf_number(cur)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -347,4 +411,34 @@ mod tests {
     fn test_u32_to_i32_just_over_max() {
         u32_to_i32((i32::MAX as u32) + 1);
     }
+
+    #[test]
+    fn test_flatten_json_numbers() {
+        let stats: JsonValue = serde_json::from_str(
+            r#"{"busy":12.5,"layers":{"batch":{"util":90,"cpus":[1,2]}},"name":"x","on":true}"#,
+        )
+        .unwrap();
+
+        let mut fields = flatten_json_numbers(&stats, true);
+        fields.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            fields,
+            vec![
+                ("busy".to_string(), 12.5),
+                ("layers.batch.cpus.0".to_string(), 1.0),
+                ("layers.batch.cpus.1".to_string(), 2.0),
+                ("layers.batch.util".to_string(), 90.0),
+                ("on".to_string(), 1.0),
+            ]
+        );
+        assert_eq!(flatten_json_numbers(&stats, false).len(), 3);
+        assert!(flatten_json_numbers(&JsonValue::from(3), true).is_empty());
+
+        for (path, value) in fields {
+            assert_eq!(lookup_json_number(&stats, &path), Some(value));
+        }
+        assert_eq!(lookup_json_number(&stats, "layers.batch.cpus.2"), None);
+        assert_eq!(lookup_json_number(&stats, "layers.batch"), None);
+        assert_eq!(lookup_json_number(&stats, "name"), None);
+    }
 }